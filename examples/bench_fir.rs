@@ -0,0 +1,75 @@
+//! Time the dot product versions and the frame sync correlator, for checking the
+//! vectorized paths against the scalar one.
+//!
+//! Run with `cargo run --release --example bench_fir [iterations]`.
+
+extern crate p25;
+
+use std::env;
+use std::time::Instant;
+
+use p25::baseband::fir::Fir;
+use p25::baseband::simd;
+use p25::baseband::sync::{SyncCorrelator, FINGERPRINT_SAMPS, SYNC_FINGERPRINT};
+
+/// Iterations timed when none is given.
+const ITERATIONS: usize = 1_000_000;
+
+/// Time the given function over the given number of iterations, printing the time
+/// per iteration, and return the result of the last one so it isn't optimized away.
+fn time<F: FnMut(usize) -> f32>(name: &str, iterations: usize, mut f: F) -> f32 {
+    let start = Instant::now();
+    let mut out = 0.0;
+
+    for i in 0..iterations {
+        out = f(i);
+    }
+
+    let elapsed = start.elapsed();
+    let ns = elapsed.as_secs() as f64 * 1e9 + elapsed.subsec_nanos() as f64;
+
+    println!("{:<24} {:>10.2} ns/iter", name, ns / iterations as f64);
+
+    out
+}
+
+/// Pseudorandom samples in -0.5 to 0.5, the same on every run.
+fn samples(n: usize) -> Vec<f32> {
+    let mut x = 0x9E3779B9u32;
+
+    (0..n).map(|_| {
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        x as f32 / std::u32::MAX as f32 - 0.5
+    }).collect()
+}
+
+fn main() {
+    let iterations = env::args().nth(1)
+        .map(|s| s.parse().expect("iterations must be a number"))
+        .unwrap_or(ITERATIONS);
+
+    let hist = samples(FINGERPRINT_SAMPS);
+    let input = samples(4096);
+    let taps = &SYNC_FINGERPRINT[..];
+    let selected = simd::select();
+
+    let mut sink = 0.0;
+
+    sink += time("dot_scalar", iterations, |_| simd::dot_scalar(0.0, taps, &hist));
+    sink += time("dot (checked per call)", iterations, |_| simd::dot(0.0, taps, &hist));
+    sink += time("select (chosen once)", iterations, |_| selected(0.0, taps, &hist));
+
+    let mut fir = Fir::new(&SYNC_FINGERPRINT);
+    sink += time("Fir::feed", iterations, |i| fir.feed(input[i % input.len()]));
+
+    let mut corr = SyncCorrelator::new();
+    sink += time("SyncCorrelator::feed", iterations,
+                 |i| corr.feed(input[i % input.len()]).0);
+
+    // Keep the results live.
+    if sink.is_nan() {
+        println!("{}", sink);
+    }
+}
@@ -0,0 +1,6 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use p25::fuzz::{check_lc, LduDibits};
+
+fuzz_target!(|input: LduDibits| check_lc(&input));
@@ -0,0 +1,6 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use p25::fuzz::{check_nid, NidDibits};
+
+fuzz_target!(|input: NidDibits| check_nid(&input));
@@ -0,0 +1,6 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use p25::fuzz::{check_pdu, PduBlocks};
+
+fuzz_target!(|input: PduBlocks| check_pdu(&input));
@@ -0,0 +1,6 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use p25::fuzz::{check_tsbk, TsbkBytes};
+
+fuzz_target!(|input: TsbkBytes| check_tsbk(&input));
@@ -0,0 +1,6 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use p25::fuzz::{check_header, HeaderDibits};
+
+fuzz_target!(|input: HeaderDibits| check_header(&input));
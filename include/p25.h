@@ -0,0 +1,177 @@
+#ifndef P25_H
+#define P25_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Kind of event produced by a receiver.
+ */
+typedef enum {
+  /**
+   * Frame sync was acquired for the first time or after being lost.
+   */
+  P25_EVENT_SYNC_ACQUIRED,
+  /**
+   * Frame sync was lost.
+   */
+  P25_EVENT_SYNC_LOST,
+  /**
+   * A call started on the control channel, described by `call`.
+   */
+  P25_EVENT_CALL_START,
+  /**
+   * Grants for an active call, described by `call`, are still being received.
+   */
+  P25_EVENT_CALL_UPDATE,
+  /**
+   * A call on the control channel, described by `call`, ended.
+   */
+  P25_EVENT_CALL_END,
+  /**
+   * A voice frame was received, with its IMBE vectors in `chunks` and `errors`.
+   */
+  P25_EVENT_VOICE_FRAME,
+  /**
+   * A trunking signalling packet was received, with its bytes in `tsbk`.
+   */
+  P25_EVENT_TSBK,
+} p25_event_kind_t;
+
+/**
+ * Opaque receiver handle.
+ */
+typedef struct p25_receiver_t p25_receiver_t;
+
+/**
+ * Call tracked from the grants on a control channel.
+ */
+typedef struct {
+  /**
+   * Traffic channel carrying the call, with the channel ID in the upper 4 bits and
+   * the channel number in the lower 12.
+   */
+  uint16_t channel;
+  /**
+   * Whether the call is to a talkgroup rather than to a single unit.
+   */
+  bool group;
+  /**
+   * Talkgroup or unit address the call is to.
+   */
+  uint32_t target;
+  /**
+   * Whether `src_unit` is valid.
+   */
+  bool has_src_unit;
+  /**
+   * Unit that initiated the call.
+   */
+  uint32_t src_unit;
+  /**
+   * Whether any grant for the call was flagged as an emergency.
+   */
+  bool emergency;
+  /**
+   * Sample index of the first grant for the call.
+   */
+  uint64_t started;
+  /**
+   * Sample index of the most recent grant or update for the call.
+   */
+  uint64_t updated;
+} p25_call_t;
+
+/**
+ * Event produced by a receiver. Only the fields noted for the event's kind are valid,
+ * and the rest are zeroed.
+ */
+typedef struct {
+  /**
+   * What happened.
+   */
+  p25_event_kind_t kind;
+  /**
+   * Index of the sample the event occurred at.
+   */
+  uint64_t sample;
+  /**
+   * Whether the event belongs to a NAC, given in `nac`.
+   */
+  bool has_nac;
+  /**
+   * 12-bit NAC of the data unit or call the event belongs to.
+   */
+  uint16_t nac;
+  /**
+   * Call that changed state.
+   */
+  p25_call_t call;
+  /**
+   * Prioritized IMBE chunks `u_0`, ..., `u_7` of a voice frame.
+   */
+  uint32_t chunks[8];
+  /**
+   * Number of FEC errors corrected in each of the chunks `u_0`, ..., `u_6`.
+   */
+  uint32_t errors[7];
+  /**
+   * Bytes of a TSBK, including the CRC.
+   */
+  uint8_t tsbk[12];
+  /**
+   * Number of valid bytes in `tsbk`.
+   */
+  size_t tsbk_len;
+} p25_event_t;
+
+/**
+ * Function called with each event as it occurs, along with the context pointer given
+ * when the callback was set. The event is only valid until the function returns.
+ */
+typedef void (*p25_callback_t)(const p25_event_t *event, void *ctx);
+
+/**
+ * Create a new receiver with the default configuration, or return null if it couldn't
+ * be created. The returned receiver is owned by the caller and must be destroyed with
+ * `p25_receiver_free`.
+ */
+p25_receiver_t *p25_receiver_new(void);
+
+/**
+ * Destroy the given receiver, along with any events still waiting to be polled. Null
+ * is ignored.
+ */
+void p25_receiver_free(p25_receiver_t *recv);
+
+/**
+ * Pass each following event to the given callback, with the given context pointer,
+ * instead of queueing it. Events already queued can still be polled. A null callback
+ * switches back to queueing.
+ */
+void p25_receiver_set_callback(p25_receiver_t *recv, p25_callback_t callback, void *ctx);
+
+/**
+ * Feed in the given `len` baseband samples. The samples are only read during the call.
+ * Return `false` if the receiver panicked, now or in an earlier call.
+ */
+bool p25_receiver_feed(p25_receiver_t *recv, const float *samples, size_t len);
+
+/**
+ * Signal the end of the input, producing the loss of sync and the end of every active
+ * call. Return `false` if the receiver panicked, now or in an earlier call.
+ */
+bool p25_receiver_finish(p25_receiver_t *recv);
+
+/**
+ * Copy the oldest queued event into the given event and remove it from the queue,
+ * returning whether there was one.
+ */
+bool p25_receiver_next_event(p25_receiver_t *recv, p25_event_t *event);
+
+#endif /* P25_H */
@@ -0,0 +1,197 @@
+//! Automatic frequency control, which tracks the carrier frequency offset measured at
+//! each frame sync and recommends a tuner correction.
+//!
+//! A frequency offset shows up as a DC offset in the FM discriminator output, so the
+//! midpoint between the outer symbol levels of each sync measures it directly.
+
+/// Number of consecutive syncs within tolerance before the loop is considered locked.
+const LOCK_SYNCS: usize = 3;
+
+/// Control loop that integrates the frequency offset measured at each frame sync.
+///
+/// Each measurement moves the estimate by a fraction of the remaining error, limited to
+/// a maximum step, so the estimate converges on a constant offset and follows a slow
+/// drift while riding out the odd bad measurement.
+#[derive(Copy, Clone, Debug)]
+pub struct Afc {
+    /// Hz of frequency offset per unit of baseband DC offset.
+    scale: f32,
+    /// Fraction of each measured error applied to the estimate.
+    gain: f32,
+    /// Maximum change of the estimate per sync, in Hz.
+    slew: f32,
+    /// Maximum error, in Hz, considered in lock.
+    tolerance: f32,
+    /// Whether the receiver subtracts the estimate from its input samples.
+    correct: bool,
+    /// Estimated frequency offset, in Hz.
+    offset: f32,
+    /// Number of consecutive syncs within tolerance, up to `LOCK_SYNCS`.
+    good: usize,
+}
+
+impl Afc {
+    /// Create a new `Afc` for a discriminator that outputs the given baseband level per
+    /// Hz of deviation, the inverse of which scales offsets into Hz.
+    ///
+    /// For example, a discriminator that puts the outer symbols (±1800 Hz) at ±0.18
+    /// has a scale of `0.18 / 1800.0`.
+    pub fn new(level_per_hz: f32) -> Afc {
+        assert!(level_per_hz > 0.0);
+
+        Afc {
+            scale: 1.0 / level_per_hz,
+            gain: 0.25,
+            slew: 200.0,
+            tolerance: 50.0,
+            correct: false,
+            offset: 0.0,
+            good: 0,
+        }
+    }
+
+    /// Apply the given fraction, in (0, 1], of each measured error, 0.25 by default.
+    pub fn with_gain(mut self, gain: f32) -> Self {
+        assert!(gain > 0.0 && gain <= 1.0);
+        self.gain = gain;
+        self
+    }
+
+    /// Change the estimate by at most the given number of Hz per sync, 200 by default.
+    pub fn with_slew(mut self, hz: f32) -> Self {
+        assert!(hz > 0.0);
+        self.slew = hz;
+        self
+    }
+
+    /// Consider the loop locked once the error stays within the given number of Hz,
+    /// 50 by default. Lock is lost when the error exceeds twice that.
+    pub fn with_tolerance(mut self, hz: f32) -> Self {
+        assert!(hz > 0.0);
+        self.tolerance = hz;
+        self
+    }
+
+    /// Have the receiver subtract the estimated DC offset from its input samples, if
+    /// `on` is true, so symbols are decided as if the tuner were already corrected.
+    pub fn with_correction(mut self, on: bool) -> Self {
+        self.correct = on;
+        self
+    }
+
+    /// Whether the receiver subtracts the estimate from its input samples.
+    pub fn correcting(&self) -> bool { self.correct }
+
+    /// Update the loop with the DC offset measured at a frame sync, after subtracting
+    /// the current estimate.
+    pub fn feed(&mut self, error: f32) {
+        let err = error * self.scale;
+        let step = err * self.gain;
+
+        self.offset += if step > self.slew {
+            self.slew
+        } else if step < -self.slew {
+            -self.slew
+        } else {
+            step
+        };
+
+        let mag = if err < 0.0 { -err } else { err };
+
+        if mag <= self.tolerance {
+            self.good = (self.good + 1).min(LOCK_SYNCS);
+        } else if mag > self.tolerance * 2.0 || !self.locked() {
+            self.good = 0;
+        }
+    }
+
+    /// Estimated carrier frequency offset, in Hz, which should be added to the tuner
+    /// frequency to center the signal.
+    pub fn correction_hz(&self) -> f32 { self.offset }
+
+    /// Estimated DC offset of the baseband signal.
+    pub fn dc_offset(&self) -> f32 { self.offset / self.scale }
+
+    /// Whether the error has stayed within tolerance over the last few syncs.
+    pub fn locked(&self) -> bool { self.good == LOCK_SYNCS }
+
+    /// Forget the estimate, as after retuning to another channel.
+    pub fn reset(&mut self) {
+        self.offset = 0.0;
+        self.good = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Scale of the test discriminator, with the outer symbols at ±0.18.
+    const SCALE: f32 = 0.18 / 1800.0;
+
+    /// Feed one sync measurement of a signal at the given offset in Hz.
+    fn measure(afc: &mut Afc, hz: f32) {
+        let error = hz * SCALE - afc.dc_offset();
+        afc.feed(error);
+    }
+
+    #[test]
+    fn test_constant() {
+        let mut afc = Afc::new(SCALE);
+        let mut syncs = 0;
+
+        while !afc.locked() {
+            measure(&mut afc, 300.0);
+            syncs += 1;
+            assert!(syncs < 20);
+        }
+
+        for _ in 0..20 {
+            measure(&mut afc, 300.0);
+        }
+
+        assert!(afc.locked());
+        assert!((afc.correction_hz() - 300.0).abs() < 1.0);
+        assert!((afc.dc_offset() - 0.03).abs() < 0.0001);
+
+        afc.reset();
+        assert!(!afc.locked());
+        assert_eq!(afc.correction_hz(), 0.0);
+    }
+
+    #[test]
+    fn test_drift() {
+        let mut afc = Afc::new(SCALE).with_gain(0.5);
+
+        // Drift 2 Hz per sync, which the estimate follows a few Hz behind.
+        for n in 0..200 {
+            measure(&mut afc, 100.0 + n as f32 * 2.0);
+
+            if n > 20 {
+                let err = 100.0 + n as f32 * 2.0 - afc.correction_hz();
+                assert!(err > 0.0 && err < 5.0);
+                assert!(afc.locked());
+            }
+        }
+    }
+
+    #[test]
+    fn test_slew() {
+        let mut afc = Afc::new(SCALE).with_gain(1.0).with_slew(100.0);
+
+        measure(&mut afc, 1000.0);
+        assert!((afc.correction_hz() - 100.0).abs() < 0.01);
+        assert!(!afc.locked());
+
+        // A single bad measurement doesn't break lock.
+        for _ in 0..20 {
+            measure(&mut afc, 1000.0);
+        }
+
+        assert!(afc.locked());
+        measure(&mut afc, 1080.0);
+        assert!(afc.locked());
+        measure(&mut afc, 1300.0);
+        assert!(!afc.locked());
+    }
+}
@@ -0,0 +1,234 @@
+//! Frame sync search over large buffers of recorded samples, with the fingerprint
+//! cross-correlation done in the frequency domain by overlap-save.
+//!
+//! Each FFT block holds the last `N - 1` samples of the previous block followed by as
+//! many new samples as fit, so every new sample has a full correlation history and the
+//! circular wraparound only spoils outputs that are thrown away.
+
+use baseband::fft::{Complex, Fft};
+use baseband::sync::{FINGERPRINT_SAMPS, SYNC_FINGERPRINT, SyncPosition, SyncSearch};
+
+/// Default FFT size.
+const FFT_SIZE: usize = 4096;
+
+/// Correlation, relative to the largest sample in the block, below which it's taken as
+/// rounding error spread through the block by the transform.
+const ROUNDING_FLOOR: f64 = 1.0e-12;
+
+/// Searches buffers of samples for frame syncs like `SyncScanner`, finding the same
+/// syncs, but computing the correlation a block at a time by FFT.
+///
+/// This trades latency for throughput, so it suits decoding recorded files, while the
+/// streaming `SyncScanner` remains the way to follow a live signal.
+#[derive(Clone)]
+pub struct BlockSyncCorrelator<const N: usize = FINGERPRINT_SAMPS> {
+    /// Transform of the FFT size.
+    fft: Fft,
+    /// Fingerprint taps.
+    taps: Vec<f32>,
+    /// Conjugated transform of the fingerprint, for correlating rather than convolving.
+    spectrum: Vec<Complex>,
+    /// Last `N` samples fed in, oldest first, followed by the block being processed.
+    hist: Vec<f32>,
+    /// Running sum of squares of the last `N` samples.
+    energy: f32,
+    /// Samples until the running sum is recomputed from the history.
+    resum: usize,
+    /// Peak search over the correlation output.
+    search: SyncSearch,
+    /// FFT working buffer.
+    buf: Vec<Complex>,
+}
+
+impl BlockSyncCorrelator {
+    /// Create a new `BlockSyncCorrelator` for the C4FM frame sync.
+    pub fn new() -> BlockSyncCorrelator {
+        BlockSyncCorrelator::with_fingerprint(&SYNC_FINGERPRINT)
+    }
+}
+
+impl<const N: usize> BlockSyncCorrelator<N> {
+    /// Create a new `BlockSyncCorrelator` for the given fingerprint, with the same layout
+    /// as `SYNC_FINGERPRINT`.
+    pub fn with_fingerprint(taps: &[f32; N]) -> Self {
+        let mut c = BlockSyncCorrelator {
+            fft: Fft::new(2),
+            taps: taps.to_vec(),
+            spectrum: vec![],
+            hist: vec![0.0; N],
+            energy: 0.0,
+            resum: N,
+            search: SyncSearch::new(N),
+            buf: vec![],
+        };
+
+        c.set_fft(FFT_SIZE);
+        c
+    }
+
+    /// Correlate with FFTs of the given size, a power of two more than twice the
+    /// fingerprint length, 4096 by default.
+    ///
+    /// Each block takes `size - N + 1` new samples, so larger sizes do less work per
+    /// sample, up to the point the buffers stop fitting in cache.
+    pub fn with_fft_size(mut self, size: usize) -> Self {
+        assert!(size > 2 * N);
+        self.set_fft(size);
+        self
+    }
+
+    /// Detect syncs above the given factor of the RMS signal power, `SYNC_THRESHOLD` by
+    /// default.
+    pub fn with_threshold(mut self, factor: f32) -> Self {
+        self.set_threshold(factor);
+        self
+    }
+
+    /// Change the detection threshold factor, keeping the correlation history.
+    pub fn set_threshold(&mut self, factor: f32) { self.search.set_threshold(factor); }
+
+    /// Detection threshold factor.
+    pub fn threshold(&self) -> f32 { self.search.threshold() }
+
+    /// Search the given buffer, returning the syncs found in it, with indexes into it
+    /// as given by `SyncScanner`.
+    pub fn feed_slice(&mut self, samples: &[f32]) -> Vec<SyncPosition> {
+        let mut found = vec![];
+        self.feed_slice_with(samples, |pos| found.push(pos));
+        found
+    }
+
+    /// Search the given buffer, passing each sync found in it to the given callback.
+    pub fn feed_slice_with<F: FnMut(SyncPosition)>(&mut self, samples: &[f32], mut cb: F) {
+        let block = self.fft.size() - (N - 1);
+
+        for (n, chunk) in samples.chunks(block).enumerate() {
+            self.feed_block(chunk, n * block, &mut cb);
+        }
+    }
+
+    /// Correlate the given samples, which fit in one block, and search the output,
+    /// numbering the samples from the given index.
+    fn feed_block<F: FnMut(SyncPosition)>(&mut self, chunk: &[f32], base: usize, cb: &mut F) {
+        self.hist.extend_from_slice(chunk);
+
+        // Lay out the last N - 1 samples followed by the new ones, and zero the rest.
+        for (i, x) in self.buf.iter_mut().enumerate() {
+            let s = self.hist.get(i + 1).cloned().unwrap_or(0.0);
+            *x = Complex::new(s as f64, 0.0);
+        }
+
+        let peak = self.hist.iter().fold(0.0f32, |m, &x| m.max(x.abs()));
+        let floor = peak as f64 * ROUNDING_FLOOR;
+
+        self.fft.forward(&mut self.buf);
+
+        for (x, &t) in self.buf.iter_mut().zip(self.spectrum.iter()) {
+            *x = *x * t;
+        }
+
+        self.fft.inverse(&mut self.buf);
+
+        for j in 0..chunk.len() {
+            // Output j correlates the window ending at the new sample j, which is
+            // exactly zero in silence, as from `SyncCorrelator`.
+            let corr = self.buf[j].re / N as f64;
+            let corrpow = if corr.abs() < floor { 0.0 } else { corr as f32 };
+            let sigpower = self.sig_power(j);
+            let power = self.search.polarity().correct(corrpow);
+
+            if let Some(pos) = self.search.feed(power, sigpower, base + j) {
+                cb(pos);
+            }
+        }
+
+        let keep = self.hist.len() - N;
+        self.hist.drain(..keep);
+    }
+
+    /// Update the running sum of squares in the same way as `SyncCorrelator` for the
+    /// window ending at the given new sample, and return the signal power.
+    fn sig_power(&mut self, j: usize) -> f32 {
+        let window = &self.hist[j + 1..j + 1 + N];
+
+        self.resum -= 1;
+
+        if self.resum == 0 {
+            self.resum = N;
+            self.energy = window.iter().fold(0.0, |sum, &x| sum + x * x);
+        } else {
+            let (old, new) = (self.hist[j], window[N - 1]);
+            self.energy += new * new - old * old;
+
+            if self.energy < 0.0 {
+                self.energy = 0.0;
+            }
+        }
+
+        self.energy / N as f32
+    }
+
+    /// Set up transforms of the given size for the fingerprint.
+    fn set_fft(&mut self, size: usize) {
+        self.fft = Fft::new(size);
+        self.buf = vec![Complex::default(); size];
+
+        let mut spectrum: Vec<Complex> = (0..size).map(|i| {
+            Complex::new(self.taps.get(i).cloned().unwrap_or(0.0) as f64, 0.0)
+        }).collect();
+
+        self.fft.forward(&mut spectrum);
+        self.spectrum = spectrum.into_iter().map(Complex::conj).collect();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use baseband::sync::SyncScanner;
+    use message::nid::{DataUnit, NetworkAccessCode};
+    use testutil;
+
+    #[test]
+    fn test_block() {
+        let mut dibits = testutil::frame(NetworkAccessCode::Default,
+                                         DataUnit::VoiceSimpleTerminator, vec![]);
+        dibits.extend(dibits.clone());
+
+        let mut samples = testutil::silence(0.01);
+        samples.extend(testutil::modulate(&dibits));
+        samples.extend(testutil::silence(0.01));
+        samples.extend(testutil::modulate(&testutil::voice_call(NetworkAccessCode::Default,
+                                                                1, 2, 4)));
+
+        let expected: Vec<SyncPosition> = SyncScanner::new().feed_slice(&samples).collect();
+        assert!(expected.len() > 4);
+
+        // Whole, in pieces that straddle blocks, and with small blocks.
+        let whole = BlockSyncCorrelator::new().feed_slice(&samples);
+        let small = BlockSyncCorrelator::new().with_fft_size(512).feed_slice(&samples);
+
+        let mut pieces = vec![];
+        let mut c = BlockSyncCorrelator::new();
+
+        for (n, chunk) in samples.chunks(3000).enumerate() {
+            c.feed_slice_with(chunk, |mut pos| {
+                pos.index += n * 3000;
+                pieces.push(pos);
+            });
+        }
+
+        for found in [whole, small, pieces].iter() {
+            assert_eq!(found.len(), expected.len());
+
+            for (f, e) in found.iter().zip(expected.iter()) {
+                let diff = f.index as isize - e.index as isize;
+                assert!(diff.abs() <= 1, "{:?} {:?}", f, e);
+                assert_eq!(f.polarity, e.polarity);
+                assert!((f.power - e.power).abs() <= e.power * 1.0e-4);
+            }
+        }
+
+        assert!(BlockSyncCorrelator::new().with_threshold(2.0).feed_slice(&samples).is_empty());
+    }
+}
@@ -0,0 +1,167 @@
+//! Symbol clock tracking, which measures the sample clock error from the spacing of
+//! consecutive frame syncs and corrects the symbol instants between them.
+//!
+//! Data units follow each other back to back, so consecutive frame syncs are a whole
+//! number of symbols apart, and any fraction of a symbol left over comes from the sample
+//! clock running at a different rate than the transmitter's symbol clock.
+
+use moving_avg::MovingAverage;
+
+use consts::SYMBOL_PERIOD;
+
+/// Number of sync spacings averaged by default.
+const SMOOTH_SPACINGS: usize = 4;
+
+/// Most symbols between syncs that are measured, the length of two LDUs, past which the
+/// drift at the maximum error could be mistaken for a whole symbol.
+const MAX_SPACING: usize = 2 * 1728;
+
+/// Tracks the error of the sample clock against the symbol clock, updated at each frame
+/// sync.
+///
+/// Each spacing between consecutive syncs gives a measurement, which is rejected if
+/// beyond the maximum error, and the accepted ones are averaged over the last few.
+pub struct ClockTracker {
+    /// Smooths the measured error, in ppm.
+    smooth: MovingAverage<f32>,
+    /// Number of spacings averaged.
+    spacings: usize,
+    /// Maximum error, in ppm, a measurement can show.
+    max_ppm: f32,
+    /// Sample position of the last sync, if any.
+    last: Option<f64>,
+    /// Smoothed error, in ppm.
+    ppm: f32,
+}
+
+impl ClockTracker {
+    /// Create a new `ClockTracker` with no estimate.
+    pub fn new() -> ClockTracker {
+        ClockTracker {
+            smooth: MovingAverage::new(SMOOTH_SPACINGS),
+            spacings: SMOOTH_SPACINGS,
+            max_ppm: 100.0,
+            last: None,
+            ppm: 0.0,
+        }
+    }
+
+    /// Average the error over the given number of sync spacings, 4 by default.
+    pub fn with_smoothing(mut self, spacings: usize) -> Self {
+        assert!(spacings > 0);
+        self.spacings = spacings;
+        self.smooth = MovingAverage::new(spacings);
+        self
+    }
+
+    /// Reject measurements beyond the given error, in ppm, 100 by default, such as from
+    /// a sync detected a sample off.
+    pub fn with_max_ppm(mut self, ppm: f32) -> Self {
+        assert!(ppm > 0.0);
+        self.max_ppm = ppm;
+        self
+    }
+
+    /// Record a frame sync at the given sample position, including any fraction of a
+    /// sample, returning the error measured from the spacing to the previous sync, in
+    /// ppm, if one was taken.
+    pub fn feed(&mut self, pos: f64) -> Option<f32> {
+        let last = match self.last.replace(pos) {
+            Some(last) => last,
+            None => return None,
+        };
+
+        let spacing = pos - last;
+
+        // Nearest whole number of symbols at the current estimate.
+        let symbols = (spacing / self.period() as f64 + 0.5) as usize;
+
+        if symbols == 0 || symbols > MAX_SPACING {
+            return None;
+        }
+
+        let nominal = (symbols * SYMBOL_PERIOD) as f64;
+        let ppm = ((spacing - nominal) / nominal * 1.0e6) as f32;
+
+        if ppm > self.max_ppm || ppm < -self.max_ppm {
+            return None;
+        }
+
+        self.ppm = self.smooth.feed(ppm);
+
+        Some(ppm)
+    }
+
+    /// Estimated error, in ppm, of the sample clock, positive if it runs fast and puts
+    /// more samples in each symbol.
+    pub fn ppm(&self) -> f32 { self.ppm }
+
+    /// Estimated number of samples per symbol.
+    pub fn period(&self) -> f32 { SYMBOL_PERIOD as f32 * (1.0 + self.ppm * 1.0e-6) }
+
+    /// Forget the last sync, so the next one starts a new spacing, as after losing the
+    /// signal for a while.
+    pub fn lose(&mut self) { self.last = None; }
+
+    /// Forget the estimate, as after retuning to another channel.
+    pub fn reset(&mut self) {
+        self.smooth = MovingAverage::new(self.spacings);
+        self.last = None;
+        self.ppm = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_drift() {
+        let mut clock = ClockTracker::new();
+        assert_eq!(clock.period(), 10.0);
+
+        // Alternating LDUs and other data units 50 ppm long.
+        let mut pos = 1234.5;
+        assert_eq!(clock.feed(pos), None);
+
+        for (n, &symbols) in [1728, 792, 1728, 1728, 144, 1728].iter().enumerate() {
+            pos += symbols as f64 * 10.0 * (1.0 + 50.0e-6);
+            let ppm = clock.feed(pos).unwrap();
+            assert!((ppm - 50.0).abs() < 0.1, "{} {}", n, ppm);
+        }
+
+        assert!((clock.ppm() - 50.0).abs() < 0.1);
+        assert!((clock.period() - 10.0005).abs() < 1.0e-5);
+
+        // A sync detected a few samples off is rejected.
+        pos += 1728.0 * 10.0 + 4.0;
+        assert_eq!(clock.feed(pos), None);
+        assert!((clock.ppm() - 50.0).abs() < 0.1);
+
+        // As is one too long after the last.
+        pos += 10_000.0 * 10.0;
+        assert_eq!(clock.feed(pos), None);
+
+        clock.lose();
+        assert_eq!(clock.feed(pos + 17280.0), None);
+        assert_eq!(clock.feed(pos + 2.0 * 17280.0), Some(0.0));
+
+        clock.reset();
+        assert_eq!(clock.ppm(), 0.0);
+        assert_eq!(clock.feed(pos), None);
+    }
+
+    #[test]
+    fn test_smoothing() {
+        let mut clock = ClockTracker::new().with_smoothing(2).with_max_ppm(200.0);
+        let mut pos = 0.0;
+        clock.feed(pos);
+
+        for &ppm in [150.0, -50.0, 30.0].iter() {
+            pos += 17280.0 * (1.0 + ppm * 1.0e-6);
+            clock.feed(pos);
+        }
+
+        assert!((clock.ppm() - -10.0).abs() < 0.1);
+    }
+}
@@ -0,0 +1,212 @@
+//! Demodulation of CQPSK, the linear simulcast modulation (LSM) used by many simulcast
+//! systems, from complex IQ samples into the same baseband levels as C4FM.
+//!
+//! CQPSK carries each dibit as a phase change of ±π/4 or ±3π/4 from one symbol to the
+//! next, the same phase a C4FM symbol accumulates at ±600 or ±1800 Hz of deviation.
+//! Differential detection of the phase change between symbol instants, found by a
+//! Gardner timing loop, so recovers the C4FM symbol levels, which are held between
+//! instants to form a baseband stream that the sync correlator and symbol decisions
+//! handle unchanged.
+//!
+//! An FM discriminator also recovers the phase changes of a CQPSK signal, but only at
+//! the symbol instants, with the amplitude dips between them turning into large
+//! frequency spikes, which makes for high error rates.
+
+use std;
+
+use consts::SYMBOL_PERIOD;
+use util;
+
+/// Number of IQ samples kept for interpolating the symbol instants.
+const HIST: usize = 16;
+
+/// Baseband level per radian of phase change, which puts the outer symbols at ±0.18.
+const LEVEL_PER_RADIAN: f32 = 0.18 / (3.0 * std::f32::consts::PI / 4.0);
+
+/// Most the symbol period can stray from nominal, as a fraction.
+const MAX_PERIOD_ERROR: f32 = 0.005;
+
+/// Recovers baseband symbol levels from CQPSK IQ samples at the 48 kHz baseband rate.
+#[derive(Copy, Clone, Debug)]
+pub struct CqpskDemodulator {
+    /// Last IQ samples, indexed by sample count modulo `HIST`.
+    hist: [(f32, f32); HIST],
+    /// Number of samples fed in.
+    count: usize,
+    /// Samples, after the newest, until the next symbol instant.
+    next: f32,
+    /// Estimated samples per symbol.
+    period: f32,
+    /// Fraction of each timing error applied to the next symbol instant.
+    gain: f32,
+    /// IQ sample at the last symbol instant.
+    last: (f32, f32),
+    /// Level of the last symbol, which is output until the next instant.
+    level: f32,
+}
+
+impl CqpskDemodulator {
+    /// Create a new `CqpskDemodulator` with no timing or symbol history.
+    pub fn new() -> CqpskDemodulator {
+        CqpskDemodulator {
+            hist: [(0.0, 0.0); HIST],
+            count: 0,
+            next: SYMBOL_PERIOD as f32,
+            period: SYMBOL_PERIOD as f32,
+            gain: 0.1,
+            last: (0.0, 0.0),
+            level: 0.0,
+        }
+    }
+
+    /// Move each symbol instant by the given fraction of the measured timing error, in
+    /// (0, 1), 0.1 by default. Higher gains acquire timing faster but jitter more.
+    pub fn with_loop_gain(mut self, gain: f32) -> Self {
+        assert!(gain > 0.0 && gain < 1.0);
+        self.gain = gain;
+        self
+    }
+
+    /// Estimated error of the sample clock against the symbol clock, in ppm.
+    pub fn ppm(&self) -> f32 {
+        (self.period / SYMBOL_PERIOD as f32 - 1.0) * 1.0e6
+    }
+
+    /// Feed in the given IQ sample, returning the baseband level of the last symbol
+    /// detected.
+    ///
+    /// Samples of zero amplitude give no phase, so they demodulate as no deviation.
+    pub fn feed(&mut self, i: f32, q: f32) -> f32 {
+        self.hist[self.count % HIST] = (i, q);
+        self.count += 1;
+        self.next -= 1.0;
+
+        while self.next <= 0.0 {
+            self.strobe();
+        }
+
+        self.level
+    }
+
+    /// Demodulate the given buffer of interleaved I and Q components, appending the
+    /// baseband samples to the given buffer.
+    ///
+    /// The buffer must hold whole IQ samples.
+    pub fn feed_interleaved(&mut self, iq: &[f32], out: &mut std::vec::Vec<f32>) {
+        assert!(iq.len() % 2 == 0);
+        out.extend(iq.chunks(2).map(|s| self.feed(s[0], s[1])));
+    }
+
+    /// Forget the timing and symbol history, as after retuning.
+    pub fn reset(&mut self) {
+        *self = CqpskDemodulator::new().with_loop_gain(self.gain);
+    }
+
+    /// Detect the symbol at the current instant, which is between the newest two
+    /// samples, and update the timing from the Gardner error.
+    fn strobe(&mut self) {
+        let delay = -self.next;
+        let cur = self.interpolate(delay);
+        let mid = self.interpolate(delay + self.period / 2.0);
+        let prev = self.last;
+
+        // Phase of the product with the conjugate of the previous symbol.
+        let re = cur.0 * prev.0 + cur.1 * prev.1;
+        let im = cur.1 * prev.0 - cur.0 * prev.1;
+        self.level = util::atan2(im, re) * LEVEL_PER_RADIAN;
+
+        // Gardner error, normalized by the symbol power so the loop gain doesn't
+        // depend on the signal level. A positive error means the instant is late.
+        let power = cur.0 * cur.0 + cur.1 * cur.1 + prev.0 * prev.0 + prev.1 * prev.1;
+        let err = if power > 0.0 {
+            ((cur.0 - prev.0) * mid.0 + (cur.1 - prev.1) * mid.1) / power
+        } else {
+            0.0
+        };
+
+        let nominal = SYMBOL_PERIOD as f32;
+        let max = nominal * MAX_PERIOD_ERROR;
+        let period = self.period - self.gain * self.gain / 4.0 * err;
+
+        self.period = if period > nominal + max {
+            nominal + max
+        } else if period < nominal - max {
+            nominal - max
+        } else {
+            period
+        };
+
+        self.next += self.period - self.gain * err * nominal;
+        self.last = cur;
+    }
+
+    /// Interpolate the IQ sample the given number of samples before the newest.
+    fn interpolate(&self, delay: f32) -> (f32, f32) {
+        let whole = delay as usize;
+        let frac = delay - whole as f32;
+
+        if whole + 2 > HIST || whole + 1 >= self.count {
+            return (0.0, 0.0);
+        }
+
+        let a = self.hist[(self.count - 1 - whole) % HIST];
+        let b = self.hist[(self.count - 2 - whole) % HIST];
+
+        (a.0 + (b.0 - a.0) * frac, a.1 + (b.1 - a.1) * frac)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use baseband::sync::SyncScanner;
+    use message::nid::{DataUnit, NetworkAccessCode};
+    use testutil;
+
+    /// Demodulate the given interleaved IQ.
+    fn demod(iq: &[f32]) -> Vec<f32> {
+        let mut out = vec![];
+        CqpskDemodulator::new().feed_interleaved(iq, &mut out);
+        out
+    }
+
+    #[test]
+    fn test_levels() {
+        // Steps of each size, sampled on time.
+        let dibits: Vec<_> = [0b01, 0b00, 0b10, 0b11].iter().cycle().take(400)
+            .map(|&b| ::bits::Dibit::new(b)).collect();
+
+        let out = demod(&testutil::cqpsk(&dibits, 0.0, 0.0));
+
+        for (n, &expect) in [0.18, 0.06, -0.06, -0.18].iter().enumerate() {
+            let hits = out[2000..5000].iter().filter(|&&s| (s - expect).abs() < 0.01).count();
+            assert!(hits > 600, "{} {}", n, hits);
+        }
+    }
+
+    #[test]
+    fn test_burst() {
+        let dibits = testutil::voice_call(NetworkAccessCode::Default, 0x0100, 0x123456, 9);
+        let lsm = testutil::cqpsk(&dibits, 4.6, 150.0);
+
+        let out = demod(&lsm);
+        assert_eq!(out.len(), lsm.len() / 2);
+
+        // Every sync and data unit found in the same call sent as C4FM, with the syncs
+        // delayed by under a symbol.
+        let c4fm = testutil::modulate(&dibits);
+        let found: Vec<_> = SyncScanner::new().feed_slice(&out).collect();
+
+        for expect in SyncScanner::new().feed_slice(&c4fm) {
+            assert!(found.iter().any(|pos| {
+                pos.index >= expect.index && pos.index < expect.index + SYMBOL_PERIOD
+            }));
+        }
+
+        let dus = testutil::data_units(&testutil::decode(&out));
+        assert_eq!(dus, testutil::data_units(&testutil::decode(&c4fm)));
+        assert_eq!(dus[0], DataUnit::VoiceHeader);
+
+        assert!(out.iter().all(|s| !s.is_nan()));
+    }
+}
@@ -4,10 +4,17 @@ use bits;
 use consts;
 
 /// Decodes symbol from sample at each symbol instant.
+///
+/// Symbol instants that fall between samples, when the symbol clock has been corrected,
+/// are linearly interpolated from the samples on either side.
 #[derive(Copy, Clone)]
 pub struct Decoder {
-    /// Sample index into current symbol period.
-    pos: usize,
+    /// Samples since the last symbol instant.
+    pos: f32,
+    /// Samples per symbol.
+    period: f32,
+    /// Previous sample.
+    prev: f32,
     /// Decider used for decoding symbol at each symbol instant.
     decider: Decider,
 }
@@ -16,26 +23,57 @@ impl Decoder {
     /// Create a new `Decoder` with the given symbol decider, initialized to decode the
     /// first symbol after the frame sync has been detected.
     pub fn new(decider: Decider) -> Decoder {
+        Decoder::with_timing(decider, 0.0, consts::SYMBOL_PERIOD as f32)
+    }
+
+    /// Create a new `Decoder` with the given symbol decider, for a frame sync whose
+    /// symbol instants fall the given fraction of a sample off the sampled ones, as
+    /// given by `SyncPeak::offset`, and the given number of samples per symbol.
+    pub fn with_timing(decider: Decider, offset: f32, period: f32) -> Decoder {
+        assert!(period > 1.0);
+
         Decoder {
             // The frame sync sequence is detected one sample after its last symbol
             // instant (i.e., the first sample in the next symbol period after the
             // sequence), so take that sample into account.
-            pos: 1,
+            pos: 1.0 - offset,
+            period: period,
+            prev: 0.0,
             decider: decider,
         }
     }
 
+    /// Decide symbols with the given decider from now on, keeping the symbol clock.
+    pub fn set_decider(&mut self, decider: Decider) { self.decider = decider; }
+
     /// Examine the given sample and, based on the symbol clock, decode it into a symbol
     /// or do nothing.
     pub fn feed(&mut self, s: f32) -> Option<bits::Dibit> {
-        self.pos += 1;
-        self.pos %= consts::SYMBOL_PERIOD;
+        self.instant(s).map(|s| self.decider.decide(s))
+    }
 
-        if self.pos == 0 {
-            Some(self.decider.decide(s))
-        } else {
-            None
+    /// Examine the given sample and, based on the symbol clock, decode it into a symbol
+    /// along with the confidence in it, or do nothing.
+    pub fn feed_soft(&mut self, s: f32) -> Option<SoftDecision> {
+        self.instant(s).map(|s| self.decider.decide_soft(s))
+    }
+
+    /// Advance the symbol clock by the given sample, returning the sample at the symbol
+    /// instant if one was passed.
+    fn instant(&mut self, s: f32) -> Option<f32> {
+        let prev = self.prev;
+        self.prev = s;
+        self.pos += 1.0;
+
+        if self.pos < self.period {
+            return None;
         }
+
+        // Fraction of the way from the previous sample to this one of the instant.
+        let frac = self.period - (self.pos - 1.0);
+        self.pos -= self.period;
+
+        Some(if frac >= 1.0 { s } else { prev + (s - prev) * frac })
     }
 }
 
@@ -73,6 +111,78 @@ impl Decider {
             bits::Dibit::new(0b11)
         }
     }
+
+    /// Scale the given sample so the thresholds fall at the midpoints between the ideal
+    /// symbol levels of a `SoftDibit`.
+    pub fn soft(&self, sample: f32) -> bits::SoftDibit {
+        let diff = sample - self.mthresh;
+
+        bits::SoftDibit::new(if diff > 0.0 {
+            2.0 * diff / (self.pthresh - self.mthresh)
+        } else {
+            2.0 * diff / (self.mthresh - self.nthresh)
+        })
+    }
+
+    /// Decide which symbol the given sample looks closest to, along with how far it was
+    /// from the nearest threshold.
+    pub fn decide_soft(&self, sample: f32) -> SoftDecision {
+        SoftDecision::from_level(self.soft(sample))
+    }
+}
+
+/// Hard symbol decision along with the confidence in it, for soft-decision decoding.
+///
+/// The confidence is the distance of the sample from the nearest decision threshold,
+/// from 0 right on a threshold to 255 at or past the ideal level of the symbol.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SoftDecision {
+    /// Decided symbol.
+    dibit: bits::Dibit,
+    /// Confidence in the decision.
+    confidence: u8,
+}
+
+impl SoftDecision {
+    /// Create a new `SoftDecision` with the given symbol and confidence.
+    pub fn new(dibit: bits::Dibit, confidence: u8) -> SoftDecision {
+        SoftDecision {
+            dibit: dibit,
+            confidence: confidence,
+        }
+    }
+
+    /// Decide the given scaled symbol level, with thresholds at 2, 0, and -2.
+    pub fn from_level(soft: bits::SoftDibit) -> SoftDecision {
+        let level = soft.value();
+
+        // Distance to the nearest threshold, which is at most 1 between the inner ones.
+        let dist = if level > 1.0 {
+            level - 2.0
+        } else if level < -1.0 {
+            level + 2.0
+        } else {
+            level
+        };
+
+        let dist = if dist < 0.0 { -dist } else { dist };
+        let dist = if dist > 1.0 { 1.0 } else { dist };
+
+        SoftDecision::new(soft.hard(), (dist * 255.0 + 0.5) as u8)
+    }
+
+    /// Decided symbol.
+    pub fn dibit(&self) -> bits::Dibit { self.dibit }
+
+    /// Confidence in the decision, from 0 to 255.
+    pub fn confidence(&self) -> u8 { self.confidence }
+
+    /// Confidence in the decision, from 0 to 1.
+    pub fn certainty(&self) -> f32 { self.confidence as f32 / 255.0 }
+}
+
+impl From<SoftDecision> for bits::Dibit {
+    fn from(soft: SoftDecision) -> bits::Dibit { soft.dibit }
 }
 
 #[cfg(test)]
@@ -87,6 +197,43 @@ mod test {
         assert_eq!(d.decide(-0.052).bits(), 0b00);
         assert_eq!(d.decide(-0.148).bits(), 0b10);
         assert_eq!(d.decide(-0.244).bits(), 0b11);
+
+        for &s in [0.044, -0.052, -0.148, -0.244, 0.5, -0.5].iter() {
+            assert_eq!(d.soft(s).hard(), d.decide(s));
+        }
+
+        assert!((d.soft(0.044).value() - 3.0).abs() < 1e-4);
+        assert!((d.soft(-0.148).value() + 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_soft_decision() {
+        let d = Decider::new(0.12, 0.0, -0.12);
+
+        // Ideal levels.
+        for &(s, bits) in [(0.18, 0b01), (0.06, 0b00), (-0.06, 0b10), (-0.18, 0b11)].iter() {
+            let soft = d.decide_soft(s);
+            assert_eq!(soft.dibit(), d.decide(s));
+            assert_eq!(soft.dibit().bits(), bits);
+            assert_eq!(soft.confidence(), 255);
+            assert_eq!(soft.certainty(), 1.0);
+        }
+
+        assert_eq!(d.decide_soft(0.5).confidence(), 255);
+        assert_eq!(d.decide_soft(0.0).confidence(), 0);
+        assert_eq!(d.decide_soft(0.12).confidence(), 0);
+        assert_eq!(d.decide_soft(-0.015).confidence(), 64);
+        assert_eq!(d.decide_soft(-0.165).confidence(), 191);
+        assert_eq!(d.decide_soft(0.075).confidence(), 191);
+
+        let hard: bits::Dibit = d.decide_soft(-0.165).into();
+        assert_eq!(hard.bits(), 0b11);
+
+        let mut dec = Decoder::new(d);
+        let soft: Vec<SoftDecision> = [0.0; 8].iter().chain([0.075, 0.0].iter())
+            .filter_map(|&s| dec.feed_soft(s))
+            .collect();
+        assert_eq!(soft, vec![SoftDecision::new(bits::Dibit::new(0b00), 191)]);
     }
 
     #[test]
@@ -114,4 +261,28 @@ mod test {
         assert!(d.feed(0.1816711425781250).is_none());
         assert!(d.feed(0.1799926757812500).is_some());
     }
+
+    #[test]
+    fn test_timing() {
+        let d = Decider::new(0.5, 0.0, -0.5);
+
+        // Instants half a sample early land between the ramp samples.
+        let mut dec = Decoder::with_timing(d, -0.5, 10.0);
+        let decided: Vec<bits::Dibit> = (0..30)
+            .filter_map(|n| dec.feed(if n < 8 { 0.0 } else { 1.0 }))
+            .collect();
+        assert_eq!(decided.iter().map(|d| d.bits()).collect::<Vec<_>>(),
+                   vec![0b00, 0b01, 0b01]);
+
+        // A longer period slips the instants a quarter of a sample each symbol, with
+        // each one decided at the first sample at or after it.
+        let mut dec = Decoder::with_timing(d, 0.0, 10.25);
+        let instants: Vec<usize> = (0..200)
+            .filter(|_| dec.feed(0.0).is_some())
+            .collect();
+        assert_eq!(instants.len(), 19);
+        assert_eq!(instants[0], 9);
+        assert_eq!(instants[3], 39);
+        assert_eq!(instants[7], 80);
+    }
 }
@@ -0,0 +1,241 @@
+//! Demodulation of complex IQ samples into baseband samples by either of the P25 Phase 1
+//! modulations, or by whichever of them finds a frame sync first.
+
+use std;
+use std::collections::VecDeque;
+
+use baseband::cqpsk::CqpskDemodulator;
+use baseband::fm::FmDiscriminator;
+use baseband::sync::{FINGERPRINT_SAMPS, SyncPosition, SyncScanner};
+use consts::{SAMPLE_RATE, SYMBOL_PERIOD};
+use util;
+
+/// Samples after the first sync detection in automatic mode during which a sync found
+/// by the other demodulator is still considered.
+const AUTO_WINDOW: usize = 2 * SYMBOL_PERIOD;
+
+/// Delay of the output in automatic mode, long enough that the whole of the deciding
+/// sync comes out of the chosen demodulator.
+pub const AUTO_DELAY: usize = FINGERPRINT_SAMPS + 2 * AUTO_WINDOW;
+
+/// Modulation of a P25 Phase 1 signal.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Modulation {
+    /// C4FM, demodulated by FM discrimination.
+    C4fm,
+    /// CQPSK, also called LSM, demodulated by differential phase detection.
+    Cqpsk,
+    /// Whichever demodulator finds a clearer frame sync first.
+    Auto,
+}
+
+/// Demodulates IQ samples at the 48 kHz baseband rate into baseband samples for a
+/// receiver or sync correlator.
+///
+/// In automatic mode both demodulators run until one finds a frame sync. If the other
+/// also finds it within a couple of symbols, the one with the stronger correlation for
+/// its signal power is chosen, and the other is then stopped until `reset`. Output is
+/// delayed by `AUTO_DELAY` samples, which come out as silence at the start, so the
+/// deciding sync still reaches the receiver.
+#[derive(Clone)]
+pub struct Demodulator {
+    /// Requested modulation.
+    mode: Modulation,
+    /// Modulation chosen, if any.
+    selected: Option<Modulation>,
+    /// FM discriminator for C4FM.
+    fm: FmDiscriminator,
+    /// Differential detector for CQPSK.
+    cqpsk: CqpskDemodulator,
+    /// Sync scanners over the C4FM and CQPSK outputs in automatic mode.
+    scanners: Option<(SyncScanner, SyncScanner)>,
+    /// Delayed C4FM and CQPSK outputs in automatic mode.
+    delay: VecDeque<(f32, f32)>,
+    /// Best sync quality from the C4FM and CQPSK outputs in the current window.
+    quality: (f32, f32),
+    /// Samples remaining in the decision window, if one is open.
+    window: Option<usize>,
+}
+
+impl Demodulator {
+    /// Create a new `Demodulator` for the given modulation.
+    pub fn new(mode: Modulation) -> Demodulator {
+        Demodulator {
+            mode: mode,
+            selected: match mode {
+                Modulation::Auto => None,
+                m => Some(m),
+            },
+            fm: FmDiscriminator::new(SAMPLE_RATE),
+            cqpsk: CqpskDemodulator::new(),
+            scanners: match mode {
+                Modulation::Auto => Some((SyncScanner::new(), SyncScanner::new())),
+                _ => None,
+            },
+            delay: VecDeque::new(),
+            quality: (0.0, 0.0),
+            window: None,
+        }
+    }
+
+    /// Modulation requested at creation.
+    pub fn mode(&self) -> Modulation { self.mode }
+
+    /// Modulation being demodulated, which is `None` in automatic mode until a sync
+    /// decides it.
+    pub fn selected(&self) -> Option<Modulation> { self.selected }
+
+    /// Feed in the given IQ sample, returning the next baseband sample.
+    pub fn feed(&mut self, i: f32, q: f32) -> f32 {
+        if self.mode != Modulation::Auto {
+            return self.demodulate(self.mode, i, q);
+        }
+
+        let out = match self.selected {
+            Some(m) => {
+                let s = self.demodulate(m, i, q);
+                (s, s)
+            },
+            None => self.search(i, q),
+        };
+
+        self.delay.push_back(out);
+
+        if self.delay.len() <= AUTO_DELAY {
+            return 0.0;
+        }
+
+        let (fm, cqpsk) = self.delay.pop_front().unwrap();
+
+        match self.selected {
+            Some(Modulation::Cqpsk) => cqpsk,
+            _ => fm,
+        }
+    }
+
+    /// Demodulate the given buffer of interleaved I and Q components, appending the
+    /// baseband samples to the given buffer.
+    ///
+    /// The buffer must hold whole IQ samples.
+    pub fn feed_interleaved(&mut self, iq: &[f32], out: &mut std::vec::Vec<f32>) {
+        assert!(iq.len() % 2 == 0);
+        out.extend(iq.chunks(2).map(|s| self.feed(s[0], s[1])));
+    }
+
+    /// Forget the sample history, and in automatic mode the chosen modulation, as after
+    /// retuning.
+    pub fn reset(&mut self) {
+        *self = Demodulator::new(self.mode);
+    }
+
+    /// Demodulate the given IQ sample by the given modulation.
+    fn demodulate(&mut self, m: Modulation, i: f32, q: f32) -> f32 {
+        match m {
+            Modulation::Cqpsk => self.cqpsk.feed(i, q),
+            _ => self.fm.feed(i, q).unwrap_or(0.0),
+        }
+    }
+
+    /// Demodulate the given IQ sample both ways and look for a sync in each output,
+    /// choosing a modulation at the end of the window after the first.
+    fn search(&mut self, i: f32, q: f32) -> (f32, f32) {
+        let fm = self.demodulate(Modulation::C4fm, i, q);
+        let cqpsk = self.demodulate(Modulation::Cqpsk, i, q);
+
+        let (found_fm, found_cqpsk) = match self.scanners {
+            Some((ref mut a, ref mut b)) => {
+                (a.feed_slice(&[fm]).next(), b.feed_slice(&[cqpsk]).next())
+            },
+            None => (None, None),
+        };
+
+        if let Some(pos) = found_fm {
+            self.quality.0 = max(self.quality.0, quality(&pos));
+        }
+
+        if let Some(pos) = found_cqpsk {
+            self.quality.1 = max(self.quality.1, quality(&pos));
+        }
+
+        self.window = match self.window {
+            Some(0) => {
+                self.selected = Some(if self.quality.1 > self.quality.0 {
+                    Modulation::Cqpsk
+                } else {
+                    Modulation::C4fm
+                });
+
+                self.scanners = None;
+                None
+            },
+            Some(n) => Some(n - 1),
+            None if found_fm.is_some() || found_cqpsk.is_some() => Some(AUTO_WINDOW),
+            None => None,
+        };
+
+        (fm, cqpsk)
+    }
+}
+
+/// Correlation of the given sync relative to the RMS signal power, which is highest for
+/// an undistorted sync.
+fn quality(pos: &SyncPosition) -> f32 {
+    if pos.sigpower > 0.0 { pos.power / util::sqrt(pos.sigpower) } else { 0.0 }
+}
+
+/// Larger of the given values.
+fn max(a: f32, b: f32) -> f32 { if a > b { a } else { b } }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use message::nid::{DataUnit, NetworkAccessCode};
+    use testutil;
+
+    /// Demodulate the given interleaved IQ, returning the samples and the modulation
+    /// chosen.
+    fn demod(mode: Modulation, iq: &[f32]) -> (Vec<f32>, Option<Modulation>) {
+        let mut d = Demodulator::new(mode);
+        let mut out = vec![];
+        d.feed_interleaved(iq, &mut out);
+        (out, d.selected())
+    }
+
+    #[test]
+    fn test_auto() {
+        let dibits = testutil::voice_call(NetworkAccessCode::Default, 0x0100, 0x123456, 9);
+        let expect = testutil::data_units(&testutil::decode(&testutil::modulate(&dibits)));
+        assert_eq!(expect[0], DataUnit::VoiceHeader);
+
+        let lsm = testutil::cqpsk(&dibits, 2.3, -200.0);
+        let c4fm = testutil::fm(&testutil::modulate(&dibits), 1, 0.5);
+
+        // Each fixed mode decodes its own modulation, with no delay.
+        let (out, selected) = demod(Modulation::Cqpsk, &lsm);
+        assert_eq!(selected, Some(Modulation::Cqpsk));
+        assert_eq!(out.len(), lsm.len() / 2);
+        assert_eq!(testutil::data_units(&testutil::decode(&out)), expect);
+
+        let (out, _) = demod(Modulation::C4fm, &c4fm);
+        assert_eq!(testutil::data_units(&testutil::decode(&out)), expect);
+
+        // Automatic mode picks out CQPSK from the first sync, and decodes either.
+        let (out, selected) = demod(Modulation::Auto, &lsm);
+        assert_eq!(selected, Some(Modulation::Cqpsk));
+        assert!(out[..AUTO_DELAY].iter().all(|&s| s == 0.0));
+        assert_eq!(testutil::data_units(&testutil::decode(&out)), expect);
+
+        let (out, selected) = demod(Modulation::Auto, &c4fm);
+        assert!(selected.is_some());
+        assert_eq!(testutil::data_units(&testutil::decode(&out)), expect);
+
+        // Nothing is chosen without a sync.
+        let mut d = Demodulator::new(Modulation::Auto);
+        d.feed_interleaved(&c4fm[..2000], &mut vec![]);
+        assert_eq!(d.selected(), None);
+        d.feed_interleaved(&c4fm[2000..], &mut vec![]);
+        assert!(d.selected().is_some());
+        d.reset();
+        assert_eq!(d.selected(), None);
+    }
+}
@@ -0,0 +1,159 @@
+//! Fast Fourier transform of complex sequences with a power of two length, for block
+//! processing of recorded signals.
+
+use std::f64::consts::PI;
+use std::ops::{Add, Mul, Sub};
+
+/// Complex number in double precision.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Complex {
+    /// Real part.
+    pub re: f64,
+    /// Imaginary part.
+    pub im: f64,
+}
+
+impl Complex {
+    /// Create a new `Complex` with the given real and imaginary parts.
+    pub fn new(re: f64, im: f64) -> Complex {
+        Complex { re: re, im: im }
+    }
+
+    /// Complex conjugate.
+    pub fn conj(self) -> Complex { Complex::new(self.re, -self.im) }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, o: Complex) -> Complex { Complex::new(self.re + o.re, self.im + o.im) }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, o: Complex) -> Complex { Complex::new(self.re - o.re, self.im - o.im) }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, o: Complex) -> Complex {
+        Complex::new(self.re * o.re - self.im * o.im, self.re * o.im + self.im * o.re)
+    }
+}
+
+/// Radix-2 FFT of a fixed size, with the twiddle factors and bit reversal computed up
+/// front.
+#[derive(Clone)]
+pub struct Fft {
+    /// Twiddle factors `exp(-2πik/size)` for `k` in `0..size/2`.
+    twiddles: Vec<Complex>,
+    /// Index each element is swapped with in the bit reversal.
+    rev: Vec<usize>,
+}
+
+impl Fft {
+    /// Create a new `Fft` of the given size, which must be a power of two.
+    pub fn new(size: usize) -> Fft {
+        assert!(size >= 2 && size.is_power_of_two());
+
+        let bits = size.trailing_zeros();
+
+        Fft {
+            twiddles: (0..size / 2).map(|k| {
+                let (sin, cos) = (-2.0 * PI * k as f64 / size as f64).sin_cos();
+                Complex::new(cos, sin)
+            }).collect(),
+            rev: (0..size).map(|i| i.reverse_bits() >> (usize::BITS - bits)).collect(),
+        }
+    }
+
+    /// Number of elements transformed.
+    pub fn size(&self) -> usize { self.rev.len() }
+
+    /// Transform the given elements in place to the frequency domain.
+    pub fn forward(&self, buf: &mut [Complex]) { self.transform(buf, false); }
+
+    /// Transform the given elements in place back to the time domain, scaled so it
+    /// undoes `forward`.
+    pub fn inverse(&self, buf: &mut [Complex]) {
+        self.transform(buf, true);
+
+        let scale = 1.0 / self.size() as f64;
+
+        for x in buf.iter_mut() {
+            *x = Complex::new(x.re * scale, x.im * scale);
+        }
+    }
+
+    /// Transform the given elements in place, with conjugated twiddles if `inverse`.
+    fn transform(&self, buf: &mut [Complex], inverse: bool) {
+        let size = self.size();
+        assert_eq!(buf.len(), size);
+
+        for (i, &j) in self.rev.iter().enumerate() {
+            if i < j {
+                buf.swap(i, j);
+            }
+        }
+
+        let mut len = 2;
+
+        while len <= size {
+            let half = len / 2;
+            let step = size / len;
+
+            for start in (0..size).step_by(len) {
+                for k in 0..half {
+                    let w = self.twiddles[k * step];
+                    let w = if inverse { w.conj() } else { w };
+
+                    let u = buf[start + k];
+                    let v = buf[start + k + half] * w;
+
+                    buf[start + k] = u + v;
+                    buf[start + k + half] = u - v;
+                }
+            }
+
+            len *= 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use testutil;
+
+    #[test]
+    fn test_fft() {
+        let mut rng = testutil::Rng::new(515);
+        let input: Vec<Complex> = (0..64)
+            .map(|_| Complex::new(rng.next() as f64 / 1e9, rng.next() as f64 / 1e9))
+            .collect();
+
+        let fft = Fft::new(64);
+        assert_eq!(fft.size(), 64);
+
+        let mut buf = input.clone();
+        fft.forward(&mut buf);
+
+        // Against the definition.
+        for (k, &x) in buf.iter().enumerate() {
+            let dft = input.iter().enumerate().fold(Complex::default(), |sum, (n, &s)| {
+                let (sin, cos) = (-2.0 * PI * (k * n) as f64 / 64.0).sin_cos();
+                sum + s * Complex::new(cos, sin)
+            });
+
+            assert!((x.re - dft.re).abs() < 1e-9 && (x.im - dft.im).abs() < 1e-9);
+        }
+
+        fft.inverse(&mut buf);
+
+        for (x, s) in buf.iter().zip(input.iter()) {
+            assert!((x.re - s.re).abs() < 1e-12 && (x.im - s.im).abs() < 1e-12);
+        }
+    }
+}
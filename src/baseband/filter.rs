@@ -0,0 +1,238 @@
+//! Receive filtering of FM discriminator output before sync correlation and symbol
+//! decisions.
+//!
+//! The standard C4FM receiver follows the discriminator with an integrate and dump
+//! filter, averaging over one symbol period, whose sinc response undoes the shaping
+//! filter of the transmitter and leaves the Nyquist raised cosine response overall. It
+//! also cuts the noise above the symbol rate that a wideband discriminator passes. A
+//! root raised cosine response, the receive half of the CQPSK pulse, can be used
+//! instead, with taps from `root_raised_cosine_taps`.
+//!
+//! Either filter is symmetric, so it delays the signal by half its length, and syncs
+//! found after it are reported that much later than in the discriminator output.
+
+use baseband::fir::Fir;
+use consts::SYMBOL_PERIOD;
+
+/// Number of integrate and dump taps at the 48kHz baseband rate.
+pub const RECEIVE_TAPS: usize = SYMBOL_PERIOD + 1;
+
+/// Group delay, in samples, of the integrate and dump filter at the 48kHz baseband rate.
+pub const RECEIVE_DELAY: usize = RECEIVE_TAPS / 2;
+
+/// Generate integrate and dump taps for `N - 1` samples per symbol, which integrate the
+/// signal over exactly one symbol period centered on the current sample, with the ends
+/// of the period weighted by half, and have unity gain at DC.
+pub fn integrate_dump_taps<const N: usize>() -> [f32; N] {
+    assert!(N > 2);
+
+    let period = (N - 1) as f32;
+    let mut taps = [1.0 / period; N];
+
+    taps[0] /= 2.0;
+    taps[N - 1] /= 2.0;
+
+    taps
+}
+
+/// Generate root raised cosine taps, with the P25 rolloff, for the given number of
+/// samples per symbol, covering `N` samples, an odd number, and with unity gain at DC.
+#[cfg(any(feature = "std", test))]
+pub fn root_raised_cosine_taps<const N: usize>(period: usize) -> [f32; N] {
+    use baseband::shaping::{root_raised_cosine, ROLLOFF};
+
+    assert!(N % 2 == 1);
+    assert!(period > 1);
+
+    let mid = (N / 2) as f64;
+    let mut taps = [0.0f64; N];
+
+    for (n, t) in taps.iter_mut().enumerate() {
+        *t = root_raised_cosine((n as f64 - mid) / period as f64, ROLLOFF);
+    }
+
+    let sum: f64 = taps.iter().sum();
+    let mut out = [0.0; N];
+
+    for (o, &t) in out.iter_mut().zip(taps.iter()) {
+        *o = (t / sum) as f32;
+    }
+
+    out
+}
+
+/// Filters baseband samples one at a time, ahead of a `SyncCorrelator` or receiver,
+/// with `N` symmetric taps.
+#[derive(Clone)]
+pub struct ReceiveFilter<const N: usize = RECEIVE_TAPS> {
+    /// Filter taps and history.
+    fir: Fir<N>,
+}
+
+impl ReceiveFilter {
+    /// Create a new `ReceiveFilter` with the integrate and dump response at 48kHz.
+    pub fn new() -> ReceiveFilter { ReceiveFilter::integrate_dump() }
+}
+
+impl<const N: usize> ReceiveFilter<N> {
+    /// Create a new `ReceiveFilter` with the integrate and dump response for `N - 1`
+    /// samples per symbol, such as 21 taps for 96kHz.
+    pub fn integrate_dump() -> Self {
+        ReceiveFilter::with_taps(&integrate_dump_taps())
+    }
+
+    /// Create a new `ReceiveFilter` with the given taps, which must be symmetric for the
+    /// group delay to hold.
+    pub fn with_taps(taps: &[f32; N]) -> Self {
+        ReceiveFilter {
+            fir: Fir::new(taps),
+        }
+    }
+
+    /// Group delay in samples, by which syncs found in the filtered signal are later
+    /// than in the original. This is half a sample off whole for an even number of taps.
+    pub fn delay(&self) -> f32 { (N - 1) as f32 / 2.0 }
+
+    /// Filter the given sample, returning the next output sample.
+    pub fn feed(&mut self, sample: f32) -> f32 { self.fir.feed(sample) }
+
+    /// Filter the given buffer of samples in place.
+    pub fn filter_slice(&mut self, samples: &mut [f32]) {
+        for s in samples.iter_mut() {
+            *s = self.fir.feed(*s);
+        }
+    }
+
+    /// Clear the sample history, as after retuning.
+    pub fn reset(&mut self) {
+        self.fir = Fir::new(self.fir.taps());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use baseband::modulate::C4fmModulator;
+    use baseband::sync::{FINGERPRINT_SAMPS, SyncCorrelator, SyncScanner, instant_thresholds};
+    use message::nid::{DataUnit, NetworkAccessCode};
+    use testutil::{self, Rng};
+
+    #[test]
+    fn test_taps() {
+        let taps = integrate_dump_taps::<RECEIVE_TAPS>();
+        assert_eq!(taps[0], 0.05);
+        assert_eq!(taps[5], 0.1);
+        assert_eq!(taps[10], 0.05);
+        assert!((taps.iter().sum::<f32>() - 1.0).abs() < 1.0e-6);
+
+        let taps = integrate_dump_taps::<21>();
+        assert!((taps.iter().sum::<f32>() - 1.0).abs() < 1.0e-6);
+        assert_eq!(ReceiveFilter::<21>::integrate_dump().delay(), 10.0);
+
+        let taps = root_raised_cosine_taps::<81>(SYMBOL_PERIOD);
+        assert!((taps.iter().sum::<f32>() - 1.0).abs() < 1.0e-6);
+
+        for n in 0..40 {
+            assert_eq!(taps[n], taps[80 - n]);
+            assert!(taps[n] < taps[40]);
+        }
+    }
+
+    #[test]
+    fn test_delay() {
+        assert_eq!(ReceiveFilter::new().delay(), RECEIVE_DELAY as f32);
+
+        // An impulse comes out centered on the group delay.
+        let mut filt = ReceiveFilter::<81>::with_taps(&root_raised_cosine_taps(SYMBOL_PERIOD));
+        let mut out = vec![0.0; 100];
+        out[0] = 1.0;
+        filt.filter_slice(&mut out);
+
+        let peak = (0..out.len()).max_by(|&a, &b| out[a].partial_cmp(&out[b]).unwrap())
+            .unwrap();
+
+        assert_eq!(peak as f32, filt.delay());
+        assert_eq!(out[peak - 7], out[peak + 7]);
+
+        // Syncs are found the group delay later, with the symbol levels kept.
+        let dibits = testutil::frame(NetworkAccessCode::Default,
+                                     DataUnit::VoiceSimpleTerminator, vec![]);
+
+        let mut samples = testutil::silence(0.01);
+        let mut m = C4fmModulator::new();
+        m.modulate(dibits.iter().cloned(), &mut samples);
+        m.flush(&mut samples);
+        samples.extend(testutil::silence(0.01));
+
+        let raw: Vec<_> = SyncScanner::new().feed_slice(&samples).collect();
+
+        let mut filtered = samples.clone();
+        ReceiveFilter::new().filter_slice(&mut filtered);
+        let found: Vec<_> = SyncScanner::new().feed_slice(&filtered).collect();
+
+        assert_eq!(found[0].index, raw[0].index + RECEIVE_DELAY);
+
+        let dus = testutil::data_units(&testutil::decode(&filtered));
+        assert_eq!(dus, vec![DataUnit::VoiceSimpleTerminator]);
+    }
+
+    /// Upper symbol threshold from the sync that ends at the given sample index, as
+    /// reported by `SyncScanner`.
+    fn upper(samples: &[f32], end: usize) -> f32 {
+        let mut corr = SyncCorrelator::new();
+
+        for &s in samples[end - FINGERPRINT_SAMPS..end + 1].iter() {
+            corr.feed(s);
+        }
+
+        instant_thresholds(&corr.history()).0
+    }
+
+    /// Standard deviation of the upper symbol threshold from the syncs that end at the
+    /// given sample indexes.
+    fn threshold_spread(samples: &[f32], syncs: &[usize]) -> f32 {
+        let uppers: Vec<f32> = syncs.iter().map(|&end| upper(samples, end)).collect();
+        let mean = uppers.iter().sum::<f32>() / uppers.len() as f32;
+        let var = uppers.iter().map(|&u| (u - mean) * (u - mean)).sum::<f32>() /
+            uppers.len() as f32;
+
+        var.sqrt()
+    }
+
+    #[test]
+    fn test_stability() {
+        let dibits = testutil::voice_call(NetworkAccessCode::Default, 0x0100, 0x123456, 54);
+
+        let mut samples = testutil::silence(0.01);
+        let mut m = C4fmModulator::new();
+        m.modulate(dibits.iter().cloned(), &mut samples);
+        m.flush(&mut samples);
+        samples.extend(testutil::silence(0.01));
+
+        // Frame syncs, leaving out chance matches in the voice frames, whose symbols
+        // don't sit at the sync levels.
+        let syncs: Vec<usize> = SyncScanner::new().feed_slice(&samples)
+            .map(|pos| pos.index)
+            .filter(|&idx| (upper(&samples, idx) - 0.12).abs() < 1.0e-3)
+            .collect();
+
+        assert!(syncs.len() > 5);
+
+        // Wideband noise as passed by a discriminator with no receive filter.
+        let mut rng = Rng::new(523);
+
+        for s in samples.iter_mut() {
+            *s += rng.gaussian(0.08);
+        }
+
+        let raw = threshold_spread(&samples, &syncs);
+
+        let mut filtered = samples.clone();
+        ReceiveFilter::new().filter_slice(&mut filtered);
+
+        let delayed: Vec<usize> = syncs.iter().map(|&idx| idx + RECEIVE_DELAY).collect();
+        let spread = threshold_spread(&filtered, &delayed);
+
+        assert!(spread < raw * 0.6, "{} {}", spread, raw);
+    }
+}
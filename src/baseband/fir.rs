@@ -0,0 +1,85 @@
+//! Finite impulse response filtering with the taps and sample history held inline.
+
+use std;
+
+use baseband::sample::Sample;
+
+/// FIR filter with `N` taps over a ring buffer of the last `N` samples of type `T`.
+///
+/// The first tap is applied to the oldest sample in the history and the last tap to the
+/// newest, so taps given in time order cross-correlate the input with them.
+#[derive(Clone)]
+pub struct Fir<const N: usize, T = f32> {
+    /// Filter coefficients.
+    taps: [T; N],
+    /// Ring buffer of previous samples.
+    hist: [T; N],
+    /// Index of the oldest sample in the history.
+    idx: usize,
+    /// Dot product chosen for the processor when created.
+    dot: fn(T, &[T], &[T]) -> T,
+}
+
+impl<const N: usize, T: Sample> Fir<N, T> {
+    /// Create a new `Fir` with the given taps and a zeroed history.
+    pub fn new(taps: &[T; N]) -> Self {
+        Fir {
+            taps: *taps,
+            hist: [T::zero(); N],
+            idx: 0,
+            dot: T::dot_fn(),
+        }
+    }
+
+    /// Filter coefficients.
+    pub fn taps(&self) -> &[T; N] { &self.taps }
+
+    /// Oldest sample in the history, which the next sample fed in replaces.
+    pub fn oldest(&self) -> T { self.hist[self.idx] }
+
+    /// Add the given sample to the history and return the filter output.
+    pub fn feed(&mut self, sample: T) -> T {
+        self.hist[self.idx] = sample;
+        self.idx = (self.idx + 1) % N;
+
+        // Apply the taps to each contiguous part of the history, oldest first, so the
+        // dot products can be vectorized.
+        let (newer, older) = self.hist.split_at(self.idx);
+        let (first, last) = self.taps.split_at(older.len());
+
+        (self.dot)((self.dot)(T::zero(), first, older), last, newer)
+    }
+
+    /// Iterate over the sample history from oldest to newest.
+    pub fn history<'a>(&'a self) -> impl Iterator<Item = &'a T> + 'a where T: 'a {
+        let (newer, older) = self.hist.split_at(self.idx);
+        older.iter().chain(newer.iter())
+    }
+
+    /// Iterate over the sample history in storage order, for calculations that don't
+    /// depend on sample order.
+    pub fn history_unordered(&self) -> std::slice::Iter<'_, T> { self.hist.iter() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fir() {
+        let mut fir = Fir::new(&[1.0, 2.0, 3.0]);
+
+        assert_eq!(fir.feed(1.0), 3.0);
+        assert_eq!(fir.feed(0.0), 2.0);
+        assert_eq!(fir.feed(0.0), 1.0);
+        assert_eq!(fir.feed(0.0), 0.0);
+
+        fir.feed(4.0);
+        fir.feed(5.0);
+        assert_eq!(fir.feed(6.0), 4.0 + 10.0 + 18.0);
+
+        assert_eq!(fir.history().cloned().collect::<Vec<f32>>(), vec![4.0, 5.0, 6.0]);
+        assert_eq!(fir.history_unordered().fold(0.0, |s, &x| s + x), 15.0);
+        assert_eq!(fir.taps(), &[1.0, 2.0, 3.0]);
+    }
+}
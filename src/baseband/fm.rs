@@ -0,0 +1,254 @@
+//! FM discrimination of complex IQ samples, such as from an SDR, into the real baseband
+//! samples the rest of the baseband stage expects.
+//!
+//! The phase step between consecutive IQ samples is the instantaneous frequency, which
+//! is scaled so the outer C4FM symbols, at ±1800 Hz of deviation, come out at ±0.18.
+//! IQ sampled at a multiple of the baseband rate is low-pass filtered, if a filter is
+//! given, and decimated before discrimination.
+
+use std;
+
+use baseband::fir::Fir;
+use baseband::sync::SYNC_DEVIATION;
+use consts::SAMPLE_RATE;
+use util;
+
+/// Baseband level per Hz of deviation output by `FmDiscriminator`, which can be passed
+/// to `Afc::new`.
+pub const LEVEL_PER_HZ: f32 = 0.18 / SYNC_DEVIATION;
+
+/// Default number of channel filter taps.
+pub const CHANNEL_TAPS: usize = 31;
+
+/// Quadrature FM discriminator with an optional `N` tap channel filter and decimation
+/// down to the baseband sample rate.
+#[derive(Clone)]
+pub struct FmDiscriminator<const N: usize = CHANNEL_TAPS> {
+    /// Number of IQ samples per baseband sample.
+    decim: usize,
+    /// IQ samples until the next baseband sample.
+    skip: usize,
+    /// Channel filters of the I and Q components, if any.
+    filter: Option<(Fir<N>, Fir<N>)>,
+    /// Previous IQ sample kept after decimation.
+    prev: (f32, f32),
+    /// Baseband level per radian of phase step.
+    gain: f32,
+    /// I component waiting for its Q component in an interleaved stream.
+    half: Option<f32>,
+}
+
+impl<const N: usize> FmDiscriminator<N> {
+    /// Create a new `FmDiscriminator` for IQ samples at the given rate, a multiple of
+    /// the 48 kHz baseband rate, with no channel filter.
+    ///
+    /// Without a filter, decimation keeps every nth sample, so the source must already
+    /// be band limited to the baseband rate.
+    pub fn new(rate: usize) -> Self {
+        assert!(rate >= SAMPLE_RATE && rate % SAMPLE_RATE == 0);
+
+        FmDiscriminator {
+            decim: rate / SAMPLE_RATE,
+            skip: 0,
+            filter: None,
+            prev: (0.0, 0.0),
+            gain: SAMPLE_RATE as f32 / (2.0 * std::f32::consts::PI) * LEVEL_PER_HZ,
+            half: None,
+        }
+    }
+
+    /// Low-pass filter the IQ samples with the given taps, at the IQ rate, before
+    /// decimation, such as from `lowpass_taps`.
+    pub fn with_filter(mut self, taps: &[f32; N]) -> Self {
+        self.filter = Some((Fir::new(taps), Fir::new(taps)));
+        self
+    }
+
+    /// Number of IQ samples per baseband sample.
+    pub fn decimation(&self) -> usize { self.decim }
+
+    /// Feed in the given IQ sample, returning the next baseband sample, if one is due
+    /// after decimation.
+    ///
+    /// Samples of zero amplitude give no phase, so they demodulate as no deviation.
+    pub fn feed(&mut self, i: f32, q: f32) -> Option<f32> {
+        let (i, q) = match self.filter {
+            Some((ref mut fi, ref mut fq)) => (fi.feed(i), fq.feed(q)),
+            None => (i, q),
+        };
+
+        if self.skip > 0 {
+            self.skip -= 1;
+            return None;
+        }
+
+        self.skip = self.decim - 1;
+
+        // Phase of the product with the conjugate of the previous sample.
+        let (pi, pq) = self.prev;
+        let re = i * pi + q * pq;
+        let im = q * pi - i * pq;
+
+        self.prev = (i, q);
+
+        Some(util::atan2(im, re) * self.gain)
+    }
+
+    /// Demodulate the given buffer of interleaved I and Q components, returning an
+    /// iterator over the baseband samples.
+    ///
+    /// An I component left over at the end of the buffer is paired with the first Q
+    /// component of the next.
+    pub fn feed_interleaved<'a>(&'a mut self, iq: &'a [f32]) -> Demodulated<'a, N> {
+        Demodulated {
+            disc: self,
+            iq: iq.iter(),
+        }
+    }
+
+    /// Forget the previous samples, as after retuning.
+    pub fn reset(&mut self) {
+        self.skip = 0;
+        self.prev = (0.0, 0.0);
+        self.half = None;
+
+        if let Some((ref mut fi, ref mut fq)) = self.filter {
+            *fi = Fir::new(fi.taps());
+            *fq = Fir::new(fq.taps());
+        }
+    }
+}
+
+/// Iterator over the baseband samples demodulated from a buffer of IQ, created by
+/// `FmDiscriminator::feed_interleaved`.
+pub struct Demodulated<'a, const N: usize> {
+    /// Discriminator doing the demodulation.
+    disc: &'a mut FmDiscriminator<N>,
+    /// Remaining IQ components.
+    iq: std::slice::Iter<'a, f32>,
+}
+
+impl<'a, const N: usize> Iterator for Demodulated<'a, N> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        for &x in self.iq.by_ref() {
+            let i = match self.disc.half.take() {
+                Some(i) => i,
+                None => {
+                    self.disc.half = Some(x);
+                    continue;
+                },
+            };
+
+            if let Some(s) = self.disc.feed(i, x) {
+                return Some(s);
+            }
+        }
+
+        None
+    }
+}
+
+/// Design a Hamming windowed sinc low-pass filter of `N` taps, an odd number, with the
+/// given cutoff for samples at the given rate, both in Hz, and unity gain at DC.
+#[cfg(any(feature = "std", test))]
+pub fn lowpass_taps<const N: usize>(rate: usize, cutoff: f32) -> [f32; N] {
+    use std::f64::consts::PI;
+
+    assert!(N % 2 == 1);
+    assert!(cutoff > 0.0 && cutoff < rate as f32 / 2.0);
+
+    let fc = cutoff as f64 / rate as f64;
+    let mid = (N / 2) as f64;
+    let mut taps = [0.0; N];
+    let mut sum = 0.0;
+
+    for (n, t) in taps.iter_mut().enumerate() {
+        let x = n as f64 - mid;
+        let sinc = if x == 0.0 { 2.0 * fc } else { (2.0 * PI * fc * x).sin() / (PI * x) };
+        let window = 0.54 - 0.46 * (2.0 * PI * n as f64 / (N - 1) as f64).cos();
+
+        *t = (sinc * window) as f32;
+        sum += *t as f64;
+    }
+
+    for t in taps.iter_mut() {
+        *t = (*t as f64 / sum) as f32;
+    }
+
+    taps
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::f32::consts::PI;
+    use baseband::sync::SyncScanner;
+    use message::nid::{DataUnit, NetworkAccessCode};
+    use testutil;
+
+    #[test]
+    fn test_tone() {
+        // A steady 600 Hz tone at 96 kHz, split mid sample.
+        let iq = testutil::fm(&vec![0.06; 200], 2, 0.5);
+        let mut disc = FmDiscriminator::<CHANNEL_TAPS>::new(96000);
+        assert_eq!(disc.decimation(), 2);
+
+        let mut out: Vec<f32> = disc.feed_interleaved(&iq[..101]).collect();
+        out.extend(disc.feed_interleaved(&iq[101..]));
+        assert_eq!(out.len(), 199);
+
+        for &s in out[1..].iter() {
+            assert!((s - 0.06).abs() < 1.0e-4, "{}", s);
+        }
+
+        // No amplitude gives no deviation rather than NaN.
+        disc.reset();
+        assert_eq!(disc.feed_interleaved(&[0.0; 8]).collect::<Vec<f32>>(), vec![0.0; 2]);
+    }
+
+    #[test]
+    fn test_lowpass() {
+        let taps = lowpass_taps::<CHANNEL_TAPS>(96000, 8000.0);
+        assert!((taps.iter().sum::<f32>() - 1.0).abs() < 1.0e-6);
+        assert_eq!(taps[0], taps[CHANNEL_TAPS - 1]);
+
+        // A tone well above the cutoff is mostly rejected.
+        let mut fir = Fir::new(&taps);
+        let out: Vec<f32> = (0..200).map(|n| fir.feed((2.0 * PI * 0.3 * n as f32).cos()))
+            .collect();
+
+        assert!(out[100..].iter().all(|s| s.abs() < 0.01));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dibits = testutil::frame(NetworkAccessCode::Default,
+                                     DataUnit::VoiceSimpleTerminator, vec![]);
+
+        let mut samples = testutil::silence(0.01);
+        samples.extend(testutil::modulate(&dibits));
+        samples.extend(testutil::silence(0.01));
+
+        // Through a channel filter at twice the baseband rate, with the signal fading
+        // out completely for a while before it.
+        let mut iq = testutil::fm(&samples, 2, 0.7);
+
+        for x in iq[1000..3000].iter_mut() {
+            *x = 0.0;
+        }
+
+        let mut disc = FmDiscriminator::new(96000)
+            .with_filter(&lowpass_taps::<CHANNEL_TAPS>(96000, 8000.0));
+
+        let out: Vec<f32> = disc.feed_interleaved(&iq).collect();
+        assert!(out.iter().all(|s| !s.is_nan()));
+
+        let syncs: Vec<_> = SyncScanner::new().feed_slice(&out).collect();
+        assert_eq!(syncs.len(), 1);
+
+        let dus = testutil::data_units(&testutil::decode(&out));
+        assert_eq!(dus, vec![DataUnit::VoiceSimpleTerminator]);
+    }
+}
@@ -1,5 +1,26 @@
 //! Sychronization and symbol encoding/decoding of C4FM signal used by P25.
 
+pub mod afc;
+#[cfg(feature = "std")]
+pub mod block;
+pub mod clock;
+pub mod cqpsk;
 pub mod decode;
+pub mod demod;
 pub mod encode;
+#[cfg(feature = "std")]
+pub mod fft;
+pub mod filter;
+pub mod fir;
+pub mod fm;
+#[cfg(any(feature = "std", test))]
+pub mod modulate;
+pub mod pcm;
+pub mod phase2;
+pub mod sample;
+#[cfg(any(feature = "std", test))]
+pub mod shaping;
+pub mod simd;
 pub mod sync;
+#[cfg(any(feature = "std", test))]
+pub mod syncgen;
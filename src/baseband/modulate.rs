@@ -0,0 +1,254 @@
+//! C4FM modulation of a dibit stream into baseband samples, for generating test signals
+//! on the transmit side.
+//!
+//! Each dibit is mapped to one of the four deviation levels, upsampled to the sample
+//! rate, and shaped by the transmit pulse, by default the P25 raised cosine, the same
+//! pulse `SyncGenerator` uses. The pulse reaches a number of symbols either side of its
+//! center, so each symbol comes out that many symbols after it's fed in, and `flush`
+//! drains the symbols still in the filter at the end of a transmission.
+
+use std;
+use std::collections::VecDeque;
+use std::vec::Vec;
+
+use baseband::fm::LEVEL_PER_HZ;
+use baseband::shaping::{PulseShape, ROLLOFF};
+use baseband::sync::{Polarity, SYNC_DEVIATION};
+use bits::Dibit;
+use consts::SYMBOL_PERIOD;
+
+/// Modulates dibits into C4FM baseband samples.
+///
+/// Filter state carries from one call to the next, so frames fed in separately make up
+/// one continuous signal.
+#[derive(Clone)]
+pub struct C4fmModulator {
+    /// Pulse each symbol is shaped by.
+    shape: PulseShape,
+    /// Samples per symbol.
+    period: usize,
+    /// Level of the outer symbols.
+    level: f32,
+    /// Polarity of the generated signal.
+    polarity: Polarity,
+    /// Pulse values for each sample within a symbol period, in rows of one per symbol in
+    /// the window, oldest first.
+    coefs: Vec<f32>,
+    /// Levels of the symbols the pulse currently reaches, oldest first, with the symbol
+    /// being output in the middle.
+    window: VecDeque<f32>,
+}
+
+impl C4fmModulator {
+    /// Create a new `C4fmModulator` with the P25 raised cosine pulse at 48kHz, the
+    /// standard deviation, and normal polarity.
+    pub fn new() -> C4fmModulator {
+        C4fmModulator::with_shape(PulseShape::RaisedCosine(ROLLOFF), SYMBOL_PERIOD)
+    }
+
+    /// Create a new `C4fmModulator` with the given pulse and number of samples per
+    /// symbol, such as 20 for 96kHz.
+    pub fn with_shape(shape: PulseShape, period: usize) -> C4fmModulator {
+        assert!(period > 1);
+
+        let span = shape.span();
+        let width = 2 * span + 1;
+        let mut coefs = Vec::with_capacity(period * width);
+
+        for j in 0..period {
+            for k in 0..width {
+                let x = span as f64 + j as f64 / period as f64 - k as f64;
+                coefs.push(shape.at(x) as f32);
+            }
+        }
+
+        C4fmModulator {
+            shape: shape,
+            period: period,
+            level: SYNC_DEVIATION * LEVEL_PER_HZ,
+            polarity: Polarity::Normal,
+            coefs: coefs,
+            window: std::iter::repeat(0.0).take(width).collect(),
+        }
+    }
+
+    /// Put the outer symbols at the given deviation in Hz, `SYNC_DEVIATION` by default,
+    /// scaled by `LEVEL_PER_HZ` as an FM discriminator would.
+    pub fn with_deviation(mut self, hz: f32) -> Self {
+        assert!(hz > 0.0);
+        self.level = hz * LEVEL_PER_HZ;
+        self
+    }
+
+    /// Generate a signal of the given polarity.
+    pub fn with_polarity(mut self, polarity: Polarity) -> Self {
+        self.polarity = polarity;
+        self
+    }
+
+    /// Pulse each symbol is shaped by.
+    pub fn shape(&self) -> PulseShape { self.shape }
+
+    /// Samples per symbol.
+    pub fn period(&self) -> usize { self.period }
+
+    /// Number of samples from feeding in a symbol to its instant in the output, which is
+    /// the first sample of its period.
+    pub fn delay(&self) -> usize { self.shape.span() * self.period }
+
+    /// Feed in the given dibit, appending a symbol period of samples to the given buffer.
+    pub fn feed(&mut self, dibit: Dibit, out: &mut Vec<f32>) {
+        let level = match dibit.bits() {
+            0b01 => 1.0,
+            0b00 => 1.0 / 3.0,
+            0b10 => -1.0 / 3.0,
+            _ => -1.0,
+        };
+
+        self.push(level * self.level, out);
+    }
+
+    /// Feed in the given dibits, appending their samples to the given buffer.
+    pub fn modulate<I: IntoIterator<Item = Dibit>>(&mut self, dibits: I, out: &mut Vec<f32>) {
+        for d in dibits {
+            self.feed(d, out);
+        }
+    }
+
+    /// Drain the symbols still in the filter, along with the tails of their pulses, into
+    /// the given buffer, leaving the modulator ready for another transmission.
+    pub fn flush(&mut self, out: &mut Vec<f32>) {
+        for _ in 0..2 * self.shape.span() {
+            self.push(0.0, out);
+        }
+    }
+
+    /// Shift the given symbol level into the window and output the symbol period of the
+    /// one in the middle.
+    fn push(&mut self, level: f32, out: &mut Vec<f32>) {
+        self.window.pop_front();
+        self.window.push_back(level);
+
+        let width = self.window.len();
+
+        for row in self.coefs.chunks(width) {
+            let s = row.iter().zip(self.window.iter()).fold(0.0, |sum, (&c, &l)| {
+                sum + c * l
+            });
+
+            out.push(self.polarity.correct(s));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use baseband::decode::{Decider, Decoder};
+    use baseband::sync::{FINGERPRINT_SAMPS, SYNC_GENERATOR, SyncCorrelator, SyncScanner,
+                         instant_thresholds};
+    use baseband::syncgen::SyncGenerator;
+    use bits::Dibits;
+    use consts::SYNC_SYMBOLS;
+    use message::nid::{DataUnit, NetworkAccessCode};
+    use testutil;
+
+    #[test]
+    fn test_sync() {
+        // The sync alone matches the sync generator once its tails are drained.
+        let mut m = C4fmModulator::new();
+        let mut out = vec![];
+        m.modulate(Dibits::new(SYNC_GENERATOR.iter().cloned()), &mut out);
+        m.flush(&mut out);
+        assert_eq!(out.len(), (SYNC_SYMBOLS + 16) * SYMBOL_PERIOD);
+
+        let mut sync = [0.0; FINGERPRINT_SAMPS];
+        SyncGenerator::new().generate(&mut sync);
+
+        let start = m.delay();
+
+        for (&s, &g) in out[start..start + FINGERPRINT_SAMPS].iter().zip(sync.iter()) {
+            assert!((s - g).abs() < 1.0e-5, "{} {}", s, g);
+        }
+
+        // Inverted at half the deviation.
+        let mut m = C4fmModulator::new().with_deviation(900.0)
+            .with_polarity(Polarity::Inverted);
+
+        let mut inv = vec![];
+        m.modulate(Dibits::new(SYNC_GENERATOR.iter().cloned()), &mut inv);
+        m.flush(&mut inv);
+
+        for (&s, &n) in inv.iter().zip(out.iter()) {
+            assert!((s + n / 2.0).abs() < 1.0e-6);
+        }
+    }
+
+    #[test]
+    fn test_continuous() {
+        let dibits = testutil::voice_call(NetworkAccessCode::Default, 0x0100, 0x123456, 3);
+
+        let mut whole = vec![];
+        let mut m = C4fmModulator::new();
+        m.modulate(dibits.iter().cloned(), &mut whole);
+        m.flush(&mut whole);
+
+        // Fed in uneven bursts, the signal is the same.
+        let mut parts = vec![];
+        let mut m = C4fmModulator::new();
+
+        for chunk in dibits.chunks(37) {
+            m.modulate(chunk.iter().cloned(), &mut parts);
+        }
+
+        m.flush(&mut parts);
+        assert_eq!(parts, whole);
+
+        // Nothing is left in the filter after a flush, so the next transmission starts
+        // afresh.
+        let mut again = vec![];
+        m.modulate(dibits.iter().cloned(), &mut again);
+        m.flush(&mut again);
+        assert_eq!(again, whole);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dibits = testutil::frame(NetworkAccessCode::Default,
+                                     DataUnit::VoiceSimpleTerminator, vec![]);
+
+        let mut m = C4fmModulator::new();
+        let mut samples = vec![0.0; 1000];
+        m.modulate(dibits.iter().cloned(), &mut samples);
+        m.flush(&mut samples);
+        samples.extend(vec![0.0; 1000]);
+
+        // Found by the sync correlator, at the end of the sync symbols.
+        let found: Vec<_> = SyncScanner::new().feed_slice(&samples).collect();
+        assert_eq!(found.len(), 1);
+
+        let end = 1000 + m.delay() + (SYNC_SYMBOLS - 1) * SYMBOL_PERIOD + 1;
+        assert_eq!(found[0].index, end);
+
+        // Symbols decided with the thresholds from the sync come out as sent.
+        let mut corr = SyncCorrelator::new();
+
+        for &s in samples[..end + 1].iter() {
+            corr.feed(s);
+        }
+
+        let (upper, mid, lower) = instant_thresholds(&corr.history());
+        let mut decoder = Decoder::new(Decider::new(upper, mid, lower));
+
+        let decoded: Vec<Dibit> = samples[end + 1..].iter()
+            .filter_map(|&s| decoder.feed(s))
+            .take(dibits.len() - SYNC_SYMBOLS)
+            .collect();
+
+        assert_eq!(&decoded[..], &dibits[SYNC_SYMBOLS..]);
+
+        // And the receiver decodes it.
+        let dus = testutil::data_units(&testutil::decode(&samples));
+        assert_eq!(dus, vec![DataUnit::VoiceSimpleTerminator]);
+    }
+}
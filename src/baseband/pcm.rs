@@ -0,0 +1,228 @@
+//! Conversion of signed 16-bit PCM, as delivered by sound cards, WAV files, and
+//! `rtl_fm`, into the floating point samples the baseband stage expects.
+//!
+//! Full scale maps to [-1, 1] by default, like `SampleFormat::I16Le`, and interleaved
+//! frames of several channels can be reduced to one of them. An optional DC blocker
+//! removes any constant offset the audio path adds.
+
+use std;
+use std::vec::Vec;
+
+/// Full scale of 16-bit samples.
+const FULL_SCALE: f32 = 1.0 / 32768.0;
+
+/// Converts buffers of 16-bit PCM into baseband samples, keeping state from one buffer
+/// to the next so a stream can be converted in arbitrary pieces.
+#[derive(Copy, Clone, Debug)]
+pub struct PcmAdapter {
+    /// Number of interleaved channels.
+    channels: usize,
+    /// Channel kept from each frame.
+    channel: usize,
+    /// Factor each sample is multiplied by.
+    scale: f32,
+    /// Pole of the DC blocker, if enabled.
+    pole: Option<f32>,
+    /// Channel of the next sample in the stream.
+    next: usize,
+    /// Previous input and output of the DC blocker.
+    prev: (f32, f32),
+}
+
+impl PcmAdapter {
+    /// Create a new `PcmAdapter` for a single channel, scaled so full-scale samples map
+    /// to [-1, 1].
+    pub fn new() -> PcmAdapter {
+        PcmAdapter {
+            channels: 1,
+            channel: 0,
+            scale: FULL_SCALE,
+            pole: None,
+            next: 0,
+            prev: (0.0, 0.0),
+        }
+    }
+
+    /// Take the given channel, counting from 0, out of frames of the given number of
+    /// interleaved channels.
+    pub fn with_channels(mut self, channels: usize, channel: usize) -> Self {
+        assert!(channel < channels);
+        self.channels = channels;
+        self.channel = channel;
+        self
+    }
+
+    /// Multiply full-scale samples by the given gain, for sources that don't use the
+    /// full range.
+    pub fn with_gain(mut self, gain: f32) -> Self {
+        self.scale = FULL_SCALE * gain;
+        self
+    }
+
+    /// Remove DC with a single pole high-pass filter with the given pole, in (0, 1).
+    ///
+    /// Poles closer to 1 cut off lower, at about `(1 - pole) * rate / 2π` Hz, so 0.999
+    /// at 48 kHz passes everything above 8 Hz and leaves the symbols alone.
+    pub fn with_dc_removal(mut self, pole: f32) -> Self {
+        assert!(pole > 0.0 && pole < 1.0);
+        self.pole = Some(pole);
+        self
+    }
+
+    /// Number of interleaved channels.
+    pub fn channels(&self) -> usize { self.channels }
+
+    /// Channel kept from each frame.
+    pub fn channel(&self) -> usize { self.channel }
+
+    /// Convert the given buffer of PCM, returning an iterator over the baseband samples
+    /// of the selected channel, which can be fed to a receiver or correlator.
+    ///
+    /// Frames can be split across buffers, as the position within a frame is carried
+    /// over to the next buffer.
+    pub fn convert<'a>(&'a mut self, pcm: &'a [i16]) -> PcmSamples<'a> {
+        PcmSamples {
+            adapter: self,
+            pcm: pcm.iter(),
+        }
+    }
+
+    /// Convert the given buffer of PCM, appending the baseband samples of the selected
+    /// channel to the given buffer, for feeding to a `feed_slice` method.
+    pub fn convert_into(&mut self, pcm: &[i16], out: &mut Vec<f32>) {
+        out.extend(self.convert(pcm));
+    }
+
+    /// Forget the DC blocker history and start the next buffer at the first channel of
+    /// a frame.
+    pub fn reset(&mut self) {
+        self.next = 0;
+        self.prev = (0.0, 0.0);
+    }
+
+    /// Convert the given PCM sample, returning the baseband sample if it's in the
+    /// selected channel.
+    fn feed(&mut self, s: i16) -> Option<f32> {
+        let chan = self.next;
+        self.next = (self.next + 1) % self.channels;
+
+        if chan != self.channel {
+            return None;
+        }
+
+        let x = s as f32 * self.scale;
+
+        let pole = match self.pole {
+            Some(pole) => pole,
+            None => return Some(x),
+        };
+
+        let y = x - self.prev.0 + pole * self.prev.1;
+        self.prev = (x, y);
+
+        Some(y)
+    }
+}
+
+/// Iterator over the baseband samples converted from a buffer of PCM, created by
+/// `PcmAdapter::convert`.
+pub struct PcmSamples<'a> {
+    /// Adapter doing the conversion.
+    adapter: &'a mut PcmAdapter,
+    /// Remaining PCM samples.
+    pcm: std::slice::Iter<'a, i16>,
+}
+
+impl<'a> Iterator for PcmSamples<'a> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        for &s in self.pcm.by_ref() {
+            if let Some(x) = self.adapter.feed(s) {
+                return Some(x);
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use baseband::sync::{SyncCorrelator, SYNC_FINGERPRINT};
+    use message::nid::{DataUnit, NetworkAccessCode};
+    use testutil;
+
+    /// Quantize the given baseband samples to full-scale PCM.
+    fn quantize(samples: &[f32]) -> Vec<i16> {
+        samples.iter().map(|&s| (s * 32768.0).round() as i16).collect()
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let levels: Vec<f32> = SYNC_FINGERPRINT.iter().map(|&f| f * 0.18).collect();
+        let pcm = quantize(&levels);
+
+        let mut expect = SyncCorrelator::new();
+        let mut corr = SyncCorrelator::new();
+
+        let (power, sigpower) = levels.iter().fold((0.0, 0.0), |_, &s| expect.feed(s));
+        let (qpower, qsigpower) = PcmAdapter::new().convert(&pcm)
+            .fold((0.0, 0.0), |_, s| corr.feed(s));
+
+        assert!((qpower - power).abs() < 1.0e-4);
+        assert!((qsigpower - sigpower).abs() < 1.0e-4);
+
+        // The same through the right channel of stereo, split mid frame.
+        let mut stereo = vec![];
+
+        for &s in pcm.iter() {
+            stereo.extend(&[-1234, s]);
+        }
+
+        let mut adapter = PcmAdapter::new().with_channels(2, 1);
+        let mut samples = vec![];
+        adapter.convert_into(&stereo[..101], &mut samples);
+        adapter.convert_into(&stereo[101..], &mut samples);
+
+        assert_eq!(samples, PcmAdapter::new().convert(&pcm).collect::<Vec<f32>>());
+    }
+
+    #[test]
+    fn test_scale() {
+        let pcm = [16384, -16384, -32768, 0];
+
+        assert_eq!(PcmAdapter::new().convert(&pcm).collect::<Vec<f32>>(),
+                   vec![0.5, -0.5, -1.0, 0.0]);
+        assert_eq!(PcmAdapter::new().with_gain(0.5).convert(&pcm).collect::<Vec<f32>>(),
+                   vec![0.25, -0.25, -0.5, 0.0]);
+        assert_eq!(PcmAdapter::new().with_channels(4, 2).convert(&pcm)
+                       .collect::<Vec<f32>>(),
+                   vec![-1.0]);
+    }
+
+    #[test]
+    fn test_dc_removal() {
+        let dibits = testutil::frame(NetworkAccessCode::Default,
+                                     DataUnit::VoiceSimpleTerminator, vec![]);
+
+        let mut samples = vec![0.0; 20000];
+        samples.extend(testutil::modulate(&dibits));
+        samples.extend(testutil::silence(0.01));
+
+        // An offset of a third of the outer symbol level throughout.
+        let offset: Vec<f32> = samples.iter().map(|&s| s + 0.06).collect();
+        let pcm = quantize(&offset);
+
+        let mut adapter = PcmAdapter::new().with_dc_removal(0.999);
+        let converted: Vec<f32> = adapter.convert(&pcm).collect();
+        assert!(converted[19999].abs() < 1.0e-3);
+
+        let dus = testutil::data_units(&testutil::decode(&converted));
+        assert_eq!(dus, vec![DataUnit::VoiceSimpleTerminator]);
+
+        adapter.reset();
+        assert_eq!(adapter.convert(&[0]).next(), Some(0.0));
+    }
+}
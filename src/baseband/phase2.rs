@@ -0,0 +1,272 @@
+//! Detection of the P25 Phase 2 TDMA sync, carried in the S-ISCH between timeslots, in
+//! the same baseband stream as Phase 1 frame syncs.
+//!
+//! Phase 2 runs at 6000 symbols per second, so its fingerprint has 8 samples per symbol
+//! rather than 10, and its own length constant. Only detection is handled so far: each
+//! sync found is reported with the protocol it belongs to and, for Phase 2, the number
+//! of 30 ms timeslots since the previous one.
+
+use std::vec::Vec;
+
+use baseband::sync::{FINGERPRINT_SAMPS, SyncCorrelator, SyncPattern, SyncPosition,
+                     SyncScanner};
+use consts::SAMPLE_RATE;
+
+/// Phase 2 symbols (dibits) per second.
+pub const PHASE2_SYMBOL_RATE: usize = 6000;
+
+/// Baseband samples per Phase 2 symbol.
+pub const PHASE2_SYMBOL_PERIOD: usize = SAMPLE_RATE / PHASE2_SYMBOL_RATE;
+
+/// Number of symbols in the Phase 2 sync sequence.
+pub const PHASE2_SYNC_SYMBOLS: usize = 20;
+
+/// Number of symbols in each 30 ms Phase 2 timeslot, including its ISCH.
+pub const PHASE2_SLOT_SYMBOLS: usize = 180;
+
+/// Symbols that make up the Phase 2 sync fingerprint.
+pub const PHASE2_SYNC_GENERATOR: &'static [u8] = &[
+    0b01010111,
+    0b01011101,
+    0b01010111,
+    0b11110111,
+    0b11111111,
+];
+
+/// Phase 2 S-ISCH sync.
+pub const PHASE2_SYNC_PATTERN: SyncPattern = SyncPattern {
+    generator: PHASE2_SYNC_GENERATOR,
+    symbols: PHASE2_SYNC_SYMBOLS,
+};
+
+/// Number of samples in the Phase 2 sync fingerprint, from first impulse to last, at
+/// 48kHz sample rate.
+pub const PHASE2_FINGERPRINT_SAMPS: usize =
+    PHASE2_SYNC_PATTERN.fingerprint_samps(PHASE2_SYMBOL_PERIOD);
+
+/// Generate the Phase 2 sync fingerprint at 48kHz, in the same layout as
+/// `SYNC_FINGERPRINT`.
+pub fn phase2_fingerprint() -> [f32; PHASE2_FINGERPRINT_SAMPS] {
+    PHASE2_SYNC_PATTERN.fingerprint()
+}
+
+/// Create a `SyncCorrelator` for the Phase 2 sync at 48kHz.
+pub fn phase2_correlator() -> SyncCorrelator<PHASE2_FINGERPRINT_SAMPS> {
+    SyncCorrelator::with_fingerprint(&phase2_fingerprint())
+}
+
+/// Default factor of the RMS signal power that Phase 2 sync correlation power must
+/// exceed, higher than for Phase 1 as the shorter sync is more often matched by chance.
+pub const PHASE2_SYNC_THRESHOLD: f32 = 0.8;
+
+/// Air interface protocol a sync belongs to.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Protocol {
+    /// Phase 1 FDMA.
+    Phase1,
+    /// Phase 2 TDMA.
+    Phase2,
+}
+
+/// Sync found by a `DualSyncScanner`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ProtocolSync {
+    /// Protocol of the sync.
+    pub protocol: Protocol,
+    /// Position of the sync, as from `SyncScanner`.
+    pub pos: SyncPosition,
+    /// Number of whole timeslots since the previous Phase 2 sync, for a Phase 2 sync
+    /// that follows another within the given limit.
+    pub slots: Option<usize>,
+}
+
+/// Most timeslots between Phase 2 syncs that are counted, a full superframe.
+const MAX_SLOTS: usize = 12;
+
+/// Searches the same baseband stream for Phase 1 and Phase 2 syncs, each with its own
+/// correlator.
+#[derive(Clone)]
+pub struct DualSyncScanner {
+    /// Phase 1 scanner.
+    phase1: SyncScanner<FINGERPRINT_SAMPS>,
+    /// Phase 2 scanner.
+    phase2: SyncScanner<PHASE2_FINGERPRINT_SAMPS>,
+    /// Number of samples fed in before the current buffer.
+    sample: u64,
+    /// Sample position of the last Phase 2 sync, if any.
+    last: Option<u64>,
+}
+
+impl DualSyncScanner {
+    /// Create a new `DualSyncScanner` with the default detection thresholds.
+    pub fn new() -> DualSyncScanner {
+        DualSyncScanner {
+            phase1: SyncScanner::new(),
+            phase2: SyncScanner::with_correlator(phase2_correlator())
+                .with_threshold(PHASE2_SYNC_THRESHOLD),
+            sample: 0,
+            last: None,
+        }
+    }
+
+    /// Detect syncs above the given factors of the RMS signal power, `SYNC_THRESHOLD`
+    /// for Phase 1 and `PHASE2_SYNC_THRESHOLD` for Phase 2 by default.
+    pub fn with_thresholds(mut self, phase1: f32, phase2: f32) -> Self {
+        self.phase1.set_threshold(phase1);
+        self.phase2.set_threshold(phase2);
+        self
+    }
+
+    /// Search the given buffer, returning the syncs of either protocol found in it, in
+    /// order, with indexes into it.
+    pub fn feed_slice(&mut self, samples: &[f32]) -> Vec<ProtocolSync> {
+        let mut found: Vec<ProtocolSync> = self.phase1.feed_slice(samples)
+            .map(|pos| ProtocolSync {
+                protocol: Protocol::Phase1,
+                pos: pos,
+                slots: None,
+            })
+            .collect();
+
+        for pos in self.phase2.feed_slice(samples) {
+            let at = self.sample + pos.index as u64;
+            let slot = (PHASE2_SLOT_SYMBOLS * PHASE2_SYMBOL_PERIOD) as u64;
+
+            let slots = self.last.and_then(|last| {
+                let n = ((at - last + slot / 2) / slot) as usize;
+                if n > 0 && n <= MAX_SLOTS { Some(n) } else { None }
+            });
+
+            self.last = Some(at);
+
+            found.push(ProtocolSync {
+                protocol: Protocol::Phase2,
+                pos: pos,
+                slots: slots,
+            });
+        }
+
+        found.sort_by_key(|s| s.pos.index);
+        self.sample += samples.len() as u64;
+
+        found
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bits::{Dibit, Dibits};
+    use message::nid::NetworkAccessCode;
+    use testutil::{self, Rng};
+
+    /// Modulate the given dibits into ideal baseband samples at the Phase 2 rate.
+    fn modulate(dibits: &[Dibit]) -> Vec<f32> {
+        dibits.iter().flat_map(|d| {
+            let level = match d.bits() {
+                0b01 => 0.18,
+                0b00 => 0.06,
+                0b10 => -0.06,
+                _ => -0.18,
+            };
+
+            vec![level; PHASE2_SYMBOL_PERIOD]
+        }).collect()
+    }
+
+    /// Build Phase 2 timeslots of random symbols, each with a sync at its end if
+    /// selected.
+    fn slots(rng: &mut Rng, syncs: &[bool]) -> Vec<Dibit> {
+        let mut dibits = vec![];
+
+        for &sync in syncs {
+            for _ in 0..PHASE2_SLOT_SYMBOLS - PHASE2_SYNC_SYMBOLS {
+                dibits.push(Dibit::new(rng.next() as u8 & 0b11));
+            }
+
+            if sync {
+                dibits.extend(Dibits::new(PHASE2_SYNC_GENERATOR.iter().cloned()));
+            } else {
+                for _ in 0..PHASE2_SYNC_SYMBOLS {
+                    dibits.push(Dibit::new(rng.next() as u8 & 0b11));
+                }
+            }
+        }
+
+        dibits
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        assert_eq!(PHASE2_SYMBOL_PERIOD, 8);
+        assert_eq!(PHASE2_FINGERPRINT_SAMPS, 153);
+
+        let fp = phase2_fingerprint();
+        assert_eq!(&fp[..5], &[1.0; 5]);
+        assert_eq!(fp[20], 0.0);
+        assert_eq!(fp[21], -1.0);
+        assert_eq!(fp[28], 0.0);
+        assert_eq!(fp[29], 1.0);
+        assert_eq!(fp[152], -1.0);
+
+        // Symbol instants of the fingerprint itself.
+        let samples: Vec<f32> = fp.iter().map(|&f| f * 0.18).collect();
+        let mut shifted = [0.0; PHASE2_FINGERPRINT_SAMPS];
+        shifted[..152].copy_from_slice(&samples[1..]);
+        shifted[152] = -0.18;
+
+        let (pavg, navg) = PHASE2_SYNC_PATTERN.averages(&shifted, 0.0);
+        assert!((pavg - 0.18).abs() < 1.0e-6);
+        assert!((navg + 0.18).abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn test_dual() {
+        let mut rng = Rng::new(519);
+
+        // A Phase 1 TSDU, then Phase 2 timeslots with syncs in the first, second, and
+        // fifth.
+        let grant = testutil::group_grant(NetworkAccessCode::Default, 1, 2, 3);
+        let mut samples = testutil::modulate(&grant);
+        let phase2_start = samples.len();
+
+        samples.extend(modulate(&slots(&mut rng, &[true, true, false, false, true,
+                                                   false])));
+        samples.extend(vec![0.0; 1000]);
+
+        let slot = PHASE2_SLOT_SYMBOLS * PHASE2_SYMBOL_PERIOD;
+        let mut scanner = DualSyncScanner::new();
+
+        // Split unevenly, including through a sync.
+        let mut found = scanner.feed_slice(&samples[..phase2_start + slot - 40]);
+        let offset = phase2_start + slot - 40;
+
+        for mut s in scanner.feed_slice(&samples[offset..]) {
+            s.pos.index += offset;
+            found.push(s);
+        }
+
+        let kinds: Vec<(Protocol, Option<usize>)> = found.iter()
+            .map(|s| (s.protocol, s.slots))
+            .collect();
+
+        assert_eq!(kinds, vec![
+            (Protocol::Phase1, None),
+            (Protocol::Phase2, None),
+            (Protocol::Phase2, Some(1)),
+            (Protocol::Phase2, Some(3)),
+        ]);
+
+        // Each Phase 2 sync is found within its last symbol.
+        for (s, &n) in found[1..].iter().zip([1, 2, 5].iter()) {
+            let end = phase2_start + n * slot;
+            assert!(s.pos.index > end - PHASE2_SYMBOL_PERIOD && s.pos.index <= end);
+        }
+
+        // Random symbols match the shorter sync often enough at the Phase 1 threshold.
+        let mut loose = DualSyncScanner::new().with_thresholds(0.65, 0.65);
+        assert!(loose.feed_slice(&samples).len() > found.len());
+
+        assert!(found[0].pos.index < phase2_start);
+    }
+}
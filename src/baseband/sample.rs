@@ -0,0 +1,88 @@
+//! Floating point types that baseband samples can be processed in.
+
+use std;
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+use baseband::simd;
+use util;
+
+/// Floating point type of baseband samples, `f32` or `f64`.
+///
+/// This covers only the arithmetic the sync machinery needs, so it works the same
+/// without the platform math library.
+pub trait Sample: Copy + Default + PartialOrd + std::fmt::Debug + From<u16> +
+    Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self> +
+    Neg<Output = Self> + AddAssign + SubAssign
+{
+    /// Convert the given `f32` value.
+    fn from_f32(x: f32) -> Self;
+
+    /// Convert to an `f32` value, rounding if needed.
+    fn to_f32(self) -> f32;
+
+    /// Convert the given count.
+    fn from_usize(n: usize) -> Self;
+
+    /// Calculate the square root of the value, which must be nonnegative.
+    fn sqrt(self) -> Self;
+
+    /// Zero.
+    fn zero() -> Self { Self::default() }
+
+    /// Add the dot product of the given equal length buffers to the given sum.
+    fn dot(sum: Self, a: &[Self], b: &[Self]) -> Self {
+        a.iter().zip(b.iter()).fold(sum, |sum, (&x, &y)| sum + x * y)
+    }
+
+    /// Version of `dot` to use on this processor, chosen once so it can be kept and
+    /// called for every sample.
+    fn dot_fn() -> fn(Self, &[Self], &[Self]) -> Self { Self::dot }
+}
+
+impl Sample for f32 {
+    fn from_f32(x: f32) -> Self { x }
+    fn to_f32(self) -> f32 { self }
+    fn from_usize(n: usize) -> Self { n as f32 }
+    fn sqrt(self) -> Self { util::sqrt(self) }
+    fn dot(sum: Self, a: &[Self], b: &[Self]) -> Self { simd::dot(sum, a, b) }
+    fn dot_fn() -> fn(Self, &[Self], &[Self]) -> Self { simd::select() }
+}
+
+impl Sample for f64 {
+    fn from_f32(x: f32) -> Self { x as f64 }
+    fn to_f32(self) -> f32 { self as f32 }
+    fn from_usize(n: usize) -> Self { n as f64 }
+
+    #[cfg(feature = "std")]
+    fn sqrt(self) -> Self { self.sqrt() }
+
+    #[cfg(not(feature = "std"))]
+    fn sqrt(self) -> Self {
+        if self <= 0.0 {
+            return 0.0;
+        }
+
+        // Refine the single precision root to double precision.
+        let mut y = util::sqrt(self as f32) as f64;
+
+        for _ in 0..2 {
+            y = 0.5 * (y + self / y);
+        }
+
+        y
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sample() {
+        assert_eq!(<f64 as Sample>::sqrt(2.25), 1.5);
+        assert_eq!(<f32 as Sample>::sqrt(2.25), 1.5);
+        assert_eq!(f64::from_f32(0.5), 0.5);
+        assert_eq!(f64::from_usize(231).to_f32(), 231.0);
+        assert_eq!(f32::zero(), 0.0);
+    }
+}
@@ -0,0 +1,139 @@
+//! Pulse shapes of the P25 Phase 1 modulations.
+//!
+//! C4FM symbols are shaped by a raised cosine Nyquist filter with a rolloff of 0.2, so
+//! an ideal baseband signal passes through each symbol level exactly at its instant and
+//! moves smoothly between them.
+
+use std::f64::consts::PI;
+
+/// Rolloff of the P25 Nyquist raised cosine filter.
+pub const ROLLOFF: f64 = 0.2;
+
+/// Value of a raised cosine pulse with the given rolloff at the given number of symbol
+/// periods from its center, which is 1 at the center and 0 at every other symbol
+/// instant.
+pub fn raised_cosine(x: f64, rolloff: f64) -> f64 {
+    let sinc = if x == 0.0 { 1.0 } else { (PI * x).sin() / (PI * x) };
+    let d = 1.0 - (2.0 * rolloff * x) * (2.0 * rolloff * x);
+
+    // The zero of the denominator is removable.
+    if d.abs() < 1.0e-9 {
+        PI / 4.0 * sinc
+    } else {
+        sinc * (PI * rolloff * x).cos() / d
+    }
+}
+
+/// Value of a root raised cosine pulse with the given rolloff at the given number of
+/// symbol periods from its center, half of a raised cosine response split between
+/// transmitter and receiver.
+pub fn root_raised_cosine(x: f64, rolloff: f64) -> f64 {
+    if x == 0.0 {
+        return 1.0 - rolloff + 4.0 * rolloff / PI;
+    }
+
+    let d = 1.0 - (4.0 * rolloff * x) * (4.0 * rolloff * x);
+
+    // The zeros of the denominator are removable.
+    if d.abs() < 1.0e-9 {
+        let a = PI / (4.0 * rolloff);
+
+        return rolloff / 2.0f64.sqrt() *
+            ((1.0 + 2.0 / PI) * a.sin() + (1.0 - 2.0 / PI) * a.cos());
+    }
+
+    ((PI * x * (1.0 - rolloff)).sin() +
+        4.0 * rolloff * x * (PI * x * (1.0 + rolloff)).cos()) / (PI * x * d)
+}
+
+/// Pulse each symbol level is shaped by when synthesizing a signal.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PulseShape {
+    /// Each level held for a symbol period, centered on its instant, with the midpoint of
+    /// two levels exactly halfway between instants, the shape of `SYNC_FINGERPRINT`.
+    Rectangular,
+    /// Raised cosine pulse with the given rolloff, `ROLLOFF` for the P25 transmit
+    /// filter.
+    RaisedCosine(f64),
+}
+
+impl PulseShape {
+    /// Value of the pulse at the given number of symbol periods from its center, which
+    /// is cut off to zero beyond its span.
+    pub fn at(&self, x: f64) -> f64 {
+        if x.abs() > self.span() as f64 {
+            return 0.0;
+        }
+
+        match *self {
+            PulseShape::Rectangular => if x.abs() < 0.5 {
+                1.0
+            } else if x.abs() == 0.5 {
+                0.5
+            } else {
+                0.0
+            },
+            PulseShape::RaisedCosine(rolloff) => raised_cosine(x, rolloff),
+        }
+    }
+
+    /// Number of symbol periods either side of its center beyond which the pulse is
+    /// taken as zero, where the raised cosine tails have fallen below 1% of the peak.
+    pub fn span(&self) -> usize {
+        match *self {
+            PulseShape::Rectangular => 1,
+            PulseShape::RaisedCosine(_) => 8,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_root_raised_cosine() {
+        // Convolved with itself, gives back the raised cosine at symbol instants.
+        let n: i32 = 40;
+        let rrc: Vec<f64> = (-16 * n..=16 * n)
+            .map(|i| root_raised_cosine(i as f64 / n as f64, ROLLOFF))
+            .collect();
+
+        for k in 0..4 {
+            let lag = (k * n) as usize;
+            let conv = (0..rrc.len() - lag).map(|i| rrc[i] * rrc[i + lag]).sum::<f64>() /
+                n as f64;
+            assert!((conv - raised_cosine(k as f64, ROLLOFF)).abs() < 0.01, "{} {}", k, conv);
+        }
+
+        let x = 0.25 / ROLLOFF;
+        let at = root_raised_cosine(x, ROLLOFF);
+        assert!((at - root_raised_cosine(x + 1.0e-6, ROLLOFF)).abs() < 1.0e-5);
+    }
+
+    #[test]
+    fn test_rectangular() {
+        let p = PulseShape::Rectangular;
+        assert_eq!(p.at(0.0), 1.0);
+        assert_eq!(p.at(0.4), 1.0);
+        assert_eq!(p.at(-0.5), 0.5);
+        assert_eq!(p.at(0.5), 0.5);
+        assert_eq!(p.at(0.6), 0.0);
+    }
+
+    #[test]
+    fn test_raised_cosine() {
+        assert_eq!(raised_cosine(0.0, ROLLOFF), 1.0);
+
+        for n in 1..10 {
+            assert!(raised_cosine(n as f64, ROLLOFF).abs() < 1.0e-12);
+            assert_eq!(raised_cosine(n as f64, ROLLOFF), raised_cosine(-(n as f64), ROLLOFF));
+        }
+
+        // Continuous through the removable zero at 1 / (2 * rolloff).
+        let x = 0.5 / ROLLOFF;
+        let at = raised_cosine(x, ROLLOFF);
+        assert!((at - raised_cosine(x + 1.0e-6, ROLLOFF)).abs() < 1.0e-6);
+        assert!((at - raised_cosine(x - 1.0e-6, ROLLOFF)).abs() < 1.0e-6);
+    }
+}
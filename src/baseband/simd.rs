@@ -0,0 +1,156 @@
+//! Dot products over sample buffers, the core of FIR filtering and sync correlation,
+//! with vectorized versions on x86_64.
+//!
+//! SSE is always available on x86_64, and AVX is used when the processor supports it,
+//! which is checked at runtime when `std` is available. Elsewhere the scalar version is
+//! used, which is also the reference for the vectorized ones. The vectorized versions
+//! sum in a different order, so their results can differ from it by rounding.
+//!
+//! Filters choose their version once with `select` and keep the returned function, so
+//! processor features aren't checked again for every sample. The `bench_fir` example
+//! times each version.
+
+/// Function that adds the dot product of two equal length buffers to a sum.
+pub type Dot = fn(f32, &[f32], &[f32]) -> f32;
+
+/// Choose the fastest dot product the processor supports.
+#[cfg(target_arch = "x86_64")]
+pub fn select() -> Dot {
+    #[cfg(any(feature = "std", test))]
+    {
+        if is_x86_feature_detected!("avx") {
+            return dot_avx_checked;
+        }
+    }
+
+    dot_sse_checked
+}
+
+/// Choose the fastest dot product the processor supports.
+#[cfg(not(target_arch = "x86_64"))]
+pub fn select() -> Dot { dot_scalar }
+
+/// Add the dot product of the given equal length buffers to the given sum, choosing the
+/// version to use on each call.
+pub fn dot(sum: f32, a: &[f32], b: &[f32]) -> f32 { select()(sum, a, b) }
+
+/// Add the dot product of the given equal length buffers to the given sum, one pair of
+/// elements at a time in order.
+pub fn dot_scalar(sum: f32, a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b.iter()).fold(sum, |sum, (&x, &y)| sum + x * y)
+}
+
+/// Add the dot product of the given equal length buffers to the given sum with SSE.
+#[cfg(target_arch = "x86_64")]
+fn dot_sse_checked(sum: f32, a: &[f32], b: &[f32]) -> f32 {
+    assert_eq!(a.len(), b.len());
+    unsafe { dot_sse(sum, a, b) }
+}
+
+/// Add the dot product of the given equal length buffers to the given sum with AVX,
+/// which `select` only returns once the processor is known to support it.
+#[cfg(all(target_arch = "x86_64", any(feature = "std", test)))]
+fn dot_avx_checked(sum: f32, a: &[f32], b: &[f32]) -> f32 {
+    assert_eq!(a.len(), b.len());
+    unsafe { dot_avx(sum, a, b) }
+}
+
+/// Add the dot product of the given equal length buffers to the given sum, four lanes
+/// at a time.
+#[cfg(target_arch = "x86_64")]
+unsafe fn dot_sse(sum: f32, a: &[f32], b: &[f32]) -> f32 {
+    use std::arch::x86_64::*;
+
+    let chunks = a.len() / 4;
+    let mut acc = _mm_setzero_ps();
+
+    for i in 0..chunks {
+        let x = _mm_loadu_ps(a.as_ptr().add(i * 4));
+        let y = _mm_loadu_ps(b.as_ptr().add(i * 4));
+        acc = _mm_add_ps(acc, _mm_mul_ps(x, y));
+    }
+
+    let mut lanes = [0.0; 4];
+    _mm_storeu_ps(lanes.as_mut_ptr(), acc);
+
+    let sum = sum + (lanes[0] + lanes[1]) + (lanes[2] + lanes[3]);
+    dot_scalar(sum, &a[chunks * 4..], &b[chunks * 4..])
+}
+
+/// Add the dot product of the given equal length buffers to the given sum, eight lanes
+/// at a time.
+#[cfg(all(target_arch = "x86_64", any(feature = "std", test)))]
+#[target_feature(enable = "avx")]
+unsafe fn dot_avx(sum: f32, a: &[f32], b: &[f32]) -> f32 {
+    use std::arch::x86_64::*;
+
+    let chunks = a.len() / 8;
+    let mut acc = _mm256_setzero_ps();
+
+    for i in 0..chunks {
+        let x = _mm256_loadu_ps(a.as_ptr().add(i * 8));
+        let y = _mm256_loadu_ps(b.as_ptr().add(i * 8));
+        acc = _mm256_add_ps(acc, _mm256_mul_ps(x, y));
+    }
+
+    let mut lanes = [0.0; 8];
+    _mm256_storeu_ps(lanes.as_mut_ptr(), acc);
+
+    let sum = sum + ((lanes[0] + lanes[1]) + (lanes[2] + lanes[3])) +
+        ((lanes[4] + lanes[5]) + (lanes[6] + lanes[7]));
+
+    dot_scalar(sum, &a[chunks * 8..], &b[chunks * 8..])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use baseband::sync::SYNC_FINGERPRINT;
+    use std;
+    use testutil;
+
+    /// Check the vectorized versions against the scalar one on the given buffers.
+    fn check(sum: f32, a: &[f32], b: &[f32]) {
+        let expected = dot_scalar(sum, a, b);
+        let mag = a.iter().zip(b.iter()).fold(sum.abs(), |m, (&x, &y)| m + (x * y).abs());
+        let tol = mag * a.len() as f32 * std::f32::EPSILON;
+
+        assert!((dot(sum, a, b) - expected).abs() <= tol);
+        assert!((select()(sum, a, b) - expected).abs() <= tol);
+
+        #[cfg(target_arch = "x86_64")]
+        unsafe {
+            assert!((dot_sse(sum, a, b) - expected).abs() <= tol);
+
+            if is_x86_feature_detected!("avx") {
+                assert!((dot_avx(sum, a, b) - expected).abs() <= tol);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dot() {
+        assert_eq!(dot(1.5, &[], &[]), 1.5);
+        assert_eq!(dot(0.0, &[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]), 32.0);
+
+        let mut rng = testutil::Rng::new(514);
+        let mut random = |n: usize| -> Vec<f32> {
+            (0..n).map(|_| rng.next() as f32 / std::u32::MAX as f32 - 0.5).collect()
+        };
+
+        // Every remainder past the vector width.
+        for n in 0..40 {
+            let (a, b) = (random(n), random(n));
+            check(0.25, &a, &b);
+        }
+
+        // The fingerprint against random history, split as the ring buffer would be.
+        for split in [0, 1, 7, 115, 230].iter().cloned() {
+            let hist = random(SYNC_FINGERPRINT.len());
+            let (newer, older) = hist.split_at(split);
+            let sum = dot_scalar(0.0, &SYNC_FINGERPRINT[..older.len()], older);
+
+            check(sum, &SYNC_FINGERPRINT[older.len()..], newer);
+        }
+    }
+}
@@ -2,121 +2,672 @@
 //! decoding thresholds from it.
 
 extern crate num;
-use num::Zero;
 
-use std;
+use baseband::decode::Decider;
+use baseband::fir::Fir;
+use baseband::sample::Sample;
+use consts::{SYMBOL_PERIOD, SYNC_SYMBOLS};
 
-use collect_slice::CollectSlice;
-use moving_avg::MovingAverage;
-use static_fir::FIRFilter;
+use util;
 
 /// Number of samples in the frame sync fingerprint, from first impulse to last, at 48kHz
 /// sample rate.
-const FINGERPRINT_SAMPS: usize = 231;
+pub const FINGERPRINT_SAMPS: usize = fingerprint_samps(SYMBOL_PERIOD);
 
-/// Number of sync sequences to smooth symbol threshold estimates over.
-const SMOOTH_AVG: usize = 4;
+/// Number of samples in the frame sync fingerprint, from first impulse to last, at the
+/// given number of samples per symbol.
+pub const fn fingerprint_samps(period: usize) -> usize {
+    SYNC_PATTERN.fingerprint_samps(period)
+}
+
+/// Number of samples per symbol of a fingerprint with `N` samples.
+fn fingerprint_period<const N: usize>() -> usize { SYNC_PATTERN.period::<N>() }
+
+/// Level of the given frame sync symbol, from 0 to 23.
+fn sync_symbol(idx: usize) -> f32 { SYNC_PATTERN.symbol(idx) }
+
+/// Generate the frame sync fingerprint for `N` samples, which must be a length given by
+/// `fingerprint_samps`, in the same layout as `SYNC_FINGERPRINT`.
+///
+/// Each symbol level is held until halfway to the next symbol instant, with the midpoint
+/// of the two levels in between if it falls on a sample.
+pub fn sync_fingerprint<const N: usize>() -> [f32; N] { SYNC_PATTERN.fingerprint() }
+
+/// Sync sequence of outer symbols, from which fingerprints and symbol levels are
+/// derived for any number of samples per symbol.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SyncPattern {
+    /// Symbols packed four to a byte, first symbol in the high bits.
+    pub generator: &'static [u8],
+    /// Number of symbols.
+    pub symbols: usize,
+}
+
+/// Phase 1 frame sync.
+pub const SYNC_PATTERN: SyncPattern = SyncPattern {
+    generator: SYNC_GENERATOR,
+    symbols: SYNC_SYMBOLS,
+};
+
+impl SyncPattern {
+    /// Number of samples in the fingerprint, from first impulse to last, at the given
+    /// number of samples per symbol.
+    pub const fn fingerprint_samps(&self, period: usize) -> usize {
+        (self.symbols - 1) * period + 1
+    }
+
+    /// Number of samples per symbol of a fingerprint with `N` samples.
+    pub fn period<const N: usize>(&self) -> usize {
+        assert!(N > 1 && (N - 1) % (self.symbols - 1) == 0,
+                "fingerprint length must be a whole number of symbols");
+
+        (N - 1) / (self.symbols - 1)
+    }
+
+    /// Bits of the given symbol.
+    pub fn dibit(&self, idx: usize) -> u8 {
+        self.generator[idx / 4] >> (6 - idx % 4 * 2) & 0b11
+    }
+
+    /// Level of the given symbol, from -1 to 1.
+    pub fn symbol(&self, idx: usize) -> f32 {
+        match self.dibit(idx) {
+            0b01 => 1.0,
+            0b00 => 1.0 / 3.0,
+            0b10 => -1.0 / 3.0,
+            _ => -1.0,
+        }
+    }
+
+    /// Generate the fingerprint for `N` samples, which must be a length given by
+    /// `fingerprint_samps`, in the same layout as `SYNC_FINGERPRINT`.
+    pub fn fingerprint<const N: usize>(&self) -> [f32; N] {
+        let period = self.period::<N>();
+        let mut taps = [0.0; N];
+
+        for (i, tap) in taps.iter_mut().enumerate() {
+            let sym = i / period;
+            let off = i % period;
+
+            *tap = if off == 0 || off * 2 < period {
+                self.symbol(sym)
+            } else if off * 2 == period {
+                (self.symbol(sym) + self.symbol(sym + 1)) / 2.0
+            } else {
+                self.symbol(sym + 1)
+            };
+        }
+
+        taps
+    }
+
+    /// Generate the fingerprint for `N` samples as the ideal raised cosine shaped
+    /// waveform of the sync symbols, in the same layout as `fingerprint`.
+    ///
+    /// The waveform overshoots between like symbols and crosses over between unlike
+    /// ones, as a received sync does, rather than stepping. Symbols either side of the
+    /// sync are unknown, so they're taken as zero. The taps are scaled to the energy of
+    /// the rectangular fingerprint so correlation power, and so the sync threshold
+    /// factor, keeps the same scale for either.
+    #[cfg(any(feature = "std", test))]
+    pub fn shaped_fingerprint<const N: usize>(&self) -> [f32; N] {
+        use baseband::shaping::{raised_cosine, ROLLOFF};
+
+        let period = self.period::<N>();
+        let mut shaped = [0.0f64; N];
+
+        for (i, tap) in shaped.iter_mut().enumerate() {
+            let t = i as f64 / period as f64;
+
+            *tap = (0..self.symbols).fold(0.0, |sum, sym| {
+                sum + self.symbol(sym) as f64 * raised_cosine(t - sym as f64, ROLLOFF)
+            });
+        }
+
+        let rect = self.fingerprint::<N>().iter().fold(0.0, |sum, &t| {
+            sum + t as f64 * t as f64
+        });
+
+        let energy = shaped.iter().fold(0.0, |sum, &t| sum + t * t);
+        let scale = (rect / energy).sqrt();
+        let mut taps = [0.0; N];
+
+        for (t, &s) in taps.iter_mut().zip(shaped.iter()) {
+            *t = (s * scale) as f32;
+        }
+
+        taps
+    }
+
+    /// Calculate the average positive and negative sample value at each symbol instant
+    /// in the given fingerprint samples, moved by the given fraction of a sample.
+    ///
+    /// The first sample should be the sample immediately after the first symbol impulse
+    /// in the fingerprint, and the last sample should be the sample immediately after the
+    /// final symbol impulse.
+    pub fn averages<T: Sample, const N: usize>(&self, samples: &[T; N], offset: f32)
+        -> (T, T)
+    {
+        let period = self.period::<N>();
+
+        let (mut psum, mut pcount) = (T::zero(), 0);
+        let (mut nsum, mut ncount) = (T::zero(), 0);
+
+        // First fingerprint symbol has been shifted off, so start at the second one.
+        for sym in 1..self.symbols {
+            let s = interpolate(samples, sym * period - 1, offset);
+
+            if self.symbol(sym) > 0.0 {
+                psum += s;
+                pcount += 1;
+            } else {
+                nsum += s;
+                ncount += 1;
+            }
+        }
+
+        (psum / T::from_usize(pcount), nsum / T::from_usize(ncount))
+    }
+
+    /// Count the symbols in the given fingerprint samples, in the same layout as for
+    /// `averages`, that the given `(upper, mid, lower)` thresholds decide differently
+    /// from the pattern, with the symbol instants moved by the given fraction of a
+    /// sample.
+    ///
+    /// The first symbol instant has been shifted off the samples, so it isn't counted.
+    pub fn symbol_errors<const N: usize>(&self, samples: &[f32; N],
+                                         thresholds: (f32, f32, f32), offset: f32)
+        -> usize
+    {
+        let period = self.period::<N>();
+        let (p, m, n) = thresholds;
+        let decider = Decider::new(p, m, n);
+
+        (1..self.symbols).filter(|&sym| {
+            let s = interpolate(samples, sym * period - 1, offset);
+            decider.decide(s).bits() != self.dibit(sym)
+        }).count()
+    }
+}
+
+/// Shape of the fingerprint a `SyncCorrelator` correlates against.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum FingerprintShape {
+    /// Symbol levels held between instants, as in `SYNC_FINGERPRINT`.
+    Rectangular,
+    /// Symbol levels joined by raised cosine pulses, as from `shaped_fingerprint`,
+    /// which better matches a filtered signal and so gives a sharper peak.
+    RaisedCosine,
+}
+
+/// Default factor of the RMS signal power that sync correlation power must exceed.
+pub const SYNC_THRESHOLD: f32 = 0.65;
+
+/// Default number of sync sequences to smooth symbol threshold estimates over.
+pub const SMOOTH_AVG: usize = 4;
 
-/// Continuously cross-correlates input signal with frame sync fingerprint.
-pub struct SyncCorrelator {
+/// Maximum number of sync sequences symbol threshold estimates can be smoothed over.
+pub const MAX_SMOOTH_AVG: usize = 16;
+
+/// Continuously cross-correlates input signal with a frame sync fingerprint of `N`
+/// samples, by default the C4FM fingerprint at 48kHz, in samples of type `T`.
+#[derive(Clone)]
+pub struct SyncCorrelator<const N: usize = FINGERPRINT_SAMPS, T = f32> {
     /// Fingerprint cross-correlator.
-    corr: FIRFilter<SyncFingerprint>,
+    corr: Fir<N, T>,
+    /// Running sum of squares of the correlation history.
+    energy: T,
+    /// Samples until the running sum is recomputed from the history.
+    resum: usize,
+    /// Sum of squares of the fingerprint.
+    taps_energy: T,
 }
 
 impl SyncCorrelator {
-    /// Create a new `SyncCorrelator` with default state.
+    /// Create a new `SyncCorrelator` for the C4FM frame sync with default state.
     pub fn new() -> SyncCorrelator {
+        SyncCorrelator::with_fingerprint(&SYNC_FINGERPRINT)
+    }
+}
+
+impl<const N: usize, T: Sample> SyncCorrelator<N, T> {
+    /// Create a new `SyncCorrelator` for the C4FM frame sync at the sample rate given by
+    /// the fingerprint length `N`, such as `fingerprint_samps(20)` for 96kHz.
+    pub fn for_rate() -> Self {
+        SyncCorrelator::with_f32_fingerprint(&sync_fingerprint::<N>())
+    }
+
+    /// Create a new `SyncCorrelator` for the C4FM frame sync at the sample rate given by
+    /// the fingerprint length `N`, with the given fingerprint shape.
+    ///
+    /// Either shape has the same energy, so the same sync threshold applies.
+    #[cfg(any(feature = "std", test))]
+    pub fn with_shape(shape: FingerprintShape) -> Self {
+        SyncCorrelator::with_f32_fingerprint(&match shape {
+            FingerprintShape::Rectangular => sync_fingerprint::<N>(),
+            FingerprintShape::RaisedCosine => SYNC_PATTERN.shaped_fingerprint::<N>(),
+        })
+    }
+
+    /// Create a new `SyncCorrelator` for the given fingerprint, such as a pulse-shaped or
+    /// TDMA sync waveform, with the same layout as `SYNC_FINGERPRINT`.
+    pub fn with_fingerprint(taps: &[T; N]) -> Self {
         SyncCorrelator {
-            corr: FIRFilter::new(),
+            corr: Fir::new(taps),
+            energy: T::zero(),
+            resum: N,
+            taps_energy: taps.iter().fold(T::zero(), |sum, &t| sum + t * t),
         }
     }
 
+    /// Create a new `SyncCorrelator` for the given fingerprint converted to samples of
+    /// type `T`.
+    fn with_f32_fingerprint(fp: &[f32; N]) -> Self {
+        let mut taps = [T::zero(); N];
+
+        for (t, &f) in taps.iter_mut().zip(fp.iter()) {
+            *t = T::from_f32(f);
+        }
+
+        SyncCorrelator::with_fingerprint(&taps)
+    }
+
     /// Cross-correlate with the given sample and return the current correlation power and
     /// signal power within the correlation history.
-    pub fn feed(&mut self, sample: f32) -> (f32, f32) {
-        (
-            self.corr.feed(sample) / FINGERPRINT_SAMPS as f32,
-            self.sig_power(),
-        )
+    pub fn feed(&mut self, sample: T) -> (T, T) {
+        let old = self.corr.oldest();
+        let corrpow = self.corr.feed(sample) / T::from_usize(N);
+
+        (corrpow, self.sig_power(old, sample))
+    }
+
+    /// Cross-correlate with the given sample and return the normalized correlation,
+    /// from -1 to 1, which doesn't depend on the signal level and so can be compared
+    /// against a fixed threshold.
+    pub fn feed_normalized(&mut self, sample: T) -> T {
+        let (corrpow, sigpower) = self.feed(sample);
+        self.normalize(corrpow, sigpower)
+    }
+
+    /// Normalize the given correlation power by the energy of the fingerprint and of the
+    /// given signal power within the correlation history, giving a value from -1 to 1.
+    pub fn normalize(&self, corrpow: T, sigpower: T) -> T {
+        let denom = (self.taps_energy * sigpower * T::from_usize(N)).sqrt();
+
+        if denom > T::zero() {
+            corrpow * T::from_usize(N) / denom
+        } else {
+            T::zero()
+        }
     }
 
-    fn sig_power(&self) -> f32 {
-        self.corr.history_unordered().fold(0.0, |sum, &x| sum + x.powi(2)) /
-            FINGERPRINT_SAMPS as f32
+    /// Update the running sum of squares for the given sample leaving the history and
+    /// the given one entering it, and return the signal power.
+    ///
+    /// Rounding errors would build up in the running sum over a long run, so it's
+    /// recomputed from scratch once per history length, which keeps the cost constant
+    /// per sample.
+    fn sig_power(&mut self, old: T, new: T) -> T {
+        self.resum -= 1;
+
+        if self.resum == 0 {
+            self.resum = N;
+            self.energy = self.corr.history_unordered()
+                .fold(T::zero(), |sum, &x| sum + x * x);
+        } else {
+            self.energy += new * new - old * old;
+
+            if self.energy < T::zero() {
+                self.energy = T::zero();
+            }
+        }
+
+        self.energy / T::from_usize(N)
     }
 
     /// Retrieve the sequence of samples that make up the current sync sequence.
-    pub fn history(&self) -> [f32; FINGERPRINT_SAMPS] {
+    pub fn history(&self) -> [T; N] {
         // Since the history is stored as a ring buffer, recreate a continuous signal by
         // concatenating the parts on either side of the split.
-        let mut combined = [0.0; FINGERPRINT_SAMPS];
-        self.corr.history().cloned().collect_slice_checked(&mut combined[..]);
+        let mut combined = [T::zero(); N];
+
+        for (dest, &s) in combined.iter_mut().zip(self.corr.history()) {
+            *dest = s;
+        }
 
         combined
     }
 }
 
-/// Computes symbol decision thresholds from sync sequences.
-pub struct SymbolThresholds {
+/// Polarity of a received signal, which depends on the discriminator and on the side of
+/// the carrier it was tuned from.
+///
+/// An inverted sync correlates as strongly as a normal one, but negatively, so the sign
+/// of the correlation peak gives the polarity without a second correlator.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Polarity {
+    /// Outer positive symbols have positive deviation.
+    Normal,
+    /// Outer positive symbols have negative deviation.
+    Inverted,
+}
+
+impl Polarity {
+    /// Polarity of a sync that peaked with the given correlation power.
+    pub fn of(corrpow: f32) -> Polarity {
+        if corrpow < 0.0 { Polarity::Inverted } else { Polarity::Normal }
+    }
+
+    /// Polarity of a signal of this polarity after correcting it for the given one.
+    pub fn combine(self, other: Polarity) -> Polarity {
+        if self == other { Polarity::Normal } else { Polarity::Inverted }
+    }
+
+    /// Correct the given sample of a signal of this polarity.
+    pub fn correct<T: Sample>(self, s: T) -> T {
+        match self {
+            Polarity::Normal => s,
+            Polarity::Inverted => -s,
+        }
+    }
+
+    /// Correct the given sync samples of a signal of this polarity.
+    pub fn correct_sync<T: Sample, const N: usize>(self, sync: &[T; N]) -> [T; N] {
+        let mut out = *sync;
+
+        for s in out.iter_mut() {
+            *s = self.correct(*s);
+        }
+
+        out
+    }
+}
+
+/// Computes symbol decision thresholds from sync sequences of samples of type `T`.
+pub struct SymbolThresholds<T = f32> {
     /// Smooths estimate for positive symbol threshold.
-    psmooth: MovingAverage<f32>,
+    psmooth: Smoother<T>,
     /// Smooths estimate for negative symbol threshold.
-    nsmooth: MovingAverage<f32>,
+    nsmooth: Smoother<T>,
+    /// Most recent smoothed `(positive, negative)` averages, if any sync was seen.
+    avgs: Option<(T, T)>,
 }
 
-impl SymbolThresholds {
+impl<T: Sample> SymbolThresholds<T> {
     /// Create a new `SymbolThresholds` with default state.
-    pub fn new() -> Self {
+    pub fn new() -> Self { SymbolThresholds::with_smoothing(SMOOTH_AVG) }
+
+    /// Create a new `SymbolThresholds` that smooths estimates over the given number of
+    /// sync sequences, from 1 to `MAX_SMOOTH_AVG`.
+    pub fn with_smoothing(syncs: usize) -> Self {
         SymbolThresholds {
-            psmooth: MovingAverage::new(SMOOTH_AVG),
-            nsmooth: MovingAverage::new(SMOOTH_AVG),
+            psmooth: Smoother::new(syncs),
+            nsmooth: Smoother::new(syncs),
+            avgs: None,
+        }
+    }
+
+    /// Create a new `SymbolThresholds` that continues smoothing from the given
+    /// `(positive, negative)` averages.
+    pub fn with_averages(pavg: T, navg: T) -> Self {
+        SymbolThresholds::new().resume(pavg, navg)
+    }
+
+    /// Continue smoothing from the given `(positive, negative)` averages, as if every
+    /// sync in the window had produced them.
+    pub fn resume(mut self, pavg: T, navg: T) -> Self {
+        for _ in 0..self.psmooth.len {
+            self.psmooth.feed(pavg);
+            self.nsmooth.feed(navg);
         }
+
+        self.avgs = Some((pavg, navg));
+        self
     }
 
+    /// Number of sync sequences estimates are smoothed over.
+    pub fn smoothing(&self) -> usize { self.psmooth.len }
+
+    /// Most recent smoothed `(positive, negative)` averages, if any sync was seen.
+    pub fn averages(&self) -> Option<(T, T)> { self.avgs }
+
     /// Calculate `(upper, mid, lower)` thresholds for symbol decoding from the given sync
-    /// fingerprint samples.
+    /// fingerprint samples, at the sample rate given by their length.
     ///
     /// The first sample should be the sample immediately after the first symbol impulse
     /// in the fingerprint, and the last sample should be the sample immediately after the
     /// final symbol impulse.
-    pub fn thresholds(&mut self, sync: &[f32; FINGERPRINT_SAMPS]) -> (f32, f32, f32) {
-        let (pavg, navg) = calc_averages(sync);
+    pub fn thresholds<const N: usize>(&mut self, sync: &[T; N]) -> (T, T, T) {
+        self.thresholds_at(sync, 0.0)
+    }
+
+    /// Calculate `(upper, mid, lower)` thresholds for symbol decoding from the given sync
+    /// fingerprint samples, with the symbol instants moved by the given fraction of a
+    /// sample, from -0.5 to 0.5, as given by `SyncPeak::offset`.
+    pub fn thresholds_at<const N: usize>(&mut self, sync: &[T; N], offset: f32)
+        -> (T, T, T)
+    {
+        let (pavg, navg) = calc_averages(sync, offset);
+
+        // Start smoothing from the first sync rather than from zero, so the first frames
+        // of a transmission aren't decided against thresholds pulled toward the center.
+        if self.avgs.is_none() {
+            for _ in 1..self.psmooth.len {
+                self.psmooth.feed(pavg);
+                self.nsmooth.feed(navg);
+            }
+        }
 
         let pavg = self.psmooth.feed(pavg);
         let navg = self.nsmooth.feed(navg);
+        self.avgs = Some((pavg, navg));
 
         calc_thresholds(pavg, navg)
     }
 }
 
-/// Calculate the average positive (symbol 01) and negative (symbol 11) sample value at
-/// each symbol instant in the given samples.
-fn calc_averages(samples: &[f32; FINGERPRINT_SAMPS]) -> (f32, f32) {
-    /// Indexes of symbol instants for symbol 01.
-    const POS: [usize; 10] = [
-        0, 10, 20, 30, 50, 60, 90, 100, 150, 170,
-    ];
+/// Moving average over the last `len` values.
+#[derive(Copy, Clone, Debug)]
+struct Smoother<T> {
+    /// Last values, as a ring buffer in the first `len` entries.
+    vals: [T; MAX_SMOOTH_AVG],
+    /// Number of values averaged.
+    len: usize,
+    /// Index of the oldest value.
+    pos: usize,
+}
+
+impl<T: Sample> Smoother<T> {
+    /// Create a new `Smoother` over the given number of values, all zero.
+    fn new(len: usize) -> Self {
+        assert!(len > 0 && len <= MAX_SMOOTH_AVG);
+
+        Smoother {
+            vals: [T::zero(); MAX_SMOOTH_AVG],
+            len: len,
+            pos: 0,
+        }
+    }
 
-    /// Indexes of symbol instants for symbol 11.
-    const NEG: [usize; 13] = [
-        40, 70, 80, 110, 120, 130, 140, 160, 180, 190, 200, 210, 220,
-    ];
+    /// Replace the oldest value with the given one and return the new average.
+    fn feed(&mut self, x: T) -> T {
+        self.vals[self.pos] = x;
+        self.pos = (self.pos + 1) % self.len;
 
-    // First fingerprint symbol has been shifted off, so start at the second one.
-    let samples = &samples[9..];
+        (&self.vals[..self.len]).iter().fold(T::zero(), |sum, &v| sum + v) /
+            T::from_usize(self.len)
+    }
+}
+
+/// Calculate `(upper, mid, lower)` thresholds for symbol decoding from the given sync
+/// fingerprint samples alone, without smoothing over previous sync sequences.
+pub fn instant_thresholds<T: Sample, const N: usize>(sync: &[T; N]) -> (T, T, T) {
+    instant_thresholds_at(sync, 0.0)
+}
+
+/// Calculate `(upper, mid, lower)` thresholds for symbol decoding from the given sync
+/// fingerprint samples alone, with the symbol instants moved by the given fraction of a
+/// sample.
+pub fn instant_thresholds_at<T: Sample, const N: usize>(sync: &[T; N], offset: f32)
+    -> (T, T, T)
+{
+    let (pavg, navg) = calc_averages(sync, offset);
+    calc_thresholds(pavg, navg)
+}
+
+/// Estimate the DC offset of the given sync fingerprint samples, as the midpoint between
+/// the outer symbol levels.
+pub fn dc_offset<T: Sample, const N: usize>(sync: &[T; N]) -> T {
+    let (pavg, navg) = calc_averages(sync, 0.0);
+    (pavg + navg) / T::from_f32(2.0)
+}
+
+/// Frequency deviation, in Hz, of the outer symbols that make up the frame sync.
+pub const SYNC_DEVIATION: f32 = 1800.0;
+
+/// Carrier frequency error measured from a frame sync.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct FrequencyError {
+    /// DC offset of the samples at the middle of the sync, as the midpoint between the
+    /// outer symbol levels.
+    pub dc: f32,
+    /// Frequency offset, in Hz, the DC offset amounts to.
+    pub hz: f32,
+    /// Change of the frequency offset, in Hz, from the first symbol instant of the sync
+    /// to the last.
+    pub drift: f32,
+}
+
+/// Estimate the carrier frequency error from the given sync fingerprint samples.
+///
+/// The sync is made up of outer symbols only, so the spread between their levels gives
+/// the scale of `SYNC_DEVIATION`, and no discriminator gain needs to be known. The drift
+/// is the slope of a line fit through the symbol instants, allowing a separate level for
+/// each symbol.
+pub fn frequency_error<const N: usize>(sync: &[f32; N]) -> FrequencyError {
+    let period = fingerprint_period::<N>();
+    let (pavg, navg) = calc_averages(sync, 0.0);
+
+    // Means of the symbol instants of each level.
+    let (mut ptime, mut pcount) = (0.0, 0);
+    let (mut ntime, mut ncount) = (0.0, 0);
+
+    for sym in 1..SYNC_SYMBOLS {
+        if sync_symbol(sym) > 0.0 {
+            ptime += sym as f32;
+            pcount += 1;
+        } else {
+            ntime += sym as f32;
+            ncount += 1;
+        }
+    }
+
+    let (ptime, ntime) = (ptime / pcount as f32, ntime / ncount as f32);
+
+    // Least squares slope, in levels per symbol, pooled over both levels.
+    let (mut cov, mut var) = (0.0, 0.0);
+
+    for sym in 1..SYNC_SYMBOLS {
+        let s = sync[sym * period - 1];
+
+        let (t, avg) = if sync_symbol(sym) > 0.0 {
+            (sym as f32 - ptime, pavg)
+        } else {
+            (sym as f32 - ntime, navg)
+        };
+
+        cov += t * (s - avg);
+        var += t * t;
+    }
+
+    let slope = cov / var;
+
+    // Move each level from the mean of its instants to the middle of the sync, so a
+    // drift doesn't skew the spread between them.
+    let mid = SYNC_SYMBOLS as f32 / 2.0;
+    let pavg = pavg + slope * (mid - ptime);
+    let navg = navg + slope * (mid - ntime);
+
+    let dc = (pavg + navg) / 2.0;
+    let amp = (pavg - navg) / 2.0;
+
+    if amp > 0.0 {
+        FrequencyError {
+            dc: dc,
+            hz: dc / amp * SYNC_DEVIATION,
+            drift: slope * (SYNC_SYMBOLS - 2) as f32 / amp * SYNC_DEVIATION,
+        }
+    } else {
+        FrequencyError { dc: dc, hz: 0.0, drift: 0.0 }
+    }
+}
+
+/// Highest signal to noise ratio, in dB, reported for a frame sync, which a clean
+/// signal would otherwise put at infinity.
+pub const MAX_SNR: f32 = 60.0;
+
+/// Estimate the signal to noise ratio, in dB, of the given sync fingerprint samples,
+/// from the spread of the samples at the symbol instants around the outer symbol levels
+/// implied by the given `(upper, mid, lower)` symbol decision thresholds.
+///
+/// The signal is taken as the squared distance of the outer levels from the middle, and
+/// the noise as the mean squared error at each instant, up to `MAX_SNR`.
+pub fn sync_snr<const N: usize>(sync: &[f32; N], thresholds: (f32, f32, f32)) -> f32 {
+    let period = fingerprint_period::<N>();
+    let (p, m, n) = thresholds;
+
+    // Undo `calc_thresholds`, which puts the outer thresholds two thirds of the way out.
+    let pavg = m + (p - m) * 1.5;
+    let navg = m + (n - m) * 1.5;
+    let amp = (pavg - navg) / 2.0;
+
+    let mut err = 0.0;
+
+    for sym in 1..SYNC_SYMBOLS {
+        let level = if sync_symbol(sym) > 0.0 { pavg } else { navg };
+        let diff = sync[sym * period - 1] - level;
+
+        err += diff * diff;
+    }
+
+    let noise = err / (SYNC_SYMBOLS - 1) as f32;
+    let signal = amp * amp;
+
+    if signal <= 0.0 {
+        -MAX_SNR
+    } else if noise * 1.0e6 <= signal {
+        MAX_SNR
+    } else {
+        10.0 * util::log10(signal / noise)
+    }
+}
 
-    let pavg = POS.iter().fold(0.0, |s, &idx| s + samples[idx]) / POS.len() as f32;
-    let navg = NEG.iter().fold(0.0, |s, &idx| s + samples[idx]) / NEG.len() as f32;
+/// Calculate the average positive (symbol 01) and negative (symbol 11) sample value at
+/// each symbol instant in the given samples, moved by the given fraction of a sample.
+fn calc_averages<T: Sample, const N: usize>(samples: &[T; N], offset: f32) -> (T, T) {
+    SYNC_PATTERN.averages(samples, offset)
+}
 
-    (pavg, navg)
+/// Linearly interpolate the given samples at the given index moved by the given fraction
+/// of a sample, from -1 to 1.
+fn interpolate<T: Sample>(samples: &[T], idx: usize, offset: f32) -> T {
+    if offset > 0.0 {
+        samples[idx] + (samples[idx + 1] - samples[idx]) * T::from_f32(offset)
+    } else if offset < 0.0 {
+        samples[idx] + (samples[idx] - samples[idx - 1]) * T::from_f32(offset)
+    } else {
+        samples[idx]
+    }
 }
 
 /// Calculate the upper, mid, and lower thresholds for symbol decisions from the given
 /// positive and negative sample values.
-fn calc_thresholds(pavg: f32, navg: f32) -> (f32, f32, f32) {
-    let mthresh = (pavg + navg) / 2.0;
-    let pthresh = mthresh + (pavg - mthresh) * (2.0 / 3.0);
-    let nthresh = mthresh + (navg - mthresh) * (2.0 / 3.0);
+fn calc_thresholds<T: Sample>(pavg: T, navg: T) -> (T, T, T) {
+    let scale = T::from_f32(2.0) / T::from_f32(3.0);
+
+    let mthresh = (pavg + navg) / T::from_f32(2.0);
+    let pthresh = mthresh + (pavg - mthresh) * scale;
+    let nthresh = mthresh + (navg - mthresh) * scale;
 
     (pthresh, mthresh, nthresh)
 }
@@ -125,16 +676,113 @@ fn calc_thresholds(pavg: f32, navg: f32) -> (f32, f32, f32) {
 pub fn sync_threshold(sigpower: f32) -> f32 {
     // Empirically-determined power threshold for detecting correlation power with
     // fingerprint, scaled by RMS power of signal under test.
-    sigpower.sqrt() * 0.65
+    scaled_sync_threshold(sigpower, SYNC_THRESHOLD)
+}
+
+/// Compute the sync correlator detection threshold for the given current signal power,
+/// as the given factor of the RMS signal power.
+///
+/// Lower factors catch distorted syncs, such as in simulcast overlap, at the cost of
+/// more false detections on other traffic.
+pub fn scaled_sync_threshold(sigpower: f32, factor: f32) -> f32 {
+    util::sqrt(sigpower) * factor
+}
+
+/// Compute the stricter detection threshold for a frame sync found while a data unit is
+/// still being decoded, for the given current signal power.
+pub fn strong_sync_threshold(sigpower: f32) -> f32 {
+    // A clean sync correlates near the RMS power of the signal, while data symbols rarely
+    // get past this.
+    util::sqrt(sigpower) * 0.8
+}
+
+/// Detects a strong frame sync while a data unit is being decoded, which means the data
+/// unit was cut off and the following one has already begun.
+#[derive(Copy, Clone, Debug)]
+pub struct ResyncDetector {
+    /// Samples remaining before the sync of the current data unit has passed out of the
+    /// correlation history.
+    holdoff: usize,
+    /// Peak detector.
+    detector: SyncDetector,
+}
+
+impl ResyncDetector {
+    /// Create a new `ResyncDetector` for a data unit whose sync was just detected.
+    pub fn new() -> ResyncDetector {
+        ResyncDetector {
+            holdoff: FINGERPRINT_SAMPS,
+            detector: SyncDetector::new(),
+        }
+    }
+
+    /// Consider the given correlation power and signal power. Return `true` if a strong
+    /// sync peaked in the previous sample and `false` otherwise.
+    pub fn detect(&mut self, corrpow: f32, sigpower: f32) -> bool {
+        if self.holdoff > 0 {
+            self.holdoff -= 1;
+            return false;
+        }
+
+        self.detector.detect(corrpow, strong_sync_threshold(sigpower))
+    }
+
+    /// Peak power of the strong sync seen so far, if any.
+    pub fn peak(&self) -> Option<f32> { self.detector.peak() }
+
+    /// Interpolated position of the strong sync just detected, if any.
+    pub fn interpolate(&self) -> Option<SyncPeak> { self.detector.interpolate() }
+}
+
+/// Correlation peak located between samples, at `frac` of the way from the sample `lag`
+/// samples before the one at which it was detected to the following sample.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SyncPeak {
+    /// Number of samples, 1 or 2, before the detection of the sample at or just before
+    /// the peak.
+    pub lag: usize,
+    /// Fraction of a sample, from 0 to 1, of the peak after that sample.
+    pub frac: f32,
+    /// Interpolated peak power.
+    pub power: f32,
+}
+
+impl SyncPeak {
+    /// Offset of the peak from the highest sample, from -0.5 to 0.5, which moves each
+    /// symbol instant of the sync.
+    pub fn offset(&self) -> f32 { self.frac - (self.lag - 1) as f32 }
+}
+
+/// What a `SyncDetector` does when the power stays above the threshold for its maximum
+/// dwell time without peaking.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DwellAction {
+    /// Detect a peak at the highest power seen.
+    Detect,
+    /// Drop the candidate and go back to waiting for the power to cross the threshold.
+    Reset,
 }
 
 /// State machine that detects a peak power above an instantaneous threshold. Once the power goes
 /// above the threshold, further thresholds are ignored and power is tracked until it peaks.
+///
+/// Power that stays flat or keeps climbing, such as with clipped input or a burst of
+/// interference, never peaks, so a maximum dwell time can be set to resolve it.
 #[derive(Copy, Clone, Debug)]
 pub struct SyncDetector {
     /// Previous maximum power above the threshold, `None` if no power above threshold has been
     /// seen.
     prev: Option<f32>,
+    /// Power of the sample before the maximum.
+    before: f32,
+    /// Power of the sample after the maximum, once it's been seen.
+    after: f32,
+    /// Power of the last sample considered.
+    last: f32,
+    /// Number of samples the power has been above the threshold without peaking.
+    dwell: usize,
+    /// Maximum dwell time and what to do when it passes, if limited.
+    max_dwell: Option<(usize, DwellAction)>,
 }
 
 impl SyncDetector {
@@ -142,32 +790,329 @@ impl SyncDetector {
     pub fn new() -> SyncDetector {
         SyncDetector {
             prev: None,
+            before: 0.0,
+            after: 0.0,
+            last: 0.0,
+            dwell: 0,
+            max_dwell: None,
         }
     }
 
+    /// Resolve a candidate that stays above the threshold for the given number of samples
+    /// without peaking with the given action, instead of waiting indefinitely.
+    pub fn with_max_dwell(mut self, samples: usize, action: DwellAction) -> Self {
+        assert!(samples > 0);
+        self.max_dwell = Some((samples, action));
+        self
+    }
+
     /// Consider the given power related to the given instantaneous power threshold. Return `true`
     /// if the power peaked above threshold in the previous sample and `false` otherwise.
     pub fn detect(&mut self, corrpow: f32, thresh: f32) -> bool {
+        let last = self.last;
+        self.last = corrpow;
+        self.after = corrpow;
+
         match self.prev {
             Some(p) => if corrpow < p {
                 return true
             } else {
+                self.before = p;
                 self.prev = Some(corrpow);
+                self.dwell += 1;
             },
             None => if corrpow > thresh {
+                self.before = last;
                 self.prev = Some(corrpow);
             },
         }
 
-        false
+        match self.max_dwell {
+            Some((max, action)) if self.dwell >= max => match action {
+                DwellAction::Detect => true,
+                DwellAction::Reset => {
+                    self.reset();
+                    false
+                },
+            },
+            _ => false,
+        }
+    }
+
+    /// Peak power above the threshold seen so far, if any.
+    pub fn peak(&self) -> Option<f32> { self.prev }
+
+    /// Locate the peak just detected between samples, by fitting a parabola through the
+    /// peak power and the powers on either side, or return `None` if no peak was seen.
+    pub fn interpolate(&self) -> Option<SyncPeak> {
+        let peak = match self.prev {
+            Some(p) => p,
+            None => return None,
+        };
+
+        let (y0, y1, y2) = (self.before, peak, self.after);
+        let denom = y0 - 2.0 * y1 + y2;
+
+        // Power that never fell, such as at the maximum dwell time, has no maximum
+        // between the samples.
+        let offset = if denom < 0.0 && y2 < y1 {
+            let offset = 0.5 * (y0 - y2) / denom;
+
+            if offset < -0.5 { -0.5 } else if offset > 0.5 { 0.5 } else { offset }
+        } else {
+            0.0
+        };
+
+        let power = y1 - 0.25 * (y0 - y2) * offset;
+
+        Some(if offset < 0.0 {
+            SyncPeak { lag: 2, frac: 1.0 + offset, power: power }
+        } else {
+            SyncPeak { lag: 1, frac: offset, power: power }
+        })
     }
+
+    /// Drop any candidate peak and go back to waiting for the power to cross the
+    /// threshold, such as when the candidate turned out to be bogus.
+    pub fn reset(&mut self) {
+        self.prev = None;
+        self.dwell = 0;
+    }
+}
+
+/// Frame sync found by a `SyncScanner`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SyncPosition {
+    /// Index, within the buffer given to `SyncScanner::feed_slice`, of the sample after
+    /// the correlation peak, when the whole sync is in the correlation history.
+    pub index: usize,
+    /// Peak correlation power.
+    pub power: f32,
+    /// Signal power within the correlation history at the sample after the peak.
+    pub sigpower: f32,
+    /// Polarity of the sync, which the scanner corrects the following samples for.
+    pub polarity: Polarity,
+    /// Detection threshold when the power first crossed it.
+    pub threshold: f32,
+    /// Peak located between samples, relative to `index`.
+    pub peak: SyncPeak,
+}
+
+/// Searches a stream of correlation and signal powers for frame sync peaks, with the
+/// `sync_threshold` detection threshold or another factor of the signal power.
+///
+/// This is the detection stage shared by `SyncScanner` and block correlators, which
+/// produce the powers in different ways.
+#[derive(Clone)]
+pub struct SyncSearch {
+    /// Peak detector for the current sync, reset after each one.
+    detector: SyncDetector,
+    /// Factor of the RMS signal power for the detection threshold.
+    factor: f32,
+    /// Threshold when the power of the current peak first crossed it.
+    thresh: f32,
+    /// Samples to skip after each sync.
+    span: usize,
+    /// Samples remaining before the last sync has passed out of the correlation
+    /// history.
+    holdoff: usize,
+    /// Polarity found at the last sync, which is corrected for until the next.
+    polarity: Polarity,
 }
 
-// Fingerprint of 24-symbol frame sync pulse waveform.
-//
-// The first sample represents the impulse instant of the first symbol, and the last
-// sample represents the impulse instant of the final symbol.
-impl_fir!(SyncFingerprint, f32, FINGERPRINT_SAMPS, [
+impl SyncSearch {
+    /// Create a new `SyncSearch` for a fingerprint of the given number of samples, which
+    /// are skipped after each sync so its own samples can't start another.
+    pub fn new(span: usize) -> SyncSearch {
+        SyncSearch {
+            detector: SyncDetector::new(),
+            factor: SYNC_THRESHOLD,
+            thresh: 0.0,
+            span: span,
+            holdoff: 0,
+            polarity: Polarity::Normal,
+        }
+    }
+
+    /// Change the detection threshold factor.
+    pub fn set_threshold(&mut self, factor: f32) {
+        assert!(factor > 0.0);
+        self.factor = factor;
+    }
+
+    /// Detection threshold factor.
+    pub fn threshold(&self) -> f32 { self.factor }
+
+    /// Polarity found at the last sync, which the powers fed in should be corrected
+    /// for.
+    pub fn polarity(&self) -> Polarity { self.polarity }
+
+    /// Consider the given correlation power, corrected for `polarity`, and signal power
+    /// of the sample at the given index within its buffer, returning the sync that
+    /// peaked in the previous sample, if any.
+    pub fn feed(&mut self, power: f32, sigpower: f32, idx: usize) -> Option<SyncPosition> {
+        if self.holdoff > 0 {
+            self.holdoff -= 1;
+            return None;
+        }
+
+        let thresh = scaled_sync_threshold(sigpower, self.factor);
+
+        if self.detector.peak().is_none() {
+            self.thresh = thresh;
+        }
+
+        if !self.detector.detect(bipolar_power(power, sigpower), thresh) {
+            return None;
+        }
+
+        self.polarity = self.polarity.combine(Polarity::of(power));
+
+        let pos = SyncPosition {
+            index: idx,
+            power: self.detector.peak().unwrap(),
+            sigpower: sigpower,
+            polarity: self.polarity,
+            threshold: self.thresh,
+            peak: self.detector.interpolate().unwrap(),
+        };
+
+        self.detector = SyncDetector::new();
+        self.holdoff = self.span;
+
+        Some(pos)
+    }
+}
+
+/// Searches buffers of samples for frame syncs, running a `SyncCorrelator` and
+/// `SyncDetector` with the `sync_threshold` detection threshold, or another factor of
+/// the signal power.
+///
+/// State is carried from one buffer to the next, so a sync split across two buffers is
+/// still found, in the buffer that holds the sample after its peak.
+#[derive(Clone)]
+pub struct SyncScanner<const N: usize = FINGERPRINT_SAMPS> {
+    /// Fingerprint cross-correlator.
+    corr: SyncCorrelator<N>,
+    /// Peak search over the correlator output.
+    search: SyncSearch,
+}
+
+impl SyncScanner {
+    /// Create a new `SyncScanner` for the C4FM frame sync.
+    pub fn new() -> SyncScanner { SyncScanner::with_correlator(SyncCorrelator::new()) }
+}
+
+impl<const N: usize> SyncScanner<N> {
+    /// Create a new `SyncScanner` around the given correlator.
+    pub fn with_correlator(corr: SyncCorrelator<N>) -> Self {
+        SyncScanner {
+            corr: corr,
+            search: SyncSearch::new(N),
+        }
+    }
+
+    /// Detect syncs above the given factor of the RMS signal power, `SYNC_THRESHOLD` by
+    /// default.
+    pub fn with_threshold(mut self, factor: f32) -> Self {
+        self.set_threshold(factor);
+        self
+    }
+
+    /// Change the detection threshold factor, keeping the correlation history.
+    pub fn set_threshold(&mut self, factor: f32) { self.search.set_threshold(factor); }
+
+    /// Detection threshold factor.
+    pub fn threshold(&self) -> f32 { self.search.threshold() }
+
+    /// Polarity found at the last sync.
+    pub fn polarity(&self) -> Polarity { self.search.polarity() }
+
+    /// Underlying correlator, whose history holds the last `N` samples fed in, corrected
+    /// for the polarity found at the last sync.
+    pub fn correlator(&self) -> &SyncCorrelator<N> { &self.corr }
+
+    /// Search the given buffer, returning an iterator over the syncs found in it.
+    ///
+    /// Samples are fed in as the iterator advances, and any left when it's dropped are
+    /// fed in then, so the scanner is always caught up with the end of the buffer.
+    pub fn feed_slice<'a>(&'a mut self, samples: &'a [f32]) -> SyncPositions<'a, N> {
+        SyncPositions {
+            scanner: self,
+            samples: samples,
+            idx: 0,
+        }
+    }
+
+    /// Search the given buffer, passing each sync found in it to the given callback.
+    pub fn feed_slice_with<F: FnMut(SyncPosition)>(&mut self, samples: &[f32], mut cb: F) {
+        for pos in self.feed_slice(samples) {
+            cb(pos);
+        }
+    }
+
+    /// Feed in the given sample at the given index within its buffer, returning the sync
+    /// that peaked in the previous sample, if any.
+    fn feed(&mut self, sample: f32, idx: usize) -> Option<SyncPosition> {
+        let (power, sigpower) = self.corr.feed(self.search.polarity().correct(sample));
+        self.search.feed(power, sigpower, idx)
+    }
+}
+
+/// Correlation power of a sync of either polarity, given the current signal power,
+/// for detection against the usual threshold.
+///
+/// Data symbols can correlate negatively with the fingerprint almost as well as a sync,
+/// and a change of polarity is rare, so a sync of the opposite polarity must also pass
+/// the strong threshold.
+pub fn bipolar_power(corrpow: f32, sigpower: f32) -> f32 {
+    if corrpow >= 0.0 {
+        corrpow
+    } else if -corrpow > strong_sync_threshold(sigpower) {
+        -corrpow
+    } else {
+        0.0
+    }
+}
+
+/// Iterator over the frame syncs in a buffer, created by `SyncScanner::feed_slice`.
+pub struct SyncPositions<'a, const N: usize> {
+    /// Scanner the samples are fed to.
+    scanner: &'a mut SyncScanner<N>,
+    /// Buffer being searched.
+    samples: &'a [f32],
+    /// Index of the next sample to feed in.
+    idx: usize,
+}
+
+impl<'a, const N: usize> Iterator for SyncPositions<'a, N> {
+    type Item = SyncPosition;
+
+    fn next(&mut self) -> Option<SyncPosition> {
+        while self.idx < self.samples.len() {
+            let idx = self.idx;
+            self.idx += 1;
+
+            if let Some(pos) = self.scanner.feed(self.samples[idx], idx) {
+                return Some(pos);
+            }
+        }
+
+        None
+    }
+}
+
+impl<'a, const N: usize> Drop for SyncPositions<'a, N> {
+    fn drop(&mut self) {
+        while self.next().is_some() {}
+    }
+}
+
+/// Fingerprint of 24-symbol frame sync pulse waveform.
+///
+/// The first sample represents the impulse instant of the first symbol, and the last
+/// sample represents the impulse instant of the final symbol.
+pub const SYNC_FINGERPRINT: [f32; FINGERPRINT_SAMPS] = [
     1.0,
 
     1.0,
@@ -445,7 +1390,7 @@ impl_fir!(SyncFingerprint, f32, FINGERPRINT_SAMPS, [
     -1.0,
 
     -1.0,
-]);
+];
 
 /// Symbols that make up the frame sync fingerprint.
 pub const SYNC_GENERATOR: &'static [u8] = &[
@@ -459,12 +1404,21 @@ pub const SYNC_GENERATOR: &'static [u8] = &[
 
 #[cfg(test)]
 mod test {
-    use super::{SyncFingerprint, calc_averages, calc_thresholds, SyncDetector};
-    use static_fir::FIRFilter;
+    use super::{SYNC_FINGERPRINT, calc_averages, calc_thresholds, SyncDetector,
+                ResyncDetector, SyncCorrelator, FINGERPRINT_SAMPS, fingerprint_samps,
+                sync_fingerprint, instant_thresholds, SyncScanner, SyncPosition,
+                DwellAction, Polarity, SyncPeak, instant_thresholds_at, frequency_error,
+                FrequencyError, sync_snr, MAX_SNR, SymbolThresholds, FingerprintShape,
+                SYNC_PATTERN};
+    use consts::{SYMBOL_PERIOD, SYNC_SYMBOLS};
+    use message::nid::{DataUnit, NetworkAccessCode};
+    use std;
+    use testutil;
+    use baseband::fir::Fir;
 
     #[test]
     fn test_calc_averages() {
-        let (pavg, navg) = calc_averages(&[
+        let (pavg, navg) = calc_averages::<f32, _>(&[
                  42.0, 42.0, 42.0, 42.0, 42.0, 42.0, 42.0, 42.0, 42.0,
             1.0, 42.0, 42.0, 42.0, 42.0, 42.0, 42.0, 42.0, 42.0, 42.0,
             1.0, 42.0, 42.0, 42.0, 42.0, 42.0, 42.0, 42.0, 42.0, 42.0,
@@ -489,7 +1443,7 @@ mod test {
             -1.0, 42.0, 42.0, 42.0, 42.0, 42.0, 42.0, 42.0, 42.0, 42.0,
             -1.0, 42.0, 42.0, 42.0, 42.0, 42.0, 42.0, 42.0, 42.0, 42.0,
             -1.0, 42.0,
-        ]);
+        ], 0.0);
 
         assert!((pavg - 1.0).abs() < 0.000000001);
         assert!((navg - -1.0).abs() < 0.000000001);
@@ -498,30 +1452,58 @@ mod test {
     #[test]
     fn test_calc_thresholds() {
         // Ideal
-        let (p, m, n) = calc_thresholds(0.18, -0.18);
+        let (p, m, n) = calc_thresholds(0.18f32, -0.18);
         assert!((p - 0.12).abs() < 0.000001);
         assert!((m - 0.0).abs() < 0.000001);
         assert!((n - -0.12).abs() < 0.000001);
 
         // Scaling
-        let (p, m, n) = calc_thresholds(0.072, -0.072);
+        let (p, m, n) = calc_thresholds(0.072f32, -0.072);
         assert!((p - 0.048).abs() < 0.000001);
         assert!((m - 0.0).abs() < 0.000001);
         assert!((n - -0.048).abs() < 0.000001);
 
         // DC bias
-        let (p, m, n) = calc_thresholds(0.15, -0.21);
+        let (p, m, n) = calc_thresholds(0.15f32, -0.21);
         assert!((p - 0.09).abs() < 0.000001);
         assert!((m - -0.03).abs() < 0.000001);
         assert!((n - -0.15).abs() < 0.000001);
 
         // Scaling and DC bias
-        let (p, m, n) = calc_thresholds(0.042, -0.102);
+        let (p, m, n) = calc_thresholds(0.042f32, -0.102);
         assert!((p - 0.018).abs() < 0.000001);
         assert!((m - -0.030).abs() < 0.000001);
         assert!((n - -0.078).abs() < 0.000001);
     }
 
+    #[test]
+    fn test_smoothing() {
+        let mut t = SymbolThresholds::<f32>::new();
+
+        // The first sync is taken as is.
+        let (p, m, n) = t.thresholds(&[0.09; FINGERPRINT_SAMPS]);
+        assert!((p - 0.09).abs() < 0.000001);
+        assert!((m - 0.09).abs() < 0.000001);
+        assert!((n - 0.09).abs() < 0.000001);
+
+        // Later syncs are averaged with it.
+        let (_, m, _) = t.thresholds(&[0.17; FINGERPRINT_SAMPS]);
+        assert!((m - 0.11).abs() < 0.000001);
+
+        // A shorter window follows new syncs faster.
+        let mut t = SymbolThresholds::<f32>::with_smoothing(2);
+        assert_eq!(t.smoothing(), 2);
+        t.thresholds(&[0.09; FINGERPRINT_SAMPS]);
+        let (_, m, _) = t.thresholds(&[0.17; FINGERPRINT_SAMPS]);
+        assert!((m - 0.13).abs() < 0.000001);
+
+        // Resuming fills the whole window.
+        let mut t = SymbolThresholds::<f32>::with_smoothing(8).resume(0.09, 0.09);
+        assert_eq!(t.averages(), Some((0.09, 0.09)));
+        let (_, m, _) = t.thresholds(&[0.17; FINGERPRINT_SAMPS]);
+        assert!((m - 0.10).abs() < 0.000001);
+    }
+
     #[test]
     fn test_detector() {
         {
@@ -551,6 +1533,110 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_inverted() {
+        let fp = sync_fingerprint::<FINGERPRINT_SAMPS>();
+
+        let mut samples = vec![0.0; 500];
+        samples.extend(fp.iter().map(|&s| s * -0.18));
+        samples.extend(vec![0.18; 500]);
+
+        let mut scanner = SyncScanner::new();
+        let found: Vec<SyncPosition> = scanner.feed_slice(&samples).collect();
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].index, 500 + FINGERPRINT_SAMPS);
+        assert_eq!(found[0].polarity, Polarity::Inverted);
+        assert!(found[0].power > 0.0);
+
+        // Thresholds come out the same as for the normal sync once corrected.
+        let mut corr = SyncCorrelator::new();
+
+        for &s in &samples[..found[0].index + 1] {
+            corr.feed(s);
+        }
+
+        let sync = found[0].polarity.correct_sync(&corr.history());
+        let (p, m, n) = instant_thresholds(&sync);
+        assert_eq!(scanner.polarity(), Polarity::Inverted);
+
+        assert!((p - 0.12).abs() < 1.0e-6);
+        assert!(m.abs() < 1.0e-6);
+        assert!((n - -0.12).abs() < 1.0e-6);
+
+        assert_eq!(Polarity::Inverted.combine(Polarity::Inverted), Polarity::Normal);
+        assert_eq!(Polarity::Normal.combine(Polarity::Inverted), Polarity::Inverted);
+    }
+
+    #[test]
+    fn test_max_dwell() {
+        // Flat power never peaks without a maximum dwell.
+        let mut d = SyncDetector::new();
+
+        for _ in 0..1000 {
+            assert!(!d.detect(0.5, 0.42));
+        }
+
+        let mut d = SyncDetector::new().with_max_dwell(10, DwellAction::Detect);
+        assert!(!d.detect(0.3, 0.42));
+
+        for _ in 0..10 {
+            assert!(!d.detect(0.5, 0.42));
+        }
+
+        assert!(d.detect(0.5, 0.42));
+        assert_eq!(d.peak(), Some(0.5));
+
+        // Climbing power is detected at the highest point seen.
+        let mut d = SyncDetector::new().with_max_dwell(10, DwellAction::Detect);
+
+        for i in 0..10 {
+            assert!(!d.detect(0.5 + i as f32 * 0.01, 0.42));
+        }
+
+        assert!(d.detect(0.6, 0.42));
+        assert_eq!(d.peak(), Some(0.6));
+
+        // Or dropped, after which a real peak is still found.
+        let mut d = SyncDetector::new().with_max_dwell(10, DwellAction::Reset);
+
+        for i in 0..10 {
+            assert!(!d.detect(0.5 + i as f32 * 0.01, 0.42));
+        }
+
+        assert!(!d.detect(0.6, 0.42));
+        assert_eq!(d.peak(), None);
+
+        assert!(!d.detect(0.7, 0.42));
+        assert!(!d.detect(0.8, 0.42));
+        assert!(d.detect(0.75, 0.42));
+        assert_eq!(d.peak(), Some(0.8));
+
+        // Dwell time only counts samples since the candidate was taken.
+        let mut d = SyncDetector::new().with_max_dwell(3, DwellAction::Reset);
+        assert!(!d.detect(0.5, 0.42));
+        d.reset();
+        assert_eq!(d.peak(), None);
+        assert!(!d.detect(0.5, 0.42));
+        assert!(!d.detect(0.6, 0.42));
+        assert!(d.detect(0.55, 0.42));
+    }
+
+    #[test]
+    fn test_resync_detector() {
+        let mut d = ResyncDetector::new();
+
+        // The sync that started the data unit is still in the history.
+        for _ in 0..FINGERPRINT_SAMPS {
+            assert!(!d.detect(0.9, 1.0));
+        }
+
+        assert!(!d.detect(0.7, 1.0));
+        assert!(!d.detect(0.85, 1.0));
+        assert!(!d.detect(0.9, 1.0));
+        assert!(d.detect(0.88, 1.0));
+    }
+
     #[test]
     fn test_corr_impulses() {
         let samps = [
@@ -603,7 +1689,7 @@ mod test {
             -1.0,
         ];
 
-        let mut corr = FIRFilter::<SyncFingerprint>::new();
+        let mut corr = Fir::new(&SYNC_FINGERPRINT);
 
         let val = samps.iter().fold(0.0, |_, &s| {
             corr.feed(s)
@@ -612,6 +1698,315 @@ mod test {
         assert!((val - 24.0).abs() < 1.0e-12);
     }
 
+    #[test]
+    fn test_corr_fingerprint() {
+        let mut corr = SyncCorrelator::with_fingerprint(&[1.0, -1.0, 1.0, -1.0]);
+
+        assert_eq!(corr.feed(2.0), (-0.5, 1.0));
+        assert_eq!(corr.feed(-2.0), (1.0, 2.0));
+        assert_eq!(corr.history(), [0.0, 0.0, 2.0, -2.0]);
+
+        let mut corr = SyncCorrelator::new();
+        assert_eq!(corr.feed(1.0), (-1.0 / FINGERPRINT_SAMPS as f32,
+                                    1.0 / FINGERPRINT_SAMPS as f32));
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        assert_eq!(FINGERPRINT_SAMPS, 231);
+        assert_eq!(&sync_fingerprint::<FINGERPRINT_SAMPS>()[..], &SYNC_FINGERPRINT[..]);
+
+        let fp = sync_fingerprint::<{ fingerprint_samps(5) }>();
+        assert_eq!(fp.len(), 116);
+        assert_eq!(&fp[20..31], &[1.0, 1.0, 1.0, -1.0, -1.0, -1.0, -1.0, -1.0, 1.0, 1.0,
+                                  1.0]);
+    }
+
+    #[test]
+    fn test_symbol_errors() {
+        // Sync samples as held after the peak, shifted by one.
+        let mut sync = [0.0; FINGERPRINT_SAMPS];
+        sync[..FINGERPRINT_SAMPS - 1].copy_from_slice(&SYNC_FINGERPRINT[1..]);
+        sync[FINGERPRINT_SAMPS - 1] = -1.0;
+
+        let thresholds = instant_thresholds(&sync);
+        assert_eq!(SYNC_PATTERN.symbol_errors(&sync, thresholds, 0.0), 0);
+        assert_eq!(SYNC_PATTERN.dibit(0), 0b01);
+        assert_eq!(SYNC_PATTERN.dibit(23), 0b11);
+
+        // Symbols pulled to the inner levels or flipped.
+        sync[SYMBOL_PERIOD - 1] = 0.2;
+        sync[5 * SYMBOL_PERIOD - 1] *= -1.0;
+        sync[23 * SYMBOL_PERIOD - 1] *= -1.0;
+        assert_eq!(SYNC_PATTERN.symbol_errors(&sync, thresholds, 0.0), 3);
+
+        // The first symbol isn't in the samples.
+        let mut sync = [0.0; FINGERPRINT_SAMPS];
+        sync[..FINGERPRINT_SAMPS - 1].copy_from_slice(&SYNC_FINGERPRINT[1..]);
+        sync[FINGERPRINT_SAMPS - 1] = -1.0;
+        sync[0] = -1.0;
+        assert_eq!(SYNC_PATTERN.symbol_errors(&sync, thresholds, 0.0), 0);
+    }
+
+    /// Correlate an ideal fingerprint of `N` samples at the given amplitude and check
+    /// the correlation peak and the thresholds from the sync samples.
+    fn check_rate<const N: usize>() {
+        let fp = sync_fingerprint::<N>();
+        let mut corr = SyncCorrelator::<N>::for_rate();
+
+        let out: Vec<(f32, f32)> = fp.iter().map(|&s| corr.feed(s * 0.18)).collect();
+        let (peak, power) = out[N - 1];
+
+        assert!(out[..N - 1].iter().all(|&(c, _)| c < peak));
+        assert!((peak - power / 0.18).abs() < 1.0e-5);
+
+        // Thresholds are taken from the sample after each symbol instant.
+        corr.feed(-0.18);
+        let (p, m, n) = instant_thresholds(&corr.history());
+
+        assert!((p - 0.12).abs() < 1.0e-6);
+        assert!(m.abs() < 1.0e-6);
+        assert!((n - -0.12).abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn test_rates() {
+        check_rate::<{ fingerprint_samps(5) }>();
+        check_rate::<{ fingerprint_samps(10) }>();
+        check_rate::<{ fingerprint_samps(20) }>();
+    }
+
+    #[test]
+    fn test_scanner() {
+        let mut dibits = testutil::frame(NetworkAccessCode::Default,
+                                         DataUnit::VoiceSimpleTerminator, vec![]);
+        dibits.extend(dibits.clone());
+
+        let mut samples = testutil::silence(0.01);
+        samples.extend(testutil::modulate(&dibits));
+        samples.extend(testutil::silence(0.01));
+
+        let whole: Vec<SyncPosition> = SyncScanner::new().feed_slice(&samples).collect();
+        assert_eq!(whole.len(), 2);
+        assert!(whole[0].power > whole[0].threshold);
+        assert!(whole[0].threshold > whole[0].sigpower.sqrt() * 0.6);
+
+        assert_eq!(SyncScanner::new().with_threshold(2.0).feed_slice(&samples).count(), 0);
+
+        // Syncs are found at the same positions when split across buffers, including
+        // one that straddles two.
+        for &size in [100, 1000, 8192].iter() {
+            let mut scanner = SyncScanner::new();
+            let mut found = vec![];
+
+            for (n, chunk) in samples.chunks(size).enumerate() {
+                scanner.feed_slice_with(chunk, |mut pos| {
+                    pos.index += n * size;
+                    found.push(pos);
+                });
+            }
+
+            assert_eq!(found, whole);
+        }
+
+        // Dropping the iterator after the first sync still feeds in the whole buffer.
+        let mut scanner = SyncScanner::new();
+        let first = scanner.feed_slice(&samples[..whole[1].index - 1]).next();
+        assert_eq!(first, Some(whole[0]));
+
+        let rest: Vec<SyncPosition> =
+            scanner.feed_slice(&samples[whole[1].index - 1..]).collect();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].index, 1);
+    }
+
+    #[test]
+    fn test_peak() {
+        let mut d = SyncDetector::new();
+        assert_eq!(d.interpolate(), None);
+        assert!(!d.detect(1.0, 2.0));
+        assert!(!d.detect(4.0, 2.0));
+        assert!(!d.detect(5.0, 2.0));
+        assert!(d.detect(3.0, 2.0));
+
+        // Closer to the sample before than after.
+        let peak = d.interpolate().unwrap();
+        assert_eq!(peak.lag, 2);
+        assert!((peak.frac - 5.0 / 6.0).abs() < 1.0e-6);
+        assert!((peak.offset() - -1.0 / 6.0).abs() < 1.0e-6);
+        assert!((peak.power - (5.0 + 1.0 / 24.0)).abs() < 1.0e-6);
+
+        let mut d = SyncDetector::new();
+        assert!(!d.detect(4.0, 2.0));
+        assert!(!d.detect(5.0, 2.0));
+        assert!(d.detect(4.0, 2.0));
+        assert_eq!(d.interpolate(), Some(SyncPeak { lag: 1, frac: 0.0, power: 5.0 }));
+
+        // Power that never falls is located at the highest sample.
+        let mut d = SyncDetector::new().with_max_dwell(2, DwellAction::Detect);
+        assert!(!d.detect(3.0, 2.0));
+        assert!(!d.detect(4.0, 2.0));
+        assert!(d.detect(5.0, 2.0));
+        assert_eq!(d.interpolate().unwrap().offset(), 0.0);
+
+        let mut samples = testutil::silence(0.01);
+        samples.extend(testutil::modulate(&testutil::frame(
+            NetworkAccessCode::Default, DataUnit::VoiceSimpleTerminator, vec![])));
+        samples.extend(testutil::silence(0.01));
+
+        // Move the waveform half a sample earlier.
+        let shifted: Vec<f32> = samples.windows(2).map(|w| (w[0] + w[1]) / 2.0).collect();
+
+        let locate = |samples: &[f32]| {
+            let pos: Vec<SyncPosition> = SyncScanner::new().feed_slice(samples).collect();
+            assert_eq!(pos.len(), 1);
+            assert!(pos[0].peak.power >= pos[0].power);
+            pos[0].index as f32 - pos[0].peak.lag as f32 + pos[0].peak.frac
+        };
+
+        let diff = locate(&samples) - locate(&shifted);
+        assert!((diff - 0.5).abs() < 0.1, "{}", diff);
+    }
+
+    #[test]
+    fn test_frequency_error() {
+        let fp = sync_fingerprint::<FINGERPRINT_SAMPS>();
+        assert_eq!(frequency_error(&fp), FrequencyError { dc: 0.0, hz: 0.0, drift: 0.0 });
+
+        // Outer symbols at ±0.18 with a constant offset of a sixth of the deviation.
+        let mut shifted = [0.0; FINGERPRINT_SAMPS];
+
+        for (s, &f) in shifted.iter_mut().zip(fp.iter()) {
+            *s = f * 0.18 + 0.03;
+        }
+
+        let err = frequency_error(&shifted);
+        assert!((err.dc - 0.03).abs() < 1.0e-6);
+        assert!((err.hz - 300.0).abs() < 1.0e-3);
+        assert!(err.drift.abs() < 1.0e-3);
+
+        // A ramp from the first symbol instant to the last.
+        let mut ramp = [0.0; FINGERPRINT_SAMPS];
+        let (first, last) = (SYMBOL_PERIOD - 1, (SYNC_SYMBOLS - 1) * SYMBOL_PERIOD - 1);
+
+        for (n, (s, &f)) in ramp.iter_mut().zip(fp.iter()).enumerate() {
+            *s = f * 0.18 + (n as f32 - first as f32) / (last - first) as f32 * 0.01;
+        }
+
+        // Halfway up the ramp at the middle of the sync.
+        let err = frequency_error(&ramp);
+        assert!((err.drift - 100.0).abs() < 1.0e-2, "{}", err.drift);
+        assert!((err.hz - 50.0).abs() < 1.0e-2, "{}", err.hz);
+
+        assert_eq!(frequency_error(&[0.0; FINGERPRINT_SAMPS]).hz, 0.0);
+    }
+
+    #[test]
+    fn test_sync_snr() {
+        let fp = sync_fingerprint::<FINGERPRINT_SAMPS>();
+        let thresh = calc_thresholds(0.18, -0.18);
+
+        let mut clean = [0.0; FINGERPRINT_SAMPS];
+
+        for (s, &f) in clean.iter_mut().zip(fp.iter()) {
+            *s = f * 0.18;
+        }
+
+        assert_eq!(sync_snr(&clean, thresh), MAX_SNR);
+        assert_eq!(sync_snr(&clean, (0.0, 0.0, 0.0)), -MAX_SNR);
+
+        let mut rng = testutil::Rng::new(510);
+
+        for &truth in [0.0, 10.0, 20.0, 30.0].iter() {
+            // Noise deviation for the given SNR.
+            let sigma = 0.18 / 10.0f32.powf(truth / 20.0);
+
+            // A single sync only has 23 symbol instants, so average over many.
+            let trials = 500;
+            let mut sum = 0.0;
+
+            for _ in 0..trials {
+                let mut noisy = clean;
+
+                for s in noisy.iter_mut() {
+                    *s += rng.gaussian(sigma);
+                }
+
+                sum += sync_snr(&noisy, thresh);
+            }
+
+            let snr = sum / trials as f32;
+            assert!((snr - truth).abs() < 1.0, "{} {}", snr, truth);
+        }
+    }
+
+    #[test]
+    fn test_thresholds_at() {
+        let fp = sync_fingerprint::<FINGERPRINT_SAMPS>();
+        assert_eq!(instant_thresholds_at(&fp, 0.0), instant_thresholds(&fp));
+
+        // Halfway between the symbol instant and a neighbouring sample of the same
+        // symbol gives the same thresholds.
+        let (p, m, n) = instant_thresholds_at(&fp, -0.5);
+        let (ep, em, en) = instant_thresholds(&fp);
+        assert!((p - ep).abs() < 1.0e-6);
+        assert!((m - em).abs() < 1.0e-6);
+        assert!((n - en).abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn test_normalized() {
+        let fp = sync_fingerprint::<FINGERPRINT_SAMPS>();
+
+        // Feed the fingerprint after some noise at the given amplitude and return the
+        // peak normalized correlation.
+        let peak = |amp: f32| {
+            let mut rng = testutil::Rng::new(504);
+            let mut corr = SyncCorrelator::new();
+
+            for _ in 0..500 {
+                corr.feed_normalized((rng.next() as f32 / std::u32::MAX as f32 - 0.5) * amp);
+            }
+
+            fp.iter().fold(-1.0, |max: f32, &s| max.max(corr.feed_normalized(s * amp)))
+        };
+
+        let weak = peak(0.1);
+        let strong = peak(10.0);
+
+        assert!((weak - 1.0).abs() < 1.0e-4);
+        assert!((weak - strong).abs() < 1.0e-4);
+
+        let mut corr = SyncCorrelator::new();
+        assert_eq!(corr.feed_normalized(0.0), 0.0);
+        // A single impulse lines up with the last fingerprint sample.
+        let energy = fp.iter().fold(0.0, |sum, &t| sum + t * t);
+        assert!((corr.feed_normalized(1.0) - -1.0 / energy.sqrt()).abs() < 1.0e-6);
+    }
+
+    #[test]
+    fn test_sig_power() {
+        let mut rng = testutil::Rng::new(503);
+        let mut corr = SyncCorrelator::new();
+
+        for n in 0..3_000_000 {
+            // Bursts of strong signal between stretches of weak noise, which would leave
+            // the largest rounding errors behind in a plain running sum.
+            let amp = if n / 50_000 % 2 == 0 { 100.0 } else { 0.001 };
+            let s = (rng.next() as f32 / std::u32::MAX as f32 - 0.5) * amp;
+
+            let (_, power) = corr.feed(s);
+
+            if n % 9973 == 0 {
+                let exact = corr.history().iter().fold(0.0, |sum, &x| sum + x * x) /
+                    FINGERPRINT_SAMPS as f32;
+
+                assert!((power - exact).abs() <= exact * 1.0e-4 + 1.0e-9,
+                        "{} {} {}", n, power, exact);
+            }
+        }
+    }
+
     #[test]
     fn test_corr_self() {
         // Verify result of correlating fingerprint with pulse-shaped version. Result
@@ -850,12 +2245,96 @@ mod test {
             -0.1800000071525574,
         ];
 
-        let mut corr = FIRFilter::<SyncFingerprint>::new();
+        let mut corr = Fir::new(&SYNC_FINGERPRINT);
 
         let val = samps.iter().fold(0.0, |_, &s| {
             corr.feed(s)
         });
 
         assert!((val - 37.710987).abs() < 0.00001);
+
+        // Double precision agrees to single precision.
+        let mut taps = [0.0f64; FINGERPRINT_SAMPS];
+
+        for (t, &f) in taps.iter_mut().zip(SYNC_FINGERPRINT.iter()) {
+            *t = f as f64;
+        }
+
+        let mut wide = Fir::new(&taps);
+
+        let wval = samps.iter().fold(0.0, |_, &s| {
+            wide.feed(s as f64)
+        });
+
+        assert!((wval - val as f64).abs() <= wval * std::f32::EPSILON as f64,
+                "{} {}", wval, val);
+
+        // Both shapes peak when aligned, but the shaped fingerprint matches the pulse
+        // shaped sync almost exactly and falls off more sharply either side, with the
+        // ends held to fill in the offset samples.
+        let peak = |shape| {
+            let mut corr = SyncCorrelator::<FINGERPRINT_SAMPS>::with_shape(shape);
+
+            let mut padded = vec![samps[0]];
+            padded.extend(samps.iter().cloned());
+            padded.push(samps[FINGERPRINT_SAMPS - 1]);
+
+            let out: Vec<f32> = padded.iter().map(|&s| corr.feed_normalized(s)).collect();
+            let (before, at, after) = (out[FINGERPRINT_SAMPS - 1], out[FINGERPRINT_SAMPS],
+                                       out[FINGERPRINT_SAMPS + 1]);
+
+            assert!(at > before && at > after);
+            (at, at - before.max(after))
+        };
+
+        let (rect, rect_fall) = peak(FingerprintShape::Rectangular);
+        let (shaped, shaped_fall) = peak(FingerprintShape::RaisedCosine);
+
+        assert!(rect < 0.95);
+        assert!(shaped > 0.999);
+        assert!(shaped_fall > rect_fall * 1.1, "{} {}", shaped_fall, rect_fall);
+
+        // Both have the same energy, so the same threshold applies.
+        let energy = |fp: &[f32]| fp.iter().fold(0.0, |sum, &t| sum + t * t);
+        let shaped = SYNC_PATTERN.shaped_fingerprint::<FINGERPRINT_SAMPS>();
+        assert!((energy(&shaped) - energy(&SYNC_FINGERPRINT)).abs() < 1.0e-3);
+    }
+
+    #[test]
+    fn test_precision() {
+        let mut samples = testutil::silence(0.01);
+        samples.extend(testutil::modulate(&testutil::frame(
+            NetworkAccessCode::Default, DataUnit::VoiceSimpleTerminator, vec![])));
+
+        let mut narrow = SyncCorrelator::new();
+        let mut wide = SyncCorrelator::<FINGERPRINT_SAMPS, f64>::for_rate();
+
+        let mut thresh = SymbolThresholds::new();
+        let mut wide_thresh = SymbolThresholds::<f64>::new();
+
+        let (mut peak, mut wide_peak) = (0.0, 0.0);
+
+        for &s in samples.iter() {
+            let (p, power) = narrow.feed(s);
+            let (wp, wpower) = wide.feed(s as f64);
+
+            assert!((wp - p as f64).abs() <= 1.0e-6, "{} {}", wp, p);
+            assert!((wpower - power as f64).abs() <= 1.0e-6, "{} {}", wpower, power);
+
+            if p > peak {
+                peak = p;
+                wide_peak = wp;
+
+                let (a, b, c) = thresh.thresholds(&narrow.history());
+                let (wa, wb, wc) = wide_thresh.thresholds(&wide.history());
+
+                assert!((wa - a as f64).abs() <= 1.0e-6);
+                assert!((wb - b as f64).abs() <= 1.0e-6);
+                assert!((wc - c as f64).abs() <= 1.0e-6);
+            }
+        }
+
+        assert!(peak > 0.1);
+        assert!((wide_peak - peak as f64).abs() <= 1.0e-6);
     }
 }
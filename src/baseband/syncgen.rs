@@ -0,0 +1,172 @@
+//! Synthesis of the frame sync waveform, for generating test signals on the transmit
+//! side.
+//!
+//! The sync symbols of `SYNC_GENERATOR` are mapped to their C4FM levels and shaped by a
+//! pulse, by default the P25 raised cosine, into samples laid out like
+//! `SYNC_FINGERPRINT`: the first sample is the instant of the first symbol and the last
+//! sample the instant of the last.
+
+use baseband::fm::LEVEL_PER_HZ;
+use baseband::shaping::{PulseShape, ROLLOFF};
+use baseband::sync::{Polarity, SyncPattern, SYNC_DEVIATION, SYNC_PATTERN};
+
+/// Generates the samples of a sync sequence.
+#[derive(Copy, Clone, Debug)]
+pub struct SyncGenerator {
+    /// Sync symbols to generate.
+    pattern: SyncPattern,
+    /// Pulse each symbol is shaped by.
+    shape: PulseShape,
+    /// Level of the outer symbols.
+    level: f32,
+    /// Polarity of the generated signal.
+    polarity: Polarity,
+}
+
+impl SyncGenerator {
+    /// Create a new `SyncGenerator` for the C4FM frame sync, raised cosine shaped, at
+    /// the standard deviation and normal polarity.
+    pub fn new() -> SyncGenerator {
+        SyncGenerator {
+            pattern: SYNC_PATTERN,
+            shape: PulseShape::RaisedCosine(ROLLOFF),
+            level: SYNC_DEVIATION * LEVEL_PER_HZ,
+            polarity: Polarity::Normal,
+        }
+    }
+
+    /// Generate the given sync sequence rather than the Phase 1 frame sync.
+    pub fn with_pattern(mut self, pattern: SyncPattern) -> Self {
+        self.pattern = pattern;
+        self
+    }
+
+    /// Shape each symbol by the given pulse.
+    pub fn with_shape(mut self, shape: PulseShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Put the outer symbols at the given deviation in Hz, `SYNC_DEVIATION` by default,
+    /// scaled by `LEVEL_PER_HZ` as an FM discriminator would.
+    pub fn with_deviation(mut self, hz: f32) -> Self {
+        assert!(hz > 0.0);
+        self.level = hz * LEVEL_PER_HZ;
+        self
+    }
+
+    /// Generate a signal of the given polarity, such as `Polarity::Inverted` for a
+    /// transmitter with its deviation reversed.
+    pub fn with_polarity(mut self, polarity: Polarity) -> Self {
+        self.polarity = polarity;
+        self
+    }
+
+    /// Level of the outer symbols, before any inversion.
+    pub fn level(&self) -> f32 { self.level }
+
+    /// Write the sync waveform into the given buffer, whose length `N` gives the samples
+    /// per symbol, as from `fingerprint_samps`, so `FINGERPRINT_SAMPS` at 48kHz.
+
+    pub fn generate<const N: usize>(&self, out: &mut [f32; N]) {
+        let period = self.pattern.period::<N>() as f64;
+
+        for (i, s) in out.iter_mut().enumerate() {
+            let t = i as f64 / period;
+
+            let sum = (0..self.pattern.symbols).fold(0.0, |sum, sym| {
+                sum + self.pattern.symbol(sym) as f64 * self.shape.at(t - sym as f64)
+            });
+
+            *s = self.polarity.correct(sum as f32 * self.level);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use baseband::fir::Fir;
+    use baseband::phase2::{PHASE2_FINGERPRINT_SAMPS, PHASE2_SYNC_PATTERN,
+                           phase2_fingerprint};
+    use baseband::sync::{FINGERPRINT_SAMPS, SYNC_FINGERPRINT, SyncCorrelator, SyncScanner,
+                         fingerprint_samps, instant_thresholds, sync_fingerprint};
+
+    #[test]
+    fn test_rectangular() {
+        // Rectangular pulses give back the fingerprints at the outer level.
+        let gen = SyncGenerator::new().with_shape(PulseShape::Rectangular);
+        assert!((gen.level() - 0.18).abs() < 1.0e-6);
+
+        let mut out = [0.0; FINGERPRINT_SAMPS];
+        gen.generate(&mut out);
+
+        for (&s, &f) in out.iter().zip(SYNC_FINGERPRINT.iter()) {
+            assert!((s - f * gen.level()).abs() < 1.0e-6);
+        }
+
+        let mut out = [0.0; fingerprint_samps(5)];
+        gen.with_deviation(900.0).with_polarity(Polarity::Inverted).generate(&mut out);
+
+        for (&s, &f) in out.iter().zip(sync_fingerprint::<{ fingerprint_samps(5) }>()
+                                           .iter())
+        {
+            assert!((s + f * 0.09).abs() < 1.0e-6);
+        }
+
+        let mut out = [0.0; PHASE2_FINGERPRINT_SAMPS];
+        gen.with_pattern(PHASE2_SYNC_PATTERN).generate(&mut out);
+
+        for (&s, &f) in out.iter().zip(phase2_fingerprint().iter()) {
+            assert!((s - f * gen.level()).abs() < 1.0e-6);
+        }
+    }
+
+    #[test]
+    fn test_loopback() {
+        let mut sync = [0.0; FINGERPRINT_SAMPS];
+        SyncGenerator::new().generate(&mut sync);
+
+        // Passes through every symbol level at its instant.
+        assert!((sync[0] - 0.18).abs() < 1.0e-6);
+        assert!((sync[FINGERPRINT_SAMPS - 1] + 0.18).abs() < 1.0e-6);
+
+        let mut samples = vec![0.0; 500];
+        samples.extend(sync.iter().cloned());
+        samples.extend(vec![0.0; 500]);
+
+        let found: Vec<_> = SyncScanner::new().feed_slice(&samples).collect();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].index, 500 + FINGERPRINT_SAMPS);
+        assert_eq!(found[0].polarity, Polarity::Normal);
+
+        // Thresholds from the history put the outer symbols at their level.
+        let mut corr = SyncCorrelator::new();
+
+        for &s in samples[..found[0].index + 1].iter() {
+            corr.feed(s);
+        }
+
+        let (upper, mid, lower) = instant_thresholds(&corr.history());
+        assert!((upper - 0.12).abs() < 1.0e-3, "{}", upper);
+        assert!(mid.abs() < 1.0e-3);
+        assert!((lower + 0.12).abs() < 1.0e-3);
+
+        // An inverted sync at a lower deviation is found with its polarity.
+        SyncGenerator::new().with_deviation(1200.0).with_polarity(Polarity::Inverted)
+            .generate(&mut sync);
+
+        samples[500..500 + FINGERPRINT_SAMPS].copy_from_slice(&sync);
+
+        let found: Vec<_> = SyncScanner::new().feed_slice(&samples).collect();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].index, 500 + FINGERPRINT_SAMPS);
+        assert_eq!(found[0].polarity, Polarity::Inverted);
+
+        // The raw correlation peaks at the same place.
+        let mut fir = Fir::new(&SYNC_FINGERPRINT);
+        let out: Vec<f32> = samples.iter().map(|&s| fir.feed(s)).collect();
+        let min = out.iter().cloned().fold(0.0, f32::min);
+        assert_eq!(out[499 + FINGERPRINT_SAMPS], min);
+    }
+}
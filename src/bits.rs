@@ -71,6 +71,51 @@ impl Dibit {
     pub fn lo(&self) -> u8 { self.0 & 1 }
 }
 
+/// Received dibit symbol before a hard decision is made, scaled so the ideal symbol
+/// levels are at 3, 1, -1, and -3.
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+pub struct SoftDibit(f32);
+
+impl SoftDibit {
+    /// Construct a new `SoftDibit` from the given scaled symbol level.
+    pub fn new(level: f32) -> SoftDibit { SoftDibit(level) }
+
+    /// Construct a new `SoftDibit` at the ideal level of the given dibit.
+    pub fn ideal(dibit: Dibit) -> SoftDibit { SoftDibit(Self::level(dibit)) }
+
+    /// Get the wrapped symbol level.
+    pub fn value(&self) -> f32 { self.0 }
+
+    /// Decide which dibit the symbol level is closest to.
+    pub fn hard(&self) -> Dibit {
+        Dibit::new(if self.0 > 2.0 {
+            0b01
+        } else if self.0 > 0.0 {
+            0b00
+        } else if self.0 > -2.0 {
+            0b10
+        } else {
+            0b11
+        })
+    }
+
+    /// Squared distance from the symbol level to the ideal level of the given dibit.
+    pub fn distance(&self, dibit: Dibit) -> f32 {
+        let diff = self.0 - Self::level(dibit);
+        diff * diff
+    }
+
+    /// Ideal symbol level of the given dibit.
+    fn level(dibit: Dibit) -> f32 {
+        match dibit.bits() {
+            0b01 => 3.0,
+            0b00 => 1.0,
+            0b10 => -1.0,
+            _ => -3.0,
+        }
+    }
+}
+
 /// Parameters for `Dibits` iterator.
 pub struct DibitParams;
 
@@ -0,0 +1,228 @@
+//! Read and write captures of already decided symbols, as stored by various tools in
+//! place of baseband audio.
+//!
+//! The symbols of a `DibitReader` can be fed to `Receiver::feed_dibit` to decode a
+//! capture without going through the baseband stage.
+
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+
+use bits::Dibit;
+
+/// Layout of the symbols in a dibit capture.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DibitLayout {
+    /// Four symbols per byte, with the first symbol in the two MSBs.
+    Packed,
+    /// One symbol per byte, in the two LSBs with the rest of the bits ignored.
+    Unpacked,
+}
+
+impl DibitLayout {
+    /// Number of symbols in each byte.
+    pub fn per_byte(&self) -> usize {
+        match *self {
+            DibitLayout::Packed => 4,
+            DibitLayout::Unpacked => 1,
+        }
+    }
+}
+
+/// Iterator over the symbols in a dibit capture.
+///
+/// Iteration stops at the end of the stream or at the first read error, which can then
+/// be retrieved with `error`.
+pub struct DibitReader<R: Read> {
+    /// Buffered source stream.
+    stream: BufReader<R>,
+    /// Layout of the symbols.
+    layout: DibitLayout,
+    /// Current byte of the stream.
+    byte: u8,
+    /// Number of symbols left in the current byte.
+    left: usize,
+    /// Error that stopped reading, if any.
+    err: Option<io::Error>,
+    /// Whether reading has stopped.
+    done: bool,
+}
+
+impl<R: Read> DibitReader<R> {
+    /// Create a new `DibitReader` over the given stream of symbols in the given layout.
+    pub fn new(stream: R, layout: DibitLayout) -> DibitReader<R> {
+        DibitReader {
+            stream: BufReader::new(stream),
+            layout: layout,
+            byte: 0,
+            left: 0,
+            err: None,
+            done: false,
+        }
+    }
+
+    /// Create a new `DibitReader` over four symbols per byte.
+    pub fn packed(stream: R) -> DibitReader<R> {
+        DibitReader::new(stream, DibitLayout::Packed)
+    }
+
+    /// Create a new `DibitReader` over one symbol per byte.
+    pub fn unpacked(stream: R) -> DibitReader<R> {
+        DibitReader::new(stream, DibitLayout::Unpacked)
+    }
+
+    /// Layout of the symbols.
+    pub fn layout(&self) -> DibitLayout { self.layout }
+
+    /// Error that stopped reading, if any.
+    pub fn error(&self) -> Option<&io::Error> { self.err.as_ref() }
+
+    /// Take the error that stopped reading, if any.
+    pub fn take_error(&mut self) -> Option<io::Error> { self.err.take() }
+}
+
+impl<R: Read> Iterator for DibitReader<R> {
+    type Item = Dibit;
+
+    fn next(&mut self) -> Option<Dibit> {
+        if self.done {
+            return None;
+        }
+
+        if self.left == 0 {
+            let mut buf = [0; 1];
+
+            if let Err(e) = self.stream.read_exact(&mut buf) {
+                self.done = true;
+
+                if e.kind() != ErrorKind::UnexpectedEof {
+                    self.err = Some(e);
+                }
+
+                return None;
+            }
+
+            self.byte = buf[0];
+            self.left = self.layout.per_byte();
+        }
+
+        self.left -= 1;
+
+        Some(Dibit::new(self.byte >> (self.left * 2) & 0b11))
+    }
+}
+
+/// Writes symbols to a dibit capture.
+///
+/// A packed capture that doesn't end on a byte boundary is padded out with zero symbols
+/// by `finish`.
+pub struct DibitWriter<W: Write> {
+    /// Buffered destination stream.
+    stream: BufWriter<W>,
+    /// Layout of the symbols.
+    layout: DibitLayout,
+    /// Symbols of the current byte, latest in the LSBs.
+    byte: u8,
+    /// Number of symbols in the current byte.
+    count: usize,
+}
+
+impl<W: Write> DibitWriter<W> {
+    /// Create a new `DibitWriter` to the given stream in the given layout.
+    pub fn new(stream: W, layout: DibitLayout) -> DibitWriter<W> {
+        DibitWriter {
+            stream: BufWriter::new(stream),
+            layout: layout,
+            byte: 0,
+            count: 0,
+        }
+    }
+
+    /// Create a new `DibitWriter` of four symbols per byte.
+    pub fn packed(stream: W) -> DibitWriter<W> {
+        DibitWriter::new(stream, DibitLayout::Packed)
+    }
+
+    /// Create a new `DibitWriter` of one symbol per byte.
+    pub fn unpacked(stream: W) -> DibitWriter<W> {
+        DibitWriter::new(stream, DibitLayout::Unpacked)
+    }
+
+    /// Write the given symbol.
+    pub fn write(&mut self, d: Dibit) -> io::Result<()> {
+        self.byte = self.byte << 2 | d.bits();
+        self.count += 1;
+
+        if self.count == self.layout.per_byte() {
+            self.flush_byte()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Write each of the given symbols.
+    pub fn write_all(&mut self, dibits: &[Dibit]) -> io::Result<()> {
+        for &d in dibits {
+            self.write(d)?;
+        }
+
+        Ok(())
+    }
+
+    /// Pad out any partial byte, flush all buffered bytes, and return the underlying
+    /// stream.
+    pub fn finish(mut self) -> io::Result<W> {
+        while self.count != 0 {
+            self.write(Dibit::new(0))?;
+        }
+
+        self.stream.into_inner().map_err(|e| e.into())
+    }
+
+    /// Write out the current byte.
+    fn flush_byte(&mut self) -> io::Result<()> {
+        let byte = self.byte;
+
+        self.byte = 0;
+        self.count = 0;
+
+        self.stream.write_all(&[byte])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    fn dibits() -> Vec<Dibit> {
+        [0b01, 0b11, 0b00, 0b10, 0b11, 0b01].iter().map(|&b| Dibit::new(b)).collect()
+    }
+
+    #[test]
+    fn test_packed() {
+        let mut w = DibitWriter::packed(vec![]);
+        w.write_all(&dibits()).unwrap();
+        let bytes = w.finish().unwrap();
+
+        assert_eq!(bytes, vec![0b01110010, 0b11010000]);
+
+        let read: Vec<Dibit> = DibitReader::packed(Cursor::new(bytes)).collect();
+        assert_eq!(&read[..6], &dibits()[..]);
+        assert_eq!(read.len(), 8);
+    }
+
+    #[test]
+    fn test_unpacked() {
+        let mut w = DibitWriter::unpacked(vec![]);
+        w.write_all(&dibits()).unwrap();
+        let bytes = w.finish().unwrap();
+
+        assert_eq!(bytes, vec![1, 3, 0, 2, 3, 1]);
+
+        // Upper bits are ignored.
+        let read: Vec<Dibit> = DibitReader::unpacked(Cursor::new(vec![1, 0xFF, 0x80, 2]))
+            .collect();
+
+        assert_eq!(read, vec![Dibit::new(1), Dibit::new(3), Dibit::new(0),
+                              Dibit::new(2)]);
+    }
+}
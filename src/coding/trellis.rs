@@ -7,6 +7,7 @@
 
 use std;
 use std::ops::{Deref, DerefMut};
+use std::vec::Vec;
 
 use collect_slice::CollectSlice;
 
@@ -410,6 +411,55 @@ impl<H: WalkHistory> Default for Walk<H> {
     fn default() -> Self { Walk::new(std::usize::MAX) }
 }
 
+/// Decode the given received soft dibit stream, which ends with the coded flushing
+/// symbol, to the nearest codeword using the Viterbi algorithm over the whole stream,
+/// with the squared distance to each dibit pair as the branch metric. Return the decoded
+/// symbols, excluding the flushing symbol.
+pub fn decode_soft<S: States>(src: &[bits::SoftDibit]) -> Vec<S::Symbol> {
+    assert!(src.len() % 2 == 0);
+
+    // Coding always starts in the first state.
+    let mut metrics = vec![std::f32::INFINITY; S::size()];
+    metrics[0] = 0.0;
+
+    // Best previous state of each state at each step.
+    let mut prevs: Vec<Vec<usize>> = Vec::with_capacity(src.len() / 2);
+
+    for input in src.chunks(2) {
+        let mut next = vec![std::f32::INFINITY; S::size()];
+        let mut prev = vec![0; S::size()];
+
+        for state in 0..S::size() {
+            for (i, &m) in metrics.iter().enumerate() {
+                let (hi, lo) = S::pair(i, state);
+                let sum = m + input[0].distance(hi) + input[1].distance(lo);
+
+                if sum < next[state] {
+                    next[state] = sum;
+                    prev[state] = i;
+                }
+            }
+        }
+
+        metrics = next;
+        prevs.push(prev);
+    }
+
+    // The stream ends in the flushing state, so trace back from there.
+    let mut state = S::state(S::finisher());
+    let mut states = Vec::with_capacity(prevs.len());
+
+    for prev in prevs.iter().rev() {
+        states.push(state);
+        state = prev[state];
+    }
+
+    states.iter().rev()
+        .take(prevs.len().saturating_sub(1))
+        .map(|&s| S::symbol(s))
+        .collect()
+}
+
 #[derive(Copy, Clone)]
 struct Edge(u8);
 
@@ -551,4 +601,30 @@ mod test {
         assert_eq!(dec.next().unwrap().unwrap().bits(), 7);
         assert_eq!(dec.next().unwrap().unwrap().bits(), 0);
     }
+
+    #[test]
+    fn test_soft_decoder() {
+        let bits = [1, 2, 3, 4, 5, 6, 7, 0, 7, 6, 5, 4];
+
+        let mut fsm = TribitFSM::new();
+        let mut soft = vec![];
+
+        for &b in bits.iter() {
+            let (hi, lo) = fsm.feed(Tribit::new(b));
+            soft.push(SoftDibit::ideal(hi));
+            soft.push(SoftDibit::ideal(lo));
+        }
+
+        let (hi, lo) = fsm.finish();
+        soft.push(SoftDibit::ideal(hi));
+        soft.push(SoftDibit::ideal(lo));
+
+        // Push some levels across decision boundaries and others toward them.
+        soft[3] = SoftDibit::new(soft[3].value() + 2.2);
+        soft[10] = SoftDibit::new(soft[10].value() * 0.3);
+        soft[17] = SoftDibit::new(soft[17].value() * 0.6);
+
+        let dec = decode_soft::<TribitStates>(&soft);
+        assert_eq!(dec.iter().map(|t| t.bits()).collect::<Vec<u8>>(), bits.to_vec());
+    }
 }
@@ -16,6 +16,8 @@ pub const CODING_DIBITS: usize = 98;
 pub const TSBK_DIBITS: usize = 48;
 /// Number of bytes in an uncoded TSBK packet.
 pub const TSBK_BYTES: usize = TSBK_DIBITS / 4;
+/// Maximum number of TSBK packets in a single TSDU.
+pub const TSDU_MAX_BLOCKS: usize = 3;
 /// Number of dibits in a coded voice frame.
 pub const FRAME_DIBITS: usize = 72;
 /// Number of hexbits in a coded voice header packet.
@@ -0,0 +1,324 @@
+//! Monitor a conventional (non-trunked) channel, opening squelch only for calls on a
+//! given NAC and, optionally, a list of talkgroups.
+
+use std::vec::Vec;
+
+use consts::SAMPLE_RATE;
+use error;
+use message::nid::{NacFilter, NetworkAccessCode};
+use receiver::{MessageHandler, Receiver, ReceiverConfig};
+use trunking::fields::TalkGroup;
+use voice::control::{GroupVoiceTraffic, LinkControlFields, LinkControlOpcode};
+use voice::frame::VoiceFrame;
+use voice::header::VoiceHeaderFields;
+
+/// Receives the squelch changes and passed voice frames of a `ConventionalMonitor`.
+///
+/// Every method does nothing by default, so a sink only needs to implement those for
+/// the events it cares about.
+pub trait VoiceSink {
+    /// Squelch opened for a call to the given talkgroup, or to an unknown one if
+    /// monitoring every talkgroup and the call hasn't identified itself yet.
+    fn on_squelch_open(&mut self, _tg: Option<TalkGroup>) {}
+    /// Squelch closed, after the hang time passed without voice or the call changed to
+    /// a talkgroup that isn't monitored.
+    fn on_squelch_close(&mut self) {}
+    /// A voice frame was received while squelch was open.
+    fn on_voice_frame(&mut self, _vf: VoiceFrame) {}
+}
+
+/// Runs a `Receiver` on a conventional channel, passing voice frames on to a
+/// `VoiceSink` only while squelch is open.
+///
+/// Data units on other NACs are skipped by the receiver. Squelch opens at the voice
+/// header of a call to a monitored talkgroup or, when entering a call late, at the first
+/// link control word that identifies it, and closes once the hang time has passed since
+/// the last voice activity.
+pub struct ConventionalMonitor {
+    /// Receive chain, filtered to the monitored NAC.
+    recv: Receiver,
+    /// Squelch state.
+    squelch: Squelch,
+}
+
+impl ConventionalMonitor {
+    /// Create a new `ConventionalMonitor` for every talkgroup on the given NAC, with the
+    /// receiver otherwise configured by the given configuration, or return an error
+    /// naming its first invalid field.
+    pub fn new(nac: NetworkAccessCode, config: ReceiverConfig)
+        -> error::Result<ConventionalMonitor>
+    {
+        Ok(ConventionalMonitor {
+            recv: Receiver::new(ReceiverConfig {
+                nac_filter: NacFilter::new().accept(nac),
+                track_calls: false,
+                ..config
+            })?,
+            squelch: Squelch {
+                talkgroups: vec![],
+                hang: SAMPLE_RATE as u64 / 2,
+                sample: 0,
+                open: false,
+                tg: None,
+                last: 0,
+            },
+        })
+    }
+
+    /// Only open squelch for calls to the given talkgroups.
+    pub fn with_talkgroups(mut self, tgs: &[TalkGroup]) -> Self {
+        self.squelch.talkgroups = tgs.to_vec();
+        self
+    }
+
+    /// Keep squelch open for the given number of samples after the last voice activity,
+    /// half a second by default.
+    pub fn with_hang_time(mut self, samples: u64) -> Self {
+        self.squelch.hang = samples;
+        self
+    }
+
+    /// Feed in a baseband sample, passing any squelch changes and voice frames to the
+    /// given sink.
+    pub fn feed_with<S: VoiceSink>(&mut self, s: f32, sink: &mut S) {
+        self.recv.feed_with(s, &mut Gate {
+            squelch: &mut self.squelch,
+            sink: sink,
+        });
+    }
+
+    /// Whether squelch is open.
+    pub fn is_open(&self) -> bool { self.squelch.open }
+
+    /// Talkgroup of the call squelch is open for, if known.
+    pub fn talkgroup(&self) -> Option<TalkGroup> { self.squelch.tg }
+
+    /// Underlying receiver.
+    pub fn receiver(&self) -> &Receiver { &self.recv }
+}
+
+/// Squelch state of a monitor.
+struct Squelch {
+    /// Talkgroups to open squelch for, or empty for all.
+    talkgroups: Vec<TalkGroup>,
+    /// Samples to stay open after the last voice activity.
+    hang: u64,
+    /// Index of the current sample.
+    sample: u64,
+    /// Whether squelch is open.
+    open: bool,
+    /// Talkgroup of the current call, if known.
+    tg: Option<TalkGroup>,
+    /// Sample of the last voice activity.
+    last: u64,
+}
+
+impl Squelch {
+    /// Whether calls to the given talkgroup are monitored.
+    fn allows(&self, tg: Option<TalkGroup>) -> bool {
+        self.talkgroups.is_empty() || tg.map_or(false, |tg| self.talkgroups.contains(&tg))
+    }
+}
+
+/// Applies receiver events to the squelch state and passes them on to a sink.
+struct Gate<'a, S: VoiceSink + 'a> {
+    /// Squelch state.
+    squelch: &'a mut Squelch,
+    /// Destination of squelch changes and voice frames.
+    sink: &'a mut S,
+}
+
+impl<'a, S: VoiceSink> Gate<'a, S> {
+    /// Handle voice activity from a call to the given talkgroup, or an unknown one.
+    fn activity(&mut self, tg: Option<TalkGroup>) {
+        let sq = &mut *self.squelch;
+
+        match (sq.open, tg) {
+            // Frames of the call that's already open, or that a call is in progress.
+            (true, None) => {},
+            (true, Some(tg)) if sq.tg.map_or(true, |cur| cur == tg) => sq.tg = Some(tg),
+            (true, Some(_)) => {
+                sq.open = false;
+                sq.tg = None;
+                self.sink.on_squelch_close();
+
+                return self.activity(tg);
+            },
+            (false, _) if sq.allows(tg) => {
+                sq.open = true;
+                sq.tg = tg;
+                self.sink.on_squelch_open(tg);
+            },
+            (false, _) => return,
+        }
+
+        sq.last = sq.sample;
+    }
+}
+
+impl<'a, S: VoiceSink> MessageHandler for Gate<'a, S> {
+    fn on_sample(&mut self, sample: u64) {
+        let sq = &mut *self.squelch;
+        sq.sample = sample;
+
+        if sq.open && sample - sq.last >= sq.hang {
+            sq.open = false;
+            sq.tg = None;
+            self.sink.on_squelch_close();
+        }
+    }
+
+    fn on_voice_header(&mut self, h: VoiceHeaderFields) {
+        self.activity(Some(h.talk_group()));
+    }
+
+    fn on_voice_frame(&mut self, vf: VoiceFrame) {
+        // Only calls to any talkgroup can open on a frame alone.
+        if self.squelch.open || self.squelch.talkgroups.is_empty() {
+            self.activity(None);
+        }
+
+        if self.squelch.open {
+            self.sink.on_voice_frame(vf);
+        }
+    }
+
+    fn on_link_control(&mut self, lc: LinkControlFields) {
+        self.activity(lc_talkgroup(&lc));
+    }
+
+    fn on_voice_term(&mut self, lc: LinkControlFields) {
+        // The terminator ends the call, but squelch hangs open for a following one.
+        if self.squelch.open {
+            self.activity(lc_talkgroup(&lc));
+        }
+    }
+}
+
+/// Talkgroup of the call described by the given link control word, if it's a group
+/// call.
+fn lc_talkgroup(lc: &LinkControlFields) -> Option<TalkGroup> {
+    match lc.opcode() {
+        Some(LinkControlOpcode::GroupVoiceTraffic) =>
+            Some(GroupVoiceTraffic::new(*lc).talkgroup()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use consts::SYMBOL_PERIOD;
+    use testutil;
+    use voice::encode::{VoiceEncoder, GROUP_FRAMES};
+
+    const NAC: NetworkAccessCode = NetworkAccessCode::Default;
+
+    /// Records squelch changes and counts voice frames.
+    #[derive(Default)]
+    struct Recorder {
+        changes: Vec<Option<Option<TalkGroup>>>,
+        frames: usize,
+    }
+
+    impl VoiceSink for Recorder {
+        fn on_squelch_open(&mut self, tg: Option<TalkGroup>) {
+            self.changes.push(Some(tg));
+        }
+
+        fn on_squelch_close(&mut self) { self.changes.push(None); }
+
+        fn on_voice_frame(&mut self, _: VoiceFrame) { self.frames += 1; }
+    }
+
+    fn run(monitor: &mut ConventionalMonitor, samples: &[f32]) -> Recorder {
+        let mut rec = Recorder::default();
+
+        for &s in samples {
+            monitor.feed_with(s, &mut rec);
+        }
+
+        rec
+    }
+
+    fn call(nac: NetworkAccessCode, tg: u16) -> Vec<f32> {
+        let mut samples = testutil::modulate(&testutil::voice_call(nac, tg, 0x123456, 18));
+        samples.extend(testutil::silence(1.0));
+        samples
+    }
+
+    #[test]
+    fn test_matching() {
+        let mut m = ConventionalMonitor::new(NAC, ReceiverConfig::default()).unwrap()
+            .with_talkgroups(&[TalkGroup::Other(0x0100), TalkGroup::Other(0x0123)]);
+
+        let rec = run(&mut m, &call(NAC, 0x0123));
+
+        assert_eq!(rec.changes, vec![Some(Some(TalkGroup::Other(0x0123))), None]);
+        assert_eq!(rec.frames, 18);
+        assert!(!m.is_open());
+
+        // Every talkgroup is monitored by default.
+        let mut m = ConventionalMonitor::new(NAC, ReceiverConfig::default()).unwrap();
+        let rec = run(&mut m, &call(NAC, 0x0456));
+        assert_eq!(rec.changes, vec![Some(Some(TalkGroup::Other(0x0456))), None]);
+        assert_eq!(rec.frames, 18);
+    }
+
+    #[test]
+    fn test_not_matching() {
+        let mut m = ConventionalMonitor::new(NAC, ReceiverConfig::default()).unwrap()
+            .with_talkgroups(&[TalkGroup::Other(0x0100)]);
+
+        let rec = run(&mut m, &call(NAC, 0x0123));
+        assert_eq!(rec.changes, vec![]);
+        assert_eq!(rec.frames, 0);
+
+        // Calls on other NACs never open squelch.
+        let mut m = ConventionalMonitor::new(NAC, ReceiverConfig::default()).unwrap();
+        let rec = run(&mut m, &call(NetworkAccessCode::Other(0x123), 0x0100));
+        assert_eq!(rec.changes, vec![]);
+        assert_eq!(rec.frames, 0);
+    }
+
+    #[test]
+    fn test_late_entry() {
+        let dibits = testutil::voice_call(NAC, 0x0123, 0x123456, 18);
+
+        // Start receiving just after the voice header.
+        let skip = VoiceEncoder::new(NAC).header(&[0; 15]).len() * SYMBOL_PERIOD + 1000;
+        let mut samples = testutil::modulate(&dibits)[skip..].to_vec();
+        samples.extend(testutil::silence(1.0));
+
+        let mut m = ConventionalMonitor::new(NAC, ReceiverConfig::default()).unwrap()
+            .with_talkgroups(&[TalkGroup::Other(0x0123)]);
+
+        let rec = run(&mut m, &samples);
+
+        // The talkgroup is only known from the link control word near the end of the
+        // LDU1, so only its last voice frames and those after it get through.
+        assert_eq!(rec.changes, vec![Some(Some(TalkGroup::Other(0x0123))), None]);
+        assert!(rec.frames > GROUP_FRAMES && rec.frames < 2 * GROUP_FRAMES);
+    }
+
+    #[test]
+    fn test_hang_time() {
+        let mut samples = call(NAC, 0x0123);
+        samples.extend(call(NAC, 0x0123));
+        samples.extend(call(NAC, 0x0456));
+
+        // Squelch stays open across both calls to the same talkgroup.
+        let mut m = ConventionalMonitor::new(NAC, ReceiverConfig::default()).unwrap()
+            .with_hang_time(SAMPLE_RATE as u64 * 2);
+
+        let rec = run(&mut m, &samples);
+
+        assert_eq!(rec.changes, vec![
+            Some(Some(TalkGroup::Other(0x0123))),
+            None,
+            Some(Some(TalkGroup::Other(0x0456))),
+        ]);
+        assert_eq!(rec.frames, 3 * 18);
+        assert_eq!(m.talkgroup(), Some(TalkGroup::Other(0x0456)));
+    }
+}
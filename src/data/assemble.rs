@@ -0,0 +1,909 @@
+//! Reassemble data packets from their coded header and data blocks.
+//!
+//! Each block is deinterleaved and decoded, using the 1/2-rate convolutional code for the
+//! header and unconfirmed data blocks and the 3/4-rate code for confirmed data blocks.
+//! Confirmed data blocks are prefixed with a serial number and CRC-9 over the block, and
+//! the last data block of each packet ends with a CRC-32 over all data and pad bytes.
+
+use std::ops::Range;
+use std::vec::Vec;
+
+use collect_slice::CollectSlice;
+
+use bits::{Dibit, DibitBytes, SoftDibit, Tribit, TribitBytes};
+use buffer::{Buffer, DataPayloadStorage};
+use coding::trellis::{self, DibitStates, TribitStates};
+use consts::{CODING_DIBITS, TSBK_DIBITS};
+use data::{coder, crc, interleave};
+use data::header::{DataHeader, ExtendedAddress, PduHeader, EXTENDED_ADDRESS_BYTES};
+use data::response::DataResponse;
+use error::{P25Error, Result};
+use stats::{HasStats, Stats};
+use util::slice_u32;
+
+/// Number of bytes in an unconfirmed data block (and the header block.)
+const UNCONFIRMED_BYTES: usize = 12;
+
+/// Number of bytes in a confirmed data block, including the serial number and CRC-9.
+const CONFIRMED_BYTES: usize = 18;
+
+/// Number of data bytes in a confirmed data block.
+const CONFIRMED_DATA_BYTES: usize = CONFIRMED_BYTES - 2;
+
+/// Number of tribits coded in a confirmed data block.
+const CONFIRMED_TRIBITS: usize = CONFIRMED_BYTES * 8 / 3;
+
+/// Number of bytes in the packet checksum at the end of the last block.
+const CHECKSUM_BYTES: usize = 4;
+
+/// Event produced by the packet assembler.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PduEvent {
+    /// A complete packet was received.
+    Packet {
+        /// Decoded packet header.
+        header: DataHeader,
+        /// Extended address header, if the packet uses extended addressing.
+        ext: Option<ExtendedAddress>,
+        /// Data bytes, with extended address header, pad bytes, and packet checksum
+        /// removed.
+        payload: Vec<u8>,
+        /// Whether the packet checksum matched the data.
+        crc_ok: bool,
+        /// Errors corrected in each block.
+        fec: FecStats,
+    },
+    /// A response packet was received.
+    Response(DataResponse),
+    /// The header block couldn't be decoded, so the packet was dropped.
+    BadHeader(P25Error),
+    /// A confirmed packet was received with some blocks that failed to decode.
+    Partial {
+        /// Decoded packet header.
+        header: DataHeader,
+        /// Extended address header, if the packet uses extended addressing and the
+        /// block carrying it was received intact.
+        ext: Option<ExtendedAddress>,
+        /// Data bytes, with extended address header, pad bytes, and packet checksum
+        /// removed and bytes of bad blocks zeroed.
+        payload: Vec<u8>,
+        /// Serial numbers of the blocks received intact.
+        serials: SerialBitmap,
+        /// Ranges of bytes in `payload` that came from bad blocks.
+        bad: Vec<Range<usize>>,
+        /// Errors corrected in each block.
+        fec: FecStats,
+    },
+    /// The unconfirmed data block at the given index (from 0) couldn't be decoded, so the
+    /// packet was dropped.
+    BadBlock {
+        /// Decoded packet header.
+        header: DataHeader,
+        /// Index of the bad block.
+        block: usize,
+        /// Cause of the failure.
+        err: P25Error,
+    },
+}
+
+/// Number of corrected point errors (received dibits that differ from the decoded
+/// codeword) in each block of a packet.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FecStats {
+    /// Errors corrected in the header block.
+    pub header: usize,
+    /// Errors corrected in each data block, or `None` if the block couldn't be decoded.
+    pub blocks: Vec<Option<usize>>,
+}
+
+impl FecStats {
+    /// Total errors corrected over all decoded blocks.
+    pub fn total(&self) -> usize {
+        self.header + self.blocks.iter().filter_map(|&b| b).sum::<usize>()
+    }
+}
+
+/// Convolutional code used for a block.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Rate {
+    /// 1/2-rate code of header and unconfirmed data blocks.
+    Half,
+    /// 3/4-rate code of confirmed data blocks.
+    ThreeQuarter,
+}
+
+impl Rate {
+    /// Decode the given deinterleaved hard dibits into block bytes.
+    fn decode(&self, dibits: &[Dibit]) -> Result<Vec<u8>> {
+        match *self {
+            Rate::Half => decode_dibits(dibits).map(|b| b.to_vec()),
+            Rate::ThreeQuarter => decode_tribits(dibits).map(|b| b.to_vec()),
+        }
+    }
+
+    /// Decode the given deinterleaved soft dibits into block bytes.
+    fn decode_soft(&self, soft: &[SoftDibit]) -> Vec<u8> {
+        match *self {
+            Rate::Half =>
+                DibitBytes::new(trellis::decode_soft::<DibitStates>(soft).into_iter())
+                    .collect(),
+            Rate::ThreeQuarter =>
+                TribitBytes::new(trellis::decode_soft::<TribitStates>(soft).into_iter())
+                    .collect(),
+        }
+    }
+
+    /// Count the received deinterleaved dibits that differ from the coding of the given
+    /// decoded block bytes.
+    fn point_errors(&self, bytes: &[u8], received: &[Dibit]) -> usize {
+        let coded = match *self {
+            Rate::Half => coder::DibitCoder::new()
+                .feed_bytes(bytes.iter().cloned())
+                .finish(),
+            Rate::ThreeQuarter => coder::TribitCoder::new()
+                .feed_bytes(bytes.iter().cloned())
+                .finish(),
+        };
+
+        coded.iter().zip(received.iter()).filter(|&(a, b)| a != b).count()
+    }
+}
+
+/// State machine for assembling a data packet from its coded blocks.
+///
+/// The first block fed in is taken as the header, which determines how many data blocks
+/// follow and how they're coded. Once the final block is received or an unconfirmed block
+/// fails to decode, the assembler returns to waiting for a header. Confirmed blocks that
+/// fail to decode are recorded so the rest of the packet can still be delivered.
+///
+/// A response header is reported as soon as it's received. Any selective retry bitmap
+/// blocks that follow it are skipped.
+///
+/// Blocks can be fed in as hard dibits or, for better performance on weak signals, as
+/// soft dibits, which are decoded with a soft-decision Viterbi search.
+pub struct PduAssembler {
+    /// Decoded header of the current packet, once received.
+    header: Option<DataHeader>,
+    /// Data bytes received so far, without confirmed block prefixes.
+    data: Vec<u8>,
+    /// Number of data blocks received so far.
+    blocks: usize,
+    /// Serial numbers of confirmed blocks received intact.
+    serials: SerialBitmap,
+    /// Indexes of confirmed blocks that failed to decode.
+    bad: Vec<usize>,
+    /// Errors corrected in the blocks received so far.
+    fec: FecStats,
+    /// Number of response data blocks remaining to be skipped.
+    skip: usize,
+    stats: Stats,
+}
+
+impl PduAssembler {
+    /// Create a new `PduAssembler` waiting for a header block.
+    pub fn new() -> PduAssembler {
+        PduAssembler {
+            header: None,
+            data: vec![],
+            blocks: 0,
+            serials: SerialBitmap::default(),
+            bad: vec![],
+            fec: FecStats::default(),
+            skip: 0,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Drop any partially received packet and wait for a header block.
+    pub fn reset(&mut self) {
+        self.header = None;
+        self.data.clear();
+        self.blocks = 0;
+        self.serials = SerialBitmap::default();
+        self.bad.clear();
+        self.fec = FecStats::default();
+        self.skip = 0;
+    }
+
+    /// Feed in the given coded block, returning an event if it completes or aborts a
+    /// packet.
+    pub fn feed(&mut self, buf: &[Dibit; CODING_DIBITS]) -> Option<PduEvent> {
+        let dibits: Vec<Dibit> = interleave::Deinterleaver::new(buf).collect();
+        let rate = self.rate();
+
+        let decoded = rate.decode(&dibits[..]).map(|bytes| {
+            let fixes = rate.point_errors(&bytes[..], &dibits[..]);
+            (bytes, fixes)
+        });
+
+        self.handle(decoded)
+    }
+
+    /// Feed in the given coded block of soft dibits, returning an event if it completes
+    /// or aborts a packet.
+    pub fn feed_soft(&mut self, buf: &[SoftDibit; CODING_DIBITS]) -> Option<PduEvent> {
+        let soft: Vec<SoftDibit> = interleave::SoftDeinterleaver::new(buf).collect();
+        let hard: Vec<Dibit> = soft.iter().map(|s| s.hard()).collect();
+        let rate = self.rate();
+
+        let bytes = rate.decode_soft(&soft[..]);
+        let fixes = rate.point_errors(&bytes[..], &hard[..]);
+
+        self.handle(Ok((bytes, fixes)))
+    }
+
+    /// Code used for the next expected block.
+    fn rate(&self) -> Rate {
+        match self.header {
+            Some(h) if h.confirmed() => Rate::ThreeQuarter,
+            _ => Rate::Half,
+        }
+    }
+
+    /// Handle the given decoded block bytes and number of corrected errors.
+    fn handle(&mut self, decoded: Result<(Vec<u8>, usize)>) -> Option<PduEvent> {
+        if self.skip > 0 {
+            self.skip -= 1;
+            return None;
+        }
+
+        let header = match self.header {
+            Some(header) => header,
+            None => return self.feed_header(decoded),
+        };
+
+        let result = if header.confirmed() {
+            Ok(self.feed_confirmed(decoded))
+        } else {
+            self.feed_unconfirmed(decoded)
+        };
+
+        if let Err(err) = result {
+            let block = self.blocks;
+            self.reset();
+
+            return Some(PduEvent::BadBlock {
+                header: header,
+                block: block,
+                err: err,
+            });
+        }
+
+        self.blocks += 1;
+
+        if self.blocks < header.blocks as usize {
+            return None;
+        }
+
+        let event = self.finish(header);
+        self.reset();
+
+        Some(event)
+    }
+
+    /// Decode the given header block.
+    fn feed_header(&mut self, decoded: Result<(Vec<u8>, usize)>) -> Option<PduEvent> {
+        let (header, fixes) = match decoded.and_then(|(bytes, fixes)| {
+            let mut buf = [0; UNCONFIRMED_BYTES];
+            buf.copy_from_slice(&bytes[..]);
+
+            PduHeader::decode(&buf).map(|h| (h, fixes))
+        }) {
+            Ok(x) => x,
+            Err(err) => return Some(PduEvent::BadHeader(err)),
+        };
+
+        self.stats.viterbi_dibit.record_fixes(fixes);
+
+        let header = match header {
+            PduHeader::Data(header) => header,
+            PduHeader::Response(resp) => {
+                self.skip = resp.blocks as usize;
+                return Some(PduEvent::Response(resp));
+            },
+        };
+
+        if header.blocks == 0 {
+            return Some(PduEvent::BadHeader(P25Error::UnsupportedFormat {
+                field: "block count",
+                value: 0,
+            }));
+        }
+
+        self.header = Some(header);
+        self.fec.header = fixes;
+
+        None
+    }
+
+    /// Buffer the bytes of the given decoded unconfirmed data block.
+    fn feed_unconfirmed(&mut self, decoded: Result<(Vec<u8>, usize)>) -> Result<()> {
+        let (bytes, fixes) = decoded?;
+
+        self.stats.viterbi_dibit.record_fixes(fixes);
+        self.fec.blocks.push(Some(fixes));
+        self.data.extend(bytes);
+
+        Ok(())
+    }
+
+    /// Verify the given decoded confirmed data block and buffer its data bytes, or zeros
+    /// in their place if the block is bad.
+    fn feed_confirmed(&mut self, decoded: Result<(Vec<u8>, usize)>) {
+        let last = self.blocks + 1 == self.header.map_or(0, |h| h.blocks as usize);
+
+        let (bytes, fixes) = match decoded {
+            Ok(x) => x,
+            Err(err) => {
+                self.stats.record_err(err);
+                self.fec.blocks.push(None);
+                return self.bad_block();
+            },
+        };
+
+        self.stats.viterbi_tribit.record_fixes(fixes);
+        self.fec.blocks.push(Some(fixes));
+
+        let mut buf = [0; CONFIRMED_BYTES];
+        buf.copy_from_slice(&bytes[..]);
+        let block = ConfirmedBlock::new(buf);
+
+        if block.crc_valid(last) {
+            self.serials.set(block.serial());
+            self.data.extend(block.data().iter().cloned());
+        } else {
+            self.bad_block();
+        }
+    }
+
+    /// Record the current confirmed block as bad.
+    fn bad_block(&mut self) {
+        self.bad.push(self.blocks);
+        self.data.extend((0..CONFIRMED_DATA_BYTES).map(|_| 0));
+    }
+
+    /// Verify the packet checksum and strip the extended address header and pads from
+    /// the buffered data.
+    fn finish(&self, header: DataHeader) -> PduEvent {
+        let (data, checksum) = self.data.split_at(self.data.len() - CHECKSUM_BYTES);
+
+        let crc = crc::CRC32::new()
+            .feed_bytes(data.iter().cloned())
+            .finish() as u32;
+
+        let end = data.len().saturating_sub(header.pads as usize);
+
+        let (start, ext) = if header.extended() {
+            let ext = if self.bad.contains(&0) {
+                None
+            } else {
+                ExtendedAddress::decode(&data[..end]).ok()
+            };
+
+            (EXTENDED_ADDRESS_BYTES.min(end), ext)
+        } else {
+            (0, None)
+        };
+
+        if self.bad.is_empty() {
+            return PduEvent::Packet {
+                header: header,
+                ext: ext,
+                payload: data[start..end].to_vec(),
+                crc_ok: slice_u32(checksum) == crc,
+                fec: self.fec.clone(),
+            };
+        }
+
+        let bad = self.bad.iter().map(|&b| {
+            let start = b * CONFIRMED_DATA_BYTES;
+            start..start + CONFIRMED_DATA_BYTES
+        }).filter_map(|r| if r.start < end && r.end > start {
+            Some(r.start.max(start) - start..r.end.min(end) - start)
+        } else {
+            None
+        }).collect();
+
+        PduEvent::Partial {
+            header: header,
+            ext: ext,
+            payload: data[start..end].to_vec(),
+            serials: self.serials,
+            bad: bad,
+            fec: self.fec.clone(),
+        }
+    }
+}
+
+impl HasStats for PduAssembler {
+    fn stats(&mut self) -> &mut Stats { &mut self.stats }
+}
+
+/// Receives the coded blocks of a data packet from a stream of dibits and assembles
+/// them into a packet.
+pub struct PduReceiver {
+    /// Current buffered dibits.
+    dibits: Buffer<DataPayloadStorage>,
+    /// Assembler for received blocks.
+    asm: PduAssembler,
+}
+
+impl PduReceiver {
+    /// Create a new `PduReceiver` waiting for a header block.
+    pub fn new() -> PduReceiver {
+        PduReceiver {
+            dibits: Buffer::new(DataPayloadStorage::new()),
+            asm: PduAssembler::new(),
+        }
+    }
+
+    /// Feed in a data symbol, returning an event if it completes a block that completes
+    /// or aborts a packet.
+    pub fn feed(&mut self, dibit: Dibit) -> Option<PduEvent> {
+        match self.dibits.feed(dibit) {
+            Some(buf) => self.asm.feed(buf),
+            None => None,
+        }
+    }
+}
+
+impl HasStats for PduReceiver {
+    fn stats(&mut self) -> &mut Stats { self.asm.stats() }
+}
+
+/// Set of confirmed block serial numbers.
+///
+/// In a packet that isn't a partial retransmission, each block's serial number is its
+/// index in the packet.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SerialBitmap(u128);
+
+impl SerialBitmap {
+    /// Add the given 7-bit serial number to the set.
+    pub fn set(&mut self, sn: u8) {
+        assert!(sn >> 7 == 0);
+        self.0 |= 1 << sn;
+    }
+
+    /// Whether the given serial number is in the set.
+    pub fn contains(&self, sn: u8) -> bool { sn >> 7 == 0 && self.0 >> sn & 1 == 1 }
+
+    /// Serial numbers below the given count that aren't in the set.
+    pub fn missing(&self, count: u8) -> Vec<u8> {
+        (0..count).filter(|&sn| !self.contains(sn)).collect()
+    }
+}
+
+/// Buffer of bytes that represents a decoded confirmed data block.
+pub type ConfirmedBuf = [u8; CONFIRMED_BYTES];
+
+/// Decoded confirmed data block.
+#[derive(Copy, Clone)]
+pub struct ConfirmedBlock(ConfirmedBuf);
+
+impl ConfirmedBlock {
+    /// Create a new `ConfirmedBlock` from the given block bytes.
+    pub fn new(buf: ConfirmedBuf) -> ConfirmedBlock { ConfirmedBlock(buf) }
+
+    /// 7-bit serial number of the block within the packet.
+    pub fn serial(&self) -> u8 { self.0[0] >> 1 }
+
+    /// Received 9-bit block checksum.
+    pub fn crc(&self) -> u16 { (self.0[0] as u16 & 1) << 8 | self.0[1] as u16 }
+
+    /// Data and pad bytes (and packet checksum for the last block) of the block.
+    pub fn data(&self) -> &[u8] { &self.0[2..] }
+
+    /// Calculate the 9-bit checksum over the serial number and data, where the checksum
+    /// of the last block in a packet doesn't cover the packet checksum.
+    pub fn calc_crc(&self, last: bool) -> u16 {
+        let data = if last {
+            &self.data()[..CONFIRMED_BYTES - 2 - CHECKSUM_BYTES]
+        } else {
+            self.data()
+        };
+
+        crc::CRC9::new()
+            .feed_bits(self.serial(), 7)
+            .feed_bytes(data.iter().cloned())
+            .finish() as u16
+    }
+
+    /// Whether the received checksum matches the calculated one.
+    pub fn crc_valid(&self, last: bool) -> bool { self.crc() == self.calc_crc(last) }
+}
+
+/// Decode the given deinterleaved 1/2-rate coded block into bytes.
+fn decode_dibits(buf: &[Dibit]) -> Result<[u8; UNCONFIRMED_BYTES]> {
+    let mut dibits = [Dibit::default(); TSBK_DIBITS];
+    let count = trellis::DibitDecoder::new(buf.iter().cloned())
+        .filter_map(|x| x.ok())
+        .collect_slice_exhaust(&mut dibits[..]);
+
+    if count != dibits.len() {
+        return Err(P25Error::DibitViterbiUnrecoverable);
+    }
+
+    let mut bytes = [0; UNCONFIRMED_BYTES];
+    DibitBytes::new(dibits.iter().cloned()).collect_slice_checked(&mut bytes[..]);
+
+    Ok(bytes)
+}
+
+/// Decode the given deinterleaved 3/4-rate coded block into bytes.
+fn decode_tribits(buf: &[Dibit]) -> Result<ConfirmedBuf> {
+    let mut tribits = [Tribit::default(); CONFIRMED_TRIBITS];
+    let count = trellis::TribitDecoder::new(buf.iter().cloned())
+        .filter_map(|x| x.ok())
+        .collect_slice_exhaust(&mut tribits[..]);
+
+    if count != tribits.len() {
+        return Err(P25Error::TribitViterbiUnrecoverable);
+    }
+
+    let mut bytes = [0; CONFIRMED_BYTES];
+    TribitBytes::new(tribits.iter().cloned()).collect_slice_checked(&mut bytes[..]);
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use std;
+    use super::*;
+    use data::fields::ServiceAccessPoint as Sap;
+    use data::header::*;
+    use data::packet;
+    use data::payload::{ConfirmedPayload, UnconfirmedPayload};
+    use data::response::{ResponseClass, ResponseType};
+
+    /// Feed the given packet dibits through an assembler block by block.
+    fn assemble(dibits: &[Dibit]) -> Vec<PduEvent> {
+        let mut a = PduAssembler::new();
+
+        dibits.chunks(CODING_DIBITS).filter_map(|chunk| {
+            let mut buf = [Dibit::default(); CODING_DIBITS];
+            buf.copy_from_slice(chunk);
+            a.feed(&buf)
+        }).collect()
+    }
+
+    /// Flip bits in the given block of the given packet.
+    fn corrupt(dibits: &mut [Dibit], block: usize) {
+        let start = block * CODING_DIBITS;
+
+        for d in dibits[start..start + CODING_DIBITS].iter_mut().step_by(3) {
+            *d = Dibit::new(d.bits() ^ 0b11);
+        }
+    }
+
+    fn unconfirmed(data: &[u8]) -> Vec<Dibit> { unconfirmed_sap(Sap::PacketData, data) }
+
+    fn unconfirmed_sap(sap: Sap, data: &[u8]) -> Vec<Dibit> {
+        let payload = UnconfirmedPayload::new(data);
+
+        packet::unconfirmed(UnconfirmedHeader::new(UnconfirmedFields {
+            preamble: UnconfirmedPreamble::outbound(),
+            sap: ServiceAccessPoint(sap),
+            mfg: Manufacturer(0),
+            addr: LogicalLink(0x123456),
+            blocks: BlockCount {
+                full_pkt: true,
+                count: payload.blocks() as u8,
+            },
+            pads: PadCount(payload.pads() as u8),
+            data_offset: DataOffset(0),
+        }), payload)
+    }
+
+    fn confirmed(data: &[u8]) -> Vec<Dibit> { confirmed_sap(Sap::PacketData, data) }
+
+    fn confirmed_sap(sap: Sap, data: &[u8]) -> Vec<Dibit> {
+        let payload = ConfirmedPayload::new(data);
+
+        packet::confirmed(ConfirmedHeader::new(ConfirmedFields {
+            preamble: ConfirmedPreamble::inbound(),
+            sap: ServiceAccessPoint(sap),
+            mfg: Manufacturer(0),
+            addr: LogicalLink(0x654321),
+            blocks: BlockCount {
+                full_pkt: true,
+                count: payload.blocks() as u8,
+            },
+            pads: PadCount(payload.pads() as u8),
+            seq: Sequencing {
+                resync: false,
+                pkt_seq: 1,
+                frag_seq: 0,
+            },
+            data_offset: DataOffset(0),
+        }), payload, 0..)
+    }
+
+    #[test]
+    fn test_unconfirmed() {
+        let data: Vec<u8> = (0..30).collect();
+        let events = assemble(&unconfirmed(&data));
+
+        match events[..] {
+            [PduEvent::Packet {
+                ref header, ext: None, ref payload, crc_ok, ref fec
+            }] => {
+                assert!(!header.confirmed());
+                assert_eq!(header.llid, 0x123456);
+                assert_eq!(header.blocks, 3);
+                assert_eq!(header.pads, 2);
+                assert_eq!(payload, &data);
+                assert!(crc_ok);
+                assert_eq!(fec, &FecStats {
+                    header: 0,
+                    blocks: vec![Some(0); 3],
+                });
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_receiver() {
+        let data: Vec<u8> = (0..30).collect();
+        let dibits = unconfirmed(&data);
+
+        let mut r = PduReceiver::new();
+        let events: Vec<PduEvent> = dibits.iter().filter_map(|&d| r.feed(d)).collect();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events, assemble(&dibits));
+    }
+
+    #[test]
+    fn test_confirmed() {
+        let data: Vec<u8> = (0..40).collect();
+        let dibits = confirmed(&data);
+
+        match assemble(&dibits)[..] {
+            [PduEvent::Packet { ref header, ref payload, crc_ok, .. }] => {
+                assert!(header.confirmed());
+                assert_eq!(header.llid, 0x654321);
+                assert_eq!(header.blocks, 3);
+                assert_eq!(payload, &data);
+                assert!(crc_ok);
+            },
+            _ => panic!(),
+        }
+
+        // Corrupt the second data block beyond repair.
+        let mut bad = dibits.clone();
+        corrupt(&mut bad, 2);
+
+        match assemble(&bad)[..] {
+            [PduEvent::Partial {
+                ref header, ref payload, serials, bad: ref ranges, ..
+            }] => {
+                assert_eq!(header.llid, 0x654321);
+                assert_eq!(serials.missing(3), vec![1]);
+                assert_eq!(ranges, &vec![16..32]);
+                assert_eq!(&payload[..16], &data[..16]);
+                assert_eq!(&payload[32..], &data[32..]);
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_retry_bitmap() {
+        let data: Vec<u8> = (0..90).collect();
+        let mut dibits = confirmed(&data);
+
+        // Corrupt data blocks 2 and 5, after the header.
+        corrupt(&mut dibits, 3);
+        corrupt(&mut dibits, 6);
+
+        match assemble(&dibits)[..] {
+            [PduEvent::Partial { ref header, ref payload, serials, ref bad, .. }] => {
+                assert_eq!(header.blocks, 6);
+                assert_eq!(serials.missing(6), vec![2, 5]);
+
+                for sn in [0, 1, 3, 4].iter() {
+                    assert!(serials.contains(*sn));
+                }
+
+                // The last block's range is clipped to the data before the pads.
+                assert_eq!(bad, &vec![32..48, 80..90]);
+                assert_eq!(payload.len(), 90);
+                assert_eq!(&payload[..32], &data[..32]);
+                assert!(payload[32..48].iter().all(|&b| b == 0));
+                assert_eq!(&payload[48..80], &data[48..80]);
+                assert!(payload[80..].iter().all(|&b| b == 0));
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_extended() {
+        let data: Vec<u8> = (0..40).collect();
+        let addr = ExtendedAddress {
+            sap: Sap::PacketData,
+            llid: 0x00ABCD,
+        };
+
+        let mut ext_data = addr.encode().to_vec();
+        ext_data.extend(data.iter().cloned());
+
+        let normal = match assemble(&unconfirmed(&data))[..] {
+            [PduEvent::Packet { header, ext: None, ref payload, crc_ok: true, .. }] => {
+                assert!(!header.extended());
+                assert_eq!(header.sap, Sap::PacketData);
+                payload.clone()
+            },
+            _ => panic!(),
+        };
+
+        match assemble(&unconfirmed_sap(Sap::ExtendedAddressing, &ext_data))[..] {
+            [PduEvent::Packet { header, ext, ref payload, crc_ok: true, .. }] => {
+                assert!(header.extended());
+                assert_eq!(header.llid, 0x123456);
+                assert_eq!(ext, Some(addr));
+                assert_eq!(payload, &normal);
+            },
+            _ => panic!(),
+        }
+
+        // Without the first data block, the extended address is unknown, but the bad
+        // ranges still line up with the payload.
+        let mut dibits = confirmed_sap(Sap::ExtendedAddressing, &ext_data);
+        corrupt(&mut dibits, 1);
+
+        match assemble(&dibits)[..] {
+            [PduEvent::Partial { ext, ref payload, ref bad, .. }] => {
+                assert_eq!(ext, None);
+                assert_eq!(bad, &vec![0..12]);
+                assert_eq!(payload.len(), 40);
+                assert_eq!(&payload[12..], &data[12..]);
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_serial_bitmap() {
+        let mut b = SerialBitmap::default();
+        assert_eq!(b.missing(3), vec![0, 1, 2]);
+
+        b.set(0);
+        b.set(127);
+        assert!(b.contains(0));
+        assert!(b.contains(127));
+        assert!(!b.contains(128));
+        assert_eq!(b.missing(3), vec![1, 2]);
+    }
+
+    /// Deterministic source of normally distributed noise.
+    struct Noise(u32);
+
+    impl Noise {
+        /// Uniformly distributed value in (0, 1].
+        fn uniform(&mut self) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+
+            self.0 as f32 / std::u32::MAX as f32
+        }
+
+        /// Normally distributed value with the given standard deviation.
+        fn gaussian(&mut self, sigma: f32) -> f32 {
+            let (u, v) = (self.uniform(), self.uniform());
+            sigma * (-2.0 * u.ln()).sqrt() * (2.0 * std::f32::consts::PI * v).cos()
+        }
+    }
+
+    #[test]
+    fn test_soft_decision() {
+        let mut noise = Noise(0x1234_5678);
+        let mut hard = PduAssembler::new();
+        let mut soft = PduAssembler::new();
+        let (mut hard_ok, mut soft_ok) = (0, 0);
+
+        for i in 0..40 {
+            let data: Vec<u8> = (0..30).map(|b| b ^ i).collect();
+            let received: Vec<SoftDibit> = unconfirmed(&data).into_iter()
+                .map(|d| SoftDibit::ideal(d).value() + noise.gaussian(0.5))
+                .map(SoftDibit::new)
+                .collect();
+
+            let complete = |e: Option<PduEvent>| match e {
+                Some(PduEvent::Packet { ref payload, crc_ok: true, .. }) =>
+                    payload == &data,
+                _ => false,
+            };
+
+            for chunk in received.chunks(CODING_DIBITS) {
+                let mut buf = [SoftDibit::default(); CODING_DIBITS];
+                buf.copy_from_slice(chunk);
+
+                let mut dibits = [Dibit::default(); CODING_DIBITS];
+
+                for (d, s) in dibits.iter_mut().zip(buf.iter()) {
+                    *d = s.hard();
+                }
+
+                if complete(hard.feed(&dibits)) {
+                    hard_ok += 1;
+                }
+
+                if complete(soft.feed_soft(&buf)) {
+                    soft_ok += 1;
+                }
+            }
+
+            hard.reset();
+            soft.reset();
+        }
+
+        assert!(soft_ok > hard_ok);
+    }
+
+    #[test]
+    fn test_response() {
+        let resp = DataResponse {
+            outbound: false,
+            class: ResponseClass::Nack,
+            type_: ResponseType::NsSequence,
+            status: 2,
+            mfg: 0,
+            llid: 0x123456,
+            source: Some(0x000042),
+            blocks: 0,
+        };
+
+        let data: Vec<u8> = (0..20).collect();
+        let mut dibits = packet::response(&resp);
+        dibits.extend(unconfirmed(&data));
+
+        match assemble(&dibits)[..] {
+            [PduEvent::Response(r), PduEvent::Packet { ref payload, crc_ok, .. }] => {
+                assert!(crc_ok);
+                assert_eq!(r, resp);
+                assert_eq!(payload, &data);
+            },
+            _ => panic!(),
+        }
+
+        // The bitmap block of a selective retry is skipped.
+        let retry = DataResponse {
+            class: ResponseClass::SelectiveRetry,
+            type_: ResponseType::SelectiveRetry,
+            blocks: 1,
+            ..resp
+        };
+
+        let code = |bytes: &[u8]| interleave::Interleaver::new(coder::DibitCoder::new()
+            .feed_bytes(bytes.iter().cloned())
+            .finish());
+
+        let mut dibits: Vec<Dibit> = code(&retry.encode()).collect();
+        dibits.extend(code(&[0xFF; UNCONFIRMED_BYTES]));
+        dibits.extend(unconfirmed(&data));
+
+        match assemble(&dibits)[..] {
+            [PduEvent::Response(r), PduEvent::Packet { ref payload, .. }] => {
+                assert_eq!(r, retry);
+                assert_eq!(payload, &data);
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_bad_header() {
+        let mut dibits = unconfirmed(&[1, 2, 3]);
+        corrupt(&mut dibits, 0);
+
+        match assemble(&dibits[..CODING_DIBITS])[..] {
+            [PduEvent::BadHeader(_)] => {},
+            _ => panic!(),
+        }
+    }
+}
@@ -0,0 +1,283 @@
+//! Track active SNDCP data contexts from the context management exchanges between
+//! subscribers and the FNE.
+
+use std::net::Ipv4Addr;
+use std::vec::Vec;
+
+use data::fields::ServiceAccessPoint;
+use data::header::DataHeader;
+use data::sndcp::{NetworkAddressType, SndcpMessage};
+
+/// Active data context of a subscriber.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DataContext {
+    /// Logical link ID of the subscriber.
+    pub llid: u32,
+    /// NSAPI identifying the context among the subscriber's contexts.
+    pub nsapi: u8,
+    /// Type of address assigned.
+    pub address_type: NetworkAddressType,
+    /// IP address assigned to the context.
+    pub address: Ipv4Addr,
+    /// Data subscriber unit type (DSUT) of the subscriber, if its activation request was
+    /// received.
+    pub dsut: Option<u8>,
+    /// Priority of data sent on the context.
+    pub priority: u8,
+    /// Code for the ready timer assigned to the context.
+    pub ready_timer: u8,
+    /// Code for the standby timer assigned to the context.
+    pub standby_timer: u8,
+    /// Time the context was activated.
+    pub activated: u64,
+    /// Time of the most recent activity on the context.
+    pub updated: u64,
+}
+
+/// Change in the state of a data context.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ContextEvent {
+    /// A context was activated.
+    ContextUp(DataContext),
+    /// A context was deactivated or timed out.
+    ContextDown(DataContext),
+}
+
+/// Activation request waiting for a response.
+#[derive(Copy, Clone, Debug)]
+struct PendingRequest {
+    /// Logical link ID of the requesting subscriber.
+    llid: u32,
+    /// NSAPI of the requested context.
+    nsapi: u8,
+    /// DSUT of the requesting subscriber.
+    dsut: u8,
+    /// Time the request was received.
+    time: u64,
+}
+
+/// Maintains the table of active data contexts of each subscriber.
+///
+/// Times are in arbitrary caller-defined units. A context is activated by an accept
+/// message, even if the matching request wasn't received, such as when monitoring
+/// starts partway through an exchange. A context ends when it's deactivated or when
+/// nothing has been sent on it for longer than `standby`.
+pub struct SndcpTracker {
+    /// Currently active contexts.
+    contexts: Vec<DataContext>,
+    /// Activation requests that haven't been answered.
+    pending: Vec<PendingRequest>,
+    /// Time without activity after which a context ends.
+    standby: u64,
+}
+
+impl SndcpTracker {
+    /// Create a new `SndcpTracker` with no active contexts.
+    pub fn new(standby: u64) -> SndcpTracker {
+        SndcpTracker {
+            contexts: vec![],
+            pending: vec![],
+            standby: standby,
+        }
+    }
+
+    /// Handle the SNDCP message carried by the given data packet, received at the given
+    /// time, and return any resulting context events.
+    pub fn feed_packet(&mut self, header: &DataHeader, payload: &[u8], time: u64)
+        -> Vec<ContextEvent>
+    {
+        match header.sap {
+            ServiceAccessPoint::SNDCPControl | ServiceAccessPoint::PacketData => {},
+            _ => return vec![],
+        }
+
+        match SndcpMessage::parse(payload, header.outbound) {
+            Ok(msg) => self.feed(header.llid, &msg, time),
+            Err(_) => vec![],
+        }
+    }
+
+    /// Handle the given SNDCP message, sent to or from the subscriber with the given
+    /// logical link ID at the given time, and return any resulting context events.
+    pub fn feed(&mut self, llid: u32, msg: &SndcpMessage, time: u64)
+        -> Vec<ContextEvent>
+    {
+        let mut events = vec![];
+
+        match *msg {
+            SndcpMessage::ActivateRequest(r) => {
+                self.pending.retain(|p| p.llid != llid || p.nsapi != r.nsapi());
+                self.pending.push(PendingRequest {
+                    llid: llid,
+                    nsapi: r.nsapi(),
+                    dsut: r.dsut(),
+                    time: time,
+                });
+            },
+            SndcpMessage::ActivateAccept(a) => {
+                let dsut = self.take_pending(llid, a.nsapi()).map(|p| p.dsut);
+
+                // A repeated accept replaces the existing context.
+                self.remove(llid, Some(a.nsapi()), &mut events);
+
+                let ctx = DataContext {
+                    llid: llid,
+                    nsapi: a.nsapi(),
+                    address_type: a.address_type(),
+                    address: a.address(),
+                    dsut: dsut,
+                    priority: a.priority(),
+                    ready_timer: a.ready_timer(),
+                    standby_timer: a.standby_timer(),
+                    activated: time,
+                    updated: time,
+                };
+
+                self.contexts.push(ctx);
+                events.push(ContextEvent::ContextUp(ctx));
+            },
+            SndcpMessage::ActivateReject(r) => {
+                self.take_pending(llid, r.nsapi());
+            },
+            SndcpMessage::DeactivateRequest(r) => self.remove(llid,
+                if r.all() { None } else { Some(r.nsapi()) }, &mut events),
+            SndcpMessage::DeactivateAccept(nsapi) =>
+                self.remove(llid, Some(nsapi), &mut events),
+            SndcpMessage::Data(d) => {
+                if let Some(ctx) = self.contexts.iter_mut()
+                    .find(|c| c.llid == llid && c.nsapi == d.nsapi())
+                {
+                    ctx.updated = time;
+                }
+            },
+        }
+
+        events
+    }
+
+    /// End all contexts without activity for longer than the standby time at the given
+    /// time, returning a `ContextDown` event for each.
+    pub fn expire(&mut self, time: u64) -> Vec<ContextEvent> {
+        let standby = self.standby;
+
+        self.pending.retain(|p| time.saturating_sub(p.time) <= standby);
+
+        let (ended, active): (Vec<DataContext>, Vec<DataContext>) = self.contexts
+            .drain(..)
+            .partition(|c| time.saturating_sub(c.updated) > standby);
+
+        self.contexts = active;
+
+        ended.into_iter().map(ContextEvent::ContextDown).collect()
+    }
+
+    /// Currently active contexts, in the order they were activated.
+    pub fn contexts(&self) -> &[DataContext] { &self.contexts[..] }
+
+    /// Find the active context with the given NSAPI of the given subscriber.
+    pub fn context(&self, llid: u32, nsapi: u8) -> Option<&DataContext> {
+        self.contexts.iter().find(|c| c.llid == llid && c.nsapi == nsapi)
+    }
+
+    /// Remove and return the pending request for the given context.
+    fn take_pending(&mut self, llid: u32, nsapi: u8) -> Option<PendingRequest> {
+        let pos = self.pending.iter().position(|p| p.llid == llid && p.nsapi == nsapi)?;
+        Some(self.pending.remove(pos))
+    }
+
+    /// Deactivate the given context of the given subscriber, or all its contexts if no
+    /// NSAPI is given.
+    fn remove(&mut self, llid: u32, nsapi: Option<u8>, events: &mut Vec<ContextEvent>) {
+        let matches = |c: &DataContext| {
+            c.llid == llid && nsapi.map_or(true, |n| c.nsapi == n)
+        };
+
+        for &ctx in self.contexts.iter().filter(|c| matches(c)) {
+            events.push(ContextEvent::ContextDown(ctx));
+        }
+
+        self.contexts.retain(|c| !matches(c));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const UNIT: u32 = 0x001234;
+
+    fn request() -> [u8; 7] { [0x01, 0x10, 0, 0, 0, 0, 0x20] }
+
+    fn accept() -> [u8; 10] { [0x01, 0x42, 0x31, 10, 0, 0, 7, 0x00, 0x06, 0x00] }
+
+    fn feed(t: &mut SndcpTracker, buf: &[u8], outbound: bool, time: u64)
+        -> Vec<ContextEvent>
+    {
+        t.feed(UNIT, &SndcpMessage::parse(buf, outbound).unwrap(), time)
+    }
+
+    #[test]
+    fn test_activate_deactivate() {
+        let mut t = SndcpTracker::new(100);
+
+        assert!(feed(&mut t, &request(), false, 0).is_empty());
+        assert!(t.contexts().is_empty());
+
+        let ctx = match feed(&mut t, &accept(), true, 1)[..] {
+            [ContextEvent::ContextUp(ctx)] => ctx,
+            _ => panic!(),
+        };
+
+        assert_eq!(ctx.llid, UNIT);
+        assert_eq!(ctx.nsapi, 1);
+        assert_eq!(ctx.address_type, NetworkAddressType::DynamicIpv4);
+        assert_eq!(ctx.address, Ipv4Addr::new(10, 0, 0, 7));
+        assert_eq!(ctx.dsut, Some(2));
+        assert_eq!(ctx.priority, 4);
+        assert_eq!(ctx.ready_timer, 2);
+        assert_eq!(ctx.standby_timer, 3);
+        assert_eq!(t.context(UNIT, 1), Some(&ctx));
+
+        // Data on the context keeps it alive.
+        assert!(feed(&mut t, &[0x41, 0x00, 0x45], true, 90).is_empty());
+        assert!(t.expire(150).is_empty());
+        assert_eq!(t.context(UNIT, 1).unwrap().updated, 90);
+
+        match feed(&mut t, &[0x21, 0x01], false, 160)[..] {
+            [ContextEvent::ContextDown(c)] => assert_eq!(c.nsapi, 1),
+            _ => panic!(),
+        }
+
+        assert!(t.contexts().is_empty());
+
+        // The accept for a context that's already down is ignored.
+        assert!(feed(&mut t, &[0x11], true, 161).is_empty());
+    }
+
+    #[test]
+    fn test_mid_join() {
+        let mut t = SndcpTracker::new(100);
+
+        // The request was missed, so the DSUT isn't known.
+        match feed(&mut t, &accept(), true, 0)[..] {
+            [ContextEvent::ContextUp(ctx)] => {
+                assert_eq!(ctx.address, Ipv4Addr::new(10, 0, 0, 7));
+                assert_eq!(ctx.dsut, None);
+            },
+            _ => panic!(),
+        }
+
+        // A repeated accept replaces the context.
+        assert_eq!(feed(&mut t, &accept(), true, 10).len(), 2);
+        assert_eq!(t.contexts().len(), 1);
+
+        assert!(t.expire(110).is_empty());
+
+        match t.expire(111)[..] {
+            [ContextEvent::ContextDown(ctx)] => assert_eq!(ctx.activated, 10),
+            _ => panic!(),
+        }
+
+        assert!(t.contexts().is_empty());
+    }
+}
@@ -1,10 +1,13 @@
-//! Header generation for data packets.
+//! Header generation and decoding for data packets.
 //!
 //! A header has several user-filled fields followed by a 16-bit checksum over those
 //! fields.
 
 use data::crc;
 use data::fields;
+use data::response::DataResponse;
+use error::{P25Error, Result};
+use util::{slice_u16, slice_u24};
 
 /// Packet header block for confirmed data packet.
 pub type ConfirmedHeader = Header<ConfirmedFields>;
@@ -141,6 +144,7 @@ impl ByteField for PadCount {
 }
 
 /// Syn, N(S), and FSNF fields.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct Sequencing {
     /// Whether the receiver should resynchronize its sequence numbers using `pkt_seq` and
     /// `frag_seq`.
@@ -151,6 +155,17 @@ pub struct Sequencing {
     pub frag_seq: u8,
 }
 
+impl Sequencing {
+    /// Parse the fields from the given byte.
+    pub fn from_byte(byte: u8) -> Sequencing {
+        Sequencing {
+            resync: byte >> 7 == 1,
+            pkt_seq: byte >> 4 & 0b111,
+            frag_seq: byte & 0xF,
+        }
+    }
+}
+
 impl ByteField for Sequencing {
     fn byte(&self) -> u8 {
         assert!(self.pkt_seq >> 3 == 0);
@@ -255,6 +270,172 @@ impl<F: BufWrite> Header<F> {
     }
 }
 
+/// Decoded header of a confirmed or unconfirmed data packet.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DataHeader {
+    /// Whether the packet is an outbound message.
+    pub outbound: bool,
+    /// Packet type, either confirmed or unconfirmed.
+    pub format: fields::DataPacketOpcode,
+    /// Destination service of the packet.
+    pub sap: fields::ServiceAccessPoint,
+    /// Manufacturer's ID.
+    pub mfg: u8,
+    /// Logical link ID of the source or destination subscriber.
+    pub llid: u32,
+    /// Whether the packet is "complete", not being partially retransmitted.
+    pub full_pkt: bool,
+    /// Number of data blocks that follow the header.
+    pub blocks: u8,
+    /// Number of pad bytes at the end of the data.
+    pub pads: u8,
+    /// Sequence numbers, present only for confirmed packets.
+    pub seq: Option<Sequencing>,
+    /// Byte offset into data payload where data header stops and data information
+    /// begins.
+    pub data_offset: u8,
+}
+
+impl DataHeader {
+    /// Decode a header from the given fields and checksum.
+    ///
+    /// Return `CrcMismatch` if the checksum doesn't match the fields and
+    /// `UnsupportedFormat` if the packet isn't a confirmed or unconfirmed packet or its
+    /// SAP isn't assigned.
+    pub fn decode(buf: &[u8; 12]) -> Result<DataHeader> {
+        let crc = crc::CRC16::new()
+            .feed_bytes(buf[..10].iter().cloned())
+            .finish() as u16;
+
+        let received = slice_u16(&buf[10..]);
+
+        if received != crc {
+            return Err(P25Error::CrcMismatch {
+                received: received as u32,
+                computed: crc as u32,
+            });
+        }
+
+        let format = match fields::DataPacketOpcode::from_bits(buf[0] & 0x1F) {
+            Some(f @ fields::DataPacketOpcode::ConfirmedPacket) |
+                Some(f @ fields::DataPacketOpcode::UnconfirmedPacket) => f,
+            _ => return Err(P25Error::UnsupportedFormat {
+                field: "packet format",
+                value: (buf[0] & 0x1F) as u32,
+            }),
+        };
+
+        let sap = match fields::ServiceAccessPoint::from_bits(buf[1] & 0x3F) {
+            Some(sap) => sap,
+            None => return Err(P25Error::UnsupportedFormat {
+                field: "SAP",
+                value: (buf[1] & 0x3F) as u32,
+            }),
+        };
+
+        Ok(DataHeader {
+            outbound: buf[0] >> 5 & 1 == 1,
+            format: format,
+            sap: sap,
+            mfg: buf[2],
+            llid: slice_u24(&buf[3..=5]),
+            full_pkt: buf[6] >> 7 == 1,
+            blocks: buf[6] & 0x7F,
+            pads: buf[7] & 0x1F,
+            seq: match format {
+                fields::DataPacketOpcode::ConfirmedPacket =>
+                    Some(Sequencing::from_byte(buf[8])),
+                _ => None,
+            },
+            data_offset: buf[9] & 0x3F,
+        })
+    }
+
+    /// Whether the packet requires confirmation.
+    pub fn confirmed(&self) -> bool {
+        self.format == fields::DataPacketOpcode::ConfirmedPacket
+    }
+
+    /// Whether the data begins with an extended address header.
+    pub fn extended(&self) -> bool {
+        self.sap == fields::ServiceAccessPoint::ExtendedAddressing
+    }
+}
+
+/// Number of bytes in an extended address header.
+pub const EXTENDED_ADDRESS_BYTES: usize = 4;
+
+/// Second header at the start of the data of a packet that uses extended addressing,
+/// giving the actual SAP of the data and the logical link ID of the source subscriber.
+///
+/// The header is covered by the packet checksum along with the rest of the data.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ExtendedAddress {
+    /// Service the data is for.
+    pub sap: fields::ServiceAccessPoint,
+    /// Logical link ID of the source subscriber.
+    pub llid: u32,
+}
+
+impl ExtendedAddress {
+    /// Decode an extended address header from the start of the given data.
+    ///
+    /// Return `Truncated` if the data is too short to hold the header and
+    /// `UnsupportedFormat` if its SAP isn't assigned.
+    pub fn decode(buf: &[u8]) -> Result<ExtendedAddress> {
+        if buf.len() < EXTENDED_ADDRESS_BYTES {
+            return Err(P25Error::Truncated {
+                needed: EXTENDED_ADDRESS_BYTES,
+                got: buf.len(),
+            });
+        }
+
+        let sap = match fields::ServiceAccessPoint::from_bits(buf[0] & 0x3F) {
+            Some(sap) => sap,
+            None => return Err(P25Error::UnsupportedFormat {
+                field: "SAP",
+                value: (buf[0] & 0x3F) as u32,
+            }),
+        };
+
+        Ok(ExtendedAddress {
+            sap: sap,
+            llid: slice_u24(&buf[1..=3]),
+        })
+    }
+
+    /// Encode the header into its bytes.
+    pub fn encode(&self) -> [u8; EXTENDED_ADDRESS_BYTES] {
+        [
+            self.sap.to_bits(),
+            (self.llid >> 16) as u8,
+            (self.llid >> 8) as u8,
+            self.llid as u8,
+        ]
+    }
+}
+
+/// Decoded header block of any supported packet format.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PduHeader {
+    /// Header of a confirmed or unconfirmed data packet.
+    Data(DataHeader),
+    /// Header of a response packet.
+    Response(DataResponse),
+}
+
+impl PduHeader {
+    /// Decode a header block, using its format field to determine how the rest of the
+    /// block is laid out.
+    pub fn decode(buf: &[u8; 12]) -> Result<PduHeader> {
+        match fields::DataPacketOpcode::from_bits(buf[0] & 0x1F) {
+            Some(fields::DataPacketOpcode::ResponsePacket) =>
+                DataResponse::decode(buf).map(PduHeader::Response),
+            _ => DataHeader::decode(buf).map(PduHeader::Data),
+        }
+    }
+}
+
 /// Convert the given Boolean to a single bit.
 fn bool_to_bit(b: bool) -> u8 {
     if b { 1 } else { 0 }
@@ -263,7 +444,9 @@ fn bool_to_bit(b: bool) -> u8 {
 #[cfg(test)]
 mod test {
     use super::*;
-    use data::fields;
+    use data::{crc, fields};
+    use data::response::{ResponseClass, ResponseType};
+    use error::P25Error;
 
     #[test]
     fn test_preamble() {
@@ -401,6 +584,176 @@ mod test {
         ]);
     }
 
+    #[test]
+    fn test_decode_confirmed() {
+        let h = DataHeader::decode(&[
+            0b01110110,
+            0b11000100,
+            0b00010010,
+            0b00110100,
+            0b00100001,
+            0b00110100,
+            0b11111111,
+            0b00000011,
+            0b01010010,
+            0b00000000,
+            0b10001010,
+            0b01110010,
+        ]).unwrap();
+
+        assert!(h.outbound);
+        assert!(h.confirmed());
+        assert_eq!(h.format, fields::DataPacketOpcode::ConfirmedPacket);
+        assert_eq!(h.sap, fields::ServiceAccessPoint::PacketData);
+        assert_eq!(h.mfg, 0x12);
+        assert_eq!(h.llid, 0x342134);
+        assert!(h.full_pkt);
+        assert_eq!(h.blocks, 127);
+        assert_eq!(h.pads, 3);
+        assert_eq!(h.seq, Some(Sequencing {
+            resync: false,
+            pkt_seq: 5,
+            frag_seq: 2,
+        }));
+        assert_eq!(h.data_offset, 0);
+    }
+
+    #[test]
+    fn test_decode_unconfirmed() {
+        let (head, checksum) = UnconfirmedHeader::new(UnconfirmedFields {
+            preamble: UnconfirmedPreamble::inbound(),
+            sap: ServiceAccessPoint(fields::ServiceAccessPoint::SNDCPControl),
+            mfg: Manufacturer(0x90),
+            addr: LogicalLink(0xABCDEF),
+            blocks: BlockCount {
+                full_pkt: false,
+                count: 3,
+            },
+            pads: PadCount(17),
+            data_offset: DataOffset(12),
+        }).build();
+
+        let mut buf = [0; 12];
+        buf[..10].copy_from_slice(&head);
+        buf[10..].copy_from_slice(&checksum);
+
+        let h = DataHeader::decode(&buf).unwrap();
+
+        assert!(!h.outbound);
+        assert!(!h.confirmed());
+        assert_eq!(h.format, fields::DataPacketOpcode::UnconfirmedPacket);
+        assert_eq!(h.sap, fields::ServiceAccessPoint::SNDCPControl);
+        assert_eq!(h.mfg, 0x90);
+        assert_eq!(h.llid, 0xABCDEF);
+        assert!(!h.full_pkt);
+        assert_eq!(h.blocks, 3);
+        assert_eq!(h.pads, 17);
+        assert_eq!(h.seq, None);
+        assert_eq!(h.data_offset, 12);
+    }
+
+    #[test]
+    fn test_route() {
+        let (head, checksum) = UnconfirmedHeader::new(UnconfirmedFields {
+            preamble: UnconfirmedPreamble::outbound(),
+            sap: ServiceAccessPoint(fields::ServiceAccessPoint::PacketData),
+            mfg: Manufacturer(0),
+            addr: LogicalLink(0x123456),
+            blocks: BlockCount {
+                full_pkt: true,
+                count: 1,
+            },
+            pads: PadCount(0),
+            data_offset: DataOffset(0),
+        }).build();
+
+        let mut buf = [0; 12];
+        buf[..10].copy_from_slice(&head);
+        buf[10..].copy_from_slice(&checksum);
+
+        match PduHeader::decode(&buf) {
+            Ok(PduHeader::Data(h)) => {
+                assert_eq!(h.format, fields::DataPacketOpcode::UnconfirmedPacket);
+                assert_eq!(h.llid, 0x123456);
+            },
+            _ => panic!(),
+        }
+
+        let resp = DataResponse {
+            outbound: true,
+            class: ResponseClass::Ack,
+            type_: ResponseType::Ack,
+            status: 1,
+            mfg: 0,
+            llid: 0x123456,
+            source: None,
+            blocks: 0,
+        }.encode();
+
+        assert_eq!(PduHeader::decode(&resp), Ok(PduHeader::Response(
+            DataResponse::decode(&resp).unwrap())));
+        assert_eq!(DataHeader::decode(&resp), Err(P25Error::UnsupportedFormat {
+            field: "packet format",
+            value: 0b00011,
+        }));
+        assert_eq!(DataResponse::decode(&buf), Err(P25Error::UnsupportedFormat {
+            field: "packet format",
+            value: 0b10101,
+        }));
+    }
+
+    #[test]
+    fn test_decode_corrupt() {
+        let mut buf = [
+            0b01110110,
+            0b11000100,
+            0b00010010,
+            0b00110100,
+            0b00100001,
+            0b00110100,
+            0b11111111,
+            0b00000011,
+            0b01010010,
+            0b00000000,
+            0b10001010,
+            0b01110010,
+        ];
+
+        buf[4] ^= 0b1000;
+        let crc = crc::CRC16::new().feed_bytes(buf[..10].iter().cloned()).finish();
+        assert_eq!(DataHeader::decode(&buf), Err(P25Error::CrcMismatch {
+            received: 0x8A72,
+            computed: crc as u32,
+        }));
+
+        // Response packets have a different layout.
+        let mut buf = [0; 12];
+        buf[0] = 0b00000011;
+        let crc = crc::CRC16::new().feed_bytes(buf[..10].iter().cloned()).finish();
+        buf[10] = (crc >> 8) as u8;
+        buf[11] = crc as u8;
+        assert_eq!(DataHeader::decode(&buf), Err(P25Error::UnsupportedFormat {
+            field: "packet format",
+            value: 0b00011,
+        }));
+    }
+
+    #[test]
+    fn test_extended_address() {
+        let a = ExtendedAddress {
+            sap: fields::ServiceAccessPoint::PacketData,
+            llid: 0x123456,
+        };
+
+        assert_eq!(ExtendedAddress::decode(&a.encode()), Ok(a));
+        assert_eq!(ExtendedAddress::decode(&a.encode()[..3]), Err(P25Error::Truncated {
+            needed: 4,
+            got: 3,
+        }));
+        assert_eq!(ExtendedAddress::decode(&[0x3E, 0x12, 0x34, 0x56]),
+                   Err(P25Error::UnsupportedFormat { field: "SAP", value: 0x3E }));
+    }
+
     #[test]
     #[should_panic]
     fn test_ll_validate() {
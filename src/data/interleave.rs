@@ -294,6 +294,31 @@ impl<'a> Iterator for Deinterleaver<'a> {
     }
 }
 
+/// Yields deinterleaved soft dibits from a borrowed buffer.
+pub struct SoftDeinterleaver<'a> {
+    dibits: &'a [bits::SoftDibit; consts::CODING_DIBITS],
+    idx: Indexes<DeinterleaveRedirector>,
+}
+
+impl<'a> SoftDeinterleaver<'a> {
+    pub fn new(dibits: &'a [bits::SoftDibit; consts::CODING_DIBITS])
+        -> SoftDeinterleaver<'a>
+    {
+        SoftDeinterleaver {
+            dibits: dibits,
+            idx: Indexes::new(),
+        }
+    }
+}
+
+impl<'a> Iterator for SoftDeinterleaver<'a> {
+    type Item = bits::SoftDibit;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.idx.next().map(|idx| self.dibits[idx])
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -0,0 +1,244 @@
+//! Parse the outer framing of key management messages (KMM), which carry over-the-air
+//! rekeying (OTAR) traffic between the key management facility and subscribers.
+//!
+//! KMMs are carried on the key management SAPs. The header identifying the message and
+//! its endpoints is always sent in the clear, but the body is left as opaque bytes since
+//! it may be encrypted with a key encryption key.
+
+use error::{P25Error, Result};
+use util::{slice_u16, slice_u24};
+
+/// Number of bytes in the KMM header.
+pub const KMM_HEADER_BYTES: usize = 10;
+
+/// Number of header bytes preceding those counted by the message length.
+const LENGTH_PREFIX_BYTES: usize = 3;
+
+/// Type of key management message.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum KmmMessageId {
+    /// Request for the capabilities of a subscriber.
+    CapabilitiesCommand,
+    /// Capabilities of a subscriber.
+    CapabilitiesResponse,
+    /// Request to change the RSI of a subscriber.
+    ChangeRsiCommand,
+    /// Result of an RSI change.
+    ChangeRsiResponse,
+    /// Request to switch to a new key.
+    ChangeoverCommand,
+    /// Result of a key changeover.
+    ChangeoverResponse,
+    /// Acknowledgement that a command will be handled later.
+    DelayedAck,
+    /// Subscriber announcing itself to the key management facility.
+    Hello,
+    /// Request for an inventory of keys or other parameters.
+    InventoryCommand,
+    /// Inventory of keys or other parameters.
+    InventoryResponse,
+    /// Request to load or change keys.
+    ModifyKeyCommand,
+    /// Rejection of a message.
+    NegativeAck,
+    /// The key management facility can't provide service.
+    NoService,
+    /// Acknowledgement of a rekey command.
+    RekeyAck,
+    /// Request to rekey using keys already loaded.
+    RekeyCommand,
+    /// Request to erase keys.
+    ZeroizeCommand,
+    /// Result of a zeroize command.
+    ZeroizeResponse,
+    /// Unknown or unassigned message ID.
+    Other(u8),
+}
+
+impl KmmMessageId {
+    /// Parse a message ID from the given byte.
+    pub fn from_bits(bits: u8) -> KmmMessageId {
+        use self::KmmMessageId::*;
+
+        match bits {
+            0x01 => CapabilitiesCommand,
+            0x02 => CapabilitiesResponse,
+            0x03 => ChangeRsiCommand,
+            0x04 => ChangeRsiResponse,
+            0x05 => ChangeoverCommand,
+            0x06 => ChangeoverResponse,
+            0x0A => DelayedAck,
+            0x0C => Hello,
+            0x0D => InventoryCommand,
+            0x0E => InventoryResponse,
+            0x13 => ModifyKeyCommand,
+            0x16 => NegativeAck,
+            0x17 => NoService,
+            0x1D => RekeyAck,
+            0x1E => RekeyCommand,
+            0x21 => ZeroizeCommand,
+            0x22 => ZeroizeResponse,
+            b => Other(b),
+        }
+    }
+
+    /// Convert message ID to its byte representation.
+    pub fn to_bits(self) -> u8 {
+        use self::KmmMessageId::*;
+
+        match self {
+            CapabilitiesCommand => 0x01,
+            CapabilitiesResponse => 0x02,
+            ChangeRsiCommand => 0x03,
+            ChangeRsiResponse => 0x04,
+            ChangeoverCommand => 0x05,
+            ChangeoverResponse => 0x06,
+            DelayedAck => 0x0A,
+            Hello => 0x0C,
+            InventoryCommand => 0x0D,
+            InventoryResponse => 0x0E,
+            ModifyKeyCommand => 0x13,
+            NegativeAck => 0x16,
+            NoService => 0x17,
+            RekeyAck => 0x1D,
+            RekeyCommand => 0x1E,
+            ZeroizeCommand => 0x21,
+            ZeroizeResponse => 0x22,
+            Other(b) => b,
+        }
+    }
+}
+
+/// Outer header of a key management message.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct KmmHeader {
+    /// Type of message.
+    pub id: KmmMessageId,
+    /// Number of bytes in the message following the length field.
+    pub length: u16,
+    /// Kind of response expected by the sender (2 bits.)
+    pub response_kind: u8,
+    /// Whether the message carries a message number.
+    pub numbered: bool,
+    /// Message authentication type (2 bits.)
+    pub mac: u8,
+    /// Whether the message is the last one of a transaction.
+    pub done: bool,
+    /// Radio set identifier (RSI) of the destination.
+    pub dest_rsi: u32,
+    /// Radio set identifier (RSI) of the source.
+    pub src_rsi: u32,
+}
+
+impl KmmHeader {
+    /// Encode the header into its bytes.
+    pub fn encode(&self) -> [u8; KMM_HEADER_BYTES] {
+        [
+            self.id.to_bits(),
+            (self.length >> 8) as u8,
+            self.length as u8,
+            (self.response_kind & 0b11) << 6 | (self.numbered as u8) << 4 |
+                (self.mac & 0b11) << 2 | self.done as u8,
+            (self.dest_rsi >> 16) as u8,
+            (self.dest_rsi >> 8) as u8,
+            self.dest_rsi as u8,
+            (self.src_rsi >> 16) as u8,
+            (self.src_rsi >> 8) as u8,
+            self.src_rsi as u8,
+        ]
+    }
+}
+
+/// Key management message, which borrows a packet payload.
+#[derive(Copy, Clone, Debug)]
+pub struct KmmMessage<'a>(&'a [u8]);
+
+impl<'a> KmmMessage<'a> {
+    /// Wrap the given payload, returning `Truncated` if it's too short to hold the
+    /// header or the length it declares and `UnsupportedFormat` if the declared length
+    /// is too short for the header.
+    pub fn new(buf: &'a [u8]) -> Result<KmmMessage<'a>> {
+        if buf.len() < KMM_HEADER_BYTES {
+            return Err(P25Error::Truncated {
+                needed: KMM_HEADER_BYTES,
+                got: buf.len(),
+            });
+        }
+
+        let declared = slice_u16(&buf[1..=2]);
+        let len = LENGTH_PREFIX_BYTES + declared as usize;
+
+        if len < KMM_HEADER_BYTES {
+            return Err(P25Error::UnsupportedFormat {
+                field: "KMM length",
+                value: declared as u32,
+            });
+        }
+
+        if len > buf.len() {
+            return Err(P25Error::Truncated {
+                needed: len,
+                got: buf.len(),
+            });
+        }
+
+        Ok(KmmMessage(&buf[..len]))
+    }
+
+    /// Decode the message header.
+    pub fn header(&self) -> KmmHeader {
+        KmmHeader {
+            id: KmmMessageId::from_bits(self.0[0]),
+            length: slice_u16(&self.0[1..=2]),
+            response_kind: self.0[3] >> 6,
+            numbered: self.0[3] >> 4 & 1 == 1,
+            mac: self.0[3] >> 2 & 0b11,
+            done: self.0[3] & 1 == 1,
+            dest_rsi: slice_u24(&self.0[4..=6]),
+            src_rsi: slice_u24(&self.0[7..=9]),
+        }
+    }
+
+    /// Message body following the header, possibly encrypted.
+    pub fn body(&self) -> &'a [u8] { &self.0[KMM_HEADER_BYTES..] }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_kmm() {
+        let buf = [
+            0x1E, 0x00, 0x0B, 0b10010001, 0x98, 0x76, 0x54, 0x12, 0x34, 0x56,
+            0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0x00,
+        ];
+
+        let m = KmmMessage::new(&buf).unwrap();
+        let h = m.header();
+
+        assert_eq!(h.id, KmmMessageId::RekeyCommand);
+        assert_eq!(h.length, 11);
+        assert_eq!(h.response_kind, 0b10);
+        assert!(h.numbered);
+        assert_eq!(h.mac, 0);
+        assert!(h.done);
+        assert_eq!(h.dest_rsi, 0x987654);
+        assert_eq!(h.src_rsi, 0x123456);
+
+        // Trailing pad bytes aren't part of the body.
+        assert_eq!(m.body(), &[0xAA, 0xBB, 0xCC, 0xDD]);
+        assert_eq!(&h.encode()[..], &buf[..KMM_HEADER_BYTES]);
+    }
+
+    #[test]
+    fn test_truncated() {
+        assert_eq!(KmmMessage::new(&[0x0C, 0x00, 0x07, 0x00, 0x00]).err(),
+                   Some(P25Error::Truncated { needed: 10, got: 5 }));
+        assert_eq!(KmmMessage::new(&[0x0C, 0x00, 0x08, 0, 0, 0, 0, 0, 0, 0]).err(),
+                   Some(P25Error::Truncated { needed: 11, got: 10 }));
+        assert_eq!(KmmMessage::new(&[0x0C, 0x00, 0x06, 0, 0, 0, 0, 0, 0, 0]).err(),
+                   Some(P25Error::UnsupportedFormat { field: "KMM length", value: 6 }));
+        assert!(KmmMessage::new(&[0x0C, 0x00, 0x07, 0, 0, 0, 0, 0, 0, 0]).is_ok());
+    }
+}
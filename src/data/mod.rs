@@ -1,20 +1,34 @@
 //! Implements Project 25's data packet specification.
 
+pub mod assemble;
 pub mod coder;
+pub mod context;
 pub mod crc;
 pub mod fields;
 pub mod fragment;
 pub mod header;
 pub mod interleave;
+pub mod kmm;
 pub mod packet;
 pub mod params;
 pub mod payload;
+#[cfg(feature = "pcap")]
+pub mod pcap;
+pub mod response;
+pub mod session;
+pub mod sndcp;
 
+pub use self::assemble::{FecStats, PduAssembler, PduEvent, PduReceiver, SerialBitmap};
+pub use self::packet::PduEncoder;
+pub use self::response::{DataResponse, ResponseClass, ResponseType};
 pub use self::fragment::{ConfirmedFragments, UnconfirmedFragments};
 
 pub use self::header::{
     ConfirmedHeader,
     ConfirmedFields,
+    DataHeader,
+    ExtendedAddress,
+    PduHeader,
     ConfirmedPreamble,
     UnconfirmedHeader,
     UnconfirmedFields,
@@ -1,7 +1,14 @@
 //! Generate data packets.
 
+use std::vec::Vec;
+
+use baseband::sync::SYNC_GENERATOR;
 use bits;
-use data::{self, payload, coder, interleave};
+use data::{self, fields, payload, coder, interleave};
+use data::header::*;
+use data::response::DataResponse;
+use message::nid::{DataUnit, NetworkAccessCode, NetworkId};
+use message::status::{StatusCode, StatusInterleaver};
 
 /// Construct a confirmed data packet with the given header, payload blocks, and serial
 /// number generator. The returned value is the coded, interleaved set of dibit symbols
@@ -16,11 +23,10 @@ pub fn confirmed<S>(header: data::ConfirmedHeader, payload: data::ConfirmedPaylo
     pkt.extend({
         let (fields, checksum) = header.build();
 
-        coder::DibitCoder::new()
+        interleave::Interleaver::new(coder::DibitCoder::new()
             .feed_bytes(fields.iter().cloned())
             .feed_bytes(checksum.iter().cloned())
-            .finish()
-            .iter().cloned()
+            .finish())
     });
 
     // Add in the normal data blocks.
@@ -55,6 +61,16 @@ pub fn confirmed<S>(header: data::ConfirmedHeader, payload: data::ConfirmedPaylo
     pkt
 }
 
+/// Construct a header-only response packet from the given response. The returned value
+/// is the coded, interleaved set of dibit symbols that make up the packet.
+pub fn response(resp: &DataResponse) -> Vec<bits::Dibit> {
+    assert!(resp.blocks == 0);
+
+    interleave::Interleaver::new(coder::DibitCoder::new()
+        .feed_bytes(resp.encode().iter().cloned())
+        .finish()).collect()
+}
+
 /// Construct an unconfirmed data packet with the given header and payload blocks. The
 /// returned value is the coded, interleaved set of dibit symbols that make up the packet.
 pub fn unconfirmed(header: data::UnconfirmedHeader, payload: data::UnconfirmedPayload)
@@ -65,11 +81,10 @@ pub fn unconfirmed(header: data::UnconfirmedHeader, payload: data::UnconfirmedPa
     pkt.extend({
         let (fields, checksum) = header.build();
 
-        coder::DibitCoder::new()
+        interleave::Interleaver::new(coder::DibitCoder::new()
             .feed_bytes(fields.iter().cloned())
             .feed_bytes(checksum.iter().cloned())
-            .finish()
-            .iter().cloned()
+            .finish())
     });
 
     for block in payload.iter() {
@@ -95,3 +110,226 @@ pub fn unconfirmed(header: data::UnconfirmedHeader, payload: data::UnconfirmedPa
 
     pkt
 }
+
+/// Builds complete transmitted data packet frames addressed to or from a subscriber.
+///
+/// Each frame is made up of the frame sync, the NID, the coded header block, and the
+/// coded data blocks, with status symbols interleaved throughout.
+pub struct PduEncoder {
+    /// NAC to transmit in the NID.
+    nac: NetworkAccessCode,
+    /// Status code to interleave.
+    status: StatusCode,
+    /// Destination service of packets.
+    sap: fields::ServiceAccessPoint,
+    /// Logical link ID of the source or destination subscriber.
+    llid: u32,
+    /// Whether packets are outbound messages.
+    outbound: bool,
+}
+
+impl PduEncoder {
+    /// Create a new `PduEncoder` for outbound packets on the given NAC, SAP, and 24-bit
+    /// logical link ID.
+    pub fn new(nac: NetworkAccessCode, sap: fields::ServiceAccessPoint, llid: u32)
+        -> PduEncoder
+    {
+        assert!(llid >> 24 == 0);
+
+        PduEncoder {
+            nac: nac,
+            status: StatusCode::InboundIdle,
+            sap: sap,
+            llid: llid,
+            outbound: true,
+        }
+    }
+
+    /// Build inbound packets sent by a subscriber to a repeater instead.
+    pub fn inbound(mut self) -> Self {
+        self.outbound = false;
+        self.status = StatusCode::SubscriberRepeater;
+        self
+    }
+
+    /// Interleave the given status code instead of the default.
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Build an unconfirmed packet frame carrying the given data.
+    pub fn unconfirmed(&self, data: &[u8]) -> Vec<bits::Dibit> {
+        let payload = data::UnconfirmedPayload::new(data);
+
+        let header = data::UnconfirmedHeader::new(UnconfirmedFields {
+            preamble: if self.outbound {
+                UnconfirmedPreamble::outbound()
+            } else {
+                UnconfirmedPreamble::inbound()
+            },
+            sap: ServiceAccessPoint(self.sap),
+            mfg: Manufacturer(0),
+            addr: LogicalLink(self.llid),
+            blocks: BlockCount {
+                full_pkt: true,
+                count: payload.blocks() as u8,
+            },
+            pads: PadCount(payload.pads() as u8),
+            data_offset: DataOffset(0),
+        });
+
+        self.frame(unconfirmed(header, payload))
+    }
+
+    /// Build a confirmed packet frame carrying the given data, with the given 3-bit
+    /// packet sequence number. Blocks are numbered with serial numbers starting from 0.
+    pub fn confirmed(&self, data: &[u8], pkt_seq: u8) -> Vec<bits::Dibit> {
+        let payload = data::ConfirmedPayload::new(data);
+
+        let header = data::ConfirmedHeader::new(ConfirmedFields {
+            preamble: if self.outbound {
+                ConfirmedPreamble::outbound()
+            } else {
+                ConfirmedPreamble::inbound()
+            },
+            sap: ServiceAccessPoint(self.sap),
+            mfg: Manufacturer(0),
+            addr: LogicalLink(self.llid),
+            blocks: BlockCount {
+                full_pkt: true,
+                count: payload.blocks() as u8,
+            },
+            pads: PadCount(payload.pads() as u8),
+            seq: Sequencing {
+                resync: false,
+                pkt_seq: pkt_seq,
+                frag_seq: 0,
+            },
+            data_offset: DataOffset(0),
+        });
+
+        self.frame(confirmed(header, payload, 0..))
+    }
+
+    /// Prefix the given coded packet with the frame sync and NID and interleave status
+    /// symbols.
+    fn frame(&self, pkt: Vec<bits::Dibit>) -> Vec<bits::Dibit> {
+        let nid = NetworkId::new(self.nac, DataUnit::DataPacket).encode();
+
+        let dibits = bits::Dibits::new(SYNC_GENERATOR.iter().cloned())
+            .chain(bits::Dibits::new(nid.iter().cloned()))
+            .chain(pkt.into_iter());
+
+        StatusInterleaver::new(dibits, self.status).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use consts::{CODING_DIBITS, NID_DIBITS, SYNC_SYMBOLS};
+    use data::assemble::{PduAssembler, PduEvent};
+    use message::nid::NidReceiver;
+    use message::status::{StatusDeinterleaver, StreamSymbol};
+
+    /// Strip the frame sync, status symbols, and NID from the given frame and feed the
+    /// blocks through an assembler.
+    fn receive(frame: &[bits::Dibit]) -> Vec<PduEvent> {
+        let mut status = StatusDeinterleaver::new();
+
+        let dibits: Vec<bits::Dibit> = frame[SYNC_SYMBOLS..].iter()
+            .filter_map(|&d| match status.feed(d) {
+                StreamSymbol::Data(d) => Some(d),
+                StreamSymbol::Status(_) => None,
+            })
+            .collect();
+
+        let mut nid = NidReceiver::new();
+        let nids: Vec<_> = dibits[..NID_DIBITS].iter()
+            .filter_map(|&d| nid.feed(d))
+            .collect();
+
+        match nids[..] {
+            [Ok(nid)] => {
+                assert_eq!(nid.access_code, NetworkAccessCode::Other(0x123));
+                assert_eq!(nid.data_unit, DataUnit::DataPacket);
+            },
+            _ => panic!(),
+        }
+
+        let mut a = PduAssembler::new();
+
+        // Status padding at the end doesn't make up a full block.
+        dibits[NID_DIBITS..].chunks(CODING_DIBITS)
+            .filter(|chunk| chunk.len() == CODING_DIBITS)
+            .filter_map(|chunk| {
+                let mut buf = [bits::Dibit::default(); CODING_DIBITS];
+                buf.copy_from_slice(chunk);
+                a.feed(&buf)
+            })
+            .collect()
+    }
+
+    fn encoder() -> PduEncoder {
+        PduEncoder::new(NetworkAccessCode::Other(0x123),
+                        fields::ServiceAccessPoint::PacketData, 0xABCDEF)
+    }
+
+    /// Check the given frame loops back to the given data with the given pads.
+    fn check(frame: &[bits::Dibit], data: &[u8], pads: u8, confirmed: bool) {
+        match receive(frame)[..] {
+            [PduEvent::Packet { ref header, ref payload, crc_ok: true, .. }] => {
+                assert_eq!(header.confirmed(), confirmed);
+                assert_eq!(header.sap, fields::ServiceAccessPoint::PacketData);
+                assert_eq!(header.llid, 0xABCDEF);
+                assert_eq!(header.pads, pads);
+                assert_eq!(&payload[..], data);
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_loopback() {
+        let data: Vec<u8> = (0..100u32).map(|i| (i * 3) as u8).collect();
+
+        let frame = encoder().unconfirmed(&data);
+        // Sync, NID, header, and 9 data blocks, with status symbols.
+        assert_eq!(frame.len(), 36 * 30);
+        assert_eq!(&frame[..4], &[
+            bits::Dibit::new(0b01),
+            bits::Dibit::new(0b01),
+            bits::Dibit::new(0b01),
+            bits::Dibit::new(0b01),
+        ]);
+        check(&frame, &data, 4, false);
+
+        check(&encoder().confirmed(&data, 3), &data, 8, true);
+
+        let frame = encoder().inbound().confirmed(&data, 3);
+
+        match receive(&frame)[..] {
+            [PduEvent::Packet { ref header, .. }] => {
+                assert!(!header.outbound);
+                assert_eq!(header.seq.unwrap().pkt_seq, 3);
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_padding() {
+        let data: Vec<u8> = (0..64).collect();
+
+        // Unconfirmed data exactly filling blocks, then requiring the most pads.
+        for &(len, pads) in [(0, 8), (8, 0), (20, 0), (9, 11), (21, 11)].iter() {
+            check(&encoder().unconfirmed(&data[..len]), &data[..len], pads, false);
+        }
+
+        // Confirmed data exactly filling blocks, then requiring the most pads.
+        for &(len, pads) in [(0, 12), (12, 0), (28, 0), (13, 15), (29, 15)].iter() {
+            check(&encoder().confirmed(&data[..len], 0), &data[..len], pads, true);
+        }
+    }
+}
@@ -370,7 +370,7 @@ mod test {
         {
             let (data, pads, checksum) = p.tail().build();
 
-            assert_eq!(data, &[]);
+            assert_eq!(data, &[] as &[u8]);
             assert_eq!(pads.count(), 8);
             assert_eq!(checksum, [0x95, 0xe6, 0x14, 0xa2]);
         }
@@ -420,7 +420,7 @@ mod test {
         {
             let (data, pads, checksum) = p.tail().build();
 
-            assert_eq!(data, &[]);
+            assert_eq!(data, &[] as &[u8]);
             assert_eq!(pads.count(), 8);
             assert_eq!(checksum, [0xFF, 0xFF, 0xFF, 0xFF]);
         }
@@ -0,0 +1,205 @@
+//! Export reassembled data packets to a pcapng capture file for analysis with tools like
+//! Wireshark.
+//!
+//! The file has a single section with two interfaces: one carrying raw IP datagrams and
+//! another, with a user-defined link type, carrying raw PDUs. Timestamps are derived from
+//! the index of the baseband sample where each packet was received.
+
+use std::io::{self, Write};
+
+use consts::SAMPLE_RATE;
+
+/// Interface that carries raw IP datagrams.
+const IP_INTERFACE: u32 = 0;
+
+/// Interface that carries raw PDUs.
+const PDU_INTERFACE: u32 = 1;
+
+/// Link type of raw IPv4/IPv6 packets.
+const LINKTYPE_RAW: u16 = 101;
+
+/// First link type reserved for private use, used for raw PDUs.
+const LINKTYPE_USER0: u16 = 147;
+
+/// Block type of the section header block.
+const BLOCK_SECTION: u32 = 0x0A0D0D0A;
+
+/// Block type of the interface description block.
+const BLOCK_INTERFACE: u32 = 0x00000001;
+
+/// Block type of the enhanced packet block.
+const BLOCK_PACKET: u32 = 0x00000006;
+
+/// Magic number that indicates the byte order of a section.
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B3C4D;
+
+/// Maximum number of bytes captured from each packet.
+const SNAP_LEN: u32 = 65535;
+
+/// Writes received packets into a pcapng stream.
+///
+/// Buffered output is flushed when the writer is dropped, but errors at that point are
+/// lost, so `flush` should be called first where they matter.
+pub struct PcapWriter<W: Write> {
+    /// Destination stream.
+    out: W,
+    /// Unix time, in microseconds, of the first baseband sample.
+    start: u64,
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// Create a new `PcapWriter` over the given stream, where the first baseband sample
+    /// was received at the given Unix time in microseconds, and write the file header.
+    pub fn new(out: W, start: u64) -> io::Result<PcapWriter<W>> {
+        let mut w = PcapWriter {
+            out: out,
+            start: start,
+        };
+
+        let mut shb = vec![];
+        push_u32(&mut shb, BYTE_ORDER_MAGIC);
+        // Version 1.0.
+        push_u16(&mut shb, 1);
+        push_u16(&mut shb, 0);
+        // Section length isn't known ahead of time.
+        shb.extend_from_slice(&[0xFF; 8]);
+        w.write_block(BLOCK_SECTION, &shb)?;
+
+        for &link in [LINKTYPE_RAW, LINKTYPE_USER0].iter() {
+            let mut idb = vec![];
+            push_u16(&mut idb, link);
+            push_u16(&mut idb, 0);
+            push_u32(&mut idb, SNAP_LEN);
+            w.write_block(BLOCK_INTERFACE, &idb)?;
+        }
+
+        Ok(w)
+    }
+
+    /// Write the given IP datagram, received at the given baseband sample index.
+    pub fn write_ip(&mut self, sample: u64, datagram: &[u8]) -> io::Result<()> {
+        self.write_packet(IP_INTERFACE, sample, datagram)
+    }
+
+    /// Write the given raw PDU, received at the given baseband sample index.
+    pub fn write_pdu(&mut self, sample: u64, pdu: &[u8]) -> io::Result<()> {
+        self.write_packet(PDU_INTERFACE, sample, pdu)
+    }
+
+    /// Flush any buffered output to the stream.
+    pub fn flush(&mut self) -> io::Result<()> { self.out.flush() }
+
+    /// Write the given packet into an enhanced packet block on the given interface.
+    fn write_packet(&mut self, iface: u32, sample: u64, data: &[u8]) -> io::Result<()> {
+        let ts = self.start + sample * 1_000_000 / SAMPLE_RATE as u64;
+        let captured = data.len().min(SNAP_LEN as usize);
+
+        let mut epb = vec![];
+        push_u32(&mut epb, iface);
+        push_u32(&mut epb, (ts >> 32) as u32);
+        push_u32(&mut epb, ts as u32);
+        push_u32(&mut epb, captured as u32);
+        push_u32(&mut epb, data.len() as u32);
+        epb.extend_from_slice(&data[..captured]);
+
+        self.write_block(BLOCK_PACKET, &epb)
+    }
+
+    /// Write a block of the given type with the given body, padded to a 32-bit boundary.
+    fn write_block(&mut self, kind: u32, body: &[u8]) -> io::Result<()> {
+        let pad = (4 - body.len() % 4) % 4;
+        let len = (12 + body.len() + pad) as u32;
+
+        let mut block = Vec::with_capacity(len as usize);
+        push_u32(&mut block, kind);
+        push_u32(&mut block, len);
+        block.extend_from_slice(body);
+        block.extend((0..pad).map(|_| 0));
+        push_u32(&mut block, len);
+
+        self.out.write_all(&block)
+    }
+}
+
+impl<W: Write> Drop for PcapWriter<W> {
+    fn drop(&mut self) {
+        self.out.flush().ok();
+    }
+}
+
+/// Append the given value in little-endian order.
+fn push_u16(buf: &mut Vec<u8>, x: u16) {
+    buf.push(x as u8);
+    buf.push((x >> 8) as u8);
+}
+
+/// Append the given value in little-endian order.
+fn push_u32(buf: &mut Vec<u8>, x: u32) {
+    push_u16(buf, x as u16);
+    push_u16(buf, (x >> 16) as u16);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn read_u32(buf: &[u8]) -> u32 {
+        (0..4).fold(0, |x, i| x | (buf[i] as u32) << (i * 8))
+    }
+
+    /// Split the given stream into (type, body) blocks, checking the framing of each.
+    fn blocks(mut buf: &[u8]) -> Vec<(u32, &[u8])> {
+        let mut blocks = vec![];
+
+        while !buf.is_empty() {
+            let len = read_u32(&buf[4..]) as usize;
+            assert_eq!(len % 4, 0);
+            assert_eq!(read_u32(&buf[len - 4..]) as usize, len);
+
+            blocks.push((read_u32(buf), &buf[8..len - 4]));
+            buf = &buf[len..];
+        }
+
+        blocks
+    }
+
+    #[test]
+    fn test_pcap() {
+        let mut out = vec![];
+
+        {
+            let mut w = PcapWriter::new(&mut out, 1_500_000_000_000_000).unwrap();
+            w.write_ip(48000, &[0x45, 0x00, 0x00, 0x14, 0x01]).unwrap();
+            w.write_pdu(72000, &[0x55; 12]).unwrap();
+        }
+
+        let blocks = blocks(&out);
+        assert_eq!(blocks.len(), 5);
+
+        let (kind, shb) = blocks[0];
+        assert_eq!(kind, BLOCK_SECTION);
+        assert_eq!(read_u32(shb), BYTE_ORDER_MAGIC);
+
+        assert_eq!(blocks[1].0, BLOCK_INTERFACE);
+        assert_eq!(&blocks[1].1[..2], &[101, 0]);
+        assert_eq!(blocks[2].0, BLOCK_INTERFACE);
+        assert_eq!(&blocks[2].1[..2], &[147, 0]);
+
+        let (kind, epb) = blocks[3];
+        assert_eq!(kind, BLOCK_PACKET);
+        assert_eq!(read_u32(epb), IP_INTERFACE);
+
+        let ts = (read_u32(&epb[4..]) as u64) << 32 | read_u32(&epb[8..]) as u64;
+        assert_eq!(ts, 1_500_000_001_000_000);
+        assert_eq!(read_u32(&epb[12..]), 5);
+        assert_eq!(read_u32(&epb[16..]), 5);
+        assert_eq!(&epb[20..25], &[0x45, 0x00, 0x00, 0x14, 0x01]);
+        assert_eq!(epb.len(), 28);
+
+        let (kind, epb) = blocks[4];
+        assert_eq!(kind, BLOCK_PACKET);
+        assert_eq!(read_u32(epb), PDU_INTERFACE);
+        assert_eq!(read_u32(&epb[8..]), 1_500_000_001_500_000u64 as u32);
+        assert_eq!(&epb[20..], &[0x55; 12]);
+    }
+}
@@ -0,0 +1,284 @@
+//! Decoding and encoding of response packets, by which the recipient of a confirmed data
+//! packet acknowledges its delivery or requests its retransmission.
+//!
+//! A response is a single header block, followed by data blocks only for a selective
+//! retry, where they carry a bitmap of the blocks to retransmit.
+
+use data::crc;
+use data::fields::DataPacketOpcode;
+use error::{P25Error, Result};
+use util::{slice_u16, slice_u24};
+
+/// Class of a response.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ResponseClass {
+    /// The packet was received successfully.
+    Ack,
+    /// The packet was rejected.
+    Nack,
+    /// Some blocks of the packet must be retransmitted.
+    SelectiveRetry,
+    /// Reserved class.
+    Reserved,
+}
+
+impl ResponseClass {
+    /// Parse a response class from the given 2 bits.
+    pub fn from_bits(bits: u8) -> ResponseClass {
+        use self::ResponseClass::*;
+
+        assert!(bits >> 2 == 0);
+
+        match bits {
+            0b00 => Ack,
+            0b01 => Nack,
+            0b10 => SelectiveRetry,
+            _ => Reserved,
+        }
+    }
+
+    /// Convert response class to its 2-bit code.
+    pub fn to_bits(&self) -> u8 {
+        use self::ResponseClass::*;
+
+        match *self {
+            Ack => 0b00,
+            Nack => 0b01,
+            SelectiveRetry => 0b10,
+            Reserved => 0b11,
+        }
+    }
+}
+
+/// Meaning of a response, determined by its class and type.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ResponseType {
+    /// All blocks of the packet were received successfully.
+    Ack,
+    /// The packet had an illegal format.
+    IllegalFormat,
+    /// The packet checksum didn't match the data.
+    PacketCrc,
+    /// The recipient had no memory to store the packet.
+    MemoryFull,
+    /// The fragment sequence number was out of sequence.
+    FsnSequence,
+    /// The packet couldn't be delivered to its destination.
+    Undeliverable,
+    /// The packet sequence number was out of sequence.
+    NsSequence,
+    /// The sender isn't allowed to use the system.
+    InvalidUser,
+    /// The blocks in the attached bitmap must be retransmitted.
+    SelectiveRetry,
+    /// Unassigned combination of class and 3-bit type.
+    Unknown(ResponseClass, u8),
+}
+
+impl ResponseType {
+    /// Parse a response type from the given class and 3-bit type code.
+    pub fn from_bits(class: ResponseClass, bits: u8) -> ResponseType {
+        use self::ResponseType::*;
+
+        assert!(bits >> 3 == 0);
+
+        match (class, bits) {
+            (ResponseClass::Ack, 0b001) => Ack,
+            (ResponseClass::Nack, 0b000) => IllegalFormat,
+            (ResponseClass::Nack, 0b001) => PacketCrc,
+            (ResponseClass::Nack, 0b010) => MemoryFull,
+            (ResponseClass::Nack, 0b011) => FsnSequence,
+            (ResponseClass::Nack, 0b100) => Undeliverable,
+            (ResponseClass::Nack, 0b101) => NsSequence,
+            (ResponseClass::Nack, 0b110) => InvalidUser,
+            (ResponseClass::SelectiveRetry, 0b000) => SelectiveRetry,
+            (class, bits) => Unknown(class, bits),
+        }
+    }
+
+    /// Convert the response type to its class and 3-bit type code.
+    pub fn to_bits(&self) -> (ResponseClass, u8) {
+        use self::ResponseType::*;
+
+        match *self {
+            Ack => (ResponseClass::Ack, 0b001),
+            IllegalFormat => (ResponseClass::Nack, 0b000),
+            PacketCrc => (ResponseClass::Nack, 0b001),
+            MemoryFull => (ResponseClass::Nack, 0b010),
+            FsnSequence => (ResponseClass::Nack, 0b011),
+            Undeliverable => (ResponseClass::Nack, 0b100),
+            NsSequence => (ResponseClass::Nack, 0b101),
+            InvalidUser => (ResponseClass::Nack, 0b110),
+            SelectiveRetry => (ResponseClass::SelectiveRetry, 0b000),
+            Unknown(class, bits) => (class, bits),
+        }
+    }
+}
+
+/// Decoded response packet header.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DataResponse {
+    /// Whether the response is outbound (FNE to subscriber) or inbound.
+    pub outbound: bool,
+    /// Class of the response.
+    pub class: ResponseClass,
+    /// Meaning of the response.
+    pub type_: ResponseType,
+    /// 3-bit status, the packet sequence number of the packet being responded to.
+    pub status: u8,
+    /// Manufacturer's ID.
+    pub mfg: u8,
+    /// Logical link ID of the destination subscriber.
+    pub llid: u32,
+    /// Logical link ID of the responding subscriber, if included.
+    pub source: Option<u32>,
+    /// Number of data blocks that follow the header.
+    pub blocks: u8,
+}
+
+impl DataResponse {
+    /// Decode a response header from the given fields and checksum.
+    ///
+    /// Return `CrcMismatch` if the checksum doesn't match the fields and
+    /// `UnsupportedFormat` if the block isn't a response header.
+    pub fn decode(buf: &[u8; 12]) -> Result<DataResponse> {
+        let crc = crc::CRC16::new()
+            .feed_bytes(buf[..10].iter().cloned())
+            .finish() as u16;
+
+        let received = slice_u16(&buf[10..]);
+
+        if received != crc {
+            return Err(P25Error::CrcMismatch {
+                received: received as u32,
+                computed: crc as u32,
+            });
+        }
+
+        if DataPacketOpcode::from_bits(buf[0] & 0x1F) !=
+            Some(DataPacketOpcode::ResponsePacket)
+        {
+            return Err(P25Error::UnsupportedFormat {
+                field: "packet format",
+                value: (buf[0] & 0x1F) as u32,
+            });
+        }
+
+        let class = ResponseClass::from_bits(buf[1] >> 6);
+
+        Ok(DataResponse {
+            outbound: buf[0] >> 5 & 1 == 1,
+            class: class,
+            type_: ResponseType::from_bits(class, buf[1] >> 3 & 0b111),
+            status: buf[1] & 0b111,
+            mfg: buf[2],
+            llid: slice_u24(&buf[3..=5]),
+            source: if buf[6] >> 7 == 0 {
+                Some(slice_u24(&buf[7..=9]))
+            } else {
+                None
+            },
+            blocks: buf[6] & 0x7F,
+        })
+    }
+
+    /// Encode the response into a header block with checksum.
+    pub fn encode(&self) -> [u8; 12] {
+        let (class, bits) = self.type_.to_bits();
+        let source = self.source.unwrap_or(0);
+
+        let mut buf = [
+            (self.outbound as u8) << 5 | DataPacketOpcode::ResponsePacket.to_bits(),
+            class.to_bits() << 6 | bits << 3 | self.status & 0b111,
+            self.mfg,
+            (self.llid >> 16) as u8,
+            (self.llid >> 8) as u8,
+            self.llid as u8,
+            (self.source.is_none() as u8) << 7 | self.blocks & 0x7F,
+            (source >> 16) as u8,
+            (source >> 8) as u8,
+            source as u8,
+            0,
+            0,
+        ];
+
+        let crc = crc::CRC16::new()
+            .feed_bytes(buf[..10].iter().cloned())
+            .finish();
+
+        buf[10] = (crc >> 8) as u8;
+        buf[11] = crc as u8;
+
+        buf
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ack() {
+        let r = DataResponse::decode(&[
+            0b00100011, 0b00001011, 0x00, 0x12, 0x34, 0x56, 0x00, 0x65, 0x43, 0x21,
+            0xC9, 0xC3,
+        ]).unwrap();
+
+        assert!(r.outbound);
+        assert_eq!(r.class, ResponseClass::Ack);
+        assert_eq!(r.type_, ResponseType::Ack);
+        assert_eq!(r.status, 3);
+        assert_eq!(r.mfg, 0);
+        assert_eq!(r.llid, 0x123456);
+        assert_eq!(r.source, Some(0x654321));
+        assert_eq!(r.blocks, 0);
+        assert_eq!(DataResponse::decode(&r.encode()), Ok(r));
+    }
+
+    #[test]
+    fn test_nack() {
+        let r = DataResponse {
+            outbound: false,
+            class: ResponseClass::Nack,
+            type_: ResponseType::PacketCrc,
+            status: 5,
+            mfg: 0x90,
+            llid: 0xABCDEF,
+            source: None,
+            blocks: 0,
+        };
+
+        let buf = r.encode();
+        assert_eq!(buf[0], 0b00000011);
+        assert_eq!(buf[1], 0b01001101);
+        assert_eq!(buf[6], 0b10000000);
+        assert_eq!(DataResponse::decode(&buf), Ok(r));
+
+        let mut bad = buf;
+        bad[3] ^= 1;
+        match DataResponse::decode(&bad) {
+            Err(P25Error::CrcMismatch { received, computed }) => {
+                assert_eq!(received, slice_u16(&buf[10..]) as u32);
+                assert!(computed != received);
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_types() {
+        for bits in 0..8 {
+            for &class in [ResponseClass::Ack, ResponseClass::Nack,
+                           ResponseClass::SelectiveRetry, ResponseClass::Reserved].iter()
+            {
+                assert_eq!(ResponseClass::from_bits(class.to_bits()), class);
+                assert_eq!(ResponseType::from_bits(class, bits).to_bits(), (class, bits));
+            }
+        }
+
+        assert_eq!(ResponseType::from_bits(ResponseClass::Nack, 0b111),
+                   ResponseType::Unknown(ResponseClass::Nack, 0b111));
+        assert_eq!(ResponseType::from_bits(ResponseClass::Ack, 0b000),
+                   ResponseType::Unknown(ResponseClass::Ack, 0b000));
+    }
+}
@@ -0,0 +1,349 @@
+//! Collect statistics on the data sessions of each subscriber.
+
+use std::vec::Vec;
+
+use data::assemble::PduEvent;
+use data::context::ContextEvent;
+use data::response::ResponseClass;
+
+/// Statistics on the data session of a subscriber.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SessionStats {
+    /// Logical link ID of the subscriber.
+    pub llid: u32,
+    /// Packets received intact.
+    pub packets: usize,
+    /// Packets received with bad blocks or a bad packet checksum.
+    pub failed: usize,
+    /// Payload bytes of the packets received intact.
+    pub bytes: usize,
+    /// Data blocks received.
+    pub blocks: usize,
+    /// Data blocks that failed to decode or had a bad block checksum.
+    pub bad_blocks: usize,
+    /// Acknowledgement responses.
+    pub acks: usize,
+    /// Negative acknowledgement and selective retry responses.
+    pub retries: usize,
+    /// Time of the first packet or response.
+    pub started: u64,
+    /// Time of the most recent packet or response.
+    pub updated: u64,
+}
+
+impl SessionStats {
+    /// Create a new `SessionStats` with nothing received.
+    fn new(llid: u32, time: u64) -> SessionStats {
+        SessionStats {
+            llid: llid,
+            packets: 0,
+            failed: 0,
+            bytes: 0,
+            blocks: 0,
+            bad_blocks: 0,
+            acks: 0,
+            retries: 0,
+            started: time,
+            updated: time,
+        }
+    }
+
+    /// Time between the first and most recent activity.
+    pub fn active_time(&self) -> u64 { self.updated - self.started }
+
+    /// Payload bytes received intact per time unit of activity, or 0 if the session
+    /// hasn't been active for any time.
+    pub fn throughput(&self) -> f32 {
+        match self.active_time() {
+            0 => 0.0,
+            t => self.bytes as f32 / t as f32,
+        }
+    }
+
+    /// Ratio of bad blocks to all received blocks, or 0 if none were received.
+    pub fn block_error_rate(&self) -> f32 {
+        match self.blocks {
+            0 => 0.0,
+            n => self.bad_blocks as f32 / n as f32,
+        }
+    }
+
+    /// Ratio of retry responses to all responses, or 0 if none were received.
+    pub fn retry_ratio(&self) -> f32 {
+        match self.acks + self.retries {
+            0 => 0.0,
+            n => self.retries as f32 / n as f32,
+        }
+    }
+}
+
+/// Accumulates data session statistics for each subscriber from packet assembler events.
+///
+/// Times are in arbitrary caller-defined units. A session ends, and its final statistics
+/// are returned, when the subscriber's data context is deactivated or nothing has been
+/// received for it for longer than `idle`.
+pub struct DataStats {
+    /// Currently active sessions.
+    sessions: Vec<SessionStats>,
+    /// Time without activity after which a session ends.
+    idle: u64,
+}
+
+impl DataStats {
+    /// Create a new `DataStats` with no active sessions.
+    pub fn new(idle: u64) -> DataStats {
+        DataStats {
+            sessions: vec![],
+            idle: idle,
+        }
+    }
+
+    /// Record the given assembler event, received at the given time.
+    pub fn feed(&mut self, event: &PduEvent, time: u64) {
+        match *event {
+            PduEvent::Packet { ref header, ref payload, crc_ok, .. } => {
+                let s = self.session(header.llid, time);
+
+                s.blocks += header.blocks as usize;
+
+                if crc_ok {
+                    s.packets += 1;
+                    s.bytes += payload.len();
+                } else {
+                    s.failed += 1;
+                }
+            },
+            PduEvent::Partial { ref header, serials, .. } => {
+                let s = self.session(header.llid, time);
+                let count = header.blocks as usize;
+
+                s.failed += 1;
+                s.blocks += count;
+                s.bad_blocks += serials.missing(header.blocks).len().min(count);
+            },
+            PduEvent::BadBlock { ref header, block, .. } => {
+                let s = self.session(header.llid, time);
+
+                s.failed += 1;
+                s.blocks += block + 1;
+                s.bad_blocks += 1;
+            },
+            PduEvent::Response(resp) => {
+                let s = self.session(resp.llid, time);
+
+                match resp.class {
+                    ResponseClass::Ack => s.acks += 1,
+                    ResponseClass::Nack | ResponseClass::SelectiveRetry => s.retries += 1,
+                    ResponseClass::Reserved => {},
+                }
+            },
+            PduEvent::BadHeader(_) => {},
+        }
+    }
+
+    /// Handle the given context event, returning the final statistics of the
+    /// subscriber's session if its context was deactivated.
+    pub fn feed_context(&mut self, event: &ContextEvent) -> Option<SessionStats> {
+        let llid = match *event {
+            ContextEvent::ContextDown(ctx) => ctx.llid,
+            ContextEvent::ContextUp(_) => return None,
+        };
+
+        let pos = self.sessions.iter().position(|s| s.llid == llid)?;
+        Some(self.sessions.remove(pos))
+    }
+
+    /// End all sessions without activity for longer than the idle time at the given
+    /// time, returning the final statistics of each.
+    pub fn expire(&mut self, time: u64) -> Vec<SessionStats> {
+        let idle = self.idle;
+        let (ended, active): (Vec<SessionStats>, Vec<SessionStats>) = self.sessions
+            .drain(..)
+            .partition(|s| time.saturating_sub(s.updated) > idle);
+
+        self.sessions = active;
+
+        ended
+    }
+
+    /// Statistics of the currently active sessions, in the order they started.
+    pub fn sessions(&self) -> &[SessionStats] { &self.sessions[..] }
+
+    /// Find the statistics of the active session of the given subscriber.
+    pub fn get(&self, llid: u32) -> Option<&SessionStats> {
+        self.sessions.iter().find(|s| s.llid == llid)
+    }
+
+    /// Get the session of the given subscriber, starting one if needed, and mark it
+    /// active at the given time.
+    fn session(&mut self, llid: u32, time: u64) -> &mut SessionStats {
+        let pos = match self.sessions.iter().position(|s| s.llid == llid) {
+            Some(pos) => pos,
+            None => {
+                self.sessions.push(SessionStats::new(llid, time));
+                self.sessions.len() - 1
+            },
+        };
+
+        let s = &mut self.sessions[pos];
+        s.updated = time;
+        s
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use data::assemble::{FecStats, SerialBitmap};
+    use data::context::DataContext;
+    use data::fields::{DataPacketOpcode, ServiceAccessPoint};
+    use data::header::DataHeader;
+    use data::response::{DataResponse, ResponseType};
+    use data::sndcp::NetworkAddressType;
+    use error::P25Error;
+
+    const UNIT: u32 = 0x001234;
+
+    fn header(confirmed: bool, blocks: u8) -> DataHeader {
+        DataHeader {
+            outbound: true,
+            format: if confirmed {
+                DataPacketOpcode::ConfirmedPacket
+            } else {
+                DataPacketOpcode::UnconfirmedPacket
+            },
+            sap: ServiceAccessPoint::PacketData,
+            mfg: 0,
+            llid: UNIT,
+            full_pkt: true,
+            blocks: blocks,
+            pads: 0,
+            seq: None,
+            data_offset: 0,
+        }
+    }
+
+    fn packet(blocks: u8, len: usize, crc_ok: bool) -> PduEvent {
+        PduEvent::Packet {
+            header: header(false, blocks),
+            ext: None,
+            payload: vec![0; len],
+            crc_ok: crc_ok,
+            fec: FecStats::default(),
+        }
+    }
+
+    fn response(type_: ResponseType) -> PduEvent {
+        PduEvent::Response(DataResponse {
+            outbound: false,
+            class: type_.to_bits().0,
+            type_: type_,
+            status: 0,
+            mfg: 0,
+            llid: UNIT,
+            source: None,
+            blocks: 0,
+        })
+    }
+
+    #[test]
+    fn test_session() {
+        let mut s = DataStats::new(100);
+
+        s.feed(&packet(3, 30, true), 10);
+        s.feed(&packet(2, 20, true), 20);
+        s.feed(&packet(2, 20, false), 25);
+
+        // Confirmed packet with 2 of 4 blocks bad.
+        let mut serials = SerialBitmap::default();
+        serials.set(0);
+        serials.set(2);
+
+        s.feed(&PduEvent::Partial {
+            header: header(true, 4),
+            ext: None,
+            payload: vec![0; 50],
+            serials: serials,
+            bad: vec![16..32, 48..50],
+            fec: FecStats::default(),
+        }, 30);
+
+        s.feed(&response(ResponseType::PacketCrc), 31);
+
+        // Unconfirmed packet that failed at its second block.
+        s.feed(&PduEvent::BadBlock {
+            header: header(false, 3),
+            block: 1,
+            err: P25Error::DibitViterbiUnrecoverable,
+        }, 40);
+
+        s.feed(&packet(1, 10, true), 50);
+        s.feed(&response(ResponseType::Ack), 51);
+        let err = P25Error::CrcMismatch { received: 0, computed: 1 };
+        s.feed(&PduEvent::BadHeader(err), 60);
+
+        let st = *s.get(UNIT).unwrap();
+        assert_eq!(st.packets, 3);
+        assert_eq!(st.failed, 3);
+        assert_eq!(st.bytes, 60);
+        assert_eq!(st.blocks, 14);
+        assert_eq!(st.bad_blocks, 3);
+        assert_eq!(st.acks, 1);
+        assert_eq!(st.retries, 1);
+        assert_eq!(st.active_time(), 41);
+        assert!((st.block_error_rate() - 3.0 / 14.0).abs() < 1e-6);
+        assert!((st.throughput() - 60.0 / 41.0).abs() < 1e-6);
+        assert_eq!(st.retry_ratio(), 0.5);
+
+        assert!(s.expire(151).is_empty());
+        assert_eq!(s.expire(152), vec![st]);
+        assert!(s.sessions().is_empty());
+    }
+
+    #[test]
+    fn test_context_down() {
+        let mut s = DataStats::new(100);
+
+        s.feed(&packet(1, 10, true), 0);
+        s.feed(&PduEvent::Packet {
+            header: DataHeader {
+                llid: 0x005678,
+                ..header(false, 2)
+            },
+            ext: None,
+            payload: vec![0; 20],
+            crc_ok: true,
+            fec: FecStats::default(),
+        }, 1);
+
+        assert_eq!(s.sessions().len(), 2);
+
+        let ctx = DataContext {
+            llid: UNIT,
+            nsapi: 1,
+            address_type: NetworkAddressType::DynamicIpv4,
+            address: Ipv4Addr::new(10, 0, 0, 1),
+            dsut: None,
+            priority: 0,
+            ready_timer: 0,
+            standby_timer: 0,
+            activated: 0,
+            updated: 0,
+        };
+
+        assert_eq!(s.feed_context(&ContextEvent::ContextUp(ctx)), None);
+
+        match s.feed_context(&ContextEvent::ContextDown(ctx)) {
+            Some(st) => {
+                assert_eq!(st.llid, UNIT);
+                assert_eq!(st.bytes, 10);
+            },
+            None => panic!(),
+        }
+
+        assert_eq!(s.sessions().len(), 1);
+        assert_eq!(s.get(0x005678).unwrap().blocks, 2);
+        assert_eq!(s.feed_context(&ContextEvent::ContextDown(ctx)), None);
+    }
+}
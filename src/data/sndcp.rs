@@ -0,0 +1,552 @@
+//! Parse Subnetwork Dependent Convergence Protocol (SNDCP) messages, which manage data
+//! contexts and encapsulate IP datagrams within data packets.
+//!
+//! Context management messages are carried on the SNDCP control SAP, and IP datagrams
+//! are carried on the packet data SAP, each prefixed with a header that identifies the
+//! context (NSAPI) and any compression applied.
+
+use std::net::Ipv4Addr;
+use std::vec::Vec;
+
+use data::assemble::PduEvent;
+use data::fields::ServiceAccessPoint;
+use data::header::DataHeader;
+use data::kmm::{KmmHeader, KmmMessage};
+use data::response::DataResponse;
+use error::{P25Error, Result};
+
+/// Number of bytes in the header of a data PDU.
+const DATA_HEADER_BYTES: usize = 2;
+
+/// Type of SNDCP PDU, which depends on the direction of the packet.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SndcpPduType {
+    /// Request from a subscriber to activate a context (inbound.)
+    ActivateRequest,
+    /// Acceptance of a context activation by the FNE (outbound.)
+    ActivateAccept,
+    /// Rejection of a context activation by the FNE (outbound.)
+    ActivateReject,
+    /// Request to deactivate one or all contexts.
+    DeactivateRequest,
+    /// Acceptance of a context deactivation.
+    DeactivateAccept,
+    /// IP datagram sent with unconfirmed delivery (outbound.)
+    UnconfirmedData,
+    /// IP datagram sent with confirmed delivery.
+    ConfirmedData,
+}
+
+impl SndcpPduType {
+    /// Parse a PDU type from the given 4 bits, sent in the given direction.
+    pub fn from_bits(bits: u8, outbound: bool) -> Option<SndcpPduType> {
+        use self::SndcpPduType::*;
+
+        assert!(bits >> 4 == 0);
+
+        match (bits, outbound) {
+            (0, false) => Some(ActivateRequest),
+            (0, true) => Some(ActivateAccept),
+            (1, _) => Some(DeactivateAccept),
+            (2, _) => Some(DeactivateRequest),
+            (3, true) => Some(ActivateReject),
+            (4, true) => Some(UnconfirmedData),
+            (5, _) => Some(ConfirmedData),
+            _ => None,
+        }
+    }
+
+    /// Convert PDU type to its 4-bit representation.
+    pub fn to_bits(self) -> u8 {
+        use self::SndcpPduType::*;
+
+        match self {
+            ActivateRequest | ActivateAccept => 0,
+            DeactivateAccept => 1,
+            DeactivateRequest => 2,
+            ActivateReject => 3,
+            UnconfirmedData => 4,
+            ConfirmedData => 5,
+        }
+    }
+}
+
+/// Parsed SNDCP message.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SndcpMessage<'a> {
+    ActivateRequest(ActivateRequest<'a>),
+    ActivateAccept(ActivateAccept<'a>),
+    ActivateReject(ActivateReject<'a>),
+    DeactivateRequest(DeactivateRequest<'a>),
+    /// Acceptance of deactivating the context with the given NSAPI.
+    DeactivateAccept(u8),
+    Data(SndcpData<'a>),
+}
+
+impl<'a> SndcpMessage<'a> {
+    /// Parse a message from the given packet payload, sent in the given direction.
+    ///
+    /// Return `UnsupportedFormat` if the PDU type is unknown and `Truncated` if the
+    /// message is truncated.
+    pub fn parse(buf: &'a [u8], outbound: bool) -> Result<SndcpMessage<'a>> {
+        let bits = match buf.first() {
+            Some(&b) => b >> 4,
+            None => return Err(P25Error::Truncated { needed: 1, got: 0 }),
+        };
+
+        let kind = match SndcpPduType::from_bits(bits, outbound) {
+            Some(kind) => kind,
+            None => return Err(P25Error::UnsupportedFormat {
+                field: "SNDCP PDU type",
+                value: bits as u32,
+            }),
+        };
+
+        let len = match kind {
+            SndcpPduType::ActivateRequest => 7,
+            SndcpPduType::ActivateAccept => 10,
+            SndcpPduType::ActivateReject => 2,
+            SndcpPduType::DeactivateRequest => 2,
+            SndcpPduType::DeactivateAccept => 1,
+            SndcpPduType::UnconfirmedData | SndcpPduType::ConfirmedData =>
+                DATA_HEADER_BYTES,
+        };
+
+        if buf.len() < len {
+            return Err(P25Error::Truncated {
+                needed: len,
+                got: buf.len(),
+            });
+        }
+
+        Ok(match kind {
+            SndcpPduType::ActivateRequest =>
+                SndcpMessage::ActivateRequest(ActivateRequest(buf)),
+            SndcpPduType::ActivateAccept =>
+                SndcpMessage::ActivateAccept(ActivateAccept(buf)),
+            SndcpPduType::ActivateReject =>
+                SndcpMessage::ActivateReject(ActivateReject(buf)),
+            SndcpPduType::DeactivateRequest =>
+                SndcpMessage::DeactivateRequest(DeactivateRequest(buf)),
+            SndcpPduType::DeactivateAccept =>
+                SndcpMessage::DeactivateAccept(buf[0] & 0xF),
+            SndcpPduType::UnconfirmedData | SndcpPduType::ConfirmedData =>
+                SndcpMessage::Data(SndcpData(buf)),
+        })
+    }
+
+    /// NSAPI of the context the message applies to.
+    pub fn nsapi(&self) -> u8 {
+        match *self {
+            SndcpMessage::ActivateRequest(m) => m.nsapi(),
+            SndcpMessage::ActivateAccept(m) => m.nsapi(),
+            SndcpMessage::ActivateReject(m) => m.nsapi(),
+            SndcpMessage::DeactivateRequest(m) => m.nsapi(),
+            SndcpMessage::DeactivateAccept(nsapi) => nsapi,
+            SndcpMessage::Data(m) => m.nsapi(),
+        }
+    }
+}
+
+/// Type of network address requested or assigned for a context.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum NetworkAddressType {
+    /// Statically configured IPv4 address.
+    StaticIpv4,
+    /// Dynamically assigned IPv4 address.
+    DynamicIpv4,
+    /// No address was given.
+    NoAddress,
+    Other(u8),
+}
+
+impl NetworkAddressType {
+    /// Parse an address type from the given 4 bits.
+    pub fn from_bits(bits: u8) -> NetworkAddressType {
+        use self::NetworkAddressType::*;
+
+        assert!(bits >> 4 == 0);
+
+        match bits {
+            0 => StaticIpv4,
+            1 => DynamicIpv4,
+            0xF => NoAddress,
+            b => Other(b),
+        }
+    }
+}
+
+/// Request from a subscriber to activate a data context.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ActivateRequest<'a>(&'a [u8]);
+
+impl<'a> ActivateRequest<'a> {
+    /// NSAPI of the requested context.
+    pub fn nsapi(&self) -> u8 { self.0[0] & 0xF }
+    /// Type of address requested.
+    pub fn address_type(&self) -> NetworkAddressType {
+        NetworkAddressType::from_bits(self.0[1] >> 4)
+    }
+    /// IP header compression supported by the subscriber.
+    pub fn ip_compression(&self) -> u8 { self.0[1] & 0xF }
+    /// Requested IP address, for static addresses.
+    pub fn address(&self) -> Ipv4Addr {
+        Ipv4Addr::new(self.0[2], self.0[3], self.0[4], self.0[5])
+    }
+    /// Data subscriber unit type (DSUT) of the subscriber.
+    pub fn dsut(&self) -> u8 { self.0[6] >> 4 }
+    /// Data compression supported by the subscriber.
+    pub fn data_compression(&self) -> u8 { self.0[6] & 0xF }
+}
+
+/// Acceptance of a context activation, which assigns the context's parameters.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ActivateAccept<'a>(&'a [u8]);
+
+impl<'a> ActivateAccept<'a> {
+    /// NSAPI of the activated context.
+    pub fn nsapi(&self) -> u8 { self.0[0] & 0xF }
+    /// Priority of data sent on the context.
+    pub fn priority(&self) -> u8 { self.0[1] >> 4 }
+    /// Code for the time the subscriber stays on a data channel after activity.
+    pub fn ready_timer(&self) -> u8 { self.0[1] & 0xF }
+    /// Code for the time the context stays active without activity.
+    pub fn standby_timer(&self) -> u8 { self.0[2] >> 4 }
+    /// Type of address assigned.
+    pub fn address_type(&self) -> NetworkAddressType {
+        NetworkAddressType::from_bits(self.0[2] & 0xF)
+    }
+    /// IP address assigned to the context.
+    pub fn address(&self) -> Ipv4Addr {
+        Ipv4Addr::new(self.0[3], self.0[4], self.0[5], self.0[6])
+    }
+    /// IP header compression to use on the context.
+    pub fn ip_compression(&self) -> u8 { self.0[7] >> 4 }
+    /// Code for the maximum transmission unit of the context.
+    pub fn mtu(&self) -> u8 { self.0[8] & 0xF }
+    /// Data compression to use on the context.
+    pub fn data_compression(&self) -> u8 { self.0[9] >> 4 }
+}
+
+/// Rejection of a context activation.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ActivateReject<'a>(&'a [u8]);
+
+impl<'a> ActivateReject<'a> {
+    /// NSAPI of the rejected context.
+    pub fn nsapi(&self) -> u8 { self.0[0] & 0xF }
+    /// Reason for the rejection.
+    pub fn reason(&self) -> u8 { self.0[1] }
+}
+
+/// Request to deactivate a context.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DeactivateRequest<'a>(&'a [u8]);
+
+impl<'a> DeactivateRequest<'a> {
+    /// NSAPI of the context to deactivate.
+    pub fn nsapi(&self) -> u8 { self.0[0] & 0xF }
+    /// Whether all of the subscriber's contexts should be deactivated, rather than only
+    /// the one with the given NSAPI.
+    pub fn all(&self) -> bool { self.0[1] == 0 }
+}
+
+/// Data PDU that encapsulates an IP datagram.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SndcpData<'a>(&'a [u8]);
+
+impl<'a> SndcpData<'a> {
+    /// Whether the datagram was sent with confirmed delivery.
+    pub fn confirmed(&self) -> bool { self.0[0] >> 4 == 5 }
+    /// NSAPI of the context carrying the datagram.
+    pub fn nsapi(&self) -> u8 { self.0[0] & 0xF }
+    /// Protocol (IP header) compression applied to the datagram, 0 for none.
+    pub fn pcomp(&self) -> u8 { self.0[1] >> 4 }
+    /// Data compression applied to the datagram, 0 for none.
+    pub fn dcomp(&self) -> u8 { self.0[1] & 0xF }
+    /// Datagram bytes, possibly compressed.
+    pub fn payload(&self) -> &'a [u8] { &self.0[DATA_HEADER_BYTES..] }
+
+    /// Uncompressed IP datagram, or `UnsupportedCompression` if any compression was
+    /// applied.
+    pub fn datagram(&self) -> Result<&'a [u8]> {
+        if self.pcomp() != 0 || self.dcomp() != 0 {
+            return Err(P25Error::UnsupportedCompression {
+                header: self.pcomp(),
+                data: self.dcomp(),
+            });
+        }
+
+        Ok(self.payload())
+    }
+}
+
+/// Build a data PDU that encapsulates the given uncompressed IP datagram.
+pub fn data_pdu(nsapi: u8, confirmed: bool, datagram: &[u8]) -> Vec<u8> {
+    assert!(nsapi >> 4 == 0);
+
+    let kind = if confirmed {
+        SndcpPduType::ConfirmedData
+    } else {
+        SndcpPduType::UnconfirmedData
+    };
+
+    let mut buf = vec![kind.to_bits() << 4 | nsapi, 0];
+    buf.extend(datagram.iter().cloned());
+    buf
+}
+
+/// Event produced from a received data packet.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DataEvent {
+    /// An IP datagram was received on the context with the given NSAPI.
+    IpPacket {
+        /// NSAPI of the context.
+        nsapi: u8,
+        /// Uncompressed datagram.
+        payload: Vec<u8>,
+    },
+    /// A response acknowledging or rejecting a confirmed packet was received.
+    Response(DataResponse),
+    /// A key management message was received.
+    Kmm {
+        /// Whether the message was sent on the encrypted key management SAP.
+        encrypted: bool,
+        /// Decoded message header.
+        header: KmmHeader,
+        /// Opaque message body.
+        body: Vec<u8>,
+    },
+}
+
+impl DataEvent {
+    /// Extract an event from the given assembler event, returning `None` if it isn't a
+    /// response or complete packet carrying an IP datagram.
+    pub fn from_pdu(event: &PduEvent) -> Option<Result<DataEvent>> {
+        match *event {
+            PduEvent::Packet { ref header, ext, ref payload, crc_ok: true, .. } => {
+                // The extended address header gives the actual SAP.
+                let header = match ext {
+                    Some(ext) => DataHeader { sap: ext.sap, ..*header },
+                    None => *header,
+                };
+
+                DataEvent::from_packet(&header, payload)
+            },
+            PduEvent::Response(resp) => Some(Ok(DataEvent::Response(resp))),
+            _ => None,
+        }
+    }
+
+    /// Extract an event from the given packet, returning `None` if it doesn't carry an
+    /// IP datagram or key management message.
+    pub fn from_packet(header: &DataHeader, payload: &[u8]) -> Option<Result<DataEvent>> {
+        match header.sap {
+            ServiceAccessPoint::PacketData => {},
+            ServiceAccessPoint::UnencryptedKeyManagement =>
+                return Some(DataEvent::from_kmm(payload, false)),
+            ServiceAccessPoint::EncryptedKeyManagement =>
+                return Some(DataEvent::from_kmm(payload, true)),
+            _ => return None,
+        }
+
+        let data = match SndcpMessage::parse(payload, header.outbound) {
+            Ok(SndcpMessage::Data(data)) => data,
+            Ok(_) => return None,
+            Err(err) => return Some(Err(err)),
+        };
+
+        Some(data.datagram().map(|datagram| DataEvent::IpPacket {
+            nsapi: data.nsapi(),
+            payload: datagram.to_vec(),
+        }))
+    }
+
+    /// Extract an event from the given key management message.
+    fn from_kmm(payload: &[u8], encrypted: bool) -> Result<DataEvent> {
+        KmmMessage::new(payload).map(|kmm| DataEvent::Kmm {
+            encrypted: encrypted,
+            header: kmm.header(),
+            body: kmm.body().to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use consts::CODING_DIBITS;
+    use bits::Dibit;
+    use data::assemble::{PduAssembler, PduEvent};
+    use data::fields;
+    use data::header::*;
+    use data::kmm::KmmMessageId;
+    use data::packet;
+    use data::payload::UnconfirmedPayload;
+
+    /// Empty UDP datagram from 10.0.0.1 to 10.0.0.2.
+    const DATAGRAM: [u8; 28] = [
+        0x45, 0x00, 0x00, 0x1C, 0x00, 0x01, 0x00, 0x00,
+        0x40, 0x11, 0x66, 0xCE, 0x0A, 0x00, 0x00, 0x01,
+        0x0A, 0x00, 0x00, 0x02, 0x30, 0x39, 0x30, 0x39,
+        0x00, 0x08, 0x00, 0x00,
+    ];
+
+    /// Send the given data through an unconfirmed packet on the given SAP.
+    fn transfer(sap: fields::ServiceAccessPoint, data: &[u8]) -> Vec<PduEvent> {
+        let payload = UnconfirmedPayload::new(data);
+
+        let dibits = packet::unconfirmed(UnconfirmedHeader::new(UnconfirmedFields {
+            preamble: UnconfirmedPreamble::outbound(),
+            sap: ServiceAccessPoint(sap),
+            mfg: Manufacturer(0),
+            addr: LogicalLink(0x001234),
+            blocks: BlockCount {
+                full_pkt: true,
+                count: payload.blocks() as u8,
+            },
+            pads: PadCount(payload.pads() as u8),
+            data_offset: DataOffset(0),
+        }), payload);
+
+        let mut a = PduAssembler::new();
+
+        dibits.chunks(CODING_DIBITS).filter_map(|chunk| {
+            let mut buf = [Dibit::default(); CODING_DIBITS];
+            buf.copy_from_slice(chunk);
+            a.feed(&buf)
+        }).collect()
+    }
+
+    #[test]
+    fn test_ip_roundtrip() {
+        let pdu = data_pdu(3, false, &DATAGRAM);
+        let events = transfer(fields::ServiceAccessPoint::PacketData, &pdu);
+
+        let (header, payload) = match events[..] {
+            [PduEvent::Packet { header, ref payload, crc_ok: true, .. }] =>
+                (header, payload),
+            _ => panic!(),
+        };
+
+        assert_eq!(DataEvent::from_packet(&header, payload), Some(Ok(DataEvent::IpPacket {
+            nsapi: 3,
+            payload: DATAGRAM.to_vec(),
+        })));
+        assert_eq!(DataEvent::from_pdu(&events[0]),
+                   DataEvent::from_packet(&header, payload));
+
+        match SndcpMessage::parse(payload, true) {
+            Ok(SndcpMessage::Data(d)) => {
+                assert!(!d.confirmed());
+                assert_eq!(d.pcomp(), 0);
+                assert_eq!(d.dcomp(), 0);
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_compressed() {
+        let buf = [0x43, 0x10, 0x01, 0x02];
+
+        match SndcpMessage::parse(&buf, true) {
+            Ok(SndcpMessage::Data(d)) => {
+                assert_eq!(d.pcomp(), 1);
+                assert_eq!(d.payload(), &[0x01, 0x02]);
+                assert_eq!(d.datagram(), Err(P25Error::UnsupportedCompression {
+                    header: 1,
+                    data: 0,
+                }));
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_activate_accept() {
+        let buf = [0x05, 0x42, 0x31, 10, 20, 30, 40, 0x00, 0x06, 0x00];
+
+        let m = match SndcpMessage::parse(&buf, true) {
+            Ok(SndcpMessage::ActivateAccept(m)) => m,
+            _ => panic!(),
+        };
+
+        assert_eq!(m.nsapi(), 5);
+        assert_eq!(m.priority(), 4);
+        assert_eq!(m.ready_timer(), 2);
+        assert_eq!(m.standby_timer(), 3);
+        assert_eq!(m.address_type(), NetworkAddressType::DynamicIpv4);
+        assert_eq!(m.address(), Ipv4Addr::new(10, 20, 30, 40));
+        assert_eq!(m.ip_compression(), 0);
+        assert_eq!(m.mtu(), 6);
+        assert_eq!(m.data_compression(), 0);
+
+        // The same type is a request when inbound.
+        match SndcpMessage::parse(&buf, false) {
+            Ok(SndcpMessage::ActivateRequest(r)) => {
+                assert_eq!(r.nsapi(), 5);
+                assert_eq!(r.dsut(), 2);
+            },
+            _ => panic!(),
+        }
+
+        // Truncated messages are rejected.
+        assert_eq!(SndcpMessage::parse(&buf[..9], true),
+                   Err(P25Error::Truncated { needed: 10, got: 9 }));
+        assert_eq!(SndcpMessage::parse(&[0; 0], true),
+                   Err(P25Error::Truncated { needed: 1, got: 0 }));
+        assert_eq!(SndcpMessage::parse(&[0x45], false), Err(P25Error::UnsupportedFormat {
+            field: "SNDCP PDU type",
+            value: 4,
+        }));
+    }
+
+    #[test]
+    fn test_kmm() {
+        let kmm = KmmHeader {
+            id: KmmMessageId::ModifyKeyCommand,
+            length: 12,
+            response_kind: 0b01,
+            numbered: false,
+            mac: 0b10,
+            done: true,
+            dest_rsi: 0x00FFFF,
+            src_rsi: 0x0A0B0C,
+        };
+
+        let mut pdu = kmm.encode().to_vec();
+        pdu.extend([0xDE, 0xAD, 0xBE, 0xEF, 0x01].iter().cloned());
+
+        let events = transfer(fields::ServiceAccessPoint::EncryptedKeyManagement, &pdu);
+
+        assert_eq!(DataEvent::from_pdu(&events[0]), Some(Ok(DataEvent::Kmm {
+            encrypted: true,
+            header: kmm,
+            body: vec![0xDE, 0xAD, 0xBE, 0xEF, 0x01],
+        })));
+
+        let events = transfer(fields::ServiceAccessPoint::UnencryptedKeyManagement, &pdu);
+
+        match DataEvent::from_pdu(&events[0]) {
+            Some(Ok(DataEvent::Kmm { encrypted: false, header, .. })) => {
+                assert_eq!(header.id, KmmMessageId::ModifyKeyCommand);
+                assert_eq!(header.dest_rsi, 0x00FFFF);
+                assert_eq!(header.src_rsi, 0x0A0B0C);
+            },
+            _ => panic!(),
+        }
+
+        // The same message on another SAP isn't a KMM.
+        let events = transfer(fields::ServiceAccessPoint::SNDCPControl, &pdu);
+        assert_eq!(DataEvent::from_pdu(&events[0]), None);
+
+        // A truncated message is an error.
+        let events = transfer(fields::ServiceAccessPoint::UnencryptedKeyManagement,
+                              &pdu[..12]);
+        assert_eq!(DataEvent::from_pdu(&events[0]), Some(Err(P25Error::Truncated {
+            needed: 15,
+            got: 12,
+        })));
+    }
+}
@@ -0,0 +1,77 @@
+//! Structured records of the internal decisions made while receiving, for finding out
+//! why a signal doesn't decode.
+
+use baseband::sync::{FrequencyError, Polarity};
+use error::P25Error;
+use message::nid::DataUnit;
+
+/// Amount of diagnostic records a receiver produces.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "ser", derive(Serialize, Deserialize))]
+pub enum Verbosity {
+    /// No records, so the receive chain does no extra work.
+    Off,
+    /// Records of data units that were cut off or failed error correction.
+    Failures,
+    /// Failure records along with every frame sync detection and NID decode.
+    All,
+}
+
+impl Default for Verbosity {
+    fn default() -> Self { Verbosity::Off }
+}
+
+/// Diagnostic record of a receiver decision.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Diagnostic {
+    /// A frame sync was detected.
+    SyncDetected {
+        /// Peak correlation power of the sync.
+        power: f32,
+        /// Signal power within the correlation history when the sync was detected.
+        sigpower: f32,
+        /// Correlation power threshold the peak was detected above.
+        threshold: f32,
+        /// Polarity of the signal found at the sync.
+        polarity: Polarity,
+        /// Whether the sync was found while a data unit was still being decoded.
+        resync: bool,
+        /// `(upper, mid, lower)` symbol decision thresholds taken from the sync.
+        symbols: (f32, f32, f32),
+        /// Carrier frequency error measured from the sync.
+        frequency: FrequencyError,
+        /// Signal to noise ratio, in dB, estimated from the sync.
+        snr: f32,
+        /// Number of sync symbols decided differently from the sync sequence.
+        errors: usize,
+    },
+    /// An NID was decoded.
+    NidDecoded {
+        /// Number of bit errors corrected by the BCH code.
+        errors: usize,
+    },
+    /// A data unit was cut off by the frame sync of a new one.
+    FrameAborted {
+        /// Type of the data unit, if its NID was decoded.
+        duid: Option<DataUnit>,
+    },
+    /// Error correction failed while decoding a data unit.
+    FecFailure {
+        /// Type of the data unit, or `None` if the NID itself failed.
+        duid: Option<DataUnit>,
+        /// Code that failed.
+        err: P25Error,
+    },
+}
+
+impl Diagnostic {
+    /// Minimum verbosity that produces the record.
+    pub fn verbosity(&self) -> Verbosity {
+        match *self {
+            Diagnostic::SyncDetected { .. } | Diagnostic::NidDecoded { .. } =>
+                Verbosity::All,
+            Diagnostic::FrameAborted { .. } | Diagnostic::FecFailure { .. } =>
+                Verbosity::Failures,
+        }
+    }
+}
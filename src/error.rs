@@ -1,6 +1,7 @@
 //! Standard errors that may occur when working with P25.
 
 use std;
+use std::fmt;
 
 /// P25 runtime errors.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -28,9 +29,160 @@ pub enum P25Error {
     /// An ambiguous symbol or too many errors were detected when attempting to decode the
     /// dibit Viterbi code.
     DibitViterbiUnrecoverable,
+    /// An ambiguous symbol or too many errors were detected when attempting to decode the
+    /// tribit Viterbi code.
+    TribitViterbiUnrecoverable,
     /// An unknown or corrupted NID was encountered.
     UnknownNid,
+    /// A decoded packet's CRC didn't match its contents.
+    CrcMismatch {
+        /// Checksum carried in the packet.
+        received: u32,
+        /// Checksum calculated over the packet contents.
+        computed: u32,
+    },
+    /// The input ended before a complete structure could be read.
+    Truncated {
+        /// Number of bytes the structure requires.
+        needed: usize,
+        /// Number of bytes available.
+        got: usize,
+    },
+    /// A packet in a format that isn't decoded was encountered.
+    UnsupportedFormat {
+        /// Name of the field that selects the format.
+        field: &'static str,
+        /// Unsupported value of the field.
+        value: u32,
+    },
+    /// An SNDCP data packet with header or data compression was encountered.
+    UnsupportedCompression {
+        /// Protocol (IP header) compression identifier.
+        header: u8,
+        /// Data compression identifier.
+        data: u8,
+    },
+    /// A message was encrypted, so its contents can't be used.
+    Encrypted {
+        /// Algorithm identifier.
+        alg: u8,
+        /// Key identifier.
+        key: u16,
+    },
+    /// A configuration field was given an invalid value.
+    InvalidConfig {
+        /// Name of the field.
+        field: &'static str,
+        /// Requirement the value didn't meet.
+        reason: &'static str,
+    },
+}
+
+impl P25Error {
+    /// Whether the error is a failure of an error correcting code.
+    pub fn is_fec(&self) -> bool {
+        use self::P25Error::*;
+
+        match *self {
+            RsShortUnrecoverable | RsMediumUnrecoverable | RsLongUnrecoverable |
+                BchUnrecoverable | GolayStdUnrecoverable | GolayShortUnrecoverable |
+                GolayExtUnrecoverable | HammingStdUnrecoverable |
+                HammingShortUnrecoverable | CyclicUnrecoverable |
+                DibitViterbiUnrecoverable | TribitViterbiUnrecoverable => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for P25Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::P25Error::*;
+
+        let code = match *self {
+            RsShortUnrecoverable => "RS(24,12,13)",
+            RsMediumUnrecoverable => "RS(24,16,9)",
+            RsLongUnrecoverable => "RS(36,20,17)",
+            BchUnrecoverable => "BCH(63,16,23)",
+            GolayStdUnrecoverable => "Golay(23,12,7)",
+            GolayShortUnrecoverable => "Golay(18,6,8)",
+            GolayExtUnrecoverable => "Golay(24,12,8)",
+            HammingStdUnrecoverable => "Hamming(15,11,3)",
+            HammingShortUnrecoverable => "Hamming(10,6,3)",
+            CyclicUnrecoverable => "cyclic(16,8,5)",
+            DibitViterbiUnrecoverable => "1/2-rate trellis",
+            TribitViterbiUnrecoverable => "3/4-rate trellis",
+            UnknownNid => return write!(f, "unknown or corrupted NID"),
+            CrcMismatch { received, computed } =>
+                return write!(f, "CRC mismatch: received {:#x}, computed {:#x}",
+                              received, computed),
+            Truncated { needed, got } =>
+                return write!(f, "truncated input: needed {} bytes, got {}", needed, got),
+            UnsupportedFormat { field, value } =>
+                return write!(f, "unsupported {}: {:#x}", field, value),
+            UnsupportedCompression { header, data } =>
+                return write!(f, "unsupported compression: header {}, data {}",
+                              header, data),
+            Encrypted { alg, key } =>
+                return write!(f, "encrypted with algorithm {:#04x}, key {:#06x}",
+                              alg, key),
+            InvalidConfig { field, reason } =>
+                return write!(f, "invalid {}: {}", field, reason),
+        };
+
+        write!(f, "unrecoverable {} code errors", code)
+    }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for P25Error {}
+
 /// Standard result using `P25Error`.
 pub type Result<T> = std::result::Result<T, P25Error>;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::P25Error::*;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(RsMediumUnrecoverable.to_string(),
+                   "unrecoverable RS(24,16,9) code errors");
+        assert_eq!(DibitViterbiUnrecoverable.to_string(),
+                   "unrecoverable 1/2-rate trellis code errors");
+        assert_eq!(UnknownNid.to_string(), "unknown or corrupted NID");
+        assert_eq!(CrcMismatch { received: 0xBEEF, computed: 0x1234 }.to_string(),
+                   "CRC mismatch: received 0xbeef, computed 0x1234");
+        assert_eq!(Truncated { needed: 12, got: 3 }.to_string(),
+                   "truncated input: needed 12 bytes, got 3");
+        assert_eq!(UnsupportedFormat { field: "SAP", value: 0x3E }.to_string(),
+                   "unsupported SAP: 0x3e");
+        assert_eq!(UnsupportedCompression { header: 1, data: 0 }.to_string(),
+                   "unsupported compression: header 1, data 0");
+        assert_eq!(Encrypted { alg: 0x84, key: 0xDEAD }.to_string(),
+                   "encrypted with algorithm 0x84, key 0xdead");
+        assert_eq!(InvalidConfig { field: "max_nacs", reason: "must be at least 1" }
+                       .to_string(),
+                   "invalid max_nacs: must be at least 1");
+    }
+
+    #[test]
+    fn test_is_fec() {
+        assert!(BchUnrecoverable.is_fec());
+        assert!(TribitViterbiUnrecoverable.is_fec());
+        assert!(!UnknownNid.is_fec());
+        assert!(!CrcMismatch { received: 0, computed: 1 }.is_fec());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_error() {
+        fn parse() -> std::result::Result<(), Box<dyn std::error::Error>> {
+            Err(Truncated { needed: 4, got: 0 })?;
+            Ok(())
+        }
+
+        assert_eq!(parse().unwrap_err().to_string(),
+                   "truncated input: needed 4 bytes, got 0");
+    }
+}
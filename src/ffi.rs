@@ -0,0 +1,377 @@
+//! C interface for embedding the receiver in applications written in other languages.
+//!
+//! A receiver is created with `p25_receiver_new`, fed baseband samples with
+//! `p25_receiver_feed`, and destroyed with `p25_receiver_free`. Feeding returns `false`
+//! if the receiver panicked, which is caught rather than unwinding into the caller, and
+//! the receiver then refuses further input and should be freed. Events are either polled
+//! one at a time with `p25_receiver_next_event` or, once a callback is set with
+//! `p25_receiver_set_callback`, passed to the callback as they occur.
+//!
+//! The caller owns every buffer passed in and every event filled in, and the receiver
+//! holds on to none of them after a call returns. An event passed to a callback is only
+//! valid for the duration of the callback. Byte buffers within events are fixed-size
+//! arrays with an explicit length of valid bytes.
+//!
+//! The header in `include/p25.h` is generated from this module with cbindgen.
+
+#![allow(non_camel_case_types)]
+
+use std::collections::VecDeque;
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use message::nid::NetworkAccessCode;
+use receiver::{MessageHandler, Receiver, ReceiverConfig};
+use trunking::calls::{Call, CallTarget};
+use trunking::tsbk::TsbkFields;
+use voice::frame::VoiceFrame;
+
+/// Kind of event produced by a receiver.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum p25_event_kind_t {
+    /// Frame sync was acquired for the first time or after being lost.
+    P25_EVENT_SYNC_ACQUIRED,
+    /// Frame sync was lost.
+    P25_EVENT_SYNC_LOST,
+    /// A call started on the control channel, described by `call`.
+    P25_EVENT_CALL_START,
+    /// Grants for an active call, described by `call`, are still being received.
+    P25_EVENT_CALL_UPDATE,
+    /// A call on the control channel, described by `call`, ended.
+    P25_EVENT_CALL_END,
+    /// A voice frame was received, with its IMBE vectors in `chunks` and `errors`.
+    P25_EVENT_VOICE_FRAME,
+    /// A trunking signalling packet was received, with its bytes in `tsbk`.
+    P25_EVENT_TSBK,
+}
+
+/// Call tracked from the grants on a control channel.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct p25_call_t {
+    /// Traffic channel carrying the call, with the channel ID in the upper 4 bits and
+    /// the channel number in the lower 12.
+    pub channel: u16,
+    /// Whether the call is to a talkgroup rather than to a single unit.
+    pub group: bool,
+    /// Talkgroup or unit address the call is to.
+    pub target: u32,
+    /// Whether `src_unit` is valid.
+    pub has_src_unit: bool,
+    /// Unit that initiated the call.
+    pub src_unit: u32,
+    /// Whether any grant for the call was flagged as an emergency.
+    pub emergency: bool,
+    /// Sample index of the first grant for the call.
+    pub started: u64,
+    /// Sample index of the most recent grant or update for the call.
+    pub updated: u64,
+}
+
+/// Event produced by a receiver. Only the fields noted for the event's kind are valid,
+/// and the rest are zeroed.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct p25_event_t {
+    /// What happened.
+    pub kind: p25_event_kind_t,
+    /// Index of the sample the event occurred at.
+    pub sample: u64,
+    /// Whether the event belongs to a NAC, given in `nac`.
+    pub has_nac: bool,
+    /// 12-bit NAC of the data unit or call the event belongs to.
+    pub nac: u16,
+    /// Call that changed state.
+    pub call: p25_call_t,
+    /// Prioritized IMBE chunks `u_0`, ..., `u_7` of a voice frame.
+    pub chunks: [u32; 8],
+    /// Number of FEC errors corrected in each of the chunks `u_0`, ..., `u_6`.
+    pub errors: [u32; 7],
+    /// Bytes of a TSBK, including the CRC.
+    pub tsbk: [u8; 12],
+    /// Number of valid bytes in `tsbk`.
+    pub tsbk_len: usize,
+}
+
+/// Function called with each event as it occurs, along with the context pointer given
+/// when the callback was set. The event is only valid until the function returns.
+pub type p25_callback_t = Option<unsafe extern "C" fn(event: *const p25_event_t,
+                                                      ctx: *mut c_void)>;
+
+/// Opaque receiver handle.
+pub struct p25_receiver_t {
+    /// Wrapped receiver.
+    recv: Receiver,
+    /// Converts receiver events to C events.
+    events: Events,
+    /// Whether the receiver panicked, leaving it in an unknown state.
+    failed: bool,
+}
+
+impl p25_receiver_t {
+    /// Run the given function on the receiver, catching any panic, and return whether
+    /// it ran to completion. Nothing is run once a panic has been caught.
+    fn run<F: FnOnce(&mut Receiver, &mut Events)>(&mut self, f: F) -> bool {
+        if self.failed {
+            return false;
+        }
+
+        let (recv, events) = (&mut self.recv, &mut self.events);
+        self.failed = panic::catch_unwind(AssertUnwindSafe(|| f(recv, events))).is_err();
+
+        !self.failed
+    }
+}
+
+/// Converts receiver events to C events and dispatches them.
+struct Events {
+    /// Sample index of the following events.
+    sample: u64,
+    /// NAC of the following events.
+    nac: Option<NetworkAccessCode>,
+    /// Function to pass events to, if any.
+    callback: p25_callback_t,
+    /// Context pointer passed to the callback.
+    ctx: *mut c_void,
+    /// Events waiting to be polled, if no callback is set.
+    queue: VecDeque<p25_event_t>,
+}
+
+impl Events {
+    /// Dispatch an event of the given kind, with its specific fields filled in by the
+    /// given function.
+    fn push<F: FnOnce(&mut p25_event_t)>(&mut self, kind: p25_event_kind_t, fill: F) {
+        let mut event = p25_event_t {
+            kind: kind,
+            sample: self.sample,
+            has_nac: self.nac.is_some(),
+            nac: self.nac.map_or(0, |n| n.to_bits()),
+            call: p25_call_t::default(),
+            chunks: [0; 8],
+            errors: [0; 7],
+            tsbk: [0; 12],
+            tsbk_len: 0,
+        };
+
+        fill(&mut event);
+
+        match self.callback {
+            Some(cb) => unsafe { cb(&event, self.ctx) },
+            None => self.queue.push_back(event),
+        }
+    }
+
+    /// Dispatch a call event of the given kind.
+    fn call(&mut self, kind: p25_event_kind_t, call: Call) {
+        self.push(kind, |e| {
+            e.call = p25_call_t {
+                channel: call.channel.to_bits(),
+                group: match call.target {
+                    CallTarget::Group(_) => true,
+                    CallTarget::Unit(_) => false,
+                },
+                target: match call.target {
+                    CallTarget::Group(tg) => tg.to_bits() as u32,
+                    CallTarget::Unit(u) => u,
+                },
+                has_src_unit: call.src_unit.is_some(),
+                src_unit: call.src_unit.unwrap_or(0),
+                emergency: call.emergency,
+                started: call.started,
+                updated: call.updated,
+            };
+        });
+    }
+}
+
+impl MessageHandler for Events {
+    fn on_sample(&mut self, sample: u64) { self.sample = sample; }
+
+    fn on_nac(&mut self, nac: Option<NetworkAccessCode>) { self.nac = nac; }
+
+    fn on_sync(&mut self, locked: bool) {
+        self.push(if locked {
+            p25_event_kind_t::P25_EVENT_SYNC_ACQUIRED
+        } else {
+            p25_event_kind_t::P25_EVENT_SYNC_LOST
+        }, |_| {});
+    }
+
+    fn on_voice_frame(&mut self, vf: VoiceFrame) {
+        self.push(p25_event_kind_t::P25_EVENT_VOICE_FRAME, |e| {
+            e.chunks = vf.chunks;
+
+            for (dst, &src) in e.errors.iter_mut().zip(vf.errors.iter()) {
+                *dst = src as u32;
+            }
+        });
+    }
+
+    fn on_tsbk(&mut self, tsbk: TsbkFields) {
+        self.push(p25_event_kind_t::P25_EVENT_TSBK, |e| {
+            e.tsbk = *tsbk.bytes();
+            e.tsbk_len = e.tsbk.len();
+        });
+    }
+
+    fn on_call_start(&mut self, call: Call) {
+        self.call(p25_event_kind_t::P25_EVENT_CALL_START, call);
+    }
+
+    fn on_call_update(&mut self, call: Call) {
+        self.call(p25_event_kind_t::P25_EVENT_CALL_UPDATE, call);
+    }
+
+    fn on_call_end(&mut self, call: Call) {
+        self.call(p25_event_kind_t::P25_EVENT_CALL_END, call);
+    }
+}
+
+/// Create a new receiver with the default configuration, or return null if it couldn't
+/// be created. The returned receiver is owned by the caller and must be destroyed with
+/// `p25_receiver_free`.
+#[no_mangle]
+pub extern "C" fn p25_receiver_new() -> *mut p25_receiver_t {
+    let recv = match Receiver::new(ReceiverConfig::default()) {
+        Ok(recv) => recv,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    Box::into_raw(Box::new(p25_receiver_t {
+        recv: recv,
+        events: Events {
+            sample: 0,
+            nac: None,
+            callback: None,
+            ctx: ptr::null_mut(),
+            queue: VecDeque::new(),
+        },
+        failed: false,
+    }))
+}
+
+/// Destroy the given receiver, along with any events still waiting to be polled. Null
+/// is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn p25_receiver_free(recv: *mut p25_receiver_t) {
+    if !recv.is_null() {
+        drop(Box::from_raw(recv));
+    }
+}
+
+/// Pass each following event to the given callback, with the given context pointer,
+/// instead of queueing it. Events already queued can still be polled. A null callback
+/// switches back to queueing.
+#[no_mangle]
+pub unsafe extern "C" fn p25_receiver_set_callback(recv: *mut p25_receiver_t,
+                                                   callback: p25_callback_t,
+                                                   ctx: *mut c_void)
+{
+    let recv = &mut *recv;
+
+    recv.events.callback = callback;
+    recv.events.ctx = ctx;
+}
+
+/// Feed in the given `len` baseband samples. The samples are only read during the call.
+/// Return `false` if the receiver panicked, now or in an earlier call.
+#[no_mangle]
+pub unsafe extern "C" fn p25_receiver_feed(recv: *mut p25_receiver_t,
+                                           samples: *const f32, len: usize) -> bool
+{
+    let recv = &mut *recv;
+
+    if len == 0 {
+        return !recv.failed;
+    }
+
+    let samples = slice::from_raw_parts(samples, len);
+
+    recv.run(|r, events| for &s in samples {
+        r.feed_with(s, events);
+    })
+}
+
+/// Signal the end of the input, producing the loss of sync and the end of every active
+/// call. Return `false` if the receiver panicked, now or in an earlier call.
+#[no_mangle]
+pub unsafe extern "C" fn p25_receiver_finish(recv: *mut p25_receiver_t) -> bool {
+    let recv = &mut *recv;
+    recv.run(|r, events| r.finish_with(events))
+}
+
+/// Copy the oldest queued event into the given event and remove it from the queue,
+/// returning whether there was one.
+#[no_mangle]
+pub unsafe extern "C" fn p25_receiver_next_event(recv: *mut p25_receiver_t,
+                                                 event: *mut p25_event_t) -> bool
+{
+    let recv = &mut *recv;
+
+    match recv.events.queue.pop_front() {
+        Some(e) => {
+            *event = e;
+            true
+        },
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std;
+
+    unsafe extern "C" fn count(event: *const p25_event_t, ctx: *mut c_void) {
+        let counts = &mut *(ctx as *mut Vec<p25_event_kind_t>);
+        counts.push((*event).kind);
+    }
+
+    #[test]
+    fn test_dispatch() {
+        unsafe {
+            let recv = p25_receiver_new();
+            (*recv).events.on_nac(Some(NetworkAccessCode::Default));
+            (*recv).events.on_sync(true);
+
+            let mut event: p25_event_t = std::mem::zeroed();
+            assert!(p25_receiver_next_event(recv, &mut event));
+            assert_eq!(event.kind, p25_event_kind_t::P25_EVENT_SYNC_ACQUIRED);
+            assert!(event.has_nac);
+            assert_eq!(event.nac, 0x293);
+            assert!(!p25_receiver_next_event(recv, &mut event));
+
+            let mut kinds: Vec<p25_event_kind_t> = vec![];
+            p25_receiver_set_callback(recv, Some(count),
+                                      &mut kinds as *mut _ as *mut c_void);
+
+            (*recv).events.on_sync(false);
+            assert!(p25_receiver_finish(recv));
+            assert!(!p25_receiver_next_event(recv, &mut event));
+            assert_eq!(kinds, vec![p25_event_kind_t::P25_EVENT_SYNC_LOST]);
+
+            p25_receiver_free(recv);
+            p25_receiver_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_panic() {
+        unsafe {
+            let recv = p25_receiver_new();
+            let samples = [0.0; 100];
+            assert!(p25_receiver_feed(recv, samples.as_ptr(), samples.len()));
+
+            // A panic is caught and reported, and the receiver refuses further input.
+            assert!(!(*recv).run(|_, _| panic!("receiver bug")));
+            assert!(!p25_receiver_feed(recv, samples.as_ptr(), samples.len()));
+            assert!(!p25_receiver_feed(recv, samples.as_ptr(), 0));
+            assert!(!p25_receiver_finish(recv));
+
+            p25_receiver_free(recv);
+        }
+    }
+}
@@ -0,0 +1,379 @@
+//! Arbitrary frame inputs and decoding checks for fuzzing the frame decoders.
+//!
+//! Each input type is a buffer of the symbols or bytes consumed by one of the frame
+//! decoders, and each check decodes an input through the same decoders the receiver
+//! uses. Malformed input must never panic, and anything that decodes successfully must
+//! encode into symbols that decode to the same value. The cargo-fuzz targets under
+//! `fuzz/` run these checks on generated inputs.
+
+use std::vec::Vec;
+
+use arbitrary::{self, Arbitrary, Unstructured};
+
+use bits::{Dibit, Dibits};
+use consts::{CODING_DIBITS, NID_DIBITS, SYNC_SYMBOLS};
+use data::assemble::{PduAssembler, PduEvent};
+use data::fields::DataPacketOpcode;
+use data::packet::PduEncoder;
+use data::sndcp::DataEvent;
+use message::nid::{NetworkAccessCode, NetworkId, NidReceiver};
+use message::status::{StatusDeinterleaver, StreamSymbol};
+use trunking::decode::TsbkMessage;
+use trunking::encode::code_block;
+use trunking::tsbk::{self, TsbkFields, TsbkReceiver};
+use voice::control::LinkControlFields;
+use voice::encode::{self, GROUP_FRAMES};
+use voice::frame_group::{FrameGroupEvent, VoiceLCFrameGroupReceiver};
+use voice::header::{VoiceHeaderFields, VoiceHeaderReceiver};
+use voice::term::VoiceLCTerminatorReceiver;
+
+/// Number of dibits in a coded voice header, with status symbols removed.
+pub const HEADER_DIBITS: usize = 324;
+
+/// Number of dibits in a coded voice frame group, with status symbols removed.
+pub const LDU_DIBITS: usize = 784;
+
+impl<'a> Arbitrary<'a> for Dibit {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Dibit::new(u8::arbitrary(u)? & 0b11))
+    }
+}
+
+/// Coded NID dibits.
+#[derive(Debug)]
+pub struct NidDibits(pub [Dibit; NID_DIBITS]);
+
+/// Decoded TSBK bytes, including the CRC.
+#[derive(Debug)]
+pub struct TsbkBytes(pub tsbk::Buf);
+
+/// Coded voice header dibits.
+#[derive(Debug)]
+pub struct HeaderDibits(pub [Dibit; HEADER_DIBITS]);
+
+/// Coded dibits of a link control frame group (LDU1.)
+#[derive(Debug)]
+pub struct LduDibits(pub [Dibit; LDU_DIBITS]);
+
+/// Sequence of coded data packet blocks, starting with a header block.
+#[derive(Debug)]
+pub struct PduBlocks(pub Vec<[Dibit; CODING_DIBITS]>);
+
+impl<'a> Arbitrary<'a> for NidDibits {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(NidDibits(Arbitrary::arbitrary(u)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for TsbkBytes {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(TsbkBytes(Arbitrary::arbitrary(u)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for HeaderDibits {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(HeaderDibits(Arbitrary::arbitrary(u)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for LduDibits {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(LduDibits(Arbitrary::arbitrary(u)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for PduBlocks {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(PduBlocks(Arbitrary::arbitrary(u)?))
+    }
+}
+
+/// Decode the given NID dibits and check that a decoded NID survives recoding.
+pub fn check_nid(input: &NidDibits) {
+    let nid = match decode_nid(&input.0[..]) {
+        Some(nid) => nid,
+        None => return,
+    };
+
+    let dibits: Vec<Dibit> = Dibits::new(nid.encode().iter().cloned()).collect();
+    let recoded = decode_nid(&dibits[..]).expect("recoded NID failed");
+
+    assert_eq!(recoded.to_bits(), nid.to_bits());
+}
+
+/// Interpret the given TSBK bytes as every kind of message and check that the packet
+/// survives coding when its CRC is valid.
+pub fn check_tsbk(input: &TsbkBytes) {
+    let tsbk = TsbkFields::new(input.0);
+    TsbkMessage::new(&tsbk);
+
+    let mut recv = TsbkReceiver::new();
+    let decoded = code_block(&tsbk).iter().filter_map(|&d| recv.feed(d)).next();
+
+    match decoded {
+        Some(Ok(recoded)) => {
+            assert!(tsbk.crc_valid());
+            assert_eq!(recoded.bytes(), tsbk.bytes());
+        },
+        Some(Err(err)) => assert!(!tsbk.crc_valid(), "coded TSBK failed: {:?}", err),
+        None => panic!("coded TSBK not received"),
+    }
+}
+
+/// Decode the given voice header dibits and check that a decoded header survives
+/// recoding.
+pub fn check_header(input: &HeaderDibits) {
+    let header = match decode_header(&input.0[..]) {
+        Some(h) => h,
+        None => return,
+    };
+
+    header.crypto_alg();
+    header.talk_group();
+
+    let recoded = decode_header(&encode::header(header.bytes())[..])
+        .expect("recoded header failed");
+
+    assert_eq!(recoded.bytes(), header.bytes());
+}
+
+/// Decode the given LDU1 dibits and check that a fully decoded frame group survives
+/// recoding, and that its link control word also survives coding as a terminator.
+pub fn check_lc(input: &LduDibits) {
+    let (frames, lc, lsd) = match decode_lc_group(&input.0[..]) {
+        Some(group) => group,
+        None => return,
+    };
+
+    lc.opcode();
+
+    let recoded = decode_lc_group(&encode::lc_frame_group(&frames, lc.bytes(), lsd)[..])
+        .expect("recoded frame group failed");
+
+    assert_eq!(recoded.0, frames);
+    assert_eq!(recoded.1.bytes(), lc.bytes());
+    assert_eq!(recoded.2, lsd);
+
+    let mut recv = VoiceLCTerminatorReceiver::new();
+
+    match encode::lc_terminator(lc.bytes()).iter().filter_map(|&d| recv.feed(d)).next() {
+        Some(Ok(term)) => assert_eq!(term.bytes(), lc.bytes()),
+        _ => panic!("coded terminator failed"),
+    }
+}
+
+/// Assemble the given data packet blocks and check that a complete packet survives
+/// being rebuilt from its header fields and payload.
+pub fn check_pdu(input: &PduBlocks) {
+    for pdu in assemble(&input.0[..]) {
+        DataEvent::from_pdu(&pdu);
+
+        let (header, payload) = match pdu {
+            PduEvent::Packet { header, ext: None, payload, crc_ok: true, .. } =>
+                (header, payload),
+            _ => continue,
+        };
+
+        if header.extended() {
+            continue;
+        }
+
+        let enc = PduEncoder::new(NetworkAccessCode::Default, header.sap, header.llid);
+        let enc = if header.outbound { enc } else { enc.inbound() };
+
+        let frame = match (header.format, header.seq) {
+            (DataPacketOpcode::ConfirmedPacket, Some(seq)) =>
+                enc.confirmed(&payload[..], seq.pkt_seq),
+            _ => enc.unconfirmed(&payload[..]),
+        };
+
+        match &assemble(&frame_blocks(&frame[..])[..])[..] {
+            &[PduEvent::Packet { header: h, payload: ref p, crc_ok: true, .. }] => {
+                assert_eq!(h.format, header.format);
+                assert_eq!(h.sap, header.sap);
+                assert_eq!(h.llid, header.llid);
+                assert_eq!(h.outbound, header.outbound);
+                assert_eq!(p, &payload);
+            },
+            events => panic!("rebuilt packet failed: {:?}", events),
+        }
+    }
+}
+
+/// Decode the first NID in the given dibits.
+fn decode_nid(dibits: &[Dibit]) -> Option<NetworkId> {
+    let mut recv = NidReceiver::new();
+
+    match dibits.iter().filter_map(|&d| recv.feed(d)).next() {
+        Some(Ok(nid)) => Some(nid),
+        _ => None,
+    }
+}
+
+/// Decode the first voice header in the given dibits.
+fn decode_header(dibits: &[Dibit]) -> Option<VoiceHeaderFields> {
+    let mut recv = VoiceHeaderReceiver::new();
+
+    match dibits.iter().filter_map(|&d| recv.feed(d)).next() {
+        Some(Ok(h)) => Some(h),
+        _ => None,
+    }
+}
+
+/// Decode the voice frame chunks, link control word, and low-speed data word of the
+/// given LDU1 dibits, if every part decoded successfully.
+fn decode_lc_group(dibits: &[Dibit])
+    -> Option<([[u32; 8]; GROUP_FRAMES], LinkControlFields, u16)>
+{
+    let mut recv = VoiceLCFrameGroupReceiver::new();
+    let mut frames = vec![];
+    let mut lc = None;
+    let mut lsd = None;
+    let mut ok = true;
+
+    for &d in dibits {
+        match recv.feed(d) {
+            Some(Ok(FrameGroupEvent::VoiceFrame(vf))) => frames.push(vf.chunks),
+            Some(Ok(FrameGroupEvent::Extra(fields))) => lc = Some(fields),
+            Some(Ok(FrameGroupEvent::DataFragment(data))) => lsd = Some(data as u16),
+            Some(Err(_)) => ok = false,
+            None => {},
+        }
+    }
+
+    if !ok || frames.len() != GROUP_FRAMES {
+        return None;
+    }
+
+    let mut chunks = [[0; 8]; GROUP_FRAMES];
+    chunks.copy_from_slice(&frames[..]);
+
+    match (lc, lsd) {
+        (Some(lc), Some(lsd)) => Some((chunks, lc, lsd)),
+        _ => None,
+    }
+}
+
+/// Feed the given coded blocks through a packet assembler.
+fn assemble(blocks: &[[Dibit; CODING_DIBITS]]) -> Vec<PduEvent> {
+    let mut asm = PduAssembler::new();
+    blocks.iter().filter_map(|b| asm.feed(b)).collect()
+}
+
+/// Strip the frame sync, status symbols, and NID from the given data packet frame,
+/// returning its coded blocks.
+fn frame_blocks(frame: &[Dibit]) -> Vec<[Dibit; CODING_DIBITS]> {
+    let mut status = StatusDeinterleaver::new();
+
+    let dibits: Vec<Dibit> = frame[SYNC_SYMBOLS..].iter()
+        .filter_map(|&d| match status.feed(d) {
+            StreamSymbol::Data(d) => Some(d),
+            StreamSymbol::Status(_) => None,
+        })
+        .collect();
+
+    // Status padding at the end doesn't make up a full block.
+    dibits[NID_DIBITS..].chunks(CODING_DIBITS)
+        .filter(|chunk| chunk.len() == CODING_DIBITS)
+        .map(|chunk| {
+            let mut buf = [Dibit::default(); CODING_DIBITS];
+            buf.copy_from_slice(chunk);
+            buf
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use data::fields::ServiceAccessPoint;
+    use message::nid::DataUnit;
+    use trunking::tsbk::TsbkOpcode;
+    use trunking::vendor::MFID_STANDARD;
+
+    /// Run every check on inputs generated from the given bytes.
+    fn check_all(bytes: &[u8]) {
+        let mut u = Unstructured::new(bytes);
+
+        check_nid(&NidDibits::arbitrary(&mut u).unwrap());
+        check_tsbk(&TsbkBytes::arbitrary(&mut u).unwrap());
+        check_header(&HeaderDibits::arbitrary(&mut u).unwrap());
+        check_lc(&LduDibits::arbitrary(&mut u).unwrap());
+        check_pdu(&PduBlocks::arbitrary(&mut u).unwrap());
+    }
+
+    #[test]
+    fn test_arbitrary() {
+        for seed in 0..50u32 {
+            let mut x = 0x9E3779B9 ^ seed;
+
+            let bytes: Vec<u8> = (0..4000).map(|_| {
+                x ^= x << 13;
+                x ^= x >> 17;
+                x ^= x << 5;
+                x as u8
+            }).collect();
+
+            check_all(&bytes[..]);
+        }
+
+        check_all(&[]);
+        check_all(&[0xFF; 4000]);
+    }
+
+    #[test]
+    fn test_valid() {
+        let nid = NetworkId::new(NetworkAccessCode::Other(0x123), DataUnit::VoiceHeader);
+        let mut dibits = [Dibit::default(); NID_DIBITS];
+
+        let coded = nid.encode();
+
+        for (dest, d) in dibits.iter_mut().zip(Dibits::new(coded.iter().cloned())) {
+            *dest = d;
+        }
+
+        assert!(decode_nid(&dibits[..]).is_some());
+        check_nid(&NidDibits(dibits));
+
+        let tsbk = TsbkFields::build(TsbkOpcode::GroupVoiceGrant.to_bits(), MFID_STANDARD,
+                                     &[0x00, 0x10, 0x20, 0x01, 0x23, 0x12, 0x34, 0x56]);
+        check_tsbk(&TsbkBytes(*tsbk.bytes()));
+
+        let mut bad = *tsbk.bytes();
+        bad[11] ^= 1;
+        check_tsbk(&TsbkBytes(bad));
+
+        let mut header = [Dibit::default(); HEADER_DIBITS];
+        header.copy_from_slice(&encode::header(&[0x42; 15])[..]);
+
+        assert!(decode_header(&header[..]).is_some());
+        check_header(&HeaderDibits(header));
+
+        let lc = [0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x12, 0x34, 0x56];
+        let chunks = [[0x123, 0x456, 0x789, 0xABC, 0x12, 0x34, 0x56, 0x78]; GROUP_FRAMES];
+
+        let mut ldu = [Dibit::default(); LDU_DIBITS];
+        ldu.copy_from_slice(&encode::lc_frame_group(&chunks, &lc, 0xBEEF)[..]);
+
+        assert!(decode_lc_group(&ldu[..]).is_some());
+        check_lc(&LduDibits(ldu));
+
+        let enc = PduEncoder::new(NetworkAccessCode::Default,
+                                  ServiceAccessPoint::PacketData, 0x123456);
+
+        let frames = [enc.unconfirmed(b"hello world"), enc.confirmed(&[0xAB; 40], 3)];
+
+        for frame in frames.iter() {
+            let blocks = PduBlocks(frame_blocks(&frame[..]));
+
+            match &assemble(&blocks.0[..])[..] {
+                &[PduEvent::Packet { crc_ok: true, .. }] => {},
+                _ => panic!(),
+            }
+
+            check_pdu(&blocks);
+        }
+    }
+}
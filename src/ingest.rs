@@ -0,0 +1,296 @@
+//! Hand off baseband samples from a real-time callback, such as an audio driver's, to a
+//! thread that decodes them.
+//!
+//! The callback pushes samples into a `SampleSink`, which never blocks or allocates,
+//! and the decoding thread drains the paired `SampleQueue` with `Receiver::pump`.
+//! Samples pushed while the queue is full are dropped and counted.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+
+/// Create a sink and queue sharing a ring buffer of the given number of samples.
+///
+/// The capacity bounds the latency between pushing a sample and decoding it, at
+/// `SAMPLE_RATE` samples per second, as long as the queue is pumped often enough to
+/// keep it from filling.
+pub fn sample_queue(capacity: usize) -> (SampleSink, SampleQueue) {
+    assert!(capacity > 0);
+
+    let ring = Arc::new(Ring {
+        slots: (0..capacity).map(|_| AtomicU32::new(0)).collect(),
+        head: AtomicUsize::new(0),
+        tail: AtomicUsize::new(0),
+        overflow: AtomicU64::new(0),
+    });
+
+    (SampleSink(ring.clone()), SampleQueue(ring))
+}
+
+/// Fixed-capacity ring buffer shared by a single producer and a single consumer.
+struct Ring {
+    /// Sample slots, holding the bits of each sample.
+    slots: Box<[AtomicU32]>,
+    /// Total number of samples written, wrapping.
+    head: AtomicUsize,
+    /// Total number of samples read, wrapping.
+    tail: AtomicUsize,
+    /// Total number of samples dropped because the ring was full.
+    overflow: AtomicU64,
+}
+
+impl Ring {
+    /// Number of samples currently buffered.
+    fn len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        self.head.load(Ordering::Acquire).wrapping_sub(tail)
+    }
+
+    /// Slot of the sample at the given index.
+    fn slot(&self, idx: usize) -> &AtomicU32 { &self.slots[idx % self.slots.len()] }
+}
+
+/// Producer half of a sample queue, for pushing samples from a real-time callback.
+pub struct SampleSink(Arc<Ring>);
+
+impl SampleSink {
+    /// Push the given sample, returning `false` and counting it as overflow if the queue
+    /// is full.
+    pub fn push(&mut self, s: f32) -> bool { self.push_slice(&[s]) == 1 }
+
+    /// Push as many of the given samples as fit in the queue, in order, returning how
+    /// many were pushed. The rest are dropped and counted as overflow.
+    pub fn push_slice(&mut self, samples: &[f32]) -> usize {
+        let ring = &*self.0;
+
+        // Only this side writes the head, and the consumer only ever frees space.
+        let head = ring.head.load(Ordering::Relaxed);
+        let tail = ring.tail.load(Ordering::Acquire);
+        let free = ring.slots.len() - head.wrapping_sub(tail);
+        let n = free.min(samples.len());
+
+        for (idx, &s) in samples[..n].iter().enumerate() {
+            ring.slot(head.wrapping_add(idx)).store(s.to_bits(), Ordering::Relaxed);
+        }
+
+        ring.head.store(head.wrapping_add(n), Ordering::Release);
+
+        if n < samples.len() {
+            ring.overflow.fetch_add((samples.len() - n) as u64, Ordering::Relaxed);
+        }
+
+        n
+    }
+
+    /// Number of samples that can be pushed before the queue is full.
+    pub fn free(&self) -> usize { self.0.slots.len() - self.0.len() }
+
+    /// Maximum number of buffered samples.
+    pub fn capacity(&self) -> usize { self.0.slots.len() }
+
+    /// Total number of samples dropped because the queue was full.
+    pub fn overflow(&self) -> u64 { self.0.overflow.load(Ordering::Relaxed) }
+}
+
+/// Consumer half of a sample queue, drained by `Receiver::pump`.
+pub struct SampleQueue(Arc<Ring>);
+
+impl SampleQueue {
+    /// Take the oldest buffered sample, if any.
+    pub fn pop(&mut self) -> Option<f32> {
+        let ring = &*self.0;
+
+        // Only this side writes the tail.
+        let tail = ring.tail.load(Ordering::Relaxed);
+
+        if ring.head.load(Ordering::Acquire) == tail {
+            return None;
+        }
+
+        let s = f32::from_bits(ring.slot(tail).load(Ordering::Relaxed));
+        ring.tail.store(tail.wrapping_add(1), Ordering::Release);
+
+        Some(s)
+    }
+
+    /// Number of samples currently buffered.
+    pub fn len(&self) -> usize { self.0.len() }
+
+    /// Whether no samples are buffered.
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// Maximum number of buffered samples.
+    pub fn capacity(&self) -> usize { self.0.slots.len() }
+
+    /// Total number of samples dropped because the queue was full.
+    pub fn overflow(&self) -> u64 { self.0.overflow.load(Ordering::Relaxed) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std;
+    use std::mem::{discriminant, Discriminant};
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+
+    use bits::Dibit;
+    use consts::SYMBOL_PERIOD;
+    use message::nid::NetworkAccessCode;
+    use receiver::{Event, EventKind, Receiver, ReceiverConfig};
+    use voice::encode::VoiceEncoder;
+
+    /// Baseband samples of a call made of a number of LDU1 frame groups with chunks
+    /// counting up, ending with a terminator.
+    fn call() -> Vec<f32> {
+        let voice = VoiceEncoder::new(NetworkAccessCode::Default);
+        let lc = [0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x12, 0x34, 0x56];
+
+        let mut dibits: Vec<Dibit> = vec![];
+
+        for n in 0..8 {
+            dibits.extend(voice.lc_frame_group(&[[n; 8]; 9], &lc, 0xBEEF));
+        }
+
+        dibits.extend(voice.simple_terminator());
+
+        let mut samples = vec![0.0; 1000];
+
+        for d in dibits {
+            let level = match d.bits() {
+                0b01 => 0.18,
+                0b00 => 0.02,
+                0b10 => -0.02,
+                _ => -0.18,
+            };
+
+            samples.extend(std::iter::repeat(level).take(SYMBOL_PERIOD));
+        }
+
+        samples.extend(std::iter::repeat(0.0).take(1000));
+        samples
+    }
+
+    /// Identifying parts of the given event.
+    fn key(e: &Event) -> (Option<NetworkAccessCode>, Discriminant<EventKind>) {
+        (e.nac, discriminant(&e.kind))
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let (mut sink, mut queue) = sample_queue(4);
+
+        assert_eq!(sink.capacity(), 4);
+        assert_eq!(sink.push_slice(&[1.0, 2.0, 3.0]), 3);
+        assert_eq!(sink.free(), 1);
+        assert_eq!(sink.push_slice(&[4.0, 5.0, 6.0]), 1);
+        assert!(!sink.push(7.0));
+        assert_eq!(sink.overflow(), 3);
+
+        assert_eq!(queue.len(), 4);
+        assert_eq!(queue.pop(), Some(1.0));
+        assert_eq!(queue.pop(), Some(2.0));
+        assert!(sink.push(8.0));
+
+        assert_eq!(queue.pop(), Some(3.0));
+        assert_eq!(queue.pop(), Some(4.0));
+        assert_eq!(queue.pop(), Some(8.0));
+        assert_eq!(queue.pop(), None);
+        assert!(queue.is_empty());
+        assert_eq!(queue.overflow(), 3);
+    }
+
+    #[test]
+    fn test_pump_lossless() {
+        let samples = call();
+
+        let mut recv = Receiver::new(ReceiverConfig::default()).unwrap();
+        let mut expect = recv.feed_slice(&samples);
+        expect.extend(recv.finish());
+
+        let (mut sink, mut queue) = sample_queue(4096);
+        let done = Arc::new(AtomicBool::new(false));
+
+        let producer = {
+            let samples = samples.clone();
+            let done = done.clone();
+
+            thread::spawn(move || {
+                for chunk in samples.chunks(512) {
+                    // Stay below capacity by waiting for the consumer.
+                    while sink.free() < chunk.len() {
+                        thread::yield_now();
+                    }
+
+                    assert_eq!(sink.push_slice(chunk), chunk.len());
+                }
+
+                done.store(true, Ordering::Release);
+                sink.overflow()
+            })
+        };
+
+        let mut recv = Receiver::new(ReceiverConfig::default()).unwrap();
+        let mut events = vec![];
+
+        while !done.load(Ordering::Acquire) || !queue.is_empty() {
+            events.extend(recv.pump(&mut queue));
+        }
+
+        events.extend(recv.finish());
+
+        assert_eq!(producer.join().unwrap(), 0);
+        assert_eq!(recv.sample(), samples.len() as u64);
+        assert!(expect.len() > 20);
+        assert_eq!(events.iter().map(key).collect::<Vec<_>>(),
+                   expect.iter().map(key).collect::<Vec<_>>());
+
+        let chunks: Vec<u32> = events.iter()
+            .filter_map(|e| match e.kind {
+                EventKind::VoiceFrame(ref vf) => Some(vf.chunks[0]),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(chunks, (0..8).flat_map(|n| vec![n; 9]).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn test_pump_overflow() {
+        let samples = call();
+        let (mut sink, mut queue) = sample_queue(700);
+        let done = Arc::new(AtomicBool::new(false));
+
+        let producer = {
+            let done = done.clone();
+
+            thread::spawn(move || {
+                let mut pushed = 0;
+                let mut dropped = 0;
+
+                // Push without waiting, so the queue regularly fills.
+                for chunk in samples.chunks(512) {
+                    let n = sink.push_slice(chunk);
+
+                    pushed += n;
+                    dropped += chunk.len() - n;
+                }
+
+                done.store(true, Ordering::Release);
+                (pushed, dropped as u64, sink.overflow())
+            })
+        };
+
+        let mut recv = Receiver::new(ReceiverConfig::default()).unwrap();
+
+        while !done.load(Ordering::Acquire) || !queue.is_empty() {
+            recv.pump(&mut queue);
+        }
+
+        let (pushed, dropped, overflow) = producer.join().unwrap();
+
+        assert!(dropped > 0);
+        assert_eq!(overflow, dropped);
+        assert_eq!(queue.overflow(), dropped);
+        assert_eq!(recv.sample(), pushed as u64);
+    }
+}
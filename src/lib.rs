@@ -1,5 +1,18 @@
 //! Implements the Project 25 (P25) air interface radio protocol, including baseband frame
 //! synchronization, symbol decoding, error correction coding, and packet reconstuction.
+//!
+//! Everything except the IO helpers builds without `std`, using only `core` and `alloc`,
+//! by disabling the default `std` feature and enabling `alloc`. Times are always tick
+//! counts supplied by the caller, so no clock is needed.
+
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
+
+#[cfg(not(any(feature = "std", feature = "alloc")))]
+compile_error!("either the `std` or the `alloc` feature must be enabled");
+
+#[cfg(all(not(feature = "std"), not(test)))]
+#[macro_use]
+extern crate alloc;
 
 extern crate binfield_matrix;
 extern crate cai_cyclic;
@@ -15,19 +28,60 @@ extern crate serde_derive;
 #[cfg(feature = "ser")]
 extern crate serde;
 
-#[macro_use]
-extern crate static_fir;
+// The pyo3 macros refer to `::core`, which needs to be in the crate root in this edition.
+#[cfg(feature = "python")]
+extern crate core;
+#[cfg(feature = "python")]
+extern crate pyo3;
+
+#[cfg(feature = "parallel")]
+extern crate rayon;
+
+#[cfg(feature = "fuzz")]
+extern crate arbitrary;
+
+/// Stand-in for the parts of `std` used outside the IO helpers, so every module can
+/// refer to the same paths with or without it.
+#[cfg(all(not(feature = "std"), not(test)))]
+mod std {
+    pub use core::*;
+    pub use alloc::{collections, vec};
+}
 
 mod buffer;
 mod util;
 
 pub mod baseband;
 pub mod bits;
+#[cfg(feature = "std")]
+pub mod capture;
 pub mod coding;
 pub mod consts;
+pub mod conventional;
 pub mod data;
+pub mod diagnostic;
 pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+#[cfg(feature = "std")]
+pub mod ingest;
 pub mod message;
+#[cfg(feature = "parallel")]
+pub mod multi;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod receiver;
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(feature = "std")]
+pub mod sink;
+#[cfg(feature = "std")]
+pub mod source;
 pub mod stats;
+#[cfg(any(feature = "std", test))]
+pub mod testutil;
+pub mod time;
 pub mod trunking;
 pub mod voice;
@@ -1,8 +1,36 @@
 //! General low-level receiver for all data units, covering frame synchronization up to
 //! symbol decoding.
 
+use std::collections::VecDeque;
+use std::vec::Vec;
+
+use baseband::afc::Afc;
+use baseband::clock::ClockTracker;
 use baseband::decode::{Decoder, Decider};
-use baseband::sync::{SyncCorrelator, SyncDetector, SymbolThresholds, sync_threshold};
+use baseband::sync::{
+    FINGERPRINT_SAMPS,
+    SYNC_GENERATOR,
+    Polarity,
+    ResyncDetector,
+    SyncCorrelator,
+    SyncDetector,
+    SymbolThresholds,
+    SYNC_PATTERN,
+    SYNC_THRESHOLD,
+    MAX_SMOOTH_AVG,
+    MAX_SNR,
+    SMOOTH_AVG,
+    dc_offset,
+    frequency_error,
+    bipolar_power,
+    instant_thresholds_at,
+    scaled_sync_threshold,
+    strong_sync_threshold,
+    sync_snr,
+};
+use bits::Dibit;
+use consts::{SYMBOL_PERIOD, SYNC_SYMBOLS};
+use diagnostic::Diagnostic;
 use error::{P25Error, Result};
 use message::nid;
 use message::status::{StreamSymbol, StatusDeinterleaver};
@@ -30,6 +58,9 @@ impl SymbolReceiver {
         }
     }
 
+    /// Decide symbols with the given decider from now on.
+    pub fn set_decider(&mut self, decider: Decider) { self.decoder.set_decider(decider); }
+
     /// Feed in a baseband symbol, possibly producing a data or status symbol.
     pub fn feed(&mut self, s: f32) -> Option<StreamSymbol> {
         match self.decoder.feed(s) {
@@ -37,8 +68,26 @@ impl SymbolReceiver {
             None => None,
         }
     }
+
+    /// Feed in an already decided symbol, producing a data or status symbol.
+    pub fn feed_dibit(&mut self, d: Dibit) -> StreamSymbol { self.status.feed(d) }
+}
+
+/// Maximum number of bit errors in a frame sync matched directly on dibits.
+pub const DIBIT_SYNC_ERRORS: u32 = 4;
+
+/// Default maximum number of symbols of a correlated frame sync that may be decided
+/// differently from the sync sequence for it to be accepted.
+pub const MAX_SYNC_ERRORS: usize = 2;
+
+/// Bits of the frame sync sequence, first symbol in the MSBs.
+fn sync_bits() -> u64 {
+    SYNC_GENERATOR.iter().fold(0, |bits, &b| bits << 8 | b as u64)
 }
 
+/// Mask covering the bits of the frame sync sequence.
+const SYNC_MASK: u64 = (1 << (SYNC_SYMBOLS * 2)) - 1;
+
 
 /// An event seen by the low-level receiver.
 #[derive(Debug)]
@@ -47,6 +96,9 @@ pub enum ReceiverEvent {
     Symbol(StreamSymbol),
     /// Decoded NID information.
     NetworkId(nid::NetworkId),
+    /// The current data unit was cut off by the frame sync of a new one, which is now
+    /// being decoded.
+    FrameAborted,
 }
 
 /// Internal state of the state machine.
@@ -91,6 +143,27 @@ impl State {
     pub fn flush_pads(recv: SymbolReceiver) -> State { FlushPads(recv) }
 }
 
+/// Symbol threshold averages of a `DataUnitReceiver`, which stay valid after retuning
+/// to another channel of the same system.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ThresholdState {
+    /// Averages shared between NACs, if any sync was seen.
+    shared: Option<(f32, f32)>,
+    /// Averages of each recently seen NAC, from least to most recent.
+    nacs: Vec<(nid::NetworkAccessCode, Option<(f32, f32)>)>,
+}
+
+/// Create symbol thresholds smoothed over the given number of syncs that continue from
+/// the given averages, if any.
+fn resume_thresholds(avgs: Option<(f32, f32)>, syncs: usize) -> SymbolThresholds {
+    let t = SymbolThresholds::with_smoothing(syncs);
+
+    match avgs {
+        Some((p, n)) => t.resume(p, n),
+        None => t,
+    }
+}
+
 /// State machine for low-level data unit reception.
 ///
 /// The state machine consumes baseband samples and performs the following steps common to
@@ -101,13 +174,58 @@ impl State {
 /// 3. Deinterleave status symbols
 /// 4. Decode NID information
 /// 5. Decode dibit symbols until stopped
+///
+/// Frame sync correlation continues while a data unit is decoded, and a strong sync
+/// aborts the current data unit and starts decoding the new one.
+///
+/// Symbol thresholds can be smoothed separately for each NAC, so data units from
+/// different systems interleaved on a channel don't skew each other. In that case the
+/// NID is decoded with thresholds from its frame sync alone, and the rest of the data
+/// unit with those smoothed over previous syncs on the same NAC.
+///
+/// Symbols that were already decided, such as those of a dibit capture, can be fed in
+/// instead of samples, in which case the frame sync is matched on the symbols directly.
 pub struct DataUnitReceiver {
     /// Current state.
     state: State,
     /// Tracks input signal power and frame synchronization statistics.
     corr: SyncCorrelator,
+    /// Watches for a new frame sync while decoding a data unit.
+    resync: ResyncDetector,
     /// Tracks thresholds for symbol decisions.
     symthresh: SymbolThresholds,
+    /// Symbol thresholds of each recently seen NAC, from least to most recent.
+    nacs: Vec<(nid::NetworkAccessCode, SymbolThresholds)>,
+    /// Maximum number of NACs to track thresholds for, or 0 to share thresholds.
+    max_nacs: usize,
+    /// Number of syncs symbol thresholds are smoothed over.
+    smoothing: usize,
+    /// Samples of the frame sync of the current data unit.
+    sync: [f32; FINGERPRINT_SAMPS],
+    /// Offset of the correlation peak of the current frame sync between samples.
+    offset: f32,
+    /// Whether to record sync and NID diagnostics.
+    diagnose: bool,
+    /// Diagnostic records waiting to be taken.
+    diags: VecDeque<Diagnostic>,
+    /// Bits of the most recent dibits fed in directly, latest in the LSBs.
+    dibits: u64,
+    /// Frequency control loop updated at each frame sync, if enabled.
+    afc: Option<Afc>,
+    /// Symbol clock tracker updated at each frame sync, if enabled.
+    clock: Option<ClockTracker>,
+    /// Number of baseband samples fed in.
+    samples: u64,
+    /// Number of samples fed in while searching for a frame sync since the last one.
+    hunt: usize,
+    /// Factor of the RMS signal power for the sync detection threshold.
+    sync_factor: f32,
+    /// Maximum number of symbol errors in an accepted frame sync.
+    max_sync_errors: usize,
+    /// Polarity of the signal found at the last frame sync.
+    polarity: Polarity,
+    /// Signal to noise ratio, in dB, estimated at the last frame sync.
+    snr: f32,
     stats: Stats,
 }
 
@@ -117,40 +235,255 @@ impl DataUnitReceiver {
         DataUnitReceiver {
             state: State::sync(),
             corr: SyncCorrelator::new(),
+            resync: ResyncDetector::new(),
             symthresh: SymbolThresholds::new(),
+            nacs: vec![],
+            max_nacs: 0,
+            smoothing: SMOOTH_AVG,
+            sync: [0.0; FINGERPRINT_SAMPS],
+            offset: 0.0,
+            diagnose: false,
+            diags: VecDeque::new(),
+            dibits: 0,
+            afc: None,
+            clock: None,
+            samples: 0,
+            hunt: 0,
+            sync_factor: SYNC_THRESHOLD,
+            max_sync_errors: MAX_SYNC_ERRORS,
+            polarity: Polarity::Normal,
+            snr: -MAX_SNR,
             stats: Stats::default(),
         }
     }
 
+    /// Record a diagnostic for each frame sync detection and NID decode, if `on` is
+    /// true.
+    pub fn set_diagnostics(&mut self, on: bool) {
+        self.diagnose = on;
+
+        if !on {
+            self.diags.clear();
+        }
+    }
+
+    /// Take the oldest diagnostic record, if any.
+    pub fn next_diagnostic(&mut self) -> Option<Diagnostic> { self.diags.pop_front() }
+
+    /// Update the given frequency control loop at each frame sync, or stop using one if
+    /// `None`.
+    pub fn set_afc(&mut self, afc: Option<Afc>) { self.afc = afc; }
+
+    /// Frequency control loop, if enabled.
+    pub fn afc(&self) -> Option<&Afc> { self.afc.as_ref() }
+
+    /// Update the given symbol clock tracker at each frame sync and sample symbols at
+    /// its corrected rate, or stop using one if `None`.
+    pub fn set_clock(&mut self, clock: Option<ClockTracker>) { self.clock = clock; }
+
+    /// Symbol clock tracker, if enabled.
+    pub fn clock(&self) -> Option<&ClockTracker> { self.clock.as_ref() }
+
+    /// Detect frame syncs above the given factor of the RMS signal power, which can be
+    /// changed at any time without disturbing the correlation history.
+    pub fn set_sync_threshold(&mut self, factor: f32) {
+        assert!(factor > 0.0);
+        self.sync_factor = factor;
+    }
+
+    /// Factor of the RMS signal power for the sync detection threshold.
+    pub fn sync_threshold(&self) -> f32 { self.sync_factor }
+
+    /// Accept a correlated frame sync only if at most the given number of its symbols,
+    /// decided with thresholds from the sync itself, differ from the sync sequence,
+    /// which catches correlation peaks on noise and analog voice. Any number from
+    /// `SYNC_SYMBOLS` up accepts every sync.
+    pub fn set_max_sync_errors(&mut self, max: usize) { self.max_sync_errors = max; }
+
+    /// Maximum number of symbol errors in an accepted frame sync.
+    pub fn max_sync_errors(&self) -> usize { self.max_sync_errors }
+
+    /// Polarity of the signal found at the last frame sync, which is corrected for
+    /// until the next one.
+    pub fn polarity(&self) -> Polarity { self.polarity }
+
+    /// Signal to noise ratio, in dB, estimated from the samples of the last frame sync,
+    /// or `-MAX_SNR` if none has been seen.
+    pub fn snr(&self) -> f32 { self.snr }
+
+    /// Track symbol thresholds separately for up to the given number of NACs, dropping
+    /// the least recently seen NAC to make room for a new one.
+    pub fn set_max_nacs(&mut self, max: usize) {
+        self.max_nacs = max;
+
+        if self.nacs.len() > max {
+            let extra = self.nacs.len() - max;
+            self.nacs.drain(..extra);
+        }
+    }
+
+    /// Smooth symbol thresholds over the given number of syncs, from 1 to
+    /// `MAX_SMOOTH_AVG`, continuing from the current averages.
+    pub fn set_smoothing(&mut self, syncs: usize) {
+        assert!(syncs > 0 && syncs <= MAX_SMOOTH_AVG);
+
+        self.smoothing = syncs;
+        self.symthresh = resume_thresholds(self.symthresh.averages(), syncs);
+
+        for &mut (_, ref mut t) in self.nacs.iter_mut() {
+            *t = resume_thresholds(t.averages(), syncs);
+        }
+    }
+
+    /// Number of syncs symbol thresholds are smoothed over.
+    pub fn smoothing(&self) -> usize { self.smoothing }
+
     /// Flush any remaining padding symbols at the end of the current packet, and reenter
-    /// the frame synchronization state afterwards.
+    /// the frame synchronization state afterwards. If no packet is being decoded, frame
+    /// synchronization is reentered immediately.
     pub fn flush_pads(&mut self) {
         match self.state {
             DecodePacket(recv) => self.state = State::flush_pads(recv),
             Sync(_) => {},
-            _ => panic!("not decoding a packet"),
+            _ => self.resync(),
         }
     }
 
     /// Force the receiver into frame synchronization.
     pub fn resync(&mut self) { self.state = State::sync(); }
 
+    /// Capture the symbol threshold averages.
+    pub fn snapshot(&self) -> ThresholdState {
+        ThresholdState {
+            shared: self.symthresh.averages(),
+            nacs: self.nacs.iter().map(|&(nac, ref t)| (nac, t.averages())).collect(),
+        }
+    }
+
+    /// Continue from the given symbol threshold averages, as after retuning, and reset
+    /// the sync correlation history, the frequency control loop, and any data unit being
+    /// decoded.
+    pub fn restore(&mut self, state: &ThresholdState) {
+        self.state = State::sync();
+        self.corr = SyncCorrelator::new();
+        self.resync = ResyncDetector::new();
+        self.dibits = 0;
+        self.polarity = Polarity::Normal;
+
+        if let Some(ref mut afc) = self.afc {
+            afc.reset();
+        }
+
+        if let Some(ref mut clock) = self.clock {
+            clock.reset();
+        }
+
+        let syncs = self.smoothing;
+        self.symthresh = resume_thresholds(state.shared, syncs);
+        self.nacs = state.nacs.iter()
+            .map(|&(nac, avgs)| (nac, resume_thresholds(avgs, syncs)))
+            .collect();
+
+        let max = self.max_nacs;
+        self.set_max_nacs(max);
+    }
+
+    /// Whether the receiver is locked onto a data unit, from its frame sync until the end
+    /// of its padding.
+    pub fn synced(&self) -> bool {
+        if let Sync(_) = self.state { false } else { true }
+    }
+
     /// Determine the next action to take based on the given sample.
     fn handle(&mut self, s: f32) -> StateChange {
+        let s = match self.afc {
+            Some(ref afc) if afc.correcting() => s - afc.dc_offset(),
+            _ => s,
+        };
+
+        // Undo the inversion found at the last frame sync, so the correlation and
+        // symbol decisions see a normal signal.
+        let s = self.polarity.correct(s);
+
         // Continuously track the input signal power.
         let (corrpow, sigpow) = self.corr.feed(s);
+        self.samples += 1;
+
+        let synced = self.synced();
+
+        if synced && self.resync.detect(corrpow, sigpow) {
+            let power = self.resync.peak().unwrap_or(corrpow);
+            let offset = self.resync.interpolate().map_or(0.0, |p| p.offset());
+
+            match self.verify(Polarity::Normal, offset) {
+                Some(errors) => {
+                    let state = self.lock(power, sigpow, strong_sync_threshold(sigpow),
+                                          true, Polarity::Normal, offset, errors);
+
+                    return EventChange(ReceiverEvent::FrameAborted, state);
+                },
+                // Keep decoding the current data unit.
+                None => self.resync = ResyncDetector::new(),
+            }
+        }
 
         match self.state {
-            Sync(ref mut sync) => if sync.detect(corrpow, sync_threshold(sigpow)) {
-                let history = self.corr.history();
-                let (p, m, n) = self.symthresh.thresholds(&history);
+            Sync(ref mut sync) => {
+                self.hunt += 1;
+                let thresh = scaled_sync_threshold(sigpow, self.sync_factor);
 
-                Change(State::decode_nid(Decoder::new(Decider::new(p, m, n))))
-            } else {
-                NoChange
+                // A sync of the opposite polarity peaks just as high, but negative.
+                if sync.detect(bipolar_power(corrpow, sigpow), thresh) {
+                    let power = sync.peak().unwrap_or(corrpow);
+                    let offset = sync.interpolate().map_or(0.0, |p| p.offset());
+                    let polarity = Polarity::of(corrpow);
+
+                    match self.verify(polarity, offset) {
+                        Some(errors) => Change(self.lock(power, sigpow, thresh, false,
+                                                         polarity, offset, errors)),
+                        // Start hunting afresh.
+                        None => Change(State::sync()),
+                    }
+                } else {
+                    NoChange
+                }
             },
+            _ => self.advance(|recv| recv.feed(s), true),
+        }
+    }
+
+    /// Determine the next action to take based on the given symbol, matching the frame
+    /// sync directly on symbols instead of correlating samples.
+    fn handle_dibit(&mut self, d: Dibit) -> StateChange {
+        self.dibits = (self.dibits << 2 | d.bits() as u64) & SYNC_MASK;
+
+        if (self.dibits ^ sync_bits()).count_ones() <= DIBIT_SYNC_ERRORS {
+            // The matched symbols can't also start the next sync.
+            self.dibits = 0;
+
+            // Symbols are already decided, so the decoder goes unused.
+            let state = State::decode_nid(Decoder::new(Decider::new(0.0, 0.0, 0.0)));
+
+            return if self.synced() {
+                EventChange(ReceiverEvent::FrameAborted, state)
+            } else {
+                Change(state)
+            };
+        }
+
+        self.advance(|recv| Some(recv.feed_dibit(d)), false)
+    }
+
+    /// Determine the next action to take while decoding a data unit, based on the
+    /// symbol produced by the given function, if any. Symbol thresholds are only
+    /// tracked if `baseband` is true.
+    fn advance<F>(&mut self, next: F, baseband: bool) -> StateChange
+        where F: FnOnce(&mut SymbolReceiver) -> Option<StreamSymbol>
+    {
+        match self.state {
+            Sync(_) => NoChange,
             DecodeNID(ref mut recv, ref mut nidrecv) => {
-                let dibit = match recv.feed(s) {
+                let dibit = match next(recv) {
                     Some(StreamSymbol::Data(d)) => d,
                     Some(s) => return Event(ReceiverEvent::Symbol(s)),
                     None => return NoChange,
@@ -158,19 +491,32 @@ impl DataUnitReceiver {
 
                 match nidrecv.feed(dibit) {
                     Some(Ok(nid)) => {
+                        if self.diagnose {
+                            self.diags.push_back(Diagnostic::NidDecoded {
+                                errors: nidrecv.stats().bch.fixed,
+                            });
+                        }
+
                         self.stats.merge(nidrecv);
+
+                        let mut recv = *recv;
+
+                        if baseband && self.max_nacs > 0 {
+                            recv.set_decider(self.nac_decider(nid.access_code));
+                        }
+
                         EventChange(ReceiverEvent::NetworkId(nid),
-                                    State::decode_packet(*recv))
+                                    State::decode_packet(recv))
                     },
                     Some(Err(e)) => Error(e),
                     None => NoChange,
                 }
             },
-            DecodePacket(ref mut recv) => match recv.feed(s) {
+            DecodePacket(ref mut recv) => match next(recv) {
                 Some(x) => Event(ReceiverEvent::Symbol(x)),
                 None => NoChange,
             },
-            FlushPads(ref mut recv) => match recv.feed(s) {
+            FlushPads(ref mut recv) => match next(recv) {
                 // According to the spec, the stream is padded until the next status
                 // symbol boundary.
                 Some(StreamSymbol::Status(_)) => Change(State::sync()),
@@ -179,11 +525,123 @@ impl DataUnitReceiver {
         }
     }
 
+    /// Check the frame sync that just peaked against the sync sequence, deciding its
+    /// symbols with thresholds from it alone, and return the number of symbol errors if
+    /// few enough to accept it. The sync has the given polarity relative to the signal
+    /// as currently corrected, and its symbol instants are the given fraction of a
+    /// sample off the sampled ones.
+    fn verify(&self, polarity: Polarity, offset: f32) -> Option<usize> {
+        let sync = polarity.correct_sync(&self.corr.history());
+        let thresholds = instant_thresholds_at(&sync, offset);
+        let errors = SYNC_PATTERN.symbol_errors(&sync, thresholds, offset);
+
+        if errors <= self.max_sync_errors { Some(errors) } else { None }
+    }
+
+    /// Lock onto the frame sync that just peaked at the given power, with the given
+    /// signal power, above the given threshold, returning the state for decoding the
+    /// following NID. The sync has the given polarity relative to the signal as
+    /// currently corrected, its symbol instants are the given fraction of a sample off
+    /// the sampled ones, and it has the given number of symbol errors.
+    fn lock(&mut self, power: f32, sigpower: f32, threshold: f32, resync: bool,
+            polarity: Polarity, offset: f32, errors: usize) -> State
+    {
+        self.sync = polarity.correct_sync(&self.corr.history());
+        self.offset = offset;
+        self.polarity = self.polarity.combine(polarity);
+
+        if let Some(ref mut afc) = self.afc {
+            // Corrected samples only show the remaining error.
+            let dc = dc_offset(&self.sync);
+            afc.feed(if afc.correcting() { dc } else { dc - afc.dc_offset() });
+        }
+
+        let (p, m, n) = if self.max_nacs > 0 {
+            instant_thresholds_at(&self.sync, offset)
+        } else {
+            self.symthresh.thresholds_at(&self.sync, offset)
+        };
+
+        self.snr = sync_snr(&self.sync, (p, m, n));
+
+        if self.diagnose {
+            self.diags.push_back(Diagnostic::SyncDetected {
+                power: power,
+                sigpower: sigpower,
+                threshold: threshold,
+                polarity: self.polarity,
+                resync: resync,
+                symbols: (p, m, n),
+                frequency: frequency_error(&self.sync),
+                snr: self.snr,
+                errors: errors,
+            });
+        }
+
+        self.resync = ResyncDetector::new();
+
+        let decider = Decider::new(p, m, n);
+        let hunt = self.hunt;
+        self.hunt = 0;
+
+        let clock = match self.clock {
+            Some(ref mut clock) => clock,
+            None => return State::decode_nid(Decoder::new(decider)),
+        };
+
+        // Only a sync that follows right after the previous data unit, rather than one
+        // cut off or one after a gap in the signal, is a whole number of symbols on.
+        if resync || hunt > (SYNC_SYMBOLS + 4) * SYMBOL_PERIOD {
+            clock.lose();
+        }
+
+        // The peak was the sample before this one.
+        clock.feed((self.samples - 1) as f64 + offset as f64);
+
+        State::decode_nid(Decoder::with_timing(decider, offset, clock.period()))
+    }
+
+    /// Smooth the thresholds from the current frame sync with those of previous syncs on
+    /// the given NAC, returning a decider that uses them.
+    fn nac_decider(&mut self, nac: nid::NetworkAccessCode) -> Decider {
+        let entry = match self.nacs.iter().position(|&(n, _)| n == nac) {
+            Some(idx) => self.nacs.remove(idx),
+            None => {
+                if self.nacs.len() == self.max_nacs {
+                    self.nacs.remove(0);
+                }
+
+                (nac, SymbolThresholds::with_smoothing(self.smoothing))
+            },
+        };
+
+        self.nacs.push(entry);
+
+        let (p, m, n) = self.nacs.last_mut().unwrap().1.thresholds_at(&self.sync, self.offset);
+        Decider::new(p, m, n)
+    }
+
     /// Feed in a baseband symbol, possibly producing a receiver event. Return
     /// `Some(Ok(event))` for any normal event, `Some(Err(err))` for any error, and `None`
     /// if no event occurred.
     pub fn feed(&mut self, s: f32) -> Option<Result<ReceiverEvent>> {
-        match self.handle(s) {
+        let change = self.handle(s);
+        self.apply(change)
+    }
+
+    /// Feed in an already decided symbol, possibly producing a receiver event, with the
+    /// same results as `feed`.
+    ///
+    /// This bypasses the baseband stage, so symbol thresholds and sync correlation
+    /// history are unaffected and no sync diagnostics are recorded.
+    pub fn feed_dibit(&mut self, d: Dibit) -> Option<Result<ReceiverEvent>> {
+        let change = self.handle_dibit(d);
+        self.apply(change)
+    }
+
+    /// Apply the given action, returning the resulting event, if any.
+    fn apply(&mut self, change: StateChange) -> Option<Result<ReceiverEvent>> {
+        match change {
             Change(state) => {
                 self.state = state;
                 None
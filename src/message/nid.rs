@@ -1,5 +1,7 @@
 //! Network ID (NID), Network Access Code (NAC), and Data Unit utilities.
 
+use std::vec::Vec;
+
 use bits::Dibit;
 use buffer;
 use coding::bch;
@@ -8,6 +10,7 @@ use stats::{Stats, HasStats};
 
 /// "Digital squelch" NAC field of the NID.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "ser", derive(Serialize, Deserialize))]
 pub enum NetworkAccessCode {
     /// Default P25 NAC.
     Default,
@@ -45,10 +48,61 @@ impl NetworkAccessCode {
             Other(bits) => bits,
         }
     }
+
+    /// Whether the NAC is one of the wildcards that unsquelch on any NAC.
+    pub fn is_wildcard(self) -> bool {
+        match self {
+            NetworkAccessCode::ReceiveAny | NetworkAccessCode::RepeatAny => true,
+            _ => false,
+        }
+    }
+
+    /// Whether a receiver configured with this NAC unsquelches on the given NAC.
+    pub fn matches(self, nac: NetworkAccessCode) -> bool {
+        self.is_wildcard() || self == nac
+    }
+}
+
+/// Decides which NACs are received, using an accept list and a reject list.
+///
+/// An empty accept list accepts every NAC, and a wildcard in the accept list matches
+/// every NAC. The reject list only matches NACs exactly, so it can drop frames that were
+/// transmitted with a wildcard NAC.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "ser", derive(Serialize, Deserialize))]
+pub struct NacFilter {
+    /// NACs to receive, or empty to receive all.
+    accept: Vec<NetworkAccessCode>,
+    /// NACs to drop.
+    reject: Vec<NetworkAccessCode>,
+}
+
+impl NacFilter {
+    /// Create a new `NacFilter` that accepts every NAC.
+    pub fn new() -> NacFilter { NacFilter::default() }
+
+    /// Add the given NAC to the accept list.
+    pub fn accept(mut self, nac: NetworkAccessCode) -> Self {
+        self.accept.push(nac);
+        self
+    }
+
+    /// Add the given NAC to the reject list.
+    pub fn reject(mut self, nac: NetworkAccessCode) -> Self {
+        self.reject.push(nac);
+        self
+    }
+
+    /// Whether frames with the given NAC should be received.
+    pub fn allows(&self, nac: NetworkAccessCode) -> bool {
+        !self.reject.contains(&nac) &&
+            (self.accept.is_empty() || self.accept.iter().any(|a| a.matches(nac)))
+    }
 }
 
 /// Data unit of associated packet.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "ser", derive(Serialize, Deserialize))]
 pub enum DataUnit {
     /// Voice header packet.
     VoiceHeader,
@@ -193,3 +247,64 @@ impl NidReceiver {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::NetworkAccessCode::*;
+
+    #[test]
+    fn test_accept() {
+        let f = NacFilter::new();
+        assert!(f.allows(Default));
+        assert!(f.allows(Other(0x123)));
+
+        let f = NacFilter::new().accept(Other(0x123)).accept(Default);
+        assert!(f.allows(Other(0x123)));
+        assert!(f.allows(Default));
+        assert!(!f.allows(Other(0x124)));
+        assert!(!f.allows(ReceiveAny));
+    }
+
+    #[test]
+    fn test_reject() {
+        let f = NacFilter::new().reject(Other(0x123));
+        assert!(!f.allows(Other(0x123)));
+        assert!(f.allows(Other(0x124)));
+        assert!(f.allows(Default));
+
+        // Rejection wins over acceptance.
+        let f = NacFilter::new().accept(Other(0x123)).reject(Other(0x123));
+        assert!(!f.allows(Other(0x123)));
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert!(ReceiveAny.is_wildcard());
+        assert!(RepeatAny.is_wildcard());
+        assert!(!Default.is_wildcard());
+        assert_eq!(NetworkAccessCode::from_bits(0xF7E), ReceiveAny);
+        assert_eq!(NetworkAccessCode::from_bits(0xF7F), RepeatAny);
+
+        for &w in [ReceiveAny, RepeatAny].iter() {
+            assert!(w.matches(Other(0x123)));
+            assert!(w.matches(Default));
+
+            let f = NacFilter::new().accept(w);
+            assert!(f.allows(Other(0x123)));
+            assert!(f.allows(ReceiveAny));
+        }
+
+        assert!(!Other(0x123).matches(ReceiveAny));
+
+        // Rejecting a wildcard only drops frames carrying it.
+        let f = NacFilter::new().reject(ReceiveAny);
+        assert!(!f.allows(ReceiveAny));
+        assert!(f.allows(RepeatAny));
+        assert!(f.allows(Other(0x123)));
+
+        let f = NacFilter::new().accept(RepeatAny).reject(Other(0x123));
+        assert!(!f.allows(Other(0x123)));
+        assert!(f.allows(Other(0x124)));
+    }
+}
@@ -1,9 +1,28 @@
 //! High-level receiver for receiving P25 voice, data, and trunking messages.
 
-use error::P25Error;
-use message::data_unit::{DataUnitReceiver, ReceiverEvent};
-use message::nid::NetworkId;
-use message::status::StreamSymbol;
+use std;
+use std::vec::Vec;
+
+use baseband::afc::Afc;
+use baseband::clock::ClockTracker;
+use baseband::sync::Polarity;
+use bits::Dibit;
+use consts::{
+    DATA_FRAG_DIBITS,
+    EXTRA_HEXBITS,
+    EXTRA_PIECE_DIBITS,
+    FRAME_DIBITS,
+    HEADER_HEXBITS,
+    HEADER_WORD_DIBITS,
+    LC_TERM_WORD_DIBITS,
+};
+use data::assemble::{PduEvent, PduReceiver};
+use diagnostic::Diagnostic;
+use error::{P25Error, Result};
+use message::data_unit::{DataUnitReceiver, ReceiverEvent, ThresholdState};
+use message::nid::{DataUnit, NacFilter, NetworkAccessCode, NetworkId};
+use message::status::{StatusCode, StreamSymbol};
+use trunking::ambt::{Ambt, AmbtReceiver};
 use trunking::tsbk::{TsbkFields, TsbkReceiver};
 use voice::control::LinkControlFields;
 use voice::crypto::CryptoControlFields;
@@ -34,10 +53,79 @@ pub enum MessageEvent {
     CryptoControl(CryptoControlFields),
     /// A voice low-speed data fragment was decoded.
     LowSpeedDataFragment(u32),
-    /// A trunking signalling packet was received.
+    /// A trunking signalling packet was received, either as a TSBK or repacked from an
+    /// AMBT data packet.
     TrunkingControl(TsbkFields),
+    /// An AMBT data packet carrying a trunking message with no TSBK form, such as an
+    /// authentication demand, was received.
+    TrunkingMultiBlock(Ambt),
     /// A voice terminator link control was received.
     VoiceTerm(LinkControlFields),
+    /// A data packet other than an AMBT was received or dropped.
+    DataPacket(PduEvent),
+    /// The data dibits of a data unit routed for raw passthrough were received, with
+    /// status symbols removed. They can be borrowed with `MessageReceiver::raw` until the
+    /// next sample is fed in.
+    RawDataUnit(DataUnit),
+    /// The current data unit was cut off by the frame sync of a new one.
+    FrameAborted,
+    /// A data unit was skipped because its NAC isn't received.
+    FrameFiltered {
+        /// NAC of the data unit.
+        nac: NetworkAccessCode,
+        /// Type of the data unit.
+        duid: DataUnit,
+    },
+}
+
+/// How the receiver handles a type of data unit.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "ser", derive(Serialize, Deserialize))]
+pub enum Route {
+    /// Fully decode the data unit.
+    Decode,
+    /// Skip over the data unit, producing no events other than its NID.
+    Skip,
+    /// Pass the data dibits of the data unit through undecoded.
+    Raw,
+}
+
+/// Route for each type of data unit.
+///
+/// Voice headers, frame groups, and LC terminators have a fixed length, so they're
+/// skipped or passed through without decoding. Trunking and data packets are still
+/// decoded to find their end, but their events are dropped. Simple terminators carry
+/// nothing after the NID, so every route behaves the same for them.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "ser", derive(Serialize, Deserialize))]
+pub struct Routes([Route; 7]);
+
+impl Default for Routes {
+    fn default() -> Self { Routes([Route::Decode; 7]) }
+}
+
+impl Routes {
+    /// Get the route of the given data unit.
+    pub fn get(&self, du: DataUnit) -> Route { self.0[route_idx(du)] }
+
+    /// Set the route of the given data unit.
+    pub fn set(&mut self, du: DataUnit, route: Route) { self.0[route_idx(du)] = route; }
+
+    /// Route the given data unit as given.
+    pub fn with(mut self, du: DataUnit, route: Route) -> Self {
+        self.set(du, route);
+        self
+    }
+}
+
+/// Handling of the events of a data unit that's decoded only to find its end.
+enum Mute {
+    /// Events are passed on.
+    Off,
+    /// Events are dropped.
+    Skip,
+    /// Events are dropped and the data dibits of the given data unit are collected.
+    Raw(DataUnit),
 }
 
 /// Internal state of the state machine.
@@ -54,6 +142,13 @@ enum State {
     DecodeLCTerminator(VoiceLCTerminatorReceiver),
     /// Decoding a trunking signalling packet.
     DecodeTSBK(TsbkReceiver),
+    /// Decoding a data packet, which may carry an AMBT trunking message until its header
+    /// shows otherwise.
+    DecodeData(Option<AmbtReceiver>, PduReceiver),
+    /// Skipping over the given number of remaining data dibits.
+    Skip(usize),
+    /// Collecting the data dibits of the given fixed-length data unit.
+    Raw(DataUnit),
 }
 
 /// Action the state machine should take.
@@ -62,6 +157,8 @@ enum StateChange {
     Event(MessageEvent),
     /// Propagate an event and change state.
     EventChange(MessageEvent, State),
+    /// Change state without an event.
+    Change(State),
     /// Do nothing.
     NoChange,
 }
@@ -72,6 +169,17 @@ pub struct MessageReceiver {
     recv: DataUnitReceiver,
     /// Current state.
     state: State,
+    /// Route of each type of data unit.
+    routes: Routes,
+    /// NACs to receive.
+    filter: NacFilter,
+    /// Handling of the events of the current data unit.
+    mute: Mute,
+    /// Data dibits of the current data unit routed for raw passthrough, reused between
+    /// data units.
+    raw: Vec<Dibit>,
+    /// Status code received with the last symbol, if any.
+    status: Option<StatusCode>,
     stats: Stats,
 }
 
@@ -81,33 +189,162 @@ impl MessageReceiver {
         MessageReceiver {
             recv: DataUnitReceiver::new(),
             state: State::Idle,
+            routes: Routes::default(),
+            filter: NacFilter::default(),
+            mute: Mute::Off,
+            raw: vec![],
+            status: None,
             stats: Stats::default(),
         }
     }
 
+    /// Route data units as given instead of decoding all of them.
+    pub fn with_routes(mut self, routes: Routes) -> Self {
+        self.routes = routes;
+        self
+    }
+
+    /// Track symbol thresholds separately for up to the given number of recently seen
+    /// NACs, or share them between all NACs if 0.
+    pub fn with_max_nacs(mut self, max: usize) -> Self {
+        self.recv.set_max_nacs(max);
+        self
+    }
+
+    /// Smooth symbol thresholds over the given number of syncs, 4 by default.
+    pub fn with_smoothing(mut self, syncs: usize) -> Self {
+        self.recv.set_smoothing(syncs);
+        self
+    }
+
+    /// Skip data units with NACs not allowed by the given filter.
+    pub fn with_nac_filter(mut self, filter: NacFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Record a diagnostic for each frame sync detection and NID decode, if `on` is
+    /// true.
+    pub fn with_diagnostics(mut self, on: bool) -> Self {
+        self.recv.set_diagnostics(on);
+        self
+    }
+
+    /// Take the oldest diagnostic record, if any.
+    pub fn next_diagnostic(&mut self) -> Option<Diagnostic> {
+        self.recv.next_diagnostic()
+    }
+
+    /// Update the given frequency control loop at each frame sync.
+    pub fn with_afc(mut self, afc: Afc) -> Self {
+        self.recv.set_afc(Some(afc));
+        self
+    }
+
+    /// Frequency control loop, if enabled.
+    pub fn afc(&self) -> Option<&Afc> { self.recv.afc() }
+
+    /// Update the given symbol clock tracker at each frame sync.
+    pub fn with_clock(mut self, clock: ClockTracker) -> Self {
+        self.recv.set_clock(Some(clock));
+        self
+    }
+
+    /// Symbol clock tracker, if enabled.
+    pub fn clock(&self) -> Option<&ClockTracker> { self.recv.clock() }
+
+    /// Detect frame syncs above the given factor of the RMS signal power, 0.65 by
+    /// default.
+    pub fn with_sync_threshold(mut self, factor: f32) -> Self {
+        self.recv.set_sync_threshold(factor);
+        self
+    }
+
+    /// Change the sync detection threshold factor, keeping the correlation history.
+    pub fn set_sync_threshold(&mut self, factor: f32) {
+        self.recv.set_sync_threshold(factor);
+    }
+
+    /// Factor of the RMS signal power for the sync detection threshold.
+    pub fn sync_threshold(&self) -> f32 { self.recv.sync_threshold() }
+
+    /// Accept frame syncs with at most the given number of symbol errors, 2 by default.
+    pub fn with_max_sync_errors(mut self, max: usize) -> Self {
+        self.recv.set_max_sync_errors(max);
+        self
+    }
+
+    /// Maximum number of symbol errors in an accepted frame sync.
+    pub fn max_sync_errors(&self) -> usize { self.recv.max_sync_errors() }
+
+    /// Polarity of the signal found at the last frame sync.
+    pub fn polarity(&self) -> Polarity { self.recv.polarity() }
+
+    /// Signal to noise ratio, in dB, estimated at the last frame sync.
+    pub fn snr(&self) -> f32 { self.recv.snr() }
+
     /// Force the receiver into frame synchronization.
     pub fn resync(&mut self) { self.recv.resync(); }
 
+    /// Data dibits of the data unit of the last `RawDataUnit` event.
+    pub fn raw(&self) -> &[Dibit] { &self.raw[..] }
+
+    /// Take the status code received with the last symbol, if any.
+    pub fn take_status(&mut self) -> Option<StatusCode> { self.status.take() }
+
+    /// Capture the symbol threshold averages.
+    pub fn snapshot(&self) -> ThresholdState { self.recv.snapshot() }
+
+    /// Continue from the given symbol threshold averages, as after retuning, dropping
+    /// any data unit being decoded.
+    pub fn restore(&mut self, state: &ThresholdState) {
+        self.recv.restore(state);
+        self.state = State::Idle;
+        self.mute = Mute::Off;
+        self.status = None;
+    }
+
+    /// Whether the receiver is locked onto a data unit, from its frame sync until the end
+    /// of its padding.
+    pub fn synced(&self) -> bool { self.recv.synced() }
+
     /// Feed in a baseband sample, possibly producing a new event or message to be handled
     /// by the given handler.
     pub fn feed(&mut self, s: f32) -> Option<MessageEvent> {
-        match self.handle(s) {
+        let event = self.recv.feed(s);
+        self.apply(event)
+    }
+
+    /// Feed in an already decided symbol, bypassing the baseband stage, possibly
+    /// producing a new event or message.
+    pub fn feed_dibit(&mut self, d: Dibit) -> Option<MessageEvent> {
+        let event = self.recv.feed_dibit(d);
+        self.apply(event)
+    }
+
+    /// Update state with the given low-level event, if any.
+    fn apply(&mut self, event: Option<Result<ReceiverEvent>>) -> Option<MessageEvent> {
+        match self.handle(event) {
             StateChange::Event(e) => Some(e),
             StateChange::EventChange(e, s) => {
                 self.state = s;
                 Some(e)
             },
+            StateChange::Change(s) => {
+                self.state = s;
+                None
+            },
             StateChange::NoChange => None,
         }
     }
 
-    /// Process the given sample and determine how to update state.
-    fn handle(&mut self, s: f32) -> StateChange {
+    /// Process the given low-level event and determine how to update state.
+    fn handle(&mut self, event: Option<Result<ReceiverEvent>>) -> StateChange {
         use self::State::*;
         use self::StateChange::*;
         use message::nid::DataUnit::*;
 
-        let event = match self.recv.feed(s) {
+        let event = match event {
             Some(Ok(event)) => event,
             Some(Err(err)) => {
                 self.recv.resync();
@@ -120,33 +357,63 @@ impl MessageReceiver {
 
         let dibit = match event {
             ReceiverEvent::NetworkId(nid) => {
-                let next = match nid.data_unit {
-                    VoiceHeader =>
-                        DecodeHeader(VoiceHeaderReceiver::new()),
-                    VoiceSimpleTerminator => {
+                let du = nid.data_unit;
+                let allowed = self.filter.allows(nid.access_code);
+
+                let route = if allowed {
+                    self.routes.get(du)
+                } else {
+                    Route::Skip
+                };
+
+                self.mute = Mute::Off;
+                self.raw.clear();
+
+                let next = match (du, route, payload_dibits(du)) {
+                    (VoiceSimpleTerminator, _, _) => {
                         self.recv.flush_pads();
                         Idle
                     },
-                    VoiceLCTerminator =>
-                        DecodeLCTerminator(VoiceLCTerminatorReceiver::new()),
-                    VoiceLCFrameGroup =>
-                        DecodeLCFrameGroup(VoiceLCFrameGroupReceiver::new()),
-                    VoiceCCFrameGroup =>
-                        DecodeCCFrameGroup(VoiceCCFrameGroupReceiver::new()),
-                    TrunkingSignaling =>
-                        DecodeTSBK(TsbkReceiver::new()),
-                    DataPacket => {
-                        self.recv.resync();
-                        Idle
+                    (_, Route::Skip, Some(n)) => Skip(n),
+                    (_, Route::Raw, Some(n)) => {
+                        self.raw.reserve(n);
+                        Raw(du)
+                    },
+                    (_, Route::Skip, None) => {
+                        self.mute = Mute::Skip;
+                        decoder(du)
+                    },
+                    (_, Route::Raw, None) => {
+                        self.mute = Mute::Raw(du);
+                        decoder(du)
                     },
+                    (_, Route::Decode, _) => decoder(du),
                 };
 
-                return EventChange(MessageEvent::PacketNID(nid), next);
+                return if allowed {
+                    EventChange(MessageEvent::PacketNID(nid), next)
+                } else {
+                    EventChange(MessageEvent::FrameFiltered {
+                        nac: nid.access_code,
+                        duid: du,
+                    }, next)
+                };
+            },
+            ReceiverEvent::FrameAborted => {
+                self.mute = Mute::Off;
+                return EventChange(MessageEvent::FrameAborted, Idle);
+            },
+            ReceiverEvent::Symbol(StreamSymbol::Status(code)) => {
+                self.status = Some(code);
+                return NoChange;
             },
-            ReceiverEvent::Symbol(StreamSymbol::Status(_)) => return NoChange,
             ReceiverEvent::Symbol(StreamSymbol::Data(dibit)) => dibit,
         };
 
+        if let Mute::Raw(_) = self.mute {
+            self.raw.push(dibit);
+        }
+
         let next = match self.state {
             DecodeHeader(ref mut head) => match head.feed(dibit) {
                 Some(Ok(h)) => {
@@ -212,19 +479,77 @@ impl MessageReceiver {
                 None => NoChange,
             },
             DecodeTSBK(ref mut dec) => match dec.feed(dibit) {
-                Some(Ok(tsbk)) => {
-                    if tsbk.is_tail() {
-                        self.recv.flush_pads();
-                    }
-
+                Some(Ok(tsbk)) => if dec.done() {
+                    self.recv.flush_pads();
+                    EventChange(MessageEvent::TrunkingControl(tsbk), Idle)
+                } else {
                     Event(MessageEvent::TrunkingControl(tsbk))
                 },
-                Some(Err(err)) => {
+                // A bad block doesn't affect the others, so keep decoding the rest of
+                // the TSDU.
+                Some(Err(err)) => if dec.done() {
                     self.recv.resync();
                     EventChange(MessageEvent::Error(err), Idle)
+                } else {
+                    Event(MessageEvent::Error(err))
                 },
                 None => NoChange,
             },
+            DecodeData(ref mut ambt, ref mut pdu) => {
+                let packet = pdu.feed(dibit);
+
+                let trunking = match *ambt {
+                    Some(ref mut dec) => dec.feed(dibit),
+                    None => None,
+                };
+
+                match trunking {
+                    Some(Ok(ambt)) => {
+                        self.recv.flush_pads();
+
+                        // Messages without a TSBK form would lose fields if repacked.
+                        EventChange(if ambt.has_tsbk_form() {
+                            MessageEvent::TrunkingControl(ambt.tsbk())
+                        } else {
+                            MessageEvent::TrunkingMultiBlock(ambt)
+                        }, Idle)
+                    },
+                    // Other data packets are left to the packet assembler.
+                    Some(Err(P25Error::UnsupportedFormat { .. })) => {
+                        *ambt = None;
+                        packet_change(&mut self.recv, packet)
+                    },
+                    Some(Err(err)) => {
+                        self.recv.resync();
+                        EventChange(MessageEvent::Error(err), Idle)
+                    },
+                    None => if ambt.is_none() {
+                        packet_change(&mut self.recv, packet)
+                    } else {
+                        NoChange
+                    },
+                }
+            },
+            Skip(ref mut remain) => {
+                *remain -= 1;
+
+                if *remain == 0 {
+                    self.recv.flush_pads();
+                    Change(Idle)
+                } else {
+                    NoChange
+                }
+            },
+            Raw(du) => {
+                self.raw.push(dibit);
+
+                if Some(self.raw.len()) == payload_dibits(du) {
+                    self.recv.flush_pads();
+                    EventChange(MessageEvent::RawDataUnit(du), Idle)
+                } else {
+                    NoChange
+                }
+            },
             Idle => NoChange,
         };
 
@@ -234,10 +559,99 @@ impl MessageReceiver {
             DecodeCCFrameGroup(ref mut fg) => self.stats.merge(fg),
             DecodeLCTerminator(ref mut term) => self.stats.merge(term),
             DecodeTSBK(ref mut tsbk) => self.stats.merge(tsbk),
-            Idle => {},
+            DecodeData(ref mut ambt, ref mut pdu) => {
+                if let Some(ref mut ambt) = *ambt {
+                    self.stats.merge(ambt);
+                }
+
+                self.stats.merge(pdu);
+            },
+            Skip(_) | Raw(_) | Idle => {},
         }
 
-        next
+        self.muted(next)
+    }
+
+    /// Drop the events of a muted data unit, passing its data dibits on once it ends if
+    /// they were collected.
+    fn muted(&mut self, change: StateChange) -> StateChange {
+        use self::StateChange::*;
+
+        match std::mem::replace(&mut self.mute, Mute::Off) {
+            Mute::Off => change,
+            mute => match change {
+                EventChange(_, state) | Change(state) => match mute {
+                    Mute::Raw(du) => EventChange(MessageEvent::RawDataUnit(du), state),
+                    _ => Change(state),
+                },
+                Event(_) | NoChange => {
+                    self.mute = mute;
+                    NoChange
+                },
+            },
+        }
+    }
+}
+
+/// Initial state for fully decoding the given data unit.
+fn decoder(du: DataUnit) -> State {
+    use self::State::*;
+    use message::nid::DataUnit::*;
+
+    match du {
+        VoiceHeader => DecodeHeader(VoiceHeaderReceiver::new()),
+        // Simple terminators carry nothing to decode.
+        VoiceSimpleTerminator => Idle,
+        VoiceLCTerminator => DecodeLCTerminator(VoiceLCTerminatorReceiver::new()),
+        VoiceLCFrameGroup => DecodeLCFrameGroup(VoiceLCFrameGroupReceiver::new()),
+        VoiceCCFrameGroup => DecodeCCFrameGroup(VoiceCCFrameGroupReceiver::new()),
+        TrunkingSignaling => DecodeTSBK(TsbkReceiver::new()),
+        DataPacket => DecodeData(Some(AmbtReceiver::new()), PduReceiver::new()),
+    }
+}
+
+/// Determine how to update state after the given packet assembler event.
+fn packet_change(recv: &mut DataUnitReceiver, packet: Option<PduEvent>) -> StateChange {
+    let event = match packet {
+        Some(event) => event,
+        None => return StateChange::NoChange,
+    };
+
+    match event {
+        PduEvent::BadHeader(_) | PduEvent::BadBlock { .. } => recv.resync(),
+        _ => recv.flush_pads(),
+    }
+
+    StateChange::EventChange(MessageEvent::DataPacket(event), State::Idle)
+}
+
+/// Index of the given data unit in the routing table.
+fn route_idx(du: DataUnit) -> usize {
+    use message::nid::DataUnit::*;
+
+    match du {
+        VoiceHeader => 0,
+        VoiceSimpleTerminator => 1,
+        VoiceLCTerminator => 2,
+        VoiceLCFrameGroup => 3,
+        VoiceCCFrameGroup => 4,
+        DataPacket => 5,
+        TrunkingSignaling => 6,
+    }
+}
+
+/// Number of data dibits following the NID in the given data unit, if it has a fixed
+/// length with a payload.
+fn payload_dibits(du: DataUnit) -> Option<usize> {
+    use message::nid::DataUnit::*;
+
+    match du {
+        VoiceHeader => Some(HEADER_HEXBITS * HEADER_WORD_DIBITS),
+        VoiceLCTerminator => Some(EXTRA_HEXBITS / 2 * LC_TERM_WORD_DIBITS),
+        VoiceLCFrameGroup | VoiceCCFrameGroup =>
+            Some(9 * FRAME_DIBITS + EXTRA_HEXBITS / 4 * EXTRA_PIECE_DIBITS +
+                 2 * DATA_FRAG_DIBITS),
+        VoiceSimpleTerminator | DataPacket | TrunkingSignaling => None,
     }
 }
 
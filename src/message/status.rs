@@ -1,4 +1,5 @@
-//! Status symbol interleaving and deinterleaving.
+//! Status symbol interleaving and deinterleaving, and decoding of the inbound channel
+//! state they signal.
 
 use bits;
 use consts::SYNC_SYMBOLS;
@@ -132,6 +133,115 @@ impl StatusDeinterleaver {
     }
 }
 
+/// State of the inbound channel signalled by the status symbols of a repeater.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ChannelStatus {
+    /// The inbound channel is in use.
+    Busy,
+    /// The inbound channel is free.
+    Idle,
+    /// The status symbols don't signal the inbound channel state, such as when received
+    /// from a subscriber rather than a repeater.
+    Unknown,
+}
+
+impl ChannelStatus {
+    /// Inbound channel state signalled by the given status code.
+    pub fn from_code(code: StatusCode) -> ChannelStatus {
+        match code {
+            InboundBusy => ChannelStatus::Busy,
+            InboundIdle => ChannelStatus::Idle,
+            SubscriberRepeater | SubscriberDirect => ChannelStatus::Unknown,
+        }
+    }
+}
+
+/// Change of the inbound channel state, with the sample index of the first status symbol
+/// that signalled the new state.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StatusEvent {
+    /// The inbound channel became busy.
+    InboundBusy(u64),
+    /// The inbound channel became idle.
+    InboundIdle(u64),
+}
+
+/// Decodes the inbound channel state from a stream of status symbols.
+///
+/// A state is only taken once it's been signalled by the given number of consecutive
+/// status symbols, so a single corrupted symbol doesn't flip the state. Symbols that
+/// don't signal the inbound channel state break up a run but otherwise leave the state
+/// alone.
+#[derive(Copy, Clone, Debug)]
+pub struct StatusChannel {
+    /// Number of consecutive symbols needed to change state.
+    debounce: usize,
+    /// Current debounced state.
+    state: ChannelStatus,
+    /// State signalled by the current run of symbols.
+    pending: ChannelStatus,
+    /// Number of symbols in the current run.
+    count: usize,
+    /// Sample index of the first symbol in the current run.
+    start: u64,
+}
+
+impl StatusChannel {
+    /// Create a new `StatusChannel` that changes state after the given number of
+    /// consecutive status symbols, which must be at least 1.
+    pub fn new(debounce: usize) -> StatusChannel {
+        assert!(debounce > 0);
+
+        StatusChannel {
+            debounce: debounce,
+            state: ChannelStatus::Unknown,
+            pending: ChannelStatus::Unknown,
+            count: 0,
+            start: 0,
+        }
+    }
+
+    /// Current debounced state.
+    pub fn state(&self) -> ChannelStatus { self.state }
+
+    /// Feed in the status code received at the given sample index, returning an event
+    /// if the state changed.
+    pub fn feed(&mut self, code: StatusCode, sample: u64) -> Option<StatusEvent> {
+        let status = ChannelStatus::from_code(code);
+
+        if status == ChannelStatus::Unknown {
+            self.count = 0;
+            return None;
+        }
+
+        if status != self.pending || self.count == 0 {
+            self.pending = status;
+            self.count = 0;
+            self.start = sample;
+        }
+
+        self.count += 1;
+
+        if self.count < self.debounce || status == self.state {
+            return None;
+        }
+
+        self.state = status;
+
+        Some(if status == ChannelStatus::Busy {
+            StatusEvent::InboundBusy(self.start)
+        } else {
+            StatusEvent::InboundIdle(self.start)
+        })
+    }
+
+    /// Forget the current state, as after losing the signal.
+    pub fn reset(&mut self) {
+        self.state = ChannelStatus::Unknown;
+        self.count = 0;
+    }
+}
+
 #[cfg(test)]
 mod test {
     use bits;
@@ -184,4 +294,40 @@ mod test {
         assert_eq!(d.feed(bits::Dibit::new(0)), StreamSymbol::Status(
                 StatusCode::SubscriberDirect));
     }
+
+    #[test]
+    fn test_channel() {
+        let mut c = StatusChannel::new(3);
+        assert_eq!(c.state(), ChannelStatus::Unknown);
+
+        // A frame's worth of status symbols, going busy halfway through with a single
+        // corrupted symbol on either side of the change.
+        let mut codes = vec![InboundIdle; 12];
+        codes[4] = InboundBusy;
+        codes.extend(vec![InboundBusy; 12]);
+        codes[18] = InboundIdle;
+
+        let events: Vec<(usize, StatusEvent)> = codes.iter().enumerate()
+            .filter_map(|(i, &code)| c.feed(code, i as u64 * 360).map(|e| (i, e)))
+            .collect();
+
+        assert_eq!(events, vec![
+            (2, StatusEvent::InboundIdle(0)),
+            (14, StatusEvent::InboundBusy(12 * 360)),
+        ]);
+        assert_eq!(c.state(), ChannelStatus::Busy);
+
+        // Subscriber status symbols break up a run without changing state.
+        assert_eq!(c.feed(InboundIdle, 0), None);
+        assert_eq!(c.feed(InboundIdle, 0), None);
+        assert_eq!(c.feed(SubscriberRepeater, 0), None);
+        assert_eq!(c.feed(InboundIdle, 0), None);
+        assert_eq!(c.state(), ChannelStatus::Busy);
+
+        c.reset();
+        assert_eq!(c.state(), ChannelStatus::Unknown);
+        assert_eq!(c.feed(InboundBusy, 1), None);
+        assert_eq!(c.feed(InboundBusy, 2), None);
+        assert_eq!(c.feed(InboundBusy, 3), Some(StatusEvent::InboundBusy(1)));
+    }
 }
@@ -0,0 +1,209 @@
+//! Decode several channels at once on a thread pool, such as every channel of a site
+//! from a channelizer.
+
+use rayon::prelude::*;
+
+use error;
+use receiver::{Event, Receiver, ReceiverConfig};
+
+/// Runs a `Receiver` for each of a fixed number of channels, feeding each block of
+/// channelized samples to all of them in parallel.
+///
+/// The events of every channel are merged in order of the sample index they occurred
+/// at, with ties broken by channel index, and the events of each channel keep the order
+/// its receiver produced them in. Only the events of the current block are held, so
+/// memory use is bounded by the block size.
+pub struct MultiReceiver {
+    /// Receiver of each channel.
+    recvs: Vec<Receiver>,
+}
+
+impl MultiReceiver {
+    /// Create a new `MultiReceiver` for the given number of channels, each with the
+    /// given configuration, or return an error naming the first invalid field.
+    pub fn new(channels: usize, config: ReceiverConfig) -> error::Result<MultiReceiver> {
+        Ok(MultiReceiver {
+            recvs: (0..channels).map(|_| Receiver::new(config.clone()))
+                .collect::<error::Result<_>>()?,
+        })
+    }
+
+    /// Number of channels.
+    pub fn channels(&self) -> usize { self.recvs.len() }
+
+    /// Receiver of the given channel.
+    pub fn receiver(&self, ch: usize) -> &Receiver { &self.recvs[ch] }
+
+    /// Mutable receiver of the given channel, such as to restore a snapshot after
+    /// retuning it.
+    pub fn receiver_mut(&mut self, ch: usize) -> &mut Receiver { &mut self.recvs[ch] }
+
+    /// Feed in the next block of baseband samples of each channel, returning the
+    /// resulting events tagged with their channel index.
+    ///
+    /// The number of blocks must match the number of channels. Blocks are normally the
+    /// same length, so every channel stays at the same sample index.
+    pub fn feed_block(&mut self, channels: &[&[f32]]) -> Vec<(usize, Event)> {
+        assert_eq!(channels.len(), self.recvs.len());
+
+        merge(self.recvs.par_iter_mut()
+            .zip(channels.par_iter())
+            .map(|(recv, samples)| recv.feed_slice(samples))
+            .collect())
+    }
+
+    /// Handle the end of the input on every channel, returning the resulting events
+    /// tagged with their channel index.
+    pub fn finish(&mut self) -> Vec<(usize, Event)> {
+        merge(self.recvs.par_iter_mut().map(|recv| recv.finish()).collect())
+    }
+}
+
+/// Merge the events of each channel into a single stream in sample order.
+fn merge(channels: Vec<Vec<Event>>) -> Vec<(usize, Event)> {
+    let mut events: Vec<(usize, Event)> = channels.into_iter()
+        .enumerate()
+        .flat_map(|(ch, events)| events.into_iter().map(move |e| (ch, e)))
+        .collect();
+
+    // The sort is stable, so events of a channel at the same sample keep their order.
+    events.sort_by_key(|&(ch, ref e)| (e.sample, ch));
+    events
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std;
+    use std::mem::{discriminant, Discriminant};
+
+    use baseband::sync::SYNC_GENERATOR;
+    use bits::{Dibit, Dibits};
+    use consts::{SAMPLE_RATE, SYMBOL_PERIOD};
+    use message::nid::{DataUnit, NetworkAccessCode, NetworkId};
+    use message::status::{StatusCode, StatusInterleaver};
+    use receiver::EventKind;
+    use trunking::calls::CallEvent;
+    use trunking::encode::Tsdu;
+    use trunking::tsbk::{TsbkFields, TsbkOpcode};
+    use trunking::vendor::MFID_STANDARD;
+    use voice::encode::VoiceEncoder;
+
+    /// Baseband samples of the given dibits.
+    fn modulate(dibits: &[Dibit]) -> Vec<f32> {
+        let mut samples = vec![];
+
+        for d in dibits {
+            let level = match d.bits() {
+                0b01 => 0.18,
+                0b00 => 0.02,
+                0b10 => -0.02,
+                _ => -0.18,
+            };
+
+            samples.extend(std::iter::repeat(level).take(SYMBOL_PERIOD));
+        }
+
+        samples
+    }
+
+    /// Samples of a channel carrying a grant for the given talkgroup and a voice frame
+    /// group with chunks from the talkgroup, starting after the given delay.
+    fn channel(delay: usize, tg: u16) -> Vec<f32> {
+        let nac = NetworkAccessCode::Default;
+
+        let tsbk = TsbkFields::build(TsbkOpcode::GroupVoiceGrant.to_bits(), MFID_STANDARD,
+                                     &[0x00, 0x10, 0x20, (tg >> 8) as u8, tg as u8,
+                                       0x12, 0x34, 0x56]);
+        let nid = NetworkId::new(nac, DataUnit::TrunkingSignaling).encode();
+
+        let mut dibits: Vec<Dibit> = StatusInterleaver::new(
+            Dibits::new(SYNC_GENERATOR.iter().cloned())
+                .chain(Dibits::new(nid.iter().cloned()))
+                .chain(Tsdu::assemble(&[tsbk]).into_iter()),
+            StatusCode::InboundIdle).collect();
+
+        let lc = [0x00, 0x00, 0x00, 0x00, tg as u8, 0x00, 0x12, 0x34, 0x56];
+        let voice = VoiceEncoder::new(nac);
+
+        dibits.extend(voice.lc_frame_group(&[[tg as u32 & 0x7F; 8]; 9], &lc, 0xBEEF));
+        dibits.extend(voice.simple_terminator());
+
+        let mut samples = vec![0.0; 1000 + delay];
+        samples.extend(modulate(&dibits));
+        samples.extend(std::iter::repeat(0.0).take(SAMPLE_RATE / 2));
+        samples
+    }
+
+    fn config() -> ReceiverConfig {
+        ReceiverConfig::builder().call_timeout(SAMPLE_RATE as u64 / 4).build().unwrap()
+    }
+
+    /// Identifying parts of the given event.
+    fn key(e: &Event) -> (Option<NetworkAccessCode>, Discriminant<EventKind>) {
+        (e.nac, discriminant(&e.kind))
+    }
+
+    #[test]
+    fn test_feed_block() {
+        let len = 40_000;
+
+        let mut channels: Vec<Vec<f32>> = (0..4)
+            .map(|ch| channel(ch * 3000, 0x100 + ch as u16))
+            .collect();
+
+        for c in channels.iter_mut() {
+            c.resize(len, 0.0);
+        }
+
+        let mut multi = MultiReceiver::new(channels.len(), config()).unwrap();
+        assert_eq!(multi.channels(), 4);
+
+        let mut events = vec![];
+
+        for start in (0..len).step_by(4096) {
+            let end = (start + 4096).min(len);
+            let blocks: Vec<&[f32]> = channels.iter().map(|c| &c[start..end]).collect();
+
+            events.extend(multi.feed_block(&blocks));
+        }
+
+        events.extend(multi.finish());
+
+        for (ch, samples) in channels.iter().enumerate() {
+            let mut recv = Receiver::new(config()).unwrap();
+
+            let mut expect = recv.feed_slice(samples);
+            expect.extend(recv.finish());
+
+            let got: Vec<&Event> = events.iter()
+                .filter(|&&(c, _)| c == ch)
+                .map(|&(_, ref e)| e)
+                .collect();
+
+            assert!(expect.len() > 10);
+            assert_eq!(got.iter().map(|e| key(e)).collect::<Vec<_>>(),
+                       expect.iter().map(key).collect::<Vec<_>>());
+
+            let chunks: Vec<[u32; 8]> = got.iter()
+                .filter_map(|e| match e.kind {
+                    EventKind::VoiceFrame(ref vf) => Some(vf.chunks),
+                    _ => None,
+                })
+                .collect();
+
+            assert_eq!(chunks, vec![[ch as u32; 8]; 9]);
+            assert_eq!(multi.receiver(ch).sample(), len as u64);
+        }
+
+        // Staggered calls start in channel order.
+        let started: Vec<usize> = events.iter()
+            .filter_map(|&(ch, ref e)| match e.kind {
+                EventKind::Call(CallEvent::Started(_)) => Some(ch),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(started, vec![0, 1, 2, 3]);
+    }
+}
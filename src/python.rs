@@ -0,0 +1,427 @@
+//! Python bindings for the top-level receiver.
+//!
+//! The `p25` Python module provides `ReceiverConfig`, `Receiver`, and `Event` classes.
+//! Samples are passed to `Receiver.feed` as any object supporting the buffer protocol
+//! with float32 items, such as a numpy `float32` array or an `array.array('f')`, and
+//! contiguous buffers are read in place without copying. Each call returns a list of
+//! the resulting events.
+//!
+//! An `Event` has `kind`, `sample`, and `nac` attributes, and kind-specific fields that
+//! can be accessed as attributes or through the `fields` dict, named the same as in
+//! `sink::JsonLinesSink` output. Byte payloads are `bytes` and enumerated values are
+//! strings.
+//!
+//! To build an extension module, enable the `python` feature along with
+//! `pyo3/extension-module` in a `cdylib` wrapper crate.
+
+use std::fmt::Write;
+
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::{PyAttributeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+
+use bits::Dibit;
+use data::assemble::PduEvent;
+use error::P25Error;
+use message::nid::{DataUnit, NetworkAccessCode, NetworkId};
+use message::status::StatusEvent;
+use receiver::{MessageHandler, Receiver, ReceiverConfig};
+use trunking::calls::Call;
+use trunking::tsbk::TsbkFields;
+use voice::control::LinkControlFields;
+use voice::crypto::{CryptoControlFields, CryptoEvent};
+use voice::frame::VoiceFrame;
+use voice::header::VoiceHeaderFields;
+
+/// Configuration of a `Receiver`, with all times in baseband samples.
+#[pyclass(name = "ReceiverConfig", module = "p25")]
+#[derive(Clone)]
+pub struct PyReceiverConfig {
+    /// Wrapped configuration.
+    config: ReceiverConfig,
+}
+
+#[pymethods]
+impl PyReceiverConfig {
+    /// Create a new configuration, with any fields not given set to their defaults.
+    #[new]
+    #[pyo3(signature = (sync_timeout=None, track_calls=None, call_heartbeat=None,
+                        call_timeout=None, max_nacs=None))]
+    fn new(sync_timeout: Option<u64>, track_calls: Option<bool>,
+           call_heartbeat: Option<u64>, call_timeout: Option<u64>,
+           max_nacs: Option<usize>)
+        -> PyResult<Self>
+    {
+        let mut b = ReceiverConfig::builder();
+
+        if let Some(t) = sync_timeout { b = b.sync_timeout(t); }
+        if let Some(t) = track_calls { b = b.track_calls(t); }
+        if let Some(t) = call_heartbeat { b = b.call_heartbeat(t); }
+        if let Some(t) = call_timeout { b = b.call_timeout(t); }
+        if let Some(n) = max_nacs { b = b.max_nacs(n); }
+
+        match b.build() {
+            Ok(config) => Ok(PyReceiverConfig { config: config }),
+            Err(err) => Err(PyValueError::new_err(err.to_string())),
+        }
+    }
+
+    #[getter]
+    fn sync_timeout(&self) -> u64 { self.config.sync_timeout }
+    #[getter]
+    fn track_calls(&self) -> bool { self.config.track_calls }
+    #[getter]
+    fn call_heartbeat(&self) -> u64 { self.config.call_heartbeat }
+    #[getter]
+    fn call_timeout(&self) -> u64 { self.config.call_timeout }
+    #[getter]
+    fn max_nacs(&self) -> usize { self.config.max_nacs }
+
+    fn __repr__(&self) -> String {
+        format!("ReceiverConfig(sync_timeout={}, track_calls={}, call_heartbeat={}, \
+                 call_timeout={}, max_nacs={})",
+                self.config.sync_timeout,
+                if self.config.track_calls { "True" } else { "False" },
+                self.config.call_heartbeat, self.config.call_timeout,
+                self.config.max_nacs)
+    }
+}
+
+/// Receives P25 events from baseband samples.
+#[pyclass(name = "Receiver", module = "p25")]
+pub struct PyReceiver {
+    /// Wrapped receiver.
+    recv: Receiver,
+    /// NAC of the most recent event, which carries over to the next call.
+    nac: Option<NetworkAccessCode>,
+}
+
+#[pymethods]
+impl PyReceiver {
+    /// Create a new receiver with the given configuration, or the default one.
+    #[new]
+    #[pyo3(signature = (config=None))]
+    fn new(config: Option<PyReceiverConfig>) -> PyResult<Self> {
+        match Receiver::new(config.map(|c| c.config).unwrap_or_default()) {
+            Ok(recv) => Ok(PyReceiver {
+                recv: recv,
+                nac: None,
+            }),
+            Err(err) => Err(PyValueError::new_err(err.to_string())),
+        }
+    }
+
+    /// Feed in the given float32 baseband samples, returning the resulting events.
+    fn feed(&mut self, py: Python, samples: PyBuffer<f32>) -> PyResult<Vec<PyEvent>> {
+        let mut events = Events::new(py, self.nac);
+
+        match samples.as_slice(py) {
+            Some(cells) => for c in cells {
+                self.recv.feed_with(c.get(), &mut events);
+            },
+            None => for s in samples.to_vec(py)? {
+                self.recv.feed_with(s, &mut events);
+            },
+        }
+
+        self.nac = events.nac;
+        events.finish()
+    }
+
+    /// Signal the end of the input, returning the loss of sync and the end of every
+    /// active call.
+    fn finish(&mut self, py: Python) -> PyResult<Vec<PyEvent>> {
+        let mut events = Events::new(py, self.nac);
+        self.recv.finish_with(&mut events);
+        self.nac = events.nac;
+        events.finish()
+    }
+
+    /// Index of the next sample to be fed in.
+    #[getter]
+    fn sample(&self) -> u64 { self.recv.sample() }
+
+    /// Whether frame sync is currently acquired.
+    #[getter]
+    fn locked(&self) -> bool { self.recv.locked() }
+
+    /// Recently seen NACs, from least to most recent.
+    #[getter]
+    fn nacs(&self) -> Vec<u16> {
+        self.recv.nacs().into_iter().map(|n| n.to_bits()).collect()
+    }
+}
+
+/// Event produced by a `Receiver`.
+#[pyclass(frozen, name = "Event", module = "p25")]
+pub struct PyEvent {
+    /// What happened, such as `"sync_acquired"` or `"call_start"`.
+    #[pyo3(get)]
+    kind: &'static str,
+    /// Index of the sample the event occurred at.
+    #[pyo3(get)]
+    sample: u64,
+    /// NAC the event belongs to, if any.
+    #[pyo3(get)]
+    nac: Option<u16>,
+    /// Fields specific to the kind of event.
+    #[pyo3(get)]
+    fields: Py<PyDict>,
+}
+
+#[pymethods]
+impl PyEvent {
+    fn __getattr__(&self, py: Python, name: &str) -> PyResult<PyObject> {
+        match self.fields.bind(py).get_item(name)? {
+            Some(v) => Ok(v.unbind()),
+            None => Err(PyAttributeError::new_err(name.to_string())),
+        }
+    }
+
+    fn __eq__(&self, py: Python, other: PyRef<PyEvent>) -> PyResult<bool> {
+        Ok(self.kind == other.kind && self.sample == other.sample &&
+           self.nac == other.nac && self.fields.bind(py).eq(other.fields.bind(py))?)
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        let mut s = format!("Event(kind='{}', sample={}, nac=", self.kind, self.sample);
+
+        match self.nac {
+            Some(n) => write!(s, "{:#05x}", n).unwrap(),
+            None => s.push_str("None"),
+        }
+
+        for (k, v) in self.fields.bind(py).iter() {
+            write!(s, ", {}={}", k, v.repr()?).unwrap();
+        }
+
+        s.push(')');
+        Ok(s)
+    }
+}
+
+/// Converts receiver events to Python events.
+struct Events<'py> {
+    /// Token for the held GIL.
+    py: Python<'py>,
+    /// Sample index of the following events.
+    sample: u64,
+    /// NAC of the following events.
+    nac: Option<NetworkAccessCode>,
+    /// Converted events.
+    events: Vec<PyEvent>,
+    /// First error raised while converting, if any.
+    err: Option<PyErr>,
+}
+
+impl<'py> Events<'py> {
+    fn new(py: Python<'py>, nac: Option<NetworkAccessCode>) -> Self {
+        Events {
+            py: py,
+            sample: 0,
+            nac: nac,
+            events: vec![],
+            err: None,
+        }
+    }
+
+    /// Return the converted events, or the first error raised.
+    fn finish(self) -> PyResult<Vec<PyEvent>> {
+        match self.err {
+            Some(e) => Err(e),
+            None => Ok(self.events),
+        }
+    }
+
+    /// Convert an event of the given kind, with its specific fields added by the given
+    /// function.
+    fn push<F>(&mut self, kind: &'static str, fields: F)
+        where F: FnOnce(&Bound<'py, PyDict>) -> PyResult<()>
+    {
+        if self.err.is_some() {
+            return;
+        }
+
+        let dict = PyDict::new(self.py);
+
+        match fields(&dict) {
+            Ok(()) => self.events.push(PyEvent {
+                kind: kind,
+                sample: self.sample,
+                nac: self.nac.map(|n| n.to_bits()),
+                fields: dict.unbind(),
+            }),
+            Err(e) => self.err = Some(e),
+        }
+    }
+}
+
+/// Set the fields of the given link control word.
+fn lc_fields(d: &Bound<PyDict>, lc: &LinkControlFields) -> PyResult<()> {
+    d.set_item("opcode", lc.opcode().map(|op| format!("{:?}", op)))?;
+    d.set_item("protected", lc.protected())?;
+    d.set_item("payload", PyBytes::new(d.py(), lc.payload()))
+}
+
+/// Set the fields of the given call.
+fn call_fields(d: &Bound<PyDict>, call: &Call) -> PyResult<()> {
+    d.set_item("channel", call.channel.to_bits())?;
+    d.set_item("target", format!("{:?}", call.target))?;
+    d.set_item("src_unit", call.src_unit)?;
+    d.set_item("emergency", call.emergency)?;
+    d.set_item("started", call.started)?;
+    d.set_item("updated", call.updated)
+}
+
+impl<'py> MessageHandler for Events<'py> {
+    fn on_sample(&mut self, sample: u64) { self.sample = sample; }
+
+    fn on_nac(&mut self, nac: Option<NetworkAccessCode>) { self.nac = nac; }
+
+    fn on_sync(&mut self, locked: bool) {
+        self.push(if locked { "sync_acquired" } else { "sync_lost" }, |_| Ok(()));
+    }
+
+    fn on_nid(&mut self, nid: NetworkId) {
+        self.push("nid", |d| d.set_item("data_unit", format!("{:?}", nid.data_unit)));
+    }
+
+    fn on_voice_header(&mut self, h: VoiceHeaderFields) {
+        self.push("voice_header", |d| {
+            d.set_item("talk_group", format!("{:?}", h.talk_group()))?;
+            d.set_item("mfg", h.mfg())?;
+            d.set_item("crypto_alg", format!("{:?}", h.crypto_alg()))?;
+            d.set_item("crypto_key", h.crypto_key())?;
+            d.set_item("crypto_init", PyBytes::new(d.py(), h.crypto_init()))
+        });
+    }
+
+    fn on_voice_frame(&mut self, vf: VoiceFrame) {
+        self.push("voice_frame", |d| {
+            d.set_item("chunks", vf.chunks.to_vec())?;
+            d.set_item("errors", vf.errors.to_vec())
+        });
+    }
+
+    fn on_link_control(&mut self, lc: LinkControlFields) {
+        self.push("link_control", |d| lc_fields(d, &lc));
+    }
+
+    fn on_crypto_control(&mut self, cc: CryptoControlFields) {
+        self.push("crypto_control", |d| {
+            d.set_item("alg", format!("{:?}", cc.alg()))?;
+            d.set_item("key", cc.key())?;
+            d.set_item("init", PyBytes::new(d.py(), cc.init()))
+        });
+    }
+
+    fn on_crypto(&mut self, event: CryptoEvent) {
+        match event {
+            CryptoEvent::MiDiscontinuity { expected, received } =>
+                self.push("mi_discontinuity", |d| {
+                    d.set_item("expected", PyBytes::new(d.py(), &expected))?;
+                    d.set_item("received", PyBytes::new(d.py(), &received))
+                }),
+        }
+    }
+
+    fn on_status(&mut self, event: StatusEvent) {
+        match event {
+            StatusEvent::InboundBusy(start) =>
+                self.push("inbound_busy", |d| d.set_item("start", start)),
+            StatusEvent::InboundIdle(start) =>
+                self.push("inbound_idle", |d| d.set_item("start", start)),
+        }
+    }
+
+    fn on_low_speed_data(&mut self, lsd: u32) {
+        self.push("low_speed_data", |d| d.set_item("data", lsd));
+    }
+
+    fn on_tsbk(&mut self, tsbk: TsbkFields) {
+        self.push("tsbk", |d| {
+            d.set_item("opcode", tsbk.opcode_bits())?;
+            d.set_item("mfg", tsbk.mfg())?;
+            d.set_item("bytes", PyBytes::new(d.py(), tsbk.bytes()))
+        });
+    }
+
+    fn on_voice_term(&mut self, lc: LinkControlFields) {
+        self.push("voice_term", |d| lc_fields(d, &lc));
+    }
+
+    fn on_pdu(&mut self, pdu: PduEvent) {
+        self.push("pdu", |d| match pdu {
+            PduEvent::Packet { ref header, ref payload, crc_ok, .. } => {
+                d.set_item("result", "packet")?;
+                d.set_item("llid", header.llid)?;
+                d.set_item("crc_ok", crc_ok)?;
+                d.set_item("payload", PyBytes::new(d.py(), payload))
+            },
+            PduEvent::Partial { ref header, ref payload, .. } => {
+                d.set_item("result", "partial")?;
+                d.set_item("llid", header.llid)?;
+                d.set_item("payload", PyBytes::new(d.py(), payload))
+            },
+            PduEvent::BadBlock { ref header, block, err } => {
+                d.set_item("result", "bad_block")?;
+                d.set_item("llid", header.llid)?;
+                d.set_item("block", block)?;
+                d.set_item("error", format!("{:?}", err))
+            },
+            PduEvent::Response(resp) => {
+                d.set_item("result", "response")?;
+                d.set_item("llid", resp.llid)?;
+                d.set_item("class", format!("{:?}", resp.class))
+            },
+            PduEvent::BadHeader(err) => {
+                d.set_item("result", "bad_header")?;
+                d.set_item("error", format!("{:?}", err))
+            },
+        });
+    }
+
+    fn on_raw(&mut self, du: DataUnit, dibits: &[Dibit]) {
+        self.push("raw", |d| {
+            d.set_item("data_unit", format!("{:?}", du))?;
+            d.set_item("dibits", dibits.iter().map(|d| d.bits()).collect::<Vec<u8>>())
+        });
+    }
+
+    fn on_frame_aborted(&mut self) { self.push("frame_aborted", |_| Ok(())); }
+
+    fn on_frame_filtered(&mut self, nac: NetworkAccessCode, duid: DataUnit) {
+        self.push("frame_filtered", |d| {
+            d.set_item("filtered_nac", nac.to_bits())?;
+            d.set_item("data_unit", format!("{:?}", duid))
+        });
+    }
+
+    fn on_call_start(&mut self, call: Call) {
+        self.push("call_start", |d| call_fields(d, &call));
+    }
+
+    fn on_call_update(&mut self, call: Call) {
+        self.push("call_update", |d| call_fields(d, &call));
+    }
+
+    fn on_call_end(&mut self, call: Call) {
+        self.push("call_end", |d| call_fields(d, &call));
+    }
+
+    fn on_error(&mut self, err: P25Error) {
+        self.push("error", |d| d.set_item("error", format!("{:?}", err)));
+    }
+}
+
+/// The `p25` Python module.
+#[pymodule]
+#[pyo3(name = "p25")]
+pub fn p25_module(m: &Bound<PyModule>) -> PyResult<()> {
+    m.add_class::<PyReceiverConfig>()?;
+    m.add_class::<PyReceiver>()?;
+    m.add_class::<PyEvent>()?;
+
+    Ok(())
+}
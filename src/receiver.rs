@@ -0,0 +1,2863 @@
+//! Top-level receiver that runs the full receive chain, from baseband samples through
+//! frame sync, symbol decoding, and message reconstruction to call tracking.
+
+use std;
+use std::collections::VecDeque;
+use std::vec::Vec;
+
+use baseband::afc::Afc;
+use baseband::clock::ClockTracker;
+use baseband::demod::{Demodulator, Modulation};
+use baseband::sync::{Polarity, MAX_SMOOTH_AVG, SMOOTH_AVG, SYNC_THRESHOLD};
+use bits::Dibit;
+use consts::{SAMPLE_RATE, SYMBOL_PERIOD};
+use data::assemble::PduEvent;
+use diagnostic::{Diagnostic, Verbosity};
+use error::{self, P25Error};
+#[cfg(feature = "std")]
+use ingest::SampleQueue;
+use message::nid::{DataUnit, NacFilter, NetworkAccessCode, NetworkId};
+use message::data_unit::ThresholdState;
+use message::receiver::{MessageEvent, MessageReceiver, Route, Routes};
+use message::status::{ChannelStatus, StatusChannel, StatusEvent};
+use stats::{HasStats, ReceiverStats};
+use trunking::ambt::Ambt;
+use trunking::calls::{Call, CallEvent, GrantTracker};
+use trunking::site::SiteInfo;
+use time::{TimeBase, WallTime};
+use trunking::tsbk::TsbkFields;
+use voice::control::LinkControlFields;
+use voice::crypto::{CryptoControlFields, CryptoEvent, MiTracker};
+use voice::frame::VoiceFrame;
+use voice::header::VoiceHeaderFields;
+
+/// Number of samples between checks for calls whose grants stopped.
+const EXPIRE_PERIOD: u64 = SAMPLE_RATE as u64 / 10;
+
+/// How the receiver fills in voice frames that fail to decode.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "ser", derive(Serialize, Deserialize))]
+pub enum Concealment {
+    /// Pass on every frame as decoded.
+    Off,
+    /// Replace each bad voice frame with a repeat of the last good one of the call, at
+    /// most `max` times in a row.
+    ///
+    /// A frame is bad if it needed more than `errors` bits corrected, in which case the
+    /// repeat is passed on instead of it, or if it failed to decode, in which case the
+    /// repeat follows the decode error.
+    Repeat {
+        /// Maximum number of repeats in a row.
+        max: usize,
+        /// Maximum number of corrected bits in a good frame.
+        errors: usize,
+    },
+}
+
+impl Default for Concealment {
+    fn default() -> Self { Concealment::Off }
+}
+
+/// Configuration of a `Receiver`, with all times in samples at the 48 kHz baseband rate.
+///
+/// The defaults are those documented on each setter of `ReceiverConfigBuilder`, which
+/// also checks each field when building.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "ser", derive(Serialize, Deserialize))]
+pub struct ReceiverConfig {
+    /// Rate of the samples fed in, which must be a multiple of the 48 kHz baseband rate
+    /// and is averaged down to it.
+    pub sample_rate: usize,
+    /// Time without a frame sync, after the end of the previous data unit, before sync
+    /// is considered lost.
+    pub sync_timeout: u64,
+    /// Whether to track calls from the voice grants on a control channel.
+    pub track_calls: bool,
+    /// Minimum time between `Continued` events for a call.
+    pub call_heartbeat: u64,
+    /// Time without grants after which a call ends.
+    pub call_timeout: u64,
+    /// Route of each type of data unit.
+    pub routes: Routes,
+    /// NACs to receive, with data units on others skipped after their NID.
+    pub nac_filter: NacFilter,
+    /// Maximum number of recently seen NACs to keep separate symbol thresholds, call
+    /// tracking, and site state for, which must be at least 1.
+    pub max_nacs: usize,
+    /// Diagnostic records to produce, none by default.
+    pub diagnostics: Verbosity,
+    /// Number of consecutive status symbols needed to change the inbound channel state,
+    /// or 0 to not decode it.
+    pub status_debounce: usize,
+    /// Number of frame syncs symbol thresholds are smoothed over, from 1 to
+    /// `MAX_SMOOTH_AVG`.
+    pub threshold_smoothing: usize,
+    /// Factor of the RMS signal power frame syncs must correlate above.
+    pub sync_threshold: f32,
+    /// How voice frames that fail to decode are filled in.
+    pub concealment: Concealment,
+}
+
+impl Default for ReceiverConfig {
+    fn default() -> Self {
+        ReceiverConfig {
+            sample_rate: SAMPLE_RATE,
+            sync_timeout: SAMPLE_RATE as u64 / 10,
+            track_calls: true,
+            call_heartbeat: SAMPLE_RATE as u64,
+            call_timeout: SAMPLE_RATE as u64 * 3,
+            routes: Routes::default(),
+            nac_filter: NacFilter::default(),
+            max_nacs: 4,
+            diagnostics: Verbosity::Off,
+            status_debounce: 0,
+            threshold_smoothing: SMOOTH_AVG,
+            sync_threshold: SYNC_THRESHOLD,
+            concealment: Concealment::Off,
+        }
+    }
+}
+
+impl ReceiverConfig {
+    /// Create a builder starting from the default configuration.
+    pub fn builder() -> ReceiverConfigBuilder { ReceiverConfigBuilder::new() }
+
+    /// Check that every field has a usable value, such as after deserializing the
+    /// configuration.
+    pub fn check(&self) -> error::Result<()> {
+        let invalid = |field, reason| Err(P25Error::InvalidConfig {
+            field: field,
+            reason: reason,
+        });
+
+        if self.sample_rate == 0 || self.sample_rate % SAMPLE_RATE != 0 {
+            return invalid("sample_rate", "must be a nonzero multiple of 48000 Hz");
+        }
+
+        if self.sync_timeout == 0 {
+            return invalid("sync_timeout", "must be at least 1 sample");
+        }
+
+        if self.call_heartbeat == 0 {
+            return invalid("call_heartbeat", "must be at least 1 sample");
+        }
+
+        if self.call_timeout == 0 {
+            return invalid("call_timeout", "must be at least 1 sample");
+        }
+
+        if self.max_nacs == 0 {
+            return invalid("max_nacs", "must be at least 1");
+        }
+
+        if self.threshold_smoothing == 0 || self.threshold_smoothing > MAX_SMOOTH_AVG {
+            return invalid("threshold_smoothing", "must be from 1 to 16 syncs");
+        }
+
+        if !self.sync_threshold.is_finite() || self.sync_threshold <= 0.0 {
+            return invalid("sync_threshold", "must be a positive factor");
+        }
+
+        if let Concealment::Repeat { max: 0, .. } = self.concealment {
+            return invalid("concealment", "must repeat at least 1 frame");
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds a `ReceiverConfig`, checking the values given when built.
+#[derive(Clone, Debug, Default)]
+pub struct ReceiverConfigBuilder {
+    /// Configuration being built.
+    config: ReceiverConfig,
+}
+
+impl ReceiverConfigBuilder {
+    /// Create a new `ReceiverConfigBuilder` starting from the default configuration.
+    pub fn new() -> Self { ReceiverConfigBuilder::default() }
+
+    /// Set the rate of the samples fed in, 48 kHz by default. Higher rates must be a
+    /// multiple of 48 kHz, and each group of samples is averaged into one.
+    pub fn sample_rate(mut self, rate: usize) -> Self {
+        self.config.sample_rate = rate;
+        self
+    }
+
+    /// Set the time without a frame sync before sync is lost, 1/10 second by default.
+    pub fn sync_timeout(mut self, samples: u64) -> Self {
+        self.config.sync_timeout = samples;
+        self
+    }
+
+    /// Set whether to track calls from voice grants, on by default.
+    pub fn track_calls(mut self, on: bool) -> Self {
+        self.config.track_calls = on;
+        self
+    }
+
+    /// Set the minimum time between `Continued` events for a call, 1 second by default.
+    pub fn call_heartbeat(mut self, samples: u64) -> Self {
+        self.config.call_heartbeat = samples;
+        self
+    }
+
+    /// Set the time without grants after which a call ends, 3 seconds by default.
+    pub fn call_timeout(mut self, samples: u64) -> Self {
+        self.config.call_timeout = samples;
+        self
+    }
+
+    /// Set the route of every type of data unit, all decoded by default.
+    pub fn routes(mut self, routes: Routes) -> Self {
+        self.config.routes = routes;
+        self
+    }
+
+    /// Set the route of the given type of data unit.
+    pub fn route(mut self, du: DataUnit, route: Route) -> Self {
+        self.config.routes.set(du, route);
+        self
+    }
+
+    /// Set the NACs to receive, all by default.
+    pub fn nac_filter(mut self, filter: NacFilter) -> Self {
+        self.config.nac_filter = filter;
+        self
+    }
+
+    /// Set the number of NACs to keep separate state for, 4 by default.
+    pub fn max_nacs(mut self, max: usize) -> Self {
+        self.config.max_nacs = max;
+        self
+    }
+
+    /// Set the diagnostic records to produce, none by default.
+    pub fn diagnostics(mut self, verbosity: Verbosity) -> Self {
+        self.config.diagnostics = verbosity;
+        self
+    }
+
+    /// Set the number of consecutive status symbols needed to change the inbound channel
+    /// state, or 0 to not decode it, off by default.
+    pub fn status_debounce(mut self, symbols: usize) -> Self {
+        self.config.status_debounce = symbols;
+        self
+    }
+
+    /// Set the number of frame syncs symbol thresholds are smoothed over, 4 by default.
+    /// Shorter windows follow fading faster, while longer ones ride out noisy syncs.
+    pub fn threshold_smoothing(mut self, syncs: usize) -> Self {
+        self.config.threshold_smoothing = syncs;
+        self
+    }
+
+    /// Set the factor of the RMS signal power frame syncs must correlate above, 0.65 by
+    /// default. Lower factors catch distorted syncs, while higher ones avoid false
+    /// detections on other traffic.
+    pub fn sync_threshold(mut self, factor: f32) -> Self {
+        self.config.sync_threshold = factor;
+        self
+    }
+
+    /// Set how voice frames that fail to decode are filled in, off by default.
+    pub fn concealment(mut self, policy: Concealment) -> Self {
+        self.config.concealment = policy;
+        self
+    }
+
+    /// Build the configuration, or return an error naming the first invalid field.
+    pub fn build(self) -> error::Result<ReceiverConfig> {
+        self.config.check()?;
+        Ok(self.config)
+    }
+}
+
+/// Kind of event produced by the top-level receiver.
+pub enum EventKind {
+    /// Frame sync was acquired for the first time or after being lost.
+    SyncAcquired,
+    /// No frame sync was found within the configured time after the last data unit.
+    SyncLost,
+    /// An NID at the start of a data unit was decoded.
+    Nid(NetworkId),
+    /// A voice header was received.
+    VoiceHeader(VoiceHeaderFields),
+    /// A voice frame was received.
+    VoiceFrame(VoiceFrame),
+    /// A link control word was decoded.
+    LinkControl(LinkControlFields),
+    /// A crypto control word was decoded.
+    CryptoControl(CryptoControlFields),
+    /// The encryption state of a voice call changed unexpectedly.
+    Crypto(CryptoEvent),
+    /// The inbound channel state signalled by the status symbols changed.
+    Status(StatusEvent),
+    /// A voice low-speed data fragment was decoded.
+    LowSpeedData(u32),
+    /// A trunking signalling packet was received.
+    Tsbk(TsbkFields),
+    /// A trunking packet with no TSBK form, such as an authentication demand, was
+    /// received as an AMBT.
+    Ambt(Ambt),
+    /// A voice terminator link control was received.
+    VoiceTerm(LinkControlFields),
+    /// A data packet was received or dropped.
+    Pdu(PduEvent),
+    /// The data dibits of a data unit routed for raw passthrough were received, copied
+    /// out of the receiver.
+    Raw(DataUnit, Vec<Dibit>),
+    /// The current data unit was cut off by the frame sync of a new one.
+    FrameAborted,
+    /// A data unit was skipped because its NAC isn't received.
+    FrameFiltered {
+        /// NAC of the data unit.
+        nac: NetworkAccessCode,
+        /// Type of the data unit.
+        duid: DataUnit,
+    },
+    /// The state of a call on the control channel changed.
+    Call(CallEvent),
+    /// A runtime error occured.
+    Error(P25Error),
+    /// A diagnostic record was produced at the configured verbosity.
+    Diagnostic(Diagnostic),
+}
+
+/// Event produced by the top-level receiver.
+pub struct Event {
+    /// NAC of the data unit or call the event belongs to, or `None` if it doesn't
+    /// belong to one, such as for sync changes and errors before the NID.
+    pub nac: Option<NetworkAccessCode>,
+    /// Index of the sample the event occurred at.
+    pub sample: u64,
+    /// Wall-clock time of the sample, if the receiver's time base was anchored.
+    pub time: Option<WallTime>,
+    /// What happened.
+    pub kind: EventKind,
+}
+
+/// Receives the events of a `Receiver` as they occur.
+///
+/// Every method does nothing by default, so a handler only needs to implement those for
+/// the events it cares about.
+///
+/// Payloads passed by reference borrow buffers owned by the receiver, which are reused
+/// for later data units, so they're only valid for the duration of the call and must be
+/// copied to be kept. Every other payload is a fixed-size value, except data packets,
+/// which own their data since they're rare and can be large. Once the NACs on a channel
+/// have been seen, decoding voice and trunking data units makes no heap allocations
+/// along this path, other than for the call events that start or continue calls.
+pub trait MessageHandler {
+    /// The following events occurred at the given sample index. This is called for
+    /// every sample before any of its events.
+    fn on_sample(&mut self, _sample: u64) {}
+    /// The following events belong to the given NAC, or to none. This is called whenever
+    /// the NAC changes from that of the previous event.
+    fn on_nac(&mut self, _nac: Option<NetworkAccessCode>) {}
+    /// Frame sync was acquired, if `locked` is true, or lost otherwise.
+    fn on_sync(&mut self, _locked: bool) {}
+    /// An NID at the start of a data unit was decoded.
+    fn on_nid(&mut self, _nid: NetworkId) {}
+    /// A voice header was received.
+    fn on_voice_header(&mut self, _header: VoiceHeaderFields) {}
+    /// A voice frame was received.
+    fn on_voice_frame(&mut self, _vf: VoiceFrame) {}
+    /// A link control word was decoded.
+    fn on_link_control(&mut self, _lc: LinkControlFields) {}
+    /// A crypto control word was decoded.
+    fn on_crypto_control(&mut self, _cc: CryptoControlFields) {}
+    /// The encryption state of a voice call changed unexpectedly.
+    fn on_crypto(&mut self, _event: CryptoEvent) {}
+    /// The inbound channel state signalled by the status symbols changed.
+    fn on_status(&mut self, _event: StatusEvent) {}
+    /// A voice low-speed data fragment was decoded.
+    fn on_low_speed_data(&mut self, _lsd: u32) {}
+    /// A trunking signalling packet was received.
+    fn on_tsbk(&mut self, _tsbk: TsbkFields) {}
+    /// A trunking packet with no TSBK form was received as an AMBT.
+    fn on_ambt(&mut self, _ambt: Ambt) {}
+    /// A voice terminator link control was received.
+    fn on_voice_term(&mut self, _lc: LinkControlFields) {}
+    /// A data packet was received or dropped.
+    fn on_pdu(&mut self, _pdu: PduEvent) {}
+    /// The data dibits of a data unit routed for raw passthrough were received.
+    fn on_raw(&mut self, _du: DataUnit, _dibits: &[Dibit]) {}
+    /// The current data unit was cut off by the frame sync of a new one.
+    fn on_frame_aborted(&mut self) {}
+    /// A data unit was skipped because its NAC isn't received.
+    fn on_frame_filtered(&mut self, _nac: NetworkAccessCode, _duid: DataUnit) {}
+    /// A call started on the control channel.
+    fn on_call_start(&mut self, _call: Call) {}
+    /// Grants for an active call are still being received.
+    fn on_call_update(&mut self, _call: Call) {}
+    /// A call on the control channel ended.
+    fn on_call_end(&mut self, _call: Call) {}
+    /// A runtime error occured.
+    fn on_error(&mut self, _err: P25Error) {}
+    /// A diagnostic record was produced at the configured verbosity.
+    fn on_diagnostic(&mut self, _diag: Diagnostic) {}
+}
+
+/// Queues each handled event as an `Event`.
+struct EventQueue {
+    /// Queued events.
+    events: VecDeque<Event>,
+    /// NAC of the following events.
+    nac: Option<NetworkAccessCode>,
+    /// Sample index of the following events.
+    sample: u64,
+    /// Maps sample indices to the wall-clock times of events.
+    time_base: TimeBase,
+}
+
+impl EventQueue {
+    /// Queue an event of the given kind.
+    fn push(&mut self, kind: EventKind) {
+        self.events.push_back(Event {
+            nac: self.nac,
+            sample: self.sample,
+            time: self.time_base.time(self.sample),
+            kind: kind,
+        });
+    }
+}
+
+impl MessageHandler for EventQueue {
+    fn on_sample(&mut self, sample: u64) { self.sample = sample; }
+
+    fn on_nac(&mut self, nac: Option<NetworkAccessCode>) { self.nac = nac; }
+
+    fn on_sync(&mut self, locked: bool) {
+        self.push(if locked { EventKind::SyncAcquired } else { EventKind::SyncLost });
+    }
+
+    fn on_nid(&mut self, nid: NetworkId) { self.push(EventKind::Nid(nid)); }
+
+    fn on_voice_header(&mut self, header: VoiceHeaderFields) {
+        self.push(EventKind::VoiceHeader(header));
+    }
+
+    fn on_voice_frame(&mut self, vf: VoiceFrame) { self.push(EventKind::VoiceFrame(vf)); }
+
+    fn on_link_control(&mut self, lc: LinkControlFields) {
+        self.push(EventKind::LinkControl(lc));
+    }
+
+    fn on_crypto_control(&mut self, cc: CryptoControlFields) {
+        self.push(EventKind::CryptoControl(cc));
+    }
+
+    fn on_crypto(&mut self, event: CryptoEvent) { self.push(EventKind::Crypto(event)); }
+
+    fn on_status(&mut self, event: StatusEvent) { self.push(EventKind::Status(event)); }
+
+    fn on_low_speed_data(&mut self, lsd: u32) { self.push(EventKind::LowSpeedData(lsd)); }
+
+    fn on_tsbk(&mut self, tsbk: TsbkFields) { self.push(EventKind::Tsbk(tsbk)); }
+
+    fn on_ambt(&mut self, ambt: Ambt) { self.push(EventKind::Ambt(ambt)); }
+
+    fn on_voice_term(&mut self, lc: LinkControlFields) {
+        self.push(EventKind::VoiceTerm(lc));
+    }
+
+    fn on_pdu(&mut self, pdu: PduEvent) { self.push(EventKind::Pdu(pdu)); }
+
+    fn on_raw(&mut self, du: DataUnit, dibits: &[Dibit]) {
+        self.push(EventKind::Raw(du, dibits.to_vec()));
+    }
+
+    fn on_frame_aborted(&mut self) { self.push(EventKind::FrameAborted); }
+
+    fn on_frame_filtered(&mut self, nac: NetworkAccessCode, duid: DataUnit) {
+        self.push(EventKind::FrameFiltered { nac: nac, duid: duid });
+    }
+
+    fn on_call_start(&mut self, call: Call) {
+        self.push(EventKind::Call(CallEvent::Started(call)));
+    }
+
+    fn on_call_update(&mut self, call: Call) {
+        self.push(EventKind::Call(CallEvent::Continued(call)));
+    }
+
+    fn on_call_end(&mut self, call: Call) {
+        self.push(EventKind::Call(CallEvent::Ended(call)));
+    }
+
+    fn on_error(&mut self, err: P25Error) { self.push(EventKind::Error(err)); }
+
+    fn on_diagnostic(&mut self, diag: Diagnostic) {
+        self.push(EventKind::Diagnostic(diag));
+    }
+}
+
+/// State of a `Receiver` that stays valid across a retune to another channel of the same
+/// system, taken with `Receiver::snapshot`.
+///
+/// This covers the site state of each recently seen NAC, including its channel
+/// identifier table, the calls tracked on each NAC, and the smoothed symbol thresholds.
+/// It doesn't cover anything tied to the signal on the channel, such as the sync
+/// correlation history or a partially decoded data unit, which are reset on restore.
+#[derive(Clone)]
+pub struct ReceiverState {
+    /// Symbol threshold averages.
+    thresholds: ThresholdState,
+    /// State of each recently seen NAC, from least to most recent.
+    nacs: Vec<NacState>,
+    /// Index of the next sample when the state was taken.
+    sample: u64,
+}
+
+/// Receives P25 events from baseband samples.
+///
+/// Output depends only on the configuration and the samples fed in, with time measured
+/// by the index of each sample. Events can either be polled with `feed` or dispatched to
+/// a `MessageHandler` with `feed_with`.
+///
+/// Call tracking and site state are kept separately for each recently seen NAC, so
+/// systems interleaved on the same channel don't interfere with each other.
+pub struct Receiver {
+    /// Receive chain.
+    chain: Chain,
+    /// Events waiting to be returned.
+    pending: EventQueue,
+    /// Demodulator for IQ samples.
+    demod: Demodulator,
+    /// Averages input samples down to the baseband rate.
+    decim: Decimator,
+}
+
+impl Receiver {
+    /// Create a new `Receiver` with the given configuration, or return an error naming
+    /// the first invalid field.
+    pub fn new(config: ReceiverConfig) -> error::Result<Receiver> {
+        config.check()?;
+
+        Ok(Receiver {
+            decim: Decimator::new(config.sample_rate / SAMPLE_RATE),
+            chain: Chain::new(config),
+            pending: EventQueue {
+                events: VecDeque::new(),
+                nac: None,
+                sample: 0,
+                time_base: TimeBase::new(),
+            },
+            demod: Demodulator::new(Modulation::C4fm),
+        })
+    }
+
+    /// Demodulate IQ samples fed in with `feed_iq` by the given modulation, C4FM by
+    /// default. Automatic selection is redone after `restore`.
+    pub fn with_modulation(mut self, mode: Modulation) -> Self {
+        self.demod = Demodulator::new(mode);
+        self
+    }
+
+    /// Track the carrier frequency offset with the given control loop, updated at each
+    /// frame sync and reset by `restore`.
+    pub fn with_afc(mut self, afc: Afc) -> Self {
+        self.chain.recv = self.chain.recv.with_afc(afc);
+        self
+    }
+
+    /// Track the error of the sample clock against the symbol clock with the given
+    /// tracker, updated at each frame sync and reset by `restore`, and sample symbols at
+    /// the corrected rate between syncs.
+    pub fn with_clock(mut self, clock: ClockTracker) -> Self {
+        self.chain.recv = self.chain.recv.with_clock(clock);
+        self
+    }
+
+    /// Change the sync detection threshold factor, such as between data units, without
+    /// disturbing the correlation history.
+    pub fn set_sync_threshold(&mut self, factor: f32) {
+        self.chain.recv.set_sync_threshold(factor);
+    }
+
+    /// Factor of the RMS signal power for the sync detection threshold.
+    pub fn sync_threshold(&self) -> f32 { self.chain.recv.sync_threshold() }
+
+    /// Accept a frame sync only if at most the given number of its symbols, 2 by
+    /// default, differ from the sync sequence, rejecting correlation peaks on noise or
+    /// analog voice that would otherwise garble the next frame's worth of data.
+    pub fn with_max_sync_errors(mut self, max: usize) -> Self {
+        self.chain.recv = self.chain.recv.with_max_sync_errors(max);
+        self
+    }
+
+    /// Maximum number of symbol errors in an accepted frame sync.
+    pub fn max_sync_errors(&self) -> usize { self.chain.recv.max_sync_errors() }
+
+    /// Polarity of the signal found at the last frame sync, which is corrected for until
+    /// the next one.
+    pub fn polarity(&self) -> Polarity { self.chain.recv.polarity() }
+
+    /// Signal to noise ratio, in dB, estimated from the samples of the last frame sync,
+    /// for showing signal quality.
+    pub fn snr(&self) -> f32 { self.chain.recv.snr() }
+
+    /// Feed in a baseband sample, returning the oldest pending event, if any.
+    ///
+    /// A single sample can produce more than one event, so any left over are returned
+    /// by later calls or by `next_event`.
+    pub fn feed(&mut self, s: f32) -> Option<Event> {
+        if let Some((s, _)) = self.decim.feed(s, 0.0) {
+            self.chain.feed(s, &mut self.pending);
+        }
+
+        self.pending.events.pop_front()
+    }
+
+    /// Feed in the given baseband samples, returning all pending events.
+    pub fn feed_slice(&mut self, samples: &[f32]) -> Vec<Event> {
+        for &s in samples {
+            if let Some((s, _)) = self.decim.feed(s, 0.0) {
+                self.chain.feed(s, &mut self.pending);
+            }
+        }
+
+        self.pending.events.drain(..).collect()
+    }
+
+    /// Feed in a baseband sample, passing any resulting events directly to the given
+    /// handler.
+    ///
+    /// Events still pending from `feed` aren't passed to the handler, and the same
+    /// handler should be used throughout so it sees every change of NAC.
+    pub fn feed_with<H: MessageHandler>(&mut self, s: f32, handler: &mut H) {
+        if let Some((s, _)) = self.decim.feed(s, 0.0) {
+            self.chain.feed(s, handler);
+        }
+    }
+
+    /// Feed in an IQ sample at the configured rate, demodulated by the modulation given
+    /// to `with_modulation`, returning the oldest pending event, if any.
+    pub fn feed_iq(&mut self, i: f32, q: f32) -> Option<Event> {
+        if let Some((i, q)) = self.decim.feed(i, q) {
+            let s = self.demod.feed(i, q);
+            self.chain.feed(s, &mut self.pending);
+        }
+
+        self.pending.events.pop_front()
+    }
+
+    /// Feed in the given IQ samples, with I and Q components interleaved, returning all
+    /// pending events.
+    pub fn feed_iq_slice(&mut self, iq: &[f32]) -> Vec<Event> {
+        assert!(iq.len() % 2 == 0);
+
+        for s in iq.chunks(2) {
+            if let Some((i, q)) = self.decim.feed(s[0], s[1]) {
+                let s = self.demod.feed(i, q);
+                self.chain.feed(s, &mut self.pending);
+            }
+        }
+
+        self.pending.events.drain(..).collect()
+    }
+
+    /// Modulation of the IQ samples, which in automatic mode is `None` until the first
+    /// frame sync decides it.
+    pub fn modulation(&self) -> Option<Modulation> { self.demod.selected() }
+
+    /// Feed in an already decided symbol, such as one read from a dibit capture,
+    /// returning the oldest pending event, if any.
+    ///
+    /// This bypasses the baseband stage, matching frame syncs on the symbols directly,
+    /// and each symbol advances the sample index by a whole symbol period. Symbols and
+    /// samples shouldn't be mixed in the same stream.
+    pub fn feed_dibit(&mut self, d: Dibit) -> Option<Event> {
+        self.chain.feed_dibit(d, &mut self.pending);
+        self.pending.events.pop_front()
+    }
+
+    /// Feed in an already decided symbol, passing any resulting events directly to the
+    /// given handler.
+    pub fn feed_dibit_with<H: MessageHandler>(&mut self, d: Dibit, handler: &mut H) {
+        self.chain.feed_dibit(d, handler);
+    }
+
+    /// Take the oldest pending event, if any.
+    pub fn next_event(&mut self) -> Option<Event> { self.pending.events.pop_front() }
+
+    /// Signal the end of the input, returning all pending events followed by the loss of
+    /// sync and the end of every active call.
+    pub fn finish(&mut self) -> Vec<Event> {
+        self.chain.finish(&mut self.pending);
+        self.pending.events.drain(..).collect()
+    }
+
+    /// Signal the end of the input, passing the loss of sync and the end of every active
+    /// call to the given handler.
+    pub fn finish_with<H: MessageHandler>(&mut self, handler: &mut H) {
+        self.chain.finish(handler);
+    }
+
+    /// Decode every sample buffered in the given queue, returning all pending events.
+    ///
+    /// This is meant to be called regularly from a decoding thread while a real-time
+    /// callback pushes samples into the queue's sink.
+    #[cfg(feature = "std")]
+    pub fn pump(&mut self, queue: &mut SampleQueue) -> Vec<Event> {
+        while let Some(s) = queue.pop() {
+            if let Some((s, _)) = self.decim.feed(s, 0.0) {
+                self.chain.feed(s, &mut self.pending);
+            }
+        }
+
+        self.pending.events.drain(..).collect()
+    }
+
+    /// Decode every sample buffered in the given queue, passing any resulting events
+    /// directly to the given handler.
+    #[cfg(feature = "std")]
+    pub fn pump_with<H: MessageHandler>(&mut self, queue: &mut SampleQueue,
+                                        handler: &mut H)
+    {
+        while let Some(s) = queue.pop() {
+            if let Some((s, _)) = self.decim.feed(s, 0.0) {
+                self.chain.feed(s, handler);
+            }
+        }
+    }
+
+    /// Index of the next sample to be decoded, counted at the 48 kHz baseband rate.
+    pub fn sample(&self) -> u64 { self.chain.sample }
+
+    /// Anchor the given sample index to the given wall-clock time, so events returned
+    /// from then on carry derived times.
+    ///
+    /// This should be called whenever the application has a trustworthy
+    /// correspondence, such as at the start of the stream or from a time announcement.
+    /// Events passed to a handler carry only sample indices, which can be converted
+    /// with a copy of `time_base`.
+    pub fn anchor(&mut self, sample: u64, time: WallTime) {
+        self.pending.time_base.anchor(sample, time);
+    }
+
+    /// Time base that derives the wall-clock times of events.
+    pub fn time_base(&self) -> &TimeBase { &self.pending.time_base }
+
+    /// Frequency control loop, if enabled.
+    pub fn afc(&self) -> Option<&Afc> { self.chain.recv.afc() }
+
+    /// Symbol clock tracker, if enabled, whose `ppm` gives the estimated sample clock
+    /// error.
+    pub fn clock(&self) -> Option<&ClockTracker> { self.chain.recv.clock() }
+
+    /// Inbound channel state signalled by the status symbols, if decoding it is
+    /// enabled.
+    pub fn inbound_status(&self) -> Option<ChannelStatus> {
+        self.chain.status.map(|c| c.state())
+    }
+
+    /// Summary counters over everything decoded so far.
+    pub fn stats(&self) -> &ReceiverStats { &self.chain.stats }
+
+    /// Reset the summary counters to zero.
+    pub fn reset_stats(&mut self) { self.chain.stats.reset(); }
+
+    /// Whether frame sync is currently acquired.
+    pub fn locked(&self) -> bool { self.chain.locked }
+
+    /// Recently seen NACs, from least to most recent.
+    pub fn nacs(&self) -> Vec<NetworkAccessCode> {
+        self.chain.nacs.iter().map(|s| s.nac).collect()
+    }
+
+    /// Currently active calls on the given NAC, or none if the NAC hasn't been seen
+    /// recently or call tracking is disabled.
+    pub fn calls(&self, nac: NetworkAccessCode) -> &[Call] {
+        match self.chain.state(nac) {
+            Some(&NacState { calls: Some(ref t), .. }) => t.calls(),
+            _ => &[],
+        }
+    }
+
+    /// State of the site on the given NAC, if the NAC has been seen recently.
+    pub fn site(&self, nac: NetworkAccessCode) -> Option<&SiteInfo> {
+        self.chain.state(nac).map(|s| &s.site)
+    }
+
+    /// Number of MI discontinuities in the current or last encrypted call on the given
+    /// NAC, if the NAC has been seen recently.
+    pub fn mi_discontinuities(&self, nac: NetworkAccessCode) -> Option<usize> {
+        self.chain.state(nac).map(|s| s.mi.discontinuities())
+    }
+
+    /// Capture the state that stays valid across a retune, such as before leaving a
+    /// control channel to follow a call.
+    pub fn snapshot(&self) -> ReceiverState {
+        ReceiverState {
+            thresholds: self.chain.recv.snapshot(),
+            nacs: self.chain.nacs.clone(),
+            sample: self.chain.sample,
+        }
+    }
+
+    /// Replace the site, call, and threshold state with the given state, such as after
+    /// returning to the channel it was taken on, and start hunting for a frame sync.
+    ///
+    /// Restored calls resume as though no time passed since the snapshot, so they only
+    /// end if their grants stay missing for the call timeout after restoring. Pending
+    /// events are kept, and the sample index never goes backwards.
+    pub fn restore(&mut self, state: &ReceiverState) {
+        self.chain.restore(state);
+        self.demod.reset();
+    }
+}
+
+/// Iterator that lazily decodes a stream of baseband samples into events.
+///
+/// Once the samples run out, sync is lost and any active calls are ended, so every call
+/// started in the stream also ends in it.
+pub struct DecodeIter<I: Iterator<Item = f32>> {
+    /// Source of baseband samples.
+    samples: I,
+    /// Receiver driven by the samples.
+    recv: Receiver,
+    /// Whether the samples have run out.
+    done: bool,
+}
+
+impl<I: Iterator<Item = f32>> DecodeIter<I> {
+    /// Create a new `DecodeIter` over the given samples with the given configuration,
+    /// or return an error naming the first invalid field.
+    pub fn new(samples: I, config: ReceiverConfig) -> error::Result<DecodeIter<I>> {
+        Ok(DecodeIter {
+            samples: samples,
+            recv: Receiver::new(config)?,
+            done: false,
+        })
+    }
+
+    /// Underlying receiver.
+    pub fn receiver(&self) -> &Receiver { &self.recv }
+}
+
+impl<I: Iterator<Item = f32>> Iterator for DecodeIter<I> {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        loop {
+            if let Some(e) = self.recv.next_event() {
+                return Some(e);
+            }
+
+            if self.done {
+                return None;
+            }
+
+            match self.samples.next() {
+                Some(s) => if let Some(e) = self.recv.feed(s) {
+                    return Some(e);
+                },
+                None => {
+                    self.done = true;
+                    self.recv.chain.finish(&mut self.recv.pending);
+                },
+            }
+        }
+    }
+}
+
+/// Decodes P25 events from an iterator of baseband samples.
+///
+/// ```
+/// extern crate p25;
+///
+/// use p25::bits::Dibit;
+/// use p25::consts::SYMBOL_PERIOD;
+/// use p25::message::nid::NetworkAccessCode;
+/// use p25::receiver::{DecodeP25, EventKind};
+/// use p25::voice::encode::VoiceEncoder;
+///
+/// # fn main() {
+/// // Modulate a voice header and a frame group, holding each symbol for a full period.
+/// let voice = VoiceEncoder::new(NetworkAccessCode::Default);
+///
+/// let mut dibits: Vec<Dibit> = voice.header(&[0; 15]);
+/// dibits.extend(voice.lc_frame_group(&[[0; 8]; 9], &[0; 9], 0));
+///
+/// let mut samples = vec![0.0; 1000];
+///
+/// for d in dibits {
+///     let level = match d.bits() {
+///         0b01 => 0.18,
+///         0b00 => 0.02,
+///         0b10 => -0.02,
+///         _ => -0.18,
+///     };
+///
+///     samples.extend(std::iter::repeat(level).take(SYMBOL_PERIOD));
+/// }
+///
+/// let frames = samples.into_iter()
+///     .decode_p25()
+///     .filter(|e| match e.kind {
+///         EventKind::VoiceFrame(_) => true,
+///         _ => false,
+///     })
+///     .count();
+///
+/// assert_eq!(frames, 9);
+/// # }
+/// ```
+pub trait DecodeP25: Iterator<Item = f32> + Sized {
+    /// Decode the samples with the default configuration.
+    fn decode_p25(self) -> DecodeIter<Self> {
+        self.decode_p25_with(Default::default()).expect("default configuration is valid")
+    }
+
+    /// Decode the samples with the given configuration, or return an error naming the
+    /// first invalid field.
+    fn decode_p25_with(self, config: ReceiverConfig) -> error::Result<DecodeIter<Self>> {
+        DecodeIter::new(self, config)
+    }
+}
+
+impl<I: Iterator<Item = f32>> DecodeP25 for I {}
+
+/// State kept separately for each recently seen NAC.
+#[derive(Clone)]
+struct NacState {
+    /// NAC the state belongs to.
+    nac: NetworkAccessCode,
+    /// Call tracker, if enabled.
+    calls: Option<GrantTracker>,
+    /// State advertised by the site.
+    site: SiteInfo,
+    /// Checks the MI continuity of encrypted calls.
+    mi: MiTracker,
+}
+
+/// Full receive chain, which passes its events to a handler.
+struct Chain {
+    /// Message-level receiver.
+    recv: MessageReceiver,
+    /// Receiver configuration.
+    config: ReceiverConfig,
+    /// State of each recently seen NAC, from least to most recent.
+    nacs: Vec<NacState>,
+    /// NAC of the current data unit, if known.
+    nac: Option<NetworkAccessCode>,
+    /// Type of the current data unit, if known.
+    duid: Option<DataUnit>,
+    /// NAC of the last event passed to a handler.
+    reported: Option<NetworkAccessCode>,
+    /// Index of the next sample.
+    sample: u64,
+    /// Whether frame sync is currently considered acquired.
+    locked: bool,
+    /// Whether the message receiver was locked onto a data unit after the previous
+    /// sample.
+    synced: bool,
+    /// Sample where the message receiver last began hunting for a frame sync.
+    hunting: u64,
+    /// Inbound channel state decoder, if enabled.
+    status: Option<StatusChannel>,
+    /// Last voice frame decoded in the current call, for concealment.
+    last_voice: Option<VoiceFrame>,
+    /// Number of times in a row the last voice frame was repeated.
+    repeats: usize,
+    /// Summary counters.
+    stats: ReceiverStats,
+}
+
+/// Averages each group of input samples down to the baseband rate.
+struct Decimator {
+    /// Number of input samples in each group.
+    factor: usize,
+    /// Number of input samples in the current group.
+    count: usize,
+    /// Sums of the I and Q components over the current group.
+    sums: (f32, f32),
+}
+
+impl Decimator {
+    /// Create a new `Decimator` averaging over groups of the given size.
+    fn new(factor: usize) -> Decimator {
+        Decimator {
+            factor: factor,
+            count: 0,
+            sums: (0.0, 0.0),
+        }
+    }
+
+    /// Add the given IQ sample, returning the average of its group if it completed one.
+    fn feed(&mut self, i: f32, q: f32) -> Option<(f32, f32)> {
+        if self.factor == 1 {
+            return Some((i, q));
+        }
+
+        self.sums.0 += i;
+        self.sums.1 += q;
+        self.count += 1;
+
+        if self.count < self.factor {
+            return None;
+        }
+
+        let n = self.factor as f32;
+        let avg = (self.sums.0 / n, self.sums.1 / n);
+
+        self.count = 0;
+        self.sums = (0.0, 0.0);
+
+        Some(avg)
+    }
+}
+
+impl Chain {
+    /// Create a new `Chain` with the given configuration.
+    fn new(config: ReceiverConfig) -> Chain {
+        let status = if config.status_debounce > 0 {
+            Some(StatusChannel::new(config.status_debounce))
+        } else {
+            None
+        };
+
+        Chain {
+            recv: MessageReceiver::new()
+                .with_routes(config.routes)
+                .with_nac_filter(config.nac_filter.clone())
+                .with_max_nacs(config.max_nacs)
+                .with_smoothing(config.threshold_smoothing)
+                .with_sync_threshold(config.sync_threshold)
+                .with_diagnostics(config.diagnostics >= Verbosity::All),
+            config: config,
+            nacs: vec![],
+            nac: None,
+            duid: None,
+            reported: None,
+            sample: 0,
+            locked: false,
+            synced: false,
+            hunting: 0,
+            status: status,
+            last_voice: None,
+            repeats: 0,
+            stats: ReceiverStats::new(),
+        }
+    }
+
+    /// Replace the state carried across a retune with the given state, resetting the
+    /// state tied to the current signal.
+    fn restore(&mut self, state: &ReceiverState) {
+        let delay = self.sample.saturating_sub(state.sample);
+
+        self.recv.restore(&state.thresholds);
+        self.nacs = state.nacs.clone();
+
+        let extra = self.nacs.len().saturating_sub(self.config.max_nacs);
+        self.nacs.drain(..extra);
+
+        for s in self.nacs.iter_mut() {
+            if let Some(ref mut t) = s.calls {
+                t.delay(delay);
+            }
+        }
+
+        self.sample = self.sample.max(state.sample);
+        self.nac = None;
+        self.duid = None;
+        self.synced = false;
+        self.hunting = self.sample;
+
+        if let Some(ref mut c) = self.status {
+            c.reset();
+        }
+    }
+
+    /// Find the state of the given NAC.
+    fn state(&self, nac: NetworkAccessCode) -> Option<&NacState> {
+        self.nacs.iter().find(|s| s.nac == nac)
+    }
+
+    /// Tell the given handler the NAC of the following events if it changed.
+    fn report<H: MessageHandler>(&mut self, nac: Option<NetworkAccessCode>, h: &mut H) {
+        if nac != self.reported {
+            self.reported = nac;
+            h.on_nac(nac);
+        }
+    }
+
+    /// Mark the given NAC as the most recently seen, making room for it if needed by
+    /// dropping the least recently seen NAC and ending its calls.
+    fn touch<H: MessageHandler>(&mut self, nac: NetworkAccessCode, h: &mut H) {
+        let state = match self.nacs.iter().position(|s| s.nac == nac) {
+            Some(idx) => self.nacs.remove(idx),
+            None => {
+                if self.nacs.len() == self.config.max_nacs {
+                    let old = self.nacs.remove(0);
+
+                    if let Some(mut t) = old.calls {
+                        self.report(Some(old.nac), h);
+
+                        for call in t.expire(std::u64::MAX) {
+                            dispatch_call(call, h);
+                        }
+                    }
+                }
+
+                NacState {
+                    nac: nac,
+                    calls: if self.config.track_calls {
+                        Some(GrantTracker::new(self.config.call_heartbeat,
+                                               self.config.call_timeout))
+                    } else {
+                        None
+                    },
+                    site: SiteInfo::new(),
+                    mi: MiTracker::new(),
+                }
+            },
+        };
+
+        self.nacs.push(state);
+    }
+
+    /// Process the given sample, passing any resulting events to the given handler.
+    fn feed<H: MessageHandler>(&mut self, s: f32, h: &mut H) {
+        let time = self.tick(1, h);
+        let event = self.recv.feed(s);
+        self.step(event, time, h);
+    }
+
+    /// Process the given symbol, which stands in for the samples of a whole symbol
+    /// period, passing any resulting events to the given handler.
+    fn feed_dibit<H: MessageHandler>(&mut self, d: Dibit, h: &mut H) {
+        let time = self.tick(SYMBOL_PERIOD as u64, h);
+        let event = self.recv.feed_dibit(d);
+        self.step(event, time, h);
+    }
+
+    /// Advance the sample index by the given number of samples, returning the index of
+    /// the first one.
+    fn tick<H: MessageHandler>(&mut self, samples: u64, h: &mut H) -> u64 {
+        let time = self.sample;
+        self.sample += samples;
+
+        h.on_sample(time);
+
+        time
+    }
+
+    /// Update sync state and pass on the given message event, if any, produced at the
+    /// given time.
+    fn step<H: MessageHandler>(&mut self, event: Option<MessageEvent>, time: u64,
+                               h: &mut H)
+    {
+        let synced = self.recv.synced();
+
+        if synced && !self.locked {
+            self.locked = true;
+            self.report(None, h);
+            h.on_sync(true);
+        }
+
+        if synced && !self.synced {
+            self.nac = None;
+            self.duid = None;
+            self.stats.syncs += 1;
+        }
+
+        if self.synced && !synced {
+            self.hunting = time;
+        }
+
+        self.synced = synced;
+
+        if !synced && self.locked && time - self.hunting > self.config.sync_timeout {
+            self.locked = false;
+            self.stats.syncs_lost += 1;
+            self.report(None, h);
+            h.on_sync(false);
+
+            if let Some(ref mut c) = self.status {
+                c.reset();
+            }
+        }
+
+        while let Some(diag) = self.recv.next_diagnostic() {
+            self.report(None, h);
+            h.on_diagnostic(diag);
+        }
+
+        if let Some(code) = self.recv.take_status() {
+            let change = match self.status {
+                Some(ref mut c) => c.feed(code, time),
+                None => None,
+            };
+
+            if let Some(change) = change {
+                let nac = self.nac;
+                self.report(nac, h);
+                h.on_status(change);
+            }
+        }
+
+        if let Some(event) = event {
+            self.handle(event, time, h);
+        }
+
+        if time % EXPIRE_PERIOD == 0 {
+            self.expire(time, h);
+        }
+    }
+
+    /// Handle the end of the input, passing the loss of sync and the end of every
+    /// active call to the given handler.
+    fn finish<H: MessageHandler>(&mut self, h: &mut H) {
+        h.on_sample(self.sample);
+
+        if self.locked {
+            self.locked = false;
+            self.stats.syncs_lost += 1;
+            self.report(None, h);
+            h.on_sync(false);
+        }
+
+        self.expire(std::u64::MAX, h);
+    }
+
+    /// End the calls on every NAC whose grants stopped before the given time, passing
+    /// the events to the given handler.
+    fn expire<H: MessageHandler>(&mut self, time: u64, h: &mut H) {
+        for i in 0..self.nacs.len() {
+            let ended = match self.nacs[i].calls {
+                Some(ref mut t) => t.expire(time),
+                None => continue,
+            };
+
+            if !ended.is_empty() {
+                let nac = self.nacs[i].nac;
+                self.report(Some(nac), h);
+            }
+
+            for call in ended {
+                dispatch_call(call, h);
+            }
+        }
+    }
+
+    /// Update the state of the current NAC with the given message event and pass it on
+    /// to the given handler, followed by any resulting call events.
+    fn handle<H: MessageHandler>(&mut self, mut event: MessageEvent, time: u64,
+                                 h: &mut H)
+    {
+        let diag = if self.config.diagnostics >= Verbosity::Failures {
+            self.failure(&event)
+        } else {
+            None
+        };
+
+        match event {
+            MessageEvent::PacketNID(nid) => {
+                self.nac = Some(nid.access_code);
+                self.duid = Some(nid.data_unit);
+                self.touch(nid.access_code, h);
+            },
+            MessageEvent::FrameFiltered { nac, duid } => {
+                self.nac = Some(nac);
+                self.duid = Some(duid);
+            },
+            MessageEvent::FrameAborted => {
+                self.duid = None;
+                self.stats.syncs += 1;
+            },
+            _ => {},
+        }
+
+        let repeat = self.conceal(&mut event);
+
+        self.stats.record(&event);
+        self.stats.record_fixes(self.recv.stats());
+
+        let nac = self.nac;
+
+        let (calls, crypto) = match nac {
+            Some(nac) => match self.nacs.iter_mut().find(|s| s.nac == nac) {
+                Some(state) => {
+                    state.site.feed(&event, time);
+
+                    let calls = match state.calls {
+                        Some(ref mut t) => t.feed(&event, time),
+                        None => vec![],
+                    };
+
+                    (calls, state.mi.feed(&event))
+                },
+                None => (vec![], None),
+            },
+            None => (vec![], None),
+        };
+
+        self.report(nac, h);
+
+        if let Some(diag) = diag {
+            h.on_diagnostic(diag);
+        }
+
+        dispatch(event, self.recv.raw(), h);
+
+        if let Some(vf) = repeat {
+            h.on_voice_frame(vf);
+        }
+
+        if let Some(crypto) = crypto {
+            h.on_crypto(crypto);
+        }
+
+        for call in calls {
+            dispatch_call(call, h);
+        }
+    }
+
+    /// Remember the last good voice frame of the current call and fill in for the given
+    /// event with a repeat of it if it's a bad voice frame, as allowed by the
+    /// concealment policy. A frame with too many corrected bits is replaced in place,
+    /// while the repeat to follow a frame that failed to decode is returned.
+    fn conceal(&mut self, event: &mut MessageEvent) -> Option<VoiceFrame> {
+        let (max, errors) = match self.config.concealment {
+            Concealment::Off => return None,
+            Concealment::Repeat { max, errors } => (max, errors),
+        };
+
+        let failed = match *event {
+            MessageEvent::PacketNID(nid) => {
+                match nid.data_unit {
+                    DataUnit::VoiceLCFrameGroup | DataUnit::VoiceCCFrameGroup => {},
+                    _ => self.last_voice = None,
+                }
+
+                return None;
+            },
+            MessageEvent::VoiceFrame(vf) if vf.errors.iter().sum::<usize>() <= errors => {
+                self.last_voice = Some(vf);
+                self.repeats = 0;
+
+                return None;
+            },
+            MessageEvent::VoiceFrame(_) => false,
+            MessageEvent::Error(P25Error::GolayStdUnrecoverable) |
+            MessageEvent::Error(P25Error::HammingStdUnrecoverable) => true,
+            _ => return None,
+        };
+
+        let repeat = match self.last_voice {
+            Some(vf) if self.repeats < max => vf,
+            _ => return None,
+        };
+
+        self.repeats += 1;
+
+        if failed {
+            Some(repeat)
+        } else {
+            *event = MessageEvent::VoiceFrame(repeat);
+            None
+        }
+    }
+
+    /// Describe the failure the given message event reports in the current data unit,
+    /// if any.
+    fn failure(&self, event: &MessageEvent) -> Option<Diagnostic> {
+        let err = match *event {
+            MessageEvent::FrameAborted =>
+                return Some(Diagnostic::FrameAborted { duid: self.duid }),
+            MessageEvent::Error(err) => err,
+            MessageEvent::DataPacket(PduEvent::BadHeader(err)) => err,
+            MessageEvent::DataPacket(PduEvent::BadBlock { err, .. }) => err,
+            _ => return None,
+        };
+
+        if err.is_fec() {
+            Some(Diagnostic::FecFailure { duid: self.duid, err: err })
+        } else {
+            None
+        }
+    }
+}
+
+/// Pass the given message event to the given handler, along with the data dibits of the
+/// last raw data unit.
+fn dispatch<H: MessageHandler>(event: MessageEvent, raw: &[Dibit], h: &mut H) {
+    match event {
+        MessageEvent::Error(err) => h.on_error(err),
+        MessageEvent::PacketNID(nid) => h.on_nid(nid),
+        MessageEvent::VoiceHeader(head) => h.on_voice_header(head),
+        MessageEvent::VoiceFrame(vf) => h.on_voice_frame(vf),
+        MessageEvent::LinkControl(lc) => h.on_link_control(lc),
+        MessageEvent::CryptoControl(cc) => h.on_crypto_control(cc),
+        MessageEvent::LowSpeedDataFragment(lsd) => h.on_low_speed_data(lsd),
+        MessageEvent::TrunkingControl(tsbk) => h.on_tsbk(tsbk),
+        MessageEvent::TrunkingMultiBlock(ambt) => h.on_ambt(ambt),
+        MessageEvent::VoiceTerm(lc) => h.on_voice_term(lc),
+        MessageEvent::DataPacket(pdu) => h.on_pdu(pdu),
+        MessageEvent::RawDataUnit(du) => h.on_raw(du, raw),
+        MessageEvent::FrameAborted => h.on_frame_aborted(),
+        MessageEvent::FrameFiltered { nac, duid } => h.on_frame_filtered(nac, duid),
+    }
+}
+
+/// Pass the given call event to the given handler.
+fn dispatch_call<H: MessageHandler>(event: CallEvent, h: &mut H) {
+    match event {
+        CallEvent::Started(call) => h.on_call_start(call),
+        CallEvent::Continued(call) => h.on_call_update(call),
+        CallEvent::Ended(call) => h.on_call_end(call),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std;
+    use bits::{Dibit, Dibits};
+    use baseband::sync::{SYNC_GENERATOR, MAX_SNR};
+    use consts::SYMBOL_PERIOD;
+    use message::nid::{DataUnit, NetworkAccessCode};
+    use message::receiver::Route;
+    use message::status::{StatusCode, StatusInterleaver};
+    use trunking::calls::CallTarget;
+    use trunking::encode::Tsdu;
+    use trunking::fields::{Channel, TalkGroup};
+    use trunking::tsbk::TsbkOpcode;
+    use trunking::vendor::MFID_STANDARD;
+    use voice::encode::{self, VoiceEncoder, GROUP_FRAMES};
+
+    const NAC: NetworkAccessCode = NetworkAccessCode::Default;
+
+    /// Modulate the given dibits into baseband samples, holding each symbol level for a
+    /// full symbol period. The inner levels are kept near zero so they're decided
+    /// correctly while the symbol thresholds settle over the first few frame syncs.
+    fn modulate(dibits: &[Dibit]) -> Vec<f32> {
+        dibits.iter()
+            .flat_map(|d| {
+                let level = match d.bits() {
+                    0b01 => 0.18,
+                    0b00 => 0.02,
+                    0b10 => -0.02,
+                    _ => -0.18,
+                };
+
+                std::iter::repeat(level).take(SYMBOL_PERIOD)
+            })
+            .collect()
+    }
+
+    /// Build a TSDU carrying a group voice grant.
+    fn grant(ch: u16, tg: u16, src: u32) -> Vec<Dibit> { grant_on(NAC, ch, tg, src) }
+
+    /// Build a TSDU on the given NAC carrying a group voice grant.
+    fn grant_on(nac: NetworkAccessCode, ch: u16, tg: u16, src: u32) -> Vec<Dibit> {
+        let tsbk = TsbkFields::build(TsbkOpcode::GroupVoiceGrant.to_bits(), MFID_STANDARD,
+                                     &[
+            0x00,
+            (ch >> 8) as u8,
+            ch as u8,
+            (tg >> 8) as u8,
+            tg as u8,
+            (src >> 16) as u8,
+            (src >> 8) as u8,
+            src as u8,
+        ]);
+
+        tsdu(nac, tsbk)
+    }
+
+    fn tsdu(nac: NetworkAccessCode, tsbk: TsbkFields) -> Vec<Dibit> {
+        let nid = NetworkId::new(nac, DataUnit::TrunkingSignaling).encode();
+
+        let dibits = Dibits::new(SYNC_GENERATOR.iter().cloned())
+            .chain(Dibits::new(nid.iter().cloned()))
+            .chain(Tsdu::assemble(&[tsbk]).into_iter());
+
+        StatusInterleaver::new(dibits, StatusCode::InboundIdle).collect()
+    }
+
+    fn frames(base: u32) -> [[u32; 8]; GROUP_FRAMES] {
+        let mut frames = [[0; 8]; GROUP_FRAMES];
+
+        for (n, f) in frames.iter_mut().enumerate() {
+            let n = base + n as u32;
+            *f = [n, n + 1, n + 2, n + 3, n + 4, n + 5, n + 6, n & 0x7F];
+        }
+
+        frames
+    }
+
+    /// Short name of the given event.
+    fn tag(e: &Event) -> &'static str {
+        match e.kind {
+            EventKind::SyncAcquired => "sync",
+            EventKind::SyncLost => "lost",
+            EventKind::Nid(_) => "nid",
+            EventKind::VoiceHeader(_) => "hdu",
+            EventKind::VoiceFrame(_) => "vf",
+            EventKind::LinkControl(_) => "lc",
+            EventKind::CryptoControl(_) => "cc",
+            EventKind::Crypto(_) => "crypto",
+            EventKind::Status(_) => "status",
+            EventKind::LowSpeedData(_) => "lsd",
+            EventKind::Tsbk(_) => "tsbk",
+            EventKind::Ambt(_) => "ambt",
+            EventKind::VoiceTerm(_) => "term",
+            EventKind::Pdu(_) => "pdu",
+            EventKind::Raw(..) => "raw",
+            EventKind::FrameAborted => "abort",
+            EventKind::FrameFiltered { .. } => "filtered",
+            EventKind::Call(CallEvent::Started(_)) => "start",
+            EventKind::Call(CallEvent::Continued(_)) => "cont",
+            EventKind::Call(CallEvent::Ended(_)) => "end",
+            EventKind::Error(_) => "err",
+            EventKind::Diagnostic(_) => "diag",
+        }
+    }
+
+    const LC: [u8; 9] = [0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x12, 0x34, 0x56];
+    const CC: [u8; 12] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0x80, 0x00, 0x00];
+
+    /// Modulate the given data units, surrounded by silence.
+    fn transmit(dibits: &[Dibit]) -> Vec<f32> {
+        let mut samples = vec![0.0; 1000];
+        samples.extend(modulate(dibits));
+        samples.extend(std::iter::repeat(0.0).take(SAMPLE_RATE * 2));
+        samples
+    }
+
+    /// Baseband samples of a voice grant followed by a short voice call on a traffic
+    /// channel, surrounded by silence.
+    fn call() -> Vec<f32> {
+        let voice = VoiceEncoder::new(NAC);
+
+        let mut dibits = grant(0x1020, 0x0100, 0x123456);
+        dibits.extend(voice.header(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0x00, 0x80, 0, 0,
+                                     0x01, 0x00]));
+        dibits.extend(voice.lc_frame_group(&frames(1), &LC, 0xBEEF));
+        dibits.extend(voice.cc_frame_group(&frames(10), &CC, 0x1234));
+        dibits.extend(voice.simple_terminator());
+
+        transmit(&dibits)
+    }
+
+    fn config() -> ReceiverConfig {
+        ReceiverConfig {
+            call_timeout: SAMPLE_RATE as u64,
+            ..ReceiverConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_voice_call() {
+        let samples = call();
+        let mut recv = Receiver::new(config()).unwrap();
+
+        let events = recv.feed_slice(&samples);
+        let tags: Vec<&str> = events.iter().map(tag).collect();
+
+        let mut expect = vec!["sync", "nid", "tsbk", "start", "nid", "hdu", "nid"];
+        expect.extend(std::iter::repeat("vf").take(7));
+        expect.extend(&["lc", "vf", "lsd", "vf", "nid"]);
+        expect.extend(std::iter::repeat("vf").take(7));
+        expect.extend(&["cc", "vf", "lsd", "vf", "nid", "lost", "end"]);
+
+        assert_eq!(tags, expect);
+
+        let nids: Vec<DataUnit> = events.iter()
+            .filter_map(|e| match e.kind {
+                EventKind::Nid(nid) => Some(nid.data_unit),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(nids, vec![
+            DataUnit::TrunkingSignaling,
+            DataUnit::VoiceHeader,
+            DataUnit::VoiceLCFrameGroup,
+            DataUnit::VoiceCCFrameGroup,
+            DataUnit::VoiceSimpleTerminator,
+        ]);
+
+        let chunks: Vec<[u32; 8]> = events.iter()
+            .filter_map(|e| match e.kind {
+                EventKind::VoiceFrame(ref vf) => Some(vf.chunks),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(&chunks[..9], &frames(1)[..]);
+        assert_eq!(&chunks[9..], &frames(10)[..]);
+
+        for e in events.iter() {
+            match e.kind {
+                EventKind::VoiceHeader(ref h) =>
+                    assert_eq!(h.talk_group(), TalkGroup::Other(0x0100)),
+                EventKind::LinkControl(ref l) => assert_eq!(l.payload(), &LC[1..]),
+                EventKind::Call(CallEvent::Started(c)) => {
+                    assert_eq!(c.target, CallTarget::Group(TalkGroup::Other(0x0100)));
+                    assert_eq!(c.src_unit, Some(0x123456));
+                },
+                _ => {},
+            }
+        }
+
+        let lsd: Vec<u32> = events.iter()
+            .filter_map(|e| match e.kind {
+                EventKind::LowSpeedData(lsd) => Some(lsd),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(lsd, vec![0xBEEF, 0x1234]);
+        assert!(!recv.locked());
+        assert!(recv.calls(NAC).is_empty());
+
+        // Same input, same output.
+        let mut again = Receiver::new(config()).unwrap();
+
+        let tags_again: Vec<&str> = again.feed_slice(&samples).iter().map(tag).collect();
+        assert_eq!(tags_again, tags);
+    }
+
+    #[test]
+    fn test_builder() {
+        let built = ReceiverConfig::builder().build().unwrap();
+        assert_eq!(built, ReceiverConfig::default());
+
+        // A default built receiver decodes the reference call the same as one
+        // configured directly.
+        let samples = call();
+
+        let direct: Vec<String> = Receiver::new(ReceiverConfig::default()).unwrap()
+            .feed_slice(&samples).into_iter().map(record).collect();
+        let built: Vec<String> = Receiver::new(built).unwrap()
+            .feed_slice(&samples).into_iter().map(record).collect();
+
+        assert!(direct.len() > 30);
+        assert_eq!(built, direct);
+
+        let config = ReceiverConfig::builder()
+            .sample_rate(SAMPLE_RATE * 2)
+            .sync_timeout(100)
+            .track_calls(false)
+            .call_heartbeat(200)
+            .call_timeout(300)
+            .route(DataUnit::VoiceHeader, Route::Skip)
+            .nac_filter(NacFilter::new().accept(NAC))
+            .max_nacs(1)
+            .diagnostics(Verbosity::Failures)
+            .status_debounce(3)
+            .threshold_smoothing(8)
+            .sync_threshold(0.5)
+            .concealment(Concealment::Repeat { max: 2, errors: 6 })
+            .build()
+            .unwrap();
+
+        assert_eq!(config, ReceiverConfig {
+            sample_rate: SAMPLE_RATE * 2,
+            sync_timeout: 100,
+            track_calls: false,
+            call_heartbeat: 200,
+            call_timeout: 300,
+            routes: Routes::default().with(DataUnit::VoiceHeader, Route::Skip),
+            nac_filter: NacFilter::new().accept(NAC),
+            max_nacs: 1,
+            diagnostics: Verbosity::Failures,
+            status_debounce: 3,
+            threshold_smoothing: 8,
+            sync_threshold: 0.5,
+            concealment: Concealment::Repeat { max: 2, errors: 6 },
+        });
+
+        let field = |b: ReceiverConfigBuilder| match b.build() {
+            Err(P25Error::InvalidConfig { field, .. }) => field,
+            _ => panic!(),
+        };
+
+        assert_eq!(field(ReceiverConfig::builder().sample_rate(0)), "sample_rate");
+        assert_eq!(field(ReceiverConfig::builder().sample_rate(50000)), "sample_rate");
+        assert_eq!(field(ReceiverConfig::builder().sync_timeout(0)), "sync_timeout");
+        assert_eq!(field(ReceiverConfig::builder().call_heartbeat(0)), "call_heartbeat");
+        assert_eq!(field(ReceiverConfig::builder().call_timeout(0)), "call_timeout");
+        assert_eq!(field(ReceiverConfig::builder().max_nacs(0)), "max_nacs");
+        assert_eq!(field(ReceiverConfig::builder().threshold_smoothing(0)),
+                   "threshold_smoothing");
+        assert_eq!(field(ReceiverConfig::builder().threshold_smoothing(17)),
+                   "threshold_smoothing");
+        assert_eq!(field(ReceiverConfig::builder().sync_threshold(0.0)), "sync_threshold");
+        assert_eq!(field(ReceiverConfig::builder().sync_threshold(std::f32::NAN)),
+                   "sync_threshold");
+        let never = Concealment::Repeat { max: 0, errors: 6 };
+        assert_eq!(field(ReceiverConfig::builder().concealment(never)), "concealment");
+
+        // Configs made elsewhere, such as by deserializing, are checked the same way,
+        // including when creating a receiver.
+        assert!(ReceiverConfig { max_nacs: 0, ..config.clone() }.check().is_err());
+        assert!(config.check().is_ok());
+
+        match Receiver::new(ReceiverConfig { sample_rate: 0, ..config.clone() }) {
+            Err(P25Error::InvalidConfig { field, .. }) => assert_eq!(field, "sample_rate"),
+            _ => panic!(),
+        }
+
+        assert!(Receiver::new(ReceiverConfig { max_nacs: 0, ..config }).is_err());
+    }
+
+    #[test]
+    fn test_sample_rate() {
+        let samples = call();
+        let expected: Vec<String> = Receiver::new(config()).unwrap()
+            .feed_slice(&samples).into_iter().map(record).collect();
+
+        // Each sample at twice the rate is averaged back down to the baseband rate.
+        let doubled: Vec<f32> = samples.iter().flat_map(|&s| vec![s, s]).collect();
+        let mut recv = Receiver::new(ReceiverConfig {
+            sample_rate: SAMPLE_RATE * 2,
+            ..config()
+        }).unwrap();
+
+        let events: Vec<String> = recv.feed_slice(&doubled).into_iter().map(record)
+            .collect();
+
+        assert_eq!(events, expected);
+        assert_eq!(recv.sample(), samples.len() as u64);
+    }
+
+    #[test]
+    fn test_concealment() {
+        use testutil;
+
+        let voice = VoiceEncoder::new(NAC);
+        let mut dibits = voice.lc_frame_group(&frames(1), &LC, 0xBEEF);
+
+        // Garble the second and third voice frames, which still decode but with most
+        // of their codewords corrected.
+        let mut rng = testutil::Rng::new(455);
+
+        for d in &mut dibits[140..290] {
+            *d = Dibit::new(rng.next() as u8 & 0b11);
+        }
+
+        dibits.extend(voice.simple_terminator());
+
+        let samples = transmit(&dibits);
+        let decode = |max| testutil::voice_frames(&testutil::decode_with(&samples,
+            ReceiverConfig {
+                concealment: Concealment::Repeat { max: max, errors: 6 },
+                ..config()
+            }));
+
+        let sent = frames(1);
+
+        let vfs = testutil::voice_frames(&testutil::decode_with(&samples, config()));
+        assert_eq!(vfs.len(), GROUP_FRAMES);
+        assert_eq!(vfs[0], sent[0]);
+        assert!(vfs[1] != sent[1] && vfs[2] != sent[2]);
+        assert_eq!(&vfs[3..], &sent[3..]);
+
+        // Bad frames are replaced by the last good one, only as many times in a row as
+        // allowed.
+        let vfs = decode(1);
+        assert_eq!(vfs.len(), GROUP_FRAMES);
+        assert_eq!(vfs[1], sent[0]);
+        assert!(vfs[2] != sent[0] && vfs[2] != sent[2]);
+        assert_eq!(&vfs[3..], &sent[3..]);
+
+        let vfs = decode(2);
+        assert_eq!(&vfs[..3], &[sent[0], sent[0], sent[0]]);
+        assert_eq!(&vfs[3..], &sent[3..]);
+
+        // Frames that fail to decode are followed by the repeat.
+        let mut chain = Chain::new(ReceiverConfig {
+            concealment: Concealment::Repeat { max: 1, errors: 6 },
+            ..config()
+        });
+
+        let mut good = MessageEvent::VoiceFrame(VoiceFrame {
+            chunks: sent[0],
+            errors: [0; 7],
+        });
+        let mut failed = MessageEvent::Error(P25Error::GolayStdUnrecoverable);
+
+        assert!(chain.conceal(&mut good).is_none());
+        assert_eq!(chain.conceal(&mut failed).map(|vf| vf.chunks), Some(sent[0]));
+        assert!(chain.conceal(&mut failed).is_none());
+    }
+
+    #[test]
+    fn test_routes() {
+        let voice = VoiceEncoder::new(NAC);
+
+        let mut dibits = grant(0x1020, 0x0100, 0x123456);
+        dibits.extend(voice.lc_frame_group(&frames(1), &LC, 0xBEEF));
+        dibits.extend(voice.cc_frame_group(&frames(10), &CC, 0x1234));
+        dibits.extend(grant(0x1030, 0x0200, 0x654321));
+        dibits.extend(voice.lc_frame_group(&frames(19), &LC, 0xCAFE));
+
+        let samples = transmit(&dibits);
+
+        // Voice frame groups are skipped but don't throw off the following TSDU.
+        let mut recv = Receiver::new(ReceiverConfig {
+            routes: Routes::default()
+                .with(DataUnit::VoiceLCFrameGroup, Route::Skip)
+                .with(DataUnit::VoiceCCFrameGroup, Route::Skip),
+            ..config()
+        }).unwrap();
+
+        let tags: Vec<&str> = recv.feed_slice(&samples).iter().map(tag).collect();
+
+        assert_eq!(tags, vec![
+            "sync", "nid", "tsbk", "start", "nid", "nid", "nid", "tsbk", "start", "nid",
+            "lost", "end", "end",
+        ]);
+
+        // Raw passthrough of LDU1 carries the coded dibits without status symbols.
+        let mut recv = Receiver::new(ReceiverConfig {
+            routes: Routes::default()
+                .with(DataUnit::VoiceLCFrameGroup, Route::Raw)
+                .with(DataUnit::VoiceCCFrameGroup, Route::Skip),
+            ..config()
+        }).unwrap();
+
+        let events = recv.feed_slice(&samples);
+        let tags: Vec<&str> = events.iter().map(tag).collect();
+
+        assert_eq!(tags, vec![
+            "sync", "nid", "tsbk", "start", "nid", "raw", "nid", "nid", "tsbk", "start",
+            "nid", "raw", "lost", "end", "end",
+        ]);
+
+        let raw: Vec<&[Dibit]> = events.iter()
+            .filter_map(|e| match e.kind {
+                EventKind::Raw(DataUnit::VoiceLCFrameGroup, ref d) => Some(&d[..]),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(raw[0], &encode::lc_frame_group(&frames(1), &LC, 0xBEEF)[..]);
+        assert_eq!(raw[1], &encode::lc_frame_group(&frames(19), &LC, 0xCAFE)[..]);
+
+        // Skipped TSDUs are still decoded far enough to find their end.
+        let mut recv = Receiver::new(ReceiverConfig {
+            routes: Routes::default()
+                .with(DataUnit::TrunkingSignaling, Route::Skip),
+            ..config()
+        }).unwrap();
+
+        let tags: Vec<&str> = recv.feed_slice(&samples).iter().map(tag).collect();
+        assert_eq!(tags.iter().filter(|&&t| t == "vf").count(), 27);
+        assert_eq!(tags.iter().filter(|&&t| t == "tsbk" || t == "start").count(), 0);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let voice = VoiceEncoder::new(NAC);
+
+        // Voice header cut off partway through, followed directly by a TSDU.
+        let mut dibits = voice.header(&[0; 15]);
+        dibits.truncate(150);
+        dibits.extend(grant(0x1020, 0x0100, 0x123456));
+        dibits.extend(voice.lc_frame_group(&frames(1), &LC, 0xBEEF));
+
+        let mut recv = Receiver::new(config()).unwrap();
+        let events = recv.feed_slice(&transmit(&dibits));
+        let tags: Vec<&str> = events.iter().map(tag).collect();
+
+        let mut expect = vec!["sync", "nid", "abort", "nid", "tsbk", "start", "nid"];
+        expect.extend(std::iter::repeat("vf").take(7));
+        expect.extend(&["lc", "vf", "lsd", "vf", "lost", "end"]);
+
+        assert_eq!(tags, expect);
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let voice = VoiceEncoder::new(NAC);
+
+        // Grant with two bit errors in its NID.
+        let mut dibits = grant(0x1020, 0x0100, 0x123456);
+        dibits[26] = Dibit::new(dibits[26].bits() ^ 0b01);
+        dibits[40] = Dibit::new(dibits[40].bits() ^ 0b10);
+
+        // Voice header cut off by a grant whose NID can't be corrected.
+        let mut header = voice.header(&[0; 15]);
+        header.truncate(150);
+        dibits.extend(header);
+
+        let mut bad = grant(0x1030, 0x0200, 0x654321);
+
+        for d in bad[24..34].iter_mut() {
+            *d = Dibit::new(d.bits() ^ 0b11);
+        }
+
+        dibits.extend(bad);
+        dibits.extend(grant(0x1020, 0x0100, 0x123456));
+
+        let samples = transmit(&dibits);
+
+        let diags = |verbosity| -> Vec<Diagnostic> {
+            Receiver::new(ReceiverConfig { diagnostics: verbosity, ..config() }).unwrap()
+                .feed_slice(&samples)
+                .into_iter()
+                .filter_map(|e| match e.kind {
+                    EventKind::Diagnostic(d) => Some(d),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let all = diags(Verbosity::All);
+
+        let kinds: Vec<&str> = all.iter()
+            .map(|d| match *d {
+                Diagnostic::SyncDetected { resync: false, .. } => "sync",
+                Diagnostic::SyncDetected { resync: true, .. } => "resync",
+                Diagnostic::NidDecoded { .. } => "nid",
+                Diagnostic::FrameAborted { .. } => "abort",
+                Diagnostic::FecFailure { .. } => "fec",
+            })
+            .collect();
+
+        assert_eq!(kinds, vec![
+            "sync", "nid", "sync", "nid", "resync", "abort", "fec", "sync", "nid",
+        ]);
+
+        assert_eq!(all[1], Diagnostic::NidDecoded { errors: 2 });
+        assert_eq!(all[3], Diagnostic::NidDecoded { errors: 0 });
+        assert_eq!(all[5], Diagnostic::FrameAborted {
+            duid: Some(DataUnit::VoiceHeader),
+        });
+        assert_eq!(all[6], Diagnostic::FecFailure {
+            duid: None,
+            err: P25Error::BchUnrecoverable,
+        });
+
+        match all[0] {
+            Diagnostic::SyncDetected { power, sigpower, threshold, symbols: (p, m, n),
+                                       frequency, snr, .. } => {
+                assert!(power > threshold);
+                assert!((threshold - sigpower.sqrt() * 0.65).abs() < 1.0e-6);
+                assert!(p > m && m > n);
+                // The test signal is on frequency.
+                assert!(frequency.hz.abs() < 50.0, "{:?}", frequency);
+                assert!(frequency.drift.abs() < 50.0, "{:?}", frequency);
+                // And free of noise.
+                assert!(snr > 20.0, "{}", snr);
+            },
+            _ => panic!(),
+        }
+
+        assert_eq!(diags(Verbosity::Failures), vec![all[5], all[6]]);
+        assert!(diags(Verbosity::Off).is_empty());
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let voice = VoiceEncoder::new(NAC);
+
+        // ID 1: base 851.00625MHz, spacing 6.25kHz.
+        let params = TsbkFields::build(TsbkOpcode::ChannelParamsUpdate.to_bits(),
+                                       MFID_STANDARD,
+                                       &[0x13, 0x22, 0xD0, 0x32, 0x0A, 0x25, 0x10, 0xA2]);
+
+        let mut dibits = tsdu(NAC, params);
+        dibits.extend(grant(0x1020, 0x0100, 0x123456));
+
+        // Control channel, retuned away from before sync is lost.
+        let mut control = vec![0.0; 1000];
+        control.extend(modulate(&dibits));
+        control.extend(std::iter::repeat(0.0).take(1000));
+
+        let mut recv = Receiver::new(config()).unwrap();
+
+        let tags: Vec<&str> = recv.feed_slice(&control).iter().map(tag).collect();
+        assert_eq!(tags, vec!["sync", "nid", "tsbk", "nid", "tsbk", "start"]);
+
+        let call = recv.calls(NAC)[0];
+        let state = recv.snapshot();
+
+        // Following the call on its traffic channel outlasts the call timeout.
+        let mut dibits = voice.lc_frame_group(&frames(1), &LC, 0xBEEF);
+        dibits.extend(voice.simple_terminator());
+
+        let tags: Vec<&str> = recv.feed_slice(&transmit(&dibits)).iter().map(tag)
+            .collect();
+
+        assert_eq!(&tags[tags.len() - 2..], &["lost", "end"]);
+        assert!(recv.calls(NAC).is_empty());
+
+        recv.restore(&state);
+
+        let restored = recv.calls(NAC)[0];
+        assert_eq!(recv.calls(NAC).len(), 1);
+        assert_eq!(restored.channel, call.channel);
+        assert_eq!(restored.target, call.target);
+        assert_eq!(restored.src_unit, call.src_unit);
+        assert_eq!(restored.started, call.started);
+        assert_eq!(restored.updated - call.updated, recv.sample() - control.len() as u64);
+        assert_eq!(recv.site(NAC).unwrap().params().unwrap().value
+                       .rx_freq(Channel::from_bits(0x1020)),
+                   Some(851_206_250));
+
+        // Returning to the control channel picks the call back up instead of starting
+        // it over.
+        let mut samples = vec![0.0; 1000];
+        samples.extend(modulate(&grant(0x1020, 0x0100, 0x123456)));
+        samples.extend(std::iter::repeat(0.0).take(1000));
+
+        let tags: Vec<&str> = recv.feed_slice(&samples).iter().map(tag).collect();
+
+        assert_eq!(tags, vec!["sync", "nid", "tsbk"]);
+        assert_eq!(recv.calls(NAC).len(), 1);
+        assert_eq!(recv.calls(NAC)[0].started, call.started);
+
+        // Restoring into a new receiver carries on from the time of the snapshot.
+        let mut fresh = Receiver::new(config()).unwrap();
+        fresh.restore(&state);
+
+        // The new receiver's clock starts at the snapshot, so no delay applies.
+        assert_eq!(fresh.sample(), control.len() as u64);
+        assert_eq!(fresh.calls(NAC), &[call]);
+
+        let tags: Vec<&str> = fresh.feed_slice(&samples).iter().map(tag).collect();
+
+        assert_eq!(tags, vec!["sync", "nid", "tsbk"]);
+    }
+
+    #[test]
+    fn test_nac_filter() {
+        let a = NetworkAccessCode::Other(0x123);
+        let b = NetworkAccessCode::Other(0x456);
+
+        let mut dibits = grant_on(a, 0x1020, 0x0100, 0x123456);
+        dibits.extend(VoiceEncoder::new(b).lc_frame_group(&frames(1), &LC, 0xBEEF));
+        dibits.extend(grant_on(b, 0x1030, 0x0200, 0x654321));
+        dibits.extend(VoiceEncoder::new(a).lc_frame_group(&frames(1), &LC, 0xBEEF));
+
+        let samples = transmit(&dibits);
+
+        let mut recv = Receiver::new(ReceiverConfig {
+            nac_filter: NacFilter::new().accept(a),
+            ..config()
+        }).unwrap();
+
+        let events = recv.feed_slice(&samples);
+        let tags: Vec<&str> = events.iter().map(tag).collect();
+
+        let mut expect = vec!["sync", "nid", "tsbk", "start", "filtered", "filtered"];
+        expect.push("nid");
+        expect.extend(std::iter::repeat("vf").take(7));
+        expect.extend(&["lc", "vf", "lsd", "vf", "lost", "end"]);
+        assert_eq!(tags, expect);
+
+        let filtered: Vec<(NetworkAccessCode, DataUnit)> = events.iter()
+            .filter_map(|e| match e.kind {
+                EventKind::FrameFiltered { nac, duid } => Some((nac, duid)),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(filtered, vec![
+            (b, DataUnit::VoiceLCFrameGroup),
+            (b, DataUnit::TrunkingSignaling),
+        ]);
+
+        let mut recv = Receiver::new(ReceiverConfig {
+            nac_filter: NacFilter::new().reject(a),
+            ..config()
+        }).unwrap();
+
+        let tags: Vec<&str> = recv.feed_slice(&samples).iter().map(tag).collect();
+
+        let mut expect = vec!["sync", "filtered", "nid"];
+        expect.extend(std::iter::repeat("vf").take(7));
+        expect.extend(&["lc", "vf", "lsd", "vf", "nid", "tsbk", "start", "filtered"]);
+        expect.extend(&["lost", "end"]);
+        assert_eq!(tags, expect);
+
+        // Accepting the wildcard receives everything.
+        let mut recv = Receiver::new(ReceiverConfig {
+            nac_filter: NacFilter::new().accept(NetworkAccessCode::ReceiveAny),
+            ..config()
+        }).unwrap();
+
+        let tags: Vec<&str> = recv.feed_slice(&samples).iter().map(tag).collect();
+        assert_eq!(tags.iter().filter(|&&t| t == "filtered").count(), 0);
+        assert_eq!(tags.iter().filter(|&&t| t == "vf").count(), 18);
+    }
+
+    #[test]
+    fn test_nac_isolation() {
+        let a = NetworkAccessCode::Other(0x123);
+        let b = NetworkAccessCode::Other(0x456);
+
+        // Two systems on the same channel, received at different signal levels.
+        let mut samples = vec![0.0; 1000];
+
+        for &(nac, tg, base, level) in [
+            (a, 0x0100, 1, 1.0),
+            (b, 0x0200, 10, 0.5),
+            (a, 0x0100, 1, 1.0),
+            (b, 0x0200, 10, 0.5),
+        ].iter() {
+            let mut dibits = grant_on(nac, 0x1020, tg, tg as u32);
+            dibits.extend(VoiceEncoder::new(nac).lc_frame_group(&frames(base), &LC, tg));
+
+            samples.extend(modulate(&dibits).into_iter().map(|s| s * level));
+        }
+
+        samples.extend(std::iter::repeat(0.0).take(SAMPLE_RATE / 2));
+
+        let mut recv = Receiver::new(config()).unwrap();
+        let events = recv.feed_slice(&samples);
+
+        assert!(events.iter().all(|e| tag(e) != "err" && tag(e) != "abort"));
+        assert_eq!(recv.nacs(), vec![a, b]);
+
+        for &(nac, tg, base) in [(a, 0x0100, 1), (b, 0x0200, 10)].iter() {
+            let calls = recv.calls(nac);
+            assert_eq!(calls.len(), 1);
+            assert_eq!(calls[0].target, CallTarget::Group(TalkGroup::Other(tg)));
+            assert_eq!(calls[0].src_unit, Some(tg as u32));
+
+            let chunks: Vec<[u32; 8]> = events.iter()
+                .filter(|e| e.nac == Some(nac))
+                .filter_map(|e| match e.kind {
+                    EventKind::VoiceFrame(ref vf) => Some(vf.chunks),
+                    _ => None,
+                })
+                .collect();
+
+            assert_eq!(chunks.len(), 2 * GROUP_FRAMES);
+            assert_eq!(&chunks[..GROUP_FRAMES], &frames(base)[..]);
+            assert_eq!(&chunks[GROUP_FRAMES..], &frames(base)[..]);
+
+            let lsd: Vec<u32> = events.iter()
+                .filter(|e| e.nac == Some(nac))
+                .filter_map(|e| match e.kind {
+                    EventKind::LowSpeedData(lsd) => Some(lsd),
+                    _ => None,
+                })
+                .collect();
+
+            assert_eq!(lsd, vec![tg as u32; 2]);
+
+            let starts = events.iter()
+                .filter(|e| e.nac == Some(nac) && tag(e) == "start")
+                .count();
+
+            assert_eq!(starts, 1);
+        }
+
+        assert!(recv.site(a).is_some());
+        assert!(recv.site(NAC).is_none());
+
+        // The least recently seen NAC is dropped to make room, ending its calls.
+        let mut recv = Receiver::new(ReceiverConfig {
+            max_nacs: 1,
+            ..config()
+        }).unwrap();
+
+        let events = recv.feed_slice(&samples);
+
+        assert_eq!(recv.nacs(), vec![b]);
+        assert_eq!(recv.calls(b).len(), 1);
+        assert!(recv.calls(a).is_empty());
+
+        let ended: Vec<Option<NetworkAccessCode>> = events.iter()
+            .filter(|e| tag(e) == "end")
+            .map(|e| e.nac)
+            .collect();
+
+        assert_eq!(ended, vec![Some(a), Some(b), Some(a)]);
+    }
+
+    #[test]
+    fn test_decode_iter() {
+        let mut samples = vec![0.0; 1000];
+        samples.extend(modulate(&grant(0x1020, 0x0100, 0x123456)));
+        samples.extend(std::iter::repeat(0.0).take(1000));
+
+        // The call is still active when the samples run out.
+        let mut recv = Receiver::new(config()).unwrap();
+        let tags: Vec<&str> = recv.feed_slice(&samples).iter().map(tag).collect();
+        assert_eq!(tags, vec!["sync", "nid", "tsbk", "start"]);
+        assert_eq!(recv.calls(NAC).len(), 1);
+
+        let events: Vec<Event> = samples.iter()
+            .cloned()
+            .decode_p25_with(config()).unwrap()
+            .collect();
+        let tags: Vec<&str> = events.iter().map(tag).collect();
+        assert_eq!(tags, vec!["sync", "nid", "tsbk", "start", "lost", "end"]);
+        assert_eq!(events[5].nac, Some(NAC));
+
+        let mut iter = samples.into_iter().decode_p25();
+        assert_eq!(iter.by_ref().count(), 6);
+        assert!(iter.next().is_none());
+        assert!(iter.receiver().calls(NAC).is_empty());
+    }
+
+    /// Records a description of each handled event.
+    struct Recorder(Vec<String>);
+
+    impl MessageHandler for Recorder {
+        fn on_nac(&mut self, nac: Option<NetworkAccessCode>) {
+            self.0.push(format!("nac {:?}", nac));
+        }
+
+        fn on_sync(&mut self, locked: bool) { self.0.push(format!("sync {}", locked)); }
+
+        fn on_nid(&mut self, nid: NetworkId) {
+            self.0.push(format!("nid {:?}", nid.data_unit));
+        }
+
+        fn on_voice_header(&mut self, h: VoiceHeaderFields) {
+            self.0.push(format!("hdu {:?}", h.talk_group()));
+        }
+
+        fn on_voice_frame(&mut self, vf: VoiceFrame) {
+            self.0.push(format!("vf {:?}", vf.chunks));
+        }
+
+        fn on_link_control(&mut self, lc: LinkControlFields) {
+            self.0.push(format!("lc {:?}", lc.payload()));
+        }
+
+        fn on_crypto_control(&mut self, cc: CryptoControlFields) {
+            self.0.push(format!("cc {:?}", cc.init()));
+        }
+
+        fn on_crypto(&mut self, event: CryptoEvent) {
+            self.0.push(format!("crypto {:?}", event));
+        }
+
+        fn on_status(&mut self, event: StatusEvent) {
+            self.0.push(format!("status {:?}", event));
+        }
+
+        fn on_low_speed_data(&mut self, lsd: u32) { self.0.push(format!("lsd {}", lsd)); }
+
+        fn on_tsbk(&mut self, tsbk: TsbkFields) {
+            self.0.push(format!("tsbk {:?}", tsbk.payload()));
+        }
+
+        fn on_ambt(&mut self, ambt: Ambt) {
+            self.0.push(format!("ambt {:?}", ambt.data()));
+        }
+
+        fn on_voice_term(&mut self, lc: LinkControlFields) {
+            self.0.push(format!("term {:?}", lc.payload()));
+        }
+
+        fn on_pdu(&mut self, pdu: PduEvent) { self.0.push(format!("pdu {:?}", pdu)); }
+
+        fn on_raw(&mut self, du: DataUnit, dibits: &[Dibit]) {
+            self.0.push(format!("raw {:?} {:?}", du, dibits));
+        }
+
+        fn on_frame_aborted(&mut self) { self.0.push("abort".to_string()); }
+
+        fn on_frame_filtered(&mut self, nac: NetworkAccessCode, duid: DataUnit) {
+            self.0.push(format!("filtered {:?} {:?}", nac, duid));
+        }
+
+        fn on_call_start(&mut self, c: Call) { self.0.push(format!("start {:?}", c)); }
+
+        fn on_call_update(&mut self, c: Call) { self.0.push(format!("cont {:?}", c)); }
+
+        fn on_call_end(&mut self, c: Call) { self.0.push(format!("end {:?}", c)); }
+
+        fn on_error(&mut self, err: P25Error) { self.0.push(format!("err {:?}", err)); }
+
+        fn on_diagnostic(&mut self, diag: Diagnostic) {
+            self.0.push(format!("diag {:?}", diag));
+        }
+    }
+
+    /// Record the given event the same way as `Recorder`.
+    fn record(e: Event) -> String {
+        let mut r = Recorder(vec![]);
+
+        match e.kind {
+            EventKind::SyncAcquired => r.on_sync(true),
+            EventKind::SyncLost => r.on_sync(false),
+            EventKind::Nid(nid) => r.on_nid(nid),
+            EventKind::VoiceHeader(h) => r.on_voice_header(h),
+            EventKind::VoiceFrame(vf) => r.on_voice_frame(vf),
+            EventKind::LinkControl(lc) => r.on_link_control(lc),
+            EventKind::CryptoControl(cc) => r.on_crypto_control(cc),
+            EventKind::Crypto(event) => r.on_crypto(event),
+            EventKind::Status(event) => r.on_status(event),
+            EventKind::LowSpeedData(lsd) => r.on_low_speed_data(lsd),
+            EventKind::Tsbk(tsbk) => r.on_tsbk(tsbk),
+            EventKind::Ambt(ambt) => r.on_ambt(ambt),
+            EventKind::VoiceTerm(lc) => r.on_voice_term(lc),
+            EventKind::Pdu(pdu) => r.on_pdu(pdu),
+            EventKind::Raw(du, dibits) => r.on_raw(du, &dibits),
+            EventKind::FrameAborted => r.on_frame_aborted(),
+            EventKind::FrameFiltered { nac, duid } => r.on_frame_filtered(nac, duid),
+            EventKind::Call(CallEvent::Started(c)) => r.on_call_start(c),
+            EventKind::Call(CallEvent::Continued(c)) => r.on_call_update(c),
+            EventKind::Call(CallEvent::Ended(c)) => r.on_call_end(c),
+            EventKind::Error(err) => r.on_error(err),
+            EventKind::Diagnostic(diag) => r.on_diagnostic(diag),
+        }
+
+        r.0.pop().unwrap()
+    }
+
+    #[test]
+    fn test_handler() {
+        let samples = call();
+
+        let mut recv = Receiver::new(config()).unwrap();
+        let mut polled = vec![];
+        let mut nac = None;
+
+        for &s in samples.iter() {
+            let mut events: Vec<Event> = recv.feed(s).into_iter().collect();
+
+            while let Some(e) = recv.next_event() {
+                events.push(e);
+            }
+
+            // Polled events carry their NAC rather than announcing changes.
+            for e in events {
+                if e.nac != nac {
+                    nac = e.nac;
+                    polled.push(format!("nac {:?}", nac));
+                }
+
+                polled.push(record(e));
+            }
+        }
+
+        let mut recv = Receiver::new(config()).unwrap();
+        let mut handler = Recorder(vec![]);
+
+        for &s in samples.iter() {
+            recv.feed_with(s, &mut handler);
+        }
+
+        assert_eq!(polled.len(), 36);
+        assert_eq!(handler.0, polled);
+
+        // Handlers only need to implement what they're interested in.
+        struct Frames(usize);
+
+        impl MessageHandler for Frames {
+            fn on_voice_frame(&mut self, _: VoiceFrame) { self.0 += 1; }
+        }
+
+        let mut recv = Receiver::new(config()).unwrap();
+        let mut frames = Frames(0);
+
+        for &s in samples.iter() {
+            recv.feed_with(s, &mut frames);
+        }
+
+        assert_eq!(frames.0, 18);
+    }
+
+    #[test]
+    fn test_time_base() {
+        let samples = call();
+
+        let mut recv = Receiver::new(config()).unwrap();
+        let events = recv.feed_slice(&samples);
+
+        let count = events.len();
+        let half = events[count / 2].sample as usize;
+
+        assert!(count > 10);
+        assert!(events.iter().all(|e| e.time.is_none()));
+
+        let t0 = 1_700_000_000_000_000;
+
+        let mut recv = Receiver::new(config()).unwrap();
+        recv.anchor(0, WallTime::from_micros(t0));
+
+        let mut events = recv.feed_slice(&samples[..half]);
+
+        // The wall clock ran 200ppm fast up to the middle event.
+        let elapsed = half as i64 * 1_000_000 / SAMPLE_RATE as i64;
+        recv.anchor(half as u64, WallTime::from_micros(t0 + elapsed + elapsed / 5000));
+        events.extend(recv.feed_slice(&samples[half..]));
+
+        assert_eq!(events.len(), count);
+        assert!(events.windows(2).all(|w| w[0].sample <= w[1].sample));
+        assert!(events.iter().any(|e| e.sample >= half as u64));
+
+        // Events before the second anchor follow the nominal rate, and those after it
+        // the measured one.
+        for e in events.iter() {
+            let nominal = e.sample as i64 * 1_000_000 / SAMPLE_RATE as i64;
+            let drift = if e.sample < half as u64 { 0 } else { nominal / 5000 };
+
+            assert!((e.time.unwrap().micros() - t0 - nominal - drift).abs() <= 2);
+        }
+    }
+
+    #[test]
+    fn test_afc() {
+        use baseband::afc::Afc;
+        use testutil;
+
+        // Outer symbols at ±0.18, and a 300Hz offset.
+        let scale = 0.18 / 1800.0;
+        let samples: Vec<f32> = testutil::modulate(&testutil::voice_call(NAC, 1, 2, 90))
+            .iter()
+            .map(|&s| s + 300.0 * scale)
+            .collect();
+
+        for &correct in [false, true].iter() {
+            let mut recv = Receiver::new(config()).unwrap()
+                .with_afc(Afc::new(scale).with_correction(correct));
+
+            let events = recv.feed_slice(&samples);
+            testutil::assert_clean(&events);
+            assert_eq!(testutil::voice_frames(&events).len(), 90);
+
+            let afc = *recv.afc().unwrap();
+            assert!(afc.locked());
+            assert!((afc.correction_hz() - 300.0).abs() < 20.0);
+
+            // Retuning starts over.
+            let state = recv.snapshot();
+            recv.restore(&state);
+            assert!(!recv.afc().unwrap().locked());
+            assert_eq!(recv.afc().unwrap().correction_hz(), 0.0);
+        }
+    }
+
+    #[test]
+    fn test_clock() {
+        use baseband::clock::ClockTracker;
+        use testutil;
+
+        let samples = testutil::modulate(&testutil::voice_call(NAC, 1, 2, 90));
+
+        // Resample as if by a sound card clock running 80ppm fast.
+        let ratio = 1.0 + 80.0e-6;
+        let fast: Vec<f32> = (0..)
+            .map(|n| n as f64 / ratio)
+            .take_while(|&t| t < (samples.len() - 1) as f64)
+            .map(|t| {
+                let (idx, frac) = (t as usize, (t - t.floor()) as f32);
+                samples[idx] + (samples[idx + 1] - samples[idx]) * frac
+            })
+            .collect();
+
+        for (input, ppm) in [(&samples, 0.0), (&fast, 80.0)].iter().cloned() {
+            let mut recv = Receiver::new(config()).unwrap().with_clock(ClockTracker::new());
+
+            let events = recv.feed_slice(input);
+            testutil::assert_clean(&events);
+            assert_eq!(testutil::voice_frames(&events).len(), 90);
+
+            let est = recv.clock().unwrap().ppm();
+            assert!((est - ppm).abs() < 5.0, "{} {}", est, ppm);
+
+            // Retuning starts over.
+            let state = recv.snapshot();
+            recv.restore(&state);
+            assert_eq!(recv.clock().unwrap().ppm(), 0.0);
+        }
+    }
+
+    #[test]
+    fn test_modulation() {
+        use testutil;
+
+        let dibits = testutil::voice_call(NAC, 1, 2, 18);
+        let lsm = testutil::cqpsk(&dibits, 6.1, 100.0);
+
+        let mut recv = Receiver::new(config()).unwrap().with_modulation(Modulation::Cqpsk);
+        let events = recv.feed_iq_slice(&lsm);
+        testutil::assert_clean(&events);
+        assert_eq!(testutil::voice_frames(&events).len(), 18);
+        assert_eq!(recv.modulation(), Some(Modulation::Cqpsk));
+
+        let mut recv = Receiver::new(config()).unwrap().with_modulation(Modulation::Auto);
+        assert_eq!(recv.modulation(), None);
+
+        let mut events = vec![];
+
+        for s in lsm.chunks(2) {
+            events.extend(recv.feed_iq(s[0], s[1]));
+        }
+
+        assert_eq!(testutil::voice_frames(&events).len(), 18);
+        assert_eq!(recv.modulation(), Some(Modulation::Cqpsk));
+
+        // Retuning decides again.
+        let state = recv.snapshot();
+        recv.restore(&state);
+        assert_eq!(recv.modulation(), None);
+
+        // C4FM by default.
+        let c4fm = testutil::fm(&testutil::modulate(&dibits), 1, 0.5);
+        let events = Receiver::new(config()).unwrap().feed_iq_slice(&c4fm);
+        assert_eq!(testutil::voice_frames(&events).len(), 18);
+    }
+
+    #[test]
+    fn test_sync_threshold() {
+        use testutil;
+
+        let samples = testutil::modulate(&testutil::voice_call(NAC, 1, 2, 18));
+        let units = |recv: &mut Receiver| testutil::data_units(&recv.feed_slice(&samples));
+
+        let mut recv = Receiver::new(config()).unwrap();
+        assert_eq!(recv.sync_threshold(), 0.65);
+        let expected = units(&mut recv);
+        assert_eq!(expected.len(), 4);
+
+        // No sync correlates that far above the signal power.
+        let mut recv = Receiver::new(ReceiverConfig {
+            sync_threshold: 2.0,
+            ..config()
+        }).unwrap();
+        assert!(units(&mut recv).is_empty());
+
+        // Changing the threshold between transmissions takes effect right away.
+        recv.set_sync_threshold(0.5);
+        assert_eq!(units(&mut recv), expected);
+    }
+
+    #[test]
+    fn test_sync_errors() {
+        use testutil;
+        use message::data_unit::MAX_SYNC_ERRORS;
+
+        // A burst that matches the frame sync in all but three symbols correlates well
+        // above the threshold, followed by noise and then a real transmission.
+        let mut burst: Vec<Dibit> = Dibits::new(SYNC_GENERATOR.iter().cloned()).collect();
+
+        for &idx in [3, 9, 20].iter() {
+            burst[idx] = Dibit::new(burst[idx].bits() ^ 0b10);
+        }
+
+        let mut rng = testutil::Rng::new(524);
+
+        for _ in 0..200 {
+            burst.push(Dibit::new(rng.next() as u8 & 0b11));
+        }
+
+        let mut samples = testutil::modulate(&burst);
+        samples.extend(testutil::modulate(&testutil::group_grant(NAC, 1, 2, 3)));
+
+        let diagnose = || ReceiverConfig {
+            diagnostics: Verbosity::All,
+            ..config()
+        };
+
+        let syncs = |events: &[Event]| -> Vec<usize> {
+            events.iter().filter_map(|e| match e.kind {
+                EventKind::Diagnostic(Diagnostic::SyncDetected { errors, .. }) =>
+                    Some(errors),
+                _ => None,
+            }).collect()
+        };
+
+        // Rejected by default, leaving only the real sync, with no symbol errors.
+        let mut recv = Receiver::new(diagnose()).unwrap();
+        assert_eq!(recv.max_sync_errors(), MAX_SYNC_ERRORS);
+
+        let events = recv.feed_slice(&samples);
+        assert_eq!(syncs(&events), vec![0]);
+        assert!(!testutil::tags(&events).contains(&"error"));
+        assert_eq!(testutil::data_units(&events), vec![DataUnit::TrunkingSignaling]);
+
+        // Accepted if that many errors are allowed, then failing to decode.
+        let events = Receiver::new(diagnose()).unwrap().with_max_sync_errors(3).feed_slice(&samples);
+        assert_eq!(syncs(&events), vec![3, 0]);
+        assert!(testutil::tags(&events).contains(&"error"));
+    }
+
+    #[test]
+    fn test_snr() {
+        use testutil;
+
+        let samples = testutil::modulate(&testutil::voice_call(NAC, 1, 2, 18));
+
+        let mut recv = Receiver::new(config()).unwrap();
+        assert_eq!(recv.snr(), -MAX_SNR);
+        recv.feed_slice(&samples);
+        let clean = recv.snr();
+        assert!(clean > 20.0, "{}", clean);
+
+        let mut rng = testutil::Rng::new(510);
+        let noisy: Vec<f32> = samples.iter().map(|&s| s + rng.gaussian(0.03)).collect();
+
+        let mut recv = Receiver::new(config()).unwrap();
+        recv.feed_slice(&noisy);
+        assert!(recv.snr() < clean - 3.0, "{} {}", recv.snr(), clean);
+    }
+
+    #[test]
+    fn test_inverted() {
+        use testutil;
+
+        let samples = testutil::modulate(&testutil::voice_call(NAC, 1, 2, 36));
+        let expected = testutil::decode(&samples);
+
+        let inverted: Vec<f32> = samples.iter().map(|&s| -s).collect();
+        let mut recv = Receiver::new(ReceiverConfig {
+            diagnostics: Verbosity::All,
+            ..config()
+        }).unwrap();
+
+        let events = recv.feed_slice(&inverted);
+
+        assert_eq!(testutil::data_units(&events), testutil::data_units(&expected));
+        assert_eq!(testutil::voice_frames(&events), testutil::voice_frames(&expected));
+        assert_eq!(recv.polarity(), Polarity::Inverted);
+
+        for e in events.iter() {
+            if let EventKind::Diagnostic(Diagnostic::SyncDetected { polarity, symbols, .. })
+                = e.kind
+            {
+                assert_eq!(polarity, Polarity::Inverted);
+                assert!(symbols.0 > 0.0 && symbols.2 < 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mi_discontinuity() {
+        use testutil;
+        use voice::crypto::{next_mi, CryptoEvent};
+        use voice::transmit::Transmitter;
+
+        let mi = [0xDE, 0xAD, 0xBE, 0xEF, 0x01, 0x23, 0x45, 0x67, 0x00];
+
+        // An AES call of 4 superframes.
+        let call = |mi: [u8; 9]| {
+            let mut hdr = [0; 15];
+            hdr[..9].copy_from_slice(&mi);
+            hdr[10] = 0x84;
+
+            let mut tx = Transmitter::new(NAC, VoiceHeaderFields::new(hdr),
+                                          LinkControlFields::new([0; 9]));
+            let mut dibits = vec![];
+
+            for n in 0..8 * GROUP_FRAMES {
+                dibits.extend(tx.push(&testutil::chunks(n as u32)));
+            }
+
+            dibits.extend(tx.finish());
+            dibits
+        };
+
+        let head = VoiceEncoder::new(NAC).header(&[0; 15]).len();
+        let ldu = VoiceEncoder::new(NAC).lc_frame_group(&[[0; 8]; GROUP_FRAMES], &[0; 9], 0)
+            .len();
+
+        let decode = |dibits: &[Dibit]| {
+            let mut recv = Receiver::new(config()).unwrap();
+            let events = recv.feed_slice(&testutil::modulate(dibits));
+
+            let crypto: Vec<CryptoEvent> = events.iter()
+                .filter_map(|e| match e.kind {
+                    EventKind::Crypto(c) => Some(c),
+                    _ => None,
+                })
+                .collect();
+
+            (crypto, recv.mi_discontinuities(NAC).unwrap())
+        };
+
+        assert_eq!(decode(&call(mi)), (vec![], 0));
+
+        // Missing the second superframe is bridged by the prediction.
+        let mut dibits = call(mi);
+        dibits.drain(head + 2 * ldu..head + 4 * ldu);
+        assert_eq!(decode(&dibits), (vec![], 0));
+
+        // Continuing with the superframes of another call isn't.
+        let other = [1, 2, 3, 4, 5, 6, 7, 8, 0];
+        let mut dibits = call(mi)[..head + 2 * ldu].to_vec();
+        dibits.extend(&call(other)[head..]);
+
+        assert_eq!(decode(&dibits), (vec![CryptoEvent::MiDiscontinuity {
+            expected: next_mi(&next_mi(&mi)),
+            received: next_mi(&other),
+        }], 1));
+    }
+
+    #[test]
+    fn test_stats() {
+        use testutil;
+        use trunking::encode::code_block;
+        use voice::transmit::Transmitter;
+
+        // A grant with one NID bit error.
+        let mut dibits = testutil::group_grant(NAC, 0x0042, 0x0123, 0x123456);
+        dibits[24] = Dibit::new(dibits[24].bits() ^ 0b10);
+
+        // A clear call and an encrypted call.
+        dibits.extend(testutil::voice_call(NAC, 0x0123, 0x123456, 2 * GROUP_FRAMES));
+
+        let mut hdr = [0; 15];
+        hdr[10] = 0x84;
+        let mut tx = Transmitter::new(NAC, VoiceHeaderFields::new(hdr),
+                                      LinkControlFields::new([0; 9]));
+
+        for n in 0..GROUP_FRAMES {
+            dibits.extend(tx.push(&testutil::chunks(n as u32)));
+        }
+
+        dibits.extend(tx.finish());
+
+        // A TSBK with a bad CRC, followed by the last one of the TSDU.
+        let tsbk = TsbkFields::build(0, MFID_STANDARD, &[0; 8]);
+
+        let mut buf = [0; 12];
+        buf.copy_from_slice(tsbk.bytes());
+        buf[11] ^= 1;
+
+        let mut payload = code_block(&TsbkFields::new(buf)).to_vec();
+        payload.extend(code_block(&tsbk.with_tail(true)).iter().cloned());
+
+        dibits.extend(testutil::frame(NAC, DataUnit::TrunkingSignaling, payload));
+
+        let mut recv = Receiver::new(config()).unwrap();
+        recv.feed_slice(&testutil::modulate(&dibits));
+        recv.finish();
+
+        let stats = recv.stats().snapshot();
+
+        assert_eq!(stats.syncs, 10);
+        assert_eq!(stats.syncs_lost, 1);
+        assert_eq!(stats.total_frames(), 10);
+        assert_eq!(stats.frames(DataUnit::TrunkingSignaling), 2);
+        assert_eq!(stats.frames(DataUnit::VoiceHeader), 2);
+        assert_eq!(stats.frames(DataUnit::VoiceLCFrameGroup), 2);
+        assert_eq!(stats.frames(DataUnit::VoiceCCFrameGroup), 2);
+        assert_eq!(stats.frames(DataUnit::VoiceLCTerminator), 2);
+        assert_eq!(stats.nid_fixed, 1);
+        assert_eq!(stats.total_fixed(), 1);
+        assert_eq!(stats.crc_failures, 1);
+        assert_eq!(stats.calls, 2);
+        assert_eq!(stats.encrypted_calls, 1);
+        assert_eq!(stats.pdus, 0);
+        assert_eq!(stats.data_bytes, 0);
+
+        assert_eq!(stats.to_string(), "syncs 10 (lost 1), frames 10 [hdu 2 ldu1 2 ldu2 2 \
+                                       tdu 0 tdulc 2 tsdu 2 pdu 0], fixed 1 [nid 1 golay 0 \
+                                       hamming 0 cyclic 0 rs 0 trellis 0], crc failures 1, \
+                                       calls 2 (1 encrypted), pdus 0 (0 bytes)");
+
+        recv.reset_stats();
+        assert_eq!(*recv.stats(), ReceiverStats::new());
+        assert!(stats != ReceiverStats::new());
+    }
+
+    #[test]
+    fn test_ambt() {
+        use testutil;
+        use trunking::decode::AmbtMessage;
+
+        // Authentication demand, which has no TSBK form, and a network status broadcast,
+        // which does.
+        let mut data = [0; 32];
+        (&mut data[..22]).copy_from_slice(&[
+            0xBE, 0xE0, 0x03, 0xA5, 0x01, 0x02, 0x03,
+            0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19,
+            0x20, 0x21, 0x22, 0x23, 0x24,
+        ]);
+
+        let mut dibits = testutil::ambt(NAC, [
+            0b0_1_1_10111, 0b11_111101, 0x00, 0x01, 0x02, 0x03, 0b1_0000011, 0b00_110001,
+            0x00, 0x00, 0x00, 0x00,
+        ], &data);
+
+        dibits.extend(testutil::ambt(NAC, [
+            0b0_1_1_10111, 0b11_111101, 0x00, 0xFF, 0xFF, 0xFF, 0b1_0000001, 0b00_111011,
+            0x00, 0xBE, 0x00, 0x00,
+        ], &[0xEF, 0x12, 0x34, 0x50, 0x00, 0x70, 0x00, 0x00]));
+
+        let events = testutil::decode(&testutil::modulate(&dibits));
+        testutil::assert_sequence(&events, &["sync_acquired", "nid", "ambt", "nid", "tsbk"]);
+
+        let ambt = events.iter()
+            .filter_map(|e| match e.kind {
+                EventKind::Ambt(ref ambt) => Some(ambt),
+                _ => None,
+            })
+            .next().unwrap();
+
+        match AmbtMessage::new(ambt) {
+            AmbtMessage::AuthDemand(d) => {
+                assert_eq!(d.dest_unit(), 0x010203);
+                assert_eq!(d.challenge(), &[0x20, 0x21, 0x22, 0x23, 0x24]);
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_status() {
+        let voice = VoiceEncoder::new(NAC);
+
+        let mut dibits = voice.header(&[0; 15]);
+        let ldu = voice.lc_frame_group(&frames(1), &LC, 0);
+
+        // The repeater signals busy from halfway through the frame group, with one
+        // corrupted status symbol just after.
+        let statuses: Vec<usize> = (0..ldu.len()).filter(|i| (i + 1) % 36 == 0).collect();
+        let half = statuses[statuses.len() / 2];
+
+        let ldu: Vec<Dibit> = ldu.iter().enumerate().map(|(i, &d)| {
+            if i == statuses[statuses.len() / 2 + 1] {
+                StatusCode::InboundIdle.to_dibit()
+            } else if i >= half && (i + 1) % 36 == 0 {
+                StatusCode::InboundBusy.to_dibit()
+            } else {
+                d
+            }
+        }).collect();
+
+        // The debounced run starts after the corrupted symbol.
+        let start = 1000 + (dibits.len() + statuses[statuses.len() / 2 + 2]) *
+            SYMBOL_PERIOD;
+        dibits.extend(ldu);
+
+        let samples = transmit(&dibits);
+
+        let mut recv = Receiver::new(ReceiverConfig {
+            status_debounce: 3,
+            ..config()
+        }).unwrap();
+
+        let events: Vec<(u64, StatusEvent)> = recv.feed_slice(&samples).into_iter()
+            .filter_map(|e| match e.kind {
+                EventKind::Status(s) => Some((e.sample, s)),
+                _ => None,
+            })
+            .collect();
+
+        // Each change is reported at the third symbol of its run, timestamped with the
+        // first.
+        let run = 2 * 36 * SYMBOL_PERIOD as u64;
+
+        match events[..] {
+            [(t0, StatusEvent::InboundIdle(s0)), (t1, StatusEvent::InboundBusy(s1))] => {
+                assert!(t0 - s0 >= run - 2 && t0 - s0 <= run + 2);
+                assert!(t1 - s1 >= run - 2 && t1 - s1 <= run + 2);
+                assert!(s1 >= start as u64 && s1 < (start + 2 * SYMBOL_PERIOD) as u64);
+            },
+            _ => panic!("{:?}", events),
+        }
+
+        // Losing sync in the silence after forgets the state.
+        assert_eq!(recv.inbound_status(), Some(ChannelStatus::Unknown));
+        assert_eq!(Receiver::new(config()).unwrap().inbound_status(), None);
+    }
+}
@@ -0,0 +1,404 @@
+//! Replay recorded captures through the full receiver, for regression tests and bug
+//! reports.
+//!
+//! A capture is decoded at full speed into a `Transcript` of every event, written as
+//! JSON lines by `JsonLinesSink`, so the same recording always produces the same
+//! transcript and can be compared against a stored golden copy.
+
+use std;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+
+use consts::SAMPLE_RATE;
+use error::P25Error;
+use receiver::{Receiver, ReceiverConfig};
+use sink::JsonLinesSink;
+use source::SampleReader;
+
+/// Environment variable that, when set, makes `Transcript::check_golden` overwrite the
+/// golden file instead of comparing against it.
+pub const BLESS_VAR: &str = "P25_BLESS";
+
+/// Errors that may occur when replaying a capture.
+#[derive(Debug)]
+pub enum ReplayError {
+    /// Reading the capture or golden file failed.
+    Io(io::Error),
+    /// The WAV header is malformed or describes an unsupported encoding.
+    BadWav(&'static str),
+    /// The receiver configuration is invalid.
+    Config(P25Error),
+    /// The sample rate of the capture doesn't match the rate the receiver runs at.
+    SampleRate {
+        /// Rate of the capture, in samples per second.
+        capture: u32,
+        /// Rate of the receiver, in samples per second.
+        receiver: u32,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReplayError::Io(ref e) => write!(f, "{}", e),
+            ReplayError::BadWav(reason) => write!(f, "bad WAV file: {}", reason),
+            ReplayError::Config(ref e) => write!(f, "{}", e),
+            ReplayError::SampleRate { capture, receiver } =>
+                write!(f, "capture sample rate {} Hz doesn't match receiver rate {} Hz",
+                       capture, receiver),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(e: io::Error) -> Self { ReplayError::Io(e) }
+}
+
+impl From<P25Error> for ReplayError {
+    fn from(e: P25Error) -> Self { ReplayError::Config(e) }
+}
+
+/// Result of replaying a capture.
+pub type Result<T> = std::result::Result<T, ReplayError>;
+
+/// Baseband samples of a recorded capture, along with the rate they were recorded at.
+pub struct Capture<R: Read> {
+    /// Samples of the capture, limited to the WAV data chunk.
+    samples: SampleReader<io::Take<R>>,
+    /// Rate of the capture, in samples per second.
+    rate: u32,
+}
+
+impl Capture<File> {
+    /// Open the capture at the given path, as a WAV file if it has a `.wav` extension
+    /// and otherwise as raw 32-bit little-endian floats recorded at the receiver's
+    /// sample rate.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Capture<File>> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+
+        let wav = path.extension()
+            .map_or(false, |ext| ext.eq_ignore_ascii_case("wav"));
+
+        if wav {
+            Capture::wav(file)
+        } else {
+            Ok(Capture::raw_f32(file, SAMPLE_RATE as u32))
+        }
+    }
+}
+
+impl<R: Read> Capture<R> {
+    /// Read a capture from the given stream of a 16-bit mono PCM WAV file.
+    pub fn wav(mut stream: R) -> Result<Capture<R>> {
+        let mut riff = [0; 12];
+        stream.read_exact(&mut riff)?;
+
+        if &riff[..4] != b"RIFF" || &riff[8..] != b"WAVE" {
+            return Err(ReplayError::BadWav("missing RIFF/WAVE header"));
+        }
+
+        let mut rate = None;
+
+        loop {
+            let mut chunk = [0; 8];
+            stream.read_exact(&mut chunk)?;
+
+            let len = le32(&chunk[4..]);
+
+            match &chunk[..4] {
+                b"fmt " => {
+                    if len < 16 {
+                        return Err(ReplayError::BadWav("short fmt chunk"));
+                    }
+
+                    let mut fmt = [0; 16];
+                    stream.read_exact(&mut fmt)?;
+                    skip(&mut stream, len as u64 - 16)?;
+
+                    if le16(&fmt[0..]) != 1 {
+                        return Err(ReplayError::BadWav("not PCM"));
+                    }
+
+                    if le16(&fmt[2..]) != 1 {
+                        return Err(ReplayError::BadWav("not mono"));
+                    }
+
+                    if le16(&fmt[14..]) != 16 {
+                        return Err(ReplayError::BadWav("not 16-bit"));
+                    }
+
+                    rate = Some(le32(&fmt[4..]));
+                },
+                b"data" => {
+                    let rate = rate.ok_or(ReplayError::BadWav("data before fmt chunk"))?;
+
+                    return Ok(Capture {
+                        samples: SampleReader::i16_le(stream.take(len as u64)),
+                        rate: rate,
+                    });
+                },
+                // Chunks are padded to an even length.
+                _ => skip(&mut stream, len as u64 + (len & 1) as u64)?,
+            }
+        }
+    }
+
+    /// Read a capture from the given stream of raw 32-bit little-endian floats, such as
+    /// those written by GNU Radio file sinks, recorded at the given rate.
+    pub fn raw_f32(stream: R, rate: u32) -> Capture<R> {
+        Capture {
+            samples: SampleReader::f32_le(stream.take(u64::MAX)),
+            rate: rate,
+        }
+    }
+
+    /// Multiply full-scale samples by the given gain, for recordings that don't use the
+    /// full range of their format.
+    pub fn with_gain(mut self, gain: f32) -> Self {
+        self.samples = self.samples.with_gain(gain);
+        self
+    }
+
+    /// Rate of the capture, in samples per second.
+    pub fn rate(&self) -> u32 { self.rate }
+
+    /// Decode every sample with a receiver using the given configuration, returning the
+    /// transcript of its events.
+    ///
+    /// The end of the capture is signalled to the receiver, so the transcript ends with
+    /// the loss of sync and the end of every active call.
+    pub fn replay(mut self, config: ReceiverConfig) -> Result<Transcript> {
+        if self.rate != config.sample_rate as u32 {
+            return Err(ReplayError::SampleRate {
+                capture: self.rate,
+                receiver: config.sample_rate as u32,
+            });
+        }
+
+        let mut recv = Receiver::new(config)?;
+        let mut sink = JsonLinesSink::new(vec![]);
+
+        for s in self.samples.by_ref() {
+            recv.feed_with(s, &mut sink);
+        }
+
+        if let Some(e) = self.samples.take_error() {
+            return Err(e.into());
+        }
+
+        recv.finish_with(&mut sink);
+
+        let bytes = sink.into_inner()?;
+
+        // The sink only ever writes valid UTF-8.
+        Ok(Transcript(String::from_utf8(bytes).unwrap()))
+    }
+}
+
+/// Decode the capture at the given path with a receiver using the given configuration,
+/// returning the transcript of its events.
+pub fn replay_file<P: AsRef<Path>>(path: P, config: ReceiverConfig) -> Result<Transcript> {
+    Capture::open(path)?.replay(config)
+}
+
+/// Events produced by replaying a capture, one JSON object per line.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Transcript(String);
+
+impl Transcript {
+    /// Text of the transcript.
+    pub fn as_str(&self) -> &str { &self.0 }
+
+    /// Number of events in the transcript.
+    pub fn events(&self) -> usize { self.0.lines().count() }
+
+    /// Compare against the given golden transcript, returning the first line that
+    /// differs, if any.
+    pub fn diff(&self, golden: &str) -> Option<Mismatch> {
+        let mut got = self.0.lines();
+        let mut expected = golden.lines();
+        let mut line = 1;
+
+        loop {
+            match (expected.next(), got.next()) {
+                (None, None) => return None,
+                (e, g) if e != g => return Some(Mismatch {
+                    line: line,
+                    expected: e.map(|s| s.to_string()),
+                    got: g.map(|s| s.to_string()),
+                }),
+                _ => line += 1,
+            }
+        }
+    }
+
+    /// Compare against the golden transcript stored at the given path, or overwrite it
+    /// if the `P25_BLESS` environment variable is set.
+    ///
+    /// Returns `Ok(None)` if the transcripts match or the golden file was written.
+    pub fn check_golden<P: AsRef<Path>>(&self, path: P) -> io::Result<Option<Mismatch>> {
+        if std::env::var_os(BLESS_VAR).is_some() {
+            fs::write(path, &self.0)?;
+            return Ok(None);
+        }
+
+        Ok(self.diff(&fs::read_to_string(path)?))
+    }
+}
+
+impl fmt::Display for Transcript {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str(&self.0) }
+}
+
+/// First line where a transcript differs from its golden copy.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Mismatch {
+    /// Line number, starting from 1.
+    pub line: usize,
+    /// Line of the golden transcript, or `None` if it ended first.
+    pub expected: Option<String>,
+    /// Line of the new transcript, or `None` if it ended first.
+    pub got: Option<String>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let show = |l: &Option<String>| l.clone().unwrap_or_else(|| "<end>".to_string());
+
+        write!(f, "transcript differs at line {}\nexpected: {}\n     got: {}",
+               self.line, show(&self.expected), show(&self.got))
+    }
+}
+
+/// Decode a 16-bit little-endian integer.
+fn le16(buf: &[u8]) -> u16 { buf[0] as u16 | (buf[1] as u16) << 8 }
+
+/// Decode a 32-bit little-endian integer.
+fn le32(buf: &[u8]) -> u32 { le16(buf) as u32 | (le16(&buf[2..]) as u32) << 16 }
+
+/// Discard the given number of bytes from the stream.
+fn skip<R: Read>(stream: &mut R, len: u64) -> io::Result<()> {
+    let skipped = io::copy(&mut stream.take(len), &mut io::sink())?;
+
+    if skipped == len {
+        Ok(())
+    } else {
+        Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated WAV chunk"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Build a WAV file with the given format fields around the given samples.
+    fn wav(format: u16, channels: u16, rate: u32, bits: u16, samples: &[i16]) -> Vec<u8> {
+        let data = samples.len() as u32 * 2;
+
+        let mut buf = vec![];
+        buf.extend(b"RIFF");
+        buf.extend(&(36 + 10 + data).to_le_bytes());
+        buf.extend(b"WAVE");
+        buf.extend(b"fmt ");
+        buf.extend(&16u32.to_le_bytes());
+        buf.extend(&format.to_le_bytes());
+        buf.extend(&channels.to_le_bytes());
+        buf.extend(&rate.to_le_bytes());
+        buf.extend(&(rate * 2).to_le_bytes());
+        buf.extend(&2u16.to_le_bytes());
+        buf.extend(&bits.to_le_bytes());
+        // Odd-length chunk that should be skipped along with its padding.
+        buf.extend(b"junk");
+        buf.extend(&1u32.to_le_bytes());
+        buf.extend(&[0xAA, 0x00]);
+        buf.extend(b"data");
+        buf.extend(&data.to_le_bytes());
+
+        for s in samples {
+            buf.extend(&s.to_le_bytes());
+        }
+
+        // Trailing chunk that isn't part of the samples.
+        buf.extend(b"LIST");
+        buf.extend(&0u32.to_le_bytes());
+
+        buf
+    }
+
+    #[test]
+    fn test_wav() {
+        let bytes = wav(1, 1, 48000, 16, &[0x4000, -0x4000, 0]);
+        let c = Capture::wav(&bytes[..]).unwrap();
+        assert_eq!(c.rate(), 48000);
+
+        let samples: Vec<f32> = c.samples.collect();
+        assert_eq!(samples, vec![0.5, -0.5, 0.0]);
+
+        let bad = |bytes: Vec<u8>| match Capture::wav(&bytes[..]) {
+            Err(ReplayError::BadWav(reason)) => reason,
+            _ => panic!(),
+        };
+
+        assert_eq!(bad(wav(3, 1, 48000, 16, &[])), "not PCM");
+        assert_eq!(bad(wav(1, 2, 48000, 16, &[])), "not mono");
+        assert_eq!(bad(wav(1, 1, 48000, 8, &[])), "not 16-bit");
+
+        let mut bytes = wav(1, 1, 48000, 16, &[]);
+        bytes[8] = b'X';
+        assert_eq!(bad(bytes), "missing RIFF/WAVE header");
+
+        match Capture::wav(&b"RIFF\0\0\0\0WAVEfmt "[..]) {
+            Err(ReplayError::Io(_)) => {},
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_sample_rate() {
+        let bytes = wav(1, 1, 44100, 16, &[0; 100]);
+
+        match Capture::wav(&bytes[..]).unwrap().replay(ReceiverConfig::default()) {
+            Err(e @ ReplayError::SampleRate { capture: 44100, receiver: 48000 }) =>
+                assert_eq!(e.to_string(),
+                           "capture sample rate 44100 Hz doesn't match receiver rate \
+                            48000 Hz"),
+            _ => panic!(),
+        }
+
+        let bytes = [0u8; 400];
+
+        match Capture::raw_f32(&bytes[..], 8000).replay(ReceiverConfig::default()) {
+            Err(ReplayError::SampleRate { capture: 8000, .. }) => {},
+            _ => panic!(),
+        }
+
+        let t = Capture::raw_f32(&bytes[..], 48000)
+            .replay(ReceiverConfig::default())
+            .unwrap();
+        assert_eq!(t.events(), 0);
+    }
+
+    #[test]
+    fn test_diff() {
+        let t = Transcript("a\nb\nc\n".to_string());
+
+        assert_eq!(t.diff("a\nb\nc\n"), None);
+        assert_eq!(t.diff("a\nx\nc\n"), Some(Mismatch {
+            line: 2,
+            expected: Some("x".to_string()),
+            got: Some("b".to_string()),
+        }));
+        assert_eq!(t.diff("a\nb\n"), Some(Mismatch {
+            line: 3,
+            expected: None,
+            got: Some("c".to_string()),
+        }));
+        assert_eq!(t.diff("a\nb\nc\nd\n").unwrap().to_string(),
+                   "transcript differs at line 4\nexpected: d\n     got: <end>");
+    }
+}
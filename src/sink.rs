@@ -0,0 +1,758 @@
+//! Write receiver events to a stream for other programs to consume.
+
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+
+use baseband::sync::Polarity;
+use bits::Dibit;
+use data::assemble::PduEvent;
+use diagnostic::Diagnostic;
+use error::P25Error;
+use message::nid::{DataUnit, NetworkAccessCode, NetworkId};
+use message::status::StatusEvent;
+use receiver::MessageHandler;
+use trunking::ambt::Ambt;
+use trunking::calls::{Call, CallTarget};
+use trunking::tsbk::TsbkFields;
+use voice::control::LinkControlFields;
+use voice::crypto::{CryptoControlFields, CryptoEvent};
+use voice::frame::VoiceFrame;
+use voice::header::VoiceHeaderFields;
+
+/// Writes each event as a JSON object on its own line.
+///
+/// Every object has a `seq` number that increases by one with each event, the `sample`
+/// index where the event occurred, the `nac` it belongs to (or `null`), and a `kind` tag,
+/// followed by fields specific to the kind. Byte payloads are written as hex strings, and
+/// coded fields such as data unit types, talkgroups, and algorithms as the numbers they
+/// have on the air. Errors and call targets are objects with a `type` tag followed by
+/// fields specific to the type.
+///
+/// Writing stops at the first error, which is returned by the next `flush`.
+pub struct JsonLinesSink<W: Write> {
+    /// Destination stream.
+    out: W,
+    /// Sequence number of the next event.
+    seq: u64,
+    /// Sample index of the following events.
+    sample: u64,
+    /// NAC of the following events.
+    nac: Option<NetworkAccessCode>,
+    /// Line being built.
+    line: String,
+    /// Error that stopped writing, if any.
+    err: Option<io::Error>,
+}
+
+impl<W: Write> JsonLinesSink<W> {
+    /// Create a new `JsonLinesSink` over the given stream.
+    pub fn new(out: W) -> JsonLinesSink<W> {
+        JsonLinesSink {
+            out: out,
+            seq: 0,
+            sample: 0,
+            nac: None,
+            line: String::new(),
+            err: None,
+        }
+    }
+
+    /// Number of events written so far.
+    pub fn events(&self) -> u64 { self.seq }
+
+    /// Flush any buffered output to the stream, returning the error that stopped
+    /// writing, if any.
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self.err.take() {
+            Some(e) => Err(e),
+            None => self.out.flush(),
+        }
+    }
+
+    /// Flush any buffered output and return the underlying stream.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.flush()?;
+        Ok(self.out)
+    }
+
+    /// Write an event of the given kind, with its specific fields added by the given
+    /// function.
+    fn event<F: FnOnce(&mut Fields)>(&mut self, kind: &str, fields: F) {
+        if self.err.is_some() {
+            return;
+        }
+
+        self.line.clear();
+
+        {
+            let mut f = Fields(&mut self.line);
+
+            f.0.push('{');
+            f.num("seq", self.seq);
+            f.num("sample", self.sample);
+
+            match self.nac {
+                Some(nac) => f.num("nac", nac.to_bits()),
+                None => f.raw("nac", "null"),
+            }
+
+            f.str("kind", kind);
+            fields(&mut f);
+            f.0.push_str("}\n");
+        }
+
+        self.seq += 1;
+
+        if let Err(e) = self.out.write_all(self.line.as_bytes()) {
+            self.err = Some(e);
+        }
+    }
+}
+
+impl<W: Write> MessageHandler for JsonLinesSink<W> {
+    fn on_sample(&mut self, sample: u64) { self.sample = sample; }
+
+    fn on_nac(&mut self, nac: Option<NetworkAccessCode>) { self.nac = nac; }
+
+    fn on_sync(&mut self, locked: bool) {
+        self.event(if locked { "sync_acquired" } else { "sync_lost" }, |_| {});
+    }
+
+    fn on_nid(&mut self, nid: NetworkId) {
+        self.event("nid", |f| f.num("data_unit", nid.data_unit.to_bits()));
+    }
+
+    fn on_voice_header(&mut self, h: VoiceHeaderFields) {
+        self.event("voice_header", |f| {
+            f.num("talk_group", h.talk_group().to_bits());
+            f.num("mfg", h.mfg());
+            f.num("crypto_alg", h.crypto_alg().to_bits());
+            f.num("crypto_key", h.crypto_key());
+            f.hex("crypto_init", h.crypto_init());
+        });
+    }
+
+    fn on_voice_frame(&mut self, vf: VoiceFrame) {
+        self.event("voice_frame", |f| {
+            f.nums("chunks", vf.chunks.iter().map(|&c| c as u64));
+            f.nums("errors", vf.errors.iter().map(|&e| e as u64));
+        });
+    }
+
+    fn on_link_control(&mut self, lc: LinkControlFields) {
+        self.event("link_control", |f| f.lc(&lc));
+    }
+
+    fn on_crypto_control(&mut self, cc: CryptoControlFields) {
+        self.event("crypto_control", |f| {
+            f.num("alg", cc.alg().to_bits());
+            f.num("key", cc.key());
+            f.hex("init", cc.init());
+        });
+    }
+
+    fn on_crypto(&mut self, event: CryptoEvent) {
+        match event {
+            CryptoEvent::MiDiscontinuity { expected, received } =>
+                self.event("mi_discontinuity", |f| {
+                    f.hex("expected", &expected);
+                    f.hex("received", &received);
+                }),
+        }
+    }
+
+    fn on_status(&mut self, event: StatusEvent) {
+        match event {
+            StatusEvent::InboundBusy(start) =>
+                self.event("inbound_busy", |f| f.num("start", start)),
+            StatusEvent::InboundIdle(start) =>
+                self.event("inbound_idle", |f| f.num("start", start)),
+        }
+    }
+
+    fn on_low_speed_data(&mut self, lsd: u32) {
+        self.event("low_speed_data", |f| f.num("data", lsd));
+    }
+
+    fn on_tsbk(&mut self, tsbk: TsbkFields) {
+        self.event("tsbk", |f| {
+            f.num("opcode", tsbk.opcode_bits());
+            f.num("mfg", tsbk.mfg());
+            f.hex("bytes", tsbk.bytes());
+        });
+    }
+
+    fn on_ambt(&mut self, ambt: Ambt) {
+        self.event("ambt", |f| {
+            f.num("opcode", ambt.header().opcode_bits());
+            f.num("mfg", ambt.header().mfg());
+            f.num("unit", ambt.header().unit());
+            f.hex("fields", ambt.header().fields());
+            f.hex("data", ambt.data());
+        });
+    }
+
+    fn on_voice_term(&mut self, lc: LinkControlFields) {
+        self.event("voice_term", |f| f.lc(&lc));
+    }
+
+    fn on_pdu(&mut self, pdu: PduEvent) {
+        self.event("pdu", |f| match pdu {
+            PduEvent::Packet { ref header, ref payload, crc_ok, .. } => {
+                f.str("result", "packet");
+                f.num("llid", header.llid);
+                f.raw("crc_ok", if crc_ok { "true" } else { "false" });
+                f.hex("payload", payload);
+            },
+            PduEvent::Partial { ref header, ref payload, .. } => {
+                f.str("result", "partial");
+                f.num("llid", header.llid);
+                f.hex("payload", payload);
+            },
+            PduEvent::BadBlock { ref header, block, err } => {
+                f.str("result", "bad_block");
+                f.num("llid", header.llid);
+                f.num("block", block as u64);
+                f.error(err);
+            },
+            PduEvent::Response(resp) => {
+                f.str("result", "response");
+                f.num("llid", resp.llid);
+                f.num("class", resp.class.to_bits());
+            },
+            PduEvent::BadHeader(err) => {
+                f.str("result", "bad_header");
+                f.error(err);
+            },
+        });
+    }
+
+    fn on_raw(&mut self, du: DataUnit, dibits: &[Dibit]) {
+        self.event("raw", |f| {
+            f.num("data_unit", du.to_bits());
+            f.dibits("dibits", dibits);
+        });
+    }
+
+    fn on_frame_aborted(&mut self) { self.event("frame_aborted", |_| {}); }
+
+    fn on_frame_filtered(&mut self, nac: NetworkAccessCode, duid: DataUnit) {
+        self.event("frame_filtered", |f| {
+            f.num("filtered_nac", nac.to_bits());
+            f.num("data_unit", duid.to_bits());
+        });
+    }
+
+    fn on_call_start(&mut self, call: Call) {
+        self.event("call_start", |f| f.call(&call));
+    }
+
+    fn on_call_update(&mut self, call: Call) {
+        self.event("call_update", |f| f.call(&call));
+    }
+
+    fn on_call_end(&mut self, call: Call) { self.event("call_end", |f| f.call(&call)); }
+
+    fn on_error(&mut self, err: P25Error) {
+        self.event("error", |f| f.error(err));
+    }
+
+    fn on_diagnostic(&mut self, diag: Diagnostic) {
+        self.event("diagnostic", |f| match diag {
+            Diagnostic::SyncDetected { power, sigpower, threshold, polarity, resync,
+                                       symbols, frequency, snr, errors } => {
+                f.str("record", "sync_detected");
+                f.float("power", power);
+                f.float("sigpower", sigpower);
+                f.float("threshold", threshold);
+                f.raw("inverted", if polarity == Polarity::Inverted { "true" } else {
+                    "false"
+                });
+                f.raw("resync", if resync { "true" } else { "false" });
+                f.float("upper", symbols.0);
+                f.float("mid", symbols.1);
+                f.float("lower", symbols.2);
+                f.float("dc", frequency.dc);
+                f.float("freq_hz", frequency.hz);
+                f.float("drift_hz", frequency.drift);
+                f.float("snr", snr);
+                f.num("errors", errors as u64);
+            },
+            Diagnostic::NidDecoded { errors } => {
+                f.str("record", "nid_decoded");
+                f.num("errors", errors as u64);
+            },
+            Diagnostic::FrameAborted { duid } => {
+                f.str("record", "frame_aborted");
+                f.duid(duid);
+            },
+            Diagnostic::FecFailure { duid, err } => {
+                f.str("record", "fec_failure");
+                f.duid(duid);
+                f.error(err);
+            },
+        });
+    }
+}
+
+/// Appends fields to a JSON object.
+struct Fields<'a>(&'a mut String);
+
+impl<'a> Fields<'a> {
+    /// Append the given key, followed by the separator for its value.
+    fn key(&mut self, key: &str) {
+        if !self.0.ends_with('{') {
+            self.0.push(',');
+        }
+
+        self.0.push('"');
+        self.0.push_str(key);
+        self.0.push_str("\":");
+    }
+
+    /// Append a field with the given JSON value, written as is.
+    fn raw(&mut self, key: &str, val: &str) {
+        self.key(key);
+        self.0.push_str(val);
+    }
+
+    /// Append a numeric field.
+    fn num<T: Into<u64>>(&mut self, key: &str, val: T) {
+        self.key(key);
+        write!(self.0, "{}", val.into()).unwrap();
+    }
+
+    /// Append a floating point field, or `null` if it isn't finite.
+    fn float(&mut self, key: &str, val: f32) {
+        self.key(key);
+
+        if val.is_finite() {
+            write!(self.0, "{}", val).unwrap();
+        } else {
+            self.0.push_str("null");
+        }
+    }
+
+    /// Append an array of numbers.
+    fn nums<I: Iterator<Item = u64>>(&mut self, key: &str, vals: I) {
+        self.key(key);
+        self.0.push('[');
+
+        for (i, v) in vals.enumerate() {
+            if i > 0 {
+                self.0.push(',');
+            }
+
+            write!(self.0, "{}", v).unwrap();
+        }
+
+        self.0.push(']');
+    }
+
+    /// Append a string field, escaping it as needed.
+    fn str(&mut self, key: &str, val: &str) {
+        self.key(key);
+        self.0.push('"');
+
+        for c in val.chars() {
+            match c {
+                '"' => self.0.push_str("\\\""),
+                '\\' => self.0.push_str("\\\\"),
+                '\n' => self.0.push_str("\\n"),
+                c if (c as u32) < 0x20 => write!(self.0, "\\u{:04x}", c as u32).unwrap(),
+                c => self.0.push(c),
+            }
+        }
+
+        self.0.push('"');
+    }
+
+    /// Append an object field with the fields added by the given function.
+    fn object<F: FnOnce(&mut Fields)>(&mut self, key: &str, fields: F) {
+        self.key(key);
+        self.0.push('{');
+        fields(self);
+        self.0.push('}');
+    }
+
+    /// Append the data unit type, or `null` if it isn't known.
+    fn duid(&mut self, duid: Option<DataUnit>) {
+        match duid {
+            Some(du) => self.num("data_unit", du.to_bits()),
+            None => self.raw("data_unit", "null"),
+        }
+    }
+
+    /// Append the given error as an object tagged with its type.
+    fn error(&mut self, err: P25Error) {
+        use error::P25Error::*;
+
+        self.object("error", |f| match err {
+            RsShortUnrecoverable => f.str("type", "rs_short"),
+            RsMediumUnrecoverable => f.str("type", "rs_medium"),
+            RsLongUnrecoverable => f.str("type", "rs_long"),
+            BchUnrecoverable => f.str("type", "bch"),
+            GolayStdUnrecoverable => f.str("type", "golay_std"),
+            GolayShortUnrecoverable => f.str("type", "golay_short"),
+            GolayExtUnrecoverable => f.str("type", "golay_ext"),
+            HammingStdUnrecoverable => f.str("type", "hamming_std"),
+            HammingShortUnrecoverable => f.str("type", "hamming_short"),
+            CyclicUnrecoverable => f.str("type", "cyclic"),
+            DibitViterbiUnrecoverable => f.str("type", "dibit_viterbi"),
+            TribitViterbiUnrecoverable => f.str("type", "tribit_viterbi"),
+            UnknownNid => f.str("type", "unknown_nid"),
+            CrcMismatch { received, computed } => {
+                f.str("type", "crc_mismatch");
+                f.num("received", received);
+                f.num("computed", computed);
+            },
+            Truncated { needed, got } => {
+                f.str("type", "truncated");
+                f.num("needed", needed as u64);
+                f.num("got", got as u64);
+            },
+            UnsupportedFormat { field, value } => {
+                f.str("type", "unsupported_format");
+                f.str("field", field);
+                f.num("value", value);
+            },
+            UnsupportedCompression { header, data } => {
+                f.str("type", "unsupported_compression");
+                f.num("header", header);
+                f.num("data", data);
+            },
+            Encrypted { alg, key } => {
+                f.str("type", "encrypted");
+                f.num("alg", alg);
+                f.num("key", key);
+            },
+            InvalidConfig { field, reason } => {
+                f.str("type", "invalid_config");
+                f.str("field", field);
+                f.str("reason", reason);
+            },
+        });
+    }
+
+    /// Append a string field with the given bytes in hex.
+    fn hex(&mut self, key: &str, bytes: &[u8]) {
+        self.key(key);
+        self.0.push('"');
+
+        for b in bytes {
+            write!(self.0, "{:02x}", b).unwrap();
+        }
+
+        self.0.push('"');
+    }
+
+    /// Append a string field with one hex digit for each of the given dibits.
+    fn dibits(&mut self, key: &str, dibits: &[Dibit]) {
+        self.key(key);
+        self.0.push('"');
+
+        for d in dibits {
+            self.0.push((b'0' + d.bits()) as char);
+        }
+
+        self.0.push('"');
+    }
+
+    /// Append the fields of the given link control word.
+    fn lc(&mut self, lc: &LinkControlFields) {
+        self.num("opcode", lc.opcode_bits());
+        self.raw("protected", if lc.protected() { "true" } else { "false" });
+        self.hex("payload", lc.payload());
+    }
+
+    /// Append the fields of the given call.
+    fn call(&mut self, call: &Call) {
+        self.num("channel", call.channel.to_bits());
+        self.object("target", |f| match call.target {
+            CallTarget::Group(tg) => {
+                f.str("type", "group");
+                f.num("talk_group", tg.to_bits());
+            },
+            CallTarget::Unit(unit) => {
+                f.str("type", "unit");
+                f.num("unit", unit);
+            },
+        });
+
+        match call.src_unit {
+            Some(src) => self.num("src_unit", src),
+            None => self.raw("src_unit", "null"),
+        }
+
+        self.raw("emergency", if call.emergency { "true" } else { "false" });
+        self.num("started", call.started);
+        self.num("updated", call.updated);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std;
+    use baseband::sync::SYNC_GENERATOR;
+    use bits::Dibits;
+    use consts::{SAMPLE_RATE, SYMBOL_PERIOD};
+    use message::status::{StatusCode, StatusInterleaver};
+    use receiver::{Receiver, ReceiverConfig};
+    use trunking::encode::Tsdu;
+    use trunking::tsbk::TsbkOpcode;
+    use trunking::vendor::MFID_STANDARD;
+    use voice::encode::VoiceEncoder;
+
+    /// Parsed JSON value.
+    #[derive(Clone, Debug, PartialEq)]
+    enum Json {
+        Null,
+        Bool(bool),
+        Num(u64),
+        Str(String),
+        Array(Vec<Json>),
+        Object(Vec<(String, Json)>),
+    }
+
+    impl Json {
+        fn get(&self, key: &str) -> &Json {
+            match *self {
+                Json::Object(ref fields) =>
+                    &fields.iter().find(|&&(ref k, _)| k == key).unwrap().1,
+                _ => panic!(),
+            }
+        }
+    }
+
+    /// Parse a JSON value from the start of the given string, returning it and the rest
+    /// of the string. Only what the sink writes is supported.
+    fn parse(s: &str) -> (Json, &str) {
+        let c = s.chars().next().unwrap();
+
+        match c {
+            'n' => (Json::Null, &s["null".len()..]),
+            't' => (Json::Bool(true), &s["true".len()..]),
+            'f' => (Json::Bool(false), &s["false".len()..]),
+            '0'..='9' => {
+                let end = s.find(|c: char| !c.is_digit(10)).unwrap_or(s.len());
+                (Json::Num(s[..end].parse().unwrap()), &s[end..])
+            },
+            '"' => {
+                let mut out = String::new();
+                let mut chars = s[1..].char_indices();
+
+                loop {
+                    match chars.next().unwrap() {
+                        (i, '"') => return (Json::Str(out), &s[i + 2..]),
+                        (_, '\\') => match chars.next().unwrap().1 {
+                            'n' => out.push('\n'),
+                            'u' => {
+                                let hex: String = chars.by_ref().take(4).map(|x| x.1)
+                                    .collect();
+                                let code = u32::from_str_radix(&hex, 16).unwrap();
+                                out.push(std::char::from_u32(code).unwrap());
+                            },
+                            c => out.push(c),
+                        },
+                        (_, c) => {
+                            assert!(c as u32 >= 0x20);
+                            out.push(c);
+                        },
+                    }
+                }
+            },
+            '[' => {
+                let mut vals = vec![];
+                let mut rest = &s[1..];
+
+                if rest.starts_with(']') {
+                    return (Json::Array(vals), &rest[1..]);
+                }
+
+                loop {
+                    let (v, r) = parse(rest);
+                    vals.push(v);
+
+                    match r.chars().next().unwrap() {
+                        ',' => rest = &r[1..],
+                        ']' => return (Json::Array(vals), &r[1..]),
+                        _ => panic!(),
+                    }
+                }
+            },
+            '{' => {
+                let mut fields = vec![];
+                let mut rest = &s[1..];
+
+                loop {
+                    let (k, r) = match parse(rest) {
+                        (Json::Str(k), r) => (k, r),
+                        _ => panic!(),
+                    };
+
+                    assert!(r.starts_with(':'));
+                    let (v, r) = parse(&r[1..]);
+                    fields.push((k, v));
+
+                    match r.chars().next().unwrap() {
+                        ',' => rest = &r[1..],
+                        '}' => return (Json::Object(fields), &r[1..]),
+                        _ => panic!(),
+                    }
+                }
+            },
+            _ => panic!(),
+        }
+    }
+
+    fn modulate(dibits: &[Dibit]) -> Vec<f32> {
+        let mut samples = vec![0.0; 1000];
+
+        for d in dibits {
+            let level = match d.bits() {
+                0b01 => 0.18,
+                0b00 => 0.02,
+                0b10 => -0.02,
+                _ => -0.18,
+            };
+
+            samples.extend(std::iter::repeat(level).take(SYMBOL_PERIOD));
+        }
+
+        samples.extend(std::iter::repeat(0.0).take(SAMPLE_RATE * 2));
+        samples
+    }
+
+    #[test]
+    fn test_call() {
+        let nac = NetworkAccessCode::Default;
+
+        let tsbk = TsbkFields::build(TsbkOpcode::GroupVoiceGrant.to_bits(), MFID_STANDARD,
+                                     &[0x00, 0x10, 0x20, 0x01, 0x00, 0x12, 0x34, 0x56]);
+        let nid = NetworkId::new(nac, DataUnit::TrunkingSignaling).encode();
+
+        let mut dibits: Vec<Dibit> = StatusInterleaver::new(
+            Dibits::new(SYNC_GENERATOR.iter().cloned())
+                .chain(Dibits::new(nid.iter().cloned()))
+                .chain(Tsdu::assemble(&[tsbk]).into_iter()),
+            StatusCode::InboundIdle).collect();
+
+        let voice = VoiceEncoder::new(nac);
+        let lc = [0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x12, 0x34, 0x56];
+
+        dibits.extend(voice.lc_frame_group(&[[7; 8]; 9], &lc, 0xBEEF));
+        dibits.extend(voice.simple_terminator());
+
+        let mut recv = Receiver::new(ReceiverConfig {
+            call_timeout: SAMPLE_RATE as u64,
+            ..ReceiverConfig::default()
+        }).unwrap();
+
+        let mut sink = JsonLinesSink::new(vec![]);
+
+        for s in modulate(&dibits) {
+            recv.feed_with(s, &mut sink);
+        }
+
+        let out = String::from_utf8(sink.into_inner().unwrap()).unwrap();
+        let lines: Vec<Json> = out.lines()
+            .map(|l| {
+                let (v, rest) = parse(l);
+                assert!(rest.is_empty());
+                v
+            })
+            .collect();
+
+        let kinds: Vec<&str> = lines.iter()
+            .map(|l| match *l.get("kind") {
+                Json::Str(ref k) => &k[..],
+                _ => panic!(),
+            })
+            .collect();
+
+        let mut expect = vec!["sync_acquired", "nid", "tsbk", "call_start", "nid"];
+        expect.extend(std::iter::repeat("voice_frame").take(7));
+        expect.extend(&["link_control", "voice_frame", "low_speed_data", "voice_frame"]);
+        expect.extend(&["nid", "sync_lost", "call_end"]);
+        assert_eq!(kinds, expect);
+
+        for (i, l) in lines.iter().enumerate() {
+            assert_eq!(*l.get("seq"), Json::Num(i as u64));
+        }
+
+        let samples: Vec<u64> = lines.iter()
+            .map(|l| match *l.get("sample") {
+                Json::Num(s) => s,
+                _ => panic!(),
+            })
+            .collect();
+
+        assert!(samples.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(*lines[0].get("nac"), Json::Null);
+        assert_eq!(*lines[1].get("nac"), Json::Num(0x293));
+
+        assert_eq!(*lines[2].get("bytes"), Json::Str(tsbk.with_tail(true).bytes().iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()));
+
+        assert_eq!(*lines[1].get("data_unit"), Json::Num(0b0111));
+        assert_eq!(*lines[3].get("src_unit"), Json::Num(0x123456));
+        assert_eq!(*lines[3].get("target"), Json::Object(vec![
+            ("type".to_string(), Json::Str("group".to_string())),
+            ("talk_group".to_string(), Json::Num(0x0100)),
+        ]));
+        assert_eq!(*lines[3].get("emergency"), Json::Bool(false));
+        assert_eq!(*lines[5].get("chunks"), Json::Array(vec![Json::Num(7); 8]));
+        assert_eq!(*lines[12].get("payload"), Json::Str("0000000100123456".to_string()));
+        assert_eq!(*lines[14].get("data"), Json::Num(0xBEEF));
+    }
+
+    /// Fails every write.
+    struct Broken;
+
+    impl Write for Broken {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::Other, "broken"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    #[test]
+    fn test_error() {
+        let mut sink = JsonLinesSink::new(Broken);
+
+        sink.on_sync(true);
+        sink.on_sync(false);
+        assert_eq!(sink.events(), 1);
+        assert!(sink.flush().is_err());
+        assert!(sink.flush().is_ok());
+    }
+
+    #[test]
+    fn test_error_fields() {
+        let mut sink = JsonLinesSink::new(vec![]);
+        sink.on_error(P25Error::CrcMismatch { received: 0xBEEF, computed: 0x1234 });
+        sink.on_error(P25Error::UnknownNid);
+
+        let out = String::from_utf8(sink.into_inner().unwrap()).unwrap();
+        let lines: Vec<Json> = out.lines().map(|l| parse(l).0).collect();
+
+        assert_eq!(*lines[0].get("error"), Json::Object(vec![
+            ("type".to_string(), Json::Str("crc_mismatch".to_string())),
+            ("received".to_string(), Json::Num(0xBEEF)),
+            ("computed".to_string(), Json::Num(0x1234)),
+        ]));
+        assert_eq!(*lines[1].get("error"), Json::Object(vec![
+            ("type".to_string(), Json::Str("unknown_nid".to_string())),
+        ]));
+    }
+
+    #[test]
+    fn test_escape() {
+        let mut line = String::from("{");
+        Fields(&mut line).str("s", "a\"b\\c\n\u{1}");
+        assert_eq!(line, "{\"s\":\"a\\\"b\\\\c\\n\\u0001\"");
+    }
+}
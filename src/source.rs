@@ -0,0 +1,262 @@
+//! Read baseband samples from raw recordings, such as those written by GNU Radio file
+//! sinks.
+//!
+//! Samples are scaled into the range of floating point samples expected by the
+//! receiver, so a reader can be passed directly to `DecodeP25::decode_p25`.
+
+use std::io::{self, BufReader, ErrorKind, Read};
+
+/// Encoding of each sample in a raw recording.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SampleFormat {
+    /// Signed 16-bit little-endian integers.
+    I16Le,
+    /// 32-bit little-endian floats.
+    F32Le,
+    /// Unsigned 8-bit integers offset by 128.
+    U8Offset,
+}
+
+impl SampleFormat {
+    /// Number of bytes in each sample.
+    pub fn width(&self) -> usize {
+        match *self {
+            SampleFormat::I16Le => 2,
+            SampleFormat::F32Le => 4,
+            SampleFormat::U8Offset => 1,
+        }
+    }
+
+    /// Scale factor that maps full-scale samples to the range [-1, 1].
+    pub fn full_scale(&self) -> f32 {
+        match *self {
+            SampleFormat::I16Le => 1.0 / 32768.0,
+            SampleFormat::F32Le => 1.0,
+            SampleFormat::U8Offset => 1.0 / 128.0,
+        }
+    }
+
+    /// Decode the sample in the given bytes, without scaling.
+    fn decode(&self, buf: &[u8]) -> f32 {
+        match *self {
+            SampleFormat::I16Le => (buf[0] as u16 | (buf[1] as u16) << 8) as i16 as f32,
+            SampleFormat::F32Le => f32::from_bits(
+                (0..4).fold(0, |x, i| x | (buf[i] as u32) << (i * 8))),
+            SampleFormat::U8Offset => buf[0] as f32 - 128.0,
+        }
+    }
+}
+
+/// Iterator over the baseband samples in a raw recording.
+///
+/// Iteration stops at the end of the stream, dropping any trailing partial sample, or at
+/// the first read error, which can then be retrieved with `error`.
+pub struct SampleReader<R: Read> {
+    /// Buffered source stream.
+    stream: BufReader<R>,
+    /// Encoding of the samples.
+    format: SampleFormat,
+    /// Factor each decoded sample is multiplied by.
+    scale: f32,
+    /// Error that stopped reading, if any.
+    err: Option<io::Error>,
+    /// Whether reading has stopped.
+    done: bool,
+}
+
+impl<R: Read> SampleReader<R> {
+    /// Create a new `SampleReader` over the given stream of samples in the given
+    /// format, scaled so full-scale samples map to [-1, 1].
+    pub fn new(stream: R, format: SampleFormat) -> SampleReader<R> {
+        SampleReader {
+            stream: BufReader::new(stream),
+            format: format,
+            scale: format.full_scale(),
+            err: None,
+            done: false,
+        }
+    }
+
+    /// Create a new `SampleReader` over signed 16-bit little-endian samples.
+    pub fn i16_le(stream: R) -> SampleReader<R> {
+        SampleReader::new(stream, SampleFormat::I16Le)
+    }
+
+    /// Create a new `SampleReader` over 32-bit little-endian float samples.
+    pub fn f32_le(stream: R) -> SampleReader<R> {
+        SampleReader::new(stream, SampleFormat::F32Le)
+    }
+
+    /// Create a new `SampleReader` over unsigned 8-bit samples offset by 128.
+    pub fn u8_offset(stream: R) -> SampleReader<R> {
+        SampleReader::new(stream, SampleFormat::U8Offset)
+    }
+
+    /// Multiply full-scale samples by the given gain, for recordings that don't use
+    /// the full range of their format.
+    pub fn with_gain(mut self, gain: f32) -> Self {
+        self.scale = self.format.full_scale() * gain;
+        self
+    }
+
+    /// Encoding of the samples.
+    pub fn format(&self) -> SampleFormat { self.format }
+
+    /// Error that stopped reading, if any.
+    pub fn error(&self) -> Option<&io::Error> { self.err.as_ref() }
+
+    /// Take the error that stopped reading, if any.
+    pub fn take_error(&mut self) -> Option<io::Error> { self.err.take() }
+}
+
+impl<R: Read> Iterator for SampleReader<R> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.done {
+            return None;
+        }
+
+        let mut buf = [0; 4];
+        let buf = &mut buf[..self.format.width()];
+
+        match self.stream.read_exact(buf) {
+            Ok(()) => Some(self.format.decode(buf) * self.scale),
+            Err(e) => {
+                self.done = true;
+
+                if e.kind() != ErrorKind::UnexpectedEof {
+                    self.err = Some(e);
+                }
+
+                None
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use bits::Dibit;
+    use consts::SYMBOL_PERIOD;
+    use message::nid::{DataUnit, NetworkAccessCode};
+    use receiver::{DecodeP25, EventKind};
+    use voice::encode::VoiceEncoder;
+
+    /// Normalized baseband samples of a voice header surrounded by silence.
+    fn signal() -> Vec<f32> {
+        let dibits: Vec<Dibit> = VoiceEncoder::new(NetworkAccessCode::Default)
+            .header(&[0; 15]);
+
+        let mut samples = vec![0.0; 1000];
+
+        for d in dibits {
+            let level = match d.bits() {
+                0b01 => 0.18,
+                0b00 => 0.02,
+                0b10 => -0.02,
+                _ => -0.18,
+            };
+
+            samples.extend(std::iter::repeat(level).take(SYMBOL_PERIOD));
+        }
+
+        samples.extend(std::iter::repeat(0.0).take(1000));
+        samples
+    }
+
+    /// Write the given bytes to a temporary file and decode it as samples in the given
+    /// format, returning the data unit of each NID.
+    fn decode(name: &str, bytes: &[u8], format: SampleFormat) -> Vec<DataUnit> {
+        let path = std::env::temp_dir()
+            .join(format!("p25-source-{}-{}", std::process::id(), name));
+
+        File::create(&path).unwrap().write_all(bytes).unwrap();
+
+        let mut reader = SampleReader::new(File::open(&path).unwrap(), format);
+
+        let nids = reader.by_ref()
+            .decode_p25()
+            .filter_map(|e| match e.kind {
+                EventKind::Nid(nid) => Some(nid.data_unit),
+                _ => None,
+            })
+            .collect();
+
+        assert!(reader.error().is_none());
+        fs::remove_file(&path).unwrap();
+
+        nids
+    }
+
+    #[test]
+    fn test_formats() {
+        let samples = signal();
+
+        let mut i16s = vec![];
+        let mut f32s = vec![];
+        let mut u8s = vec![];
+
+        for &s in samples.iter() {
+            let x = (s * 32768.0) as i16 as u16;
+            i16s.extend(&[x as u8, (x >> 8) as u8]);
+
+            let x = s.to_bits();
+            f32s.extend(&[x as u8, (x >> 8) as u8, (x >> 16) as u8, (x >> 24) as u8]);
+
+            u8s.push((s * 128.0 + 128.0) as u8);
+        }
+
+        // Trailing partial sample is dropped.
+        i16s.push(0x12);
+
+        let expect = vec![DataUnit::VoiceHeader];
+
+        assert_eq!(decode("i16", &i16s, SampleFormat::I16Le), expect);
+        assert_eq!(decode("f32", &f32s, SampleFormat::F32Le), expect);
+        assert_eq!(decode("u8", &u8s, SampleFormat::U8Offset), expect);
+    }
+
+    #[test]
+    fn test_scale() {
+        let bytes = [0x00, 0x40, 0x00, 0xC0, 0xFF];
+        let samples: Vec<f32> = SampleReader::i16_le(&bytes[..]).collect();
+        assert_eq!(samples, vec![0.5, -0.5]);
+
+        let samples: Vec<f32> = SampleReader::i16_le(&bytes[..])
+            .with_gain(2.0)
+            .collect();
+        assert_eq!(samples, vec![1.0, -1.0]);
+
+        let bytes = [0x80, 0xC0, 0x00];
+        let samples: Vec<f32> = SampleReader::u8_offset(&bytes[..]).collect();
+        assert_eq!(samples, vec![0.0, 0.5, -1.0]);
+
+        let samples: Vec<f32> = SampleReader::f32_le(&[0x00, 0x00, 0x80, 0x3F][..])
+            .collect();
+        assert_eq!(samples, vec![1.0]);
+    }
+
+    /// Fails every read.
+    struct Broken;
+
+    impl Read for Broken {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::new(ErrorKind::Other, "broken"))
+        }
+    }
+
+    #[test]
+    fn test_error() {
+        let mut reader = SampleReader::f32_le(Broken);
+        assert_eq!(reader.next(), None);
+        assert_eq!(reader.next(), None);
+        assert_eq!(reader.error().map(|e| e.kind()), Some(ErrorKind::Other));
+        assert!(reader.take_error().is_some());
+        assert!(reader.error().is_none());
+    }
+}
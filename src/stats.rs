@@ -1,6 +1,12 @@
 //! Runtime statistics.
 
+use std::fmt;
+
+use data::assemble::PduEvent;
 use error::P25Error;
+use message::nid::DataUnit;
+use message::receiver::MessageEvent;
+use voice::crypto::CryptoAlgorithm;
 
 /// Tracks stats for an error correction code.
 #[derive(Copy, Clone)]
@@ -151,9 +157,140 @@ impl Stats {
             RsMediumUnrecoverable => self.rs_med.record_err(),
             RsLongUnrecoverable => self.rs_long.record_err(),
             DibitViterbiUnrecoverable => self.viterbi_dibit.record_err(),
-            UnknownNid => {},
+            TribitViterbiUnrecoverable => self.viterbi_tribit.record_err(),
+            UnknownNid | CrcMismatch { .. } | Truncated { .. } |
+                UnsupportedFormat { .. } | UnsupportedCompression { .. } |
+                Encrypted { .. } | InvalidConfig { .. } => {},
+        }
+    }
+}
+
+/// Summary counters kept by a `Receiver` over everything it has decoded, cheap enough to
+/// poll for a dashboard.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct ReceiverStats {
+    /// Frame syncs detected.
+    pub syncs: u64,
+    /// Times frame sync was lost.
+    pub syncs_lost: u64,
+    /// Data units with a decoded NID, indexed by DUID.
+    frames: [u64; 16],
+    /// Bit errors corrected by the BCH code of the NID.
+    pub nid_fixed: u64,
+    /// Symbols corrected by the Golay codes.
+    pub golay_fixed: u64,
+    /// Symbols corrected by the Hamming codes.
+    pub hamming_fixed: u64,
+    /// Symbols corrected by the cyclic code of the low-speed data.
+    pub cyclic_fixed: u64,
+    /// Symbols corrected by the Reed-Solomon codes.
+    pub rs_fixed: u64,
+    /// Symbols corrected by the trellis codes.
+    pub trellis_fixed: u64,
+    /// Packets whose CRC didn't match.
+    pub crc_failures: u64,
+    /// Voice calls, counted at each voice header.
+    pub calls: u64,
+    /// Voice calls with an encryption algorithm.
+    pub encrypted_calls: u64,
+    /// Data packets received.
+    pub pdus: u64,
+    /// Payload bytes of the received data packets.
+    pub data_bytes: u64,
+}
+
+impl ReceiverStats {
+    /// Create a new `ReceiverStats` with all counters at zero.
+    pub fn new() -> ReceiverStats { ReceiverStats::default() }
+
+    /// Copy of the current counters.
+    pub fn snapshot(&self) -> ReceiverStats { *self }
+
+    /// Reset all counters to zero.
+    pub fn reset(&mut self) { *self = ReceiverStats::default(); }
+
+    /// Number of data units of the given type with a decoded NID.
+    pub fn frames(&self, du: DataUnit) -> u64 { self.frames[du.to_bits() as usize] }
+
+    /// Number of data units of any type with a decoded NID.
+    pub fn total_frames(&self) -> u64 { self.frames.iter().sum() }
+
+    /// Total symbols corrected by all codes.
+    pub fn total_fixed(&self) -> u64 {
+        self.nid_fixed + self.golay_fixed + self.hamming_fixed + self.cyclic_fixed +
+            self.rs_fixed + self.trellis_fixed
+    }
+
+    /// Count the given receiver event.
+    pub fn record(&mut self, event: &MessageEvent) {
+        match *event {
+            MessageEvent::PacketNID(nid) =>
+                self.frames[nid.data_unit.to_bits() as usize] += 1,
+            MessageEvent::FrameFiltered { duid, .. } =>
+                self.frames[duid.to_bits() as usize] += 1,
+            MessageEvent::VoiceHeader(ref h) => {
+                self.calls += 1;
+
+                if h.crypto_alg() != CryptoAlgorithm::Unencrypted {
+                    self.encrypted_calls += 1;
+                }
+            },
+            MessageEvent::DataPacket(ref pdu) => match *pdu {
+                PduEvent::Packet { ref payload, crc_ok, .. } => {
+                    self.pdus += 1;
+                    self.data_bytes += payload.len() as u64;
+
+                    if !crc_ok {
+                        self.crc_failures += 1;
+                    }
+                },
+                PduEvent::Partial { ref payload, .. } => {
+                    self.pdus += 1;
+                    self.data_bytes += payload.len() as u64;
+                },
+                PduEvent::BadHeader(P25Error::CrcMismatch { .. }) |
+                    PduEvent::BadBlock { err: P25Error::CrcMismatch { .. }, .. } =>
+                    self.crc_failures += 1,
+                _ => {},
+            },
+            MessageEvent::Error(P25Error::CrcMismatch { .. }) => self.crc_failures += 1,
+            _ => {},
         }
     }
+
+    /// Add the corrections counted in the given stats and clear them.
+    pub fn record_fixes(&mut self, stats: &mut Stats) {
+        let s = *stats;
+
+        self.nid_fixed += s.bch.fixed as u64;
+        self.golay_fixed += (s.golay_std.fixed + s.golay_ext.fixed + s.golay_short.fixed)
+            as u64;
+        self.hamming_fixed += (s.hamming_std.fixed + s.hamming_short.fixed) as u64;
+        self.cyclic_fixed += s.cyclic.fixed as u64;
+        self.rs_fixed += (s.rs_short.fixed + s.rs_med.fixed + s.rs_long.fixed) as u64;
+        self.trellis_fixed += (s.viterbi_dibit.fixed + s.viterbi_tribit.fixed) as u64;
+
+        stats.clear();
+    }
+}
+
+impl fmt::Display for ReceiverStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use message::nid::DataUnit::*;
+
+        write!(f, "syncs {} (lost {}), frames {} [hdu {} ldu1 {} ldu2 {} tdu {} \
+                   tdulc {} tsdu {} pdu {}], fixed {} [nid {} golay {} hamming {} \
+                   cyclic {} rs {} trellis {}], crc failures {}, calls {} \
+                   ({} encrypted), pdus {} ({} bytes)",
+               self.syncs, self.syncs_lost, self.total_frames(),
+               self.frames(VoiceHeader), self.frames(VoiceLCFrameGroup),
+               self.frames(VoiceCCFrameGroup), self.frames(VoiceSimpleTerminator),
+               self.frames(VoiceLCTerminator), self.frames(TrunkingSignaling),
+               self.frames(DataPacket), self.total_fixed(), self.nid_fixed,
+               self.golay_fixed, self.hamming_fixed, self.cyclic_fixed, self.rs_fixed,
+               self.trellis_fixed, self.crc_failures, self.calls, self.encrypted_calls,
+               self.pdus, self.data_bytes)
+    }
 }
 
 /// Indicates that a type captures statistics.
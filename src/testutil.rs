@@ -0,0 +1,484 @@
+//! Building blocks for tests that encode a signal, corrupt it, and check what the
+//! receiver decodes from it.
+//!
+//! Bug reports and downstream integration tests can usually be expressed as "encode X,
+//! corrupt bits Y, decoding should still produce Z." This module provides each step:
+//!
+//! * builders for framed data units and whole calls, such as `voice_call` and `tsdu`,
+//! * error injectors that flip bits per codeword, flip bursts of bits, and drop
+//!   samples, driven by a seeded `Rng` so failures can be reproduced,
+//! * `modulate` and `decode` to run dibits through the full receiver,
+//! * helpers that compare the decoded events against expectations, such as
+//!   `assert_sequence`.
+//!
+//! ```
+//! use p25::message::nid::{DataUnit, NetworkAccessCode};
+//! use p25::testutil::{self, Rng};
+//! use p25::voice::encode;
+//!
+//! let nac = NetworkAccessCode::Default;
+//! let mut rng = Rng::new(1);
+//!
+//! // Flip 3 bits in each 18-bit Golay word of a voice header, which are all
+//! // correctable.
+//! let mut coded = encode::header(&[0; 15]);
+//! testutil::flip_bits(&mut rng, &mut coded, 18, 3);
+//!
+//! let dibits = testutil::frame(nac, DataUnit::VoiceHeader, coded);
+//! let events = testutil::decode(&testutil::modulate(&dibits));
+//!
+//! testutil::assert_sequence(&events, &["sync_acquired", "nid", "voice_header"]);
+//! ```
+
+use std;
+
+use baseband::shaping;
+use baseband::sync::SYNC_GENERATOR;
+use bits::{Dibit, Dibits};
+use consts::{SAMPLE_RATE, SYMBOL_PERIOD};
+use message::nid::{DataUnit, NetworkAccessCode, NetworkId};
+use message::status::{StatusCode, StatusEvent, StatusInterleaver};
+use receiver::{Event, EventKind, Receiver, ReceiverConfig};
+use trunking::calls::CallEvent;
+use trunking::encode::Tsdu;
+use trunking::tsbk::{TsbkFields, TsbkOpcode};
+use trunking::vendor::MFID_STANDARD;
+use voice::control::LinkControlFields;
+use voice::crypto::CryptoEvent;
+use voice::encode::GROUP_FRAMES;
+use voice::header::VoiceHeaderFields;
+use voice::transmit::Transmitter;
+
+/// Xorshift generator, so failures can be reproduced from the seed.
+#[derive(Copy, Clone, Debug)]
+pub struct Rng(u32);
+
+impl Rng {
+    /// Create a new `Rng` with the given seed, which is replaced if zero.
+    pub fn new(seed: u32) -> Rng { Rng(if seed == 0 { 0x9E3779B9 } else { seed }) }
+
+    /// Next random 32-bit value.
+    pub fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    /// Normally distributed value with the given standard deviation.
+    pub fn gaussian(&mut self, sigma: f32) -> f32 {
+        // Map into (0, 1] so the log is finite.
+        let u = (self.next() as f32 + 1.0) / (std::u32::MAX as f32 + 1.0);
+        let v = self.next() as f32 / std::u32::MAX as f32;
+
+        sigma * (-2.0 * u.ln()).sqrt() * (2.0 * std::f32::consts::PI * v).cos()
+    }
+
+    /// Random value in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize { self.next() as usize % n }
+
+    /// Fill the given buffer with random bytes.
+    pub fn bytes(&mut self, buf: &mut [u8]) {
+        for b in buf.iter_mut() {
+            *b = self.next() as u8;
+        }
+    }
+
+    /// Random IMBE chunks `u_0`, ..., `u_7`, within the width of each coded chunk.
+    pub fn chunks(&mut self) -> [u32; 8] {
+        let mut chunks = [0; 8];
+
+        for (idx, c) in chunks.iter_mut().enumerate() {
+            *c = self.next() & match idx {
+                0..=3 => 0xFFF,
+                4..=6 => 0x7FF,
+                _ => 0x7F,
+            };
+        }
+
+        chunks
+    }
+
+    /// Random IMBE chunks for each voice frame of a frame group.
+    pub fn frames(&mut self) -> [[u32; 8]; GROUP_FRAMES] {
+        let mut frames = [[0; 8]; GROUP_FRAMES];
+
+        for f in frames.iter_mut() {
+            *f = self.chunks();
+        }
+
+        frames
+    }
+}
+
+/// IMBE chunks of the voice frame with the given sequence number, distinct for every
+/// frame of a call.
+pub fn chunks(n: u32) -> [u32; 8] {
+    [
+        (n * 0x123) & 0xFFF,
+        (n * 0x456 + 1) & 0xFFF,
+        (n * 0x789 + 2) & 0xFFF,
+        (n * 0xABC + 3) & 0xFFF,
+        (n * 0x135 + 4) & 0x7FF,
+        (n * 0x246 + 5) & 0x7FF,
+        (n * 0x357 + 6) & 0x7FF,
+        (n * 0x11 + 7) & 0x7F,
+    ]
+}
+
+/// Prefix the given coded data unit with the frame sync and NID and interleave status
+/// symbols, as sent over the air.
+pub fn frame(nac: NetworkAccessCode, du: DataUnit, payload: Vec<Dibit>) -> Vec<Dibit> {
+    let nid = NetworkId::new(nac, du).encode();
+
+    let dibits = Dibits::new(SYNC_GENERATOR.iter().cloned())
+        .chain(Dibits::new(nid.iter().cloned()))
+        .chain(payload.into_iter());
+
+    StatusInterleaver::new(dibits, StatusCode::InboundIdle).collect()
+}
+
+/// Build a TSDU carrying the given trunking signalling packets.
+pub fn tsdu(nac: NetworkAccessCode, tsbks: &[TsbkFields]) -> Vec<Dibit> {
+    frame(nac, DataUnit::TrunkingSignaling, Tsdu::assemble(tsbks))
+}
+
+/// Build a data packet carrying an AMBT with the given header and data bytes, which fill
+/// whole blocks less the packet checksum. Both checksums are filled in.
+pub fn ambt(nac: NetworkAccessCode, mut head: [u8; 12], data: &[u8]) -> Vec<Dibit> {
+    use data::coder::DibitCoder;
+    use data::crc::CRC32;
+    use data::interleave::Interleaver;
+    use trunking::ambt::AmbtHeader;
+
+    let crc = AmbtHeader::new(head).calc_crc();
+    head[10] = (crc >> 8) as u8;
+    head[11] = crc as u8;
+
+    let crc = CRC32::new().feed_bytes(data.iter().cloned()).finish() as u32;
+    let mut bytes = data.to_vec();
+    bytes.extend(&[(crc >> 24) as u8, (crc >> 16) as u8, (crc >> 8) as u8, crc as u8]);
+
+    let mut payload = vec![];
+
+    for block in Some(&head[..]).into_iter().chain(bytes.chunks(12)) {
+        payload.extend(Interleaver::new(
+            DibitCoder::new().feed_bytes(block.iter().cloned()).finish()));
+    }
+
+    frame(nac, DataUnit::DataPacket, payload)
+}
+
+/// Build a TSDU carrying a group voice grant of the given channel, talkgroup, and
+/// source unit.
+pub fn group_grant(nac: NetworkAccessCode, ch: u16, tg: u16, src: u32) -> Vec<Dibit> {
+    let tsbk = TsbkFields::build(TsbkOpcode::GroupVoiceGrant.to_bits(), MFID_STANDARD, &[
+        0x00,
+        (ch >> 8) as u8,
+        ch as u8,
+        (tg >> 8) as u8,
+        tg as u8,
+        (src >> 16) as u8,
+        (src >> 8) as u8,
+        src as u8,
+    ]);
+
+    tsdu(nac, &[tsbk])
+}
+
+/// Build an unencrypted group voice call from the given source unit to the given
+/// talkgroup, carrying the voice frames given by `chunks(0)`, `chunks(1)`, and so on.
+pub fn voice_call(nac: NetworkAccessCode, tg: u16, src: u32, frames: usize) -> Vec<Dibit> {
+    let header = VoiceHeaderFields::new([
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0x00, 0x80, 0x00, 0x00, (tg >> 8) as u8, tg as u8,
+    ]);
+
+    let lc = LinkControlFields::new([
+        0x00, 0x00, 0x00, 0x00,
+        (tg >> 8) as u8, tg as u8,
+        (src >> 16) as u8, (src >> 8) as u8, src as u8,
+    ]);
+
+    let mut tx = Transmitter::new(nac, header, lc);
+    let mut dibits = vec![];
+
+    for n in 0..frames {
+        dibits.extend(tx.push(&chunks(n as u32)));
+    }
+
+    dibits.extend(tx.finish());
+    dibits
+}
+
+/// Flip `n` distinct random bits in each consecutive codeword of the given number of
+/// bits, with the dibits taken MSB first. A trailing partial codeword is left alone.
+pub fn flip_bits(rng: &mut Rng, dibits: &mut [Dibit], codeword: usize, n: usize) {
+    assert!(n <= codeword);
+
+    for start in (0..dibits.len() * 2 / codeword).map(|c| c * codeword) {
+        let mut flipped = vec![];
+
+        while flipped.len() < n {
+            let bit = start + rng.below(codeword);
+
+            if !flipped.contains(&bit) {
+                flip(dibits, bit);
+                flipped.push(bit);
+            }
+        }
+    }
+}
+
+/// Flip a burst of `len` consecutive bits starting at the given bit, with the dibits
+/// taken MSB first.
+pub fn burst(dibits: &mut [Dibit], start: usize, len: usize) {
+    for bit in start..start + len {
+        flip(dibits, bit);
+    }
+}
+
+/// Remove `m` samples starting at the given index, as lost by an overrunning sound
+/// card.
+pub fn drop_samples(samples: &mut Vec<f32>, at: usize, m: usize) {
+    samples.drain(at..at + m);
+}
+
+/// Flip the given bit of the dibit stream.
+fn flip(dibits: &mut [Dibit], bit: usize) {
+    let d = &mut dibits[bit / 2];
+    *d = Dibit::new(d.bits() ^ if bit % 2 == 0 { 0b10 } else { 0b01 });
+}
+
+/// Modulate the given dibits into ideal baseband samples, with a short silence on
+/// either side.
+pub fn modulate(dibits: &[Dibit]) -> Vec<f32> {
+    let mut samples = vec![0.0; 1000];
+
+    for d in dibits {
+        let level = match d.bits() {
+            0b01 => 0.18,
+            0b00 => 0.02,
+            0b10 => -0.02,
+            _ => -0.18,
+        };
+
+        samples.extend(std::iter::repeat(level).take(SYMBOL_PERIOD));
+    }
+
+    samples.extend(std::iter::repeat(0.0).take(1000));
+    samples
+}
+
+/// FM modulate the given baseband samples onto interleaved IQ at the given multiple of
+/// the baseband rate, with the given amplitude, as from a C4FM transmitter, with the
+/// samples interpolated linearly.
+pub fn fm(samples: &[f32], decim: usize, amp: f32) -> Vec<f32> {
+    use baseband::fm::LEVEL_PER_HZ;
+
+    let mut iq = vec![];
+    let mut phase = 0.0f32;
+    let rate = (SAMPLE_RATE * decim) as f32;
+
+    for w in samples.windows(2) {
+        for k in 0..decim {
+            let s = w[0] + (w[1] - w[0]) * k as f32 / decim as f32;
+            phase += 2.0 * std::f32::consts::PI * s / LEVEL_PER_HZ / rate;
+            iq.extend(&[amp * phase.cos(), amp * phase.sin()]);
+        }
+    }
+
+    iq
+}
+
+/// Modulate the given dibits into interleaved IQ of an ideal CQPSK (LSM) signal, with
+/// raised cosine pulses, delayed by the given number of samples and shifted by the
+/// given carrier offset in Hz, with a short silence on either side.
+pub fn cqpsk(dibits: &[Dibit], delay: f32, offset: f32) -> Vec<f32> {
+    use std::f32::consts::PI;
+
+    let mut phase = 0.0f32;
+    let symbols: Vec<(f32, f32)> = dibits.iter().map(|d| {
+        phase += match d.bits() {
+            0b01 => 3.0 * PI / 4.0,
+            0b00 => PI / 4.0,
+            0b10 => -PI / 4.0,
+            _ => -3.0 * PI / 4.0,
+        };
+
+        (phase.cos(), phase.sin())
+    }).collect();
+
+    let len = 2000 + dibits.len() * SYMBOL_PERIOD;
+    let mut iq = Vec::with_capacity(len * 2);
+
+    for n in 0..len {
+        // Time in symbols since the center of the first.
+        let t = (n as f32 - 1000.0 - delay) / SYMBOL_PERIOD as f32;
+        let first = std::cmp::max(t.floor() as isize - 8, 0) as usize;
+        let last = std::cmp::min((t.floor() as isize + 9).max(0) as usize, symbols.len());
+
+        let (i, q) = (first..last).fold((0.0, 0.0), |(i, q), k| {
+            let p = shaping::raised_cosine((t - k as f32) as f64, shaping::ROLLOFF) as f32;
+            (i + symbols[k].0 * p, q + symbols[k].1 * p)
+        });
+
+        let rot = 2.0 * PI * offset * n as f32 / SAMPLE_RATE as f32;
+        let (sin, cos) = rot.sin_cos();
+
+        iq.extend(&[0.5 * (i * cos - q * sin), 0.5 * (i * sin + q * cos)]);
+    }
+
+    iq
+}
+
+/// Silent baseband samples lasting the given number of seconds.
+pub fn silence(secs: f32) -> Vec<f32> { vec![0.0; (secs * SAMPLE_RATE as f32) as usize] }
+
+/// Decode the given samples with a receiver using the default configuration, returning
+/// every event up to the end of the input.
+pub fn decode(samples: &[f32]) -> Vec<Event> {
+    decode_with(samples, ReceiverConfig::default())
+}
+
+/// Decode the given samples with a receiver using the given configuration, returning
+/// every event up to the end of the input.
+pub fn decode_with(samples: &[f32], config: ReceiverConfig) -> Vec<Event> {
+    let mut recv = Receiver::new(config).unwrap();
+    let mut events = recv.feed_slice(samples);
+    events.extend(recv.finish());
+    events
+}
+
+/// Name of the given kind of event, the same as the `kind` tag written by
+/// `sink::JsonLinesSink`.
+pub fn tag(kind: &EventKind) -> &'static str {
+    match *kind {
+        EventKind::SyncAcquired => "sync_acquired",
+        EventKind::SyncLost => "sync_lost",
+        EventKind::Nid(_) => "nid",
+        EventKind::VoiceHeader(_) => "voice_header",
+        EventKind::VoiceFrame(_) => "voice_frame",
+        EventKind::LinkControl(_) => "link_control",
+        EventKind::CryptoControl(_) => "crypto_control",
+        EventKind::Crypto(CryptoEvent::MiDiscontinuity { .. }) => "mi_discontinuity",
+        EventKind::Status(StatusEvent::InboundBusy(_)) => "inbound_busy",
+        EventKind::Status(StatusEvent::InboundIdle(_)) => "inbound_idle",
+        EventKind::LowSpeedData(_) => "low_speed_data",
+        EventKind::Tsbk(_) => "tsbk",
+        EventKind::Ambt(_) => "ambt",
+        EventKind::VoiceTerm(_) => "voice_term",
+        EventKind::Pdu(_) => "pdu",
+        EventKind::Raw(..) => "raw",
+        EventKind::FrameAborted => "frame_aborted",
+        EventKind::FrameFiltered { .. } => "frame_filtered",
+        EventKind::Call(CallEvent::Started(_)) => "call_start",
+        EventKind::Call(CallEvent::Continued(_)) => "call_update",
+        EventKind::Call(CallEvent::Ended(_)) => "call_end",
+        EventKind::Error(_) => "error",
+        EventKind::Diagnostic(_) => "diagnostic",
+    }
+}
+
+/// Names of the given events, in order.
+pub fn tags(events: &[Event]) -> Vec<&'static str> {
+    events.iter().map(|e| tag(&e.kind)).collect()
+}
+
+/// Type of each data unit whose NID was decoded, in order.
+pub fn data_units(events: &[Event]) -> Vec<DataUnit> {
+    events.iter()
+        .filter_map(|e| match e.kind {
+            EventKind::Nid(nid) => Some(nid.data_unit),
+            _ => None,
+        })
+        .collect()
+}
+
+/// IMBE chunks of each decoded voice frame, in order.
+pub fn voice_frames(events: &[Event]) -> Vec<[u32; 8]> {
+    events.iter()
+        .filter_map(|e| match e.kind {
+            EventKind::VoiceFrame(ref vf) => Some(vf.chunks),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Check that events with the given names occur in the given order, possibly with
+/// other events in between, panicking with the names of every event if not.
+pub fn assert_sequence(events: &[Event], expected: &[&str]) {
+    let tags = tags(events);
+    let mut rest = tags.iter();
+
+    for name in expected {
+        if !rest.any(|t| t == name) {
+            panic!("expected events {:?} in order, got {:?}", expected, tags);
+        }
+    }
+}
+
+/// Check that no event is an error or a dropped or aborted data unit, panicking with
+/// the names of every event if not.
+pub fn assert_clean(events: &[Event]) {
+    let clean = events.iter().all(|e| match e.kind {
+        EventKind::Error(_) | EventKind::FrameAborted => false,
+        _ => true,
+    });
+
+    if !clean {
+        panic!("expected no errors, got {:?}", tags(events));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_flip_bits() {
+        let mut rng = Rng::new(7);
+        let mut dibits = vec![Dibit::new(0); 20];
+
+        // Two 16-bit codewords, with the last 8 bits left alone.
+        flip_bits(&mut rng, &mut dibits, 16, 3);
+
+        let ones = |d: &[Dibit]| d.iter().map(|d| d.bits().count_ones()).sum::<u32>();
+
+        assert_eq!(ones(&dibits[..8]), 3);
+        assert_eq!(ones(&dibits[8..16]), 3);
+        assert_eq!(ones(&dibits[16..]), 0);
+    }
+
+    #[test]
+    fn test_burst() {
+        let mut dibits = vec![Dibit::new(0); 4];
+        burst(&mut dibits, 1, 4);
+
+        let bits: Vec<u8> = dibits.iter().map(|d| d.bits()).collect();
+        assert_eq!(bits, vec![0b01, 0b11, 0b10, 0b00]);
+    }
+
+    #[test]
+    fn test_voice_call() {
+        let nac = NetworkAccessCode::Default;
+        let events = decode(&modulate(&voice_call(nac, 0x0123, 0x123456, 18)));
+
+        assert_clean(&events);
+        assert_eq!(data_units(&events), vec![
+            DataUnit::VoiceHeader,
+            DataUnit::VoiceLCFrameGroup,
+            DataUnit::VoiceCCFrameGroup,
+            DataUnit::VoiceLCTerminator,
+        ]);
+        assert_eq!(voice_frames(&events), (0..18).map(chunks).collect::<Vec<_>>());
+        assert_sequence(&events, &["sync_acquired", "voice_header", "voice_term",
+                                   "sync_lost"]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_assert_sequence() {
+        let events = decode(&modulate(&group_grant(NetworkAccessCode::Default, 1, 2, 3)));
+        assert_sequence(&events, &["tsbk", "nid"]);
+    }
+}
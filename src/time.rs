@@ -0,0 +1,203 @@
+//! Derive wall-clock timestamps from sample indices.
+//!
+//! The receiver counts time in samples, so the application anchors sample indices to
+//! wall-clock times whenever it has a trustworthy correspondence, such as the system
+//! time at the start of a stream. Between and beyond anchors, times follow the sample
+//! clock rate measured across the last two anchors, which corrects for drift between the
+//! sample clock and the wall clock.
+
+#[cfg(feature = "std")]
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use consts::SAMPLE_RATE;
+
+/// Number of microseconds in a second.
+const MICROS: i128 = 1_000_000;
+
+/// Largest sample clock error, in parts per million, accepted between anchors. Anchors
+/// implying a larger error are taken as a step in wall-clock time, such as from the
+/// clock being set, rather than drift.
+pub const MAX_DRIFT_PPM: i128 = 500;
+
+/// Wall-clock time, in microseconds since the UNIX epoch.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct WallTime(i64);
+
+impl WallTime {
+    /// Create a new `WallTime` at the given number of microseconds since the UNIX epoch.
+    pub fn from_micros(micros: i64) -> WallTime { WallTime(micros) }
+
+    /// Number of microseconds since the UNIX epoch.
+    pub fn micros(&self) -> i64 { self.0 }
+
+    /// Convert to a system time.
+    #[cfg(feature = "std")]
+    pub fn to_system(&self) -> SystemTime {
+        let d = Duration::from_micros(self.0.unsigned_abs());
+        if self.0 < 0 { UNIX_EPOCH - d } else { UNIX_EPOCH + d }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<SystemTime> for WallTime {
+    fn from(t: SystemTime) -> WallTime {
+        match t.duration_since(UNIX_EPOCH) {
+            Ok(d) => WallTime(d.as_micros() as i64),
+            Err(e) => WallTime(-(e.duration().as_micros() as i64)),
+        }
+    }
+}
+
+/// Correspondence between a sample index and a wall-clock time.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+struct Anchor {
+    /// Sample index.
+    sample: u64,
+    /// Wall-clock time of the sample.
+    time: WallTime,
+}
+
+/// Maps sample indices to wall-clock times from the anchors given so far.
+///
+/// With no anchors, no times are derived. With one anchor, times follow the nominal
+/// sample rate from it. Once a later anchor is given, times follow the line through the
+/// last two anchors, interpolating between them and extrapolating beyond them.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct TimeBase {
+    /// Anchor before the last, if it's consistent with the last one.
+    prev: Option<Anchor>,
+    /// Most recent anchor.
+    last: Option<Anchor>,
+}
+
+impl TimeBase {
+    /// Create a new `TimeBase` with no anchors.
+    pub fn new() -> TimeBase { TimeBase::default() }
+
+    /// Anchor the given sample index to the given wall-clock time.
+    ///
+    /// An anchor that doesn't come after the previous one, or that implies a sample
+    /// clock error beyond `MAX_DRIFT_PPM`, restarts timing from the nominal rate.
+    pub fn anchor(&mut self, sample: u64, time: WallTime) {
+        let next = Anchor {
+            sample: sample,
+            time: time,
+        };
+
+        self.prev = match self.last {
+            Some(last) if sample > last.sample && consistent(last, next) => Some(last),
+            _ => None,
+        };
+
+        self.last = Some(next);
+    }
+
+    /// Whether any anchor was given, so times can be derived.
+    pub fn anchored(&self) -> bool { self.last.is_some() }
+
+    /// Wall-clock time of the given sample index, if any anchor was given.
+    pub fn time(&self, sample: u64) -> Option<WallTime> {
+        let last = match self.last {
+            Some(last) => last,
+            None => return None,
+        };
+
+        let (base, dt, ds) = match self.prev {
+            Some(prev) => (prev, (last.time.0 - prev.time.0) as i128,
+                           (last.sample - prev.sample) as i128),
+            None => (last, MICROS, SAMPLE_RATE as i128),
+        };
+
+        let offset = (sample as i128 - base.sample as i128) * dt / ds;
+
+        Some(WallTime(base.time.0 + offset as i64))
+    }
+}
+
+/// Whether the time between the given anchors matches the nominal sample rate within
+/// the maximum drift.
+fn consistent(a: Anchor, b: Anchor) -> bool {
+    let nominal = (b.sample - a.sample) as i128 * MICROS / SAMPLE_RATE as i128;
+    let elapsed = (b.time.0 - a.time.0) as i128;
+
+    (elapsed - nominal).abs() * MICROS <= nominal * MAX_DRIFT_PPM
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Start of the test timeline, in microseconds.
+    const T0: i64 = 1_700_000_000_000_000;
+
+    #[test]
+    fn test_unanchored() {
+        let tb = TimeBase::new();
+
+        assert!(!tb.anchored());
+        assert_eq!(tb.time(0), None);
+        assert_eq!(tb.time(123_456), None);
+    }
+
+    #[test]
+    fn test_nominal() {
+        let mut tb = TimeBase::new();
+        tb.anchor(48_000, WallTime::from_micros(T0));
+
+        assert!(tb.anchored());
+        assert_eq!(tb.time(48_000), Some(WallTime::from_micros(T0)));
+        assert_eq!(tb.time(96_000), Some(WallTime::from_micros(T0 + 1_000_000)));
+        assert_eq!(tb.time(48_048), Some(WallTime::from_micros(T0 + 1_000)));
+        assert_eq!(tb.time(0), Some(WallTime::from_micros(T0 - 1_000_000)));
+    }
+
+    #[test]
+    fn test_drift() {
+        let mut tb = TimeBase::new();
+
+        // The sample clock runs 100ppm slow, so 10s of samples take 10.001s.
+        tb.anchor(0, WallTime::from_micros(T0));
+        tb.anchor(480_000, WallTime::from_micros(T0 + 10_001_000));
+
+        // Interpolated between the anchors.
+        assert_eq!(tb.time(240_000), Some(WallTime::from_micros(T0 + 5_000_500)));
+        assert_eq!(tb.time(48_000), Some(WallTime::from_micros(T0 + 1_000_100)));
+
+        // Extrapolated beyond them at the measured rate.
+        assert_eq!(tb.time(960_000), Some(WallTime::from_micros(T0 + 20_002_000)));
+
+        // A further anchor measures the rate over the last two.
+        tb.anchor(960_000, WallTime::from_micros(T0 + 20_001_000));
+        assert_eq!(tb.time(720_000), Some(WallTime::from_micros(T0 + 15_001_000)));
+        assert_eq!(tb.time(1_440_000), Some(WallTime::from_micros(T0 + 30_001_000)));
+    }
+
+    #[test]
+    fn test_step() {
+        let mut tb = TimeBase::new();
+
+        tb.anchor(0, WallTime::from_micros(T0));
+
+        // The wall clock was set forward an hour, which isn't drift.
+        tb.anchor(480_000, WallTime::from_micros(T0 + 3_610_000_000));
+        assert_eq!(tb.time(528_000), Some(WallTime::from_micros(T0 + 3_611_000_000)));
+
+        // Anchoring an earlier sample also restarts from the nominal rate.
+        tb.anchor(48_000, WallTime::from_micros(T0));
+        assert_eq!(tb.time(96_000), Some(WallTime::from_micros(T0 + 1_000_000)));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_system() {
+        let t = UNIX_EPOCH + Duration::from_micros(T0 as u64);
+
+        assert_eq!(WallTime::from(t), WallTime::from_micros(T0));
+        assert_eq!(WallTime::from_micros(T0).to_system(), t);
+
+        let t = UNIX_EPOCH - Duration::from_micros(1_500);
+
+        assert_eq!(WallTime::from(t), WallTime::from_micros(-1_500));
+        assert_eq!(WallTime::from_micros(-1_500).to_system(), t);
+    }
+}
@@ -0,0 +1,546 @@
+//! Receive Alternate Multi-Block Trunking (AMBT) packets, which carry trunking messages
+//! too large for a single TSBK in an unconfirmed data packet.
+//!
+//! An AMBT packet has a header block, which identifies it with the trunking packet format
+//! and trunking control SAP and carries the trunking opcode, followed by one or more data
+//! blocks whose contents depend on the opcode.
+
+use std::vec::Vec;
+
+use collect_slice::CollectSlice;
+
+use bits::{Dibit, DibitBytes};
+use buffer::{Buffer, DataPayloadStorage};
+use coding::trellis;
+use consts::{CODING_DIBITS, TSBK_DIBITS, TSBK_BYTES};
+use data::{crc, interleave};
+use data::fields::{DataPacketOpcode, ServiceAccessPoint};
+use error::{Result, P25Error};
+use stats::{Stats, HasStats};
+use util::{slice_u16, slice_u16_checked, slice_u24, slice_u24_checked, slice_u32_checked};
+
+use trunking::tsbk::{TsbkFields, TsbkOpcode};
+use trunking::vendor::ManufacturerId;
+
+/// Number of data bytes in each AMBT block.
+const BLOCK_BYTES: usize = TSBK_BYTES;
+
+/// Number of bytes in the packet checksum at the end of the last block.
+const CHECKSUM_BYTES: usize = 4;
+
+/// State machine for receiving an AMBT packet.
+///
+/// The header and each data block are coded like a TSBK, so each is buffered,
+/// deinterleaved, and decoded using the 1/2-rate convolutional code. Data packets that
+/// aren't in the AMBT format cause an `UnsupportedFormat` error once their header is
+/// decoded.
+pub struct AmbtReceiver {
+    /// Current buffered dibits.
+    dibits: Buffer<DataPayloadStorage>,
+    /// Decoded header, once received.
+    header: Option<AmbtHeader>,
+    /// Data block bytes received so far.
+    data: Vec<u8>,
+    stats: Stats,
+}
+
+impl AmbtReceiver {
+    /// Create a new `AmbtReceiver` in the initial state.
+    pub fn new() -> AmbtReceiver {
+        AmbtReceiver {
+            dibits: Buffer::new(DataPayloadStorage::new()),
+            header: None,
+            data: vec![],
+            stats: Stats::default(),
+        }
+    }
+
+    /// Feed in a baseband symbol, possibly producing a complete AMBT packet. Return
+    /// `Some(Ok(pkt))` if a packet was successfully received, `Some(Err(err))` if an
+    /// error occurred, and `None` in the case of no event.
+    pub fn feed(&mut self, dibit: Dibit) -> Option<Result<Ambt>> {
+        let bytes = match self.dibits.feed(dibit) {
+            Some(buf) => decode_block(buf),
+            None => return None,
+        };
+
+        let bytes = match bytes {
+            Ok(bytes) => bytes,
+            Err(err) => return Some(Err(err)),
+        };
+
+        // TODO: determine number of corrected bits.
+        self.stats.viterbi_dibit.record_fixes(0);
+
+        let header = match self.header {
+            Some(header) => header,
+            None => {
+                let header = AmbtHeader::new(bytes);
+
+                if let Err(err) = header.check_crc() {
+                    return Some(Err(err));
+                }
+
+                if let Err(err) = header.check_ambt() {
+                    return Some(Err(err));
+                }
+
+                self.header = Some(header);
+
+                return None;
+            },
+        };
+
+        self.data.extend(bytes.iter().cloned());
+
+        if self.data.len() < header.blocks() as usize * BLOCK_BYTES {
+            return None;
+        }
+
+        let (data, checksum) = self.data.split_at(self.data.len() - CHECKSUM_BYTES);
+
+        let crc = crc::CRC32::new()
+            .feed_bytes(data.iter().cloned())
+            .finish() as u32;
+
+        match slice_u32_checked(checksum) {
+            Some(received) if received == crc => {},
+            Some(received) => return Some(Err(P25Error::CrcMismatch {
+                received: received,
+                computed: crc,
+            })),
+            None => return Some(Err(P25Error::Truncated {
+                needed: CHECKSUM_BYTES,
+                got: checksum.len(),
+            })),
+        }
+
+        Some(Ok(Ambt::new(header, data.to_vec())))
+    }
+}
+
+impl HasStats for AmbtReceiver {
+    fn stats(&mut self) -> &mut Stats { &mut self.stats }
+}
+
+/// Deinterleave and decode the given coded block into bytes.
+fn decode_block(buf: &[Dibit; CODING_DIBITS]) -> Result<[u8; BLOCK_BYTES]> {
+    let mut dibits = [Dibit::default(); TSBK_DIBITS];
+    let count = trellis::DibitDecoder::new(interleave::Deinterleaver::new(buf))
+        .filter_map(|x| x.ok())
+        .collect_slice_exhaust(&mut dibits[..]);
+
+    if count != dibits.len() {
+        return Err(P25Error::DibitViterbiUnrecoverable);
+    }
+
+    let mut bytes = [0; BLOCK_BYTES];
+    DibitBytes::new(dibits.iter().cloned()).collect_slice_checked(&mut bytes[..]);
+
+    Ok(bytes)
+}
+
+/// Buffer of bytes that represents an AMBT header block.
+pub type Buf = [u8; BLOCK_BYTES];
+
+/// Header block of an AMBT packet.
+#[derive(Copy, Clone)]
+pub struct AmbtHeader(Buf);
+
+impl AmbtHeader {
+    /// Create a new `AmbtHeader` decoder from the given header bytes.
+    pub fn new(buf: Buf) -> AmbtHeader { AmbtHeader(buf) }
+
+    /// Whether the packet is outbound from the site.
+    pub fn outbound(&self) -> bool { self.0[0] >> 5 & 1 == 1 }
+    /// Data packet format.
+    pub fn format(&self) -> Option<DataPacketOpcode> {
+        DataPacketOpcode::from_bits(self.0[0] & 0x1F)
+    }
+    /// Destination service of the packet.
+    pub fn sap(&self) -> Option<ServiceAccessPoint> {
+        ServiceAccessPoint::from_bits(self.0[1] & 0x3F)
+    }
+    /// Manufacturer ID, which determines how the opcode is interpreted.
+    pub fn mfg(&self) -> u8 { self.0[2] }
+    /// Source or destination unit of the packet.
+    pub fn unit(&self) -> u32 { slice_u24(&self.0[3..=5]) }
+    /// Number of data blocks following the header.
+    pub fn blocks(&self) -> u8 { self.0[6] & 0x7F }
+    /// Raw 6-bit trunking opcode.
+    pub fn opcode_bits(&self) -> u8 { self.0[7] & 0x3F }
+    /// Trunking opcode of the contained message.
+    pub fn opcode(&self) -> Option<TsbkOpcode> { TsbkOpcode::from_bits(self.opcode_bits()) }
+    /// Opcode-specific fields carried in the header.
+    pub fn fields(&self) -> &[u8] { &self.0[8..=9] }
+
+    /// Whether the header identifies an AMBT packet.
+    pub fn is_ambt(&self) -> bool {
+        self.format() == Some(DataPacketOpcode::TrunkingPacket) &&
+            self.sap() == Some(ServiceAccessPoint::TrunkingControl)
+    }
+
+    /// Return `UnsupportedFormat` with the offending field if the header doesn't
+    /// identify an AMBT packet with at least one data block.
+    pub fn check_ambt(&self) -> Result<()> {
+        if self.format() != Some(DataPacketOpcode::TrunkingPacket) {
+            return Err(P25Error::UnsupportedFormat {
+                field: "packet format",
+                value: (self.0[0] & 0x1F) as u32,
+            });
+        }
+
+        if self.sap() != Some(ServiceAccessPoint::TrunkingControl) {
+            return Err(P25Error::UnsupportedFormat {
+                field: "SAP",
+                value: (self.0[1] & 0x3F) as u32,
+            });
+        }
+
+        if self.blocks() == 0 {
+            return Err(P25Error::UnsupportedFormat {
+                field: "block count",
+                value: 0,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Received CRC-16 of the header.
+    pub fn crc(&self) -> u16 { slice_u16(&self.0[10..]) }
+
+    /// Calculate 16-bit CRC over bytes in the header.
+    pub fn calc_crc(&self) -> u16 {
+        crc::CRC16::new()
+            .feed_bytes((&self.0[..10]).iter().cloned())
+            .finish() as u16
+    }
+
+    /// Verify the received CRC matches the calculated CRC.
+    pub fn crc_valid(&self) -> bool { self.check_crc().is_ok() }
+
+    /// Return `CrcMismatch` with both checksums if the received CRC doesn't match the
+    /// calculated CRC.
+    pub fn check_crc(&self) -> Result<()> {
+        let (received, computed) = (self.crc(), self.calc_crc());
+
+        if received != computed {
+            return Err(P25Error::CrcMismatch {
+                received: received as u32,
+                computed: computed as u32,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Complete AMBT packet.
+pub struct Ambt {
+    /// Header block of the packet.
+    header: AmbtHeader,
+    /// Data block bytes, with the packet checksum removed.
+    data: Vec<u8>,
+}
+
+impl Ambt {
+    /// Create a new `Ambt` from the given header and data bytes.
+    pub fn new(header: AmbtHeader, data: Vec<u8>) -> Ambt {
+        Ambt {
+            header: header,
+            data: data,
+        }
+    }
+
+    /// Header block of the packet.
+    pub fn header(&self) -> &AmbtHeader { &self.header }
+    /// Data bytes following the header.
+    pub fn data(&self) -> &[u8] { &self.data[..] }
+
+    /// 16-bit field at the given byte offset into the data, or `None` if the packet has
+    /// too few data blocks to hold it.
+    pub fn data_u16(&self, offset: usize) -> Option<u16> {
+        self.data.get(offset..).and_then(slice_u16_checked)
+    }
+
+    /// 24-bit field, such as a unit address, at the given byte offset into the data, or
+    /// `None` if the packet has too few data blocks to hold it.
+    pub fn data_u24(&self, offset: usize) -> Option<u32> {
+        self.data.get(offset..).and_then(slice_u24_checked)
+    }
+
+    /// Whether the message has an equivalent TSBK form that `tsbk` can repack it into.
+    ///
+    /// Standard messages only carried in the AMBT format, such as the authentication
+    /// demand, would lose their fields if repacked.
+    pub fn has_tsbk_form(&self) -> bool {
+        !ManufacturerId::from_bits(self.header.mfg()).is_standard() ||
+            self.header.opcode() != Some(TsbkOpcode::UnitAuthDemand)
+    }
+
+    /// Repack the message into a TSBK so it can be handled by the TSBK decoders.
+    ///
+    /// The TSBK payload is taken from the two header fields followed by the first six
+    /// data bytes, which matches the TSBK layout of messages whose AMBT form only adds
+    /// fields after the TSBK ones. The resulting TSBK is marked as the last block and
+    /// has a valid CRC.
+    pub fn tsbk(&self) -> TsbkFields {
+        let mut payload = [0; 8];
+        (&mut payload[..2]).copy_from_slice(self.header.fields());
+
+        for (dest, &src) in (&mut payload[2..]).iter_mut().zip(self.data.iter()) {
+            *dest = src;
+        }
+
+        TsbkFields::build(self.header.opcode_bits(), self.header.mfg(), &payload)
+            .with_tail(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use data::coder::DibitCoder;
+    use trunking::decode::{AmbtMessage, TsbkMessage};
+    use trunking::fields::NetworkIdentity;
+
+    /// Fill in the CRC of the given header bytes.
+    fn header(mut buf: Buf) -> Buf {
+        let crc = AmbtHeader::new(buf).calc_crc();
+        buf[10] = (crc >> 8) as u8;
+        buf[11] = crc as u8;
+        buf
+    }
+
+    /// Code and interleave the given block bytes into dibits.
+    fn encode(bytes: &[u8]) -> Vec<Dibit> {
+        interleave::Interleaver::new(
+            DibitCoder::new().feed_bytes(bytes.iter().cloned()).finish()
+        ).collect()
+    }
+
+    /// Build the dibits of an AMBT packet with the given header and data bytes.
+    fn packet(head: Buf, data: &[u8]) -> Vec<Dibit> {
+        let mut bytes = data.to_vec();
+        let crc = crc::CRC32::new().feed_bytes(data.iter().cloned()).finish() as u32;
+
+        bytes.extend([(crc >> 24) as u8, (crc >> 16) as u8, (crc >> 8) as u8, crc as u8]
+            .iter().cloned());
+
+        let mut dibits = encode(&header(head));
+
+        for block in bytes.chunks(BLOCK_BYTES) {
+            dibits.extend(encode(block));
+        }
+
+        dibits
+    }
+
+    fn receive(dibits: &[Dibit]) -> Vec<Result<Ambt>> {
+        let mut recv = AmbtReceiver::new();
+        dibits.iter().filter_map(|&d| recv.feed(d)).collect()
+    }
+
+    fn net_status_header() -> Buf {
+        [
+            0b0_1_1_10111,
+            0b11_111101,
+            0x00,
+            0xFF,
+            0xFF,
+            0xFF,
+            0b1_0000001,
+            0b00_111011,
+            0x00,
+            0xBE,
+            0x00,
+            0x00,
+        ]
+    }
+
+    #[test]
+    fn test_ambt_header() {
+        let h = AmbtHeader::new(header(net_status_header()));
+
+        assert!(h.crc_valid());
+        assert!(h.is_ambt());
+        assert!(h.outbound());
+        assert_eq!(h.mfg(), 0x00);
+        assert_eq!(h.unit(), 0xFFFFFF);
+        assert_eq!(h.blocks(), 1);
+        assert_eq!(h.opcode(), Some(TsbkOpcode::NetworkStatusBroadcast));
+        assert_eq!(h.fields(), &[0x00, 0xBE]);
+    }
+
+    #[test]
+    fn test_network_status() {
+        let data = [0xEF, 0x12, 0x34, 0x50, 0x00, 0x70, 0x00, 0x00];
+        let events = receive(&packet(net_status_header(), &data));
+        assert_eq!(events.len(), 1);
+
+        let ambt = match events[0] {
+            Ok(ref ambt) => ambt,
+            Err(_) => panic!(),
+        };
+
+        assert_eq!(ambt.data(), &data);
+        assert_eq!(ambt.data_u16(0), Some(0xEF12));
+        assert_eq!(ambt.data_u24(5), Some(0x700000));
+        assert_eq!(ambt.data_u16(7), None);
+        assert_eq!(ambt.data_u24(6), None);
+        assert_eq!(ambt.data_u24(20), None);
+
+        let from_ambt = ambt.tsbk();
+        assert!(from_ambt.crc_valid());
+
+        let mut buf = [
+            0b10_111011,
+            0x00,
+            0x00,
+            0xBE,
+            0xEF,
+            0x12,
+            0x34,
+            0x50,
+            0x00,
+            0x70,
+            0x00,
+            0x00,
+        ];
+        let crc = TsbkFields::new(buf).calc_crc();
+        buf[10] = (crc >> 8) as u8;
+        buf[11] = crc as u8;
+        let from_tsbk = TsbkFields::new(buf);
+
+        assert_eq!(from_ambt.opcode(), from_tsbk.opcode());
+        assert_eq!(from_ambt.mfg(), from_tsbk.mfg());
+        assert_eq!(from_ambt.payload(), from_tsbk.payload());
+
+        match (TsbkMessage::new(&from_ambt), TsbkMessage::new(&from_tsbk)) {
+            (TsbkMessage::NetworkStatusBroadcast(a),
+             TsbkMessage::NetworkStatusBroadcast(b)) => {
+                assert_eq!(a.area(), b.area());
+                assert_eq!(a.wacn(), b.wacn());
+                assert_eq!(a.wacn(), 0xBEEF1);
+                assert_eq!(a.system(), b.system());
+                assert_eq!(a.system(), 0x234);
+                assert_eq!(a.channel(), b.channel());
+            },
+            _ => panic!(),
+        }
+    }
+
+    /// Header of an authentication demand with the given manufacturer ID and number of
+    /// data blocks.
+    fn auth_demand_header(mfg: u8, blocks: u8) -> Buf {
+        [
+            0b0_1_1_10111,
+            0b11_111101,
+            mfg,
+            0x01,
+            0x02,
+            0x03,
+            0b1_0000000 | blocks,
+            0b00_110001,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+        ]
+    }
+
+    #[test]
+    fn test_auth_demand() {
+        let mut data = [0; 32];
+        (&mut data[..22]).copy_from_slice(&[
+            0xBE, 0xE0, 0x03, 0xA5,
+            0x01, 0x02, 0x03,
+            0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19,
+            0x20, 0x21, 0x22, 0x23, 0x24,
+        ]);
+
+        let events = receive(&packet(auth_demand_header(0x00, 3), &data));
+        assert_eq!(events.len(), 1);
+
+        let ambt = events[0].as_ref().ok().unwrap();
+        assert_eq!(ambt.header().opcode(), Some(TsbkOpcode::UnitAuthDemand));
+        assert!(!ambt.has_tsbk_form());
+
+        match AmbtMessage::new(ambt) {
+            AmbtMessage::AuthDemand(d) => {
+                assert_eq!(d.network(), NetworkIdentity::new(0xBEE00, 0x3A5));
+                assert_eq!(d.dest_unit(), 0x010203);
+                assert_eq!(d.seed(), &[0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17,
+                                       0x18, 0x19]);
+                assert_eq!(d.challenge(), &[0x20, 0x21, 0x22, 0x23, 0x24]);
+            },
+            _ => panic!(),
+        }
+
+        // Too short for the standard format, so passed through whole.
+        let events = receive(&packet(auth_demand_header(0x00, 1), &data[..8]));
+        let ambt = events[0].as_ref().ok().unwrap();
+
+        match AmbtMessage::new(ambt) {
+            AmbtMessage::Unknown(a) => assert_eq!(a.data(), &data[..8]),
+            _ => panic!(),
+        }
+
+        // Manufacturer-specific formats are passed through as vendor TSBKs.
+        let events = receive(&packet(auth_demand_header(0x68, 3), &data));
+        let ambt = events[0].as_ref().ok().unwrap();
+        assert!(ambt.has_tsbk_form());
+
+        match AmbtMessage::new(ambt) {
+            AmbtMessage::Tsbk(t) => match TsbkMessage::new(&t) {
+                TsbkMessage::Vendor(v) => assert_eq!(v.mfid(), 0x68),
+                _ => panic!(),
+            },
+            _ => panic!(),
+        }
+
+        // Messages with a TSBK form are repacked.
+        let events = receive(&packet(net_status_header(), &[0; 8]));
+
+        match AmbtMessage::new(events[0].as_ref().ok().unwrap()) {
+            AmbtMessage::Tsbk(t) =>
+                assert_eq!(t.opcode(), Some(TsbkOpcode::NetworkStatusBroadcast)),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_bad_checksum() {
+        let data = [0xEF, 0x12, 0x34, 0x50, 0x00, 0x70, 0x00, 0x00];
+        let mut dibits = packet(net_status_header(), &data);
+        let n = dibits.len();
+
+        // Corrupt the coded block beyond what the convolutional code can correct by
+        // replacing it with a block coded from different bytes.
+        let other = encode(&[0xAA; BLOCK_BYTES]);
+        (&mut dibits[n - other.len()..]).copy_from_slice(&other);
+
+        let events = receive(&dibits);
+        assert_eq!(events.len(), 1);
+        match events[0] {
+            Err(P25Error::CrcMismatch { received, computed }) =>
+                assert!(received != computed),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_not_ambt() {
+        let mut head = net_status_header();
+        // Unconfirmed packet format.
+        head[0] = 0b0_1_1_10101;
+
+        let events = receive(&encode(&header(head)));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].as_ref().err(), Some(&P25Error::UnsupportedFormat {
+            field: "packet format",
+            value: 0b10101,
+        }));
+    }
+}
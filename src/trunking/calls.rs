@@ -0,0 +1,404 @@
+//! Track active calls from the voice channel grants on the control channel.
+
+use std::vec::Vec;
+
+use message::receiver::MessageEvent;
+use trunking::decode::TsbkMessage;
+use trunking::fields::{Channel, TalkGroup};
+
+/// Destination of a call.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CallTarget {
+    /// Call to all units in a talkgroup.
+    Group(TalkGroup),
+    /// Call to a single unit.
+    Unit(u32),
+}
+
+/// Logical call collapsed from the repeated grants for a traffic channel.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Call {
+    /// Traffic channel carrying the call.
+    pub channel: Channel,
+    /// Destination of the call.
+    pub target: CallTarget,
+    /// Unit that initiated the call, if a grant that includes it was received.
+    pub src_unit: Option<u32>,
+    /// Whether any grant for the call was flagged as an emergency.
+    pub emergency: bool,
+    /// Time the first grant for the call was received.
+    pub started: u64,
+    /// Time the most recent grant or update for the call was received.
+    pub updated: u64,
+    /// Time the last `Started` or `Continued` event was emitted.
+    reported: u64,
+}
+
+/// Change in the state of a call.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CallEvent {
+    /// The first grant for a call was received.
+    Started(Call),
+    /// Grants for the call are still being received.
+    Continued(Call),
+    /// The call ended because its traffic channel was granted to another call or its
+    /// grants stopped.
+    Ended(Call),
+}
+
+/// Collapses repeated voice grants and grant updates into a table of active calls.
+///
+/// Times are in arbitrary caller-defined units. A `Continued` event is emitted at most
+/// once per `heartbeat` for each call, and a call ends once no grant for it has been
+/// received for longer than `timeout`.
+#[derive(Clone)]
+pub struct GrantTracker {
+    /// Currently active calls.
+    calls: Vec<Call>,
+    /// Most recent ended call on each traffic channel.
+    ended: Vec<Call>,
+    /// Minimum time between `Continued` events for a call.
+    heartbeat: u64,
+    /// Time without grants after which a call ends.
+    timeout: u64,
+}
+
+impl GrantTracker {
+    /// Create a new `GrantTracker` with no active calls.
+    pub fn new(heartbeat: u64, timeout: u64) -> GrantTracker {
+        GrantTracker {
+            calls: vec![],
+            ended: vec![],
+            heartbeat: heartbeat,
+            timeout: timeout,
+        }
+    }
+
+    /// Handle the given receiver event, received at the given time, and return any
+    /// resulting call events.
+    pub fn feed(&mut self, event: &MessageEvent, time: u64) -> Vec<CallEvent> {
+        let tsbk = match *event {
+            MessageEvent::TrunkingControl(ref tsbk) => tsbk,
+            _ => return vec![],
+        };
+
+        let mut events = vec![];
+
+        match TsbkMessage::new(tsbk) {
+            TsbkMessage::GroupVoiceGrant(g) => self.grant(g.channel(),
+                CallTarget::Group(g.talkgroup()), Some(g.src_unit()),
+                g.opts().emergency(), time, &mut events),
+            TsbkMessage::GroupVoiceUpdate(u) => for &(ch, tg) in u.updates().iter() {
+                if tg != TalkGroup::Nobody {
+                    self.grant(ch, CallTarget::Group(tg), None, false, time, &mut events);
+                }
+            },
+            TsbkMessage::GroupVoiceUpdateExplicit(u) => self.grant(u.channel(),
+                CallTarget::Group(u.talkgroup()), None, u.opts().emergency(), time,
+                &mut events),
+            TsbkMessage::UnitVoiceGrant(g) | TsbkMessage::UnitVoiceUpdate(g) =>
+                self.grant(g.channel(), CallTarget::Unit(g.dest_unit()),
+                           Some(g.src_unit()), false, time, &mut events),
+            _ => {},
+        }
+
+        events
+    }
+
+    /// End all calls whose grants stopped before the given time, returning an `Ended`
+    /// event for each.
+    pub fn expire(&mut self, time: u64) -> Vec<CallEvent> {
+        let mut events = vec![];
+        let mut idx = 0;
+
+        // Calls are removed in place, so nothing is allocated unless a call ends.
+        while idx < self.calls.len() {
+            if time.saturating_sub(self.calls[idx].updated) > self.timeout {
+                let call = self.calls.remove(idx);
+                self.record_ended(call);
+                events.push(CallEvent::Ended(call));
+            } else {
+                idx += 1;
+            }
+        }
+
+        events
+    }
+
+    /// Delay the end and next `Continued` event of every active call by the given time,
+    /// as though the time didn't pass for them. This moves their `updated` time forward.
+    pub fn delay(&mut self, time: u64) {
+        for call in self.calls.iter_mut() {
+            call.updated += time;
+            call.reported += time;
+        }
+    }
+
+    /// Currently active calls, in the order they started.
+    pub fn calls(&self) -> &[Call] { &self.calls[..] }
+
+    /// Find the active call on the given traffic channel.
+    pub fn call(&self, ch: Channel) -> Option<&Call> {
+        self.calls.iter().find(|c| c.channel == ch)
+    }
+
+    /// Traffic channels with an active call or a call last granted at or after the given
+    /// time, in channel order.
+    pub fn recent(&self, since: u64) -> Vec<Channel> {
+        let mut chans: Vec<Channel> = self.calls.iter()
+            .chain(self.ended.iter().filter(|c| c.updated >= since))
+            .map(|c| c.channel)
+            .collect();
+
+        chans.sort_by_key(|ch| ch.to_bits());
+        chans.dedup();
+        chans
+    }
+
+    /// Remember the given call as the last one to end on its channel.
+    fn record_ended(&mut self, call: Call) {
+        self.ended.retain(|c| c.channel != call.channel);
+        self.ended.push(call);
+    }
+
+    /// Record a grant of the given channel to the given target.
+    fn grant(&mut self, ch: Channel, target: CallTarget, src: Option<u32>,
+             emergency: bool, time: u64, events: &mut Vec<CallEvent>)
+    {
+        if let Some(idx) = self.calls.iter().position(|c| c.channel == ch) {
+            if self.calls[idx].target == target {
+                let call = &mut self.calls[idx];
+
+                call.updated = time;
+                call.src_unit = src.or(call.src_unit);
+                call.emergency |= emergency;
+
+                if time.saturating_sub(call.reported) >= self.heartbeat {
+                    call.reported = time;
+                    events.push(CallEvent::Continued(*call));
+                }
+
+                return;
+            }
+
+            // The channel was reassigned, so the previous call is over.
+            let prev = self.calls.remove(idx);
+            self.record_ended(prev);
+            events.push(CallEvent::Ended(prev));
+        }
+
+        let call = Call {
+            channel: ch,
+            target: target,
+            src_unit: src,
+            emergency: emergency,
+            started: time,
+            updated: time,
+            reported: time,
+        };
+
+        self.calls.push(call);
+        events.push(CallEvent::Started(call));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use trunking::tsbk::{TsbkFields, TsbkOpcode};
+    use trunking::vendor::MFID_STANDARD;
+
+    fn event(opcode: TsbkOpcode, payload: &[u8]) -> MessageEvent {
+        MessageEvent::TrunkingControl(TsbkFields::build(opcode.to_bits(), MFID_STANDARD,
+                                                        payload))
+    }
+
+    fn group_grant(ch: u16, tg: u16, src: u32) -> MessageEvent {
+        event(TsbkOpcode::GroupVoiceGrant, &[
+            0x00,
+            (ch >> 8) as u8,
+            ch as u8,
+            (tg >> 8) as u8,
+            tg as u8,
+            (src >> 16) as u8,
+            (src >> 8) as u8,
+            src as u8,
+        ])
+    }
+
+    fn group_update(ch: u16, tg: u16) -> MessageEvent {
+        event(TsbkOpcode::GroupVoiceUpdate, &[
+            (ch >> 8) as u8,
+            ch as u8,
+            (tg >> 8) as u8,
+            tg as u8,
+            (ch >> 8) as u8,
+            ch as u8,
+            (tg >> 8) as u8,
+            tg as u8,
+        ])
+    }
+
+    fn unit_grant(ch: u16, dest: u32, src: u32) -> MessageEvent {
+        event(TsbkOpcode::UnitVoiceGrant, &[
+            (ch >> 8) as u8,
+            ch as u8,
+            (dest >> 16) as u8,
+            (dest >> 8) as u8,
+            dest as u8,
+            (src >> 16) as u8,
+            (src >> 8) as u8,
+            src as u8,
+        ])
+    }
+
+    #[test]
+    fn test_collapse() {
+        let mut t = GrantTracker::new(10, 30);
+
+        let ev = t.feed(&group_grant(0x1020, 0x1234, 0x00ABCD), 0);
+        assert_eq!(ev.len(), 1);
+
+        let call = match ev[0] {
+            CallEvent::Started(call) => call,
+            _ => panic!(),
+        };
+
+        assert_eq!(call.channel, Channel::from_bits(0x1020));
+        assert_eq!(call.target, CallTarget::Group(TalkGroup::Other(0x1234)));
+        assert_eq!(call.src_unit, Some(0x00ABCD));
+        assert!(!call.emergency);
+        assert_eq!(call.started, 0);
+
+        // Repeats within the heartbeat are collapsed silently.
+        assert!(t.feed(&group_grant(0x1020, 0x1234, 0x00ABCD), 3).is_empty());
+        assert!(t.feed(&group_update(0x1020, 0x1234), 6).is_empty());
+        assert_eq!(t.calls().len(), 1);
+        assert_eq!(t.calls()[0].updated, 6);
+
+        // Heartbeat once it's due, keeping the source from the original grant.
+        match t.feed(&group_update(0x1020, 0x1234), 10)[..] {
+            [CallEvent::Continued(c)] => {
+                assert_eq!(c.started, 0);
+                assert_eq!(c.updated, 10);
+                assert_eq!(c.src_unit, Some(0x00ABCD));
+            },
+            _ => panic!(),
+        }
+
+        assert!(t.feed(&group_update(0x1020, 0x1234), 15).is_empty());
+        assert_eq!(t.feed(&group_update(0x1020, 0x1234), 20).len(), 1);
+
+        // Other messages are ignored.
+        assert!(t.feed(&MessageEvent::LowSpeedDataFragment(0), 21).is_empty());
+        assert_eq!(t.calls().len(), 1);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut t = GrantTracker::new(10, 30);
+
+        // Updates for unknown calls start them without a source.
+        match t.feed(&group_update(0x1020, 0x1234), 0)[..] {
+            [CallEvent::Started(c)] => assert_eq!(c.src_unit, None),
+            _ => panic!(),
+        }
+
+        t.feed(&group_update(0x1020, 0x1234), 20);
+
+        assert!(t.expire(50).is_empty());
+        assert_eq!(t.calls().len(), 1);
+
+        match t.expire(51)[..] {
+            [CallEvent::Ended(c)] => {
+                assert_eq!(c.started, 0);
+                assert_eq!(c.updated, 20);
+            },
+            _ => panic!(),
+        }
+
+        assert!(t.calls().is_empty());
+        assert!(t.expire(100).is_empty());
+
+        // A new grant after the end is a new call.
+        match t.feed(&group_update(0x1020, 0x1234), 100)[..] {
+            [CallEvent::Started(c)] => assert_eq!(c.started, 100),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_delay() {
+        let mut t = GrantTracker::new(10, 30);
+
+        t.feed(&group_grant(0x1020, 0x1234, 1), 0);
+        t.delay(100);
+
+        // The call lasts as long after the delay as it would have at the start.
+        assert!(t.expire(130).is_empty());
+        assert!(t.feed(&group_update(0x1020, 0x1234), 105).is_empty());
+        assert_eq!(t.feed(&group_update(0x1020, 0x1234), 110).len(), 1);
+        assert_eq!(t.calls()[0].started, 0);
+        assert_eq!(t.expire(141).len(), 1);
+    }
+
+    #[test]
+    fn test_simultaneous() {
+        let mut t = GrantTracker::new(10, 30);
+
+        assert_eq!(t.feed(&group_grant(0x1020, 0x1234, 1), 0).len(), 1);
+        assert_eq!(t.feed(&unit_grant(0x1030, 0x000042, 2), 5).len(), 1);
+        assert_eq!(t.calls().len(), 2);
+
+        assert!(t.feed(&group_grant(0x1020, 0x1234, 1), 6).is_empty());
+        assert!(t.feed(&unit_grant(0x1030, 0x000042, 2), 7).is_empty());
+        assert_eq!(t.calls().len(), 2);
+
+        assert_eq!(t.call(Channel::from_bits(0x1020)).unwrap().target,
+                   CallTarget::Group(TalkGroup::Other(0x1234)));
+        assert_eq!(t.call(Channel::from_bits(0x1030)).unwrap().target,
+                   CallTarget::Unit(0x000042));
+        assert!(t.call(Channel::from_bits(0x1040)).is_none());
+
+        // Only the call that stopped being granted ends.
+        t.feed(&unit_grant(0x1030, 0x000042, 2), 30);
+
+        match t.expire(37)[..] {
+            [CallEvent::Ended(c)] => assert_eq!(c.channel, Channel::from_bits(0x1020)),
+            _ => panic!(),
+        }
+
+        assert_eq!(t.calls().len(), 1);
+        assert_eq!(t.calls()[0].channel, Channel::from_bits(0x1030));
+    }
+
+    #[test]
+    fn test_reassign() {
+        let mut t = GrantTracker::new(10, 30);
+
+        t.feed(&group_grant(0x1020, 0x1234, 1), 0);
+
+        match t.feed(&group_grant(0x1020, 0x5678, 2), 5)[..] {
+            [CallEvent::Ended(old), CallEvent::Started(new)] => {
+                assert_eq!(old.target, CallTarget::Group(TalkGroup::Other(0x1234)));
+                assert_eq!(new.target, CallTarget::Group(TalkGroup::Other(0x5678)));
+            },
+            _ => panic!(),
+        }
+
+        assert_eq!(t.calls().len(), 1);
+
+        // The old call is remembered, but the channel is only listed once.
+        assert_eq!(t.recent(0), vec![Channel::from_bits(0x1020)]);
+        t.feed(&unit_grant(0x1030, 0x000042, 2), 6);
+        assert_eq!(t.recent(100), vec![Channel::from_bits(0x1020),
+                                       Channel::from_bits(0x1030)]);
+
+        t.expire(100);
+        assert!(t.recent(100).is_empty());
+        assert_eq!(t.recent(6), vec![Channel::from_bits(0x1030)]);
+        assert_eq!(t.recent(5), vec![Channel::from_bits(0x1020),
+                                     Channel::from_bits(0x1030)]);
+        assert!(t.recent(7).is_empty());
+    }
+}
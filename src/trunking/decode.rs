@@ -0,0 +1,598 @@
+//! Route TSBK packets to their typed payload decoders.
+
+use std::convert::TryFrom;
+
+use trunking::ambt::Ambt;
+
+use trunking::fields::{
+    AdjacentSite,
+    AltControlChannel,
+    AuthDemand,
+    ChannelParamsUpdate,
+    ChannelParamsUpdateTdma,
+    ChannelParamsUpdateVu,
+    ExtendedFunctionCommand,
+    GroupTrafficUpdate,
+    NetworkStatusBroadcast,
+    PhoneAlert,
+    ProtectionParamBroadcast,
+    RfssStatusBroadcast,
+    SystemServiceBroadcast,
+    UnitCallAlert,
+    UnitCallRequest,
+};
+
+use trunking::harris::HarrisTsbk;
+use trunking::motorola::MotorolaTsbk;
+
+use trunking::tsbk::{
+    AckResponse,
+    AuthCommand,
+    DenyResponse,
+    GroupDataGrant,
+    GroupVoiceGrant,
+    GroupVoiceUpdateExplicit,
+    LocRegResponse,
+    MessageUpdate,
+    PhoneGrant,
+    QueuedResponse,
+    RoamingAddrCommand,
+    RoamingAddrUpdate,
+    TsbkFields,
+    TsbkOpcode,
+    UnitDeregAck,
+    UnitRegCommand,
+    UnitRegResponse,
+    UnitStatusQuery,
+    UnitStatusUpdate,
+    UnitTrafficChannel,
+};
+
+use trunking::vendor::{ManufacturerId, VendorOpcode, VendorTsbk};
+
+/// Trunking message decoded from a TSBK packet.
+///
+/// Manufacturer-specific packets are dispatched on their manufacturer ID and passed
+/// through as `Vendor` if they have no decoder, and standard packets with an opcode
+/// without a decoder are passed through as `Unknown`. Messages carried in other formats
+/// are routed by first repacking them into an equivalent `TsbkFields`.
+pub enum TsbkMessage<'a> {
+    GroupVoiceGrant(GroupVoiceGrant),
+    GroupVoiceUpdate(GroupTrafficUpdate<'a>),
+    GroupVoiceUpdateExplicit(GroupVoiceUpdateExplicit),
+    UnitVoiceGrant(UnitTrafficChannel),
+    UnitCallRequest(UnitCallRequest<'a>),
+    UnitVoiceUpdate(UnitTrafficChannel),
+    PhoneGrant(PhoneGrant),
+    PhoneAlert(PhoneAlert<'a>),
+    UnitDataGrant(UnitTrafficChannel),
+    GroupDataGrant(GroupDataGrant),
+    UnitStatusUpdate(UnitStatusUpdate),
+    UnitStatusQuery(UnitStatusQuery),
+    UnitShortMessage(MessageUpdate),
+    UnitCallAlert(UnitCallAlert<'a>),
+    AckResponse(AckResponse),
+    QueuedResponse(QueuedResponse),
+    ExtendedFunctionCommand(ExtendedFunctionCommand<'a>),
+    DenyResponse(DenyResponse),
+    LocRegResponse(LocRegResponse),
+    UnitRegResponse(UnitRegResponse),
+    UnitRegCommand(UnitRegCommand),
+    UnitAuthCommand(AuthCommand),
+    UnitDeregAck(UnitDeregAck),
+    RoamingAddrCommand(RoamingAddrCommand),
+    RoamingAddrUpdate(RoamingAddrUpdate),
+    ChannelParamsUpdateTdma(ChannelParamsUpdateTdma<'a>),
+    ChannelParamsUpdateVu(ChannelParamsUpdateVu<'a>),
+    SystemServiceBroadcast(SystemServiceBroadcast<'a>),
+    AltControlChannel(AltControlChannel<'a>),
+    RfssStatusBroadcast(RfssStatusBroadcast<'a>),
+    NetworkStatusBroadcast(NetworkStatusBroadcast<'a>),
+    AdjacentSite(AdjacentSite<'a>),
+    ChannelParamsUpdate(ChannelParamsUpdate<'a>),
+    ProtectionParamBroadcast(ProtectionParamBroadcast<'a>),
+    Motorola(MotorolaTsbk<'a>),
+    Harris(HarrisTsbk<'a>),
+    Vendor(VendorTsbk),
+    Unknown(TsbkFields),
+}
+
+impl<'a> TsbkMessage<'a> {
+    /// Decode the given TSBK packet based on its opcode.
+    pub fn new(tsbk: &'a TsbkFields) -> TsbkMessage<'a> {
+        let mfid = ManufacturerId::from_bits(tsbk.mfg());
+
+        let opcode = match TsbkOpcode::try_from((mfid, tsbk.opcode_bits())) {
+            Ok(opcode) => opcode,
+            Err(VendorOpcode { mfid: ManufacturerId::Motorola, opcode }) =>
+                return match MotorolaTsbk::new(opcode, tsbk.payload()) {
+                    Some(m) => TsbkMessage::Motorola(m),
+                    None => TsbkMessage::Vendor(VendorTsbk::new(*tsbk)),
+                },
+            Err(VendorOpcode { mfid: ManufacturerId::Harris, opcode }) =>
+                return match HarrisTsbk::new(opcode, tsbk.payload()) {
+                    Some(m) => TsbkMessage::Harris(m),
+                    None => TsbkMessage::Vendor(VendorTsbk::new(*tsbk)),
+                },
+            Err(_) => return TsbkMessage::Vendor(VendorTsbk::new(*tsbk)),
+        };
+
+        let payload = tsbk.payload();
+
+        match opcode {
+            TsbkOpcode::GroupVoiceGrant =>
+                TsbkMessage::GroupVoiceGrant(GroupVoiceGrant::new(*tsbk)),
+            TsbkOpcode::GroupVoiceUpdate =>
+                TsbkMessage::GroupVoiceUpdate(GroupTrafficUpdate::new(payload)),
+            TsbkOpcode::GroupVoiceUpdateExplicit =>
+                TsbkMessage::GroupVoiceUpdateExplicit(GroupVoiceUpdateExplicit::new(*tsbk)),
+            TsbkOpcode::UnitVoiceGrant =>
+                TsbkMessage::UnitVoiceGrant(UnitTrafficChannel::new(*tsbk)),
+            TsbkOpcode::UnitCallRequest =>
+                TsbkMessage::UnitCallRequest(UnitCallRequest::new(payload)),
+            TsbkOpcode::UnitVoiceUpdate =>
+                TsbkMessage::UnitVoiceUpdate(UnitTrafficChannel::new(*tsbk)),
+            TsbkOpcode::PhoneGrant => TsbkMessage::PhoneGrant(PhoneGrant::new(*tsbk)),
+            TsbkOpcode::PhoneAlert => TsbkMessage::PhoneAlert(PhoneAlert::new(payload)),
+            TsbkOpcode::UnitDataGrant =>
+                TsbkMessage::UnitDataGrant(UnitTrafficChannel::new(*tsbk)),
+            TsbkOpcode::GroupDataGrant =>
+                TsbkMessage::GroupDataGrant(GroupDataGrant::new(*tsbk)),
+            TsbkOpcode::UnitStatusUpdate =>
+                TsbkMessage::UnitStatusUpdate(UnitStatusUpdate::new(*tsbk)),
+            TsbkOpcode::UnitStatusQuery =>
+                TsbkMessage::UnitStatusQuery(UnitStatusQuery::new(*tsbk)),
+            TsbkOpcode::UnitShortMessage =>
+                TsbkMessage::UnitShortMessage(MessageUpdate::new(*tsbk)),
+            TsbkOpcode::UnitCallAlert =>
+                TsbkMessage::UnitCallAlert(UnitCallAlert::new(payload)),
+            TsbkOpcode::AckResponse => TsbkMessage::AckResponse(AckResponse::new(*tsbk)),
+            TsbkOpcode::QueuedResponse =>
+                TsbkMessage::QueuedResponse(QueuedResponse::new(*tsbk)),
+            TsbkOpcode::ExtendedFunctionResponse =>
+                TsbkMessage::ExtendedFunctionCommand(ExtendedFunctionCommand::new(payload)),
+            TsbkOpcode::DenyResponse => TsbkMessage::DenyResponse(DenyResponse::new(*tsbk)),
+            TsbkOpcode::LocRegResponse =>
+                TsbkMessage::LocRegResponse(LocRegResponse::new(*tsbk)),
+            TsbkOpcode::UnitRegResponse =>
+                TsbkMessage::UnitRegResponse(UnitRegResponse::new(*tsbk)),
+            TsbkOpcode::UnitRegCommand =>
+                TsbkMessage::UnitRegCommand(UnitRegCommand::new(*tsbk)),
+            TsbkOpcode::UnitAuthCommand =>
+                TsbkMessage::UnitAuthCommand(AuthCommand::new(*tsbk)),
+            TsbkOpcode::UnitDeregAck =>
+                TsbkMessage::UnitDeregAck(UnitDeregAck::new(*tsbk)),
+            TsbkOpcode::RoamingAddrCommand =>
+                TsbkMessage::RoamingAddrCommand(RoamingAddrCommand::new(*tsbk)),
+            TsbkOpcode::RoamingAddrUpdate =>
+                TsbkMessage::RoamingAddrUpdate(RoamingAddrUpdate::new(*tsbk)),
+            TsbkOpcode::ChannelParamsUpdateTdma =>
+                TsbkMessage::ChannelParamsUpdateTdma(ChannelParamsUpdateTdma::new(payload)),
+            TsbkOpcode::ChannelParamsUpdateVu =>
+                TsbkMessage::ChannelParamsUpdateVu(ChannelParamsUpdateVu::new(payload)),
+            TsbkOpcode::SystemServiceBroadcast =>
+                TsbkMessage::SystemServiceBroadcast(SystemServiceBroadcast::new(payload)),
+            TsbkOpcode::AltControlChannel =>
+                TsbkMessage::AltControlChannel(AltControlChannel::new(payload)),
+            TsbkOpcode::RfssStatusBroadcast =>
+                TsbkMessage::RfssStatusBroadcast(RfssStatusBroadcast::new(payload)),
+            TsbkOpcode::NetworkStatusBroadcast =>
+                TsbkMessage::NetworkStatusBroadcast(NetworkStatusBroadcast::new(payload)),
+            TsbkOpcode::AdjacentSite =>
+                TsbkMessage::AdjacentSite(AdjacentSite::new(payload)),
+            TsbkOpcode::ChannelParamsUpdate =>
+                TsbkMessage::ChannelParamsUpdate(ChannelParamsUpdate::new(payload)),
+            TsbkOpcode::ProtectionParamBroadcast =>
+                TsbkMessage::ProtectionParamBroadcast(ProtectionParamBroadcast::new(payload)),
+            TsbkOpcode::GroupDataUpdate |
+            TsbkOpcode::GroupDataUpdateExplicit |
+            TsbkOpcode::UnitMonitor |
+            TsbkOpcode::GroupAffiliationResponse |
+            TsbkOpcode::GroupAffiliationQuery |
+            TsbkOpcode::ProtectionParamUpdate |
+            TsbkOpcode::UnitAuthDemand |
+            TsbkOpcode::Reserved(_) => TsbkMessage::Unknown(*tsbk),
+        }
+    }
+}
+
+/// Trunking message decoded from an AMBT packet.
+///
+/// Messages with an equivalent TSBK form are repacked into it for `TsbkMessage`, and the
+/// rest are decoded from the whole packet. Packets of those that don't hold the standard
+/// format, such as a truncated authentication demand, are passed through as `Unknown`.
+pub enum AmbtMessage<'a> {
+    Tsbk(TsbkFields),
+    AuthDemand(AuthDemand<'a>),
+    Unknown(&'a Ambt),
+}
+
+impl<'a> AmbtMessage<'a> {
+    /// Decode the given AMBT packet based on its opcode.
+    pub fn new(ambt: &'a Ambt) -> AmbtMessage<'a> {
+        if ambt.has_tsbk_form() {
+            return AmbtMessage::Tsbk(ambt.tsbk());
+        }
+
+        match ambt.header().opcode() {
+            Some(TsbkOpcode::UnitAuthDemand) => match AuthDemand::new(ambt.data()) {
+                Ok(d) => AmbtMessage::AuthDemand(d),
+                Err(_) => AmbtMessage::Unknown(ambt),
+            },
+            _ => AmbtMessage::Unknown(ambt),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use trunking::tsbk::TsbkHeader;
+    use trunking::fields::{ExtendedFunction, NetworkIdentity, RegResponse, TalkGroup};
+    use voice::crypto::CryptoAlgorithm;
+
+    fn tsbk(opcode: u8, mfg: u8) -> TsbkFields {
+        TsbkFields::new([
+            opcode,
+            mfg,
+            0b10000001,
+            0b00001111,
+            0b11001100,
+            0b00110011,
+            0b10101010,
+            0b00000001,
+            0b00000010,
+            0b00000011,
+            0b11111111,
+            0b11111111,
+        ])
+    }
+
+    #[test]
+    fn test_status_update() {
+        let t = tsbk(0b00011000, 0);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::UnitStatusUpdate(s) => {
+                assert_eq!(s.user_status(), 0b10000001);
+                assert_eq!(s.unit_status(), 0b00001111);
+                assert_eq!(s.dest_unit(), 0b11001100_00110011_10101010);
+                assert_eq!(s.src_unit(), 0x010203);
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_status_query() {
+        let t = tsbk(0b00011010, 0);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::UnitStatusQuery(s) => {
+                assert_eq!(s.dest_unit(), 0b11001100_00110011_10101010);
+                assert_eq!(s.src_unit(), 0x010203);
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_message_update() {
+        let t = tsbk(0b00011100, 0);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::UnitShortMessage(m) => {
+                assert_eq!(m.msg(), 0b10000001_00001111);
+                assert_eq!(m.dest_unit(), 0b11001100_00110011_10101010);
+                assert_eq!(m.src_unit(), 0x010203);
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_extended_function() {
+        let t = TsbkFields::new([
+            0b00100100,
+            0b00000000,
+            0b00000000,
+            0b01111111,
+            0xFF,
+            0xFF,
+            0xFD,
+            0x00,
+            0x00,
+            0x01,
+            0b11111111,
+            0b11111111,
+        ]);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::ExtendedFunctionCommand(c) => {
+                assert_eq!(c.function(), ExtendedFunction::RadioInhibit);
+                assert_eq!(c.argument(), 0xFFFFFD);
+                assert_eq!(c.dest_unit(), 0x000001);
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_registration() {
+        let t = tsbk(0b00101100, 0);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::UnitRegResponse(r) => {
+                assert_eq!(r.response(), RegResponse::Accept);
+                assert_eq!(r.system(), 0b000100001111);
+            },
+            _ => panic!(),
+        }
+
+        let t = tsbk(0b00101101, 0);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::UnitRegCommand(c) => assert_eq!(c.src_unit(), 0x010203),
+            _ => panic!(),
+        }
+
+        let t = tsbk(0b00101111, 0);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::UnitDeregAck(a) => assert_eq!(a.src_unit(), 0x010203),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_loc_reg_response() {
+        let t = tsbk(0b00101011, 0);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::LocRegResponse(r) => {
+                assert_eq!(r.response(), RegResponse::Fail);
+                assert_eq!(r.talkgroup(), TalkGroup::Other(0b00001111_11001100));
+                assert_eq!(r.site_addr().to_string(), "051-170");
+                assert_eq!(r.dest_unit(), 0x010203);
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_payload_decoders() {
+        let t = tsbk(0b00011111, 1);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::UnitCallAlert(a) => {
+                assert_eq!(a.dest_unit(), 0b11001100_00110011_10101010);
+                assert_eq!(a.src_unit(), 0x010203);
+            },
+            _ => panic!(),
+        }
+
+        let t = tsbk(0b00111101, 0);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::ChannelParamsUpdate(p) => assert_eq!(p.id(), 0b1000),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_auth_command() {
+        let t = tsbk(0b00101110, 0);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::UnitAuthCommand(c) => {
+                assert_eq!(c.network(), NetworkIdentity::new(0x0FCC3, 0x3AA));
+                assert_eq!(c.dest_unit(), 0x010203);
+            },
+            _ => panic!(),
+        }
+
+        // Manufacturer-specific authentication formats are passed through raw.
+        let t = tsbk(0b00101110, 0xA4);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::Vendor(v) => assert_eq!(v.payload(), t.payload()),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_responses() {
+        // Denying a group voice grant, with additional information.
+        let t = TsbkFields::new([
+            0b00100111, 0, 0b10000000, 0b00001111, 0b11001100, 0b00110011, 0b10101010,
+            0b00000001, 0b00000010, 0b00000011, 0b11111111, 0b11111111,
+        ]);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::DenyResponse(d) => {
+                assert_eq!(d.service(), Some(TsbkOpcode::GroupVoiceGrant));
+                assert_eq!(d.dest_unit(), 0x010203);
+            },
+            _ => panic!(),
+        }
+
+        let t = tsbk(0b00100001, 0);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::QueuedResponse(q) => assert_eq!(q.dest_unit(), 0x010203),
+            _ => panic!(),
+        }
+
+        let t = tsbk(0b00100000, 0);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::AckResponse(a) => assert_eq!(a.dest_unit(), 0x010203),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_protection_params() {
+        let t = tsbk(0b00111110, 0);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::ProtectionParamBroadcast(p) => {
+                assert_eq!(p.alg(), CryptoAlgorithm::Other(0b11001100));
+                assert_eq!(p.key(), 0b00110011_10101010);
+                assert_eq!(p.dest_unit(), 0x010203);
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_opcode_namespace() {
+        for bits in 0..64 {
+            for &mfid in [ManufacturerId::Standard, ManufacturerId::StandardAlt].iter() {
+                let op = TsbkOpcode::try_from((mfid, bits)).unwrap();
+                assert_eq!(op.to_bits(), bits);
+                assert_eq!(Some(op), TsbkOpcode::from_bits(bits));
+            }
+
+            for &mfid in [ManufacturerId::Motorola, ManufacturerId::Harris,
+                          ManufacturerId::Other(0x42)].iter()
+            {
+                assert_eq!(TsbkOpcode::try_from((mfid, bits)),
+                           Err(VendorOpcode::new(mfid, bits)));
+            }
+        }
+
+        assert_eq!(TsbkOpcode::try_from((ManufacturerId::Standard, 0)),
+                   Ok(TsbkOpcode::GroupVoiceGrant));
+        assert_eq!(TsbkOpcode::try_from((ManufacturerId::Standard, 0b111011)),
+                   Ok(TsbkOpcode::NetworkStatusBroadcast));
+        assert_eq!(TsbkOpcode::try_from((ManufacturerId::Standard, 0b110101)),
+                   Ok(TsbkOpcode::Reserved(0b110101)));
+    }
+
+    #[test]
+    fn test_header_peek() {
+        for &mfg in [0x00, 0x01, 0x90, 0xA4, 0x42].iter() {
+            for bits in 0..64 {
+                let t = tsbk(bits, mfg).with_tail(bits % 2 == 0);
+                let h = TsbkHeader::peek(&t).unwrap();
+
+                assert_eq!(h.mfid.to_bits(), mfg);
+                assert_eq!(h.last_block, bits % 2 == 0);
+
+                match (h.opcode, TsbkMessage::new(&t)) {
+                    (Ok(_), TsbkMessage::Motorola(_)) |
+                    (Ok(_), TsbkMessage::Harris(_)) |
+                    (Ok(_), TsbkMessage::Vendor(_)) => panic!(),
+                    (Ok(op), TsbkMessage::Unknown(u)) => assert_eq!(u.opcode(), Some(op)),
+                    (Ok(op), _) => assert_eq!(t.opcode(), Some(op)),
+                    (Err(v), TsbkMessage::Motorola(_)) =>
+                        assert_eq!(v.mfid, ManufacturerId::Motorola),
+                    (Err(v), TsbkMessage::Harris(_)) =>
+                        assert_eq!(v.mfid, ManufacturerId::Harris),
+                    (Err(v), TsbkMessage::Vendor(m)) => {
+                        assert_eq!(v.mfid.to_bits(), m.mfid());
+                        assert_eq!(v.opcode, m.opcode());
+                    },
+                    (Err(_), _) => panic!(),
+                }
+            }
+        }
+
+        // Corrupted packets aren't peeked.
+        let mut buf = *tsbk(0, 0).with_tail(false).bytes();
+        buf[5] ^= 1;
+        assert!(TsbkHeader::peek(&TsbkFields::new(buf)).is_none());
+    }
+
+    #[test]
+    fn test_unknown() {
+        // Reserved opcode.
+        let t = tsbk(0b00000001, 0);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::Unknown(u) =>
+                assert_eq!(u.opcode(), Some(TsbkOpcode::Reserved(0b000001))),
+            _ => panic!(),
+        }
+
+        // Manufacturer-specific opcode.
+        let t = tsbk(0b00011000, 0x90);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::Vendor(v) => {
+                assert_eq!(v.mfid(), 0x90);
+                assert_eq!(v.opcode(), 0b011000);
+                assert_eq!(v.payload(), t.payload());
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_harris() {
+        let t = tsbk(0b00110000, 0xA4);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::Harris(HarrisTsbk::GroupRegroupCommand(c)) => {
+                assert!(c.group_target());
+                assert_eq!(c.supergroup(), TalkGroup::Other(0x0FCC));
+                assert_eq!(c.target(), 0x010203);
+            },
+            _ => panic!(),
+        }
+
+        // Unknown opcodes pass through byte-exact, sharing the standard CRC check.
+        let mut buf = [
+            0b10_101010,
+            0xA4,
+            0x01,
+            0x23,
+            0x45,
+            0x67,
+            0x89,
+            0xAB,
+            0xCD,
+            0xEF,
+            0x00,
+            0x00,
+        ];
+        let crc = TsbkFields::new(buf).calc_crc();
+        buf[10] = (crc >> 8) as u8;
+        buf[11] = crc as u8;
+        let t = TsbkFields::new(buf);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::Vendor(v) => {
+                assert_eq!(v.mfid(), 0xA4);
+                assert_eq!(v.opcode(), 0b101010);
+                assert_eq!(v.payload(), &buf[2..=9]);
+                assert!(v.tsbk().is_tail());
+                assert!(v.tsbk().crc_valid());
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_motorola() {
+        let t = tsbk(0b00000000, 0x90);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::Motorola(MotorolaTsbk::PatchGroupAdd(p)) => {
+                assert_eq!(p.supergroup(), TalkGroup::Other(0x810F));
+                assert_eq!(p.groups()[0], TalkGroup::Other(0xCC33));
+            },
+            _ => panic!(),
+        }
+
+        let t = tsbk(0b00000001, 0x90);
+
+        match TsbkMessage::new(&t) {
+            TsbkMessage::Motorola(MotorolaTsbk::PatchGroupDelete(p)) => {
+                assert_eq!(p.supergroup(), TalkGroup::Other(0x810F));
+            },
+            _ => panic!(),
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Encode trunking messages into TSBK packets and TSDU dibit streams.
+
+use std::vec::Vec;
+
+use collect_slice::CollectSlice;
+
+use bits::Dibit;
+use consts::{CODING_DIBITS, TSDU_MAX_BLOCKS};
+use data::{coder, interleave};
+
+use trunking::decode::TsbkMessage;
+use trunking::tsbk::TsbkFields;
+
+/// Serializes a typed trunking message into a TSBK packet.
+pub trait TsbkEncode {
+    /// Build a TSBK packet, with a valid CRC and the last-block flag cleared, that
+    /// decodes back to the same message.
+    fn encode(&self) -> TsbkFields;
+}
+
+impl<'a> TsbkEncode for TsbkMessage<'a> {
+    fn encode(&self) -> TsbkFields {
+        use trunking::decode::TsbkMessage::*;
+        use trunking::harris::HarrisTsbk;
+        use trunking::motorola::MotorolaTsbk;
+
+        match *self {
+            GroupVoiceGrant(ref m) => m.encode(),
+            GroupVoiceUpdate(ref m) => m.encode(),
+            GroupVoiceUpdateExplicit(ref m) => m.encode(),
+            UnitVoiceGrant(ref m) => m.encode(),
+            UnitCallRequest(ref m) => m.encode(),
+            UnitVoiceUpdate(ref m) => m.encode(),
+            PhoneGrant(ref m) => m.encode(),
+            PhoneAlert(ref m) => m.encode(),
+            UnitDataGrant(ref m) => m.encode(),
+            GroupDataGrant(ref m) => m.encode(),
+            UnitStatusUpdate(ref m) => m.encode(),
+            UnitStatusQuery(ref m) => m.encode(),
+            UnitShortMessage(ref m) => m.encode(),
+            UnitCallAlert(ref m) => m.encode(),
+            AckResponse(ref m) => m.encode(),
+            QueuedResponse(ref m) => m.encode(),
+            ExtendedFunctionCommand(ref m) => m.encode(),
+            DenyResponse(ref m) => m.encode(),
+            LocRegResponse(ref m) => m.encode(),
+            UnitRegResponse(ref m) => m.encode(),
+            UnitRegCommand(ref m) => m.encode(),
+            UnitAuthCommand(ref m) => m.encode(),
+            UnitDeregAck(ref m) => m.encode(),
+            RoamingAddrCommand(ref m) => m.encode(),
+            RoamingAddrUpdate(ref m) => m.encode(),
+            ChannelParamsUpdateTdma(ref m) => m.encode(),
+            ChannelParamsUpdateVu(ref m) => m.encode(),
+            SystemServiceBroadcast(ref m) => m.encode(),
+            AltControlChannel(ref m) => m.encode(),
+            RfssStatusBroadcast(ref m) => m.encode(),
+            NetworkStatusBroadcast(ref m) => m.encode(),
+            AdjacentSite(ref m) => m.encode(),
+            ChannelParamsUpdate(ref m) => m.encode(),
+            ProtectionParamBroadcast(ref m) => m.encode(),
+            Motorola(MotorolaTsbk::PatchGroupAdd(ref m)) => m.encode(),
+            Motorola(MotorolaTsbk::PatchGroupDelete(ref m)) => m.encode(),
+            Motorola(MotorolaTsbk::SystemLoad(ref m)) => m.encode(),
+            Motorola(MotorolaTsbk::BaseStationId(ref m)) => m.encode(),
+            Harris(HarrisTsbk::GroupRegroupCommand(ref m)) => m.encode(),
+            Vendor(ref v) => v.tsbk().with_tail(false),
+            Unknown(ref t) => t.with_tail(false),
+        }
+    }
+}
+
+/// Code the given TSBK packet with the 1/2-rate convolutional code and interleave it into
+/// the dibits transmitted for one block of a TSDU.
+pub fn code_block(tsbk: &TsbkFields) -> [Dibit; CODING_DIBITS] {
+    let mut dibits = [Dibit::default(); CODING_DIBITS];
+
+    interleave::Interleaver::new(coder::DibitCoder::new()
+        .feed_bytes(tsbk.bytes().iter().cloned())
+        .finish())
+        .collect_slice_checked(&mut dibits[..]);
+
+    dibits
+}
+
+/// Trunking Signalling Data Unit (TSDU), which carries up to three TSBK packets.
+pub struct Tsdu;
+
+impl Tsdu {
+    /// Pack the given TSBK packets into the coded dibits of a TSDU payload, setting the
+    /// last-block flag on only the final packet.
+    pub fn assemble(tsbks: &[TsbkFields]) -> Vec<Dibit> {
+        assert!(!tsbks.is_empty() && tsbks.len() <= TSDU_MAX_BLOCKS);
+
+        let mut dibits = Vec::with_capacity(tsbks.len() * CODING_DIBITS);
+
+        for (i, tsbk) in tsbks.iter().enumerate() {
+            let tsbk = tsbk.with_tail(i == tsbks.len() - 1);
+            dibits.extend(code_block(&tsbk).iter().cloned());
+        }
+
+        dibits
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use trunking::tsbk::TsbkReceiver;
+    use trunking::vendor::{MFID_HARRIS, MFID_MOTOROLA, MFID_STANDARD};
+
+    const PAYLOAD: [u8; 8] = [
+        0b10000001,
+        0b00001111,
+        0b11001100,
+        0b00110011,
+        0b10101010,
+        0b00000001,
+        0b00000010,
+        0b00000011,
+    ];
+
+    fn assert_same(a: &TsbkFields, b: &TsbkFields) {
+        assert_eq!(a.opcode_bits(), b.opcode_bits());
+        assert_eq!(a.mfg(), b.mfg());
+        assert_eq!(a.payload(), b.payload());
+    }
+
+    /// Every packet the decoder can route, covering every standard opcode and each
+    /// vendor opcode with a decoder.
+    fn packets() -> Vec<TsbkFields> {
+        let mut pkts: Vec<_> = (0..64)
+            .map(|op| TsbkFields::build(op, MFID_STANDARD, &PAYLOAD))
+            .collect();
+
+        pkts.push(TsbkFields::build(0x00, MFID_MOTOROLA, &PAYLOAD));
+        pkts.push(TsbkFields::build(0x01, MFID_MOTOROLA, &PAYLOAD));
+        pkts.push(TsbkFields::build(0x09, MFID_MOTOROLA, &PAYLOAD));
+        pkts.push(TsbkFields::build(0x0B, MFID_MOTOROLA, &PAYLOAD));
+        pkts.push(TsbkFields::build(0x30, MFID_HARRIS, &PAYLOAD));
+        pkts.push(TsbkFields::build(0x3F, 0x42, &PAYLOAD));
+
+        pkts
+    }
+
+    #[test]
+    fn test_build() {
+        let t = TsbkFields::build(0b111011, MFID_STANDARD, &PAYLOAD);
+
+        assert!(!t.is_tail());
+        assert!(!t.protected());
+        assert!(t.crc_valid());
+        assert_eq!(t.opcode_bits(), 0b111011);
+        assert_eq!(t.payload(), &PAYLOAD);
+
+        let t = t.with_tail(true);
+        assert!(t.is_tail());
+        assert!(t.crc_valid());
+        assert_eq!(t.opcode_bits(), 0b111011);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for pkt in packets() {
+            let msg = TsbkMessage::new(&pkt);
+
+            if let TsbkMessage::Unknown(_) = msg {
+                // Only reserved and undecoded standard opcodes pass through.
+                assert!(pkt.mfg() == MFID_STANDARD);
+            }
+
+            let enc = msg.encode();
+            assert!(enc.crc_valid());
+            assert!(!enc.is_tail());
+            assert_same(&enc, &pkt);
+        }
+    }
+
+    #[test]
+    fn test_assemble() {
+        let pkts = packets();
+
+        for tsbks in pkts.chunks(TSDU_MAX_BLOCKS) {
+            let dibits = Tsdu::assemble(tsbks);
+            assert_eq!(dibits.len(), tsbks.len() * CODING_DIBITS);
+
+            let mut recv = TsbkReceiver::new();
+            let decoded: Vec<_> = dibits.iter()
+                .filter_map(|&d| recv.feed(d))
+                .map(|r| r.unwrap())
+                .collect();
+
+            assert!(recv.done());
+            assert_eq!(decoded.len(), tsbks.len());
+
+            for (i, (dec, orig)) in decoded.iter().zip(tsbks.iter()).enumerate() {
+                assert_same(dec, orig);
+                assert_eq!(dec.is_tail(), i == tsbks.len() - 1);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_assemble_too_many() {
+        let t = TsbkFields::build(0, MFID_STANDARD, &PAYLOAD);
+        Tsdu::assemble(&[t, t, t, t]);
+    }
+}
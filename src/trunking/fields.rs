@@ -1,8 +1,24 @@
 //! Decode various trunking-related packet fields.
 
+use std::cmp::Ordering;
+#[cfg(feature = "std")]
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use error::{self, P25Error};
 use util::{slice_u16, slice_u24, slice_u32};
+use voice::crypto::CryptoAlgorithm;
+
+use trunking::encode::TsbkEncode;
+use trunking::tsbk::{TsbkFields, TsbkOpcode};
+use trunking::vendor::MFID_STANDARD;
 
 /// Options that can be requested/granted by a service.
+///
+/// This octet is shared by the grants, requests, and link control words of every
+/// service that allocates a channel.
+#[derive(Copy, Clone, Eq, PartialEq)]
 pub struct ServiceOptions(u8);
 
 impl ServiceOptions {
@@ -23,18 +39,93 @@ impl ServiceOptions {
     pub fn prio(&self) -> u8 { self.0 & 0x7 }
 }
 
+impl From<u8> for ServiceOptions {
+    fn from(opts: u8) -> ServiceOptions { ServiceOptions::new(opts) }
+}
+
+impl From<ServiceOptions> for u8 {
+    fn from(opts: ServiceOptions) -> u8 { opts.0 }
+}
+
+impl fmt::Debug for ServiceOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ServiceOptions")
+            .field("emergency", &self.emergency())
+            .field("protected", &self.protected())
+            .field("full_duplex", &self.full_duplex())
+            .field("packet_switched", &self.packet_switched())
+            .field("prio", &self.prio())
+            .finish()
+    }
+}
+
+/// Formats as the names of the set flags followed by the priority, such as
+/// `emergency protected prio 4`.
+impl fmt::Display for ServiceOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let flags = [
+            (self.emergency(), "emergency"),
+            (self.protected(), "protected"),
+            (self.full_duplex(), "full-duplex"),
+            (self.packet_switched(), "packet"),
+        ];
+
+        for &(_, name) in flags.iter().filter(|&&(set, _)| set) {
+            write!(f, "{} ", name)?;
+        }
+
+        write!(f, "prio {}", self.prio())
+    }
+}
+
 /// Uniquely identifies a channel within a site.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct Channel(u16);
 
 impl Channel {
+    /// Create a new `Channel` from the given 16 bit slice.
+    pub fn new(bytes: &[u8]) -> Channel { Self::from_bits(slice_u16(bytes)) }
+
     /// Create a new `Channel` from the given 16 bits.
-    pub fn new(bytes: &[u8]) -> Channel { Channel(slice_u16(bytes)) }
+    pub fn from_bits(bits: u16) -> Channel { Channel(bits) }
 
     /// Channel ID whose parameters to use.
     pub fn id(&self) -> u8 { (self.0 >> 12) as u8 }
     /// Individual channel number within the channel.
     pub fn number(&self) -> u16 { self.0 & 0xFFF }
+    /// Convert the channel to its 16-bit representation.
+    pub fn to_bits(&self) -> u16 { self.0 }
+}
+
+/// Downlink and uplink channels assigned by a grant.
+///
+/// Implicit grants carry only the downlink channel, with the uplink derived from it
+/// through the transmit offset of its channel parameters, while explicit grants carry
+/// each channel separately.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ChannelPair {
+    /// Channel the subscriber receives on.
+    pub rx: Channel,
+    /// Channel the subscriber transmits on, if given explicitly.
+    pub tx: Option<Channel>,
+}
+
+impl ChannelPair {
+    /// Create a new `ChannelPair` from the single channel of an implicit grant.
+    pub fn implicit(ch: Channel) -> ChannelPair {
+        ChannelPair {
+            rx: ch,
+            tx: None,
+        }
+    }
+
+    /// Create a new `ChannelPair` from the separate channels of an explicit grant.
+    pub fn explicit(rx: Channel, tx: Channel) -> ChannelPair {
+        ChannelPair {
+            rx: rx,
+            tx: Some(tx),
+        }
+    }
 }
 
 /// Identifies which group a message belongs to.
@@ -43,7 +134,10 @@ impl Channel {
 /// talkgroups, and the radio will only unsquelch if one of those talkgroups is seen.
 /// Additionally, the user directs each transmission to a talkgroup selected on the
 /// radio.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+///
+/// Talkgroups are compared and ordered by their 16-bit value, so `Other` holding one of
+/// the special values is equivalent to the corresponding special variant.
+#[derive(Copy, Clone, Debug)]
 #[cfg_attr(feature = "ser", derive(Serialize))]
 pub enum TalkGroup {
     /// Includes nobody.
@@ -73,6 +167,92 @@ impl TalkGroup {
             _ => Other(bits),
         }
     }
+
+    /// Convert the talkgroup to its 16-bit representation.
+    pub fn to_bits(self) -> u16 {
+        use self::TalkGroup::*;
+
+        match self {
+            Nobody => 0x0000,
+            Default => 0x0001,
+            Everbody => 0xFFFF,
+            Other(bits) => bits,
+        }
+    }
+}
+
+impl From<u16> for TalkGroup {
+    fn from(bits: u16) -> TalkGroup { TalkGroup::from_bits(bits) }
+}
+
+impl From<TalkGroup> for u16 {
+    fn from(tg: TalkGroup) -> u16 { tg.to_bits() }
+}
+
+impl PartialEq for TalkGroup {
+    fn eq(&self, other: &TalkGroup) -> bool { self.to_bits() == other.to_bits() }
+}
+
+impl Eq for TalkGroup {}
+
+impl PartialOrd for TalkGroup {
+    fn partial_cmp(&self, other: &TalkGroup) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl Ord for TalkGroup {
+    fn cmp(&self, other: &TalkGroup) -> Ordering { self.to_bits().cmp(&other.to_bits()) }
+}
+
+/// Formats as `NONE` and `ALL` for the special talkgroups and decimal otherwise.
+impl fmt::Display for TalkGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.to_bits() {
+            0x0000 => write!(f, "NONE"),
+            0xFFFF => write!(f, "ALL"),
+            bits => write!(f, "{}", bits),
+        }
+    }
+}
+
+/// Parses decimal, hex with a `0x` prefix, or the case-insensitive `NONE` and `ALL`
+/// keywords.
+impl FromStr for TalkGroup {
+    type Err = ParseTalkGroupError;
+
+    fn from_str(s: &str) -> Result<TalkGroup, ParseTalkGroupError> {
+        let s = s.trim();
+
+        if s.eq_ignore_ascii_case("none") {
+            return Ok(TalkGroup::Nobody);
+        }
+
+        if s.eq_ignore_ascii_case("all") {
+            return Ok(TalkGroup::Everbody);
+        }
+
+        let bits = if s.starts_with("0x") || s.starts_with("0X") {
+            u16::from_str_radix(&s[2..], 16)
+        } else {
+            s.parse()
+        };
+
+        bits.map(TalkGroup::from_bits).map_err(|_| ParseTalkGroupError)
+    }
+}
+
+/// Error returned when a talkgroup can't be parsed from a string.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ParseTalkGroupError;
+
+impl fmt::Display for ParseTalkGroupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid talkgroup")
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for ParseTalkGroupError {
+    fn description(&self) -> &str { "invalid talkgroup" }
 }
 
 /// Supported services of a control channel.
@@ -101,7 +281,7 @@ impl SystemServices {
 }
 
 /// Maps channel identifiers (maximum 16 per control channel) to their tuning parameters.
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct ChannelParamsMap([Option<ChannelParams>; 16]);
 
 impl ChannelParamsMap {
@@ -110,21 +290,67 @@ impl ChannelParamsMap {
         self.0[upd.id() as usize] = Some(upd.params());
     }
 
+    /// Update the map with the given VHF/UHF channel parameters.
+    pub fn update_vu(&mut self, upd: &ChannelParamsUpdateVu) {
+        self.0[upd.id() as usize] = Some(upd.params());
+    }
+
+    /// Update the map with the given TDMA channel parameters.
+    pub fn update_tdma(&mut self, upd: &ChannelParamsUpdateTdma) {
+        self.0[upd.id() as usize] = Some(upd.params());
+    }
+
     /// Try to retrieve channel parameters for the given channel ID.
     pub fn lookup(&self, id: u8) -> Option<ChannelParams> {
         self.0[id as usize]
     }
+
+    /// Receive frequency of the given channel in Hz, if parameters for its channel ID
+    /// have been received.
+    pub fn rx_freq(&self, ch: Channel) -> Option<u64> {
+        self.lookup(ch.id()).map(|p| p.rx_freq(ch.number()))
+    }
+
+    /// Transmit frequency of the given channel in Hz, if parameters for its channel ID
+    /// have been received.
+    pub fn tx_freq(&self, ch: Channel) -> Option<u64> {
+        self.lookup(ch.id()).map(|p| p.tx_freq(ch.number()))
+    }
+
+    /// Receive and transmit frequencies of the given channel pair in Hz, if parameters
+    /// for the channel IDs involved have been received.
+    ///
+    /// An explicit transmit channel is tuned directly from its own channel parameters,
+    /// and an implicit one applies the transmit offset to the receive channel.
+    pub fn frequencies(&self, pair: ChannelPair) -> Option<(u64, u64)> {
+        let rx = self.rx_freq(pair.rx)?;
+
+        let tx = match pair.tx {
+            Some(ch) => self.rx_freq(ch)?,
+            None => self.tx_freq(pair.rx)?,
+        };
+
+        Some((rx, tx))
+    }
+
+    /// Receive frequency of the given channel in Hz along with its TDMA slot, if any, if
+    /// parameters for its channel ID have been received.
+    pub fn resolve(&self, ch: Channel) -> Option<(u64, Option<u8>)> {
+        self.lookup(ch.id()).map(|p| (p.rx_freq(ch.number()), p.slot(ch.number())))
+    }
 }
 
 /// Computes TX/RX frequencies and bandwidth for channel numbers within a site.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct ChannelParams {
     /// Base frequency in Hz.
-    base: u32,
+    base: u64,
     /// Channel spacing in Hz.
     spacing: u32,
     /// Transmit frequency offset in Hz.
-    offset: i32,
+    offset: i64,
+    /// Number of TDMA slots per carrier (1 for FDMA channels.)
+    slots: u8,
     /// Channel bandwidth in Hz.
     pub bandwidth: u32,
 }
@@ -134,28 +360,105 @@ impl ChannelParams {
     /// (125Hz steps), TX offset (250kHz steps), and inter-channel spacing (125Hz steps.)
     pub fn new(base: u32, bandwidth: u16, offset: u16, spacing: u16) -> ChannelParams {
         // The MSB denotes the sign and the lower byte is the actual offset.
-        let off = (offset as i32 & 0xFF) * 250_000;
+        let off = (offset as i64 & 0xFF) * 250_000;
 
         ChannelParams {
-            base: base * 5,
+            base: base as u64 * 5,
             spacing: spacing as u32 * 125,
             offset: if offset >> 8 == 0 { -off } else { off },
+            slots: 1,
             bandwidth: bandwidth as u32 * 125,
         }
     }
 
+    /// Create a new `ChannelParams` from the given VHF/UHF base frequency (5Hz steps),
+    /// bandwidth code, TX offset (channel spacing steps), and inter-channel spacing (125Hz
+    /// steps.)
+    ///
+    /// Reserved bandwidth codes result in a bandwidth of 0.
+    pub fn new_vu(base: u32, bandwidth: u8, offset: u16, spacing: u16) -> ChannelParams {
+        assert!(bandwidth >> 4 == 0);
+
+        ChannelParams {
+            base: base as u64 * 5,
+            spacing: spacing as u32 * 125,
+            offset: spaced_offset(offset, spacing),
+            slots: 1,
+            bandwidth: match bandwidth {
+                0b0100 => 6_250,
+                0b0101 => 12_500,
+                _ => 0,
+            },
+        }
+    }
+
+    /// Create a new `ChannelParams` from the given base frequency (5Hz steps), channel
+    /// access type, TX offset (channel spacing steps), and inter-channel spacing (125Hz
+    /// steps.)
+    ///
+    /// Reserved channel types result in a single-slot channel with a bandwidth of 0.
+    pub fn new_tdma(base: u32, kind: u8, offset: u16, spacing: u16) -> ChannelParams {
+        assert!(kind >> 4 == 0);
+
+        let (slots, bandwidth) = match kind {
+            0b0000 => (1, 6_250),
+            0b0001 => (1, 12_500),
+            0b0010 => (1, 6_250),
+            0b0011 => (2, 12_500),
+            0b0100 => (4, 25_000),
+            0b0101 => (2, 12_500),
+            _ => (1, 0),
+        };
+
+        ChannelParams {
+            base: base as u64 * 5,
+            spacing: spacing as u32 * 125,
+            offset: spaced_offset(offset, spacing),
+            slots: slots,
+            bandwidth: bandwidth,
+        }
+    }
+
+    /// Number of TDMA slots on each carrier, which is 1 for FDMA channels.
+    pub fn slots(&self) -> u8 { self.slots }
+
+    /// Whether channels are TDMA, with multiple slots per carrier.
+    pub fn is_tdma(&self) -> bool { self.slots > 1 }
+
     /// Receive frequency for the given channel number in Hz.
-    pub fn rx_freq(&self, ch: u16) -> u32 {
-        self.base + self.spacing * ch as u32
+    pub fn rx_freq(&self, ch: u16) -> u64 {
+        self.base + self.spacing as u64 * (ch / self.slots as u16) as u64
     }
 
     /// Transmit frequency for the given channel number in Hz.
-    pub fn tx_freq(&self, ch: u16) -> u32 {
-        self.rx_freq(ch) + self.offset as u32
+    pub fn tx_freq(&self, ch: u16) -> u64 {
+        (self.rx_freq(ch) as i64 + self.offset) as u64
+    }
+
+    /// TDMA slot for the given channel number, or `None` for FDMA channels.
+    pub fn slot(&self, ch: u16) -> Option<u8> {
+        if self.is_tdma() {
+            Some((ch % self.slots as u16) as u8)
+        } else {
+            None
+        }
     }
 }
 
+/// Compute the TX offset in Hz from the given 14-bit sign/magnitude offset, in steps of
+/// the given channel spacing (125Hz steps.)
+fn spaced_offset(offset: u16, spacing: u16) -> i64 {
+    assert!(offset >> 14 == 0);
+    assert!(spacing >> 10 == 0);
+
+    // The MSB denotes the sign and the lower 13 bits are the actual offset.
+    let off = (offset as i64 & 0x1FFF) * spacing as i64 * 125;
+
+    if offset >> 13 == 0 { -off } else { off }
+}
+
 /// Options for a P25 site.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct SiteOptions(u8);
 
 impl SiteOptions {
@@ -196,6 +499,12 @@ impl<'a> GroupTrafficUpdate<'a> {
     }
 }
 
+impl<'a> TsbkEncode for GroupTrafficUpdate<'a> {
+    fn encode(&self) -> TsbkFields {
+        TsbkFields::build(TsbkOpcode::GroupVoiceUpdate.to_bits(), MFID_STANDARD, self.0)
+    }
+}
+
 /// Advertisement of an adjacent/nearby site within the same WACN (Wide Area Communication
 /// Network.)
 pub struct AdjacentSite<'a>(&'a [u8]);
@@ -221,6 +530,12 @@ impl<'a> AdjacentSite<'a> {
     pub fn services(&self) -> SystemServices { SystemServices::new(self.0[7]) }
 }
 
+impl<'a> TsbkEncode for AdjacentSite<'a> {
+    fn encode(&self) -> TsbkFields {
+        TsbkFields::build(TsbkOpcode::AdjacentSite.to_bits(), MFID_STANDARD, self.0)
+    }
+}
+
 /// Advertisement of parameters used to calculate TX/RX frequencies within the given
 /// associated channel.
 pub struct ChannelParamsUpdate<'a>(&'a [u8]);
@@ -240,23 +555,106 @@ impl<'a> ChannelParamsUpdate<'a> {
 
     /// Bandwidth in steps of 125Hz.
     fn bandwidth(&self) -> u16 {
-        (self.0[0] as u16 & 0xF) << 5 | (self.0[1] >> 3) as u16
+        slice_u16(&self.0[0..=1]) >> 3 & 0x1FF
     }
 
     /// Offset of TX frequency from base RX frequency in steps of 250kHz.
     fn offset(&self) -> u16 {
-        (self.0[1] as u16 & 0x7) << 6 | (self.0[2] >> 2) as u16
+        slice_u16(&self.0[1..=2]) >> 2 & 0x1FF
     }
 
     /// Spacing between individual channel numbers in steps of 125Hz.
     fn spacing(&self) -> u16 {
-        (self.0[2] as u16 & 0x3) << 8 | self.0[3] as u16
+        slice_u16(&self.0[2..=3]) & 0x3FF
+    }
+
+    /// Base RX frequency in steps of 5Hz.
+    fn base(&self) -> u32 { slice_u32(&self.0[4..=7]) }
+}
+
+impl<'a> TsbkEncode for ChannelParamsUpdate<'a> {
+    fn encode(&self) -> TsbkFields {
+        TsbkFields::build(TsbkOpcode::ChannelParamsUpdate.to_bits(),
+                          MFID_STANDARD, self.0)
+    }
+}
+
+/// Advertisement of parameters used to calculate TX/RX frequencies within the given
+/// associated channel on VHF/UHF systems.
+pub struct ChannelParamsUpdateVu<'a>(&'a [u8]);
+
+impl<'a> ChannelParamsUpdateVu<'a> {
+    /// Create a new `ChannelParamsUpdateVu` decoder from the given payload bytes.
+    pub fn new(payload: &'a [u8]) -> Self { ChannelParamsUpdateVu(payload) }
+
+    /// Channel ID associated with the enclosed parameters (can be up to 16 per control
+    /// channel.)
+    pub fn id(&self) -> u8 { self.0[0] >> 4 }
+
+    /// Parameters for the associated channel.
+    pub fn params(&self) -> ChannelParams {
+        ChannelParams::new_vu(self.base(), self.bandwidth(), self.offset(), self.spacing())
+    }
+
+    /// Bandwidth code.
+    fn bandwidth(&self) -> u8 { self.0[0] & 0xF }
+
+    /// Sign and magnitude of TX frequency offset from base RX frequency in steps of
+    /// channel spacing.
+    fn offset(&self) -> u16 { (slice_u24(&self.0[1..=3]) >> 10) as u16 }
+
+    /// Spacing between individual channel numbers in steps of 125Hz.
+    fn spacing(&self) -> u16 { slice_u16(&self.0[2..=3]) & 0x3FF }
+
+    /// Base RX frequency in steps of 5Hz.
+    fn base(&self) -> u32 { slice_u32(&self.0[4..=7]) }
+}
+
+impl<'a> TsbkEncode for ChannelParamsUpdateVu<'a> {
+    fn encode(&self) -> TsbkFields {
+        TsbkFields::build(TsbkOpcode::ChannelParamsUpdateVu.to_bits(),
+                          MFID_STANDARD, self.0)
     }
+}
+
+/// Advertisement of parameters used to calculate TX/RX frequencies and TDMA slots within
+/// the given associated channel.
+pub struct ChannelParamsUpdateTdma<'a>(&'a [u8]);
+
+impl<'a> ChannelParamsUpdateTdma<'a> {
+    /// Create a new `ChannelParamsUpdateTdma` decoder from the given payload bytes.
+    pub fn new(payload: &'a [u8]) -> Self { ChannelParamsUpdateTdma(payload) }
+
+    /// Channel ID associated with the enclosed parameters (can be up to 16 per control
+    /// channel.)
+    pub fn id(&self) -> u8 { self.0[0] >> 4 }
+
+    /// Parameters for the associated channel.
+    pub fn params(&self) -> ChannelParams {
+        ChannelParams::new_tdma(self.base(), self.kind(), self.offset(), self.spacing())
+    }
+
+    /// Channel access type, which determines bandwidth and slots per carrier.
+    fn kind(&self) -> u8 { self.0[0] & 0xF }
+
+    /// Sign and magnitude of TX frequency offset from base RX frequency in steps of
+    /// channel spacing.
+    fn offset(&self) -> u16 { (slice_u24(&self.0[1..=3]) >> 10) as u16 }
+
+    /// Spacing between individual channel numbers in steps of 125Hz.
+    fn spacing(&self) -> u16 { slice_u16(&self.0[2..=3]) & 0x3FF }
 
     /// Base RX frequency in steps of 5Hz.
     fn base(&self) -> u32 { slice_u32(&self.0[4..=7]) }
 }
 
+impl<'a> TsbkEncode for ChannelParamsUpdateTdma<'a> {
+    fn encode(&self) -> TsbkFields {
+        TsbkFields::build(TsbkOpcode::ChannelParamsUpdateTdma.to_bits(),
+                          MFID_STANDARD, self.0)
+    }
+}
+
 /// Advertisement of one or more alternative control channels for the current site.
 pub struct AltControlChannel<'a>(&'a [u8]);
 
@@ -279,6 +677,12 @@ impl<'a> AltControlChannel<'a> {
     }
 }
 
+impl<'a> TsbkEncode for AltControlChannel<'a> {
+    fn encode(&self) -> TsbkFields {
+        TsbkFields::build(TsbkOpcode::AltControlChannel.to_bits(), MFID_STANDARD, self.0)
+    }
+}
+
 /// Site and RFSS information of current control channel.
 pub struct RfssStatusBroadcast<'a>(&'a [u8]);
 
@@ -297,12 +701,21 @@ impl<'a> RfssStatusBroadcast<'a> {
     pub fn rfss(&self) -> u8 { self.0[3] }
     /// Site ID of current site within RFSS.
     pub fn site(&self) -> u8 { self.0[4] }
+    /// Combined RFSS and Site ID of current site.
+    pub fn site_addr(&self) -> SiteAddress { SiteAddress::new(self.rfss(), self.site()) }
     /// Channel information for computing TX/RX frequencies.
     pub fn channel(&self) -> Channel { Channel::new(&self.0[5..=6]) }
     /// Services supported by the current site.
     pub fn services(&self) -> SystemServices { SystemServices::new(self.0[7]) }
 }
 
+impl<'a> TsbkEncode for RfssStatusBroadcast<'a> {
+    fn encode(&self) -> TsbkFields {
+        TsbkFields::build(TsbkOpcode::RfssStatusBroadcast.to_bits(),
+                          MFID_STANDARD, self.0)
+    }
+}
+
 /// WACN (Wide Area Communication Network) and System ID information of current control
 /// channel.
 pub struct NetworkStatusBroadcast<'a>(&'a [u8]);
@@ -317,12 +730,317 @@ impl<'a> NetworkStatusBroadcast<'a> {
     pub fn wacn(&self) -> u32 { slice_u24(&self.0[1..=3]) >> 4 }
     /// System ID of site within WACN.
     pub fn system(&self) -> u16 { slice_u16(&self.0[3..=4]) & 0xFFF }
+    /// Combined WACN and System ID, which uniquely identifies the network.
+    pub fn network(&self) -> NetworkIdentity {
+        NetworkIdentity::new(self.wacn(), self.system())
+    }
     /// Channel information for computing TX/RX frequencies.
     pub fn channel(&self) -> Channel { Channel::new(&self.0[5..=6]) }
     /// Services supported by the current site.
     pub fn services(&self) -> SystemServices { SystemServices::new(self.0[7]) }
 }
 
+impl<'a> TsbkEncode for NetworkStatusBroadcast<'a> {
+    fn encode(&self) -> TsbkFields {
+        TsbkFields::build(TsbkOpcode::NetworkStatusBroadcast.to_bits(),
+                          MFID_STANDARD, self.0)
+    }
+}
+
+/// 20-bit Wide Area Communication Network (WACN) ID.
+///
+/// This is formatted as 5 hex digits (for example `BEE00`.)
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct Wacn(u32);
+
+impl Wacn {
+    /// Create a new `Wacn` from the given bits, returning `None` if they don't fit in 20
+    /// bits.
+    pub fn new(bits: u32) -> Option<Wacn> {
+        if bits >> 20 == 0 { Some(Wacn(bits)) } else { None }
+    }
+
+    /// Raw 20-bit WACN ID.
+    pub fn value(&self) -> u32 { self.0 }
+}
+
+impl fmt::Display for Wacn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:05X}", self.0)
+    }
+}
+
+/// 12-bit System ID within a WACN.
+///
+/// This is formatted as 3 hex digits (for example `3A5`.)
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct SystemId(u16);
+
+impl SystemId {
+    /// Create a new `SystemId` from the given bits, returning `None` if they don't fit in
+    /// 12 bits.
+    pub fn new(bits: u16) -> Option<SystemId> {
+        if bits >> 12 == 0 { Some(SystemId(bits)) } else { None }
+    }
+
+    /// Raw 12-bit System ID.
+    pub fn value(&self) -> u16 { self.0 }
+}
+
+impl fmt::Display for SystemId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:03X}", self.0)
+    }
+}
+
+/// 8-bit RF Subsystem (RFSS) ID within a system.
+///
+/// This is formatted as 3 zero-padded decimal digits (for example `001`.)
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct Rfss(u8);
+
+impl Rfss {
+    /// Create a new `Rfss` from the given ID.
+    pub fn new(id: u8) -> Rfss { Rfss(id) }
+
+    /// Raw RFSS ID.
+    pub fn value(&self) -> u8 { self.0 }
+}
+
+impl fmt::Display for Rfss {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:03}", self.0)
+    }
+}
+
+/// 8-bit Site ID within an RFSS.
+///
+/// This is formatted as 3 zero-padded decimal digits (for example `012`.)
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct SiteId(u8);
+
+impl SiteId {
+    /// Create a new `SiteId` from the given ID.
+    pub fn new(id: u8) -> SiteId { SiteId(id) }
+
+    /// Raw Site ID.
+    pub fn value(&self) -> u8 { self.0 }
+}
+
+impl fmt::Display for SiteId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:03}", self.0)
+    }
+}
+
+/// WACN and System ID pair that uniquely identifies a trunked network.
+///
+/// This is formatted as `WACN.SYSID` in hex (for example `BEE00.3A5`), as commonly used
+/// in scanner databases.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct NetworkIdentity {
+    /// WACN ID.
+    pub wacn: Wacn,
+    /// System ID within the WACN.
+    pub system: SystemId,
+}
+
+impl NetworkIdentity {
+    /// Create a new `NetworkIdentity` from the given 20-bit WACN and 12-bit System ID.
+    pub fn new(wacn: u32, system: u16) -> NetworkIdentity {
+        NetworkIdentity {
+            wacn: Wacn::new(wacn).expect("WACN exceeds 20 bits"),
+            system: SystemId::new(system).expect("System ID exceeds 12 bits"),
+        }
+    }
+}
+
+impl fmt::Display for NetworkIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.wacn, self.system)
+    }
+}
+
+/// RFSS and Site ID pair that identifies a site within a system.
+///
+/// This is formatted as `RFSS-SITE` in zero-padded decimal (for example `001-012`.)
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct SiteAddress {
+    /// RF Subsystem ID within the System.
+    pub rfss: Rfss,
+    /// Site ID within the RFSS.
+    pub site: SiteId,
+}
+
+impl SiteAddress {
+    /// Create a new `SiteAddress` from the given RFSS and Site IDs.
+    pub fn new(rfss: u8, site: u8) -> SiteAddress {
+        SiteAddress {
+            rfss: Rfss::new(rfss),
+            site: SiteId::new(site),
+        }
+    }
+}
+
+impl fmt::Display for SiteAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.rfss, self.site)
+    }
+}
+
+/// Full identity of a site, which changes whenever the receiver moves to another site.
+///
+/// This is formatted as `WACN.SYSID RFSS-SITE` (for example `BEE00.3A5 001-012`.)
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct SiteIdentity {
+    /// Network the site belongs to.
+    pub network: NetworkIdentity,
+    /// Address of the site within the network.
+    pub site: SiteAddress,
+}
+
+impl SiteIdentity {
+    /// Create a new `SiteIdentity` from the given network and site address.
+    pub fn new(network: NetworkIdentity, site: SiteAddress) -> SiteIdentity {
+        SiteIdentity {
+            network: network,
+            site: site,
+        }
+    }
+}
+
+impl fmt::Display for SiteIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.network, self.site)
+    }
+}
+
+/// Advertisement of the services offered by the current site.
+pub struct SystemServiceBroadcast<'a>(&'a [u8]);
+
+impl<'a> SystemServiceBroadcast<'a> {
+    /// Create a new `SystemServiceBroadcast` decoder from the given payload bytes.
+    pub fn new(payload: &'a [u8]) -> Self { SystemServiceBroadcast(payload) }
+
+    /// Services currently available at the site.
+    pub fn available(&self) -> ServiceFlags {
+        ServiceFlags::new(slice_u24(&self.0[1..=3]))
+    }
+    /// Services the site is capable of supporting.
+    pub fn supported(&self) -> ServiceFlags {
+        ServiceFlags::new(slice_u24(&self.0[4..=6]))
+    }
+    /// Minimum priority level of service requests currently accepted by the site.
+    pub fn request_prio(&self) -> u8 { self.0[7] }
+}
+
+impl<'a> TsbkEncode for SystemServiceBroadcast<'a> {
+    fn encode(&self) -> TsbkFields {
+        TsbkFields::build(TsbkOpcode::SystemServiceBroadcast.to_bits(),
+                          MFID_STANDARD, self.0)
+    }
+}
+
+/// Set of services offered by a site.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ServiceFlags(u32);
+
+/// Name of each defined service bit.
+const SERVICE_NAMES: [(u32, &str); 20] = [
+    (0x800000, "group voice"),
+    (0x400000, "unit voice"),
+    (0x200000, "phone to unit voice"),
+    (0x100000, "unit to phone voice"),
+    (0x040000, "group data"),
+    (0x020000, "unit data"),
+    (0x008000, "registration"),
+    (0x004000, "group affiliation"),
+    (0x002000, "group affiliation query"),
+    (0x001000, "authentication"),
+    (0x000800, "encryption"),
+    (0x000400, "unit id check"),
+    (0x000200, "emergency alarm"),
+    (0x000100, "call alert"),
+    (0x000080, "short message"),
+    (0x000040, "status"),
+    (0x000020, "unit monitor"),
+    (0x000010, "radio check"),
+    (0x000008, "inhibit"),
+    (0x000004, "dynamic regroup"),
+];
+
+impl ServiceFlags {
+    /// Create a new `ServiceFlags` from the given 24 bits.
+    pub fn new(bits: u32) -> ServiceFlags {
+        assert!(bits >> 24 == 0);
+        ServiceFlags(bits)
+    }
+
+    /// Raw 24-bit service word.
+    pub fn bits(&self) -> u32 { self.0 }
+
+    /// Group voice calls.
+    pub fn group_voice(&self) -> bool { self.0 & 0x800000 != 0 }
+    /// Unit-to-unit voice calls.
+    pub fn unit_voice(&self) -> bool { self.0 & 0x400000 != 0 }
+    /// Telephone interconnect calls to a unit.
+    pub fn phone_to_unit_voice(&self) -> bool { self.0 & 0x200000 != 0 }
+    /// Telephone interconnect calls from a unit.
+    pub fn unit_to_phone_voice(&self) -> bool { self.0 & 0x100000 != 0 }
+    /// Group data channels.
+    pub fn group_data(&self) -> bool { self.0 & 0x040000 != 0 }
+    /// Unit-to-unit data channels.
+    pub fn unit_data(&self) -> bool { self.0 & 0x020000 != 0 }
+    /// Unit registration.
+    pub fn registration(&self) -> bool { self.0 & 0x008000 != 0 }
+    /// Talkgroup affiliation.
+    pub fn group_affiliation(&self) -> bool { self.0 & 0x004000 != 0 }
+    /// Queries of a unit's talkgroup affiliation.
+    pub fn group_affiliation_query(&self) -> bool { self.0 & 0x002000 != 0 }
+    /// Unit authentication.
+    pub fn authentication(&self) -> bool { self.0 & 0x001000 != 0 }
+    /// Encrypted calls.
+    pub fn encryption(&self) -> bool { self.0 & 0x000800 != 0 }
+    /// Unit ID checks.
+    pub fn unit_id_check(&self) -> bool { self.0 & 0x000400 != 0 }
+    /// Emergency alarms.
+    pub fn emergency_alarm(&self) -> bool { self.0 & 0x000200 != 0 }
+    /// Call alerts.
+    pub fn call_alert(&self) -> bool { self.0 & 0x000100 != 0 }
+    /// Short messages.
+    pub fn short_message(&self) -> bool { self.0 & 0x000080 != 0 }
+    /// Unit status updates and queries.
+    pub fn status(&self) -> bool { self.0 & 0x000040 != 0 }
+    /// Remote unit monitoring.
+    pub fn unit_monitor(&self) -> bool { self.0 & 0x000020 != 0 }
+    /// Radio checks.
+    pub fn radio_check(&self) -> bool { self.0 & 0x000010 != 0 }
+    /// Unit inhibit/uninhibit.
+    pub fn inhibit(&self) -> bool { self.0 & 0x000008 != 0 }
+    /// Dynamic regrouping.
+    pub fn dynamic_regroup(&self) -> bool { self.0 & 0x000004 != 0 }
+}
+
+/// Formats as a comma-separated list of the offered services, or `none`.
+impl fmt::Display for ServiceFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut names = SERVICE_NAMES.iter()
+            .filter(|&&(bit, _)| self.0 & bit != 0)
+            .map(|&(_, name)| name);
+
+        match names.next() {
+            Some(name) => f.write_str(name)?,
+            None => return f.write_str("none"),
+        }
+
+        for name in names {
+            write!(f, ", {}", name)?;
+        }
+
+        Ok(())
+    }
+}
+
 /// Registration response.
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 #[cfg_attr(feature = "ser", derive(Serialize))]
@@ -367,6 +1085,12 @@ impl<'a> UnitCallAlert<'a> {
     pub fn src_unit(&self) -> u32 { slice_u24(&self.0[5..=7]) }
 }
 
+impl<'a> TsbkEncode for UnitCallAlert<'a> {
+    fn encode(&self) -> TsbkFields {
+        TsbkFields::build(TsbkOpcode::UnitCallAlert.to_bits(), MFID_STANDARD, self.0)
+    }
+}
+
 /// Signals a target unit that a unit-to-unit all has been requested.
 pub struct UnitCallRequest<'a>(&'a [u8]);
 
@@ -382,6 +1106,143 @@ impl<'a> UnitCallRequest<'a> {
     pub fn src_unit(&self) -> u32 { slice_u24(&self.0[5..=7]) }
 }
 
+impl<'a> TsbkEncode for UnitCallRequest<'a> {
+    fn encode(&self) -> TsbkFields {
+        TsbkFields::build(TsbkOpcode::UnitCallRequest.to_bits(), MFID_STANDARD, self.0)
+    }
+}
+
+/// Command for a target unit to perform an extended function, such as a radio check or
+/// inhibit.
+pub struct ExtendedFunctionCommand<'a>(&'a [u8]);
+
+impl<'a> ExtendedFunctionCommand<'a> {
+    /// Create a new `ExtendedFunctionCommand` decoder from the given payload bytes.
+    pub fn new(payload: &'a [u8]) -> Self { ExtendedFunctionCommand(payload) }
+
+    /// Function to perform.
+    pub fn function(&self) -> ExtendedFunction {
+        ExtendedFunction::from_bits(slice_u16(&self.0[0..=1]))
+    }
+    /// Function-specific argument, which is the address of the commanding unit for radio
+    /// check, detach, and inhibit/uninhibit functions.
+    pub fn argument(&self) -> u32 { slice_u24(&self.0[2..=4]) }
+    /// Unit to perform the function.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[5..=7]) }
+}
+
+impl<'a> TsbkEncode for ExtendedFunctionCommand<'a> {
+    fn encode(&self) -> TsbkFields {
+        TsbkFields::build(TsbkOpcode::ExtendedFunctionResponse.to_bits(),
+                          MFID_STANDARD, self.0)
+    }
+}
+
+/// Function commanded by an extended function command.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "ser", derive(Serialize))]
+pub enum ExtendedFunction {
+    /// Verify the unit is present on the system.
+    RadioCheck,
+    /// Acknowledgement of a radio check.
+    RadioCheckAck,
+    /// Detach the unit from the system.
+    RadioDetach,
+    /// Acknowledgement of a radio detach.
+    RadioDetachAck,
+    /// Re-enable a previously inhibited unit.
+    RadioUninhibit,
+    /// Acknowledgement of a radio uninhibit.
+    RadioUninhibitAck,
+    /// Disable the unit so it can no longer transmit or receive.
+    RadioInhibit,
+    /// Acknowledgement of a radio inhibit.
+    RadioInhibitAck,
+    /// Unassigned or manufacturer-specific class and operand.
+    Unknown(u16),
+}
+
+impl ExtendedFunction {
+    /// Parse an extended function from the given 16-bit class and operand.
+    pub fn from_bits(bits: u16) -> ExtendedFunction {
+        use self::ExtendedFunction::*;
+
+        match bits {
+            0x0000 => RadioCheck,
+            0x0080 => RadioCheckAck,
+            0x007D => RadioDetach,
+            0x00FD => RadioDetachAck,
+            0x007E => RadioUninhibit,
+            0x00FE => RadioUninhibitAck,
+            0x007F => RadioInhibit,
+            0x00FF => RadioInhibitAck,
+            _ => Unknown(bits),
+        }
+    }
+}
+
+/// Number of data bytes in an authentication demand.
+const AUTH_DEMAND_BYTES: usize = 22;
+
+/// Challenge for a unit to authenticate with its home network, carried in the alternate
+/// multi-block format.
+///
+/// The unit must compute a response from the random seed and challenge using its
+/// provisioned key, so only the challenge itself can be inspected.
+pub struct AuthDemand<'a>(&'a [u8]);
+
+impl<'a> AuthDemand<'a> {
+    /// Create a new `AuthDemand` decoder from the given data bytes, returning
+    /// `Truncated` if there are fewer than 22.
+    pub fn new(data: &'a [u8]) -> error::Result<Self> {
+        if data.len() < AUTH_DEMAND_BYTES {
+            return Err(P25Error::Truncated {
+                needed: AUTH_DEMAND_BYTES,
+                got: data.len(),
+            });
+        }
+
+        Ok(AuthDemand(data))
+    }
+
+    /// WACN ID of the unit's home network.
+    pub fn wacn(&self) -> u32 { slice_u24(&self.0[0..=2]) >> 4 }
+    /// System ID of the unit's home network.
+    pub fn system(&self) -> u16 { slice_u16(&self.0[2..=3]) & 0xFFF }
+    /// Combined WACN and System ID of the unit's home network.
+    pub fn network(&self) -> NetworkIdentity {
+        NetworkIdentity::new(self.wacn(), self.system())
+    }
+    /// Unit that should authenticate.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[4..=6]) }
+    /// 80-bit random seed used to derive the session authentication key.
+    pub fn seed(&self) -> &[u8] { &self.0[7..=16] }
+    /// 40-bit random challenge the unit must respond to.
+    pub fn challenge(&self) -> &[u8] { &self.0[17..=21] }
+}
+
+/// Advertisement of the encryption parameters in use on the system.
+pub struct ProtectionParamBroadcast<'a>(&'a [u8]);
+
+impl<'a> ProtectionParamBroadcast<'a> {
+    /// Create a new `ProtectionParamBroadcast` decoder from the given payload bytes.
+    pub fn new(payload: &'a [u8]) -> Self { ProtectionParamBroadcast(payload) }
+
+    /// Type of crypto algorithm in use.
+    pub fn alg(&self) -> CryptoAlgorithm { CryptoAlgorithm::from_bits(self.0[2]) }
+    /// Encryption key in use.
+    pub fn key(&self) -> u16 { slice_u16(&self.0[3..=4]) }
+    /// Unit the parameters apply to.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[5..=7]) }
+}
+
+impl<'a> TsbkEncode for ProtectionParamBroadcast<'a> {
+    fn encode(&self) -> TsbkFields {
+        TsbkFields::build(TsbkOpcode::ProtectionParamBroadcast.to_bits(),
+                          MFID_STANDARD, self.0)
+    }
+}
+
 /// Alerts a unit of a call from the public phone network.
 pub struct PhoneAlert<'a>(&'a [u8]);
 
@@ -395,9 +1256,103 @@ impl<'a> PhoneAlert<'a> {
     pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[5..=7]) }
 }
 
+impl<'a> TsbkEncode for PhoneAlert<'a> {
+    fn encode(&self) -> TsbkFields {
+        TsbkFields::build(TsbkOpcode::PhoneAlert.to_bits(), MFID_STANDARD, self.0)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::collections::{BTreeMap, HashSet};
+
+    #[test]
+    fn test_service_options() {
+        for bits in 0..=0xFF {
+            let o = ServiceOptions::from(bits as u8);
+
+            assert_eq!(o.emergency(), bits & 0b1000_0000 != 0);
+            assert_eq!(o.protected(), bits & 0b0100_0000 != 0);
+            assert_eq!(o.full_duplex(), bits & 0b0010_0000 != 0);
+            assert_eq!(o.packet_switched(), bits & 0b0001_0000 != 0);
+            assert_eq!(o.prio(), bits as u8 & 0b0000_0111);
+            assert_eq!(u8::from(o), bits as u8);
+
+            let text = o.to_string();
+            assert_eq!(text.contains("emergency"), o.emergency());
+            assert_eq!(text.contains("protected"), o.protected());
+            assert_eq!(text.contains("full-duplex"), o.full_duplex());
+            assert_eq!(text.contains("packet"), o.packet_switched());
+            assert!(text.ends_with(&format!("prio {}", o.prio())));
+        }
+
+        assert_eq!(ServiceOptions::from(0b1011_0101).to_string(),
+                   "emergency full-duplex packet prio 5");
+        assert_eq!(ServiceOptions::from(0).to_string(), "prio 0");
+        assert_eq!(format!("{:?}", ServiceOptions::from(0b0100_0011)),
+                   "ServiceOptions { emergency: false, protected: true, \
+                    full_duplex: false, packet_switched: false, prio: 3 }");
+    }
+
+    #[test]
+    fn test_talkgroup_format() {
+        for &(bits, text) in [
+            (0x0000, "NONE"),
+            (0x0001, "1"),
+            (0x0002, "2"),
+            (0xFFFE, "65534"),
+            (0xFFFF, "ALL"),
+        ].iter() {
+            let tg = TalkGroup::from_bits(bits);
+            assert_eq!(tg.to_string(), text);
+            assert_eq!(text.parse::<TalkGroup>(), Ok(tg));
+            assert_eq!(u16::from(text.parse::<TalkGroup>().unwrap()), bits);
+        }
+
+        for bits in 0..=0xFFFF {
+            let tg = TalkGroup::from(bits as u16);
+            assert_eq!(u16::from(tg), bits as u16);
+            assert_eq!(tg.to_string().parse::<TalkGroup>().map(u16::from),
+                       Ok(bits as u16));
+        }
+    }
+
+    #[test]
+    fn test_talkgroup_parse() {
+        assert_eq!("0x0".parse::<TalkGroup>(), Ok(TalkGroup::Nobody));
+        assert_eq!("0x1".parse::<TalkGroup>(), Ok(TalkGroup::Default));
+        assert_eq!("0xFFFE".parse::<TalkGroup>(), Ok(TalkGroup::Other(0xFFFE)));
+        assert_eq!("0Xffff".parse::<TalkGroup>(), Ok(TalkGroup::Everbody));
+        assert_eq!("none".parse::<TalkGroup>(), Ok(TalkGroup::Nobody));
+        assert_eq!("All".parse::<TalkGroup>(), Ok(TalkGroup::Everbody));
+        assert_eq!(" 1234 ".parse::<TalkGroup>(), Ok(TalkGroup::Other(1234)));
+
+        for s in ["", "0x", "65536", "0x10000", "-1", "12a", "everybody"].iter() {
+            assert_eq!(s.parse::<TalkGroup>(), Err(ParseTalkGroupError));
+        }
+    }
+
+    #[test]
+    fn test_talkgroup_ord() {
+        assert_eq!(TalkGroup::Other(0), TalkGroup::Nobody);
+        assert_eq!(TalkGroup::Other(0xFFFF), TalkGroup::Everbody);
+        assert!(TalkGroup::Nobody < TalkGroup::Default);
+        assert!(TalkGroup::Default < TalkGroup::Other(2));
+        assert!(TalkGroup::Other(0xFFFE) < TalkGroup::Everbody);
+
+        let mut map = BTreeMap::new();
+        map.insert(TalkGroup::Everbody, "all");
+        map.insert(TalkGroup::Other(0x1234), "group");
+        map.insert(TalkGroup::Nobody, "none");
+        map.insert(TalkGroup::Other(0), "zero");
+
+        assert_eq!(map.len(), 3);
+        assert_eq!(map[&TalkGroup::Nobody], "zero");
+        assert_eq!(map.values().cloned().collect::<Vec<_>>(),
+                   vec!["zero", "group", "all"]);
+    }
+
 
     #[test]
     fn test_channel_params() {
@@ -408,6 +1363,433 @@ mod test {
         assert_eq!(p.offset, -45_000_000);
         assert_eq!(p.bandwidth, 12_500);
         assert_eq!(p.rx_freq(0b1001), 851_062_500);
+        assert_eq!(p.tx_freq(0b1001), 806_062_500);
+    }
+
+    #[test]
+    fn test_channel_params_800() {
+        // 800MHz rebanded NPSPAC: 851.0125MHz base, 12.5kHz spacing, -45MHz offset.
+        let p = ChannelParamsUpdate::new(&[
+            0b0010_0011,
+            0b00100_010,
+            0b110100_00,
+            0b01100100,
+            0x0A,
+            0x25,
+            0x15,
+            0x84,
+        ]).params();
+
+        assert_eq!(p.rx_freq(0), 851_012_500);
+        assert_eq!(p.rx_freq(1), 851_025_000);
+        assert_eq!(p.rx_freq(559), 858_000_000);
+        assert_eq!(p.tx_freq(0), 806_012_500);
+        assert_eq!(p.tx_freq(559), 813_000_000);
+    }
+
+    #[test]
+    fn test_channel_params_700() {
+        // 700MHz: 769.00625MHz base, 6.25kHz spacing, +30MHz offset.
+        let p = ChannelParamsUpdate::new(&[
+            0b0011_0011,
+            0b00100_101,
+            0b111000_00,
+            0b00110010,
+            0x09,
+            0x2A,
+            0xD2,
+            0x22,
+        ]).params();
+
+        assert_eq!(p.bandwidth, 12_500);
+        assert_eq!(p.rx_freq(0), 769_006_250);
+        assert_eq!(p.rx_freq(1), 769_012_500);
+        assert_eq!(p.tx_freq(1), 799_012_500);
+    }
+
+    #[test]
+    fn test_channel_params_large() {
+        let p = ChannelParams::new(0xFFFFFFFF, 0, 0b111111111, 0x3FF);
+        assert_eq!(p.rx_freq(0), 21_474_836_475);
+        assert_eq!(p.rx_freq(0xFFF), 21_474_836_475 + 0xFFF * 127_875);
+        assert_eq!(p.tx_freq(0), 21_474_836_475 + 63_750_000);
+    }
+
+    #[test]
+    fn test_channel_params_vu() {
+        // UHF: 450.00625MHz base, 12.5kHz spacing, +5MHz offset.
+        let buf = [
+            0b0001_0101,
+            0b1_0000110,
+            0b010000_00,
+            0b01100100,
+            0x05,
+            0x5D,
+            0x4F,
+            0x62,
+        ];
+        let u = ChannelParamsUpdateVu::new(&buf);
+        assert_eq!(u.id(), 1);
+        assert_eq!(u.offset(), 0b1_0000110010000);
+        assert_eq!(u.spacing(), 100);
+
+        let p = u.params();
+        assert_eq!(p.base, 450_006_250);
+        assert_eq!(p.spacing, 12_500);
+        assert_eq!(p.offset, 5_000_000);
+        assert_eq!(p.bandwidth, 12_500);
+        assert_eq!(p.rx_freq(0), 450_006_250);
+        assert_eq!(p.rx_freq(40), 450_506_250);
+        assert_eq!(p.tx_freq(40), 455_506_250);
+
+        // VHF: 150.9975MHz base, 7.5kHz spacing, -2.4MHz offset, 6.25kHz bandwidth.
+        let buf = [
+            0b0010_0100,
+            0b0_0000101,
+            0b000000_00,
+            0b00111100,
+            0x01,
+            0xCC,
+            0xCE,
+            0xCC,
+        ];
+        let u = ChannelParamsUpdateVu::new(&buf);
+        assert_eq!(u.id(), 2);
+
+        let p = u.params();
+        assert_eq!(p.base, 150_997_500);
+        assert_eq!(p.spacing, 7_500);
+        assert_eq!(p.offset, -2_400_000);
+        assert_eq!(p.bandwidth, 6_250);
+        assert_eq!(p.rx_freq(2), 151_012_500);
+        assert_eq!(p.tx_freq(2), 148_612_500);
+    }
+
+    #[test]
+    fn test_channel_params_vu_bandwidth() {
+        assert_eq!(ChannelParams::new_vu(0, 0b0100, 0, 0).bandwidth, 6_250);
+        assert_eq!(ChannelParams::new_vu(0, 0b0101, 0, 0).bandwidth, 12_500);
+        assert_eq!(ChannelParams::new_vu(0, 0b0000, 0, 0).bandwidth, 0);
+        assert_eq!(ChannelParams::new_vu(0, 0b1111, 0, 0).bandwidth, 0);
+    }
+
+    #[test]
+    fn test_channel_params_tdma() {
+        // 851.0125MHz base, 12.5kHz spacing, -45MHz offset, 2-slot TDMA.
+        let buf = [
+            0b0011_0011,
+            0b0_0111000,
+            0b010000_00,
+            0b01100100,
+            0x0A,
+            0x25,
+            0x15,
+            0x84,
+        ];
+        let u = ChannelParamsUpdateTdma::new(&buf);
+        assert_eq!(u.id(), 3);
+        assert_eq!(u.offset(), 3600);
+
+        let p = u.params();
+        assert_eq!(p.slots(), 2);
+        assert!(p.is_tdma());
+        assert_eq!(p.bandwidth, 12_500);
+        assert_eq!(p.offset, -45_000_000);
+
+        assert_eq!(p.rx_freq(208), 852_312_500);
+        assert_eq!(p.rx_freq(209), 852_312_500);
+        assert_eq!(p.tx_freq(209), 807_312_500);
+        assert_eq!(p.slot(208), Some(0));
+        assert_eq!(p.slot(209), Some(1));
+        assert_eq!(p.rx_freq(210), 852_325_000);
+        assert_eq!(p.slot(210), Some(0));
+
+        let mut map = ChannelParamsMap::default();
+        map.update_tdma(&u);
+        assert_eq!(map.resolve(Channel::from_bits(0x30D0)), Some((852_312_500, Some(0))));
+        assert_eq!(map.resolve(Channel::from_bits(0x30D1)), Some((852_312_500, Some(1))));
+        assert_eq!(map.resolve(Channel::from_bits(0x20D1)), None);
+    }
+
+    #[test]
+    fn test_channel_params_tdma_kinds() {
+        let slots: Vec<_> = (0..16).map(|k| ChannelParams::new_tdma(0, k, 0, 0))
+            .map(|p| (p.slots(), p.bandwidth))
+            .collect();
+
+        assert_eq!(&slots[..6], &[
+            (1, 6_250),
+            (1, 12_500),
+            (1, 6_250),
+            (2, 12_500),
+            (4, 25_000),
+            (2, 12_500),
+        ]);
+        assert!(slots[6..].iter().all(|&s| s == (1, 0)));
+
+        // 4-slot carriers.
+        let p = ChannelParams::new_tdma(170202500, 0b0100, 0, 200);
+        assert_eq!(p.rx_freq(7), 851_037_500);
+        assert_eq!(p.slot(7), Some(3));
+    }
+
+    #[test]
+    fn test_channel_params_fdma_slot() {
+        let p = ChannelParams::new(170201250, 0x64, 0b010110100, 0x32);
+        assert_eq!(p.slots(), 1);
+        assert!(!p.is_tdma());
+        assert_eq!(p.slot(9), None);
+
+        let mut map = ChannelParamsMap::default();
+        assert_eq!(map.resolve(Channel::from_bits(0x1009)), None);
+        map.0[1] = Some(p);
+        assert_eq!(map.resolve(Channel::from_bits(0x1009)), Some((851_062_500, None)));
+    }
+
+    #[test]
+    fn test_system_service_broadcast() {
+        let buf = [
+            0b00000000,
+            0b11000100,
+            0b10011000,
+            0b00000000,
+            0b11110110,
+            0b11111111,
+            0b11111100,
+            0b00000011,
+        ];
+        let s = SystemServiceBroadcast::new(&buf);
+
+        let a = s.available();
+        assert_eq!(a.bits(), 0xC49800);
+        assert!(a.group_voice());
+        assert!(a.unit_voice());
+        assert!(!a.phone_to_unit_voice());
+        assert!(!a.unit_to_phone_voice());
+        assert!(a.group_data());
+        assert!(!a.unit_data());
+        assert!(a.registration());
+        assert!(!a.group_affiliation());
+        assert!(!a.group_affiliation_query());
+        assert!(a.authentication());
+        assert!(a.encryption());
+        assert!(!a.status());
+
+        assert_eq!(s.supported().bits(), 0xF6FFFC);
+        assert_eq!(s.request_prio(), 3);
+    }
+
+    #[test]
+    fn test_service_flags_bits() {
+        let f = ServiceFlags::new(0xFFFFFF);
+        assert!(f.group_voice() && f.unit_voice() && f.phone_to_unit_voice() &&
+                f.unit_to_phone_voice() && f.group_data() && f.unit_data() &&
+                f.registration() && f.group_affiliation() &&
+                f.group_affiliation_query() && f.authentication() && f.encryption() &&
+                f.unit_id_check() && f.emergency_alarm() && f.call_alert() &&
+                f.short_message() && f.status() && f.unit_monitor() && f.radio_check() &&
+                f.inhibit() && f.dynamic_regroup());
+
+        let checks: [fn(&ServiceFlags) -> bool; 20] = [
+            ServiceFlags::group_voice,
+            ServiceFlags::unit_voice,
+            ServiceFlags::phone_to_unit_voice,
+            ServiceFlags::unit_to_phone_voice,
+            ServiceFlags::group_data,
+            ServiceFlags::unit_data,
+            ServiceFlags::registration,
+            ServiceFlags::group_affiliation,
+            ServiceFlags::group_affiliation_query,
+            ServiceFlags::authentication,
+            ServiceFlags::encryption,
+            ServiceFlags::unit_id_check,
+            ServiceFlags::emergency_alarm,
+            ServiceFlags::call_alert,
+            ServiceFlags::short_message,
+            ServiceFlags::status,
+            ServiceFlags::unit_monitor,
+            ServiceFlags::radio_check,
+            ServiceFlags::inhibit,
+            ServiceFlags::dynamic_regroup,
+        ];
+
+        // Each accessor tests exactly the bit listed in the name table.
+        for (i, &(bit, _)) in SERVICE_NAMES.iter().enumerate() {
+            let f = ServiceFlags::new(bit);
+
+            for (j, check) in checks.iter().enumerate() {
+                assert_eq!(check(&f), i == j);
+            }
+        }
+
+        // Reserved bits.
+        let f = ServiceFlags::new(0x090003);
+        assert!(checks.iter().all(|check| !check(&f)));
+    }
+
+    #[test]
+    fn test_service_flags_display() {
+        assert_eq!(ServiceFlags::new(0).to_string(), "none");
+        assert_eq!(ServiceFlags::new(0x090003).to_string(), "none");
+        assert_eq!(ServiceFlags::new(0x800000).to_string(), "group voice");
+        assert_eq!(ServiceFlags::new(0xC09800).to_string(),
+                   "group voice, unit voice, registration, authentication, encryption");
+        assert_eq!(ServiceFlags::new(0x000014).to_string(),
+                   "radio check, dynamic regroup");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_service_flags_validate() {
+        ServiceFlags::new(0x1000000);
+    }
+
+    #[test]
+    fn test_reg_response() {
+        assert_eq!(RegResponse::from_bits(0b00), RegResponse::Accept);
+        assert_eq!(RegResponse::from_bits(0b01), RegResponse::Fail);
+        assert_eq!(RegResponse::from_bits(0b10), RegResponse::Deny);
+        assert_eq!(RegResponse::from_bits(0b11), RegResponse::Refuse);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_reg_response_validate() {
+        RegResponse::from_bits(0b100);
+    }
+
+    #[test]
+    fn test_extended_function() {
+        use self::ExtendedFunction::*;
+
+        assert_eq!(ExtendedFunction::from_bits(0x0000), RadioCheck);
+        assert_eq!(ExtendedFunction::from_bits(0x0080), RadioCheckAck);
+        assert_eq!(ExtendedFunction::from_bits(0x007D), RadioDetach);
+        assert_eq!(ExtendedFunction::from_bits(0x00FD), RadioDetachAck);
+        assert_eq!(ExtendedFunction::from_bits(0x007E), RadioUninhibit);
+        assert_eq!(ExtendedFunction::from_bits(0x00FE), RadioUninhibitAck);
+        assert_eq!(ExtendedFunction::from_bits(0x007F), RadioInhibit);
+        assert_eq!(ExtendedFunction::from_bits(0x00FF), RadioInhibitAck);
+        assert_eq!(ExtendedFunction::from_bits(0x0001), Unknown(0x0001));
+        assert_eq!(ExtendedFunction::from_bits(0x907F), Unknown(0x907F));
+    }
+
+    #[test]
+    fn test_extended_function_command() {
+        // Radio check from unit 0x123456 to unit 0xABCDEF.
+        let buf = [
+            0b00000000,
+            0b00000000,
+            0x12,
+            0x34,
+            0x56,
+            0xAB,
+            0xCD,
+            0xEF,
+        ];
+        let c = ExtendedFunctionCommand::new(&buf);
+        assert_eq!(c.function(), ExtendedFunction::RadioCheck);
+        assert_eq!(c.argument(), 0x123456);
+        assert_eq!(c.dest_unit(), 0xABCDEF);
+
+        // Inhibit of unit 0x000001 commanded by console 0xFFFFFD.
+        let buf = [
+            0b00000000,
+            0b01111111,
+            0xFF,
+            0xFF,
+            0xFD,
+            0x00,
+            0x00,
+            0x01,
+        ];
+        let c = ExtendedFunctionCommand::new(&buf);
+        assert_eq!(c.function(), ExtendedFunction::RadioInhibit);
+        assert_eq!(c.argument(), 0xFFFFFD);
+        assert_eq!(c.dest_unit(), 0x000001);
+    }
+
+    #[test]
+    fn test_auth_demand() {
+        let buf = [
+            0xBE, 0xE0, 0x03, 0xA5,
+            0x01, 0x02, 0x03,
+            0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19,
+            0x20, 0x21, 0x22, 0x23, 0x24,
+            0xFF, 0xFF,
+        ];
+        let d = AuthDemand::new(&buf).unwrap();
+        assert_eq!(d.network(), NetworkIdentity::new(0xBEE00, 0x3A5));
+        assert_eq!(d.dest_unit(), 0x010203);
+        assert_eq!(d.seed(), &[0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19]);
+        assert_eq!(d.challenge(), &[0x20, 0x21, 0x22, 0x23, 0x24]);
+    }
+
+    #[test]
+    fn test_auth_demand_validate() {
+        assert_eq!(AuthDemand::new(&[0; 21]).err(),
+                   Some(P25Error::Truncated { needed: 22, got: 21 }));
+    }
+
+    #[test]
+    fn test_protection_param_broadcast() {
+        let buf = [
+            0b00000000,
+            0b00000000,
+            0b10000100,
+            0b00010010,
+            0b00110100,
+            0b11001100,
+            0b00110011,
+            0b10101010,
+        ];
+        let p = ProtectionParamBroadcast::new(&buf);
+        assert_eq!(p.alg(), CryptoAlgorithm::Aes);
+        assert_eq!(p.key(), 0x1234);
+        assert_eq!(p.dest_unit(), 0b11001100_00110011_10101010);
+
+        let buf = [0, 0, 0xAA, 0, 1, 0xFF, 0xFF, 0xFF];
+        let p = ProtectionParamBroadcast::new(&buf);
+        assert_eq!(p.alg(), CryptoAlgorithm::Other(0xAA));
+        assert_eq!(p.key(), 1);
+        assert_eq!(p.dest_unit(), 0xFFFFFF);
+    }
+
+    #[test]
+    fn test_channel_params_map() {
+        let mut map = ChannelParamsMap::default();
+        assert_eq!(map.rx_freq(Channel::from_bits(0x6009)), None);
+        assert_eq!(map.tx_freq(Channel::from_bits(0x6009)), None);
+
+        map.update(&ChannelParamsUpdate::new(&[
+            0b0110_0011,
+            0b00100_010,
+            0b110100_00,
+            0b00110010,
+            0x0A,
+            0x25,
+            0x10,
+            0xA2,
+        ]));
+
+        assert_eq!(map.rx_freq(Channel::from_bits(0x6009)), Some(851_062_500));
+        assert_eq!(map.tx_freq(Channel::from_bits(0x6009)), Some(806_062_500));
+        assert_eq!(map.rx_freq(Channel::from_bits(0x5009)), None);
+
+        map.update_vu(&ChannelParamsUpdateVu::new(&[
+            0b0101_0101,
+            0b1_0000110,
+            0b010000_00,
+            0b01100100,
+            0x05,
+            0x5D,
+            0x4F,
+            0x62,
+        ]));
+
+        assert_eq!(map.rx_freq(Channel::from_bits(0x5028)), Some(450_506_250));
+        assert_eq!(map.tx_freq(Channel::from_bits(0x5028)), Some(455_506_250));
+        assert_eq!(map.rx_freq(Channel::from_bits(0x6009)), Some(851_062_500));
     }
 
     #[test]
@@ -432,4 +1814,98 @@ mod test {
         assert_eq!(u[1].0.number(), 0b000100000001);
         assert_eq!(u[1].1, TalkGroup::Other(0b1010101010101010));
     }
+
+    #[test]
+    fn test_network_status_wacn() {
+        let buf = [
+            0b00000001,
+            0xBE,
+            0xE0,
+            0x03,
+            0xA5,
+            0b00010000,
+            0b00000001,
+            0b00110000,
+        ];
+
+        let n = NetworkStatusBroadcast::new(&buf[..]);
+        assert_eq!(n.wacn(), 0xBEE00);
+        assert_eq!(n.system(), 0x3A5);
+        assert_eq!(n.network(), NetworkIdentity::new(0xBEE00, 0x3A5));
+
+        // WACN nibble shares a byte with the System ID.
+        let buf = [0, 0x00, 0x00, 0xF0, 0x00, 0, 0, 0];
+        let n = NetworkStatusBroadcast::new(&buf[..]);
+        assert_eq!(n.wacn(), 0x0000F);
+        assert_eq!(n.system(), 0x000);
+
+        let buf = [0, 0x00, 0x00, 0x0F, 0xFF, 0, 0, 0];
+        let n = NetworkStatusBroadcast::new(&buf[..]);
+        assert_eq!(n.wacn(), 0x00000);
+        assert_eq!(n.system(), 0xFFF);
+    }
+
+    #[test]
+    fn test_network_identity_display() {
+        assert_eq!(format!("{}", NetworkIdentity::new(0xBEE00, 0x3A5)), "BEE00.3A5");
+        assert_eq!(format!("{}", NetworkIdentity::new(0x1, 0x2)), "00001.002");
+        assert_eq!(format!("{}", NetworkIdentity::new(0xFFFFF, 0xFFF)), "FFFFF.FFF");
+    }
+
+    #[test]
+    fn test_site_address_display() {
+        assert_eq!(format!("{}", SiteAddress::new(1, 12)), "001-012");
+        assert_eq!(format!("{}", SiteAddress::new(0, 0)), "000-000");
+        assert_eq!(format!("{}", SiteAddress::new(255, 255)), "255-255");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_network_identity_validate() {
+        NetworkIdentity::new(0x100000, 0);
+    }
+
+    #[test]
+    fn test_identifiers() {
+        assert_eq!(Wacn::new(0xFFFFF).map(|w| w.value()), Some(0xFFFFF));
+        assert_eq!(Wacn::new(0x100000), None);
+        assert_eq!(SystemId::new(0xFFF).map(|s| s.value()), Some(0xFFF));
+        assert_eq!(SystemId::new(0x1000), None);
+
+        assert_eq!(Wacn::new(0xBEE00).unwrap().to_string(), "BEE00");
+        assert_eq!(Wacn::new(0xA).unwrap().to_string(), "0000A");
+        assert_eq!(SystemId::new(0x3A5).unwrap().to_string(), "3A5");
+        assert_eq!(SystemId::new(0x1).unwrap().to_string(), "001");
+        assert_eq!(Rfss::new(1).to_string(), "001");
+        assert_eq!(SiteId::new(255).to_string(), "255");
+    }
+
+    #[test]
+    fn test_site_identity() {
+        let a = SiteIdentity::new(NetworkIdentity::new(0xBEE00, 0x3A5),
+                                  SiteAddress::new(1, 12));
+        assert_eq!(a.to_string(), "BEE00.3A5 001-012");
+
+        let mut sites = HashSet::new();
+        sites.insert(a);
+        sites.insert(SiteIdentity::new(NetworkIdentity::new(0xBEE00, 0x3A5),
+                                       SiteAddress::new(1, 12)));
+        assert_eq!(sites.len(), 1);
+
+        let b = SiteIdentity::new(NetworkIdentity::new(0xBEE00, 0x3A5),
+                                  SiteAddress::new(1, 13));
+        assert!(a != b);
+        sites.insert(b);
+        assert_eq!(sites.len(), 2);
+
+        let c = SiteIdentity::new(NetworkIdentity::new(0xBEE00, 0x3A6),
+                                  SiteAddress::new(1, 12));
+        assert!(a != c);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_system_id_validate() {
+        NetworkIdentity::new(0, 0x1000);
+    }
 }
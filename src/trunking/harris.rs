@@ -0,0 +1,83 @@
+//! Decode Harris (MFID 0xA4) TSBK packets.
+
+use trunking::encode::TsbkEncode;
+use trunking::fields::TalkGroup;
+use trunking::tsbk::TsbkFields;
+use trunking::vendor::MFID_HARRIS;
+use util::{slice_u16, slice_u24};
+
+/// Harris TSBK packet decoded based on its manufacturer-specific opcode.
+pub enum HarrisTsbk<'a> {
+    GroupRegroupCommand(GroupRegroupCommand<'a>),
+}
+
+impl<'a> HarrisTsbk<'a> {
+    /// Try to decode the given payload based on the given 6-bit Harris opcode, returning
+    /// `None` if the opcode has no decoder.
+    pub fn new(opcode: u8, payload: &'a [u8]) -> Option<HarrisTsbk<'a>> {
+        match opcode {
+            0x30 => Some(HarrisTsbk::GroupRegroupCommand(GroupRegroupCommand::new(payload))),
+            _ => None,
+        }
+    }
+}
+
+/// Command to add a talkgroup or unit to (or remove it from) a regrouped supergroup,
+/// with the encryption key to use on the supergroup.
+pub struct GroupRegroupCommand<'a>(&'a [u8]);
+
+impl<'a> GroupRegroupCommand<'a> {
+    /// Create a new `GroupRegroupCommand` decoder from the given payload bytes.
+    pub fn new(payload: &'a [u8]) -> Self { GroupRegroupCommand(payload) }
+
+    /// Whether the target is a talkgroup (otherwise it's a unit.)
+    pub fn group_target(&self) -> bool { self.0[0] >> 7 == 1 }
+    /// Whether the target is being added to the supergroup (otherwise it's being
+    /// removed.)
+    pub fn activate(&self) -> bool { self.0[0] >> 6 & 1 == 1 }
+    /// Whether traffic on the supergroup is encrypted.
+    pub fn protected(&self) -> bool { self.0[0] >> 5 & 1 == 1 }
+    /// Supergroup that carries the regrouped traffic.
+    pub fn supergroup(&self) -> TalkGroup { TalkGroup::new(&self.0[1..=2]) }
+    /// Encryption key used on the supergroup.
+    pub fn key(&self) -> u16 { slice_u16(&self.0[3..=4]) }
+    /// Talkgroup or unit being regrouped.
+    pub fn target(&self) -> u32 { slice_u24(&self.0[5..=7]) }
+}
+
+impl<'a> TsbkEncode for GroupRegroupCommand<'a> {
+    fn encode(&self) -> TsbkFields { TsbkFields::build(0x30, MFID_HARRIS, self.0) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_group_regroup_command() {
+        let buf = [
+            0b11000000,
+            0x12,
+            0x34,
+            0xAB,
+            0xCD,
+            0x00,
+            0x56,
+            0x78,
+        ];
+
+        match HarrisTsbk::new(0x30, &buf) {
+            Some(HarrisTsbk::GroupRegroupCommand(c)) => {
+                assert!(c.group_target());
+                assert!(c.activate());
+                assert!(!c.protected());
+                assert_eq!(c.supergroup(), TalkGroup::Other(0x1234));
+                assert_eq!(c.key(), 0xABCD);
+                assert_eq!(c.target(), 0x005678);
+            },
+            _ => panic!(),
+        }
+
+        assert!(HarrisTsbk::new(0x00, &buf).is_none());
+    }
+}
@@ -0,0 +1,280 @@
+//! Measure the health of a control channel to decide when to abandon it.
+
+use std::collections::VecDeque;
+
+use error::P25Error;
+use message::receiver::MessageEvent;
+
+/// Outcome of a decode attempt recorded in the health window.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Outcome {
+    /// A valid TSBK was decoded.
+    Valid,
+    /// A TSBK failed to decode or had a bad CRC.
+    Failed,
+    /// Frame sync was lost or the NID following it was corrupted.
+    SyncMiss,
+}
+
+/// Limits a control channel must stay within to be considered healthy.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct HealthThresholds {
+    /// Minimum valid TSBKs within the window.
+    pub min_tsbks: usize,
+    /// Maximum ratio of failed TSBKs to all TSBKs within the window.
+    pub max_failure_ratio: f32,
+    /// Maximum sync misses within the window.
+    pub max_sync_misses: usize,
+    /// Maximum time since the last valid TSBK.
+    pub max_silence: u64,
+}
+
+/// Health of a control channel over the most recent window.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct HealthSnapshot {
+    /// Length of the window.
+    pub window: u64,
+    /// Valid TSBKs decoded within the window.
+    pub tsbks: usize,
+    /// TSBKs that failed to decode within the window.
+    pub failures: usize,
+    /// Sync misses within the window.
+    pub sync_misses: usize,
+    /// Time since the last valid TSBK, if one was ever received.
+    pub since_valid: Option<u64>,
+}
+
+impl HealthSnapshot {
+    /// Valid TSBKs decoded per time unit over the window.
+    pub fn tsbk_rate(&self) -> f32 { self.tsbks as f32 / self.window as f32 }
+
+    /// Ratio of failed TSBKs to all TSBKs within the window, or 0 if there were none.
+    pub fn failure_ratio(&self) -> f32 {
+        match self.tsbks + self.failures {
+            0 => 0.0,
+            total => self.failures as f32 / total as f32,
+        }
+    }
+
+    /// Whether the channel is within all the given limits.
+    pub fn is_healthy(&self, limits: &HealthThresholds) -> bool {
+        match self.since_valid {
+            Some(t) if t <= limits.max_silence => {},
+            _ => return false,
+        }
+
+        self.tsbks >= limits.min_tsbks &&
+            self.failure_ratio() <= limits.max_failure_ratio &&
+            self.sync_misses <= limits.max_sync_misses
+    }
+}
+
+/// Accumulates decode results of a control channel over a sliding window.
+///
+/// Times are in arbitrary caller-defined units, and only results received within
+/// `window` of the current time are counted. The accumulator should be reset when the
+/// receiver is retuned.
+pub struct ControlChannelHealth {
+    /// Outcomes within the window, oldest first.
+    outcomes: VecDeque<(u64, Outcome)>,
+    /// Length of the window.
+    window: u64,
+    /// Time of the last valid TSBK.
+    last_valid: Option<u64>,
+}
+
+impl ControlChannelHealth {
+    /// Create a new `ControlChannelHealth` over a sliding window of the given length.
+    pub fn new(window: u64) -> ControlChannelHealth {
+        assert!(window > 0);
+
+        ControlChannelHealth {
+            outcomes: VecDeque::new(),
+            window: window,
+            last_valid: None,
+        }
+    }
+
+    /// Record the given receiver event, received at the given time.
+    pub fn feed(&mut self, event: &MessageEvent, time: u64) {
+        match *event {
+            MessageEvent::TrunkingControl(_) | MessageEvent::TrunkingMultiBlock(_) => {
+                self.last_valid = Some(time);
+                self.record(Outcome::Valid, time);
+            },
+            MessageEvent::Error(P25Error::UnknownNid) =>
+                self.record(Outcome::SyncMiss, time),
+            MessageEvent::Error(_) => self.record(Outcome::Failed, time),
+            _ => {},
+        }
+    }
+
+    /// Record that frame sync was expected but not found at the given time.
+    pub fn sync_miss(&mut self, time: u64) { self.record(Outcome::SyncMiss, time) }
+
+    /// Forget all recorded results, such as after retuning.
+    pub fn reset(&mut self) {
+        self.outcomes.clear();
+        self.last_valid = None;
+    }
+
+    /// Summarize the results within the window ending at the given time.
+    pub fn snapshot(&self, now: u64) -> HealthSnapshot {
+        let count = |kind| self.outcomes.iter()
+            .filter(|&&(t, o)| o == kind && self.within(t, now))
+            .count();
+
+        HealthSnapshot {
+            window: self.window,
+            tsbks: count(Outcome::Valid),
+            failures: count(Outcome::Failed),
+            sync_misses: count(Outcome::SyncMiss),
+            since_valid: self.last_valid.map(|t| now.saturating_sub(t)),
+        }
+    }
+
+    /// Whether the channel is within the given limits at the given time.
+    pub fn is_healthy(&self, now: u64, limits: &HealthThresholds) -> bool {
+        self.snapshot(now).is_healthy(limits)
+    }
+
+    /// Record the given outcome and drop outcomes that left the window.
+    fn record(&mut self, outcome: Outcome, time: u64) {
+        self.outcomes.push_back((time, outcome));
+
+        while let Some(&(t, _)) = self.outcomes.front() {
+            if self.within(t, time) {
+                break;
+            }
+
+            self.outcomes.pop_front();
+        }
+    }
+
+    /// Whether the given time is within the window ending at `now`.
+    fn within(&self, time: u64, now: u64) -> bool {
+        now.saturating_sub(time) < self.window
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use trunking::tsbk::{TsbkFields, TsbkOpcode};
+    use trunking::vendor::MFID_STANDARD;
+
+    const LIMITS: HealthThresholds = HealthThresholds {
+        min_tsbks: 20,
+        max_failure_ratio: 0.25,
+        max_sync_misses: 3,
+        max_silence: 2,
+    };
+
+    fn tsbk() -> MessageEvent {
+        MessageEvent::TrunkingControl(TsbkFields::build(
+            TsbkOpcode::RfssStatusBroadcast.to_bits(), MFID_STANDARD,
+            &[0x01, 0x13, 0xA5, 0x01, 0x07, 0x10, 0x20, 0x70]))
+    }
+
+    fn crc() -> MessageEvent {
+        MessageEvent::Error(P25Error::CrcMismatch { received: 0, computed: 1 })
+    }
+
+    #[test]
+    fn test_good() {
+        let mut h = ControlChannelHealth::new(5);
+
+        // Roughly 30 TSBKs per time unit with an occasional error.
+        for t in 0..10 {
+            for i in 0..30 {
+                h.feed(&if i == 0 { crc() } else { tsbk() }, t);
+            }
+        }
+
+        let s = h.snapshot(9);
+        assert_eq!(s.tsbks, 5 * 29);
+        assert_eq!(s.failures, 5);
+        assert_eq!(s.sync_misses, 0);
+        assert_eq!(s.since_valid, Some(0));
+        assert_eq!(s.tsbk_rate(), 29.0);
+        assert!((s.failure_ratio() - 1.0 / 30.0).abs() < 1e-6);
+        assert!(s.is_healthy(&LIMITS));
+        assert!(h.is_healthy(9, &LIMITS));
+
+        // Other events don't count.
+        h.feed(&MessageEvent::LowSpeedDataFragment(0), 9);
+        assert_eq!(h.snapshot(9), s);
+    }
+
+    #[test]
+    fn test_fading() {
+        let mut h = ControlChannelHealth::new(5);
+
+        // The share of failed TSBKs grows by 10% each time unit.
+        for t in 0..10 {
+            for i in 0..10 {
+                h.feed(&if i < t { crc() } else { tsbk() }, t);
+            }
+
+            if t >= 6 {
+                h.sync_miss(t);
+            }
+
+            // Still healthy midway through.
+            if t == 4 {
+                let s = h.snapshot(4);
+                assert_eq!(s.tsbks, 40);
+                assert_eq!(s.failures, 10);
+                assert!(s.is_healthy(&LIMITS));
+            }
+        }
+
+        // Only the last window counts by the end.
+        let s = h.snapshot(9);
+        assert_eq!(s.tsbks, 15);
+        assert_eq!(s.failures, 35);
+        assert_eq!(s.sync_misses, 4);
+        assert!(!s.is_healthy(&LIMITS));
+
+        // Fresh results are counted after a reset.
+        h.reset();
+        assert_eq!(h.snapshot(9).failures, 0);
+        assert_eq!(h.snapshot(9).since_valid, None);
+    }
+
+    #[test]
+    fn test_dead() {
+        let mut h = ControlChannelHealth::new(5);
+
+        // Never received anything.
+        assert!(!h.is_healthy(0, &LIMITS));
+
+        for t in 0..5 {
+            for _ in 0..30 {
+                h.feed(&tsbk(), t);
+            }
+        }
+
+        assert!(h.is_healthy(4, &LIMITS));
+
+        // The channel goes silent, with only corrupted NIDs.
+        for t in 5..10 {
+            h.feed(&MessageEvent::Error(P25Error::UnknownNid), t);
+
+            let s = h.snapshot(t);
+            assert_eq!(s.since_valid, Some(t - 4));
+
+            match t {
+                5 | 6 => assert!(s.is_healthy(&LIMITS)),
+                _ => assert!(!s.is_healthy(&LIMITS)),
+            }
+        }
+
+        let s = h.snapshot(9);
+        assert_eq!(s.tsbks, 0);
+        assert_eq!(s.sync_misses, 5);
+        assert_eq!(s.tsbk_rate(), 0.0);
+        assert_eq!(s.failure_ratio(), 0.0);
+        assert!(!s.is_healthy(&LIMITS));
+    }
+}
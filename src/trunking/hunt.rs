@@ -0,0 +1,309 @@
+//! Find a live control channel among a set of candidate frequencies.
+
+use std::vec::Vec;
+
+use message::receiver::MessageEvent;
+use trunking::decode::TsbkMessage;
+use trunking::fields::{Channel, ChannelParamsMap};
+
+/// Action requested by the hunter.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum HuntEvent {
+    /// Tune to the given frequency (Hz), then report back with `tuned`.
+    Tune(u64),
+    /// A control channel was found on the given frequency (Hz).
+    Locked(u64),
+}
+
+/// State machine for hunting for a control channel.
+///
+/// The application tunes to the current target frequency, reports when it's tuned, and
+/// then feeds in receiver events and periodic time ticks. Lock is declared after a
+/// number of valid TSBKs are received, and the next candidate is tried if that doesn't
+/// happen within the timeout. Times are in arbitrary caller-defined units.
+///
+/// Alternate control channels and adjacent sites heard along the way are tried before
+/// the configured candidates, in that order, once their channel parameters are known.
+pub struct ControlChannelHunter {
+    /// Configured candidate frequencies.
+    candidates: Vec<u64>,
+    /// Alternate control channels of sites heard so far.
+    alts: Vec<Channel>,
+    /// Control channels of adjacent sites heard so far.
+    adjacent: Vec<Channel>,
+    /// Channel parameters heard so far, used to resolve hinted channels.
+    params: ChannelParamsMap,
+    /// Frequencies tried in the current pass over all candidates.
+    tried: Vec<u64>,
+    /// Frequency currently being hunted.
+    target: u64,
+    /// Frequency that was tuned and the time it was tuned, if any.
+    tuned: Option<(u64, u64)>,
+    /// Valid TSBKs received since tuning.
+    valid: usize,
+    /// Valid TSBKs required to declare lock.
+    lock_count: usize,
+    /// Time to wait for lock before moving to the next candidate.
+    timeout: u64,
+    /// Whether a control channel is locked.
+    locked: bool,
+    /// Frequency of the control channel most recently locked, which is tried last.
+    last_lock: Option<u64>,
+}
+
+impl ControlChannelHunter {
+    /// Create a new `ControlChannelHunter` over the given candidate frequencies (Hz),
+    /// which declares lock after `lock_count` valid TSBKs and moves on after `timeout`
+    /// without lock.
+    pub fn new(candidates: Vec<u64>, lock_count: usize, timeout: u64)
+        -> ControlChannelHunter
+    {
+        assert!(!candidates.is_empty());
+        assert!(lock_count > 0);
+
+        let target = candidates[0];
+
+        ControlChannelHunter {
+            candidates: candidates,
+            alts: vec![],
+            adjacent: vec![],
+            params: ChannelParamsMap::default(),
+            tried: vec![target],
+            target: target,
+            tuned: None,
+            valid: 0,
+            lock_count: lock_count,
+            timeout: timeout,
+            locked: false,
+            last_lock: None,
+        }
+    }
+
+    /// Frequency (Hz) that should be tuned.
+    pub fn target(&self) -> u64 { self.target }
+
+    /// Whether a control channel is currently locked.
+    pub fn locked(&self) -> bool { self.locked }
+
+    /// Notify that the given frequency (Hz) was tuned at the given time.
+    pub fn tuned(&mut self, freq: u64, time: u64) {
+        self.tuned = Some((freq, time));
+        self.valid = 0;
+        self.locked = false;
+    }
+
+    /// Handle the given receiver event, returning `Locked` if it completes a lock.
+    pub fn feed(&mut self, event: &MessageEvent) -> Option<HuntEvent> {
+        let tsbk = match *event {
+            MessageEvent::TrunkingControl(ref tsbk) => tsbk,
+            _ => return None,
+        };
+
+        match TsbkMessage::new(tsbk) {
+            TsbkMessage::ChannelParamsUpdate(u) => self.params.update(&u),
+            TsbkMessage::ChannelParamsUpdateVu(u) => self.params.update_vu(&u),
+            TsbkMessage::ChannelParamsUpdateTdma(u) => self.params.update_tdma(&u),
+            TsbkMessage::AltControlChannel(a) => for &(ch, _) in a.alts().iter() {
+                add_hint(&mut self.alts, ch);
+            },
+            TsbkMessage::AdjacentSite(a) => add_hint(&mut self.adjacent, a.channel()),
+            _ => {},
+        }
+
+        let freq = match self.tuned {
+            Some((freq, _)) if !self.locked => freq,
+            _ => return None,
+        };
+
+        self.valid += 1;
+
+        if self.valid < self.lock_count {
+            return None;
+        }
+
+        self.locked = true;
+        self.last_lock = Some(freq);
+        // Start a fresh pass the next time lock is lost.
+        self.tried.clear();
+
+        Some(HuntEvent::Locked(freq))
+    }
+
+    /// Check for timeout at the given time, returning `Tune` with the next candidate if
+    /// the current one timed out without lock.
+    pub fn tick(&mut self, time: u64) -> Option<HuntEvent> {
+        if self.locked {
+            return None;
+        }
+
+        match self.tuned {
+            Some((_, start)) if time.saturating_sub(start) > self.timeout => {},
+            _ => return None,
+        }
+
+        Some(self.advance())
+    }
+
+    /// Notify that the locked control channel was lost, returning `Tune` with the next
+    /// candidate to try. The lost channel is retried only after all others.
+    pub fn lost(&mut self) -> HuntEvent {
+        self.locked = false;
+        self.advance()
+    }
+
+    /// Move to the next untried candidate, starting a new pass once all have been tried.
+    fn advance(&mut self) -> HuntEvent {
+        let order = self.order();
+
+        let next = match order.iter().find(|f| !self.tried.contains(f)) {
+            Some(&f) => f,
+            None => {
+                self.tried.clear();
+                order[0]
+            },
+        };
+
+        self.tried.push(next);
+        self.target = next;
+        self.tuned = None;
+
+        HuntEvent::Tune(next)
+    }
+
+    /// Compute the deduplicated order candidates should be tried in.
+    fn order(&self) -> Vec<u64> {
+        let mut freqs = vec![];
+
+        let hinted = self.alts.iter()
+            .chain(self.adjacent.iter())
+            .filter_map(|&ch| self.params.rx_freq(ch));
+
+        for f in hinted.chain(self.candidates.iter().cloned()) {
+            if !freqs.contains(&f) && Some(f) != self.last_lock {
+                freqs.push(f);
+            }
+        }
+
+        freqs.extend(self.last_lock);
+
+        freqs
+    }
+}
+
+/// Add the given channel to the hints if it isn't already present.
+fn add_hint(hints: &mut Vec<Channel>, ch: Channel) {
+    if !hints.contains(&ch) {
+        hints.push(ch);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use error::P25Error;
+    use trunking::tsbk::{TsbkFields, TsbkOpcode};
+    use trunking::vendor::MFID_STANDARD;
+
+    fn tsbk(opcode: TsbkOpcode, payload: &[u8]) -> MessageEvent {
+        MessageEvent::TrunkingControl(TsbkFields::build(opcode.to_bits(), MFID_STANDARD,
+                                                        payload))
+    }
+
+    fn status() -> MessageEvent {
+        tsbk(TsbkOpcode::RfssStatusBroadcast, &[0x01, 0x13, 0xA5, 0x01, 0x07, 0x10, 0x20,
+                                                0x70])
+    }
+
+    fn params() -> MessageEvent {
+        // ID 1: base 851.00625MHz, spacing 6.25kHz.
+        tsbk(TsbkOpcode::ChannelParamsUpdate, &[
+            0b0001_0011,
+            0b00100_010,
+            0b110100_00,
+            0b00110010,
+            0x0A,
+            0x25,
+            0x10,
+            0xA2,
+        ])
+    }
+
+    #[test]
+    fn test_hunt() {
+        let mut h = ControlChannelHunter::new(vec![851_000_000, 852_000_000, 853_000_000],
+                                              3, 10);
+        assert_eq!(h.target(), 851_000_000);
+
+        // Nothing happens until tuned.
+        assert_eq!(h.tick(100), None);
+
+        // First candidate is dead air.
+        h.tuned(851_000_000, 0);
+        assert_eq!(h.feed(&MessageEvent::Error(P25Error::UnknownNid)), None);
+        assert_eq!(h.tick(10), None);
+        assert_eq!(h.tick(11), Some(HuntEvent::Tune(852_000_000)));
+        assert_eq!(h.target(), 852_000_000);
+
+        // Second candidate only produces errors.
+        h.tuned(852_000_000, 11);
+        let err = P25Error::CrcMismatch { received: 0, computed: 1 };
+        assert_eq!(h.feed(&MessageEvent::Error(err)), None);
+        assert_eq!(h.tick(22), Some(HuntEvent::Tune(853_000_000)));
+
+        // Third candidate is live.
+        h.tuned(853_000_000, 22);
+        assert_eq!(h.feed(&status()), None);
+        assert_eq!(h.feed(&status()), None);
+        assert!(!h.locked());
+        assert_eq!(h.feed(&status()), Some(HuntEvent::Locked(853_000_000)));
+        assert!(h.locked());
+
+        // Further TSBKs and ticks don't affect the lock.
+        assert_eq!(h.feed(&status()), None);
+        assert_eq!(h.tick(1000), None);
+
+        // Losing lock starts a fresh pass.
+        assert_eq!(h.lost(), HuntEvent::Tune(851_000_000));
+        h.tuned(851_000_000, 1000);
+        assert_eq!(h.tick(1011), Some(HuntEvent::Tune(852_000_000)));
+        h.tuned(852_000_000, 1011);
+        assert_eq!(h.tick(1022), Some(HuntEvent::Tune(853_000_000)));
+        h.tuned(853_000_000, 1022);
+        assert_eq!(h.tick(1033), Some(HuntEvent::Tune(851_000_000)));
+    }
+
+    #[test]
+    fn test_hints() {
+        let mut h = ControlChannelHunter::new(vec![851_000_000, 852_000_000], 1, 10);
+
+        h.tuned(851_000_000, 0);
+        assert_eq!(h.feed(&status()), Some(HuntEvent::Locked(851_000_000)));
+
+        // Hints heard before channel parameters are resolved once they arrive.
+        h.feed(&tsbk(TsbkOpcode::AdjacentSite, &[0x01, 0x13, 0xA5, 0x02, 0x01, 0x10, 0x40,
+                                                  0x70]));
+        h.feed(&tsbk(TsbkOpcode::AltControlChannel, &[0x01, 0x07, 0x10, 0x20, 0x70, 0x10,
+                                                       0x30, 0x70]));
+        h.feed(&params());
+
+        // Alternate control channels are tried first, then adjacent sites, then the
+        // remaining configured candidates.
+        assert_eq!(h.lost(), HuntEvent::Tune(851_206_250));
+        h.tuned(851_206_250, 0);
+        assert_eq!(h.tick(11), Some(HuntEvent::Tune(851_306_250)));
+        h.tuned(851_306_250, 11);
+        assert_eq!(h.tick(22), Some(HuntEvent::Tune(851_406_250)));
+        h.tuned(851_406_250, 22);
+        assert_eq!(h.tick(33), Some(HuntEvent::Tune(852_000_000)));
+        h.tuned(852_000_000, 33);
+        assert_eq!(h.tick(44), Some(HuntEvent::Tune(851_000_000)));
+        h.tuned(851_000_000, 44);
+        assert_eq!(h.tick(55), Some(HuntEvent::Tune(851_206_250)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_no_candidates() {
+        ControlChannelHunter::new(vec![], 1, 10);
+    }
+}
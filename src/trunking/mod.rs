@@ -1,4 +1,15 @@
 //! Trunking control message decoding.
 
+pub mod ambt;
+pub mod calls;
+pub mod decode;
+pub mod encode;
 pub mod fields;
+pub mod harris;
+pub mod health;
+pub mod hunt;
+pub mod motorola;
+pub mod policy;
+pub mod site;
 pub mod tsbk;
+pub mod vendor;
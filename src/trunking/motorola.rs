@@ -0,0 +1,351 @@
+//! Decode Motorola (MFID 0x90) TSBK packets and track patched talkgroups.
+
+use std::vec::Vec;
+
+use trunking::encode::TsbkEncode;
+use trunking::fields::{Channel, TalkGroup};
+use trunking::tsbk::TsbkFields;
+use trunking::vendor::MFID_MOTOROLA;
+use util::slice_u24;
+
+/// Motorola TSBK packet decoded based on its manufacturer-specific opcode.
+pub enum MotorolaTsbk<'a> {
+    PatchGroupAdd(PatchGroupAdd<'a>),
+    PatchGroupDelete(PatchGroupDelete<'a>),
+    SystemLoad(SystemLoad<'a>),
+    BaseStationId(BaseStationId<'a>),
+}
+
+impl<'a> MotorolaTsbk<'a> {
+    /// Try to decode the given payload based on the given 6-bit Motorola opcode,
+    /// returning `None` if the opcode has no decoder.
+    pub fn new(opcode: u8, payload: &'a [u8]) -> Option<MotorolaTsbk<'a>> {
+        match opcode {
+            0x00 => Some(MotorolaTsbk::PatchGroupAdd(PatchGroupAdd::new(payload))),
+            0x01 => Some(MotorolaTsbk::PatchGroupDelete(PatchGroupDelete::new(payload))),
+            0x09 => Some(MotorolaTsbk::SystemLoad(SystemLoad::new(payload))),
+            0x0B => Some(MotorolaTsbk::BaseStationId(BaseStationId::new(payload))),
+            _ => None,
+        }
+    }
+}
+
+/// Adds up to three talkgroups to a patch, so traffic on any of them is carried on the
+/// patch's supergroup.
+pub struct PatchGroupAdd<'a>(&'a [u8]);
+
+impl<'a> PatchGroupAdd<'a> {
+    /// Create a new `PatchGroupAdd` decoder from the given payload bytes.
+    pub fn new(payload: &'a [u8]) -> Self { PatchGroupAdd(payload) }
+
+    /// Supergroup that carries traffic for the patch.
+    pub fn supergroup(&self) -> TalkGroup { TalkGroup::new(&self.0[0..=1]) }
+    /// Talkgroups added to the patch, where unused entries hold `Nobody` or the
+    /// supergroup itself.
+    pub fn groups(&self) -> [TalkGroup; 3] { patch_groups(self.0) }
+}
+
+impl<'a> TsbkEncode for PatchGroupAdd<'a> {
+    fn encode(&self) -> TsbkFields { TsbkFields::build(0x00, MFID_MOTOROLA, self.0) }
+}
+
+/// Removes up to three talkgroups from a patch.
+pub struct PatchGroupDelete<'a>(&'a [u8]);
+
+impl<'a> PatchGroupDelete<'a> {
+    /// Create a new `PatchGroupDelete` decoder from the given payload bytes.
+    pub fn new(payload: &'a [u8]) -> Self { PatchGroupDelete(payload) }
+
+    /// Supergroup that carries traffic for the patch.
+    pub fn supergroup(&self) -> TalkGroup { TalkGroup::new(&self.0[0..=1]) }
+    /// Talkgroups removed from the patch, where unused entries hold `Nobody` or the
+    /// supergroup itself.
+    pub fn groups(&self) -> [TalkGroup; 3] { patch_groups(self.0) }
+}
+
+impl<'a> TsbkEncode for PatchGroupDelete<'a> {
+    fn encode(&self) -> TsbkFields { TsbkFields::build(0x01, MFID_MOTOROLA, self.0) }
+}
+
+/// Parse the three member talkgroups of a patch group payload.
+fn patch_groups(payload: &[u8]) -> [TalkGroup; 3] {
+    [
+        TalkGroup::new(&payload[2..=3]),
+        TalkGroup::new(&payload[4..=5]),
+        TalkGroup::new(&payload[6..=7]),
+    ]
+}
+
+/// System loading announcement, which reports how busy the site's traffic channels are.
+pub struct SystemLoad<'a>(&'a [u8]);
+
+impl<'a> SystemLoad<'a> {
+    /// Create a new `SystemLoad` decoder from the given payload bytes.
+    pub fn new(payload: &'a [u8]) -> Self { SystemLoad(payload) }
+
+    /// Share of traffic channels in use, as a percentage.
+    pub fn load(&self) -> u8 { self.0[0] }
+    /// Number of channel requests waiting for a free traffic channel.
+    pub fn queued(&self) -> u8 { self.0[1] }
+    /// Number of traffic channels at the site.
+    pub fn channels(&self) -> u8 { self.0[2] }
+    /// Number of traffic channels currently carrying calls.
+    pub fn busy(&self) -> u8 { self.0[3] }
+    /// Control channel of the site.
+    pub fn channel(&self) -> Channel { Channel::new(&self.0[6..=7]) }
+}
+
+impl<'a> TsbkEncode for SystemLoad<'a> {
+    fn encode(&self) -> TsbkFields { TsbkFields::build(0x09, MFID_MOTOROLA, self.0) }
+}
+
+/// Base station identification, which announces the site's callsign.
+pub struct BaseStationId<'a>(&'a [u8]);
+
+impl<'a> BaseStationId<'a> {
+    /// Create a new `BaseStationId` decoder from the given payload bytes.
+    pub fn new(payload: &'a [u8]) -> Self { BaseStationId(payload) }
+
+    /// Callsign of the site, as 8 ASCII characters.
+    pub fn callsign(&self) -> [u8; 8] {
+        let bits = (slice_u24(&self.0[0..=2]) as u64) << 24 |
+                   slice_u24(&self.0[3..=5]) as u64;
+
+        let mut chars = [0; 8];
+
+        for (i, c) in chars.iter_mut().enumerate() {
+            // Each 6-bit character is offset from '+'.
+            *c = (bits >> (42 - 6 * i) & 0x3F) as u8 + b'+';
+        }
+
+        chars
+    }
+    /// Control channel of the site.
+    pub fn channel(&self) -> Channel { Channel::new(&self.0[6..=7]) }
+}
+
+impl<'a> TsbkEncode for BaseStationId<'a> {
+    fn encode(&self) -> TsbkFields { TsbkFields::build(0x0B, MFID_MOTOROLA, self.0) }
+}
+
+/// Talkgroups patched together onto a supergroup.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Patch {
+    /// Supergroup that carries traffic for the patch.
+    pub supergroup: TalkGroup,
+    /// Talkgroups included in the patch.
+    pub members: Vec<TalkGroup>,
+}
+
+/// Tracks active patches from Motorola patch group add/delete commands.
+#[derive(Default)]
+pub struct PatchTable {
+    patches: Vec<Patch>,
+}
+
+impl PatchTable {
+    /// Create a new `PatchTable` with no active patches.
+    pub fn new() -> PatchTable { PatchTable::default() }
+
+    /// Add the talkgroups in the given command to their patch, creating it if needed.
+    pub fn add(&mut self, cmd: &PatchGroupAdd) {
+        let sg = cmd.supergroup();
+
+        let idx = match self.patches.iter().position(|p| p.supergroup == sg) {
+            Some(idx) => idx,
+            None => {
+                self.patches.push(Patch {
+                    supergroup: sg,
+                    members: vec![],
+                });
+
+                self.patches.len() - 1
+            },
+        };
+
+        let patch = &mut self.patches[idx];
+
+        for &tg in cmd.groups().iter() {
+            if tg != TalkGroup::Nobody && tg != sg && !patch.members.contains(&tg) {
+                patch.members.push(tg);
+            }
+        }
+    }
+
+    /// Remove the talkgroups in the given command from their patch, removing the whole
+    /// patch if no talkgroups are given or none remain.
+    pub fn delete(&mut self, cmd: &PatchGroupDelete) {
+        let sg = cmd.supergroup();
+
+        let idx = match self.patches.iter().position(|p| p.supergroup == sg) {
+            Some(idx) => idx,
+            None => return,
+        };
+
+        let groups: Vec<_> = cmd.groups().iter().cloned()
+            .filter(|&tg| tg != TalkGroup::Nobody && tg != sg)
+            .collect();
+
+        if !groups.is_empty() {
+            self.patches[idx].members.retain(|tg| !groups.contains(tg));
+        }
+
+        if groups.is_empty() || self.patches[idx].members.is_empty() {
+            self.patches.remove(idx);
+        }
+    }
+
+    /// Currently active patches.
+    pub fn patches(&self) -> &[Patch] { &self.patches[..] }
+
+    /// Talkgroups patched onto the given supergroup, if it's active.
+    pub fn members(&self, sg: TalkGroup) -> Option<&[TalkGroup]> {
+        self.patches.iter()
+            .find(|p| p.supergroup == sg)
+            .map(|p| &p.members[..])
+    }
+
+    /// Supergroup the given talkgroup is patched onto, if any.
+    pub fn supergroup(&self, tg: TalkGroup) -> Option<TalkGroup> {
+        self.patches.iter()
+            .find(|p| p.members.contains(&tg))
+            .map(|p| p.supergroup)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn patch(sg: u16, groups: [u16; 3]) -> [u8; 8] {
+        [
+            (sg >> 8) as u8,
+            sg as u8,
+            (groups[0] >> 8) as u8,
+            groups[0] as u8,
+            (groups[1] >> 8) as u8,
+            groups[1] as u8,
+            (groups[2] >> 8) as u8,
+            groups[2] as u8,
+        ]
+    }
+
+    #[test]
+    fn test_patch_group_add() {
+        let buf = patch(0xFF01, [0x0123, 0x4567, 0]);
+
+        match MotorolaTsbk::new(0x00, &buf) {
+            Some(MotorolaTsbk::PatchGroupAdd(p)) => {
+                assert_eq!(p.supergroup(), TalkGroup::Other(0xFF01));
+                assert_eq!(p.groups(), [
+                    TalkGroup::Other(0x0123),
+                    TalkGroup::Other(0x4567),
+                    TalkGroup::Nobody,
+                ]);
+            },
+            _ => panic!(),
+        }
+
+        match MotorolaTsbk::new(0x01, &buf) {
+            Some(MotorolaTsbk::PatchGroupDelete(p)) => {
+                assert_eq!(p.supergroup(), TalkGroup::Other(0xFF01));
+            },
+            _ => panic!(),
+        }
+
+        assert!(MotorolaTsbk::new(0x3F, &buf).is_none());
+    }
+
+    #[test]
+    fn test_system_load() {
+        let buf = [75, 3, 10, 8, 0, 0, 0x10, 0x42];
+
+        match MotorolaTsbk::new(0x09, &buf) {
+            Some(MotorolaTsbk::SystemLoad(l)) => {
+                assert_eq!(l.load(), 75);
+                assert_eq!(l.queued(), 3);
+                assert_eq!(l.channels(), 10);
+                assert_eq!(l.busy(), 8);
+                assert_eq!(l.channel().id(), 1);
+                assert_eq!(l.channel().number(), 0x042);
+
+                let t = l.encode();
+                assert_eq!(t.mfg(), MFID_MOTOROLA);
+                assert_eq!(t.opcode_bits(), 0x09);
+                assert_eq!(t.payload(), &buf);
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_base_station_id() {
+        let chars = b"WQAB123+";
+        let mut bits = 0u64;
+
+        for &c in chars.iter() {
+            bits = bits << 6 | (c - b'+') as u64;
+        }
+
+        let buf = [
+            (bits >> 40) as u8,
+            (bits >> 32) as u8,
+            (bits >> 24) as u8,
+            (bits >> 16) as u8,
+            (bits >> 8) as u8,
+            bits as u8,
+            0x10,
+            0x42,
+        ];
+
+        match MotorolaTsbk::new(0x0B, &buf) {
+            Some(MotorolaTsbk::BaseStationId(b)) => {
+                assert_eq!(&b.callsign(), chars);
+                assert_eq!(b.channel().id(), 1);
+                assert_eq!(b.channel().number(), 0x042);
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_patch_table() {
+        let mut t = PatchTable::new();
+        assert!(t.patches().is_empty());
+
+        t.add(&PatchGroupAdd::new(&patch(0xFF01, [0x0123, 0x4567, 0xFF01])));
+        assert_eq!(t.members(TalkGroup::Other(0xFF01)),
+                   Some(&[TalkGroup::Other(0x0123), TalkGroup::Other(0x4567)][..]));
+
+        // Repeated adds don't duplicate members.
+        t.add(&PatchGroupAdd::new(&patch(0xFF01, [0x0123, 0x89AB, 0])));
+        assert_eq!(t.members(TalkGroup::Other(0xFF01)).unwrap().len(), 3);
+        assert_eq!(t.supergroup(TalkGroup::Other(0x89AB)), Some(TalkGroup::Other(0xFF01)));
+
+        t.add(&PatchGroupAdd::new(&patch(0xFF02, [0x0011, 0, 0])));
+        assert_eq!(t.patches().len(), 2);
+        assert_eq!(t.supergroup(TalkGroup::Other(0x0011)), Some(TalkGroup::Other(0xFF02)));
+        assert_eq!(t.supergroup(TalkGroup::Other(0xFF02)), None);
+
+        t.add(&PatchGroupAdd::new(&patch(0xFF02, [0x0012, 0, 0])));
+        assert_eq!(t.patches().len(), 2);
+
+        t.delete(&PatchGroupDelete::new(&patch(0xFF01, [0x0123, 0, 0])));
+        assert_eq!(t.members(TalkGroup::Other(0xFF01)),
+                   Some(&[TalkGroup::Other(0x4567), TalkGroup::Other(0x89AB)][..]));
+        assert_eq!(t.supergroup(TalkGroup::Other(0x0123)), None);
+
+        // Removing the remaining members removes the patch.
+        t.delete(&PatchGroupDelete::new(&patch(0xFF01, [0x4567, 0x89AB, 0])));
+        assert_eq!(t.members(TalkGroup::Other(0xFF01)), None);
+        assert_eq!(t.patches().len(), 1);
+
+        // A bare supergroup removes the whole patch.
+        t.delete(&PatchGroupDelete::new(&patch(0xFF02, [0, 0, 0xFF02])));
+        assert!(t.patches().is_empty());
+
+        // Deleting unknown patches is ignored.
+        t.delete(&PatchGroupDelete::new(&patch(0xFF03, [0x0001, 0, 0])));
+        assert!(t.patches().is_empty());
+    }
+}
@@ -0,0 +1,306 @@
+//! Decide which active call to follow based on talkgroup priorities and filters.
+
+use std::vec::Vec;
+
+use trunking::calls::{Call, CallEvent, CallTarget};
+use trunking::fields::{ChannelParamsMap, TalkGroup};
+
+/// Why a retune is recommended.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TuneReason {
+    /// A call started while no call was being followed.
+    CallStarted,
+    /// A call with higher priority than the followed call started.
+    Preempted,
+    /// An emergency call started while a non-emergency call was being followed.
+    Emergency,
+    /// The followed call ended, so return to the control channel.
+    CallEnded,
+}
+
+/// Recommendation to retune the receiver.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TuneRecommendation {
+    /// Frequency (Hz) to tune to.
+    pub frequency: u64,
+    /// TDMA slot to follow, if the channel is TDMA.
+    pub slot: Option<u8>,
+    /// Talkgroup of the call, if it's a group call.
+    pub talkgroup: Option<TalkGroup>,
+    /// Why the retune is recommended.
+    pub reason: TuneReason,
+}
+
+/// Chooses which call to follow from the events of a `GrantTracker`.
+///
+/// Talkgroups are followed with priority 0 unless given another priority, and the call
+/// with the highest priority is followed, with emergency calls preempting all
+/// non-emergency calls. Calls on denied talkgroups are never followed. Unit-to-unit
+/// calls are followed with priority 0.
+pub struct TunePolicy {
+    /// Frequency (Hz) of the control channel to return to when idle.
+    control: u64,
+    /// Talkgroups with non-default priority.
+    priorities: Vec<(TalkGroup, u8)>,
+    /// Talkgroups that are never followed.
+    denied: Vec<TalkGroup>,
+    /// Call currently being followed.
+    following: Option<Call>,
+}
+
+impl TunePolicy {
+    /// Create a new `TunePolicy` that returns to the given control channel frequency (Hz)
+    /// when idle.
+    pub fn new(control: u64) -> TunePolicy {
+        TunePolicy {
+            control: control,
+            priorities: vec![],
+            denied: vec![],
+            following: None,
+        }
+    }
+
+    /// Set the priority of the given talkgroup, where higher values take precedence.
+    pub fn set_priority(&mut self, tg: TalkGroup, prio: u8) {
+        self.priorities.retain(|&(t, _)| t != tg);
+        self.priorities.push((tg, prio));
+    }
+
+    /// Never follow calls on the given talkgroup.
+    pub fn deny(&mut self, tg: TalkGroup) {
+        if !self.denied.contains(&tg) {
+            self.denied.push(tg);
+        }
+    }
+
+    /// Call currently being followed, if any.
+    pub fn following(&self) -> Option<&Call> { self.following.as_ref() }
+
+    /// Handle the given call event, using the given channel parameters to resolve
+    /// frequencies, and return a recommendation if the receiver should be retuned.
+    pub fn feed(&mut self, event: &CallEvent, params: &ChannelParamsMap)
+        -> Option<TuneRecommendation>
+    {
+        match *event {
+            CallEvent::Started(call) | CallEvent::Continued(call) =>
+                self.offer(call, params),
+            CallEvent::Ended(call) => self.end(call),
+        }
+    }
+
+    /// Priority of the given call.
+    fn priority(&self, call: &Call) -> u8 {
+        match call.target {
+            CallTarget::Group(tg) => self.priorities.iter()
+                .find(|&&(t, _)| t == tg)
+                .map_or(0, |&(_, prio)| prio),
+            CallTarget::Unit(_) => 0,
+        }
+    }
+
+    /// Whether the given call should never be followed.
+    fn denied(&self, call: &Call) -> bool {
+        match call.target {
+            CallTarget::Group(tg) => self.denied.contains(&tg),
+            CallTarget::Unit(_) => false,
+        }
+    }
+
+    /// Consider following the given active call.
+    fn offer(&mut self, call: Call, params: &ChannelParamsMap)
+        -> Option<TuneRecommendation>
+    {
+        if self.denied(&call) {
+            return None;
+        }
+
+        let reason = match self.following {
+            None => TuneReason::CallStarted,
+            Some(ref cur) if cur.channel == call.channel && cur.target == call.target =>
+                return None,
+            Some(ref cur) if call.emergency && !cur.emergency => TuneReason::Emergency,
+            Some(ref cur) if cur.emergency && !call.emergency => return None,
+            Some(ref cur) if self.priority(&call) > self.priority(cur) =>
+                TuneReason::Preempted,
+            Some(_) => return None,
+        };
+
+        let (frequency, slot) = params.resolve(call.channel)?;
+
+        self.following = Some(call);
+
+        Some(TuneRecommendation {
+            frequency: frequency,
+            slot: slot,
+            talkgroup: match call.target {
+                CallTarget::Group(tg) => Some(tg),
+                CallTarget::Unit(_) => None,
+            },
+            reason: reason,
+        })
+    }
+
+    /// Stop following the given call if it's the followed one.
+    fn end(&mut self, call: Call) -> Option<TuneRecommendation> {
+        match self.following {
+            Some(cur) if cur.channel == call.channel && cur.target == call.target => {},
+            _ => return None,
+        }
+
+        self.following = None;
+
+        Some(TuneRecommendation {
+            frequency: self.control,
+            slot: None,
+            talkgroup: None,
+            reason: TuneReason::CallEnded,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use message::receiver::MessageEvent;
+    use trunking::calls::GrantTracker;
+    use trunking::fields::ChannelParamsUpdate;
+    use trunking::tsbk::{TsbkFields, TsbkOpcode};
+    use trunking::vendor::MFID_STANDARD;
+
+    const CONTROL: u64 = 851_006_250;
+
+    fn params() -> ChannelParamsMap {
+        let mut map = ChannelParamsMap::default();
+        // ID 1: base 851.00625MHz, spacing 6.25kHz.
+        map.update(&ChannelParamsUpdate::new(&[
+            0b0001_0011,
+            0b00100_010,
+            0b110100_00,
+            0b00110010,
+            0x0A,
+            0x25,
+            0x10,
+            0xA2,
+        ]));
+        map
+    }
+
+    fn grant(opts: u8, ch: u16, tg: u16) -> MessageEvent {
+        MessageEvent::TrunkingControl(TsbkFields::build(
+            TsbkOpcode::GroupVoiceGrant.to_bits(), MFID_STANDARD, &[
+                opts,
+                (ch >> 8) as u8,
+                ch as u8,
+                (tg >> 8) as u8,
+                tg as u8,
+                0x00,
+                0x00,
+                0x01,
+            ]))
+    }
+
+    /// Feed the given grant through a tracker and policy, returning the recommendation.
+    fn run(t: &mut GrantTracker, p: &mut TunePolicy, ev: MessageEvent, time: u64)
+        -> Vec<TuneRecommendation>
+    {
+        t.feed(&ev, time).iter()
+            .filter_map(|e| p.feed(e, &params()))
+            .collect()
+    }
+
+    #[test]
+    fn test_preempt() {
+        let mut t = GrantTracker::new(10, 30);
+        let mut p = TunePolicy::new(CONTROL);
+        p.set_priority(TalkGroup::Other(0x100), 5);
+        p.set_priority(TalkGroup::Other(0x200), 2);
+
+        assert_eq!(run(&mut t, &mut p, grant(0, 0x1020, 0x200), 0), vec![
+            TuneRecommendation {
+                frequency: 851_206_250,
+                slot: None,
+                talkgroup: Some(TalkGroup::Other(0x200)),
+                reason: TuneReason::CallStarted,
+            },
+        ]);
+
+        // Lower priority calls don't preempt.
+        assert!(run(&mut t, &mut p, grant(0, 0x1030, 0x300), 1).is_empty());
+        // Neither do repeats of the followed call.
+        assert!(run(&mut t, &mut p, grant(0, 0x1020, 0x200), 2).is_empty());
+
+        match run(&mut t, &mut p, grant(0, 0x1040, 0x100), 3)[..] {
+            [r] => {
+                assert_eq!(r.frequency, 851_406_250);
+                assert_eq!(r.talkgroup, Some(TalkGroup::Other(0x100)));
+                assert_eq!(r.reason, TuneReason::Preempted);
+            },
+            _ => panic!(),
+        }
+
+        // Emergencies preempt regardless of priority, and aren't preempted by
+        // non-emergencies.
+        match run(&mut t, &mut p, grant(0x80, 0x1050, 0x300), 4)[..] {
+            [r] => {
+                assert_eq!(r.frequency, 851_506_250);
+                assert_eq!(r.reason, TuneReason::Emergency);
+            },
+            _ => panic!(),
+        }
+
+        p.set_priority(TalkGroup::Other(0x400), 7);
+        assert!(run(&mut t, &mut p, grant(0, 0x1060, 0x400), 5).is_empty());
+        assert_eq!(p.following().unwrap().target,
+                   CallTarget::Group(TalkGroup::Other(0x300)));
+    }
+
+    #[test]
+    fn test_deny() {
+        let mut t = GrantTracker::new(10, 30);
+        let mut p = TunePolicy::new(CONTROL);
+        p.deny(TalkGroup::Other(0x100));
+
+        assert!(run(&mut t, &mut p, grant(0, 0x1020, 0x100), 0).is_empty());
+        assert!(run(&mut t, &mut p, grant(0x80, 0x1020, 0x100), 20).is_empty());
+        assert!(p.following().is_none());
+
+        assert_eq!(run(&mut t, &mut p, grant(0, 0x1030, 0x200), 1).len(), 1);
+        assert!(p.following().is_some());
+    }
+
+    #[test]
+    fn test_return() {
+        let mut t = GrantTracker::new(10, 30);
+        let mut p = TunePolicy::new(CONTROL);
+
+        assert_eq!(run(&mut t, &mut p, grant(0, 0x1020, 0x100), 0).len(), 1);
+        assert!(run(&mut t, &mut p, grant(0, 0x1030, 0x200), 10).is_empty());
+
+        // Only the followed call ending causes a return.
+        let recs: Vec<_> = t.expire(31).iter()
+            .filter_map(|e| p.feed(e, &params()))
+            .collect();
+
+        assert_eq!(recs, vec![
+            TuneRecommendation {
+                frequency: CONTROL,
+                slot: None,
+                talkgroup: None,
+                reason: TuneReason::CallEnded,
+            },
+        ]);
+
+        assert!(p.following().is_none());
+
+        // Heartbeats from calls that are still active can be picked up.
+        match run(&mut t, &mut p, grant(0, 0x1030, 0x200), 40)[..] {
+            [r] => {
+                assert_eq!(r.frequency, 851_306_250);
+                assert_eq!(r.reason, TuneReason::CallStarted);
+            },
+            _ => panic!(),
+        }
+
+        assert!(t.expire(100).iter().filter_map(|e| p.feed(e, &params())).count() == 1);
+    }
+}
@@ -0,0 +1,699 @@
+//! Track state advertised by a site's control channel.
+
+use std::collections::BTreeMap;
+use std::collections::btree_map::Values;
+use std::vec::Vec;
+
+use message::nid::NetworkAccessCode;
+use message::receiver::MessageEvent;
+use trunking::calls::GrantTracker;
+use trunking::decode::TsbkMessage;
+use trunking::fields::{AdjacentSite, Channel, ChannelParamsMap, NetworkIdentity,
+                       ServiceFlags, SiteAddress, SiteIdentity, SiteOptions,
+                       SystemServices};
+
+/// Adjacent site as last advertised by the current control channel.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Neighbor {
+    /// Location registration area of the site.
+    pub area: u8,
+    /// System ID of the site within the WACN.
+    pub system: u16,
+    /// RF Subsystem ID of the site within the system.
+    pub rfss: u8,
+    /// Site ID within the RFSS.
+    pub site: u8,
+    /// Control channel of the site.
+    pub channel: Channel,
+    /// Services supported by the site.
+    pub services: SystemServices,
+    /// Current operating conditions of the site.
+    pub conditions: SiteOptions,
+    /// Time the site was last advertised.
+    pub updated: u64,
+}
+
+impl Neighbor {
+    /// Create a new `Neighbor` from the given adjacent site broadcast received at the
+    /// given time.
+    pub fn new(adj: &AdjacentSite, time: u64) -> Neighbor {
+        Neighbor {
+            area: adj.area(),
+            system: adj.system(),
+            rfss: adj.rfss(),
+            site: adj.site(),
+            channel: adj.channel(),
+            services: adj.services(),
+            conditions: adj.opts(),
+            updated: time,
+        }
+    }
+
+    /// Compute the control channel frequency (Hz) of the site, if parameters for its
+    /// channel ID are known.
+    pub fn rx_freq(&self, params: &ChannelParamsMap) -> Option<u64> {
+        params.rx_freq(self.channel)
+    }
+}
+
+/// Collects adjacent site broadcasts into a deduplicated set of neighboring sites.
+///
+/// Times are in arbitrary caller-defined units (such as a sample or symbol counter), and
+/// sites that haven't been advertised within `max_age` of these units are dropped when
+/// the table is expired.
+pub struct NeighborTable {
+    /// Neighbors keyed by (system, rfss, site).
+    sites: BTreeMap<(u16, u8, u8), Neighbor>,
+    /// Maximum time since a site's last advertisement before it's dropped.
+    max_age: u64,
+}
+
+impl NeighborTable {
+    /// Create a new, empty `NeighborTable` that drops sites not seen within the given
+    /// time.
+    pub fn new(max_age: u64) -> NeighborTable {
+        NeighborTable {
+            sites: BTreeMap::new(),
+            max_age: max_age,
+        }
+    }
+
+    /// Add or refresh the site in the given broadcast, received at the given time.
+    pub fn update(&mut self, adj: &AdjacentSite, time: u64) {
+        let n = Neighbor::new(adj, time);
+        self.sites.insert((n.system, n.rfss, n.site), n);
+    }
+
+    /// Drop all sites that haven't been refreshed within the maximum age as of the given
+    /// time.
+    pub fn expire(&mut self, time: u64) {
+        let max_age = self.max_age;
+        let stale: Vec<_> = self.sites.iter()
+            .filter(|&(_, n)| time.saturating_sub(n.updated) > max_age)
+            .map(|(&k, _)| k)
+            .collect();
+
+        for k in stale {
+            self.sites.remove(&k);
+        }
+    }
+
+    /// Iterate over the current neighbors, ordered by system, RFSS, and site.
+    pub fn neighbors(&self) -> Values<'_, (u16, u8, u8), Neighbor> { self.sites.values() }
+
+    /// Number of tracked neighbors.
+    pub fn len(&self) -> usize { self.sites.len() }
+
+    /// Whether there are no tracked neighbors.
+    pub fn is_empty(&self) -> bool { self.sites.is_empty() }
+
+    /// Compute the sorted, deduplicated control channel frequencies (Hz) of all neighbors
+    /// whose channel parameters are known.
+    ///
+    /// Frequencies are resolved at query time, so sites advertised before their channel
+    /// parameters were received are included once the parameters arrive.
+    pub fn freqs(&self, params: &ChannelParamsMap) -> Vec<u64> {
+        let mut freqs: Vec<u64> = self.sites.values()
+            .filter_map(|n| n.rx_freq(params))
+            .collect();
+
+        freqs.sort();
+        freqs.dedup();
+        freqs
+    }
+}
+
+/// Role of a frequency in a scan list, in decreasing order of significance.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum ScanKind {
+    /// Primary control channel of the current site.
+    Control,
+    /// Secondary control channel of the current site.
+    AltControl,
+    /// Control channel of an adjacent site.
+    NeighborControl,
+    /// Traffic channel that recently carried a call.
+    RecentVoice,
+}
+
+/// Frequency worth scanning along with why it's included.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ScanEntry {
+    /// Frequency (Hz) to scan.
+    pub frequency: u64,
+    /// Role of the frequency.
+    pub kind: ScanKind,
+}
+
+impl ScanEntry {
+    /// Create a new `ScanEntry` for the given frequency (Hz) and role.
+    pub fn new(frequency: u64, kind: ScanKind) -> ScanEntry {
+        ScanEntry {
+            frequency: frequency,
+            kind: kind,
+        }
+    }
+}
+
+/// Site field along with the time it was last updated.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Timestamped<T> {
+    /// Most recently received value.
+    pub value: T,
+    /// Time the value was last received.
+    pub updated: u64,
+}
+
+impl<T> Timestamped<T> {
+    /// Create a new `Timestamped` with the given value received at the given time.
+    pub fn new(value: T, updated: u64) -> Timestamped<T> {
+        Timestamped {
+            value: value,
+            updated: updated,
+        }
+    }
+}
+
+/// Aggregates the broadcasts of the current control channel into a picture of the site.
+///
+/// Times are in arbitrary caller-defined units, and each field records when it was last
+/// received so stale fields can be detected. When the NAC, network, or site identity
+/// changes (such as after retuning to a different site), all state learned from the
+/// previous site is dropped.
+#[derive(Clone, Default)]
+pub struct SiteInfo {
+    /// NAC of received packets.
+    nac: Option<Timestamped<NetworkAccessCode>>,
+    /// WACN and System ID of the network.
+    network: Option<Timestamped<NetworkIdentity>>,
+    /// RFSS and Site ID of the site.
+    site: Option<Timestamped<SiteAddress>>,
+    /// Primary control channel of the site.
+    control: Option<Timestamped<Channel>>,
+    /// Channel parameters (IdenTable) of the site.
+    params: Option<Timestamped<ChannelParamsMap>>,
+    /// Services currently available at the site.
+    available: Option<Timestamped<ServiceFlags>>,
+    /// Services supported by the site.
+    supported: Option<Timestamped<ServiceFlags>>,
+    /// Secondary control channels of the site.
+    alts: Option<Timestamped<Vec<Channel>>>,
+}
+
+impl SiteInfo {
+    /// Create a new `SiteInfo` with nothing known about the site.
+    pub fn new() -> SiteInfo { SiteInfo::default() }
+
+    /// Update the site from the given receiver event, received at the given time. Return
+    /// `true` if the event changed the site identity and previous state was dropped.
+    pub fn feed(&mut self, event: &MessageEvent, time: u64) -> bool {
+        let tsbk = match *event {
+            MessageEvent::PacketNID(ref nid) =>
+                return self.set_nac(nid.access_code, time),
+            MessageEvent::TrunkingControl(ref tsbk) => tsbk,
+            _ => return false,
+        };
+
+        match TsbkMessage::new(tsbk) {
+            TsbkMessage::NetworkStatusBroadcast(n) => {
+                let changed = self.set_network(n.network(), time);
+                self.control = Some(Timestamped::new(n.channel(), time));
+                changed
+            },
+            TsbkMessage::RfssStatusBroadcast(r) => {
+                let changed = self.set_site(r.site_addr(), time);
+                self.control = Some(Timestamped::new(r.channel(), time));
+                changed
+            },
+            TsbkMessage::ChannelParamsUpdate(u) => {
+                self.params_mut(time).update(&u);
+                false
+            },
+            TsbkMessage::ChannelParamsUpdateVu(u) => {
+                self.params_mut(time).update_vu(&u);
+                false
+            },
+            TsbkMessage::ChannelParamsUpdateTdma(u) => {
+                self.params_mut(time).update_tdma(&u);
+                false
+            },
+            TsbkMessage::SystemServiceBroadcast(b) => {
+                self.available = Some(Timestamped::new(b.available(), time));
+                self.supported = Some(Timestamped::new(b.supported(), time));
+                false
+            },
+            TsbkMessage::AltControlChannel(a) => {
+                let alts = self.alts.get_or_insert(Timestamped::new(vec![], time));
+                alts.updated = time;
+
+                for &(ch, _) in a.alts().iter() {
+                    if !alts.value.contains(&ch) {
+                        alts.value.push(ch);
+                    }
+                }
+
+                false
+            },
+            _ => false,
+        }
+    }
+
+    /// Copy the current state, such as for display.
+    pub fn snapshot(&self) -> SiteInfo { self.clone() }
+
+    /// NAC of the site.
+    pub fn nac(&self) -> Option<Timestamped<NetworkAccessCode>> { self.nac }
+    /// WACN and System ID of the site.
+    pub fn network(&self) -> Option<Timestamped<NetworkIdentity>> { self.network }
+    /// RFSS and Site ID of the site.
+    pub fn site(&self) -> Option<Timestamped<SiteAddress>> { self.site }
+    /// Full identity of the site, once both the network and site address are known.
+    pub fn identity(&self) -> Option<SiteIdentity> {
+        match (self.network, self.site) {
+            (Some(n), Some(s)) => Some(SiteIdentity::new(n.value, s.value)),
+            _ => None,
+        }
+    }
+    /// Primary control channel of the site.
+    pub fn control(&self) -> Option<Timestamped<Channel>> { self.control }
+    /// Channel parameters (IdenTable) of the site.
+    pub fn params(&self) -> Option<&Timestamped<ChannelParamsMap>> {
+        self.params.as_ref()
+    }
+    /// Services currently available at the site.
+    pub fn available(&self) -> Option<Timestamped<ServiceFlags>> { self.available }
+    /// Services supported by the site.
+    pub fn supported(&self) -> Option<Timestamped<ServiceFlags>> { self.supported }
+    /// Secondary control channels of the site.
+    pub fn alts(&self) -> Option<&Timestamped<Vec<Channel>>> { self.alts.as_ref() }
+
+    /// Build the list of frequencies worth scanning when falling back to conventional
+    /// scanning, from the channels of this site, the given neighbors, and the traffic
+    /// channels of calls in the given tracker active within `lookback` of time `now`.
+    ///
+    /// Only channels whose parameters are known are included. Each frequency appears
+    /// once, labeled with its most significant kind, and entries are ordered by kind and
+    /// then by frequency.
+    pub fn scan_list(&self, neighbors: &NeighborTable, grants: &GrantTracker, now: u64,
+                     lookback: u64)
+        -> Vec<ScanEntry>
+    {
+        let params = match self.params {
+            Some(ref p) => &p.value,
+            None => return vec![],
+        };
+
+        let control = self.control.iter().map(|c| (c.value, ScanKind::Control));
+        let alts = self.alts.iter()
+            .flat_map(|a| a.value.iter())
+            .map(|&ch| (ch, ScanKind::AltControl));
+        let adjacent = neighbors.neighbors()
+            .map(|n| (n.channel, ScanKind::NeighborControl));
+        let voice = grants.recent(now.saturating_sub(lookback)).into_iter()
+            .map(|ch| (ch, ScanKind::RecentVoice));
+
+        let mut entries: Vec<ScanEntry> = control.chain(alts).chain(adjacent).chain(voice)
+            .filter_map(|(ch, kind)| params.rx_freq(ch).map(|f| ScanEntry::new(f, kind)))
+            .collect();
+
+        entries.sort_by_key(|e| (e.frequency, e.kind));
+        entries.dedup_by_key(|e| e.frequency);
+        entries.sort_by_key(|e| (e.kind, e.frequency));
+
+        entries
+    }
+
+    /// Set the NAC, resetting all other state if it changed.
+    fn set_nac(&mut self, nac: NetworkAccessCode, time: u64) -> bool {
+        let changed = changed(self.nac, nac);
+
+        if changed {
+            self.network = None;
+            self.site = None;
+            self.reset();
+        }
+
+        self.nac = Some(Timestamped::new(nac, time));
+
+        changed
+    }
+
+    /// Set the network identity, resetting site state if it changed.
+    fn set_network(&mut self, network: NetworkIdentity, time: u64) -> bool {
+        let changed = changed(self.network, network);
+
+        if changed {
+            self.site = None;
+            self.reset();
+        }
+
+        self.network = Some(Timestamped::new(network, time));
+
+        changed
+    }
+
+    /// Set the site identity, resetting site state if it changed.
+    fn set_site(&mut self, site: SiteAddress, time: u64) -> bool {
+        let changed = changed(self.site, site);
+
+        if changed {
+            self.reset();
+        }
+
+        self.site = Some(Timestamped::new(site, time));
+
+        changed
+    }
+
+    /// Drop state that depends on the site identity.
+    fn reset(&mut self) {
+        self.control = None;
+        self.params = None;
+        self.available = None;
+        self.supported = None;
+        self.alts = None;
+    }
+
+    /// Get the channel parameters for updating at the given time.
+    fn params_mut(&mut self, time: u64) -> &mut ChannelParamsMap {
+        let params = self.params.get_or_insert(
+            Timestamped::new(ChannelParamsMap::default(), time));
+
+        params.updated = time;
+
+        &mut params.value
+    }
+}
+
+/// Check if the given new value differs from the previously known value, if any.
+fn changed<T: Copy + PartialEq>(prev: Option<Timestamped<T>>, next: T) -> bool {
+    match prev {
+        Some(prev) => prev.value != next,
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use message::nid::{DataUnit, NetworkId};
+    use trunking::fields::ChannelParamsUpdate;
+    use trunking::tsbk::{TsbkFields, TsbkOpcode};
+    use trunking::vendor::MFID_STANDARD;
+
+    fn adjacent(system: u16, rfss: u8, site: u8, ch: u16) -> [u8; 8] {
+        [
+            0x01,
+            0b0001_0000 | (system >> 8) as u8,
+            system as u8,
+            rfss,
+            site,
+            (ch >> 8) as u8,
+            ch as u8,
+            0b0111_0000,
+        ]
+    }
+
+    fn params() -> ChannelParamsMap {
+        let mut map = ChannelParamsMap::default();
+        // ID 1: base 851.00625MHz, spacing 6.25kHz.
+        map.update(&ChannelParamsUpdate::new(&[
+            0b0001_0011,
+            0b00100_010,
+            0b110100_00,
+            0b00110010,
+            0x0A,
+            0x25,
+            0x10,
+            0xA2,
+        ]));
+        map
+    }
+
+    fn event(opcode: TsbkOpcode, payload: &[u8]) -> MessageEvent {
+        MessageEvent::TrunkingControl(TsbkFields::build(opcode.to_bits(), MFID_STANDARD,
+                                                        payload))
+    }
+
+    fn nid(nac: u16) -> MessageEvent {
+        MessageEvent::PacketNID(NetworkId::new(NetworkAccessCode::Other(nac),
+                                               DataUnit::TrunkingSignaling))
+    }
+
+    fn net_status(system: u16) -> MessageEvent {
+        event(TsbkOpcode::NetworkStatusBroadcast, &[
+            0x01,
+            0xBE,
+            0xE0,
+            (system >> 8) as u8,
+            system as u8,
+            0x10,
+            0x20,
+            0x70,
+        ])
+    }
+
+    fn rfss_status(site: u8) -> MessageEvent {
+        event(TsbkOpcode::RfssStatusBroadcast, &[0x01, 0x13, 0xA5, 0x01, site, 0x10, 0x20,
+                                                 0x70])
+    }
+
+    fn iden_up() -> MessageEvent {
+        event(TsbkOpcode::ChannelParamsUpdate, &[
+            0b0001_0011,
+            0b00100_010,
+            0b110100_00,
+            0b00110010,
+            0x0A,
+            0x25,
+            0x10,
+            0xA2,
+        ])
+    }
+
+    fn services() -> MessageEvent {
+        event(TsbkOpcode::SystemServiceBroadcast, &[0x00, 0x80, 0x00, 0x00, 0xC0, 0x00,
+                                                    0x00, 0x01])
+    }
+
+    fn alt_cc(ch: u16) -> MessageEvent {
+        event(TsbkOpcode::AltControlChannel, &[
+            0x01,
+            0x07,
+            (ch >> 8) as u8,
+            ch as u8,
+            0x70,
+            (ch >> 8) as u8,
+            ch as u8,
+            0x70,
+        ])
+    }
+
+    /// Build a `SiteInfo` with every field received, at increasing times.
+    fn full_site() -> SiteInfo {
+        let mut s = SiteInfo::new();
+
+        assert!(!s.feed(&nid(0x3A5), 0));
+        assert!(!s.feed(&net_status(0x3A5), 1));
+        assert!(!s.feed(&rfss_status(7), 2));
+        assert!(!s.feed(&iden_up(), 3));
+        assert!(!s.feed(&services(), 4));
+        assert!(!s.feed(&alt_cc(0x1030), 5));
+
+        s
+    }
+
+    #[test]
+    fn test_site_info() {
+        let mut s = full_site();
+
+        assert_eq!(s.nac(), Some(Timestamped::new(NetworkAccessCode::Other(0x3A5), 0)));
+        assert_eq!(s.network().unwrap().value, NetworkIdentity::new(0xBEE00, 0x3A5));
+        assert_eq!(s.network().unwrap().updated, 1);
+        assert_eq!(s.site(), Some(Timestamped::new(SiteAddress::new(1, 7), 2)));
+        assert_eq!(s.identity().unwrap().to_string(), "BEE00.3A5 001-007");
+        assert_eq!(s.params().unwrap().updated, 3);
+        assert_eq!(s.params().unwrap().value.rx_freq(Channel::from_bits(0x1020)),
+                   Some(851_206_250));
+        assert!(s.available().unwrap().value.group_voice());
+        assert!(!s.available().unwrap().value.unit_voice());
+        assert!(s.supported().unwrap().value.unit_voice());
+        assert_eq!(s.supported().unwrap().updated, 4);
+        assert_eq!(s.alts().unwrap().value, vec![Channel::from_bits(0x1030)]);
+
+        // Repeated broadcasts refresh the timestamps without duplicating.
+        assert!(!s.feed(&alt_cc(0x1030), 10));
+        assert!(!s.feed(&rfss_status(7), 11));
+        assert_eq!(s.alts().unwrap().value.len(), 1);
+        assert_eq!(s.alts().unwrap().updated, 10);
+        assert_eq!(s.site().unwrap().updated, 11);
+
+        // Other events are ignored.
+        assert!(!s.feed(&MessageEvent::LowSpeedDataFragment(0), 12));
+
+        let snap = s.snapshot();
+        assert!(!s.feed(&alt_cc(0x1040), 13));
+        assert_eq!(s.alts().unwrap().value.len(), 2);
+        assert_eq!(snap.alts().unwrap().value.len(), 1);
+    }
+
+    #[test]
+    fn test_site_change() {
+        let mut s = full_site();
+        let first = s.identity().unwrap();
+
+        // Retuning to another site in the same system keeps the network identity.
+        assert!(s.feed(&rfss_status(8), 20));
+        assert_eq!(s.site(), Some(Timestamped::new(SiteAddress::new(1, 8), 20)));
+        assert!(s.identity().unwrap() != first);
+        assert_eq!(s.network().unwrap().updated, 1);
+        assert!(s.nac().is_some());
+        assert!(s.params().is_none());
+        assert!(s.available().is_none());
+        assert!(s.supported().is_none());
+        assert!(s.alts().is_none());
+
+        assert!(!s.feed(&iden_up(), 21));
+        assert!(s.params().is_some());
+
+        // A different system drops the site identity too.
+        assert!(s.feed(&net_status(0x3A6), 22));
+        assert_eq!(s.network().unwrap().value, NetworkIdentity::new(0xBEE00, 0x3A6));
+        assert!(s.site().is_none());
+        assert!(s.identity().is_none());
+        assert!(s.params().is_none());
+
+        // The first site identity after a reset isn't a change.
+        assert!(!s.feed(&rfss_status(8), 23));
+        assert!(!s.feed(&iden_up(), 24));
+
+        // A different NAC drops everything.
+        assert!(s.feed(&nid(0x3A6), 25));
+        assert_eq!(s.nac().unwrap().value, NetworkAccessCode::Other(0x3A6));
+        assert!(s.network().is_none());
+        assert!(s.site().is_none());
+        assert!(s.params().is_none());
+
+        // The same NAC just refreshes.
+        assert!(!s.feed(&nid(0x3A6), 26));
+        assert_eq!(s.nac().unwrap().updated, 26);
+    }
+
+    #[test]
+    fn test_neighbor() {
+        let buf = adjacent(0x3A5, 1, 7, 0x1020);
+        let n = Neighbor::new(&AdjacentSite::new(&buf), 42);
+
+        assert_eq!(n.area, 0x01);
+        assert_eq!(n.system, 0x3A5);
+        assert_eq!(n.rfss, 1);
+        assert_eq!(n.site, 7);
+        assert_eq!(n.channel.id(), 1);
+        assert_eq!(n.channel.number(), 0x020);
+        assert!(n.services.has_voice());
+        assert!(n.conditions.networked());
+        assert_eq!(n.updated, 42);
+        assert_eq!(n.rx_freq(&params()), Some(851_206_250));
+        assert_eq!(n.rx_freq(&ChannelParamsMap::default()), None);
+    }
+
+    #[test]
+    fn test_dedup() {
+        let mut t = NeighborTable::new(100);
+        assert!(t.is_empty());
+
+        t.update(&AdjacentSite::new(&adjacent(0x3A5, 1, 7, 0x1020)), 0);
+        t.update(&AdjacentSite::new(&adjacent(0x3A5, 1, 8, 0x1030)), 1);
+        t.update(&AdjacentSite::new(&adjacent(0x3A5, 1, 7, 0x1040)), 2);
+        assert_eq!(t.len(), 2);
+
+        let sites: Vec<_> = t.neighbors().map(|n| (n.site, n.channel.number())).collect();
+        assert_eq!(sites, vec![(7, 0x040), (8, 0x030)]);
+
+        // Different RFSS with the same site ID is a distinct site.
+        t.update(&AdjacentSite::new(&adjacent(0x3A5, 2, 7, 0x1040)), 3);
+        assert_eq!(t.len(), 3);
+
+        assert_eq!(t.freqs(&params()), vec![851_306_250, 851_406_250]);
+    }
+
+    #[test]
+    fn test_aging() {
+        let mut t = NeighborTable::new(10);
+
+        t.update(&AdjacentSite::new(&adjacent(0x3A5, 1, 7, 0x1020)), 0);
+        t.update(&AdjacentSite::new(&adjacent(0x3A5, 1, 8, 0x1030)), 5);
+
+        t.expire(10);
+        assert_eq!(t.len(), 2);
+
+        t.expire(11);
+        assert_eq!(t.len(), 1);
+        assert_eq!(t.neighbors().next().unwrap().site, 8);
+
+        // Refreshing resets the age.
+        t.update(&AdjacentSite::new(&adjacent(0x3A5, 1, 8, 0x1030)), 14);
+        t.expire(20);
+        assert_eq!(t.len(), 1);
+
+        t.expire(25);
+        assert!(t.is_empty());
+    }
+
+    #[test]
+    fn test_late_params() {
+        let mut t = NeighborTable::new(100);
+        let mut map = ChannelParamsMap::default();
+
+        t.update(&AdjacentSite::new(&adjacent(0x3A5, 1, 7, 0x1020)), 0);
+        assert!(t.freqs(&map).is_empty());
+
+        map = params();
+        assert_eq!(t.freqs(&map), vec![851_206_250]);
+    }
+
+    #[test]
+    fn test_scan_list() {
+        let grant = |ch: u16| event(TsbkOpcode::GroupVoiceGrant, &[
+            0x00,
+            (ch >> 8) as u8,
+            ch as u8,
+            0x12,
+            0x34,
+            0x00,
+            0x00,
+            0x01,
+        ]);
+
+        let s = full_site();
+
+        let mut n = NeighborTable::new(100);
+        n.update(&AdjacentSite::new(&adjacent(0x3A5, 1, 8, 0x1040)), 0);
+        // Also an alternate control channel of the current site.
+        n.update(&AdjacentSite::new(&adjacent(0x3A5, 1, 9, 0x1030)), 0);
+        // Unknown channel parameters.
+        n.update(&AdjacentSite::new(&adjacent(0x3A5, 1, 10, 0x2050)), 0);
+
+        let mut g = GrantTracker::new(10, 30);
+        g.feed(&grant(0x1070), 0);
+        g.feed(&grant(0x1060), 50);
+        assert_eq!(g.expire(100).len(), 2);
+        g.feed(&grant(0x1050), 100);
+
+        assert_eq!(s.scan_list(&n, &g, 100, 60), vec![
+            ScanEntry::new(851_206_250, ScanKind::Control),
+            ScanEntry::new(851_306_250, ScanKind::AltControl),
+            ScanEntry::new(851_406_250, ScanKind::NeighborControl),
+            ScanEntry::new(851_506_250, ScanKind::RecentVoice),
+            ScanEntry::new(851_606_250, ScanKind::RecentVoice),
+        ]);
+
+        // A longer lookback picks up older calls.
+        let list = s.scan_list(&n, &g, 100, 100);
+        assert_eq!(list.len(), 6);
+        assert_eq!(list[5], ScanEntry::new(851_706_250, ScanKind::RecentVoice));
+
+        // Nothing can be resolved without channel parameters.
+        assert!(SiteInfo::new().scan_list(&n, &g, 100, 100).is_empty());
+    }
+}
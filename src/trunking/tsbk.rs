@@ -1,17 +1,24 @@
 //! Receive Trunking Signalling Block (TSBK) packets and decode the various TSBK payloads.
 
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::vec::Vec;
+
 use collect_slice::CollectSlice;
 
 use bits::{Dibit, DibitBytes};
 use buffer::{Buffer, DataPayloadStorage};
 use coding::trellis;
-use consts::{TSBK_DIBITS, TSBK_BYTES};
+use consts::{TSBK_DIBITS, TSBK_BYTES, TSDU_MAX_BLOCKS};
 use data::{crc, interleave};
 use error::{Result, P25Error};
 use stats::{Stats, HasStats};
 use util::{slice_u16, slice_u24};
 
-use trunking::fields::{Channel, TalkGroup, ServiceOptions, RegResponse};
+use trunking::encode::TsbkEncode;
+use trunking::fields::{Channel, ChannelPair, TalkGroup, ServiceOptions, RegResponse,
+                       NetworkIdentity, SiteAddress};
+use trunking::vendor::{ManufacturerId, VendorOpcode};
 
 /// State machine for receiving a TSBK packet.
 ///
@@ -21,9 +28,17 @@ use trunking::fields::{Channel, TalkGroup, ServiceOptions, RegResponse};
 /// 2. Descramble symbols using the same deinterleaver as data packets
 /// 3. Decode 1/2-rate convolutional code and attempt to correct any errors
 /// 4. Group dibits into a buffer of bytes for further interpretation
+/// 5. Verify the packet CRC
+///
+/// A TSDU carries up to three packed TSBKs, so these steps repeat for each block until
+/// one with the last-block flag is received or the maximum number of blocks is reached.
 pub struct TsbkReceiver {
     /// Current buffered dibits.
     dibits: Buffer<DataPayloadStorage>,
+    /// Number of blocks received so far in the current TSDU.
+    blocks: usize,
+    /// Whether the final block of the TSDU has been received.
+    done: bool,
     stats: Stats,
 }
 
@@ -32,13 +47,22 @@ impl TsbkReceiver {
     pub fn new() -> TsbkReceiver {
         TsbkReceiver {
             dibits: Buffer::new(DataPayloadStorage::new()),
+            blocks: 0,
+            done: false,
             stats: Stats::default(),
         }
     }
 
+    /// Whether the final TSBK of the current TSDU has been received, either because it
+    /// had the last-block flag set or because the maximum number of blocks was reached.
+    pub fn done(&self) -> bool { self.done }
+
     /// Feed in a baseband symbol, possibly producing a complete TSBK packet. Return
     /// `Some(Ok(pkt))` if a packet was successfully received, `Some(Err(err))` if an
     /// error occurred, and `None` in the case of no event.
+    ///
+    /// Each block is decoded independently, so an error in one block doesn't prevent
+    /// decoding the blocks that follow it.
     pub fn feed(&mut self, dibit: Dibit) -> Option<Result<TsbkFields>> {
         let (count, dibits) = {
             let buf = match self.dibits.feed(dibit) {
@@ -54,6 +78,9 @@ impl TsbkReceiver {
             (count, dibits)
         };
 
+        self.blocks += 1;
+        self.done = self.blocks >= TSDU_MAX_BLOCKS;
+
         if count != dibits.len() {
             return Some(Err(P25Error::DibitViterbiUnrecoverable));
         }
@@ -64,7 +91,15 @@ impl TsbkReceiver {
         let mut bytes = [0; TSBK_BYTES];
         DibitBytes::new(dibits.iter().cloned()).collect_slice_checked(&mut bytes[..]);
 
-        Some(Ok(TsbkFields::new(bytes)))
+        let tsbk = TsbkFields::new(bytes);
+
+        if let Err(err) = tsbk.check_crc() {
+            return Some(Err(err));
+        }
+
+        self.done |= tsbk.is_tail();
+
+        Some(Ok(tsbk))
     }
 }
 
@@ -103,8 +138,12 @@ pub enum TsbkOpcode {
     UnitRegCommand,
     UnitAuthCommand,
     UnitDeregAck,
+    /// Authentication demand, which is only carried in the alternate multi-block format.
+    UnitAuthDemand,
     RoamingAddrCommand,
     RoamingAddrUpdate,
+    ChannelParamsUpdateTdma,
+    ChannelParamsUpdateVu,
     SystemServiceBroadcast,
     AltControlChannel,
     RfssStatusBroadcast,
@@ -113,7 +152,8 @@ pub enum TsbkOpcode {
     ChannelParamsUpdate,
     ProtectionParamBroadcast,
     ProtectionParamUpdate,
-    Reserved,
+    /// Opcode with no standard assignment.
+    Reserved(u8),
 }
 
 impl TsbkOpcode {
@@ -125,44 +165,44 @@ impl TsbkOpcode {
 
         match bits {
             0b000000 => Some(GroupVoiceGrant),
-            0b000001 => Some(Reserved),
+            0b000001 => Some(Reserved(bits)),
             0b000010 => Some(GroupVoiceUpdate),
             0b000011 => Some(GroupVoiceUpdateExplicit),
             0b000100 => Some(UnitVoiceGrant),
             0b000101 => Some(UnitCallRequest),
             0b000110 => Some(UnitVoiceUpdate),
-            0b000111 => Some(Reserved),
+            0b000111 => Some(Reserved(bits)),
 
             0b001000 => Some(PhoneGrant),
-            0b001001 => Some(Reserved),
+            0b001001 => Some(Reserved(bits)),
             0b001010 => Some(PhoneAlert),
-            0b001011..=0b001111 => Some(Reserved),
+            0b001011..=0b001111 => Some(Reserved(bits)),
 
             0b010000 => Some(UnitDataGrant),
             0b010001 => Some(GroupDataGrant),
             0b010010 => Some(GroupDataUpdate),
             0b010011 => Some(GroupDataUpdateExplicit),
-            0b010100..=0b010111 => Some(Reserved),
+            0b010100..=0b010111 => Some(Reserved(bits)),
 
             0b011000 => Some(UnitStatusUpdate),
-            0b011001 => Some(Reserved),
+            0b011001 => Some(Reserved(bits)),
             0b011010 => Some(UnitStatusQuery),
-            0b011011 => Some(Reserved),
+            0b011011 => Some(Reserved(bits)),
             0b011100 => Some(UnitShortMessage),
             0b011101 => Some(UnitMonitor),
-            0b011110 => Some(Reserved),
+            0b011110 => Some(Reserved(bits)),
             0b011111 => Some(UnitCallAlert),
             0b100000 => Some(AckResponse),
             0b100001 => Some(QueuedResponse),
-            0b100010 => Some(Reserved),
-            0b100011 => Some(Reserved),
+            0b100010 => Some(Reserved(bits)),
+            0b100011 => Some(Reserved(bits)),
             0b100100 => Some(ExtendedFunctionResponse),
-            0b100101 => Some(Reserved),
-            0b100110 => Some(Reserved),
+            0b100101 => Some(Reserved(bits)),
+            0b100110 => Some(Reserved(bits)),
             0b100111 => Some(DenyResponse),
 
             0b101000 => Some(GroupAffiliationResponse),
-            0b101001 => Some(Reserved),
+            0b101001 => Some(Reserved(bits)),
             0b101010 => Some(GroupAffiliationQuery),
             0b101011 => Some(LocRegResponse),
             0b101100 => Some(UnitRegResponse),
@@ -170,7 +210,12 @@ impl TsbkOpcode {
             0b101110 => Some(UnitAuthCommand),
             0b101111 => Some(UnitDeregAck),
 
-            0b110000..=0b110101 => Some(Reserved),
+            0b110000 => Some(Reserved(bits)),
+            0b110001 => Some(UnitAuthDemand),
+            0b110010 => Some(Reserved(bits)),
+            0b110011 => Some(ChannelParamsUpdateTdma),
+            0b110100 => Some(ChannelParamsUpdateVu),
+            0b110101 => Some(Reserved(bits)),
             0b110110 => Some(RoamingAddrCommand),
             0b110111 => Some(RoamingAddrUpdate),
 
@@ -186,6 +231,104 @@ impl TsbkOpcode {
             _ => None,
         }
     }
+
+    /// Convert the opcode to its 6-bit representation.
+    pub fn to_bits(self) -> u8 {
+        use self::TsbkOpcode::*;
+
+        match self {
+            GroupVoiceGrant => 0b000000,
+            GroupVoiceUpdate => 0b000010,
+            GroupVoiceUpdateExplicit => 0b000011,
+            UnitVoiceGrant => 0b000100,
+            UnitCallRequest => 0b000101,
+            UnitVoiceUpdate => 0b000110,
+            PhoneGrant => 0b001000,
+            PhoneAlert => 0b001010,
+            UnitDataGrant => 0b010000,
+            GroupDataGrant => 0b010001,
+            GroupDataUpdate => 0b010010,
+            GroupDataUpdateExplicit => 0b010011,
+            UnitStatusUpdate => 0b011000,
+            UnitStatusQuery => 0b011010,
+            UnitShortMessage => 0b011100,
+            UnitMonitor => 0b011101,
+            UnitCallAlert => 0b011111,
+            AckResponse => 0b100000,
+            QueuedResponse => 0b100001,
+            ExtendedFunctionResponse => 0b100100,
+            DenyResponse => 0b100111,
+            GroupAffiliationResponse => 0b101000,
+            GroupAffiliationQuery => 0b101010,
+            LocRegResponse => 0b101011,
+            UnitRegResponse => 0b101100,
+            UnitRegCommand => 0b101101,
+            UnitAuthCommand => 0b101110,
+            UnitDeregAck => 0b101111,
+            UnitAuthDemand => 0b110001,
+            ChannelParamsUpdateTdma => 0b110011,
+            ChannelParamsUpdateVu => 0b110100,
+            RoamingAddrCommand => 0b110110,
+            RoamingAddrUpdate => 0b110111,
+            SystemServiceBroadcast => 0b111000,
+            AltControlChannel => 0b111001,
+            RfssStatusBroadcast => 0b111010,
+            NetworkStatusBroadcast => 0b111011,
+            AdjacentSite => 0b111100,
+            ChannelParamsUpdate => 0b111101,
+            ProtectionParamBroadcast => 0b111110,
+            ProtectionParamUpdate => 0b111111,
+            Reserved(bits) => bits,
+        }
+    }
+}
+
+/// Interprets a 6-bit opcode within the namespace of the given manufacturer ID, so
+/// manufacturer-specific opcodes are never mistaken for standard ones. Only the lower 6
+/// bits of the opcode are used.
+impl TryFrom<(ManufacturerId, u8)> for TsbkOpcode {
+    type Error = VendorOpcode;
+
+    fn try_from((mfid, bits): (ManufacturerId, u8))
+        -> ::std::result::Result<TsbkOpcode, VendorOpcode>
+    {
+        let bits = bits & 0x3F;
+
+        if !mfid.is_standard() {
+            return Err(VendorOpcode::new(mfid, bits));
+        }
+
+        Ok(TsbkOpcode::from_bits(bits).expect("6-bit opcode"))
+    }
+}
+
+/// Lightweight view of the routing information of a TSBK packet, for filtering packets
+/// without fully decoding them.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TsbkHeader {
+    /// Manufacturer ID, which determines the opcode namespace.
+    pub mfid: ManufacturerId,
+    /// Standard opcode, or the manufacturer-specific opcode for other manufacturer IDs.
+    pub opcode: ::std::result::Result<TsbkOpcode, VendorOpcode>,
+    /// Whether this packet is the last one in the TSBK group.
+    pub last_block: bool,
+}
+
+impl TsbkHeader {
+    /// Peek at the header of the given packet, returning `None` if its CRC is invalid.
+    pub fn peek(tsbk: &TsbkFields) -> Option<TsbkHeader> {
+        if !tsbk.crc_valid() {
+            return None;
+        }
+
+        let mfid = ManufacturerId::from_bits(tsbk.mfg());
+
+        Some(TsbkHeader {
+            mfid: mfid,
+            opcode: TsbkOpcode::try_from((mfid, tsbk.opcode_bits())),
+            last_block: tsbk.is_tail(),
+        })
+    }
 }
 
 /// Buffer of bytes that represents a TSBK packet.
@@ -199,12 +342,42 @@ impl TsbkFields {
     /// Interpret the given bytes as a TSBK packet.
     pub fn new(buf: Buf) -> TsbkFields { TsbkFields(buf) }
 
+    /// Build a TSBK packet with the given 6-bit opcode, manufacturer ID, and 8 payload
+    /// bytes, filling in the CRC.
+    pub fn build(opcode: u8, mfg: u8, payload: &[u8]) -> TsbkFields {
+        assert!(opcode >> 6 == 0);
+        assert!(payload.len() == 8);
+
+        let mut buf = [0; TSBK_BYTES];
+        buf[0] = opcode;
+        buf[1] = mfg;
+        (&mut buf[2..=9]).copy_from_slice(payload);
+
+        TsbkFields(buf).with_tail(false)
+    }
+
+    /// Copy the packet with the last-block flag set as given and the CRC recalculated.
+    pub fn with_tail(mut self, tail: bool) -> TsbkFields {
+        self.0[0] = self.0[0] & 0x7F | (tail as u8) << 7;
+
+        let crc = self.calc_crc();
+        self.0[10] = (crc >> 8) as u8;
+        self.0[11] = crc as u8;
+
+        self
+    }
+
+    /// Bytes that make up the packet.
+    pub fn bytes(&self) -> &Buf { &self.0 }
+
     /// Whether this packet is the last one in the TSBK group.
     pub fn is_tail(&self) -> bool { self.0[0] >> 7 == 1 }
     /// Whether the packet is encrypted.
     pub fn protected(&self) -> bool { self.0[0] >> 6 & 1 == 1 }
     /// Type of data contained in the payload.
-    pub fn opcode(&self) -> Option<TsbkOpcode> { TsbkOpcode::from_bits(self.0[0] & 0x3F) }
+    pub fn opcode(&self) -> Option<TsbkOpcode> { TsbkOpcode::from_bits(self.opcode_bits()) }
+    /// Raw 6-bit opcode, whose meaning depends on the manufacturer ID.
+    pub fn opcode_bits(&self) -> u8 { self.0[0] & 0x3F }
     /// Manufacturer ID, which determines if the packet is standardized.
     pub fn mfg(&self) -> u8 { self.0[1] }
     /// Transmitted CRC.
@@ -218,8 +391,21 @@ impl TsbkFields {
     }
 
     /// Verify if the calculated CRC matches the transmitted one.
-    pub fn crc_valid(&self) -> bool {
-        self.crc() == self.calc_crc()
+    pub fn crc_valid(&self) -> bool { self.check_crc().is_ok() }
+
+    /// Return `CrcMismatch` with both checksums if the calculated CRC doesn't match the
+    /// transmitted one.
+    pub fn check_crc(&self) -> Result<()> {
+        let (received, computed) = (self.crc(), self.calc_crc());
+
+        if received != computed {
+            return Err(P25Error::CrcMismatch {
+                received: received as u32,
+                computed: computed as u32,
+            });
+        }
+
+        Ok(())
     }
 
     /// Bytes that make up the payload of the packet.
@@ -241,10 +427,16 @@ impl LocRegResponse {
     pub fn rfss(&self) -> u8 { self.0[5] }
     /// Site ID of site within RFSS.
     pub fn site(&self) -> u8 { self.0[6] }
+    /// Combined RFSS and Site ID of site.
+    pub fn site_addr(&self) -> SiteAddress { SiteAddress::new(self.rfss(), self.site()) }
     /// Address of requesting unit.
     pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[7..=9]) }
 }
 
+impl TsbkEncode for LocRegResponse {
+    fn encode(&self) -> TsbkFields { TsbkFields::new(self.0).with_tail(false) }
+}
+
 /// Response given to an attempted user registration.
 pub struct UnitRegResponse(Buf);
 
@@ -266,6 +458,50 @@ impl UnitRegResponse {
     pub fn src_addr(&self) -> u32 { slice_u24(&self.0[7..=9]) }
 }
 
+impl TsbkEncode for UnitRegResponse {
+    fn encode(&self) -> TsbkFields { TsbkFields::new(self.0).with_tail(false) }
+}
+
+/// Command for a unit to register with the system.
+pub struct UnitRegCommand(Buf);
+
+impl UnitRegCommand {
+    /// Create a new `UnitRegCommand` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { UnitRegCommand(tsbk.0) }
+
+    /// Unit that should register.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[4..=6]) }
+    /// Unit that issued the command.
+    pub fn src_unit(&self) -> u32 { slice_u24(&self.0[7..=9]) }
+}
+
+impl TsbkEncode for UnitRegCommand {
+    fn encode(&self) -> TsbkFields { TsbkFields::new(self.0).with_tail(false) }
+}
+
+/// Command for a unit to begin authentication with its home network.
+pub struct AuthCommand(Buf);
+
+impl AuthCommand {
+    /// Create a new `AuthCommand` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { AuthCommand(tsbk.0) }
+
+    /// WACN ID of the unit's home network.
+    pub fn wacn(&self) -> u32 { slice_u24(&self.0[3..=5]) >> 4 }
+    /// System ID of the unit's home network.
+    pub fn system(&self) -> u16 { slice_u16(&self.0[5..=6]) & 0xFFF }
+    /// Combined WACN and System ID of the unit's home network.
+    pub fn network(&self) -> NetworkIdentity {
+        NetworkIdentity::new(self.wacn(), self.system())
+    }
+    /// Unit that should authenticate.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[7..=9]) }
+}
+
+impl TsbkEncode for AuthCommand {
+    fn encode(&self) -> TsbkFields { TsbkFields::new(self.0).with_tail(false) }
+}
+
 /// Acknowledgement of successful user deregistration request.
 pub struct UnitDeregAck(Buf);
 
@@ -277,10 +513,18 @@ impl UnitDeregAck {
     pub fn wacn(&self) -> u32 { slice_u24(&self.0[3..=5]) >> 4 }
     /// System ID within WACN.
     pub fn system(&self) -> u16 { slice_u16(&self.0[5..=6]) & 0xFFF }
+    /// Combined WACN and System ID of the unit's home network.
+    pub fn network(&self) -> NetworkIdentity {
+        NetworkIdentity::new(self.wacn(), self.system())
+    }
     /// ID of affected unit.
     pub fn src_unit(&self) -> u32 { slice_u24(&self.0[7..=9]) }
 }
 
+impl TsbkEncode for UnitDeregAck {
+    fn encode(&self) -> TsbkFields { TsbkFields::new(self.0).with_tail(false) }
+}
+
 /// Indicates a talkgroup has been granted a voice traffic channel.
 pub struct GroupVoiceGrant(Buf);
 
@@ -292,12 +536,42 @@ impl GroupVoiceGrant {
     pub fn opts(&self) -> ServiceOptions { ServiceOptions::new(self.0[2]) }
     /// Parameters for tuning to the traffic channel.
     pub fn channel(&self) -> Channel { Channel::new(&self.0[3..=4]) }
+    /// Receive and transmit channels of the traffic channel.
+    pub fn channels(&self) -> ChannelPair { ChannelPair::implicit(self.channel()) }
     /// Talkgroup for the conversation.
     pub fn talkgroup(&self) -> TalkGroup { TalkGroup::new(&self.0[5..]) }
     /// Unit that initiated the conversation.
     pub fn src_unit(&self) -> u32 { slice_u24(&self.0[7..]) }
 }
 
+impl TsbkEncode for GroupVoiceGrant {
+    fn encode(&self) -> TsbkFields { TsbkFields::new(self.0).with_tail(false) }
+}
+
+/// Indicates a talkgroup is active on a voice traffic channel with separately specified
+/// receive and transmit channels.
+pub struct GroupVoiceUpdateExplicit(Buf);
+
+impl GroupVoiceUpdateExplicit {
+    /// Create a new `GroupVoiceUpdateExplicit` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { GroupVoiceUpdateExplicit(tsbk.0) }
+
+    /// Options granted for the traffic channel.
+    pub fn opts(&self) -> ServiceOptions { ServiceOptions::new(self.0[2]) }
+    /// Channel the subscriber receives the conversation on.
+    pub fn channel(&self) -> Channel { Channel::new(&self.0[4..=5]) }
+    /// Receive and transmit channels of the traffic channel.
+    pub fn channels(&self) -> ChannelPair {
+        ChannelPair::explicit(self.channel(), Channel::new(&self.0[6..=7]))
+    }
+    /// Talkgroup for the conversation.
+    pub fn talkgroup(&self) -> TalkGroup { TalkGroup::new(&self.0[8..=9]) }
+}
+
+impl TsbkEncode for GroupVoiceUpdateExplicit {
+    fn encode(&self) -> TsbkFields { TsbkFields::new(self.0).with_tail(false) }
+}
+
 /// Indicates a pair of units have been granted a traffic channel.
 ///
 /// Note that this decoder can be used with `UnitVoiceGrant`, `UnitVoiceUpdate`, and
@@ -310,12 +584,18 @@ impl UnitTrafficChannel {
 
     /// Parameters for tuning to the traffic channel.
     pub fn channel(&self) -> Channel { Channel::new(&self.0[2..]) }
+    /// Receive and transmit channels of the traffic channel.
+    pub fn channels(&self) -> ChannelPair { ChannelPair::implicit(self.channel()) }
     /// Destination unit of the call.
     pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[4..]) }
     /// Originating unit of the call.
     pub fn src_unit(&self) -> u32 { slice_u24(&self.0[7..]) }
 }
 
+impl TsbkEncode for UnitTrafficChannel {
+    fn encode(&self) -> TsbkFields { TsbkFields::new(self.0).with_tail(false) }
+}
+
 /// Indicates a unit has been granted a traffic channel for a phone call.
 pub struct PhoneGrant(Buf);
 
@@ -327,6 +607,8 @@ impl PhoneGrant {
     pub fn opts(&self) -> ServiceOptions { ServiceOptions::new(self.0[2]) }
     /// Parameters for tuning to the traffic channel.
     pub fn channel(&self) -> Channel { Channel::new(&self.0[3..]) }
+    /// Receive and transmit channels of the traffic channel.
+    pub fn channels(&self) -> ChannelPair { ChannelPair::implicit(self.channel()) }
     /// Maximum amount of time (in units of 100ms) that the phone call can occupy the
     /// traffic channel.
     pub fn call_timer(&self) -> u16 { slice_u16(&self.0[5..]) }
@@ -334,6 +616,10 @@ impl PhoneGrant {
     pub fn unit(&self) -> u32 { slice_u24(&self.0[7..]) }
 }
 
+impl TsbkEncode for PhoneGrant {
+    fn encode(&self) -> TsbkFields { TsbkFields::new(self.0).with_tail(false) }
+}
+
 /// Indicates a talkgroup has been granted a data traffic channel.
 pub struct GroupDataGrant(Buf);
 
@@ -345,16 +631,623 @@ impl GroupDataGrant {
     pub fn opts(&self) -> ServiceOptions { ServiceOptions::new(self.0[2]) }
     /// Parameters for tuning to the traffic channel.
     pub fn channel(&self) -> Channel { Channel::new(&self.0[3..=4]) }
+    /// Receive and transmit channels of the traffic channel.
+    pub fn channels(&self) -> ChannelPair { ChannelPair::implicit(self.channel()) }
     /// Talkgroup assigned to the channel.
     pub fn talkgroup(&self) -> TalkGroup { TalkGroup::new(&self.0[5..=6]) }
     /// Originating unit for the data traffic.
     pub fn src_unit(&self) -> u32 { slice_u24(&self.0[7..=9]) }
 }
 
+impl TsbkEncode for GroupDataGrant {
+    fn encode(&self) -> TsbkFields { TsbkFields::new(self.0).with_tail(false) }
+}
+
+/// Status sent from one unit to another, or to the system.
+pub struct UnitStatusUpdate(Buf);
+
+impl UnitStatusUpdate {
+    /// Create a new `UnitStatusUpdate` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { UnitStatusUpdate(tsbk.0) }
+
+    /// User-defined status of the originating user.
+    pub fn user_status(&self) -> u8 { self.0[2] }
+    /// User-defined status of the originating unit.
+    pub fn unit_status(&self) -> u8 { self.0[3] }
+    /// Target unit of the status.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[4..=6]) }
+    /// Originating unit of the status.
+    pub fn src_unit(&self) -> u32 { slice_u24(&self.0[7..=9]) }
+}
+
+impl TsbkEncode for UnitStatusUpdate {
+    fn encode(&self) -> TsbkFields { TsbkFields::new(self.0).with_tail(false) }
+}
+
+/// Request for a unit to respond with its current status.
+pub struct UnitStatusQuery(Buf);
+
+impl UnitStatusQuery {
+    /// Create a new `UnitStatusQuery` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { UnitStatusQuery(tsbk.0) }
+
+    /// Unit whose status is requested.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[4..=6]) }
+    /// Unit requesting the status.
+    pub fn src_unit(&self) -> u32 { slice_u24(&self.0[7..=9]) }
+}
+
+impl TsbkEncode for UnitStatusQuery {
+    fn encode(&self) -> TsbkFields { TsbkFields::new(self.0).with_tail(false) }
+}
+
+/// Short predefined message sent from one unit to another.
+pub struct MessageUpdate(Buf);
+
+impl MessageUpdate {
+    /// Create a new `MessageUpdate` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { MessageUpdate(tsbk.0) }
+
+    /// User-defined message value.
+    pub fn msg(&self) -> u16 { slice_u16(&self.0[2..=3]) }
+    /// Target unit of the message.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[4..=6]) }
+    /// Originating unit of the message.
+    pub fn src_unit(&self) -> u32 { slice_u24(&self.0[7..=9]) }
+}
+
+impl TsbkEncode for MessageUpdate {
+    fn encode(&self) -> TsbkFields { TsbkFields::new(self.0).with_tail(false) }
+}
+
+/// Acknowledgement of a unit's service request.
+pub struct AckResponse(Buf);
+
+impl AckResponse {
+    /// Create a new `AckResponse` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { AckResponse(tsbk.0) }
+
+    /// Whether the additional information field is valid.
+    pub fn has_info(&self) -> bool { self.0[2] >> 7 == 1 }
+    /// Whether the additional information field holds a WACN and System ID rather than a
+    /// unit address.
+    pub fn extended(&self) -> bool { self.0[2] >> 6 & 1 == 1 }
+    /// Type of service being acknowledged.
+    pub fn service(&self) -> Option<TsbkOpcode> { TsbkOpcode::from_bits(self.0[2] & 0x3F) }
+    /// Additional information about the request, if valid.
+    pub fn info(&self) -> Option<AckInfo> {
+        if !self.has_info() {
+            return None;
+        }
+
+        Some(if self.extended() {
+            AckInfo::Network(NetworkIdentity::new(slice_u24(&self.0[3..=5]) >> 4,
+                                                  slice_u16(&self.0[5..=6]) & 0xFFF))
+        } else {
+            AckInfo::Unit(slice_u24(&self.0[4..=6]))
+        })
+    }
+    /// Unit that made the request.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[7..=9]) }
+}
+
+impl TsbkEncode for AckResponse {
+    fn encode(&self) -> TsbkFields { TsbkFields::new(self.0).with_tail(false) }
+}
+
+/// Additional information carried by an `AckResponse`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum AckInfo {
+    /// Address of the unit on the other side of the request.
+    Unit(u32),
+    /// Home network of the requesting unit.
+    Network(NetworkIdentity),
+}
+
+/// Denial of a unit's service request.
+pub struct DenyResponse(Buf);
+
+impl DenyResponse {
+    /// Create a new `DenyResponse` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { DenyResponse(tsbk.0) }
+
+    /// Whether the additional information field is valid.
+    pub fn has_info(&self) -> bool { self.0[2] >> 7 == 1 }
+    /// Type of service being denied.
+    pub fn service(&self) -> Option<TsbkOpcode> { TsbkOpcode::from_bits(self.0[2] & 0x3F) }
+    /// Reason the request was denied.
+    pub fn reason(&self) -> DenyReason { DenyReason::from_bits(self.0[3]) }
+    /// Additional information about the request, if valid.
+    pub fn info(&self) -> Option<ResponseInfo> {
+        if self.has_info() {
+            self.service().map(|s| ResponseInfo::new(s, &self.0[4..=6]))
+        } else {
+            None
+        }
+    }
+    /// Unit that made the request.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[7..=9]) }
+}
+
+impl TsbkEncode for DenyResponse {
+    fn encode(&self) -> TsbkFields { TsbkFields::new(self.0).with_tail(false) }
+}
+
+/// Notice that a unit's service request has been queued until resources are available.
+pub struct QueuedResponse(Buf);
+
+impl QueuedResponse {
+    /// Create a new `QueuedResponse` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { QueuedResponse(tsbk.0) }
+
+    /// Whether the additional information field is valid.
+    pub fn has_info(&self) -> bool { self.0[2] >> 7 == 1 }
+    /// Type of service being queued.
+    pub fn service(&self) -> Option<TsbkOpcode> { TsbkOpcode::from_bits(self.0[2] & 0x3F) }
+    /// Reason the request was queued.
+    pub fn reason(&self) -> QueueReason { QueueReason::from_bits(self.0[3]) }
+    /// Additional information about the request, if valid.
+    pub fn info(&self) -> Option<ResponseInfo> {
+        if self.has_info() {
+            self.service().map(|s| ResponseInfo::new(s, &self.0[4..=6]))
+        } else {
+            None
+        }
+    }
+    /// Unit that made the request.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[7..=9]) }
+}
+
+impl TsbkEncode for QueuedResponse {
+    fn encode(&self) -> TsbkFields { TsbkFields::new(self.0).with_tail(false) }
+}
+
+/// Additional information carried by a `DenyResponse` or `QueuedResponse`, whose
+/// meaning depends on the type of service requested.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ResponseInfo {
+    /// Talkgroup targeted by a group service request.
+    Group(TalkGroup),
+    /// Unit targeted by a unit-to-unit service request.
+    Unit(u32),
+    /// Uninterpreted 24-bit field for other service types.
+    Other(u32),
+}
+
+impl ResponseInfo {
+    /// Interpret the given 24-bit field based on the given service type.
+    pub fn new(service: TsbkOpcode, bytes: &[u8]) -> ResponseInfo {
+        use self::TsbkOpcode::*;
+
+        match service {
+            GroupVoiceGrant | GroupDataGrant | GroupAffiliationResponse =>
+                ResponseInfo::Group(TalkGroup::new(&bytes[1..=2])),
+            UnitVoiceGrant | UnitCallRequest | UnitDataGrant | UnitStatusUpdate |
+            UnitStatusQuery | UnitShortMessage | UnitCallAlert =>
+                ResponseInfo::Unit(slice_u24(bytes)),
+            _ => ResponseInfo::Other(slice_u24(bytes)),
+        }
+    }
+}
+
+/// Reason a service request was denied.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "ser", derive(Serialize))]
+pub enum DenyReason {
+    /// Requesting unit isn't valid on the system.
+    SrcUnitInvalid,
+    /// Requesting unit isn't authorized for the service.
+    SrcUnitUnauthorized,
+    /// Target unit isn't valid on the system.
+    DestUnitInvalid,
+    /// Target unit isn't authorized for the service.
+    DestUnitUnauthorized,
+    /// Target unit refused the call.
+    DestUnitRefused,
+    /// Target talkgroup isn't valid on the system.
+    GroupInvalid,
+    /// Requesting unit isn't authorized for the target talkgroup.
+    GroupUnauthorized,
+    /// Dialed phone number is malformed.
+    InvalidDialing,
+    /// Requesting unit isn't authorized to call the phone number.
+    PhoneUnauthorized,
+    /// Phone interconnect isn't available.
+    PhoneInvalid,
+    /// Call timed out.
+    CallTimeout,
+    /// Phone party ended the call.
+    PhoneTerminated,
+    /// Subscriber unit ended the call.
+    UnitTerminated,
+    /// Call was preempted by a higher priority call.
+    CallPreempted,
+    /// Requesting unit isn't allowed to access the site.
+    SiteAccessDenied,
+    /// Service isn't supported by the system.
+    ServiceUnsupported,
+    /// User or system defined reason.
+    Custom(u8),
+    /// Unassigned reason.
+    Unknown(u8),
+}
+
+impl DenyReason {
+    /// Parse a deny reason from the given byte.
+    pub fn from_bits(bits: u8) -> DenyReason {
+        use self::DenyReason::*;
+
+        match bits {
+            0x10 => SrcUnitInvalid,
+            0x11 => SrcUnitUnauthorized,
+            0x20 => DestUnitInvalid,
+            0x21 => DestUnitUnauthorized,
+            0x2F => DestUnitRefused,
+            0x30 => GroupInvalid,
+            0x31 => GroupUnauthorized,
+            0x40 => InvalidDialing,
+            0x41 => PhoneUnauthorized,
+            0x42 => PhoneInvalid,
+            0x50 => CallTimeout,
+            0x51 => PhoneTerminated,
+            0x52 => UnitTerminated,
+            0x5F => CallPreempted,
+            0x60 => SiteAccessDenied,
+            0xFF => ServiceUnsupported,
+            0xF0..=0xFE => Custom(bits),
+            _ => Unknown(bits),
+        }
+    }
+}
+
+/// Reason a service request was queued.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "ser", derive(Serialize))]
+pub enum QueueReason {
+    /// Requested resources aren't currently available.
+    ResourcesBusy,
+    /// Target unit is busy with another service.
+    DestUnitBusy,
+    /// Target unit has queued the request.
+    DestUnitQueued,
+    /// Target talkgroup is busy with another service.
+    GroupBusy,
+    /// Channel resources aren't currently available.
+    ChannelBusy,
+    /// User or system defined reason.
+    Custom(u8),
+    /// Unassigned reason.
+    Unknown(u8),
+}
+
+impl QueueReason {
+    /// Parse a queue reason from the given byte.
+    pub fn from_bits(bits: u8) -> QueueReason {
+        use self::QueueReason::*;
+
+        match bits {
+            0x10 => ResourcesBusy,
+            0x2F => DestUnitQueued,
+            0x20 => DestUnitBusy,
+            0x30 => GroupBusy,
+            0x40 => ChannelBusy,
+            0xF0..=0xFF => Custom(bits),
+            _ => Unknown(bits),
+        }
+    }
+}
+
+/// Command for a unit to modify its stack of roaming networks.
+pub struct RoamingAddrCommand(Buf);
+
+impl RoamingAddrCommand {
+    /// Create a new `RoamingAddrCommand` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { RoamingAddrCommand(tsbk.0) }
+
+    /// Operation to perform on the roaming address stack.
+    pub fn op(&self) -> RoamingStackOp { RoamingStackOp::from_bits(self.0[2]) }
+    /// WACN ID of the roaming network.
+    pub fn wacn(&self) -> u32 { slice_u24(&self.0[3..=5]) >> 4 }
+    /// System ID of the roaming network.
+    pub fn system(&self) -> u16 { slice_u16(&self.0[5..=6]) & 0xFFF }
+    /// Combined WACN and System ID of the roaming network.
+    pub fn network(&self) -> NetworkIdentity {
+        NetworkIdentity::new(self.wacn(), self.system())
+    }
+    /// Unit whose stack should be modified.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[7..=9]) }
+}
+
+impl TsbkEncode for RoamingAddrCommand {
+    fn encode(&self) -> TsbkFields { TsbkFields::new(self.0).with_tail(false) }
+}
+
+/// Operation on a unit's roaming address stack.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "ser", derive(Serialize))]
+pub enum RoamingStackOp {
+    /// Remove all entries from the stack.
+    Clear,
+    /// Add the given network to the stack.
+    Write,
+    /// Remove the given network from the stack.
+    Delete,
+    /// Report the contents of the stack.
+    Read,
+    /// Unassigned operation.
+    Unknown(u8),
+}
+
+impl RoamingStackOp {
+    /// Parse a stack operation from the given byte.
+    pub fn from_bits(bits: u8) -> RoamingStackOp {
+        use self::RoamingStackOp::*;
+
+        match bits {
+            0x00 => Clear,
+            0x01 => Write,
+            0x02 => Delete,
+            0x03 => Read,
+            _ => Unknown(bits),
+        }
+    }
+}
+
+/// Entry in a unit's list of roaming networks, which may be stacked across several
+/// packets.
+pub struct RoamingAddrUpdate(Buf);
+
+impl RoamingAddrUpdate {
+    /// Create a new `RoamingAddrUpdate` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { RoamingAddrUpdate(tsbk.0) }
+
+    /// Whether this is the last packet in the list.
+    pub fn is_last(&self) -> bool { self.0[2] >> 7 == 1 }
+    /// Sequence number of this packet within the list, starting at 0.
+    pub fn seq(&self) -> u8 { self.0[2] & 0xF }
+    /// Unit the list is for.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[3..=5]) }
+    /// WACN ID of the roaming network.
+    pub fn wacn(&self) -> u32 { slice_u24(&self.0[6..=8]) >> 4 }
+    /// System ID of the roaming network.
+    pub fn system(&self) -> u16 { slice_u16(&self.0[8..=9]) & 0xFFF }
+    /// Combined WACN and System ID of the roaming network.
+    pub fn network(&self) -> NetworkIdentity {
+        NetworkIdentity::new(self.wacn(), self.system())
+    }
+}
+
+impl TsbkEncode for RoamingAddrUpdate {
+    fn encode(&self) -> TsbkFields { TsbkFields::new(self.0).with_tail(false) }
+}
+
+/// Complete list of roaming networks sent to a unit.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RoamingAddrList {
+    /// Unit the list is for.
+    pub unit: u32,
+    /// Networks in sequence order.
+    pub networks: Vec<NetworkIdentity>,
+}
+
+/// Default number of partially received roaming address lists kept at once.
+const MAX_ROAMING_LISTS: usize = 64;
+
+/// Partially received roaming address list.
+#[derive(Default)]
+struct PartialRoamingList {
+    /// Networks received so far, indexed by sequence number.
+    networks: [Option<NetworkIdentity>; 16],
+    /// Sequence number of the last packet, if it's been received.
+    last: Option<u8>,
+    /// Number of packets the assembler had received when this list was last updated.
+    updated: u64,
+}
+
+impl PartialRoamingList {
+    /// Whether the given packet can't belong to the same transmission as the packets
+    /// received so far.
+    fn conflicts(&self, upd: &RoamingAddrUpdate) -> bool {
+        match self.last {
+            Some(last) if upd.is_last() => upd.seq() != last,
+            Some(last) => upd.seq() >= last,
+            None => false,
+        }
+    }
+}
+
+/// Reassembles roaming address lists stacked across several `RoamingAddrUpdate` packets.
+///
+/// Packets after the first can arrive in any order, and each list is emitted once its
+/// last packet and all the packets before it have been received. A list that lost
+/// packets is dropped when the first packet of the unit's next list arrives, or when a
+/// packet conflicts with its last packet, so it never merges with a later one. At most
+/// 64 lists are kept in progress by default, with the least recently updated dropped to
+/// make room.
+pub struct RoamingAddrAssembler {
+    /// Lists in progress, keyed by unit.
+    lists: BTreeMap<u32, PartialRoamingList>,
+    /// Maximum number of lists in progress.
+    max_lists: usize,
+    /// Number of packets received.
+    packets: u64,
+}
+
+impl Default for RoamingAddrAssembler {
+    fn default() -> Self { RoamingAddrAssembler::new() }
+}
+
+impl RoamingAddrAssembler {
+    /// Create a new `RoamingAddrAssembler` with no lists in progress.
+    pub fn new() -> RoamingAddrAssembler {
+        RoamingAddrAssembler {
+            lists: BTreeMap::new(),
+            max_lists: MAX_ROAMING_LISTS,
+            packets: 0,
+        }
+    }
+
+    /// Keep at most the given number of lists in progress, 64 by default.
+    pub fn with_max_lists(mut self, max: usize) -> Self {
+        assert!(max > 0);
+        self.max_lists = max;
+        self
+    }
+
+    /// Add the given packet to its unit's list, returning the list if it's now complete.
+    pub fn feed(&mut self, upd: &RoamingAddrUpdate) -> Option<RoamingAddrList> {
+        let unit = upd.dest_unit();
+        self.packets += 1;
+
+        if !self.lists.contains_key(&unit) && self.lists.len() >= self.max_lists {
+            self.evict();
+        }
+
+        let networks = {
+            let list = self.lists.entry(unit).or_insert_with(PartialRoamingList::default);
+
+            // The first packet starts a new transmission, and any stale packets of a
+            // list that lost some would otherwise be merged into it.
+            if upd.seq() == 0 || list.conflicts(upd) {
+                *list = PartialRoamingList::default();
+            }
+
+            list.networks[upd.seq() as usize] = Some(upd.network());
+            list.updated = self.packets;
+
+            if upd.is_last() {
+                list.last = Some(upd.seq());
+            }
+
+            let last = match list.last {
+                Some(last) => last as usize,
+                None => return None,
+            };
+
+            if list.networks[..=last].iter().any(|n| n.is_none()) {
+                return None;
+            }
+
+            list.networks[..=last].iter().map(|n| n.unwrap()).collect()
+        };
+
+        self.lists.remove(&unit);
+
+        Some(RoamingAddrList {
+            unit: unit,
+            networks: networks,
+        })
+    }
+
+    /// Number of lists in progress.
+    pub fn pending(&self) -> usize { self.lists.len() }
+
+    /// Drop any partially received lists.
+    pub fn reset(&mut self) { self.lists.clear() }
+
+    /// Drop the least recently updated list.
+    fn evict(&mut self) {
+        let oldest = self.lists.iter()
+            .min_by_key(|&(_, list)| list.updated)
+            .map(|(&unit, _)| unit);
+
+        if let Some(unit) = oldest {
+            self.lists.remove(&unit);
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use trunking::fields::*;
+    use data::coder::DibitCoder;
+
+    /// Encode the given TSBK bytes, with the CRC filled in, into interleaved dibits.
+    fn encode(mut bytes: [u8; TSBK_BYTES], good_crc: bool) -> Vec<Dibit> {
+        let crc = TsbkFields::new(bytes).calc_crc() ^ if good_crc { 0 } else { 0xFFFF };
+        bytes[10] = (crc >> 8) as u8;
+        bytes[11] = crc as u8;
+
+        interleave::Interleaver::new(
+            DibitCoder::new().feed_bytes(bytes.iter().cloned()).finish()
+        ).collect()
+    }
+
+    fn block(last: bool, opcode: u8) -> [u8; TSBK_BYTES] {
+        [
+            (last as u8) << 7 | opcode,
+            0x00,
+            0x01,
+            0x02,
+            0x03,
+            0x04,
+            0x05,
+            0x06,
+            0x07,
+            0x08,
+            0x00,
+            0x00,
+        ]
+    }
+
+    fn decode(dibits: &[Dibit]) -> (Vec<Result<TsbkFields>>, bool) {
+        let mut recv = TsbkReceiver::new();
+        let events = dibits.iter().filter_map(|&d| recv.feed(d)).collect();
+
+        (events, recv.done())
+    }
+
+    #[test]
+    fn test_tsdu_blocks() {
+        let mut dibits = encode(block(false, 0b000000), true);
+        dibits.extend(encode(block(false, 0b101000), true));
+        dibits.extend(encode(block(true, 0b111010), true));
+
+        let (events, done) = decode(&dibits);
+        assert!(done);
+        assert_eq!(events.len(), 3);
+
+        let opcodes: Vec<_> = events.iter()
+            .map(|e| e.as_ref().unwrap().opcode())
+            .collect();
+
+        assert_eq!(opcodes, vec![
+            Some(TsbkOpcode::GroupVoiceGrant),
+            Some(TsbkOpcode::GroupAffiliationResponse),
+            Some(TsbkOpcode::RfssStatusBroadcast),
+        ]);
+
+        assert!(!events[0].as_ref().unwrap().is_tail());
+        assert!(events[2].as_ref().unwrap().is_tail());
+    }
+
+    #[test]
+    fn test_tsdu_bad_block() {
+        let mut dibits = encode(block(false, 0b000000), true);
+        dibits.extend(encode(block(false, 0b101000), false));
+        dibits.extend(encode(block(true, 0b111010), true));
+
+        let (events, done) = decode(&dibits);
+        assert!(done);
+        assert_eq!(events.len(), 3);
+
+        assert_eq!(events[0].unwrap().opcode(), Some(TsbkOpcode::GroupVoiceGrant));
+        let crc = TsbkFields::new(block(false, 0b101000)).calc_crc() as u32;
+        assert_eq!(events[1].err(), Some(P25Error::CrcMismatch {
+            received: crc ^ 0xFFFF,
+            computed: crc,
+        }));
+        assert_eq!(events[2].unwrap().opcode(), Some(TsbkOpcode::RfssStatusBroadcast));
+    }
+
+    #[test]
+    fn test_tsdu_single_block() {
+        let (events, done) = decode(&encode(block(true, 0b111011), true));
+        assert!(done);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].unwrap().opcode(), Some(TsbkOpcode::NetworkStatusBroadcast));
+    }
 
     #[test]
     fn test_tsbk_fields() {
@@ -455,6 +1348,175 @@ mod test {
         assert_eq!(p.params().rx_freq(0b1001), 851_062_500);
     }
 
+    #[test]
+    fn test_channel_params_update_vu() {
+        let t = TsbkFields::new([
+            0b00110100,
+            0b00000000,
+            0b0001_0101,
+            0b1_0000110,
+            0b010000_00,
+            0b01100100,
+            0x05,
+            0x5D,
+            0x4F,
+            0x62,
+            0b11111111,
+            0b11111111,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::ChannelParamsUpdateVu));
+        let p = ChannelParamsUpdateVu::new(t.payload());
+
+        assert_eq!(p.id(), 0b0001);
+        assert_eq!(p.params().bandwidth, 12_500);
+        assert_eq!(p.params().rx_freq(40), 450_506_250);
+        assert_eq!(p.params().tx_freq(40), 455_506_250);
+    }
+
+    #[test]
+    fn test_channel_params_update_tdma() {
+        let t = TsbkFields::new([
+            0b00110011,
+            0b00000000,
+            0b0011_0011,
+            0b0_0111000,
+            0b010000_00,
+            0b01100100,
+            0x0A,
+            0x25,
+            0x15,
+            0x84,
+            0b11111111,
+            0b11111111,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::ChannelParamsUpdateTdma));
+        let p = ChannelParamsUpdateTdma::new(t.payload());
+
+        assert_eq!(p.id(), 0b0011);
+        assert_eq!(p.params().slots(), 2);
+        assert_eq!(p.params().rx_freq(209), 852_312_500);
+        assert_eq!(p.params().slot(209), Some(1));
+    }
+
+    #[test]
+    fn test_system_service_broadcast() {
+        let t = TsbkFields::new([
+            0b00111000,
+            0b00000000,
+            0b00000000,
+            0b11000000,
+            0b00000000,
+            0b00000000,
+            0b11110000,
+            0b11111111,
+            0b11111100,
+            0b00000001,
+            0b11111111,
+            0b11111111,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::SystemServiceBroadcast));
+        let s = SystemServiceBroadcast::new(t.payload());
+
+        assert_eq!(s.available().bits(), 0xC00000);
+        assert_eq!(s.supported().bits(), 0xF0FFFC);
+        assert_eq!(s.request_prio(), 1);
+    }
+
+    #[test]
+    fn test_unit_status_update() {
+        let t = TsbkFields::new([
+            0b00011000,
+            0b00000000,
+            0b10000001,
+            0b00001111,
+            0b11001100,
+            0b00110011,
+            0b10101010,
+            0b00000001,
+            0b00000010,
+            0b00000011,
+            0b11111111,
+            0b11111111,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::UnitStatusUpdate));
+        let s = UnitStatusUpdate::new(t);
+
+        assert_eq!(s.user_status(), 0b10000001);
+        assert_eq!(s.unit_status(), 0b00001111);
+        assert_eq!(s.dest_unit(), 0b11001100_00110011_10101010);
+        assert_eq!(s.src_unit(), 0x010203);
+    }
+
+    #[test]
+    fn test_unit_status_query() {
+        let t = TsbkFields::new([
+            0b00011010,
+            0b00000000,
+            0b00000000,
+            0b00000000,
+            0b11001100,
+            0b00110011,
+            0b10101010,
+            0b00000001,
+            0b00000010,
+            0b00000011,
+            0b11111111,
+            0b11111111,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::UnitStatusQuery));
+        let s = UnitStatusQuery::new(t);
+
+        assert_eq!(s.dest_unit(), 0b11001100_00110011_10101010);
+        assert_eq!(s.src_unit(), 0x010203);
+    }
+
+    #[test]
+    fn test_message_update() {
+        let t = TsbkFields::new([
+            0b00011100,
+            0b00000000,
+            0b10000001,
+            0b00001111,
+            0b11001100,
+            0b00110011,
+            0b10101010,
+            0b00000001,
+            0b00000010,
+            0b00000011,
+            0b11111111,
+            0b11111111,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::UnitShortMessage));
+        let m = MessageUpdate::new(t);
+
+        assert_eq!(m.msg(), 0b10000001_00001111);
+        assert_eq!(m.dest_unit(), 0b11001100_00110011_10101010);
+        assert_eq!(m.src_unit(), 0x010203);
+    }
+
+    #[test]
+    fn test_unit_call_alert() {
+        let t = TsbkFields::new([
+            0b00011111,
+            0b00000000,
+            0b00000000,
+            0b00000000,
+            0b11001100,
+            0b00110011,
+            0b10101010,
+            0b00000001,
+            0b00000010,
+            0b00000011,
+            0b11111111,
+            0b11111111,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::UnitCallAlert));
+        let a = UnitCallAlert::new(t.payload());
+
+        assert_eq!(a.dest_unit(), 0b11001100_00110011_10101010);
+        assert_eq!(a.src_unit(), 0x010203);
+    }
+
     #[test]
     fn test_group_traffic_update() {
         let t = TsbkFields::new([
@@ -638,6 +1700,401 @@ mod test {
         assert_eq!(a.wacn(), 0b11001100001100111010);
         assert_eq!(a.system(), 0b000111110011);
         assert_eq!(a.src_unit(), 0b111111000000001111100111);
+        assert_eq!(a.network(), NetworkIdentity::new(0b11001100001100111010,
+                                                     0b000111110011));
+    }
+
+    #[test]
+    fn test_auth_command() {
+        let t = TsbkFields::new([
+            0b00101110,
+            0b00000000,
+            0b00000000,
+            0b10111110,
+            0b11100000,
+            0b00000011,
+            0b10100101,
+            0b00000001,
+            0b00000010,
+            0b00000011,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::UnitAuthCommand));
+        let c = AuthCommand::new(t);
+        assert_eq!(c.wacn(), 0xBEE00);
+        assert_eq!(c.system(), 0x3A5);
+        assert_eq!(c.network().to_string(), "BEE00.3A5");
+        assert_eq!(c.dest_unit(), 0x010203);
+    }
+
+    fn roaming_update(seq: u8, last: bool, wacn: u32, system: u16) -> RoamingAddrUpdate {
+        RoamingAddrUpdate::new(TsbkFields::new([
+            0b00110111,
+            0b00000000,
+            (last as u8) << 7 | seq,
+            0x01,
+            0x02,
+            0x03,
+            (wacn >> 12) as u8,
+            (wacn >> 4) as u8,
+            (wacn << 4) as u8 | (system >> 8) as u8,
+            system as u8,
+            0b00000000,
+            0b00000000,
+        ]))
+    }
+
+    #[test]
+    fn test_roaming_addr_command() {
+        let t = TsbkFields::new([
+            0b00110110,
+            0b00000000,
+            0b00000001,
+            0b10111110,
+            0b11100000,
+            0b00000011,
+            0b10100101,
+            0b00000001,
+            0b00000010,
+            0b00000011,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::RoamingAddrCommand));
+        let c = RoamingAddrCommand::new(t);
+        assert_eq!(c.op(), RoamingStackOp::Write);
+        assert_eq!(c.network(), NetworkIdentity::new(0xBEE00, 0x3A5));
+        assert_eq!(c.dest_unit(), 0x010203);
+
+        assert_eq!(RoamingStackOp::from_bits(0), RoamingStackOp::Clear);
+        assert_eq!(RoamingStackOp::from_bits(2), RoamingStackOp::Delete);
+        assert_eq!(RoamingStackOp::from_bits(3), RoamingStackOp::Read);
+        assert_eq!(RoamingStackOp::from_bits(4), RoamingStackOp::Unknown(4));
+    }
+
+    #[test]
+    fn test_roaming_addr_update() {
+        let u = roaming_update(0b0101, true, 0xBEE00, 0x3A5);
+        assert!(u.is_last());
+        assert_eq!(u.seq(), 0b0101);
+        assert_eq!(u.dest_unit(), 0x010203);
+        assert_eq!(u.network(), NetworkIdentity::new(0xBEE00, 0x3A5));
+
+        let u = roaming_update(0, false, 0x12345, 0x678);
+        assert!(!u.is_last());
+        assert_eq!(u.seq(), 0);
+        assert_eq!(u.network(), NetworkIdentity::new(0x12345, 0x678));
+    }
+
+    #[test]
+    fn test_roaming_addr_assembler() {
+        let mut a = RoamingAddrAssembler::new();
+
+        assert_eq!(a.feed(&roaming_update(0, false, 0xBEE00, 0x3A5)), None);
+        assert_eq!(a.feed(&roaming_update(1, true, 0x12345, 0x678)), Some(RoamingAddrList {
+            unit: 0x010203,
+            networks: vec![
+                NetworkIdentity::new(0xBEE00, 0x3A5),
+                NetworkIdentity::new(0x12345, 0x678),
+            ],
+        }));
+
+        // Single-packet list.
+        assert_eq!(a.feed(&roaming_update(0, true, 0x12345, 0x678)), Some(RoamingAddrList {
+            unit: 0x010203,
+            networks: vec![NetworkIdentity::new(0x12345, 0x678)],
+        }));
+    }
+
+    #[test]
+    fn test_roaming_addr_assembler_order() {
+        let mut a = RoamingAddrAssembler::new();
+
+        assert_eq!(a.feed(&roaming_update(0, false, 0x00001, 0x001)), None);
+        assert_eq!(a.feed(&roaming_update(2, true, 0x00003, 0x003)), None);
+        assert_eq!(a.feed(&roaming_update(1, false, 0x00002, 0x002)), Some(RoamingAddrList {
+            unit: 0x010203,
+            networks: vec![
+                NetworkIdentity::new(0x00001, 0x001),
+                NetworkIdentity::new(0x00002, 0x002),
+                NetworkIdentity::new(0x00003, 0x003),
+            ],
+        }));
+
+        // Partial lists are dropped on reset.
+        assert_eq!(a.feed(&roaming_update(0, false, 0x00001, 0x001)), None);
+        a.reset();
+        assert_eq!(a.feed(&roaming_update(1, true, 0x00002, 0x002)), None);
+    }
+
+    #[test]
+    fn test_roaming_addr_assembler_lost() {
+        let mut a = RoamingAddrAssembler::new();
+
+        // The first packet of a list was lost, so the next list starts over rather than
+        // completing with the stale packets.
+        assert_eq!(a.feed(&roaming_update(1, false, 0x00001, 0x001)), None);
+        assert_eq!(a.feed(&roaming_update(2, true, 0x00002, 0x002)), None);
+        assert_eq!(a.feed(&roaming_update(0, false, 0x00003, 0x003)), None);
+        assert_eq!(a.feed(&roaming_update(1, true, 0x00004, 0x004)), Some(RoamingAddrList {
+            unit: 0x010203,
+            networks: vec![
+                NetworkIdentity::new(0x00003, 0x003),
+                NetworkIdentity::new(0x00004, 0x004),
+            ],
+        }));
+
+        // A middle packet of a list was lost, along with the first of the next, whose
+        // last packet conflicts with the stale one.
+        assert_eq!(a.feed(&roaming_update(0, false, 0x00001, 0x001)), None);
+        assert_eq!(a.feed(&roaming_update(3, true, 0x00002, 0x002)), None);
+        assert_eq!(a.feed(&roaming_update(1, false, 0x00003, 0x003)), None);
+        assert_eq!(a.feed(&roaming_update(2, true, 0x00004, 0x004)), None);
+        assert_eq!(a.pending(), 1);
+
+        // A packet past the last conflicts too.
+        assert_eq!(a.feed(&roaming_update(0, false, 0x00001, 0x001)), None);
+        assert_eq!(a.feed(&roaming_update(1, true, 0x00002, 0x002)).unwrap().networks.len(),
+                   2);
+        assert_eq!(a.feed(&roaming_update(0, false, 0x00001, 0x001)), None);
+        assert_eq!(a.feed(&roaming_update(2, true, 0x00003, 0x003)), None);
+        assert_eq!(a.feed(&roaming_update(3, false, 0x00004, 0x004)), None);
+        assert_eq!(a.feed(&roaming_update(1, false, 0x00002, 0x002)), None);
+    }
+
+    #[test]
+    fn test_roaming_addr_assembler_bound() {
+        let mut a = RoamingAddrAssembler::new().with_max_lists(4);
+
+        assert_eq!(a.feed(&roaming_update(0, false, 0x00001, 0x001)), None);
+
+        for unit in 0x10..0x14 {
+            let mut upd = roaming_update(0, false, 0x00001, 0x001);
+            upd.0[5] = unit;
+            assert_eq!(a.feed(&upd), None);
+        }
+
+        // The oldest list was dropped to make room.
+        assert_eq!(a.pending(), 4);
+        assert_eq!(a.feed(&roaming_update(1, true, 0x00002, 0x002)), None);
+        assert_eq!(a.pending(), 4);
+    }
+
+    #[test]
+    fn test_ack_response() {
+        let t = TsbkFields::new([
+            0b00100000,
+            0b00000000,
+            0b10_011111,
+            0b00000000,
+            0b11001100,
+            0b00110011,
+            0b10101010,
+            0b00000001,
+            0b00000010,
+            0b00000011,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::AckResponse));
+        let a = AckResponse::new(t);
+        assert!(a.has_info());
+        assert!(!a.extended());
+        assert_eq!(a.service(), Some(TsbkOpcode::UnitCallAlert));
+        assert_eq!(a.info(), Some(AckInfo::Unit(0b11001100_00110011_10101010)));
+        assert_eq!(a.dest_unit(), 0x010203);
+
+        let t = TsbkFields::new([
+            0b00100000,
+            0b00000000,
+            0b11_101100,
+            0b10111110,
+            0b11100000,
+            0b00000011,
+            0b10100101,
+            0b00000001,
+            0b00000010,
+            0b00000011,
+            0b00000000,
+            0b00000000,
+        ]);
+        let a = AckResponse::new(t);
+        assert!(a.extended());
+        assert_eq!(a.service(), Some(TsbkOpcode::UnitRegResponse));
+        assert_eq!(a.info(), Some(AckInfo::Network(NetworkIdentity::new(0xBEE00, 0x3A5))));
+
+        let t = TsbkFields::new([
+            0b00100000,
+            0b00000000,
+            0b00_011111,
+            0b00000000,
+            0b11001100,
+            0b00110011,
+            0b10101010,
+            0b00000001,
+            0b00000010,
+            0b00000011,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(AckResponse::new(t).info(), None);
+    }
+
+    #[test]
+    fn test_deny_response() {
+        let t = TsbkFields::new([
+            0b00100111,
+            0b00000000,
+            0b10_000000,
+            0b00110001,
+            0b00000000,
+            0b11001100,
+            0b00110011,
+            0b00000001,
+            0b00000010,
+            0b00000011,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::DenyResponse));
+        let d = DenyResponse::new(t);
+        assert!(d.has_info());
+        assert_eq!(d.service(), Some(TsbkOpcode::GroupVoiceGrant));
+        assert_eq!(d.reason(), DenyReason::GroupUnauthorized);
+        assert_eq!(d.info(), Some(ResponseInfo::Group(TalkGroup::Other(0xCC33))));
+        assert_eq!(d.dest_unit(), 0x010203);
+    }
+
+    #[test]
+    fn test_queued_response() {
+        let t = TsbkFields::new([
+            0b00100001,
+            0b00000000,
+            0b10_000100,
+            0b00101111,
+            0b11001100,
+            0b00110011,
+            0b10101010,
+            0b00000001,
+            0b00000010,
+            0b00000011,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::QueuedResponse));
+        let q = QueuedResponse::new(t);
+        assert_eq!(q.service(), Some(TsbkOpcode::UnitVoiceGrant));
+        assert_eq!(q.reason(), QueueReason::DestUnitQueued);
+        assert_eq!(q.info(), Some(ResponseInfo::Unit(0b11001100_00110011_10101010)));
+        assert_eq!(q.dest_unit(), 0x010203);
+
+        let t = TsbkFields::new([
+            0b00100001,
+            0b00000000,
+            0b00_000000,
+            0b01000000,
+            0b11001100,
+            0b00110011,
+            0b10101010,
+            0b00000001,
+            0b00000010,
+            0b00000011,
+            0b00000000,
+            0b00000000,
+        ]);
+        let q = QueuedResponse::new(t);
+        assert_eq!(q.reason(), QueueReason::ChannelBusy);
+        assert_eq!(q.info(), None);
+    }
+
+    #[test]
+    fn test_response_info() {
+        let buf = [0xAB, 0x12, 0x34];
+
+        assert_eq!(ResponseInfo::new(TsbkOpcode::GroupVoiceGrant, &buf),
+                   ResponseInfo::Group(TalkGroup::Other(0x1234)));
+        assert_eq!(ResponseInfo::new(TsbkOpcode::GroupDataGrant, &buf),
+                   ResponseInfo::Group(TalkGroup::Other(0x1234)));
+        assert_eq!(ResponseInfo::new(TsbkOpcode::UnitVoiceGrant, &buf),
+                   ResponseInfo::Unit(0xAB1234));
+        assert_eq!(ResponseInfo::new(TsbkOpcode::PhoneGrant, &buf),
+                   ResponseInfo::Other(0xAB1234));
+    }
+
+    #[test]
+    fn test_deny_reason() {
+        use self::DenyReason::*;
+
+        let codes = [
+            (0x10, SrcUnitInvalid),
+            (0x11, SrcUnitUnauthorized),
+            (0x20, DestUnitInvalid),
+            (0x21, DestUnitUnauthorized),
+            (0x2F, DestUnitRefused),
+            (0x30, GroupInvalid),
+            (0x31, GroupUnauthorized),
+            (0x40, InvalidDialing),
+            (0x41, PhoneUnauthorized),
+            (0x42, PhoneInvalid),
+            (0x50, CallTimeout),
+            (0x51, PhoneTerminated),
+            (0x52, UnitTerminated),
+            (0x5F, CallPreempted),
+            (0x60, SiteAccessDenied),
+            (0xFF, ServiceUnsupported),
+            (0xF0, Custom(0xF0)),
+            (0xFE, Custom(0xFE)),
+            (0x00, Unknown(0x00)),
+            (0x12, Unknown(0x12)),
+        ];
+
+        for &(bits, reason) in codes.iter() {
+            assert_eq!(DenyReason::from_bits(bits), reason);
+        }
+    }
+
+    #[test]
+    fn test_queue_reason() {
+        use self::QueueReason::*;
+
+        let codes = [
+            (0x10, ResourcesBusy),
+            (0x20, DestUnitBusy),
+            (0x2F, DestUnitQueued),
+            (0x30, GroupBusy),
+            (0x40, ChannelBusy),
+            (0xF0, Custom(0xF0)),
+            (0xFF, Custom(0xFF)),
+            (0x00, Unknown(0x00)),
+            (0x41, Unknown(0x41)),
+        ];
+
+        for &(bits, reason) in codes.iter() {
+            assert_eq!(QueueReason::from_bits(bits), reason);
+        }
+    }
+
+    #[test]
+    fn test_unit_reg_command() {
+        let t = TsbkFields::new([
+            0b00101101,
+            0b00000000,
+            0b00000000,
+            0b00000000,
+            0b11001100,
+            0b00110011,
+            0b10101010,
+            0b00000001,
+            0b00000010,
+            0b00000011,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::UnitRegCommand));
+        let c = UnitRegCommand::new(t);
+        assert_eq!(c.dest_unit(), 0b11001100_00110011_10101010);
+        assert_eq!(c.src_unit(), 0x010203);
     }
 
     #[test]
@@ -663,6 +2120,8 @@ mod test {
         assert_eq!(r.rfss(), 0b11011010);
         assert_eq!(r.site(), 0b10101010);
         assert_eq!(r.dest_unit(), 0b111100000000111100110011);
+        assert_eq!(r.site_addr(), SiteAddress::new(0b11011010, 0b10101010));
+        assert_eq!(r.site_addr().to_string(), "218-170");
     }
 
     #[test]
@@ -743,6 +2202,68 @@ mod test {
         assert_eq!(g.channel().number(), 0b010111001100);
         assert_eq!(g.talkgroup(), TalkGroup::Other(0b0001100011100111));
         assert_eq!(g.src_unit(), 0b111100000111100000111100);
+        assert_eq!(g.channels(), ChannelPair::implicit(g.channel()));
+    }
+
+    #[test]
+    fn test_group_voice_update_explicit() {
+        // 700MHz site with downlinks on 769.00625MHz + 6.25kHz steps (ID 3) and
+        // explicit uplinks on 799.00625MHz + 6.25kHz steps (ID 4.)
+        let mut map = ChannelParamsMap::default();
+        map.update(&ChannelParamsUpdate::new(&[
+            0b0011_0011,
+            0b00100_101,
+            0b111000_00,
+            0b00110010,
+            0x09,
+            0x2A,
+            0xD2,
+            0x22,
+        ]));
+        map.update(&ChannelParamsUpdate::new(&[
+            0b0100_0011,
+            0b00100_000,
+            0b000000_00,
+            0b00110010,
+            0x09,
+            0x86,
+            0x5F,
+            0xA2,
+        ]));
+
+        let t = TsbkFields::new([
+            0b00000011,
+            0b00000000,
+            0b00000100,
+            0b00000000,
+            0x30, 0xC8,
+            0x40, 0xC8,
+            0x12, 0x34,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::GroupVoiceUpdateExplicit));
+        let u = GroupVoiceUpdateExplicit::new(t);
+        assert!(!u.opts().emergency());
+        assert_eq!(u.opts().prio(), 4);
+        assert_eq!(u.channel(), Channel::from_bits(0x30C8));
+        assert_eq!(u.channels(), ChannelPair::explicit(Channel::from_bits(0x30C8),
+                                                       Channel::from_bits(0x40C8)));
+        assert_eq!(u.talkgroup(), TalkGroup::Other(0x1234));
+
+        assert_eq!(map.frequencies(u.channels()), Some((770_256_250, 800_256_250)));
+        assert_eq!(map.rx_freq(Channel::from_bits(0x30C8)), Some(770_256_250));
+
+        // The same downlink granted implicitly reaches the same uplink through the
+        // transmit offset.
+        assert_eq!(map.tx_freq(Channel::from_bits(0x30C8)), Some(800_256_250));
+        assert_eq!(map.frequencies(ChannelPair::implicit(u.channel())),
+                   Some((770_256_250, 800_256_250)));
+
+        // Both channel IDs must be known.
+        assert_eq!(map.frequencies(ChannelPair::explicit(Channel::from_bits(0x30C8),
+                                                         Channel::from_bits(0x50C8))),
+                   None);
     }
 
     #[test]
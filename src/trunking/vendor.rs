@@ -0,0 +1,142 @@
+//! Manufacturer-specific TSBK packets.
+
+use trunking::tsbk::TsbkFields;
+
+/// Manufacturer ID of standard packets.
+pub const MFID_STANDARD: u8 = 0x00;
+/// Alternate manufacturer ID of standard packets.
+pub const MFID_STANDARD_ALT: u8 = 0x01;
+/// Manufacturer ID of Motorola packets.
+pub const MFID_MOTOROLA: u8 = 0x90;
+/// Manufacturer ID of Harris (L3Harris) packets.
+pub const MFID_HARRIS: u8 = 0xA4;
+
+/// Manufacturer ID, which determines the namespace of a packet's opcode.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ManufacturerId {
+    /// Standard packet.
+    Standard,
+    /// Standard packet with the alternate manufacturer ID.
+    StandardAlt,
+    /// Motorola packet.
+    Motorola,
+    /// Harris (L3Harris) packet.
+    Harris,
+    /// Packet from another manufacturer.
+    Other(u8),
+}
+
+impl ManufacturerId {
+    /// Parse a manufacturer ID from the given byte.
+    pub fn from_bits(bits: u8) -> ManufacturerId {
+        use self::ManufacturerId::*;
+
+        match bits {
+            MFID_STANDARD => Standard,
+            MFID_STANDARD_ALT => StandardAlt,
+            MFID_MOTOROLA => Motorola,
+            MFID_HARRIS => Harris,
+            _ => Other(bits),
+        }
+    }
+
+    /// Convert the manufacturer ID to its byte representation.
+    pub fn to_bits(self) -> u8 {
+        use self::ManufacturerId::*;
+
+        match self {
+            Standard => MFID_STANDARD,
+            StandardAlt => MFID_STANDARD_ALT,
+            Motorola => MFID_MOTOROLA,
+            Harris => MFID_HARRIS,
+            Other(bits) => bits,
+        }
+    }
+
+    /// Whether packets with this manufacturer ID use the standard opcodes.
+    pub fn is_standard(self) -> bool {
+        match self {
+            ManufacturerId::Standard | ManufacturerId::StandardAlt => true,
+            _ => false,
+        }
+    }
+}
+
+/// Manufacturer-specific 6-bit opcode, only meaningful along with its manufacturer ID.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct VendorOpcode {
+    /// Manufacturer that defines the opcode.
+    pub mfid: ManufacturerId,
+    /// Raw 6-bit opcode.
+    pub opcode: u8,
+}
+
+impl VendorOpcode {
+    /// Create a new `VendorOpcode` in the namespace of the given manufacturer.
+    pub fn new(mfid: ManufacturerId, opcode: u8) -> VendorOpcode {
+        VendorOpcode {
+            mfid: mfid,
+            opcode: opcode,
+        }
+    }
+}
+
+/// Manufacturer-specific TSBK packet without a typed decoder, passed through as-is.
+#[derive(Copy, Clone)]
+pub struct VendorTsbk(TsbkFields);
+
+impl VendorTsbk {
+    /// Create a new `VendorTsbk` from the given base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { VendorTsbk(tsbk) }
+
+    /// Manufacturer ID, which determines how the opcode and payload are interpreted.
+    pub fn mfid(&self) -> u8 { self.0.mfg() }
+    /// Manufacturer-specific 6-bit opcode.
+    pub fn opcode(&self) -> u8 { self.0.opcode_bits() }
+    /// Manufacturer-specific payload bytes.
+    pub fn payload(&self) -> &[u8] { self.0.payload() }
+    /// Underlying TSBK packet.
+    pub fn tsbk(&self) -> &TsbkFields { &self.0 }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_vendor_tsbk() {
+        let buf = [
+            0b10_111111,
+            0xA4,
+            0x01,
+            0x23,
+            0x45,
+            0x67,
+            0x89,
+            0xAB,
+            0xCD,
+            0xEF,
+            0xFF,
+            0xFF,
+        ];
+        let v = VendorTsbk::new(TsbkFields::new(buf));
+
+        assert_eq!(v.mfid(), MFID_HARRIS);
+        assert_eq!(v.opcode(), 0b111111);
+        assert_eq!(v.payload(), &buf[2..=9]);
+        assert!(v.tsbk().is_tail());
+    }
+
+    #[test]
+    fn test_manufacturer_id() {
+        for bits in 0..=0xFF {
+            assert_eq!(ManufacturerId::from_bits(bits).to_bits(), bits);
+        }
+
+        assert_eq!(ManufacturerId::from_bits(0x90), ManufacturerId::Motorola);
+        assert_eq!(ManufacturerId::from_bits(0x42), ManufacturerId::Other(0x42));
+        assert!(ManufacturerId::from_bits(0x00).is_standard());
+        assert!(ManufacturerId::from_bits(0x01).is_standard());
+        assert!(!ManufacturerId::from_bits(0xA4).is_standard());
+    }
+}
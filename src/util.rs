@@ -11,6 +11,119 @@ pub fn div_ceil<T>(a: T, b: T) -> T where
     (a + b - T::one()) / b
 }
 
+/// Calculate the square root of the given nonnegative value.
+#[cfg(any(feature = "std", test))]
+pub fn sqrt(x: f32) -> f32 { x.sqrt() }
+
+/// Calculate the square root of the given nonnegative value.
+#[cfg(not(any(feature = "std", test)))]
+pub fn sqrt(x: f32) -> f32 { newton_sqrt(x) }
+
+/// Calculate the base 10 logarithm of the given positive value.
+#[cfg(any(feature = "std", test))]
+pub fn log10(x: f32) -> f32 { x.log10() }
+
+/// Calculate the base 10 logarithm of the given positive value.
+#[cfg(not(any(feature = "std", test)))]
+pub fn log10(x: f32) -> f32 { series_log10(x) }
+
+/// Calculate the angle, in radians within [-π, π], of the given point, which is 0 at the
+/// origin.
+#[cfg(any(feature = "std", test))]
+pub fn atan2(y: f32, x: f32) -> f32 { y.atan2(x) }
+
+/// Calculate the angle, in radians within [-π, π], of the given point, which is 0 at the
+/// origin.
+#[cfg(not(any(feature = "std", test)))]
+pub fn atan2(y: f32, x: f32) -> f32 { series_atan2(y, x) }
+
+/// Calculate the base 10 logarithm of the given positive value without the platform
+/// math library.
+#[cfg(any(not(feature = "std"), test))]
+fn series_log10(x: f32) -> f32 {
+    if x <= 0.0 {
+        return std::f32::NEG_INFINITY;
+    }
+
+    // Split into a power of two and a mantissa in [1, 2), then take the natural log of
+    // the mantissa from the series for atanh, which converges quickly in that range.
+    let bits = x.to_bits();
+    let exp = ((bits >> 23) & 0xFF) as i32 - 127;
+    let m = f32::from_bits((bits & 0x007F_FFFF) | 0x3F80_0000);
+
+    let z = (m - 1.0) / (m + 1.0);
+    let z2 = z * z;
+    let mut term = z;
+    let mut ln = 0.0;
+
+    for k in 0..8 {
+        ln += term / (2 * k + 1) as f32;
+        term *= z2;
+    }
+
+    (2.0 * ln + exp as f32 * std::f32::consts::LN_2) * std::f32::consts::LOG10_E
+}
+
+/// Calculate the angle of the given point without the platform math library.
+#[cfg(any(not(feature = "std"), test))]
+fn series_atan2(y: f32, x: f32) -> f32 {
+    use std::f32::consts::PI;
+
+    let (ax, ay) = (if x < 0.0 { -x } else { x }, if y < 0.0 { -y } else { y });
+
+    if ax == 0.0 && ay == 0.0 {
+        return 0.0;
+    }
+
+    // Fold into the first octant, then halve the angle twice so the series for atan
+    // converges quickly.
+    let mut z = if ay <= ax { ay / ax } else { ax / ay };
+
+    for _ in 0..2 {
+        z /= 1.0 + newton_sqrt(1.0 + z * z);
+    }
+
+    let z2 = -z * z;
+    let mut term = z;
+    let mut atan = 0.0;
+
+    for k in 0..6 {
+        atan += term / (2 * k + 1) as f32;
+        term *= z2;
+    }
+
+    let mut a = 4.0 * atan;
+
+    if ay > ax {
+        a = PI / 2.0 - a;
+    }
+
+    if x < 0.0 {
+        a = PI - a;
+    }
+
+    if y < 0.0 { -a } else { a }
+}
+
+/// Calculate the square root of the given nonnegative value without the platform math
+/// library.
+#[cfg(any(not(feature = "std"), test))]
+fn newton_sqrt(x: f32) -> f32 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+
+    // Halving the exponent gives an estimate within a few percent, which Newton's method
+    // refines to full precision in a few steps.
+    let mut y = f32::from_bits((x.to_bits() >> 1) + 0x1FC0_0000);
+
+    for _ in 0..4 {
+        y = 0.5 * (y + x / y);
+    }
+
+    y
+}
+
 /// Slice 16 bits from the given bytes (in P25 big endian format.).
 pub fn slice_u16(bytes: &[u8]) -> u16 {
     (bytes[0] as u16) << 8 | bytes[1] as u16
@@ -26,10 +139,55 @@ pub fn slice_u32(bytes: &[u8]) -> u32 {
     (slice_u16(bytes) as u32) << 16 | slice_u16(&bytes[2..]) as u32
 }
 
+/// Slice 16 bits from the given bytes, or return `None` if there are fewer than 2.
+pub fn slice_u16_checked(bytes: &[u8]) -> Option<u16> {
+    if bytes.len() < 2 { None } else { Some(slice_u16(bytes)) }
+}
+
+/// Slice 24 bits from the given bytes, or return `None` if there are fewer than 3.
+pub fn slice_u24_checked(bytes: &[u8]) -> Option<u32> {
+    if bytes.len() < 3 { None } else { Some(slice_u24(bytes)) }
+}
+
+/// Slice 32 bits from the given bytes, or return `None` if there are fewer than 4.
+pub fn slice_u32_checked(bytes: &[u8]) -> Option<u32> {
+    if bytes.len() < 4 { None } else { Some(slice_u32(bytes)) }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn test_sqrt() {
+        assert_eq!(newton_sqrt(0.0), 0.0);
+        assert_eq!(newton_sqrt(4.0), 2.0);
+
+        for &x in [1e-6, 0.0123, 0.5, 1.0, 37.7, 1e6].iter() {
+            assert!((newton_sqrt(x) - x.sqrt()).abs() <= x.sqrt() * 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_series_log10() {
+        for &x in [1e-6, 0.0123, 0.5, 1.0, 1.999, 37.7, 1e6].iter() {
+            assert!((series_log10(x) - x.log10()).abs() <= 1e-5, "{}", x);
+        }
+
+        assert_eq!(series_log10(0.0), std::f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn test_series_atan2() {
+        for &(y, x) in [(0.0, 1.0), (1.0, 0.0), (0.3, 0.7), (0.7, 0.3), (0.5, -0.2),
+                        (-0.1, -4.0), (-3.0, 1e-3), (1e-6, 1.0), (0.0, -1.0)].iter()
+        {
+            assert!((series_atan2(y, x) - y.atan2(x)).abs() <= 1e-6, "{} {}", y, x);
+        }
+
+        assert_eq!(series_atan2(0.0, 0.0), 0.0);
+    }
+
     #[test]
     fn test_div_ceil() {
         assert_eq!(div_ceil(13, 12), 2);
@@ -54,4 +212,36 @@ mod test {
         assert_eq!(slice_u32(&[0xDE, 0xAD, 0xBE, 0xEF]), 0xDEADBEEF);
         assert_eq!(slice_u32(&[0xDE, 0xAD, 0xBE, 0xEF, 0x12]), 0xDEADBEEF);
     }
+
+    #[test]
+    #[should_panic]
+    fn test_slice_u24_short() {
+        slice_u24(&[0xDE, 0xAD]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_slice_u32_short() {
+        slice_u32(&[0xDE, 0xAD, 0xBE]);
+    }
+
+    #[test]
+    fn test_slice_checked() {
+        assert_eq!(slice_u16_checked(&[0xDE, 0xAD]), Some(0xDEAD));
+        assert_eq!(slice_u16_checked(&[0xDE, 0xAD, 0xBE]), Some(0xDEAD));
+        assert_eq!(slice_u16_checked(&[0xDE]), None);
+        assert_eq!(slice_u16_checked(&[]), None);
+
+        assert_eq!(slice_u24_checked(&[0xDE, 0xAD, 0xBE]), Some(0xDEADBE));
+        assert_eq!(slice_u24_checked(&[0xDE, 0xAD, 0xBE, 0xEF]), Some(0xDEADBE));
+        assert_eq!(slice_u24_checked(&[0xDE, 0xAD]), None);
+
+        assert_eq!(slice_u32_checked(&[0xDE, 0xAD, 0xBE, 0xEF]), Some(0xDEADBEEF));
+        assert_eq!(slice_u32_checked(&[0xDE, 0xAD, 0xBE, 0xEF, 0x12]), Some(0xDEADBEEF));
+        assert_eq!(slice_u32_checked(&[0xDE, 0xAD, 0xBE]), None);
+
+        // Most significant byte first.
+        assert_eq!(slice_u32_checked(&[0x00, 0x00, 0x00, 0x01]), Some(1));
+        assert_eq!(slice_u24_checked(&[0x80, 0x00, 0x00]), Some(0x800000));
+    }
 }
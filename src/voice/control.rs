@@ -3,7 +3,7 @@
 use consts::LINK_CONTROL_BYTES;
 use util::{slice_u16, slice_u24};
 
-use trunking::fields::{TalkGroup, ServiceOptions};
+use trunking::fields::{Channel, ChannelPair, TalkGroup, ServiceOptions};
 
 /// Buffer of bytes that represents a link control packet.
 pub type Buf = [u8; LINK_CONTROL_BYTES];
@@ -95,11 +95,16 @@ impl LinkControlFields {
 
     /// Type of data contained in the payload.
     pub fn opcode(&self) -> Option<LinkControlOpcode> {
-        LinkControlOpcode::from_bits(self.0[0] & 0x3F)
+        LinkControlOpcode::from_bits(self.opcode_bits())
     }
+    /// Raw 6-bit opcode, including those without a decoder.
+    pub fn opcode_bits(&self) -> u8 { self.0[0] & 0x3F }
 
     /// Bytes that make up the payload.
     pub fn payload(&self) -> &[u8] { &self.0[1..=8] }
+
+    /// Bytes that make up the packet.
+    pub fn bytes(&self) -> &Buf { &self.0 }
 }
 
 /// Identity of unit transmitting on the current talkgroup traffic channel.
@@ -119,6 +124,26 @@ impl GroupVoiceTraffic {
     pub fn src_unit(&self) -> u32 { slice_u24(&self.0[6..]) }
 }
 
+/// Talkgroup active on a voice traffic channel with separately specified receive and
+/// transmit channels.
+pub struct GroupVoiceUpdateExplicit(Buf);
+
+impl GroupVoiceUpdateExplicit {
+    /// Create a new `GroupVoiceUpdateExplicit` from the base LC decoder.
+    pub fn new(lc: LinkControlFields) -> Self { GroupVoiceUpdateExplicit(lc.0) }
+
+    /// Options granted for the traffic channel.
+    pub fn opts(&self) -> ServiceOptions { ServiceOptions::new(self.0[2]) }
+    /// Talkgroup for the conversation.
+    pub fn talkgroup(&self) -> TalkGroup { TalkGroup::new(&self.0[3..=4]) }
+    /// Channel the subscriber receives the conversation on.
+    pub fn channel(&self) -> Channel { Channel::new(&self.0[5..=6]) }
+    /// Receive and transmit channels of the traffic channel.
+    pub fn channels(&self) -> ChannelPair {
+        ChannelPair::explicit(self.channel(), Channel::new(&self.0[7..=8]))
+    }
+}
+
 /// Identity of units transmitting on current unit-to-unit traffic channel.
 pub struct UnitVoiceTraffic(Buf);
 
@@ -234,6 +259,26 @@ mod test {
         assert_eq!(opts.prio(), 5);
     }
 
+    #[test]
+    fn test_group_voice_update_explicit() {
+        let lc = LinkControlFields::new([
+            0b00000100,
+            0b00000000,
+            0b10000011,
+            0x12, 0x34,
+            0x30, 0xC8,
+            0x40, 0xC8,
+        ]);
+        assert_eq!(lc.opcode(), Some(LinkControlOpcode::GroupVoiceUpdateExplicit));
+        let dec = GroupVoiceUpdateExplicit::new(lc);
+
+        assert!(dec.opts().emergency());
+        assert_eq!(dec.opts().prio(), 3);
+        assert_eq!(dec.talkgroup(), TalkGroup::Other(0x1234));
+        assert_eq!(dec.channel(), Channel::from_bits(0x30C8));
+        assert_eq!(dec.channels().tx, Some(Channel::from_bits(0x40C8)));
+    }
+
     #[test]
     fn test_channel_params_update() {
         let l = LinkControlFields::new([
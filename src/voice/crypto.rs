@@ -1,11 +1,24 @@
 //! Decode Cryptographic Control (CC) packets.
 
+use std::vec::Vec;
+
 use consts::CRYPTO_CONTROL_BYTES;
+use error::{Result, P25Error};
+use message::receiver::MessageEvent;
+use trunking::decode::TsbkMessage;
+use trunking::fields::ProtectionParamBroadcast;
 use util::slice_u16;
 
 /// Buffer of bytes that represent a crypto control packet.
 pub type Buf = [u8; CRYPTO_CONTROL_BYTES];
 
+/// Message indicator, the initialization vector of a superframe.
+pub type Mi = [u8; 9];
+
+/// Number of missed superframes the MI prediction bridges before reporting a
+/// discontinuity.
+const MAX_MISSED: usize = 2;
+
 /// Information necessary to decrypt an encrypted message.
 pub struct CryptoControlFields(Buf);
 
@@ -19,6 +32,248 @@ impl CryptoControlFields {
     pub fn alg(&self) -> CryptoAlgorithm { CryptoAlgorithm::from_bits(self.0[9]) }
     /// Encryption key to use.
     pub fn key(&self) -> u16 { slice_u16(&self.0[10..]) }
+
+    /// Return `Encrypted` with the algorithm and key if the message is encrypted.
+    pub fn check_clear(&self) -> Result<()> {
+        match self.alg() {
+            CryptoAlgorithm::Unencrypted => Ok(()),
+            _ => Err(P25Error::Encrypted {
+                alg: self.0[9],
+                key: self.key(),
+            }),
+        }
+    }
+}
+
+/// Predict the MI of the superframe following the one with the given MI, by stepping the
+/// first 64 bits through the MI LFSR.
+pub fn next_mi(mi: &[u8]) -> Mi {
+    let mut lfsr = mi[..8].iter().fold(0u64, |s, &b| s << 8 | b as u64);
+
+    for _ in 0..64 {
+        let bit = (lfsr >> 63 ^ lfsr >> 61 ^ lfsr >> 45 ^ lfsr >> 37 ^ lfsr >> 26 ^
+                   lfsr >> 14) & 1;
+        lfsr = lfsr << 1 | bit;
+    }
+
+    let mut next = [0; 9];
+
+    for (i, b) in next[..8].iter_mut().enumerate() {
+        *b = (lfsr >> (56 - i * 8)) as u8;
+    }
+
+    next
+}
+
+/// Event in the encryption state of a voice call.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CryptoEvent {
+    /// The MI of an LDU2 didn't follow from those before it, as after a transmitter
+    /// restart, so the keystream has to be reseeded from the received MI.
+    MiDiscontinuity {
+        /// MI predicted from the previous superframe.
+        expected: Mi,
+        /// MI actually received.
+        received: Mi,
+    },
+}
+
+/// Checks that the MI of each superframe of an encrypted call follows from the previous
+/// one.
+///
+/// The MI of the voice header seeds the prediction, and each LDU2 carries the MI of the
+/// next superframe. Up to a couple of missed superframes are bridged by stepping the
+/// prediction further, and anything else reseeds it from the received MI.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MiTracker {
+    /// MI of the current superframe, if in an encrypted call.
+    mi: Option<Mi>,
+    /// Number of discontinuities in the current or last call.
+    discontinuities: usize,
+}
+
+impl MiTracker {
+    /// Create a new `MiTracker` outside of any call.
+    pub fn new() -> MiTracker { MiTracker::default() }
+
+    /// Handle the given receiver event, returning any resulting crypto event.
+    pub fn feed(&mut self, event: &MessageEvent) -> Option<CryptoEvent> {
+        match *event {
+            MessageEvent::VoiceHeader(ref h) => {
+                self.discontinuities = 0;
+                self.mi = seed(h.crypto_alg(), h.crypto_init());
+                None
+            },
+            MessageEvent::CryptoControl(ref cc) => self.check(cc),
+            MessageEvent::VoiceTerm(_) => {
+                self.mi = None;
+                None
+            },
+            _ => None,
+        }
+    }
+
+    /// Check the MI of the given crypto control word against the prediction.
+    fn check(&mut self, cc: &CryptoControlFields) -> Option<CryptoEvent> {
+        let prev = self.mi;
+
+        // Following superframes are predicted from the received MI whatever the outcome.
+        self.mi = seed(cc.alg(), cc.init());
+
+        let (prev, received) = match (prev, self.mi) {
+            (Some(prev), Some(received)) => (prev, received),
+            // Late entry, or the call is in the clear.
+            _ => return None,
+        };
+
+        let expected = next_mi(&prev);
+        let mut mi = expected;
+
+        for _ in 0..MAX_MISSED + 1 {
+            if mi == received {
+                return None;
+            }
+
+            mi = next_mi(&mi);
+        }
+
+        self.discontinuities += 1;
+
+        Some(CryptoEvent::MiDiscontinuity {
+            expected: expected,
+            received: received,
+        })
+    }
+
+    /// Number of discontinuities in the current call, or the last one if none is in
+    /// progress.
+    pub fn discontinuities(&self) -> usize { self.discontinuities }
+}
+
+/// MI to track for a call using the given algorithm and MI, if it's encrypted.
+fn seed(alg: CryptoAlgorithm, init: &[u8]) -> Option<Mi> {
+    if alg == CryptoAlgorithm::Unencrypted {
+        return None;
+    }
+
+    let mut mi = [0; 9];
+    mi.copy_from_slice(init);
+
+    Some(mi)
+}
+
+/// Encryption key identified by its algorithm and key ID.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct KeyId {
+    /// Algorithm the key is used with.
+    pub alg: CryptoAlgorithm,
+    /// Key ID within the algorithm.
+    pub key: u16,
+}
+
+impl KeyId {
+    /// Create a new `KeyId` for the given algorithm and key ID.
+    pub fn new(alg: CryptoAlgorithm, key: u16) -> KeyId {
+        KeyId {
+            alg: alg,
+            key: key,
+        }
+    }
+}
+
+/// Key in use on the system that isn't provisioned in a `KeyStore`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MissingKey {
+    /// Algorithm and key ID of the key.
+    pub id: KeyId,
+    /// Unit the key was advertised for, if it came from a protection parameter
+    /// broadcast.
+    pub unit: Option<u32>,
+}
+
+/// Set of provisioned encryption keys, which reports the keys advertised by the system
+/// or used by calls that aren't provisioned.
+///
+/// Protection parameter broadcasts advertise keys before any call uses them, so feeding
+/// the store every receiver event validates the provisioned keys ahead of time. Each
+/// missing key is reported once, and clear calls never report anything.
+#[derive(Clone, Debug, Default)]
+pub struct KeyStore {
+    /// Provisioned keys.
+    keys: Vec<KeyId>,
+    /// Missing keys reported so far, in order.
+    missing: Vec<KeyId>,
+}
+
+impl KeyStore {
+    /// Create a new `KeyStore` with no provisioned keys.
+    pub fn new() -> KeyStore { KeyStore::default() }
+
+    /// Provision the key with the given algorithm and key ID.
+    pub fn with_key(mut self, alg: CryptoAlgorithm, key: u16) -> Self {
+        self.insert(alg, key);
+        self
+    }
+
+    /// Provision the key with the given algorithm and key ID.
+    pub fn insert(&mut self, alg: CryptoAlgorithm, key: u16) {
+        let id = KeyId::new(alg, key);
+
+        if !self.keys.contains(&id) {
+            self.keys.push(id);
+        }
+
+        self.missing.retain(|&m| m != id);
+    }
+
+    /// Whether the key with the given algorithm and key ID is provisioned, which is
+    /// always the case for clear calls.
+    pub fn contains(&self, alg: CryptoAlgorithm, key: u16) -> bool {
+        alg == CryptoAlgorithm::Unencrypted || self.keys.contains(&KeyId::new(alg, key))
+    }
+
+    /// Handle the given receiver event, returning the key it advertises or uses if
+    /// that key isn't provisioned and hasn't been reported yet.
+    pub fn feed(&mut self, event: &MessageEvent) -> Option<MissingKey> {
+        match *event {
+            MessageEvent::TrunkingControl(ref tsbk) => match TsbkMessage::new(tsbk) {
+                TsbkMessage::ProtectionParamBroadcast(p) => self.check_broadcast(&p),
+                _ => None,
+            },
+            MessageEvent::VoiceHeader(ref h) =>
+                self.check(h.crypto_alg(), h.crypto_key(), None),
+            MessageEvent::CryptoControl(ref cc) => self.check(cc.alg(), cc.key(), None),
+            _ => None,
+        }
+    }
+
+    /// Check the key advertised by the given protection parameter broadcast.
+    pub fn check_broadcast(&mut self, params: &ProtectionParamBroadcast)
+        -> Option<MissingKey>
+    {
+        self.check(params.alg(), params.key(), Some(params.dest_unit()))
+    }
+
+    /// Check the given key, reporting it if it's missing for the first time.
+    fn check(&mut self, alg: CryptoAlgorithm, key: u16, unit: Option<u32>)
+        -> Option<MissingKey>
+    {
+        let id = KeyId::new(alg, key);
+
+        if self.contains(alg, key) || self.missing.contains(&id) {
+            return None;
+        }
+
+        self.missing.push(id);
+
+        Some(MissingKey {
+            id: id,
+            unit: unit,
+        })
+    }
+
+    /// Missing keys reported so far, in the order they were first seen.
+    pub fn missing(&self) -> &[KeyId] { &self.missing[..] }
 }
 
 /// Type of cryptographic algorithm.
@@ -57,6 +312,25 @@ impl CryptoAlgorithm {
             b => Other(b),
         }
     }
+
+    /// Convert the crypto algorithm to its 8-bit identifier.
+    pub fn to_bits(self) -> u8 {
+        use self::CryptoAlgorithm::*;
+
+        match self {
+            Accordion => 0x00,
+            BatonEven => 0x01,
+            Firefly => 0x02,
+            Mayfly => 0x03,
+            Saville => 0x04,
+            BatonOdd => 0x41,
+            Unencrypted => 0x80,
+            Des => 0x81,
+            TripleDes => 0x83,
+            Aes => 0x84,
+            Other(b) => b,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -75,5 +349,103 @@ mod test {
         assert_eq!(c.init(), &[0,0,0,1,0,0,0,2,0]);
         assert_eq!(c.alg(), Aes);
         assert_eq!(c.key(), 0xDEAD);
+        assert_eq!(c.check_clear(), Err(P25Error::Encrypted { alg: 0x84, key: 0xDEAD }));
+
+        let c = CryptoControlFields::new([0, 0, 0, 0, 0, 0, 0, 0, 0, 0x80, 0, 0]);
+        assert_eq!(c.check_clear(), Ok(()));
+    }
+
+    /// Crypto control word with the given MI for an AES call.
+    fn cc(mi: Mi) -> MessageEvent {
+        let mut buf = [0; CRYPTO_CONTROL_BYTES];
+        buf[..9].copy_from_slice(&mi);
+        buf[9] = 0x84;
+
+        MessageEvent::CryptoControl(CryptoControlFields::new(buf))
+    }
+
+    #[test]
+    fn test_alg_bits() {
+        for bits in 0..=0xFF {
+            assert_eq!(CryptoAlgorithm::from_bits(bits).to_bits(), bits);
+        }
+    }
+
+    #[test]
+    fn test_next_mi() {
+        assert_eq!(next_mi(&[0; 9]), [0; 9]);
+
+        let mi = next_mi(&[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert!(mi != [1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(mi[8], 0);
+        assert_eq!(next_mi(&mi), next_mi(&[mi[0], mi[1], mi[2], mi[3], mi[4], mi[5], mi[6],
+                                           mi[7], 0xFF]));
+    }
+
+    #[test]
+    fn test_tracker() {
+        let mut t = MiTracker::new();
+        let mut mi = [0xDE, 0xAD, 0xBE, 0xEF, 0x01, 0x23, 0x45, 0x67, 0x00];
+
+        // Late entry seeds the prediction.
+        assert_eq!(t.feed(&cc(mi)), None);
+
+        for _ in 0..4 {
+            mi = next_mi(&mi);
+            assert_eq!(t.feed(&cc(mi)), None);
+        }
+
+        // A single missed superframe is bridged.
+        mi = next_mi(&next_mi(&mi));
+        assert_eq!(t.feed(&cc(mi)), None);
+        assert_eq!(t.discontinuities(), 0);
+
+        // A restart is reported, and the prediction continues from it.
+        let restart = [1, 2, 3, 4, 5, 6, 7, 8, 0];
+        assert_eq!(t.feed(&cc(restart)), Some(CryptoEvent::MiDiscontinuity {
+            expected: next_mi(&mi),
+            received: restart,
+        }));
+        assert_eq!(t.feed(&cc(next_mi(&restart))), None);
+        assert_eq!(t.discontinuities(), 1);
+
+        // Clear calls aren't checked.
+        let clear = CryptoControlFields::new([1, 0, 0, 0, 0, 0, 0, 0, 0, 0x80, 0, 0]);
+        assert_eq!(t.feed(&MessageEvent::CryptoControl(clear)), None);
+        assert_eq!(t.feed(&cc(restart)), None);
+    }
+
+    #[test]
+    fn test_keystore() {
+        let mut ks = KeyStore::new().with_key(Aes, 0x1234);
+
+        assert!(ks.contains(Aes, 0x1234));
+        assert!(!ks.contains(Aes, 0x1235));
+        assert!(!ks.contains(Des, 0x1234));
+        assert!(ks.contains(Unencrypted, 0));
+
+        let buf = [0, 0, 0x81, 0xBE, 0xEF, 0x12, 0x34, 0x56];
+        assert_eq!(ks.check_broadcast(&ProtectionParamBroadcast::new(&buf)),
+                   Some(MissingKey { id: KeyId::new(Des, 0xBEEF), unit: Some(0x123456) }));
+
+        // Only reported once, whether advertised again or used by a call.
+        assert_eq!(ks.check_broadcast(&ProtectionParamBroadcast::new(&buf)), None);
+        assert_eq!(ks.feed(&MessageEvent::CryptoControl(CryptoControlFields::new(
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0x81, 0xBE, 0xEF]))), None);
+        assert_eq!(ks.missing(), &[KeyId::new(Des, 0xBEEF)]);
+
+        // Provisioned and clear keys aren't reported.
+        let buf = [0, 0, 0x84, 0x12, 0x34, 0x12, 0x34, 0x56];
+        assert_eq!(ks.check_broadcast(&ProtectionParamBroadcast::new(&buf)), None);
+        assert_eq!(ks.feed(&MessageEvent::CryptoControl(CryptoControlFields::new(
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0x80, 0, 0]))), None);
+
+        // Calls with missing keys are reported without a unit.
+        assert_eq!(ks.feed(&cc([1; 9])),
+                   Some(MissingKey { id: KeyId::new(Aes, 0), unit: None }));
+
+        // Provisioning a key clears its report.
+        ks.insert(Des, 0xBEEF);
+        assert_eq!(ks.missing(), &[KeyId::new(Aes, 0)]);
     }
 }
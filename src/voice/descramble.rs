@@ -9,6 +9,12 @@ pub fn descramble(dibits: &[Dibit; consts::FRAME_DIBITS], idx: usize) -> u32 {
     DESCRAMBLERS[idx].descramble(dibits)
 }
 
+/// Scramble the given PN-scrambled, coded chunk `u_{idx}` into its portion of the given
+/// voice frame dibits. This is the inverse of `descramble`.
+pub fn scramble(dibits: &mut [Dibit; consts::FRAME_DIBITS], idx: usize, word: u32) {
+    DESCRAMBLERS[idx].scramble(dibits, word)
+}
+
 /// Set of descramblers for each associated chunk `u_0`, ..., `u_7`.
 const DESCRAMBLERS: [VoiceFrameDescrambler; 8] = [
     VoiceFrameDescrambler(&[
@@ -59,6 +65,22 @@ impl VoiceFrameDescrambler {
             })
         })
     }
+
+    /// Scramble the given chunk into the dibits, MSB first.
+    pub fn scramble(&self, dibits: &mut [Dibit; consts::FRAME_DIBITS], word: u32) {
+        let bits = self.0.iter().fold(0, |sum, zz| sum + zz.remain);
+
+        for (i, (idx, hi)) in self.0.iter().flat_map(|&zz| zz).enumerate() {
+            let bit = (word >> (bits - 1 - i) & 1) as u8;
+            let cur = dibits[idx].bits();
+
+            dibits[idx] = Dibit::new(if hi {
+                bit << 1 | cur & 0b01
+            } else {
+                cur & 0b10 | bit
+            });
+        }
+    }
 }
 
 /// Walks the zigzagging interleave schedule used for voice frames.
@@ -117,7 +139,8 @@ impl Iterator for ZigZag {
 
 #[cfg(test)]
 mod test {
-    use super::DESCRAMBLERS;
+    use super::{DESCRAMBLERS, descramble, scramble};
+    use bits::Dibit;
     use consts;
 
     #[test]
@@ -136,4 +159,27 @@ mod test {
             assert_eq!(v, 2);
         }
     }
+
+    #[test]
+    fn test_scramble() {
+        let mut dibits = [Dibit::default(); consts::FRAME_DIBITS];
+        let words = [
+            0x5A5A5A,
+            0x2C3E1F,
+            0x7FFFFF,
+            0x000001,
+            0x5A5A,
+            0x2AAA,
+            0x7001,
+            0x55,
+        ];
+
+        for (idx, &w) in words.iter().enumerate() {
+            scramble(&mut dibits, idx, w);
+        }
+
+        for (idx, &w) in words.iter().enumerate() {
+            assert_eq!(descramble(&dibits, idx), w);
+        }
+    }
 }
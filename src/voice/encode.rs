@@ -0,0 +1,375 @@
+//! Encode voice data units into coded dibit streams.
+
+use std::vec::Vec;
+
+use collect_slice::CollectSlice;
+
+use baseband::sync::SYNC_GENERATOR;
+use bits::{Dibit, Dibits, Hexbit, Hexbits};
+use coding::{cyclic, golay, hamming, reed_solomon};
+use message::nid::{DataUnit, NetworkAccessCode, NetworkId};
+use message::status::{StatusCode, StatusInterleaver};
+use voice::descramble::scramble;
+use voice::rand::PseudoRand;
+use voice::{control, crypto, header};
+
+use consts::{
+    DATA_FRAG_DIBITS,
+    EXTRA_HEXBITS,
+    EXTRA_PIECE_DIBITS,
+    EXTRA_WORD_DIBITS,
+    FRAME_DIBITS,
+    HEADER_HEXBITS,
+    HEADER_WORD_DIBITS,
+    LC_TERM_WORD_DIBITS,
+};
+
+/// Number of voice frames in each frame group.
+pub const GROUP_FRAMES: usize = 9;
+
+/// Number of zero dibits following the NID in a simple terminator.
+const TERM_ZERO_DIBITS: usize = 14;
+
+/// Code the given voice header into the dibits of a header packet.
+pub fn header(buf: &header::Buf) -> Vec<Dibit> {
+    let mut hexbits = [Hexbit::default(); HEADER_HEXBITS];
+    Hexbits::new(buf.iter().cloned()).collect_slice(&mut hexbits[..]);
+
+    reed_solomon::long::encode(&mut hexbits);
+
+    hexbits.iter()
+        .flat_map(|h| word(golay::shortened::encode(h.bits()) as u64, HEADER_WORD_DIBITS))
+        .collect()
+}
+
+/// Code and scramble the given IMBE chunks `u_0`, ..., `u_7` into the interleaved
+/// dibits of a voice frame.
+pub fn voice_frame(chunks: &[u32; 8]) -> [Dibit; FRAME_DIBITS] {
+    let mut dibits = [Dibit::default(); FRAME_DIBITS];
+
+    // The first chunk seeds the PN sequence that scrambles the others.
+    let mut prand = PseudoRand::new(chunks[0] as u16);
+    scramble(&mut dibits, 0, golay::standard::encode(chunks[0] as u16));
+
+    for idx in 1..=3 {
+        scramble(&mut dibits, idx,
+                 golay::standard::encode(chunks[idx] as u16) ^ prand.next_23());
+    }
+
+    for idx in 4..=6 {
+        scramble(&mut dibits, idx,
+                 hamming::standard::encode(chunks[idx] as u16) as u32 ^ prand.next_15());
+    }
+
+    scramble(&mut dibits, 7, chunks[7]);
+
+    dibits
+}
+
+/// Code the given voice frames, link control word, and low-speed data word into the
+/// dibits of a link control frame group (LDU1.)
+pub fn lc_frame_group(frames: &[[u32; 8]; GROUP_FRAMES], lc: &control::Buf, lsd: u16)
+    -> Vec<Dibit>
+{
+    let mut hexbits = [Hexbit::default(); EXTRA_HEXBITS];
+    Hexbits::new(lc.iter().cloned()).collect_slice(&mut hexbits[..]);
+
+    reed_solomon::short::encode(&mut hexbits);
+
+    frame_group(frames, &hexbits, lsd)
+}
+
+/// Code the given voice frames, crypto control word, and low-speed data word into the
+/// dibits of a crypto control frame group (LDU2.)
+pub fn cc_frame_group(frames: &[[u32; 8]; GROUP_FRAMES], cc: &crypto::Buf, lsd: u16)
+    -> Vec<Dibit>
+{
+    let mut hexbits = [Hexbit::default(); EXTRA_HEXBITS];
+    Hexbits::new(cc.iter().cloned()).collect_slice(&mut hexbits[..]);
+
+    reed_solomon::medium::encode(&mut hexbits);
+
+    frame_group(frames, &hexbits, lsd)
+}
+
+/// Code the given link control word into the dibits of a link control terminator.
+pub fn lc_terminator(lc: &control::Buf) -> Vec<Dibit> {
+    let mut hexbits = [Hexbit::default(); EXTRA_HEXBITS];
+    Hexbits::new(lc.iter().cloned()).collect_slice(&mut hexbits[..]);
+
+    reed_solomon::short::encode(&mut hexbits);
+
+    // Each pair of hexbits is coded as a single 12-bit word.
+    hexbits.chunks(2)
+        .flat_map(|pair| {
+            let data = (pair[0].bits() as u16) << 6 | pair[1].bits() as u16;
+            word(golay::extended::encode(data) as u64, LC_TERM_WORD_DIBITS)
+        })
+        .collect()
+}
+
+/// Interleave the given voice frames, RS-coded extra packet, and low-speed data word into
+/// the dibits of a frame group.
+fn frame_group(frames: &[[u32; 8]; GROUP_FRAMES], extra: &[Hexbit; EXTRA_HEXBITS],
+               lsd: u16)
+    -> Vec<Dibit>
+{
+    let extra: Vec<Dibit> = extra.iter()
+        .flat_map(|h| {
+            word(hamming::shortened::encode(h.bits()) as u64, EXTRA_WORD_DIBITS)
+        })
+        .collect();
+
+    let mut pieces = extra.chunks(EXTRA_PIECE_DIBITS);
+    let mut dibits = vec![];
+
+    for (idx, chunks) in frames.iter().enumerate() {
+        dibits.extend(voice_frame(chunks).iter().cloned());
+
+        match idx {
+            1..=6 => dibits.extend(pieces.next().unwrap().iter().cloned()),
+            7 => for &byte in [(lsd >> 8) as u8, lsd as u8].iter() {
+                dibits.extend(word(cyclic::encode(byte) as u64, DATA_FRAG_DIBITS));
+            },
+            _ => {},
+        }
+    }
+
+    dibits
+}
+
+/// Split the given codeword into the given number of dibits, MSB first.
+fn word(bits: u64, dibits: usize) -> Vec<Dibit> {
+    (0..dibits).rev()
+        .map(|idx| Dibit::new((bits >> (idx * 2)) as u8 & 0b11))
+        .collect()
+}
+
+/// Builds voice data units, framed with the frame sync, NID, and status symbols.
+pub struct VoiceEncoder {
+    /// NAC of data units.
+    nac: NetworkAccessCode,
+    /// Status code interleaved into data units.
+    status: StatusCode,
+}
+
+impl VoiceEncoder {
+    /// Create a new `VoiceEncoder` for data units on the given NAC.
+    pub fn new(nac: NetworkAccessCode) -> VoiceEncoder {
+        VoiceEncoder {
+            nac: nac,
+            status: StatusCode::InboundIdle,
+        }
+    }
+
+    /// Interleave the given status code instead of the default.
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Build a voice header data unit.
+    pub fn header(&self, buf: &header::Buf) -> Vec<Dibit> {
+        self.frame(DataUnit::VoiceHeader, header(buf))
+    }
+
+    /// Build a link control frame group (LDU1) data unit.
+    pub fn lc_frame_group(&self, frames: &[[u32; 8]; GROUP_FRAMES], lc: &control::Buf,
+                          lsd: u16)
+        -> Vec<Dibit>
+    {
+        self.frame(DataUnit::VoiceLCFrameGroup, lc_frame_group(frames, lc, lsd))
+    }
+
+    /// Build a crypto control frame group (LDU2) data unit.
+    pub fn cc_frame_group(&self, frames: &[[u32; 8]; GROUP_FRAMES], cc: &crypto::Buf,
+                          lsd: u16)
+        -> Vec<Dibit>
+    {
+        self.frame(DataUnit::VoiceCCFrameGroup, cc_frame_group(frames, cc, lsd))
+    }
+
+    /// Build a simple terminator data unit.
+    pub fn simple_terminator(&self) -> Vec<Dibit> {
+        self.frame(DataUnit::VoiceSimpleTerminator,
+                   vec![Dibit::default(); TERM_ZERO_DIBITS])
+    }
+
+    /// Build a link control terminator data unit.
+    pub fn lc_terminator(&self, lc: &control::Buf) -> Vec<Dibit> {
+        self.frame(DataUnit::VoiceLCTerminator, lc_terminator(lc))
+    }
+
+    /// Prefix the given coded data unit with the frame sync and NID and interleave status
+    /// symbols.
+    fn frame(&self, du: DataUnit, payload: Vec<Dibit>) -> Vec<Dibit> {
+        let nid = NetworkId::new(self.nac, du).encode();
+
+        let dibits = Dibits::new(SYNC_GENERATOR.iter().cloned())
+            .chain(Dibits::new(nid.iter().cloned()))
+            .chain(payload.into_iter());
+
+        StatusInterleaver::new(dibits, self.status).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use consts::{SYNC_SYMBOLS, NID_DIBITS};
+    use message::status::{StatusDeinterleaver, StreamSymbol};
+    use trunking::fields::TalkGroup;
+    use voice::crypto::CryptoAlgorithm;
+    use voice::frame::VoiceFrame;
+    use voice::frame_group::{
+        FrameGroupEvent,
+        VoiceCCFrameGroupReceiver,
+        VoiceLCFrameGroupReceiver,
+    };
+    use voice::header::VoiceHeaderReceiver;
+    use voice::term::VoiceLCTerminatorReceiver;
+
+    fn chunks(n: u32) -> [u32; 8] {
+        [
+            (n * 0x123) & 0xFFF,
+            (n * 0x456 + 1) & 0xFFF,
+            (n * 0x789 + 2) & 0xFFF,
+            (n * 0xABC + 3) & 0xFFF,
+            (n * 0x135 + 4) & 0x7FF,
+            (n * 0x246 + 5) & 0x7FF,
+            (n * 0x357 + 6) & 0x7FF,
+            (n * 0x11 + 7) & 0x7F,
+        ]
+    }
+
+    fn frames() -> [[u32; 8]; GROUP_FRAMES] {
+        let mut frames = [[0; 8]; GROUP_FRAMES];
+
+        for (n, f) in frames.iter_mut().enumerate() {
+            *f = chunks(n as u32 + 1);
+        }
+
+        frames
+    }
+
+    #[test]
+    fn test_voice_frame() {
+        for n in 0..16 {
+            let vf = VoiceFrame::new(&voice_frame(&chunks(n))).unwrap();
+            assert_eq!(vf.chunks, chunks(n));
+            assert_eq!(vf.errors, [0; 7]);
+        }
+    }
+
+    #[test]
+    fn test_header() {
+        let buf = [1, 2, 3, 4, 5, 6, 7, 8, 9, 0x00, 0x80, 0x00, 0x00, 0x01, 0x23];
+        let dibits = header(&buf);
+        assert_eq!(dibits.len(), HEADER_HEXBITS * HEADER_WORD_DIBITS);
+
+        let mut recv = VoiceHeaderReceiver::new();
+        let events: Vec<_> = dibits.iter().filter_map(|&d| recv.feed(d)).collect();
+
+        match events[..] {
+            [Ok(ref h)] => {
+                assert_eq!(h.crypto_init(), &buf[..9]);
+                assert_eq!(h.crypto_alg(), CryptoAlgorithm::Unencrypted);
+                assert_eq!(h.talk_group(), TalkGroup::Other(0x0123));
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_lc_frame_group() {
+        let lc = [0x00, 0x00, 0x00, 0x00, 0x01, 0x23, 0x12, 0x34, 0x56];
+        let dibits = lc_frame_group(&frames(), &lc, 0xBEEF);
+
+        let mut recv = VoiceLCFrameGroupReceiver::new();
+        let mut vf = 0;
+        let mut seen = (false, false);
+
+        for &d in dibits.iter() {
+            match recv.feed(d) {
+                Some(Ok(FrameGroupEvent::VoiceFrame(f))) => {
+                    vf += 1;
+                    assert_eq!(f.chunks, chunks(vf));
+                },
+                Some(Ok(FrameGroupEvent::Extra(l))) => {
+                    assert_eq!(vf, 7);
+                    assert_eq!(l.payload(), &lc[1..]);
+                    seen.0 = true;
+                },
+                Some(Ok(FrameGroupEvent::DataFragment(lsd))) => {
+                    assert_eq!(vf, 8);
+                    assert_eq!(lsd, 0xBEEF);
+                    seen.1 = true;
+                },
+                Some(Err(_)) => panic!(),
+                None => {},
+            }
+        }
+
+        assert_eq!(vf, 9);
+        assert_eq!(seen, (true, true));
+        assert!(recv.done());
+    }
+
+    #[test]
+    fn test_cc_frame_group() {
+        let cc = [9, 8, 7, 6, 5, 4, 3, 2, 1, 0x80, 0x00, 0x00];
+        let dibits = cc_frame_group(&frames(), &cc, 0x1234);
+
+        let mut recv = VoiceCCFrameGroupReceiver::new();
+
+        let extras: Vec<_> = dibits.iter()
+            .filter_map(|&d| match recv.feed(d) {
+                Some(Ok(FrameGroupEvent::Extra(c))) => Some(c),
+                Some(Err(_)) => panic!(),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(extras.len(), 1);
+        assert_eq!(extras[0].init(), &cc[..9]);
+        assert_eq!(extras[0].alg(), CryptoAlgorithm::Unencrypted);
+        assert!(recv.done());
+    }
+
+    #[test]
+    fn test_lc_terminator() {
+        let lc = [0x00, 0x00, 0x00, 0x00, 0x04, 0x56, 0x65, 0x43, 0x21];
+
+        let mut recv = VoiceLCTerminatorReceiver::new();
+        let events: Vec<_> = lc_terminator(&lc).iter()
+            .filter_map(|&d| recv.feed(d))
+            .collect();
+
+        match events[..] {
+            [Ok(l)] => assert_eq!(l.payload(), &lc[1..]),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_frame() {
+        let e = VoiceEncoder::new(NetworkAccessCode::Other(0x293));
+        let dibits = e.simple_terminator();
+        assert_eq!(dibits.len(), 72);
+
+        let mut status = StatusDeinterleaver::new();
+        let data: Vec<Dibit> = dibits[SYNC_SYMBOLS..].iter()
+            .filter_map(|&d| match status.feed(d) {
+                StreamSymbol::Data(d) => Some(d),
+                StreamSymbol::Status(_) => None,
+            })
+            .collect();
+
+        assert_eq!(data.len(), NID_DIBITS + TERM_ZERO_DIBITS);
+
+        // Every data unit is padded out to a status symbol boundary.
+        assert_eq!(e.header(&[0; 15]).len() % 36, 0);
+        assert_eq!(e.lc_frame_group(&frames(), &[0; 9], 0).len(), 864);
+        assert_eq!(e.cc_frame_group(&frames(), &[0; 12], 0).len(), 864);
+    }
+}
@@ -11,6 +11,7 @@ use voice::rand;
 use error::P25Error::*;
 
 /// IMBE-encoded voice frame.
+#[derive(Copy, Clone)]
 pub struct VoiceFrame {
     /// Chunks of IMBE-prioritized data, Known as `u_0`, ..., `u_7` in the standard.
     pub chunks: [u32; 8],
@@ -100,6 +100,9 @@ impl VoiceHeaderFields {
     pub fn talk_group(&self) -> TalkGroup {
         TalkGroup::from_bits(slice_u16(&self.0[13..]))
     }
+
+    /// Bytes that make up the packet.
+    pub fn bytes(&self) -> &Buf { &self.0 }
 }
 
 #[cfg(test)]
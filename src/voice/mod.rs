@@ -3,8 +3,10 @@
 pub mod control;
 pub mod crypto;
 pub mod descramble;
+pub mod encode;
 pub mod frame;
 pub mod frame_group;
 pub mod header;
 pub mod rand;
 pub mod term;
+pub mod transmit;
@@ -0,0 +1,357 @@
+//! Sequence the data units of a complete voice call into a continuous dibit stream.
+//!
+//! The dibits can be turned into baseband samples with `baseband::encode::C4fmImpulses`
+//! followed by the shaping filter of the transmitter.
+
+use std::vec::Vec;
+
+use bits::Dibit;
+use consts::CRYPTO_CONTROL_BYTES;
+use message::nid::NetworkAccessCode;
+use message::status::StatusCode;
+use voice::control::LinkControlFields;
+use voice::crypto;
+use voice::encode::{VoiceEncoder, GROUP_FRAMES};
+use voice::header::VoiceHeaderFields;
+
+/// Transmits a voice call, accepting IMBE frames as they're produced by the vocoder.
+///
+/// The call starts with a voice header and continues with alternating LDU1 and LDU2
+/// frame groups, carrying the link control word and the encryption sync derived from
+/// the header, respectively. The MI of an encrypted call advances with each LDU2. Each
+/// frame group is emitted as soon as its 9 voice frames have been pushed.
+pub struct Transmitter {
+    /// Builds each framed data unit.
+    enc: VoiceEncoder,
+    /// Voice header sent at the start of the call.
+    header: VoiceHeaderFields,
+    /// Link control word carried in each LDU1 and the terminator.
+    lc: LinkControlFields,
+    /// Encryption sync carried in each LDU2.
+    es: crypto::Buf,
+    /// Low-speed data word carried in each frame group.
+    lsd: u16,
+    /// IMBE chunks used to fill out the last frame group.
+    fill: [u32; 8],
+    /// Whether to end with a simple terminator instead of a link control terminator.
+    simple_term: bool,
+    /// Voice frames of the current frame group.
+    frames: [[u32; 8]; GROUP_FRAMES],
+    /// Number of voice frames pushed into the current frame group.
+    pushed: usize,
+    /// Number of frame groups sent, so even groups are LDU1 and odd are LDU2.
+    groups: usize,
+    /// Whether the voice header has been sent.
+    started: bool,
+}
+
+impl Transmitter {
+    /// Create a new `Transmitter` for a call on the given NAC with the given voice header
+    /// and link control word.
+    pub fn new(nac: NetworkAccessCode, header: VoiceHeaderFields, lc: LinkControlFields)
+        -> Transmitter
+    {
+        let mut es = [0; CRYPTO_CONTROL_BYTES];
+
+        // The encryption sync repeats the crypto fields of the header.
+        es[..9].copy_from_slice(header.crypto_init());
+        es[9] = header.bytes()[10];
+        es[10..].copy_from_slice(&header.bytes()[11..13]);
+
+        Transmitter {
+            enc: VoiceEncoder::new(nac),
+            header: header,
+            lc: lc,
+            es: es,
+            lsd: 0,
+            fill: [0; 8],
+            simple_term: false,
+            frames: [[0; 8]; GROUP_FRAMES],
+            pushed: 0,
+            groups: 0,
+            started: false,
+        }
+    }
+
+    /// Interleave the given status code instead of the default.
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.enc = self.enc.with_status(status);
+        self
+    }
+
+    /// Carry the given low-speed data word in each frame group, instead of zero.
+    pub fn with_lsd(mut self, lsd: u16) -> Self {
+        self.lsd = lsd;
+        self
+    }
+
+    /// Fill out the last frame group with the given IMBE chunks, such as those of a
+    /// silent frame, instead of all zeroes.
+    pub fn with_fill(mut self, chunks: [u32; 8]) -> Self {
+        self.fill = chunks;
+        self
+    }
+
+    /// End the call with a simple terminator instead of a link control terminator.
+    pub fn with_simple_terminator(mut self) -> Self {
+        self.simple_term = true;
+        self
+    }
+
+    /// Push the IMBE chunks `u_0`, ..., `u_7` of the next voice frame, returning the
+    /// dibits that are ready to be transmitted, which may be none.
+    pub fn push(&mut self, chunks: &[u32; 8]) -> Vec<Dibit> {
+        let mut dibits = self.start();
+
+        self.frames[self.pushed] = *chunks;
+        self.pushed += 1;
+
+        if self.pushed == GROUP_FRAMES {
+            dibits.extend(self.group());
+        }
+
+        dibits
+    }
+
+    /// End the call, returning the remaining dibits.
+    ///
+    /// A partial frame group is filled out, followed by an LDU2 if needed to complete
+    /// the superframe, then the terminator.
+    pub fn finish(mut self) -> Vec<Dibit> {
+        let mut dibits = self.start();
+
+        if self.pushed > 0 || self.groups % 2 == 1 {
+            for idx in self.pushed..GROUP_FRAMES {
+                self.frames[idx] = self.fill;
+            }
+
+            dibits.extend(self.group());
+        }
+
+        if self.groups % 2 == 1 {
+            self.frames = [self.fill; GROUP_FRAMES];
+            dibits.extend(self.group());
+        }
+
+        if self.simple_term {
+            dibits.extend(self.enc.simple_terminator());
+        } else {
+            dibits.extend(self.enc.lc_terminator(self.lc.bytes()));
+        }
+
+        dibits
+    }
+
+    /// Build the voice header if it hasn't been sent yet.
+    fn start(&mut self) -> Vec<Dibit> {
+        if self.started {
+            return vec![];
+        }
+
+        self.started = true;
+        self.enc.header(self.header.bytes())
+    }
+
+    /// Build the frame group of the current voice frames and start the next one.
+    fn group(&mut self) -> Vec<Dibit> {
+        let dibits = if self.groups % 2 == 0 {
+            self.enc.lc_frame_group(&self.frames, self.lc.bytes(), self.lsd)
+        } else {
+            // Each LDU2 carries the MI of the following superframe.
+            if self.header.crypto_alg() != crypto::CryptoAlgorithm::Unencrypted {
+                let mi = crypto::next_mi(&self.es[..9]);
+                self.es[..9].copy_from_slice(&mi);
+            }
+
+            self.enc.cc_frame_group(&self.frames, &self.es, self.lsd)
+        };
+
+        self.pushed = 0;
+        self.groups += 1;
+
+        dibits
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std;
+    use consts::SYMBOL_PERIOD;
+    use message::nid::DataUnit;
+    use receiver::{EventKind, Receiver, ReceiverConfig};
+    use trunking::fields::TalkGroup;
+    use voice::control::GroupVoiceTraffic;
+    use voice::crypto::CryptoAlgorithm;
+
+    const NAC: NetworkAccessCode = NetworkAccessCode::Default;
+
+    fn chunks(n: u32) -> [u32; 8] {
+        [
+            (n * 0x123) & 0xFFF,
+            (n * 0x456 + 1) & 0xFFF,
+            (n * 0x789 + 2) & 0xFFF,
+            (n * 0xABC + 3) & 0xFFF,
+            (n * 0x135 + 4) & 0x7FF,
+            (n * 0x246 + 5) & 0x7FF,
+            (n * 0x357 + 6) & 0x7FF,
+            (n * 0x11 + 7) & 0x7F,
+        ]
+    }
+
+    fn transmitter() -> Transmitter {
+        let header = VoiceHeaderFields::new([1, 2, 3, 4, 5, 6, 7, 8, 9, 0x00, 0x80, 0x00,
+                                             0x00, 0x01, 0x23]);
+        let lc = LinkControlFields::new([0x00, 0x00, 0x00, 0x00, 0x01, 0x23, 0x12,
+                                         0x34, 0x56]);
+
+        Transmitter::new(NAC, header, lc).with_lsd(0xBEEF)
+    }
+
+    /// Decode the given dibits with the full receiver.
+    fn receive(dibits: &[Dibit]) -> Vec<EventKind> {
+        let mut samples = vec![0.0; 1000];
+
+        for d in dibits {
+            let level = match d.bits() {
+                0b01 => 0.18,
+                0b00 => 0.02,
+                0b10 => -0.02,
+                _ => -0.18,
+            };
+
+            samples.extend(std::iter::repeat(level).take(SYMBOL_PERIOD));
+        }
+
+        samples.extend(std::iter::repeat(0.0).take(1000));
+
+        let mut recv = Receiver::new(ReceiverConfig::default()).unwrap();
+        let mut events = recv.feed_slice(&samples);
+        events.extend(recv.finish());
+
+        events.into_iter().map(|e| e.kind).collect()
+    }
+
+    #[test]
+    fn test_streaming() {
+        let mut tx = transmitter();
+
+        // The header goes out with the first frame, and each frame group once full.
+        assert!(!tx.push(&chunks(0)).is_empty());
+
+        for n in 1..8 {
+            assert!(tx.push(&chunks(n)).is_empty());
+        }
+
+        assert!(!tx.push(&chunks(8)).is_empty());
+        assert!(tx.push(&chunks(9)).is_empty());
+    }
+
+    #[test]
+    fn test_call() {
+        let mut tx = transmitter();
+        let mut dibits = vec![];
+
+        // One full superframe and a partial frame group.
+        for n in 0..21 {
+            dibits.extend(tx.push(&chunks(n)));
+        }
+
+        dibits.extend(tx.finish());
+
+        let events = receive(&dibits);
+
+        let nids: Vec<DataUnit> = events.iter()
+            .filter_map(|e| match *e {
+                EventKind::Nid(nid) => Some(nid.data_unit),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(nids, vec![
+            DataUnit::VoiceHeader,
+            DataUnit::VoiceLCFrameGroup,
+            DataUnit::VoiceCCFrameGroup,
+            DataUnit::VoiceLCFrameGroup,
+            DataUnit::VoiceCCFrameGroup,
+            DataUnit::VoiceLCTerminator,
+        ]);
+
+        let frames: Vec<[u32; 8]> = events.iter()
+            .filter_map(|e| match *e {
+                EventKind::VoiceFrame(ref vf) => {
+                    assert_eq!(vf.errors, [0; 7]);
+                    Some(vf.chunks)
+                },
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(frames.len(), 4 * GROUP_FRAMES);
+
+        for (n, f) in frames.iter().enumerate() {
+            assert_eq!(*f, if n < 21 { chunks(n as u32) } else { [0; 8] });
+        }
+
+        let mut seen = (0, 0, 0, 0);
+
+        for e in events.iter() {
+            match *e {
+                EventKind::VoiceHeader(ref h) => {
+                    assert_eq!(h.talk_group(), TalkGroup::Other(0x0123));
+                    seen.0 += 1;
+                },
+                EventKind::LinkControl(ref lc) => {
+                    let gv = GroupVoiceTraffic::new(*lc);
+                    assert_eq!(gv.talkgroup(), TalkGroup::Other(0x0123));
+                    assert_eq!(gv.src_unit(), 0x123456);
+                    seen.1 += 1;
+                },
+                EventKind::CryptoControl(ref cc) => {
+                    assert_eq!(cc.init(), &[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+                    assert_eq!(cc.alg(), CryptoAlgorithm::Unencrypted);
+                    assert_eq!(cc.key(), 0);
+                    seen.2 += 1;
+                },
+                EventKind::LowSpeedData(lsd) => {
+                    assert_eq!(lsd, 0xBEEF);
+                    seen.3 += 1;
+                },
+                _ => {},
+            }
+        }
+
+        assert_eq!(seen, (1, 2, 2, 4));
+    }
+
+    #[test]
+    fn test_finish() {
+        // A call without voice is just a header and terminator.
+        let dibits = transmitter().with_simple_terminator().finish();
+
+        let nids: Vec<DataUnit> = receive(&dibits).iter()
+            .filter_map(|e| match *e {
+                EventKind::Nid(nid) => Some(nid.data_unit),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(nids, vec![DataUnit::VoiceHeader, DataUnit::VoiceSimpleTerminator]);
+
+        // A call ending on a full LDU1 gets a filler LDU2.
+        let mut tx = transmitter().with_fill(chunks(100));
+
+        for n in 0..9 {
+            tx.push(&chunks(n));
+        }
+
+        let frames: Vec<[u32; 8]> = receive(&tx.finish()).iter()
+            .filter_map(|e| match *e {
+                EventKind::VoiceFrame(ref vf) => Some(vf.chunks),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(frames, vec![chunks(100); GROUP_FRAMES]);
+    }
+}
@@ -0,0 +1,147 @@
+//! Decode a stream of voice frame groups through the handler API with a counting
+//! allocator, checking that no heap allocations are made once the receiver has seen the
+//! stream's NAC.
+
+#![cfg(feature = "std")]
+
+extern crate p25;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use p25::bits::Dibit;
+use p25::message::nid::{DataUnit, NetworkAccessCode};
+use p25::message::receiver::{Route, Routes};
+use p25::receiver::{MessageHandler, Receiver, ReceiverConfig};
+use p25::testutil;
+use p25::voice::control::LinkControlFields;
+use p25::voice::crypto::CryptoControlFields;
+use p25::voice::encode::VoiceEncoder;
+use p25::voice::frame::VoiceFrame;
+
+/// Allocator that counts the allocations made by threads that enabled counting.
+struct Counting;
+
+/// Number of allocations counted.
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// Whether to count allocations made by the current thread.
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if COUNTING.try_with(|c| c.get()).unwrap_or(false) {
+            ALLOCS.fetch_add(1, Ordering::SeqCst);
+        }
+
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) { System.dealloc(ptr, layout) }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Count the allocations made by the given function on the current thread.
+fn allocs<F: FnOnce()>(f: F) -> usize {
+    let start = ALLOCS.load(Ordering::SeqCst);
+
+    COUNTING.with(|c| c.set(true));
+    f();
+    COUNTING.with(|c| c.set(false));
+
+    ALLOCS.load(Ordering::SeqCst) - start
+}
+
+/// Counts voice events without keeping them.
+#[derive(Default)]
+struct Counter {
+    frames: usize,
+    lc: usize,
+    cc: usize,
+    raw: usize,
+}
+
+impl MessageHandler for Counter {
+    fn on_voice_frame(&mut self, _: VoiceFrame) { self.frames += 1; }
+
+    fn on_link_control(&mut self, _: LinkControlFields) { self.lc += 1; }
+
+    fn on_crypto_control(&mut self, _: CryptoControlFields) { self.cc += 1; }
+
+    fn on_raw(&mut self, _: DataUnit, dibits: &[Dibit]) {
+        assert!(!dibits.is_empty());
+        self.raw += 1;
+    }
+}
+
+/// Baseband samples of a call on the default NAC, as the given number of pairs of LC
+/// and CC frame groups.
+fn call(pairs: usize) -> Vec<f32> {
+    let voice = VoiceEncoder::new(NetworkAccessCode::Default);
+    let lc = [0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x12, 0x34, 0x56];
+    let cc = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0x80, 0x00, 0x00];
+
+    let mut dibits = vec![];
+
+    for n in 0..pairs {
+        let chunks = [[n as u32; 8]; 9];
+
+        dibits.extend(voice.lc_frame_group(&chunks, &lc, 0xBEEF));
+        dibits.extend(voice.cc_frame_group(&chunks, &cc, 0x1234));
+    }
+
+    testutil::modulate(&dibits)
+}
+
+#[test]
+fn test_voice_allocs() {
+    let samples = call(10);
+    // One frame group in, past the leading silence.
+    let warmup = samples.len() / 20 + 1000;
+
+    let mut recv = Receiver::new(ReceiverConfig::default()).unwrap();
+    let mut h = Counter::default();
+
+    // The first frame group introduces the NAC, which sets up its state.
+    for &s in samples[..warmup].iter() {
+        recv.feed_with(s, &mut h);
+    }
+
+    assert_eq!(h.frames, 9);
+
+    let n = allocs(|| for &s in samples[warmup..].iter() {
+        recv.feed_with(s, &mut h);
+    });
+
+    assert_eq!(n, 0);
+    assert_eq!(h.frames, 9 * 20);
+    assert_eq!(h.lc, 10);
+    assert_eq!(h.cc, 10);
+
+    // Raw passthrough lends out a buffer kept by the receiver.
+    let mut recv = Receiver::new(ReceiverConfig::builder()
+        .routes(Routes::default()
+            .with(DataUnit::VoiceLCFrameGroup, Route::Raw)
+            .with(DataUnit::VoiceCCFrameGroup, Route::Raw))
+        .build()
+        .unwrap()).unwrap();
+
+    let mut h = Counter::default();
+
+    for &s in samples[..warmup].iter() {
+        recv.feed_with(s, &mut h);
+    }
+
+    let n = allocs(|| for &s in samples[warmup..].iter() {
+        recv.feed_with(s, &mut h);
+    });
+
+    assert_eq!(n, 0);
+    assert_eq!(h.raw, 20);
+    assert_eq!(h.frames, 0);
+}
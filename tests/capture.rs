@@ -0,0 +1,93 @@
+//! Decode dibit captures of synthetic traffic and compare with decoding the same traffic
+//! as baseband samples.
+
+#![cfg(feature = "std")]
+
+extern crate p25;
+
+use std::io::Cursor;
+
+use p25::bits::Dibit;
+use p25::capture::{DibitLayout, DibitReader, DibitWriter};
+use p25::message::nid::NetworkAccessCode;
+use p25::receiver::{Event, Receiver, ReceiverConfig};
+use p25::testutil;
+
+const NAC: NetworkAccessCode = NetworkAccessCode::Default;
+
+/// A group grant followed by the granted call.
+fn traffic() -> Vec<Dibit> {
+    let mut dibits = testutil::group_grant(NAC, 0x0042, 0x0123, 0x123456);
+    dibits.extend(testutil::voice_call(NAC, 0x0123, 0x123456, 20));
+    dibits
+}
+
+/// Write the given dibits to a capture in the given layout and decode it.
+fn decode_capture(dibits: &[Dibit], layout: DibitLayout) -> Vec<Event> {
+    let mut w = DibitWriter::new(vec![], layout);
+    w.write_all(dibits).unwrap();
+    let bytes = w.finish().unwrap();
+
+    let mut reader = DibitReader::new(Cursor::new(bytes), layout);
+    let mut recv = Receiver::new(ReceiverConfig::default()).unwrap();
+    let mut events = vec![];
+
+    for d in reader.by_ref() {
+        events.extend(recv.feed_dibit(d));
+
+        while let Some(e) = recv.next_event() {
+            events.push(e);
+        }
+    }
+
+    assert!(reader.error().is_none());
+    events.extend(recv.finish());
+
+    events
+}
+
+#[test]
+fn test_same_as_samples() {
+    let dibits = traffic();
+    let expected = testutil::decode(&testutil::modulate(&dibits));
+
+    testutil::assert_clean(&expected);
+
+    for &layout in [DibitLayout::Packed, DibitLayout::Unpacked].iter() {
+        let events = decode_capture(&dibits, layout);
+
+        assert_eq!(testutil::tags(&events), testutil::tags(&expected));
+        assert_eq!(testutil::data_units(&events), testutil::data_units(&expected));
+        assert_eq!(testutil::voice_frames(&events), testutil::voice_frames(&expected));
+    }
+}
+
+#[test]
+fn test_sync_errors() {
+    let mut rng = testutil::Rng::new(7);
+    let clean = traffic();
+    let expected = decode_capture(&clean, DibitLayout::Packed);
+
+    // A few bit errors in each frame sync are tolerated.
+    let mut dibits = clean.clone();
+    let mut start = 0;
+
+    while let Some(pos) = find_sync(&dibits[start..]) {
+        testutil::flip_bits(&mut rng, &mut dibits[start + pos..start + pos + 24], 48, 3);
+        start += pos + 24;
+    }
+
+    assert!(start > 0);
+
+    let events = decode_capture(&dibits, DibitLayout::Packed);
+    assert_eq!(testutil::data_units(&events), testutil::data_units(&expected));
+}
+
+/// Find the first frame sync in the given dibits.
+fn find_sync(dibits: &[Dibit]) -> Option<usize> {
+    let sync = 0x5575F5FF77FF;
+
+    (0..dibits.len().saturating_sub(23)).find(|&i| {
+        dibits[i..i + 24].iter().fold(0u64, |s, d| s << 2 | d.bits() as u64) == sync
+    })
+}
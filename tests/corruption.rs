@@ -0,0 +1,71 @@
+//! Encode calls, corrupt them in controlled ways, and check what the receiver still
+//! decodes, using the building blocks in `p25::testutil`.
+
+#![cfg(feature = "std")]
+
+extern crate p25;
+
+use p25::message::nid::{DataUnit, NetworkAccessCode};
+use p25::receiver::EventKind;
+use p25::testutil::{self, Rng};
+use p25::voice::encode;
+
+const NAC: NetworkAccessCode = NetworkAccessCode::Default;
+
+#[test]
+fn test_correctable_header() {
+    for seed in 1..20 {
+        let mut rng = Rng::new(seed);
+
+        // The (18, 6, 8) Golay words of the header each correct up to 3 errors.
+        let mut coded = encode::header(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0x00, 0x80, 0x00, 0x00,
+                                         0x01, 0x23]);
+        testutil::flip_bits(&mut rng, &mut coded, 18, 3);
+
+        let dibits = testutil::frame(NAC, DataUnit::VoiceHeader, coded);
+        let events = testutil::decode(&testutil::modulate(&dibits));
+
+        testutil::assert_clean(&events);
+        testutil::assert_sequence(&events, &["sync_acquired", "nid", "voice_header"]);
+    }
+}
+
+#[test]
+fn test_voice_burst() {
+    let mut dibits = testutil::voice_call(NAC, 0x0123, 0x123456, 9);
+    let clean = testutil::voice_frames(&testutil::decode(&testutil::modulate(&dibits)));
+
+    // Wipe out part of the first voice frame of the LDU1, past its sync and NID.
+    let ldu = encode::VoiceEncoder::new(NAC).header(&[0; 15]).len();
+    testutil::burst(&mut dibits, (ldu + 60) * 2, 40);
+
+    let events = testutil::decode(&testutil::modulate(&dibits));
+    let frames = testutil::voice_frames(&events);
+
+    // The corrupted frame comes out wrong or with errors, but the rest are untouched.
+    assert_eq!(frames.len(), clean.len());
+    assert_eq!(&frames[1..], &clean[1..]);
+
+    let errors = events.iter().any(|e| match e.kind {
+        EventKind::VoiceFrame(ref vf) => vf.errors.iter().any(|&n| n > 0),
+        _ => false,
+    });
+
+    assert!(errors || frames[0] != clean[0]);
+}
+
+#[test]
+fn test_dropped_samples() {
+    let mut samples = testutil::modulate(&testutil::voice_call(NAC, 0x0123, 0x123456, 18));
+    samples.extend(testutil::silence(0.5));
+
+    // Lose a few symbols in the middle of the LDU2, which aborts it, and check the
+    // terminator after it still gets through.
+    let at = samples.len() / 2 + 5000;
+    testutil::drop_samples(&mut samples, at, 37);
+
+    let events = testutil::decode(&samples);
+
+    testutil::assert_sequence(&events, &["voice_header", "voice_term", "sync_lost"]);
+    assert_eq!(testutil::data_units(&events).last(), Some(&DataUnit::VoiceLCTerminator));
+}
@@ -0,0 +1,127 @@
+//! Decode a vector through the C interface with the C program in `tests/ffi`, which is
+//! compiled against the receiver's shared library and run as a separate process.
+
+#![cfg(all(feature = "ffi", unix))]
+
+extern crate cc;
+extern crate p25;
+
+use std::env;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use p25::baseband::sync::SYNC_GENERATOR;
+use p25::bits::{Dibit, Dibits};
+use p25::consts::SYMBOL_PERIOD;
+use p25::message::nid::{DataUnit, NetworkAccessCode, NetworkId};
+use p25::message::status::{StatusCode, StatusInterleaver};
+use p25::trunking::encode::Tsdu;
+use p25::trunking::tsbk::{TsbkFields, TsbkOpcode};
+use p25::trunking::vendor::MFID_STANDARD;
+use p25::voice::encode::VoiceEncoder;
+
+/// Baseband samples of a group voice grant followed by a voice frame group carrying
+/// the granted call.
+fn vector() -> Vec<f32> {
+    let nac = NetworkAccessCode::Default;
+
+    let tsbk = TsbkFields::build(TsbkOpcode::GroupVoiceGrant.to_bits(), MFID_STANDARD,
+                                 &[0x00, 0x10, 0x20, 0x01, 0x00, 0x12, 0x34, 0x56]);
+    let nid = NetworkId::new(nac, DataUnit::TrunkingSignaling).encode();
+
+    let mut dibits: Vec<Dibit> = StatusInterleaver::new(
+        Dibits::new(SYNC_GENERATOR.iter().cloned())
+            .chain(Dibits::new(nid.iter().cloned()))
+            .chain(Tsdu::assemble(&[tsbk]).into_iter()),
+        StatusCode::InboundIdle).collect();
+
+    let voice = VoiceEncoder::new(nac);
+    let lc = [0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x12, 0x34, 0x56];
+
+    dibits.extend(voice.lc_frame_group(&[[7; 8]; 9], &lc, 0));
+
+    let mut samples = vec![0.0; 1000];
+
+    for d in dibits {
+        let level = match d.bits() {
+            0b01 => 0.18,
+            0b00 => 0.02,
+            0b10 => -0.02,
+            _ => -0.18,
+        };
+
+        samples.extend(std::iter::repeat(level).take(SYMBOL_PERIOD));
+    }
+
+    // End the input before sync times out, so it's lost when the receiver finishes.
+    samples.extend(std::iter::repeat(0.0).take(1000));
+    samples
+}
+
+/// Target triple of the host, which the tests are built for.
+fn host() -> String {
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let out = Command::new(rustc).arg("-vV").output().unwrap();
+
+    String::from_utf8(out.stdout).unwrap()
+        .lines()
+        .find(|l| l.starts_with("host: "))
+        .map(|l| l["host: ".len()..].to_string())
+        .unwrap()
+}
+
+/// Compile the C program against the shared library, returning the path of the
+/// executable.
+fn compile() -> PathBuf {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let exe = Path::new(env!("CARGO_TARGET_TMPDIR")).join("p25_ffi_decode");
+    // The shared library is built into the same directory as the test executable.
+    let lib = env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    let host = host();
+
+    let status = cc::Build::new()
+        .target(&host)
+        .host(&host)
+        .opt_level(0)
+        .cargo_metadata(false)
+        .cargo_warnings(false)
+        .warnings(true)
+        .include(root.join("include"))
+        .get_compiler()
+        .to_command()
+        .arg(root.join("tests/ffi/decode.c"))
+        .arg("-o").arg(&exe)
+        .arg("-L").arg(&lib)
+        .arg(format!("-Wl,-rpath,{}", lib.display()))
+        .arg("-lp25")
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+
+    exe
+}
+
+#[test]
+fn test_decode() {
+    // Cargo's library path can hold a copy of the library built without the C
+    // interface, so only the path the program was linked with is searched.
+    let mut child = Command::new(compile())
+        .env_remove("LD_LIBRARY_PATH")
+        .env_remove("DYLD_LIBRARY_PATH")
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    {
+        let stdin = child.stdin.as_mut().unwrap();
+
+        for s in vector() {
+            stdin.write_all(&s.to_ne_bytes()).unwrap();
+        }
+    }
+
+    drop(child.stdin.take());
+    assert_eq!(child.wait().unwrap().code(), Some(0));
+}
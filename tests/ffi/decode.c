@@ -0,0 +1,158 @@
+/*
+ * Decode baseband samples through the C interface and check the events produced, as an
+ * application embedding the receiver would. Built and run by tests/ffi.rs, which writes
+ * a control channel grant for talkgroup 0x0100 followed by a voice frame group on NAC
+ * 0x293 to standard input as native-endian float32 samples. The exit status is 0 if
+ * every check passed, or the code of the first failed check otherwise.
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "p25.h"
+
+/* Block size used while polling, to exercise feeding in pieces. */
+#define BLOCK 100
+
+/* Events seen so far. */
+typedef struct {
+    size_t sync_acquired;
+    size_t sync_lost;
+    size_t tsbks;
+    size_t calls_started;
+    size_t calls_ended;
+    size_t voice_frames;
+    /* Code of the first failed check, or 0 if all passed. */
+    int err;
+} counts_t;
+
+static void check(counts_t *c, bool ok, int code) {
+    if (!ok && c->err == 0) {
+        c->err = code;
+    }
+}
+
+static void count(counts_t *c, const p25_event_t *e) {
+    size_t i;
+
+    switch (e->kind) {
+    case P25_EVENT_SYNC_ACQUIRED:
+        c->sync_acquired++;
+        break;
+
+    case P25_EVENT_SYNC_LOST:
+        c->sync_lost++;
+        break;
+
+    case P25_EVENT_TSBK:
+        c->tsbks++;
+        check(c, e->tsbk_len == sizeof(e->tsbk), 1);
+        /* Group voice grant. */
+        check(c, (e->tsbk[0] & 0x3F) == 0x00, 2);
+        check(c, e->has_nac && e->nac == 0x293, 3);
+        break;
+
+    case P25_EVENT_CALL_START:
+    case P25_EVENT_CALL_END:
+        if (e->kind == P25_EVENT_CALL_START) {
+            c->calls_started++;
+        } else {
+            c->calls_ended++;
+        }
+
+        check(c, e->call.group && e->call.target == 0x0100, 4);
+        check(c, e->call.channel == 0x1020, 5);
+        check(c, e->call.has_src_unit && e->call.src_unit == 0x123456, 6);
+        break;
+
+    case P25_EVENT_VOICE_FRAME:
+        c->voice_frames++;
+
+        for (i = 0; i < 8; i++) {
+            check(c, e->chunks[i] == 7, 7);
+        }
+
+        for (i = 0; i < 7; i++) {
+            check(c, e->errors[i] == 0, 8);
+        }
+
+        break;
+
+    default:
+        break;
+    }
+}
+
+static void on_event(const p25_event_t *e, void *ctx) {
+    count((counts_t *)ctx, e);
+}
+
+/* Decode the given `len` samples, returning 0 if every expected event was seen or the
+ * code of the first failed check otherwise. */
+static int decode(const float *samples, size_t len) {
+    counts_t c = {0};
+    p25_event_t e;
+    p25_receiver_t *recv = p25_receiver_new();
+    size_t half = len / 2;
+    size_t i;
+
+    if (recv == NULL) {
+        return 17;
+    }
+
+    /* Poll for events while feeding the first half in blocks. */
+    for (i = 0; i < half; i += BLOCK) {
+        check(&c, p25_receiver_feed(recv, samples + i,
+                                    half - i < BLOCK ? half - i : BLOCK), 16);
+
+        while (p25_receiver_next_event(recv, &e)) {
+            count(&c, &e);
+        }
+    }
+
+    /* Then receive the rest through the callback. */
+    p25_receiver_set_callback(recv, on_event, &c);
+    check(&c, p25_receiver_feed(recv, samples + half, len - half), 16);
+    check(&c, p25_receiver_finish(recv), 16);
+
+    check(&c, !p25_receiver_next_event(recv, &e), 9);
+    p25_receiver_free(recv);
+
+    check(&c, c.sync_acquired == 1, 10);
+    check(&c, c.sync_lost == 1, 11);
+    check(&c, c.tsbks == 1, 12);
+    check(&c, c.calls_started == 1, 13);
+    check(&c, c.calls_ended == 1, 14);
+    check(&c, c.voice_frames == 9, 15);
+
+    return c.err;
+}
+
+int main(void) {
+    float *samples = NULL;
+    size_t len = 0;
+    size_t cap = 0;
+    int err;
+
+    for (;;) {
+        if (len == cap) {
+            cap = cap ? cap * 2 : 4096;
+            samples = realloc(samples, cap * sizeof(float));
+
+            if (!samples) {
+                return 100;
+            }
+        }
+
+        if (fread(samples + len, sizeof(float), 1, stdin) != 1) {
+            break;
+        }
+
+        len++;
+    }
+
+    err = decode(samples, len);
+    free(samples);
+
+    return err;
+}
@@ -0,0 +1,212 @@
+//! Feed randomly mutated frames of every kind through the full receiver and the
+//! decoders applied to its events, checking that malformed input never panics.
+
+#![cfg(feature = "std")]
+
+extern crate p25;
+
+use std::io;
+
+use p25::bits::Dibit;
+use p25::data::assemble::PduEvent;
+use p25::data::fields::ServiceAccessPoint;
+use p25::data::packet::PduEncoder;
+use p25::data::sndcp::DataEvent;
+use p25::diagnostic::{Diagnostic, Verbosity};
+use p25::error::P25Error;
+use p25::message::nid::{DataUnit, NetworkAccessCode, NetworkId};
+use p25::message::status::StatusEvent;
+use p25::receiver::{MessageHandler, Receiver, ReceiverConfig};
+use p25::sink::JsonLinesSink;
+use p25::testutil::{self, Rng};
+use p25::trunking::calls::Call;
+use p25::trunking::decode::TsbkMessage;
+use p25::trunking::tsbk::TsbkFields;
+use p25::voice::control::LinkControlFields;
+use p25::voice::crypto::{CryptoControlFields, CryptoEvent};
+use p25::voice::encode::VoiceEncoder;
+use p25::voice::frame::VoiceFrame;
+use p25::voice::header::VoiceHeaderFields;
+
+/// Number of mutated streams to decode, unless overridden by the
+/// `P25_FUZZ_ITERATIONS` environment variable.
+const ITERATIONS: usize = 5000;
+
+/// Every this many streams is modulated and decoded from baseband, and the rest are fed
+/// in as symbols, which is much faster but skips the baseband stage.
+const BASEBAND_EVERY: usize = 16;
+
+/// Dibits of a random kind of data unit, with random field contents that are still
+/// coded correctly.
+fn data_unit(rng: &mut Rng) -> Vec<Dibit> {
+    let nac = NetworkAccessCode::Default;
+    let voice = VoiceEncoder::new(nac);
+
+    let mut lc = [0; 9];
+    rng.bytes(&mut lc);
+
+    match rng.below(6) {
+        0 => {
+            let mut payload = [0; 8];
+            rng.bytes(&mut payload);
+
+            let tsbk = TsbkFields::build(rng.next() as u8 & 0x3F, rng.next() as u8,
+                                         &payload);
+            testutil::tsdu(nac, &[tsbk])
+        },
+        1 => {
+            let mut header = [0; 15];
+            rng.bytes(&mut header);
+            voice.header(&header)
+        },
+        2 => voice.lc_frame_group(&rng.frames(), &lc, rng.next() as u16),
+        3 => {
+            let mut cc = [0; 12];
+            rng.bytes(&mut cc);
+            voice.cc_frame_group(&rng.frames(), &cc, rng.next() as u16)
+        },
+        4 => voice.lc_terminator(&lc),
+        _ => {
+            let saps = [
+                ServiceAccessPoint::PacketData,
+                ServiceAccessPoint::SNDCPControl,
+                ServiceAccessPoint::ExtendedAddressing,
+                ServiceAccessPoint::UnencryptedKeyManagement,
+                ServiceAccessPoint::TrunkingControl,
+            ];
+
+            let mut data = vec![0; rng.below(64)];
+            rng.bytes(&mut data);
+
+            let sap = saps[rng.below(saps.len())];
+            let pdu = PduEncoder::new(nac, sap, rng.next() & 0xFFFFFF);
+
+            if rng.below(2) == 0 {
+                pdu.unconfirmed(&data)
+            } else {
+                pdu.confirmed(&data, rng.next() as u8 & 7)
+            }
+        },
+    }
+}
+
+/// Corrupt the given dibits with random symbol errors, dropped runs, and repeated
+/// runs.
+fn mutate(rng: &mut Rng, dibits: &mut Vec<Dibit>) {
+    for _ in 0..rng.below(40) {
+        let idx = rng.below(dibits.len());
+        dibits[idx] = Dibit::new(rng.next() as u8 & 3);
+    }
+
+    for _ in 0..rng.below(3) {
+        let start = rng.below(dibits.len());
+        let end = (start + rng.below(200)).min(dibits.len());
+
+        if rng.below(2) == 0 {
+            dibits.drain(start..end);
+        } else {
+            let run: Vec<Dibit> = dibits[start..end].to_vec();
+            let at = rng.below(dibits.len());
+            dibits.splice(at..at, run);
+        }
+    }
+}
+
+/// Decodes the contents of each event, after passing it on to a JSON sink.
+struct Decode(JsonLinesSink<io::Sink>);
+
+impl MessageHandler for Decode {
+    fn on_sample(&mut self, sample: u64) { self.0.on_sample(sample) }
+
+    fn on_nac(&mut self, nac: Option<NetworkAccessCode>) { self.0.on_nac(nac) }
+
+    fn on_sync(&mut self, locked: bool) { self.0.on_sync(locked) }
+
+    fn on_nid(&mut self, nid: NetworkId) { self.0.on_nid(nid) }
+
+    fn on_voice_header(&mut self, h: VoiceHeaderFields) { self.0.on_voice_header(h) }
+
+    fn on_voice_frame(&mut self, vf: VoiceFrame) { self.0.on_voice_frame(vf) }
+
+    fn on_link_control(&mut self, lc: LinkControlFields) {
+        lc.opcode();
+        self.0.on_link_control(lc);
+    }
+
+    fn on_crypto_control(&mut self, cc: CryptoControlFields) {
+        let _ = cc.check_clear();
+        self.0.on_crypto_control(cc);
+    }
+
+    fn on_crypto(&mut self, event: CryptoEvent) { self.0.on_crypto(event) }
+
+    fn on_status(&mut self, event: StatusEvent) { self.0.on_status(event) }
+
+    fn on_low_speed_data(&mut self, lsd: u32) { self.0.on_low_speed_data(lsd) }
+
+    fn on_tsbk(&mut self, tsbk: TsbkFields) {
+        TsbkMessage::new(&tsbk);
+        self.0.on_tsbk(tsbk);
+    }
+
+    fn on_voice_term(&mut self, lc: LinkControlFields) {
+        lc.opcode();
+        self.0.on_voice_term(lc);
+    }
+
+    fn on_pdu(&mut self, pdu: PduEvent) {
+        DataEvent::from_pdu(&pdu);
+        self.0.on_pdu(pdu);
+    }
+
+    fn on_raw(&mut self, du: DataUnit, dibits: &[Dibit]) { self.0.on_raw(du, dibits) }
+
+    fn on_frame_aborted(&mut self) { self.0.on_frame_aborted() }
+
+    fn on_frame_filtered(&mut self, nac: NetworkAccessCode, du: DataUnit) {
+        self.0.on_frame_filtered(nac, du)
+    }
+
+    fn on_call_start(&mut self, call: Call) { self.0.on_call_start(call) }
+
+    fn on_call_update(&mut self, call: Call) { self.0.on_call_update(call) }
+
+    fn on_call_end(&mut self, call: Call) { self.0.on_call_end(call) }
+
+    fn on_error(&mut self, err: P25Error) { self.0.on_error(err) }
+
+    fn on_diagnostic(&mut self, diag: Diagnostic) { self.0.on_diagnostic(diag) }
+}
+
+#[test]
+fn test_mutated_frames() {
+    let iterations = std::env::var("P25_FUZZ_ITERATIONS").ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(ITERATIONS);
+
+    for iter in 0..iterations {
+        let mut rng = Rng::new(0x9E3779B9 ^ iter as u32);
+
+        let mut dibits = data_unit(&mut rng);
+        dibits.extend(data_unit(&mut rng));
+        mutate(&mut rng, &mut dibits);
+
+        let mut recv = Receiver::new(ReceiverConfig {
+            diagnostics: Verbosity::All,
+            ..ReceiverConfig::default()
+        }).unwrap();
+        let mut sink = Decode(JsonLinesSink::new(io::sink()));
+
+        if iter % BASEBAND_EVERY == 0 {
+            for s in testutil::modulate(&dibits) {
+                recv.feed_with(s, &mut sink);
+            }
+        } else {
+            for &d in dibits.iter() {
+                recv.feed_dibit_with(d, &mut sink);
+            }
+        }
+
+        recv.finish_with(&mut sink);
+    }
+}
@@ -0,0 +1,72 @@
+//! Decode protection parameter broadcasts from a control channel and check them against
+//! a keystore, which reports the advertised keys that aren't provisioned.
+
+#![cfg(feature = "std")]
+
+extern crate p25;
+
+use p25::message::nid::NetworkAccessCode;
+use p25::message::receiver::MessageEvent;
+use p25::receiver::EventKind;
+use p25::testutil;
+use p25::trunking::decode::TsbkMessage;
+use p25::trunking::tsbk::{TsbkFields, TsbkOpcode};
+use p25::trunking::vendor::MFID_STANDARD;
+use p25::voice::crypto::{CryptoAlgorithm, KeyId, KeyStore, MissingKey};
+
+const NAC: NetworkAccessCode = NetworkAccessCode::Default;
+
+/// Protection parameter broadcast advertising the given key to the given unit.
+fn params(alg: u8, key: u16, unit: u32) -> TsbkFields {
+    TsbkFields::build(TsbkOpcode::ProtectionParamBroadcast.to_bits(), MFID_STANDARD, &[
+        0, 0, alg, (key >> 8) as u8, key as u8, (unit >> 16) as u8, (unit >> 8) as u8,
+        unit as u8,
+    ])
+}
+
+#[test]
+fn test_missing_keys() {
+    let mut dibits = testutil::tsdu(NAC, &[
+        params(0x84, 0x0001, 0x123456),
+        params(0x84, 0x0002, 0x123457),
+        params(0x81, 0x0010, 0xFFFFFF),
+    ]);
+    dibits.extend(testutil::tsdu(NAC, &[params(0x84, 0x0002, 0x654321)]));
+
+    let events = testutil::decode(&testutil::modulate(&dibits));
+    testutil::assert_clean(&events);
+
+    let mut ks = KeyStore::new().with_key(CryptoAlgorithm::Aes, 0x0001);
+    let mut advertised = vec![];
+    let mut missing = vec![];
+
+    for e in events {
+        let tsbk = match e.kind {
+            EventKind::Tsbk(tsbk) => tsbk,
+            _ => continue,
+        };
+
+        if let TsbkMessage::ProtectionParamBroadcast(p) = TsbkMessage::new(&tsbk) {
+            advertised.push((p.alg(), p.key(), p.dest_unit()));
+        }
+
+        missing.extend(ks.feed(&MessageEvent::TrunkingControl(tsbk)));
+    }
+
+    assert_eq!(advertised, &[
+        (CryptoAlgorithm::Aes, 0x0001, 0x123456),
+        (CryptoAlgorithm::Aes, 0x0002, 0x123457),
+        (CryptoAlgorithm::Des, 0x0010, 0xFFFFFF),
+        (CryptoAlgorithm::Aes, 0x0002, 0x654321),
+    ]);
+
+    // The provisioned key isn't reported, and the repeated one only the first time.
+    assert_eq!(missing, &[
+        MissingKey { id: KeyId::new(CryptoAlgorithm::Aes, 0x0002), unit: Some(0x123457) },
+        MissingKey { id: KeyId::new(CryptoAlgorithm::Des, 0x0010), unit: Some(0xFFFFFF) },
+    ]);
+    assert_eq!(ks.missing(), &[
+        KeyId::new(CryptoAlgorithm::Aes, 0x0002),
+        KeyId::new(CryptoAlgorithm::Des, 0x0010),
+    ]);
+}
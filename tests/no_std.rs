@@ -0,0 +1,43 @@
+//! Decode a control channel grant with the crate built without `std`, as an embedded
+//! receiver pulling dibits from a hardware demodulator would. Only `core` is used here,
+//! and no heap allocations are made along the way.
+
+#![no_std]
+
+extern crate p25;
+
+use p25::trunking::decode::TsbkMessage;
+use p25::trunking::encode::code_block;
+use p25::trunking::fields::TalkGroup;
+use p25::trunking::tsbk::{TsbkFields, TsbkOpcode, TsbkReceiver};
+use p25::trunking::vendor::MFID_STANDARD;
+
+#[test]
+fn test_tsbk() {
+    let tsbk = TsbkFields::build(TsbkOpcode::GroupVoiceGrant.to_bits(), MFID_STANDARD, &[
+        0x00, 0x10, 0x20, 0x01, 0x00, 0x12, 0x34, 0x56,
+    ]).with_tail(true);
+
+    // Coded dibits of the block, as they follow the NID of a TSDU.
+    let dibits = code_block(&tsbk);
+
+    let mut recv = TsbkReceiver::new();
+    let mut decoded = None;
+
+    for &d in dibits.iter() {
+        if let Some(r) = recv.feed(d) {
+            decoded = Some(r.unwrap());
+        }
+    }
+
+    assert!(recv.done());
+
+    match TsbkMessage::new(&decoded.unwrap()) {
+        TsbkMessage::GroupVoiceGrant(g) => {
+            assert_eq!(g.talkgroup(), TalkGroup::Other(0x0100));
+            assert_eq!(g.src_unit(), 0x123456);
+            assert_eq!(g.channel().number(), 0x020);
+        },
+        _ => panic!(),
+    }
+}
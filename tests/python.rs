@@ -0,0 +1,112 @@
+//! Run the Python tests in `tests/python` against the `p25` module, with the
+//! interpreter embedded in the test.
+
+#![cfg(feature = "python")]
+
+extern crate p25;
+extern crate pyo3;
+
+use std::ffi::CString;
+use std::fs::{self, File};
+use std::io::Write;
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use pyo3::wrap_pymodule;
+
+use p25::baseband::sync::SYNC_GENERATOR;
+use p25::bits::{Dibit, Dibits};
+use p25::consts::{SAMPLE_RATE, SYMBOL_PERIOD};
+use p25::message::nid::{DataUnit, NetworkAccessCode, NetworkId};
+use p25::message::status::{StatusCode, StatusInterleaver};
+use p25::trunking::encode::Tsdu;
+use p25::trunking::tsbk::{TsbkFields, TsbkOpcode};
+use p25::trunking::vendor::MFID_STANDARD;
+use p25::voice::encode::VoiceEncoder;
+
+/// Baseband samples of a group voice grant followed by a voice frame group and
+/// terminator carrying the granted call.
+fn vector() -> Vec<f32> {
+    let nac = NetworkAccessCode::Default;
+
+    let tsbk = TsbkFields::build(TsbkOpcode::GroupVoiceGrant.to_bits(), MFID_STANDARD,
+                                 &[0x00, 0x10, 0x20, 0x01, 0x00, 0x12, 0x34, 0x56]);
+    let nid = NetworkId::new(nac, DataUnit::TrunkingSignaling).encode();
+
+    let mut dibits: Vec<Dibit> = StatusInterleaver::new(
+        Dibits::new(SYNC_GENERATOR.iter().cloned())
+            .chain(Dibits::new(nid.iter().cloned()))
+            .chain(Tsdu::assemble(&[tsbk]).into_iter()),
+        StatusCode::InboundIdle).collect();
+
+    let voice = VoiceEncoder::new(nac);
+    let lc = [0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x12, 0x34, 0x56];
+
+    dibits.extend(voice.lc_frame_group(&[[7; 8]; 9], &lc, 0xBEEF));
+    dibits.extend(voice.simple_terminator());
+
+    let mut samples = vec![0.0; 1000];
+
+    for d in dibits {
+        let level = match d.bits() {
+            0b01 => 0.18,
+            0b00 => 0.02,
+            0b10 => -0.02,
+            _ => -0.18,
+        };
+
+        samples.extend(std::iter::repeat(level).take(SYMBOL_PERIOD));
+    }
+
+    samples.extend(std::iter::repeat(0.0).take(SAMPLE_RATE * 2));
+    samples
+}
+
+#[test]
+fn test_python() {
+    let path = std::env::temp_dir()
+        .join(format!("p25-python-{}.f32", std::process::id()));
+
+    let mut file = File::create(&path).unwrap();
+
+    for s in vector() {
+        let x = s.to_bits();
+        file.write_all(&[x as u8, (x >> 8) as u8, (x >> 16) as u8, (x >> 24) as u8])
+            .unwrap();
+    }
+
+    drop(file);
+    std::env::set_var("P25_TEST_VECTOR", &path);
+
+    pyo3::prepare_freethreaded_python();
+
+    let failed: Vec<String> = Python::with_gil(|py| -> PyResult<Vec<String>> {
+        let module = wrap_pymodule!(p25::python::p25_module)(py);
+        py.import("sys")?.getattr("modules")?.set_item("p25", module)?;
+
+        let code = CString::new(include_str!("python/test_receiver.py")).unwrap();
+        let globals = PyDict::new(py);
+        globals.set_item("__name__", "test_receiver")?;
+        py.run(&code, Some(&globals), None)?;
+
+        let mut failed = vec![];
+
+        for (name, test) in globals.iter() {
+            let name: String = name.extract()?;
+
+            if !name.starts_with("test_") {
+                continue;
+            }
+
+            if let Err(e) = test.call0() {
+                e.display(py);
+                failed.push(name);
+            }
+        }
+
+        Ok(failed)
+    }).unwrap();
+
+    fs::remove_file(&path).unwrap();
+    assert!(failed.is_empty(), "failed: {:?}", failed);
+}
@@ -0,0 +1,110 @@
+"""Tests for the p25 Python module, run by tests/python.rs with the module built in.
+
+The test vector is read from the float32 file named by the P25_TEST_VECTOR environment
+variable. It holds a group voice grant on NAC 0x293 followed by a voice frame group and
+a terminator for the granted call, then two seconds of silence.
+"""
+
+import array
+import os
+
+import p25
+
+
+def load_vector():
+    samples = array.array("f")
+
+    with open(os.environ["P25_TEST_VECTOR"], "rb") as f:
+        samples.frombytes(f.read())
+
+    return samples
+
+
+def decode(samples):
+    recv = p25.Receiver(p25.ReceiverConfig(call_timeout=48000))
+    events = recv.feed(samples)
+    events += recv.finish()
+
+    return events
+
+
+def test_config():
+    config = p25.ReceiverConfig(max_nacs=2)
+    assert config.max_nacs == 2
+    assert config.track_calls
+
+    try:
+        p25.ReceiverConfig(max_nacs=0)
+    except ValueError:
+        pass
+    else:
+        assert False
+
+
+def test_events():
+    events = decode(load_vector())
+
+    kinds = [e.kind for e in events]
+    assert kinds == (["sync_acquired", "nid", "tsbk", "call_start", "nid"] +
+                     ["voice_frame"] * 7 +
+                     ["link_control", "voice_frame", "low_speed_data", "voice_frame"] +
+                     ["nid", "sync_lost", "call_end"])
+
+    samples = [e.sample for e in events]
+    assert samples == sorted(samples)
+
+    # Sync changes don't belong to a NAC.
+    assert all(e.nac is None for e in events if e.kind.startswith("sync_"))
+    assert all(e.nac == 0x293 for e in events if not e.kind.startswith("sync_"))
+
+    tsbk = events[2]
+    assert tsbk.opcode == 0
+    assert len(tsbk.bytes) == 12
+    assert tsbk.fields["bytes"] == tsbk.bytes
+
+    call = events[3]
+    assert call.channel == 0x1020
+    assert call.src_unit == 0x123456
+    assert not call.emergency
+    assert events[-1].started == call.started
+
+    frame = events[5]
+    assert frame.chunks == [7] * 8
+    assert frame.errors == [0] * 7
+
+    assert events[12].payload == bytes.fromhex("0000000100123456")
+    assert events[14].data == 0xBEEF
+
+    try:
+        frame.talk_group
+    except AttributeError:
+        pass
+    else:
+        assert False
+
+    assert repr(events[0]).startswith("Event(kind='sync_acquired', sample=")
+
+
+def test_strided():
+    samples = load_vector()
+
+    # Every other item of this buffer is a sample, so it can't be read in place.
+    padded = array.array("f", [x for s in samples for x in (s, 1.0)])
+
+    assert decode(memoryview(padded)[::2]) == decode(samples)
+
+
+def test_chunks():
+    samples = load_vector()
+    recv = p25.Receiver()
+    events = []
+
+    for i in range(0, len(samples), 1000):
+        events += recv.feed(samples[i:i + 1000])
+
+    assert recv.sample == len(samples)
+    assert recv.nacs == [0x293]
+    assert [e.kind for e in events if e.kind == "call_start"] == ["call_start"]
+
+    # The NAC carries over between calls.
+    assert all(e.nac == 0x293 for e in events if e.kind == "voice_frame")
@@ -0,0 +1,35 @@
+//! Replay the bundled captures through the full receiver and compare their transcripts
+//! against the golden copies.
+//!
+//! Run with `P25_BLESS=1` to regenerate the golden transcripts after an intended change
+//! in receiver output.
+
+#![cfg(feature = "std")]
+
+extern crate p25;
+
+use p25::receiver::ReceiverConfig;
+use p25::replay::{self, ReplayError};
+
+#[test]
+fn test_voice_call() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data");
+
+    let t = replay::replay_file(format!("{}/voice_call.wav", dir), ReceiverConfig::default())
+        .unwrap();
+
+    assert!(t.as_str().contains("\"kind\":\"voice_header\""));
+    assert!(t.as_str().contains("\"kind\":\"voice_term\""));
+
+    if let Some(m) = t.check_golden(format!("{}/voice_call.jsonl", dir)).unwrap() {
+        panic!("{}", m);
+    }
+}
+
+#[test]
+fn test_missing() {
+    match replay::replay_file("does/not/exist.wav", ReceiverConfig::default()) {
+        Err(ReplayError::Io(_)) => {},
+        _ => panic!(),
+    }
+}